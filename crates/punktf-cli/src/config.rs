@@ -0,0 +1,165 @@
+//! Persistent user configuration which provides defaults for command line
+//! options.
+//!
+//! The configuration is read from `$XDG_CONFIG_HOME/punktf/config.toml`
+//! (`~/.config/punktf/config.toml` if `XDG_CONFIG_HOME` is not set) or from
+//! the file given with the environment variable `PUNKTF_CONFIG`:
+//!
+//! ```toml
+//! source = "/home/demo/dotfiles"
+//! profile = "arch"
+//! verbosity = 1
+//! merge_policy = "keep"
+//! diff_format = "unified"
+//! ```
+//!
+//! All values are only defaults. Options given on the command line or by
+//! environment variables (e.g. `PUNKTF_SOURCE`) take precedence.
+
+use std::path::{Path, PathBuf};
+
+use clap::Command;
+use color_eyre::eyre::Context as _;
+use color_eyre::Result;
+use serde::Deserialize;
+
+use crate::PUNKTF_CONFIG_ENVVAR;
+
+/// Subcommands which have an option with the same name as a configuration
+/// value, but with a different meaning.
+const EXCLUDED_SUBCOMMANDS: &[(&str, &str)] = &[("import", "profile")];
+
+/// Defaults for command line options (related: [module](`self`)).
+///
+/// The values are kept as strings, so that they are validated by the command
+/// line parser like any other value.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+	/// Default for `--source`.
+	source: Option<PathBuf>,
+
+	/// Default for `--profile`.
+	profile: Option<String>,
+
+	/// Default for the number of `--verbose` flags.
+	verbosity: Option<u8>,
+
+	/// Default for `--merge-policy`.
+	merge_policy: Option<String>,
+
+	/// Default for `diff --format`.
+	diff_format: Option<String>,
+}
+
+impl UserConfig {
+	/// Returns the path of the configuration file.
+	///
+	/// Returns `None` if no path is set with [`PUNKTF_CONFIG_ENVVAR`] and the
+	/// configuration directory can not be determined.
+	pub fn path() -> Option<PathBuf> {
+		if let Some(path) = std::env::var_os(PUNKTF_CONFIG_ENVVAR) {
+			return Some(PathBuf::from(path));
+		}
+
+		let non_empty = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty());
+
+		let dir = non_empty("XDG_CONFIG_HOME")
+			.map(PathBuf::from)
+			.or_else(|| {
+				if cfg!(windows) {
+					non_empty("APPDATA").map(PathBuf::from)
+				} else {
+					None
+				}
+			})
+			.or_else(|| non_empty("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+		Some(dir.join("punktf").join("config.toml"))
+	}
+
+	/// Loads the configuration from [`UserConfig::path`].
+	///
+	/// A missing configuration file results in an empty configuration,
+	/// unless it was explicitly set with [`PUNKTF_CONFIG_ENVVAR`].
+	pub fn load() -> Result<Self> {
+		let Some(path) = Self::path() else {
+			return Ok(Self::default());
+		};
+
+		if !path.exists() && std::env::var_os(PUNKTF_CONFIG_ENVVAR).is_none() {
+			return Ok(Self::default());
+		}
+
+		Self::from_file(&path)
+	}
+
+	/// Reads the configuration from the file at `path`.
+	pub fn from_file(path: &Path) -> Result<Self> {
+		let content = std::fs::read_to_string(path)
+			.wrap_err_with(|| format!("Failed to read user config `{}`", path.display()))?;
+
+		toml::from_str(&content)
+			.wrap_err_with(|| format!("Failed to parse user config `{}`", path.display()))
+	}
+
+	/// Sets the configured values as defaults of the options of `command`
+	/// and all of its subcommands.
+	pub fn apply(&self, command: Command) -> Command {
+		let verbosity = self.verbosity.map(|verbosity| verbosity.to_string());
+		let source = self
+			.source
+			.as_ref()
+			.map(|source| source.to_string_lossy().into_owned());
+
+		let command = set_default(command, "verbose", verbosity.as_deref());
+
+		[
+			("source", source.as_deref()),
+			("profile", self.profile.as_deref()),
+			("merge_policy", self.merge_policy.as_deref()),
+		]
+		.into_iter()
+		.fold(command, |command, (id, value)| {
+			set_subcommand_defaults(command, id, value)
+		})
+		.mut_subcommand("diff", |diff| {
+			set_default(diff, "format", self.diff_format.as_deref())
+		})
+	}
+}
+
+/// Sets `value` as default of the option `id` of `command`, if both exist.
+fn set_default(command: Command, id: &str, value: Option<&str>) -> Command {
+	let Some(value) = value else {
+		return command;
+	};
+
+	if !command.get_arguments().any(|arg| arg.get_id() == id) {
+		return command;
+	}
+
+	// With a default the option is never missing, so all requirements are
+	// dropped (e.g. `--profile` is otherwise required unless `--auto` is
+	// given).
+	let value = value.to_string();
+	command.mut_arg(id, |arg| {
+		arg.default_value(value)
+			.required(false)
+			.required_unless_present(clap::builder::Resettable::Reset)
+	})
+}
+
+/// Sets `value` as default of the option `id` of all subcommands of
+/// `command` (related: [`EXCLUDED_SUBCOMMANDS`]).
+fn set_subcommand_defaults(command: Command, id: &str, value: Option<&str>) -> Command {
+	let names: Vec<_> = command
+		.get_subcommands()
+		.map(|subcommand| subcommand.get_name().to_string())
+		.filter(|name| !EXCLUDED_SUBCOMMANDS.contains(&(name.as_str(), id)))
+		.collect();
+
+	names.into_iter().fold(command, |command, name| {
+		command.mut_subcommand(name, |subcommand| set_default(subcommand, id, value))
+	})
+}
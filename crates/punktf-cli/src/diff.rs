@@ -3,17 +3,23 @@
 
 use crate::opt::DiffFormat;
 use console::{style, Style};
-use punktf_lib::visit::diff::Event;
+use punktf_lib::visit::diff::{encoding::Encoding, Event};
 use similar::{ChangeTag, TextDiff};
-use std::{fmt, path::Path};
+use std::{
+	fmt,
+	io::{self, Write},
+	path::Path,
+};
 
-/// Processes diff [`Event`s](`punktf_lib::visit::diff::Event`) from the visitor.
-pub fn diff(format: DiffFormat, event: Event<'_>) {
+/// Processes diff [`Event`s](`punktf_lib::visit::diff::Event`) from the visitor
+/// and writes them to `out`.
+pub fn diff(out: &mut dyn Write, format: DiffFormat, event: Event<'_>) -> io::Result<()> {
 	match event {
 		Event::NewFile {
 			relative_source_path,
 			target_path,
-		} => println!(
+		} => writeln!(
+			out,
 			"[{} => {}] New file",
 			style(relative_source_path.display())
 				.bold()
@@ -24,7 +30,8 @@ pub fn diff(format: DiffFormat, event: Event<'_>) {
 		Event::NewDirectory {
 			relative_source_path,
 			target_path,
-		} => println!(
+		} => writeln!(
+			out,
 			"[{} => {}] New directory",
 			style(relative_source_path.display())
 				.bold()
@@ -36,31 +43,62 @@ pub fn diff(format: DiffFormat, event: Event<'_>) {
 			relative_source_path,
 			target_path,
 			old_content,
+			old_encoding,
 			new_content,
 		} => {
 			if format == DiffFormat::Unified {
-				print_udiff(target_path, &old_content, &new_content);
+				print_udiff(out, target_path, &old_content, &new_content)
 			} else {
 				print_pretty(
+					out,
 					relative_source_path,
 					target_path,
+					old_encoding,
 					&old_content,
 					&new_content,
-				);
+				)
 			}
 		}
+		Event::NewLink {
+			source_path,
+			target_path,
+		} => writeln!(
+			out,
+			"[{} => {}] New link",
+			style(source_path.display()).bold().black().bright(),
+			style(target_path.display()).bold().bright()
+		),
+		Event::RetargetedLink {
+			source_path,
+			target_path,
+			current_source_path,
+		} => writeln!(
+			out,
+			"[{} => {}] Retargeted link (currently points to {})",
+			style(source_path.display()).bold().black().bright(),
+			style(target_path.display()).bold().bright(),
+			style(current_source_path.display()).bold().black().bright(),
+		),
+		Event::TypeConflict {
+			source_path,
+			target_path,
+		} => writeln!(
+			out,
+			"[{} => {}] {} - Target exists and is not the expected link",
+			style(source_path.display()).bold().black().bright(),
+			style(target_path.display()).bold().bright(),
+			style("Type conflict").red()
+		),
 	}
 }
 
-/// Prints a file diff with the gnu unified format.
-fn print_udiff(target: &Path, old: &str, new: &str) {
+/// Writes a file diff with the gnu unified format to `out`.
+fn print_udiff(out: &mut dyn Write, target: &Path, old: &str, new: &str) -> io::Result<()> {
 	let diff = TextDiff::from_lines(old, new);
 
-	println!("--- {path}\r\n+++ {path}", path = target.display());
+	writeln!(out, "--- {path}\r\n+++ {path}", path = target.display())?;
 
-	diff.unified_diff()
-		.to_writer(std::io::stdout())
-		.expect("Writing to stdout to never fail");
+	diff.unified_diff().to_writer(out)
 }
 
 /// Used to pretty print diff line numbers.
@@ -75,21 +113,35 @@ impl fmt::Display for Line {
 	}
 }
 
-/// Prints a file diff with ansii escape codes.
-fn print_pretty(source: &Path, target: &Path, old: &str, new: &str) {
+/// Writes a file diff with ansii escape codes to `out`.
+fn print_pretty(
+	out: &mut dyn Write,
+	source: &Path,
+	target: &Path,
+	encoding: Encoding,
+	old: &str,
+	new: &str,
+) -> io::Result<()> {
 	let diff = TextDiff::from_lines(old, new);
 
 	for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
 		if idx == 0 {
-			println!(
+			write!(
+				out,
 				">> {} => {}",
 				style(source.display()).bold().black().bright(),
 				style(target.display()).bold().bright()
-			);
+			)?;
+
+			if encoding.is_utf8() {
+				writeln!(out)?;
+			} else {
+				writeln!(out, " {}", style(format!("({encoding})")).dim())?;
+			}
 		}
 
 		if idx > 0 {
-			println!("{:-^1$}", "-", 80);
+			writeln!(out, "{:-^1$}", "-", 80)?;
 		}
 
 		for op in group {
@@ -99,23 +151,26 @@ fn print_pretty(source: &Path, target: &Path, old: &str, new: &str) {
 					ChangeTag::Insert => ("+", Style::new().green()),
 					ChangeTag::Equal => (" ", Style::new().dim()),
 				};
-				print!(
+				write!(
+					out,
 					"{}{} |{}",
 					style(Line(change.old_index())).dim(),
 					style(Line(change.new_index())).dim(),
 					s.apply_to(sign).bold(),
-				);
+				)?;
 				for (emphasized, value) in change.iter_strings_lossy() {
 					if emphasized {
-						print!("{}", s.apply_to(value).underlined().on_black());
+						write!(out, "{}", s.apply_to(value).underlined().on_black())?;
 					} else {
-						print!("{}", s.apply_to(value));
+						write!(out, "{}", s.apply_to(value))?;
 					}
 				}
 				if change.missing_newline() {
-					println!();
+					writeln!(out)?;
 				}
 			}
 		}
 	}
+
+	Ok(())
 }
@@ -0,0 +1,2100 @@
+//! punktf - A cross-platform multi-target dotfiles manager
+//!
+//! ## Yet another dotfile manager?!
+//!
+//! Well, yes, but hear me out: This project was driven by the personal need of having to manage several dotfiles for different machines/targets. You want the same experience everywhere: On your Windows workstation along with an Ubuntu WSL instance, your Debian server and your private Arch installation. This tool fixes that problem while being cross-platform and blazingly fast. You won't need multiple sets of dotfile configurations ever again!
+//!
+//! Features:
+//!
+//! - Compile and deploy your dotfiles with one command across different platforms
+//! - Use handlebar-like instructions to insert variables and compile sections conditionally
+//! - Define pre- and post-hooks to customize the behavior with your own commands
+//! - Create multiple profiles for different targets
+//! - Works on Windows and Linux
+//!
+//! ## Usage
+//!
+//! ### Commands
+//!
+//! To deploy a profile, use the `deploy` subcommand:
+//!
+//! ```sh
+//! # deploy 'windows' profile
+//! punktf deploy windows
+//!
+//! # deploy (custom source folder)
+//! punktf --source /home/demo/mydotfiles deploy windows
+//! ```
+//!
+//! Adding the `-h`/`--help` flag to a given subcommand, will print usage instructions.
+//!
+//! ### Source Folder
+//!
+//! The punktf source folder, is the folder containing the dotfiles and punktf profiles. We recommend setting the `PUNKTF_SOURCE` environment variable, so that the dotfiles can be compiled using `punktf deploy <profile>`.
+//!
+//! punktf searches for the source folder in the following order:
+//!
+//! 1. CLI argument given with `-s`/`--source`
+//! 2. Environment variable `PUNKTF_SOURCE`
+//! 3. Value `source` of the user config (`$XDG_CONFIG_HOME/punktf/config.toml` or `PUNKTF_CONFIG`)
+//! 4. Current working directory of the shell
+//!
+//! The source folder should contain two sub-folders:
+//!
+//! * `profiles\`: Contains the punktf profile definitions (`.yaml` or `.json`)
+//! * `dotfiles\`: Contains folders and the actual dotfiles
+//!
+//! Example punktf source folder structure:
+//!
+//! ```ls
+//! + profiles
+//!     + windows.yaml
+//!     + base.yaml
+//!     + arch.json
+//! + dotfiles
+//!     + .gitconfig
+//!     + init.vim.win
+//!     + base
+//!         + demo.txt
+//!     + linux
+//!         + .bashrc
+//!     + windows
+//!         + alacritty.yml
+//! ```
+//!
+//! ### Target
+//!
+//! Determines where `punktf` will deploy files too.
+//! It can be set with:
+//!
+//! 1. Variable `target` in the punktf profile file
+//! 2. Environment variable `PUNKTF_TARGET`
+//!
+//! ### Profiles
+//!
+//! Profiles define which dotfiles should be used. They can be a `.json` or `.yaml` file.
+//!
+//! Example punktf profile:
+//!
+//! ```yaml
+//! variables:
+//!   OS: "windows"
+//!
+//! target: "C:\\Users\\Demo"
+//!
+//! dotfiles:
+//!   - path: "base"
+//!   - path: "windows/alacritty.yml"
+//!     target:
+//!         Path: "C:\\Users\\Demo\\AppData\\Local\\alacritty.yml"
+//!     merge: Ask
+//! ```
+//!
+//! All properties are explained [in the wiki](https://github.com/Shemnei/punktf/wiki/Profiles).
+//!
+//! ## Templates
+//!
+//! Please refer to the [wiki](https://github.com/Shemnei/punktf/wiki/Templating) for the templating syntax.
+//!
+//! ## Dotfile Repositories using punktf
+//!
+//! - [michidk/dotfiles](https://gitlab.com/michidk/dotfiles)
+
+#![allow(rustdoc::private_intra_doc_links)]
+#![deny(
+	dead_code,
+	deprecated_in_future,
+	exported_private_dependencies,
+	future_incompatible,
+	missing_copy_implementations,
+	rustdoc::missing_crate_level_docs,
+	rustdoc::broken_intra_doc_links,
+	missing_docs,
+	clippy::missing_docs_in_private_items,
+	missing_debug_implementations,
+	rust_2018_compatibility,
+	rust_2018_idioms,
+	trivial_casts,
+	trivial_numeric_casts,
+	unsafe_code,
+	unstable_features,
+	unused_import_braces,
+	unused_qualifications,
+
+	// clippy attributes
+	clippy::missing_const_for_fn,
+	clippy::redundant_pub_crate,
+	clippy::use_self
+)]
+#![cfg_attr(docsrs, feature(doc_cfg), feature(doc_alias))]
+
+mod config;
+mod diff;
+mod opt;
+mod util;
+use std::cell::RefCell;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::{CommandFactory, FromArgMatches, Parser};
+use color_eyre::eyre::{eyre, Context as _};
+use color_eyre::Result;
+use console::style;
+use opt::Command;
+use punktf_lib::error::BoxError;
+use punktf_lib::profile::dotfile::Dotfile;
+use punktf_lib::profile::hook::Hook;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::{
+	collect_profile_names, import, init, migrate, profile_path, resolve_inline_profile,
+	resolve_profile, select, validate, LayeredProfile, Profile,
+};
+use punktf_lib::template::check_all;
+use punktf_lib::template::engine::{EngineKind, RenderContext};
+use punktf_lib::template::mode::TemplateMode;
+use punktf_lib::visit::conflicts::ConflictFinder;
+use punktf_lib::visit::deploy::ask::{MergeDecision, MergeRequest, MergeStrategy};
+use punktf_lib::visit::deploy::audit::{AuditManifest, AUDIT_MANIFEST_VERSION};
+use punktf_lib::visit::deploy::deployment::{
+	CommandKind, Deployment, DeploymentBuilder, ItemStatus,
+};
+use punktf_lib::visit::deploy::report::{FileSink, ReportFormat, ReportSpec};
+use punktf_lib::visit::deploy::{drift::DriftWatcher, *};
+use punktf_lib::visit::diff::Diff;
+use punktf_lib::visit::doctor::Doctor;
+use punktf_lib::visit::explain::Explainer;
+use punktf_lib::visit::TagFilter;
+
+/// Name of the `punktf` binary.
+const BINARY_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// Name of the environment variable which defines the default source path for
+/// `punktf`.
+pub const PUNKTF_SOURCE_ENVVAR: &str = "PUNKTF_SOURCE";
+
+/// Name of the environment variable which defines the default target path for
+/// `punktf`.
+pub const PUNKTF_TARGET_ENVVAR: &str = "PUNKTF_TARGET";
+
+/// Name of the environment variable which defines the default profile for
+/// `punktf`.
+pub const PUNKTF_PROFILE_ENVVAR: &str = "PUNKTF_PROFILE";
+
+/// Name of the environment variable which defines the cache directory used by
+/// `punktf` (e.g. for dotfiles deployed with the `CacheLink` strategy).
+pub const PUNKTF_CACHE_ENVVAR: &str = "PUNKTF_CACHE";
+
+/// Name of the environment variable which defines the state directory used by
+/// `punktf` (e.g. for the manifests of deployed files).
+pub const PUNKTF_STATE_ENVVAR: &str = "PUNKTF_STATE";
+
+/// Name of the environment variable which defines the path of the user
+/// configuration file (related: [`config::UserConfig`]).
+pub const PUNKTF_CONFIG_ENVVAR: &str = "PUNKTF_CONFIG";
+
+/// Input and output streams used by [`run`].
+///
+/// All user facing output (e.g. the deployment summary or the rendered
+/// template) is written to `stdout`, errors are written to `stderr` and
+/// interactive prompts read from `stdin`. Logging is not affected by this and
+/// still goes through the global logger.
+pub struct IoStreams<'a> {
+	/// Stream from which user input is read.
+	pub stdin: Box<dyn BufRead + 'a>,
+
+	/// Stream to which regular output is written.
+	pub stdout: Box<dyn Write + 'a>,
+
+	/// Stream to which errors are written.
+	pub stderr: Box<dyn Write + 'a>,
+}
+
+impl IoStreams<'static> {
+	/// Creates new streams which are connected to the standard streams of the
+	/// process.
+	pub fn std() -> Self {
+		Self {
+			stdin: Box::new(std::io::stdin().lock()),
+			stdout: Box::new(std::io::stdout()),
+			stderr: Box::new(std::io::stderr()),
+		}
+	}
+}
+
+/// Interactive [`MergeStrategy`] which asks the user via the streams what to
+/// do with an existing target (related: [`util::ask_user_merge`]).
+struct MergePrompt<'a, 'b, 'io>(&'a RefCell<&'b mut IoStreams<'io>>);
+
+impl MergeStrategy for MergePrompt<'_, '_, '_> {
+	fn decide(&mut self, request: &MergeRequest<'_>) -> Result<MergeDecision, BoxError> {
+		let mut io = self.0.borrow_mut();
+		let IoStreams { stdin, stdout, .. } = &mut **io;
+
+		util::ask_user_merge(stdin, stdout, request).map_err(Into::into)
+	}
+}
+
+impl fmt::Debug for IoStreams<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("IoStreams").finish_non_exhaustive()
+	}
+}
+
+/// Runs `punktf` with the command line arguments `args`.
+///
+/// The first item of `args` is expected to be the name of the binary. All
+/// output is written to the given `io` streams and no call will exit the
+/// process, which makes this function usable for integration tests.
+///
+/// Returns the exit code with which the process should exit.
+pub fn run<I, T>(args: I, mut io: IoStreams<'_>) -> ExitCode
+where
+	I: IntoIterator<Item = T>,
+	T: Into<OsString> + Clone,
+{
+	// This can only be installed once per process. As `run` might be called
+	// multiple times (e.g. from tests), errors are ignored.
+	let _ = color_eyre::install();
+
+	let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+
+	// The hidden `__complete` command is not part of `Opts`, so it does not
+	// show up in the help, man pages or generated completions.
+	if args.get(1).is_some_and(|arg| arg == opt::COMPLETE_COMMAND) {
+		let args = args.iter().take(1).chain(args.iter().skip(2));
+
+		return match opt::Complete::try_parse_from(args)
+			.map_err(Into::into)
+			.and_then(|complete| handle_command_complete(complete, &mut io))
+		{
+			Ok(()) => ExitCode::SUCCESS,
+			Err(_) => ExitCode::FAILURE,
+		};
+	}
+
+	let config = match config::UserConfig::load() {
+		Ok(config) => config,
+		Err(err) => {
+			let _ = writeln!(io.stderr, "Error: {err:?}");
+			let _ = io.stderr.flush();
+
+			return ExitCode::FAILURE;
+		}
+	};
+
+	// Values of the user config are only defaults, so options given on the
+	// command line or by environment variables take precedence.
+	let opts = match config
+		.apply(opt::Opts::command())
+		.try_get_matches_from(args)
+		.and_then(|matches| opt::Opts::from_arg_matches(&matches))
+	{
+		Ok(opts) => opts,
+		Err(err) => {
+			let out = if err.use_stderr() {
+				&mut io.stderr
+			} else {
+				&mut io.stdout
+			};
+
+			let _ = write!(out, "{}", err.render().ansi());
+			let _ = out.flush();
+
+			return ExitCode::from(u8::try_from(err.exit_code()).unwrap_or(1));
+		}
+	};
+
+	let log_level = if opts.shared.quite {
+		log::Level::Error
+	} else {
+		match opts.shared.verbose {
+			// Default if no value for `verbose` is given
+			0 => log::Level::Warn,
+			1 => log::Level::Info,
+			2 => log::Level::Debug,
+			_ => log::Level::Trace,
+		}
+	};
+
+	let mut logger = env_logger::Builder::from_env(
+		env_logger::Env::default().default_filter_or(log_level.as_str()),
+	);
+
+	if let Some(spec) = &opts.shared.log_filter {
+		let _ = logger.parse_filters(spec);
+	}
+
+	let _ = logger.try_init();
+
+	log::debug!("Loaded user config: {:#?}", config);
+	log::debug!("Parsed Opts:\n{:#?}", opts);
+
+	let result = handle_command(opts.command, &mut io);
+
+	let _ = io.stdout.flush();
+
+	match result {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(err) => {
+			let _ = writeln!(io.stderr, "Error: {err:?}");
+			let _ = io.stderr.flush();
+
+			ExitCode::FAILURE
+		}
+	}
+}
+
+/// Gets the parsed command line arguments and evaluates them.
+fn handle_command(command: Command, io: &mut IoStreams<'_>) -> Result<()> {
+	match command {
+		Command::Init(c) => handle_command_init(c, io),
+		Command::Import(c) => handle_command_import(c, io),
+		Command::Deploy(c) => handle_command_deploy(c, io),
+		Command::Render(c) => handle_command_render(c, io),
+		Command::Verify(c) => handle_command_verify(c, io),
+		Command::Diff(c) => handle_command_diff(c, io),
+		Command::Clean(c) => handle_command_clean(c, io),
+		Command::Explain(c) => handle_command_explain(c, io),
+		Command::Conflicts(c) => handle_command_conflicts(c, io),
+		Command::Doctor(c) => handle_command_doctor(c, io),
+		Command::Manifest(c) => handle_command_manifest(c, io),
+		Command::Hooks(c) => handle_command_hooks(c, io),
+		Command::Watch(c) => handle_command_watch(c, io),
+		Command::MigrateProfiles(c) => handle_command_migrate_profiles(c, io),
+		Command::ValidateProfile(c) => handle_command_validate_profile(c, io),
+		Command::Edit(c) => handle_command_edit(c),
+		Command::Man(c) => handle_command_man(c),
+		Command::Completions(c) => handle_command_completions(c),
+	}
+}
+
+/// Reads and creates a profile from a path.
+///
+/// If `inline` is given, it is used as profile named `profile_name` instead
+/// of looking it up in the `profiles` directory.
+fn setup_profile(
+	profile_name: &str,
+	inline: Option<Profile>,
+	source: &PunktfSource,
+	target: Option<PathBuf>,
+) -> Result<LayeredProfile> {
+	let mut builder = LayeredProfile::build();
+
+	// Add target cli argument to top
+	let target_cli_profile = Profile {
+		target,
+		..Default::default()
+	};
+	builder.add(String::from("target_cli_argument"), target_cli_profile);
+
+	match inline {
+		Some(inline) => resolve_inline_profile(&mut builder, source, profile_name, inline)?,
+		None => resolve_profile(&mut builder, source, profile_name)?,
+	}
+
+	// Add target environment variable to bottom
+	let target_env_profile = Profile {
+		target: util::get_target_path(),
+		..Default::default()
+	};
+	builder.add(
+		String::from("target_environment_variable"),
+		target_env_profile,
+	);
+
+	let mut profile = builder.finish();
+	profile.add_variable_overlays(source)?;
+	profile.add_builtin_variables(source, profile_name);
+
+	Ok(profile)
+}
+
+/// Handles the writing of the deployment status to output files/formats.
+fn handle_output(
+	opt::OutputShared {
+		json_output,
+		yaml_output,
+		mut reports,
+		compare,
+	}: opt::OutputShared,
+	deployment: &Deployment,
+	out: &mut dyn Write,
+) {
+	let files = [
+		(json_output, ReportFormat::Json),
+		(yaml_output, ReportFormat::Yaml),
+	];
+	reports.extend(
+		files.into_iter().filter_map(|(path, format)| {
+			path.map(|path| ReportSpec::File(FileSink { path, format }))
+		}),
+	);
+
+	for spec in &reports {
+		let mut sink = spec.sink(&mut *out);
+
+		if let Err(err) = sink.report(deployment) {
+			log::error!(
+				"Failed to write deployment status to {}: {err}",
+				sink.describe()
+			);
+		}
+	}
+
+	if let Some(compare_path) = compare {
+		match util::read_deployment(&compare_path) {
+			Ok(previous) => util::log_deployment_delta(&deployment.compare(&previous), Some(out)),
+			Err(err) => log::error!("Failed to read previous deployment status: {err:?}"),
+		}
+	}
+}
+
+/// Updates the manifest of deployed files for the profile and optionally
+/// removes files which are no longer part of the profile (`prune`).
+///
+/// If `dry_run` is set, the manifest is not updated and no files are removed.
+fn handle_manifest(
+	profile_name: &str,
+	deployment: &Deployment,
+	prune: bool,
+	dry_run: bool,
+	out: &mut dyn Write,
+) -> Result<()> {
+	let Some(manifest_path) = util::get_manifest_path(profile_name) else {
+		log::warn!("No state directory found. Deployed files are not tracked");
+		return Ok(());
+	};
+
+	let mut manifest = util::read_manifest(&manifest_path)?;
+
+	if prune {
+		if deployment.status().is_failed() {
+			log::warn!("Skipping removal of orphaned files, as the deployment failed");
+		} else {
+			util::log_pruned(&manifest.prune(deployment, dry_run), dry_run, Some(out));
+		}
+	}
+
+	if !dry_run {
+		manifest.update(deployment);
+		util::write_manifest(&manifest_path, &manifest)?;
+	}
+
+	Ok(())
+}
+
+/// Returns the name of the profile to use together with its definition if it
+/// was given on the command line (related: [`opt::RepoShared`]).
+///
+/// If `auto` is set, the profile is selected by the machines file of `source`
+/// (related: [`punktf_lib::profile::select`]). If `stdin` is set or `inline`
+/// is given, the profile is parsed from it and named `profile` (or `stdin`
+/// and `inline` if not given). Otherwise `profile` is used.
+fn select_profile_name(
+	profile: Option<String>,
+	auto: bool,
+	stdin: bool,
+	inline: Option<String>,
+	source: &PunktfSource,
+	io: &mut IoStreams<'_>,
+) -> Result<(String, Option<Profile>)> {
+	if auto {
+		let name = select::select(source)?;
+		log::info!("Selected profile `{name}` for this machine");
+		Ok((name, None))
+	} else if stdin {
+		let mut content = String::new();
+		io.stdin
+			.read_to_string(&mut content)
+			.wrap_err("Failed to read the profile from stdin")?;
+
+		Ok((
+			profile.unwrap_or_else(|| String::from("stdin")),
+			Some(Profile::parse("stdin", &content)?),
+		))
+	} else if let Some(content) = inline {
+		Ok((
+			profile.unwrap_or_else(|| String::from("inline")),
+			Some(Profile::parse("inline", &content)?),
+		))
+	} else {
+		Ok((profile.ok_or_else(|| eyre!("No profile given"))?, None))
+	}
+}
+
+/// Handles the `deploy` command processing.
+fn handle_command_deploy(
+	opt::Deploy {
+		shared:
+			opt::RepoShared {
+				source,
+				profile,
+				auto,
+				profile_stdin,
+				profile_inline,
+			},
+		filter: opt::FilterShared { only, skip },
+		target,
+		dry_run,
+		skip_space_check,
+		prune,
+		only_path,
+		destdir,
+		strict,
+		no_lock,
+		no_exec,
+		progress,
+		stats,
+		template_cache,
+		merge_policy,
+		force,
+		only_managed,
+		emit_manifest,
+		output,
+		report: opt::ReportShared {
+			fail_on_skip,
+			summary_only,
+		},
+	}: opt::Deploy,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	let ptf_src = PunktfSource::from_root(source)?;
+	let (profile_name, inline_profile) =
+		select_profile_name(profile, auto, profile_stdin, profile_inline, &ptf_src, io)?;
+	let mut profile = setup_profile(&profile_name, inline_profile, &ptf_src, target)?;
+
+	// Ensure target is set
+	if profile.target_path().is_none() {
+		return Err(eyre!(
+			"No target path for the deployment set. Either use the command line argument \
+			 `-t/--target`, the profile attribute `target` or the environment variable \
+			 `{PUNKTF_TARGET_ENVVAR}`"
+		));
+	}
+
+	log::debug!("Profile:\n{:#?}", profile);
+	log::debug!("Source: {}", ptf_src.root().display());
+	log::debug!("Target: {:?}", profile.target_path());
+
+	let only_managed = if only_managed {
+		let manifest_path =
+			util::get_manifest_path(&profile_name).ok_or_else(|| {
+				eyre!("Failed to find the manifest of deployed files; `--only-managed` is not available")
+			})?;
+
+		Some(util::read_manifest(&manifest_path)?)
+	} else {
+		None
+	};
+
+	let options = DeployOptions {
+		dry_run,
+		check_free_space: !skip_space_check,
+		cache_dir: util::get_cache_path(),
+		strict,
+		no_lock,
+		destdir: destdir.clone(),
+		filter: TagFilter { only, skip },
+		no_exec,
+		merge_policy: merge_policy.map(|policy| match policy {
+			opt::MergePolicy::Overwrite => MergePolicy::Overwrite,
+			opt::MergePolicy::Keep => MergePolicy::Keep,
+			opt::MergePolicy::Fail => MergePolicy::Fail,
+		}),
+		template_cache,
+		force,
+		only_managed,
+	};
+	let deployment = {
+		let io = RefCell::new(&mut *io);
+		let deployer = Deployer::new(options, MergePrompt(&io));
+
+		if let Some(only_path) = only_path {
+			deployer.deploy_single(&ptf_src, &profile, &only_path)?
+		} else if progress {
+			let interactive = console::Term::stderr().is_term();
+			let mut bar = util::ProgressBar::new(interactive);
+			let deployment = deployer.deploy_with_progress(&ptf_src, &mut profile, |progress| {
+				bar.update(&mut io.borrow_mut().stderr, progress)
+			});
+			bar.finish(&mut io.borrow_mut().stderr);
+			deployment?
+		} else {
+			deployer.deploy(&ptf_src, &mut profile)?
+		}
+	};
+
+	log::debug!("Deployment:\n{:#?}", deployment);
+	util::log_deployment(&deployment, summary_only, Some(&mut io.stdout));
+
+	if let Some(slowest) = stats {
+		util::log_deployment_stats(&deployment.stats(), slowest, Some(&mut io.stdout));
+	}
+
+	handle_output(output, &deployment, &mut io.stdout);
+
+	if let Some(manifest_path) = emit_manifest {
+		let manifest = AuditManifest::from_deployment(&profile_name, &profile, &deployment)
+			.wrap_err("Failed to create manifest")?;
+		let file = File::create(&manifest_path)
+			.wrap_err_with(|| format!("Failed to create {}", manifest_path.display()))?;
+		serde_json::to_writer_pretty(file, &manifest).wrap_err("Failed to write manifest")?;
+	}
+
+	if let Some(destdir) = destdir {
+		log::info!(
+			"Deployment was staged into `{}`; it is not recorded in the manifest",
+			destdir.display()
+		);
+	} else {
+		handle_manifest(&profile_name, &deployment, prune, dry_run, &mut io.stdout)?;
+	}
+
+	if dry_run {
+		log::info!("Note: No files were actually deployed, since dry run mode was enabled");
+	}
+
+	if deployment.status().is_failed() {
+		Err(eyre!("Some dotfiles failed to deploy"))
+	} else {
+		check_skipped(&deployment, fail_on_skip)
+	}
+}
+
+/// Returns an error if `fail_on_skip` is set and any item of the `deployment`
+/// was skipped.
+fn check_skipped(deployment: &Deployment, fail_on_skip: bool) -> Result<()> {
+	if !fail_on_skip {
+		return Ok(());
+	}
+
+	let skipped = deployment
+		.dotfiles()
+		.values()
+		.map(AsRef::<ItemStatus>::as_ref)
+		.chain(
+			deployment
+				.symlinks()
+				.values()
+				.map(AsRef::<ItemStatus>::as_ref),
+		)
+		.filter(|status| status.is_skipped())
+		.count();
+
+	if skipped > 0 {
+		Err(eyre!("{skipped} items were skipped"))
+	} else {
+		Ok(())
+	}
+}
+
+/// Handles the `render` command processing.
+fn handle_command_render(
+	opt::Render {
+		shared:
+			opt::RepoShared {
+				source,
+				profile,
+				auto,
+				profile_stdin,
+				profile_inline,
+			},
+		dotfile,
+		out,
+		check,
+	}: opt::Render,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	/// Finds the relevant dotfile from which includes the file a the given
+	/// `relative_source_path`.
+	/// This is needed as some files might stem from a directory dotfile.
+	fn find_dotfile<'a, 'b>(
+		dotfiles: impl Iterator<Item = &'a Dotfile>,
+		relative_source_path: &'b Path,
+	) -> Option<&'a Dotfile> {
+		dotfiles
+			.filter_map(|d| {
+				relative_source_path
+					.strip_prefix(&d.path)
+					.map(|p| (d, p))
+					.ok()
+			})
+			.reduce(|a, i| {
+				// First sort by tiniest difference to dotfile path
+				// then by highest priority.
+				match (i.1.as_os_str().len(), a.1.as_os_str().len()) {
+					(i_len, a_len) if i_len == a_len => match (i.0.priority, a.0.priority) {
+						(Some(ip), Some(ap)) if ip >= ap => i,
+						(Some(_), None) | (None, None) => i,
+						_ => a,
+					},
+					(i_len, a_len) if i_len < a_len => i,
+					_ => a,
+				}
+			})
+			.map(|(d, _)| d)
+	}
+
+	let ptf_src = PunktfSource::from_root(source)?;
+	let (profile_name, inline_profile) =
+		select_profile_name(profile, auto, profile_stdin, profile_inline, &ptf_src, io)?;
+	let mut profile = setup_profile(&profile_name, inline_profile, &ptf_src, None)?;
+
+	log::debug!("Profile:\n{:#?}", profile);
+	log::debug!("Source: {}", ptf_src.root().display());
+	log::debug!("Target: {:?}", profile.target_path());
+
+	if let Some(out) = out {
+		return render_profile(&ptf_src, &mut profile, out, io);
+	}
+
+	if check {
+		let report = check_all(&ptf_src, &mut profile);
+
+		log::debug!("Template check:\n{:#?}", report);
+		util::log_template_check(&report, Some(&mut io.stdout));
+
+		return if report.is_ok() {
+			Ok(())
+		} else {
+			Err(eyre!("Found {} invalid templates", report.problems.len()))
+		};
+	}
+
+	let dotfile = dotfile.ok_or_else(|| eyre!("No dotfile to render given"))?;
+
+	let (dotfile_vars, mode, engine) =
+		if let Some(dotfile) = find_dotfile(profile.dotfiles(), &dotfile) {
+			log::debug!("Dotfile found in profile");
+			(
+				dotfile.variables.as_ref(),
+				dotfile.template_mode(),
+				dotfile.engine(),
+			)
+		} else {
+			log::warn!("Dotfile not found in profile");
+			(None, TemplateMode::default(), EngineKind::default())
+		};
+
+	let file = ptf_src.dotfiles().join(dotfile);
+	let content = std::fs::read_to_string(&file)?;
+	let resolved = engine.engine()?.render(
+		&content,
+		&RenderContext {
+			path: &file,
+			limits: profile.template_limits(),
+			mode,
+			profile_vars: profile.variables(),
+			dotfile_vars,
+			order: profile.variable_resolution_order(),
+			env: profile.env(),
+		},
+	)?;
+
+	write!(io.stdout, "{resolved}")?;
+
+	Ok(())
+}
+
+/// Renders all dotfiles of `profile` into the staging directory `out`.
+///
+/// This reuses the deploy pipeline with all target paths prefixed by `out`
+/// (related: [`DeployOptions::destdir`]).
+fn render_profile(
+	ptf_src: &PunktfSource,
+	profile: &mut LayeredProfile,
+	out: PathBuf,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	if profile.target_path().is_none() {
+		return Err(eyre!(
+			"No target path for the rendering set. Either use the profile attribute `target` \
+			 or the environment variable `{PUNKTF_TARGET_ENVVAR}`"
+		));
+	}
+
+	// Rendering must not have any side effects outside of `out`, so no
+	// commands of the profile are executed
+	let options = DeployOptions {
+		dry_run: false,
+		check_free_space: false,
+		cache_dir: util::get_cache_path(),
+		strict: false,
+		no_lock: true,
+		destdir: Some(out),
+		filter: TagFilter::default(),
+		no_exec: true,
+		merge_policy: None,
+		template_cache: false,
+		force: false,
+		only_managed: None,
+	};
+	let deployment =
+		Deployer::new(options, |_: &Path, _: &Path| Ok(true)).deploy(ptf_src, profile)?;
+
+	log::debug!("Deployment:\n{:#?}", deployment);
+	util::log_deployment(&deployment, false, Some(&mut io.stdout));
+
+	if deployment.status().is_failed() {
+		Err(eyre!("Some dotfiles failed to render"))
+	} else {
+		Ok(())
+	}
+}
+
+/// Handles the `verify` command processing.
+///
+/// This is basically a alias for `deploy --dry-run`.
+fn handle_command_verify(
+	opt::Verify {
+		shared:
+			opt::RepoShared {
+				source,
+				profile,
+				auto,
+				profile_stdin,
+				profile_inline,
+			},
+		filter: opt::FilterShared { only, skip },
+		output,
+		report: opt::ReportShared {
+			fail_on_skip,
+			summary_only,
+		},
+	}: opt::Verify,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	let ptf_src = PunktfSource::from_root(source)?;
+	let (profile_name, inline_profile) =
+		select_profile_name(profile, auto, profile_stdin, profile_inline, &ptf_src, io)?;
+	let mut profile = setup_profile(&profile_name, inline_profile, &ptf_src, None)?;
+
+	log::debug!("Profile:\n{:#?}", profile);
+	log::debug!("Source: {}", ptf_src.root().display());
+	log::debug!("Target: {:?}", profile.target_path());
+
+	let options = DeployOptions {
+		dry_run: true,
+		check_free_space: false,
+		cache_dir: util::get_cache_path(),
+		strict: false,
+		no_lock: true,
+		destdir: None,
+		filter: TagFilter { only, skip },
+		no_exec: false,
+		merge_policy: None,
+		template_cache: false,
+		force: false,
+		only_managed: None,
+	};
+	let deployment = {
+		let io = RefCell::new(&mut *io);
+		Deployer::new(options, MergePrompt(&io)).deploy(&ptf_src, &mut profile)?
+	};
+
+	log::debug!("Deployment:\n{:#?}", deployment);
+	util::log_deployment(&deployment, summary_only, Some(&mut io.stdout));
+
+	handle_output(output, &deployment, &mut io.stdout);
+
+	check_skipped(&deployment, fail_on_skip)
+}
+
+/// Handles the `clean` command processing.
+fn handle_command_clean(
+	opt::Clean {
+		shared:
+			opt::RepoShared {
+				source,
+				profile,
+				auto,
+				profile_stdin,
+				profile_inline,
+			},
+		target,
+		dry_run,
+	}: opt::Clean,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	let ptf_src = PunktfSource::from_root(source)?;
+	let (profile_name, inline_profile) =
+		select_profile_name(profile, auto, profile_stdin, profile_inline, &ptf_src, io)?;
+	let mut profile = setup_profile(&profile_name, inline_profile, &ptf_src, target)?;
+
+	log::debug!("Profile:\n{:#?}", profile);
+	log::debug!("Source: {}", ptf_src.root().display());
+	log::debug!("Target: {:?}", profile.target_path());
+
+	// Only used to determine all items which are part of the profile, so no
+	// commands are executed and the user is never asked about merges
+	let options = DeployOptions {
+		dry_run: true,
+		check_free_space: false,
+		cache_dir: util::get_cache_path(),
+		strict: false,
+		no_lock: true,
+		destdir: None,
+		filter: TagFilter::default(),
+		no_exec: true,
+		merge_policy: Some(MergePolicy::Keep),
+		template_cache: false,
+		force: false,
+		only_managed: None,
+	};
+	let deployment =
+		Deployer::new(options, |_: &Path, _: &Path| Ok(false)).deploy(&ptf_src, &mut profile)?;
+
+	log::debug!("Deployment:\n{:#?}", deployment);
+
+	if deployment.status().is_failed() {
+		return Err(eyre!(
+			"Failed to resolve all items of the profile. No files were removed"
+		));
+	}
+
+	let Some(manifest_path) = util::get_manifest_path(&profile_name) else {
+		return Err(eyre!("No state directory found"));
+	};
+
+	let mut manifest = util::read_manifest(&manifest_path)?;
+	util::log_pruned(
+		&manifest.prune(&deployment, dry_run),
+		dry_run,
+		Some(&mut io.stdout),
+	);
+
+	if dry_run {
+		log::info!("Note: No files were actually removed, since dry run mode was enabled");
+	} else {
+		util::write_manifest(&manifest_path, &manifest)?;
+	}
+
+	Ok(())
+}
+
+/// Handles the `explain` command processing.
+fn handle_command_explain(
+	opt::Explain {
+		shared:
+			opt::RepoShared {
+				source,
+				profile,
+				auto,
+				profile_stdin,
+				profile_inline,
+			},
+		target,
+		path,
+	}: opt::Explain,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	let ptf_src = PunktfSource::from_root(source)?;
+	let (profile_name, inline_profile) =
+		select_profile_name(profile, auto, profile_stdin, profile_inline, &ptf_src, io)?;
+	let mut profile = setup_profile(&profile_name, inline_profile, &ptf_src, target)?;
+
+	log::debug!("Profile:\n{:#?}", profile);
+	log::debug!("Source: {}", ptf_src.root().display());
+	log::debug!("Target: {:?}", profile.target_path());
+
+	let explanation = Explainer::new(path).explain(&ptf_src, &mut profile);
+
+	log::debug!("Explanation:\n{:#?}", explanation);
+	util::log_explanation(&explanation, Some(&mut io.stdout));
+
+	Ok(())
+}
+
+/// Handles the `conflicts` command processing.
+fn handle_command_conflicts(
+	opt::Conflicts {
+		shared:
+			opt::RepoShared {
+				source,
+				profile,
+				auto,
+				profile_stdin,
+				profile_inline,
+			},
+		target,
+	}: opt::Conflicts,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	let ptf_src = PunktfSource::from_root(source)?;
+	let (profile_name, inline_profile) =
+		select_profile_name(profile, auto, profile_stdin, profile_inline, &ptf_src, io)?;
+	let mut profile = setup_profile(&profile_name, inline_profile, &ptf_src, target)?;
+
+	log::debug!("Profile:\n{:#?}", profile);
+	log::debug!("Source: {}", ptf_src.root().display());
+	log::debug!("Target: {:?}", profile.target_path());
+
+	let conflicts = ConflictFinder::new().find(&ptf_src, &mut profile);
+
+	log::debug!("Conflicts:\n{:#?}", conflicts);
+	util::log_conflicts(&conflicts, Some(&mut io.stdout));
+
+	Ok(())
+}
+
+/// Handles the `doctor` command processing.
+fn handle_command_doctor(
+	opt::Doctor {
+		shared:
+			opt::RepoShared {
+				source,
+				profile,
+				auto,
+				profile_stdin,
+				profile_inline,
+			},
+		target,
+		json_output,
+	}: opt::Doctor,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	let ptf_src = PunktfSource::from_root(source)?;
+	let (profile_name, inline_profile) =
+		select_profile_name(profile, auto, profile_stdin, profile_inline, &ptf_src, io)?;
+	let mut profile = setup_profile(&profile_name, inline_profile, &ptf_src, target)?;
+
+	log::debug!("Profile:\n{:#?}", profile);
+	log::debug!("Source: {}", ptf_src.root().display());
+	log::debug!("Target: {:?}", profile.target_path());
+
+	let mut doctor = Doctor::new();
+
+	match util::get_manifest_path(&profile_name) {
+		Some(manifest_path) => {
+			doctor = doctor.with_manifest(&util::read_manifest(&manifest_path)?);
+		}
+		None => log::warn!("No state directory found. Orphaned files are not reported"),
+	}
+
+	let report = doctor.diagnose(&ptf_src, &mut profile);
+
+	log::debug!("Report:\n{:#?}", report);
+	util::log_doctor_report(&report, Some(&mut io.stdout));
+
+	if let Some(json_path) = json_output {
+		let file = File::create(&json_path)
+			.wrap_err_with(|| format!("Failed to create {}", json_path.display()))?;
+		serde_json::to_writer_pretty(file, &report)
+			.wrap_err("Failed to write report to json output file")?;
+	}
+
+	if report.is_healthy() {
+		Ok(())
+	} else {
+		Err(eyre!("Found {} problems", report.findings.len()))
+	}
+}
+
+/// Handles the `manifest` command processing.
+fn handle_command_manifest(
+	opt::Manifest { command }: opt::Manifest,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	match command {
+		opt::ManifestCommand::Verify(opt::ManifestVerify { path }) => {
+			let file =
+				File::open(&path).wrap_err_with(|| format!("Failed to open {}", path.display()))?;
+			let manifest: AuditManifest = serde_json::from_reader(file)
+				.wrap_err_with(|| format!("Failed to parse manifest `{}`", path.display()))?;
+
+			if manifest.version != AUDIT_MANIFEST_VERSION {
+				return Err(eyre!(
+					"Unsupported manifest version {} (expected {AUDIT_MANIFEST_VERSION})",
+					manifest.version
+				));
+			}
+
+			let mismatches = manifest.verify();
+
+			log::debug!("Mismatches:\n{:#?}", mismatches);
+			util::log_audit_mismatches(&manifest, &mismatches, Some(&mut io.stdout));
+
+			if mismatches.is_empty() {
+				Ok(())
+			} else {
+				Err(eyre!("Found {} mismatching items", mismatches.len()))
+			}
+		}
+	}
+}
+
+/// Handles the `hooks` command processing.
+fn handle_command_hooks(opt::Hooks { command }: opt::Hooks, io: &mut IoStreams<'_>) -> Result<()> {
+	match command {
+		opt::HooksCommand::List(opt::HooksList {
+			shared:
+				opt::RepoShared {
+					source,
+					profile,
+					auto,
+					profile_stdin,
+					profile_inline,
+				},
+		}) => {
+			let ptf_src = PunktfSource::from_root(source)?;
+			let (profile_name, inline_profile) =
+				select_profile_name(profile, auto, profile_stdin, profile_inline, &ptf_src, io)?;
+			let profile = setup_profile(&profile_name, inline_profile, &ptf_src, None)?;
+
+			util::log_hooks(&profile, Some(&mut io.stdout));
+
+			Ok(())
+		}
+		opt::HooksCommand::Run(opt::HooksRun {
+			shared:
+				opt::RepoShared {
+					source,
+					profile,
+					auto,
+					profile_stdin,
+					profile_inline,
+				},
+			kind,
+			number,
+		}) => {
+			let kind = match kind {
+				opt::HookKind::Pre => CommandKind::PreHook,
+				opt::HookKind::Post => CommandKind::PostHook,
+				opt::HookKind::OnFailure => CommandKind::OnFailureHook,
+				opt::HookKind::PreDotfile => CommandKind::PreDotfileHook,
+				opt::HookKind::PostDotfile => CommandKind::PostDotfileHook,
+			};
+
+			let ptf_src = PunktfSource::from_root(source)?;
+			let (profile_name, inline_profile) =
+				select_profile_name(profile, auto, profile_stdin, profile_inline, &ptf_src, io)?;
+			let profile = setup_profile(&profile_name, inline_profile, &ptf_src, None)?;
+
+			let hooks = util::profile_hooks(&profile, kind);
+			let Some((_, hook)) = number.checked_sub(1).and_then(|idx| hooks.get(idx)) else {
+				return Err(eyre!(
+					"No {kind} number {number} found; the profile defines {}",
+					hooks.len()
+				));
+			};
+
+			if !profile.trusted() {
+				return Err(eyre!(
+					"Profile `{profile_name}` is not trusted; none of its hooks are executed"
+				));
+			}
+
+			// Every run gets a new id, like a deployment would.
+			let id = DeploymentBuilder::default().id().to_string();
+			let envs = profile
+				.env()
+				.iter()
+				.chain([(DEPLOYMENT_ID_ENVVAR, id.as_str())]);
+
+			log::info!("[{id}] Executing {kind}: {}", hook.command());
+
+			let output = if matches!(
+				kind,
+				CommandKind::PreDotfileHook | CommandKind::PostDotfileHook
+			) {
+				let mut input = Vec::new();
+				io.stdin
+					.read_to_end(&mut input)
+					.wrap_err("Failed to read the item from stdin")?;
+
+				hook.run_with_input(ptf_src.profiles(), envs, &input)
+			} else {
+				hook.run_with_env(ptf_src.profiles(), envs)
+			}
+			.wrap_err_with(|| format!("Failed to execute {kind} `{}`", hook.command()))?;
+
+			io.stdout.write_all(output.stdout.as_bytes())?;
+			io.stderr.write_all(output.stderr.as_bytes())?;
+
+			output
+				.exit_ok()
+				.wrap_err_with(|| format!("Failed to execute {kind} `{}`", hook.command()))
+		}
+	}
+}
+
+/// Handles the `watch` command processing.
+fn handle_command_watch(
+	opt::Watch {
+		shared:
+			opt::RepoShared {
+				source,
+				profile,
+				auto,
+				profile_stdin,
+				profile_inline,
+			},
+		drift,
+		interval,
+		notify,
+	}: opt::Watch,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	if !drift {
+		return Err(eyre!(
+			"Nothing to watch; use `--drift` to watch the deployed items for changes"
+		));
+	}
+
+	let ptf_src = PunktfSource::from_root(source)?;
+	let (profile_name, _) =
+		select_profile_name(profile, auto, profile_stdin, profile_inline, &ptf_src, io)?;
+
+	let Some(manifest_path) = util::get_manifest_path(&profile_name) else {
+		return Err(eyre!("No state directory found"));
+	};
+
+	let manifest = util::read_manifest(&manifest_path)?;
+	let mut watcher = DriftWatcher::from_manifest(&manifest);
+
+	if watcher.is_empty() {
+		return Err(eyre!(
+			"No deployed items recorded for profile `{profile_name}`; deploy it first"
+		));
+	}
+
+	log::info!(
+		"Watching {} deployed items of profile `{profile_name}` for changes",
+		watcher.len()
+	);
+
+	let notify = notify.map(Hook::new);
+
+	loop {
+		std::thread::sleep(Duration::from_secs(interval));
+
+		for (path, drift) in watcher.poll() {
+			writeln!(
+				io.stdout,
+				"[{}] Changed outside of punktf ({drift})",
+				path.display()
+			)?;
+
+			if let Some(notify) = &notify {
+				let kind = drift.to_string();
+				let envs = [
+					("PUNKTF_DRIFT_PATH", path.as_os_str()),
+					("PUNKTF_DRIFT_KIND", kind.as_ref()),
+				];
+
+				if let Err(err) = notify.execute_with_env(ptf_src.root(), envs) {
+					log::error!(
+						"[{}] Failed to execute notify command: {err}",
+						path.display()
+					);
+				}
+			}
+		}
+
+		io.stdout.flush()?;
+	}
+}
+
+/// Handles the `diff` command processing.
+fn handle_command_diff(
+	opt::Diff {
+		shared:
+			opt::RepoShared {
+				source,
+				profile,
+				auto,
+				profile_stdin,
+				profile_inline,
+			},
+		filter: opt::FilterShared { only, skip },
+		format,
+		baseline,
+	}: opt::Diff,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	let ptf_src = PunktfSource::from_root(source)?;
+	let (profile_name, inline_profile) =
+		select_profile_name(profile, auto, profile_stdin, profile_inline, &ptf_src, io)?;
+	let mut profile = setup_profile(&profile_name, inline_profile, &ptf_src, None)?;
+
+	log::debug!("Profile:\n{:#?}", profile);
+	log::debug!("Source: {}", ptf_src.root().display());
+	log::debug!("Target: {:?}", profile.target_path());
+
+	if let Some(baseline) = baseline {
+		let baseline = util::read_deployment(&baseline)?;
+
+		// Deploy to the same location as the baseline, so that the target
+		// paths of both deployments match. Like a normal diff, this must not
+		// run any hooks or fail because of a full disk.
+		let options = DeployOptions {
+			dry_run: true,
+			check_free_space: false,
+			cache_dir: util::get_cache_path(),
+			strict: false,
+			no_lock: true,
+			destdir: baseline.destdir().map(Path::to_path_buf),
+			filter: TagFilter { only, skip },
+			no_exec: true,
+			merge_policy: None,
+			template_cache: false,
+			force: false,
+			only_managed: None,
+		};
+		let deployment = {
+			let io = RefCell::new(&mut *io);
+			Deployer::new(options, MergePrompt(&io)).deploy(&ptf_src, &mut profile)?
+		};
+
+		log::debug!("Deployment:\n{:#?}", deployment);
+		util::log_deployment_delta(
+			&deployment.compare_contents(&baseline),
+			Some(&mut io.stdout),
+		);
+
+		return Ok(());
+	}
+
+	// Only the first write error is kept; further events are ignored.
+	let output = RefCell::new((&mut io.stdout, Ok(())));
+
+	Diff::new(|event| {
+		let (stdout, result) = &mut *output.borrow_mut();
+
+		if result.is_ok() {
+			*result = diff::diff(stdout, format, event);
+		}
+	})
+	.with_filter(TagFilter { only, skip })
+	.diff(&ptf_src, &mut profile);
+
+	Ok(output.into_inner().1?)
+}
+
+/// Handles the `init` command processing.
+fn handle_command_init(opt::Init { path, git }: opt::Init, io: &mut IoStreams<'_>) -> Result<()> {
+	let created = init::init(&path, init::InitOptions { git })?;
+
+	if created.is_empty() {
+		writeln!(
+			io.stdout,
+			"Source directory {} is already set up",
+			path.display()
+		)?;
+		return Ok(());
+	}
+
+	for path in &created {
+		writeln!(io.stdout, "Created {}", path.display())?;
+	}
+
+	writeln!(
+		io.stdout,
+		"Add dotfiles to {} and list them in the profile, then run `{BINARY_NAME} deploy --source {} --profile {}`",
+		path.join("dotfiles").display(),
+		path.display(),
+		init::STARTER_PROFILE_NAME
+	)?;
+
+	Ok(())
+}
+
+/// Handles the `import` command processing.
+fn handle_command_import(
+	opt::Import {
+		from,
+		path,
+		source,
+		profile,
+		dry_run,
+	}: opt::Import,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	let format = match from {
+		opt::ImportFormat::Stow => import::ImportFormat::Stow,
+		opt::ImportFormat::Chezmoi => import::ImportFormat::Chezmoi,
+		opt::ImportFormat::Dotbot => import::ImportFormat::Dotbot,
+	};
+
+	let imported = import::import(format, &path)?;
+
+	for file in &imported.files {
+		writeln!(
+			io.stdout,
+			"{} -> {}",
+			file.from.display(),
+			source.join("dotfiles").join(&file.path).display()
+		)?;
+	}
+
+	for note in &imported.notes {
+		writeln!(
+			io.stdout,
+			"{}{} {note}",
+			style("note").yellow().bold(),
+			style(':').bold()
+		)?;
+	}
+
+	if dry_run {
+		log::info!("Note: Nothing was actually written, since dry run mode was enabled");
+		return Ok(());
+	}
+
+	let written = imported.write(&source, &profile)?;
+	writeln!(
+		io.stdout,
+		"Imported {} dotfiles and {} links into {}",
+		imported.profile.dotfiles.len(),
+		imported.profile.symlinks.len(),
+		written.display()
+	)?;
+
+	Ok(())
+}
+
+/// Handles the `migrate-profiles` command processing.
+fn handle_command_migrate_profiles(
+	opt::MigrateProfiles { source, dry_run }: opt::MigrateProfiles,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	let ptf_src = PunktfSource::from_root(source)?;
+
+	let mut paths = Vec::new();
+	for dent in ptf_src.profiles().read_dir()? {
+		let path = dent?.path();
+
+		if path.is_file() {
+			paths.push(path);
+		}
+	}
+	paths.sort();
+
+	for path in paths {
+		let deprecations = migrate::migrate_file(&path, dry_run)?;
+
+		if deprecations.is_empty() {
+			log::info!("[{}] Profile is up to date", path.display());
+			continue;
+		}
+
+		let action = if dry_run { "Would migrate" } else { "Migrated" };
+		writeln!(io.stdout, "{action} {}", path.display())?;
+
+		for deprecation in deprecations {
+			writeln!(io.stdout, "\t{deprecation}")?;
+		}
+	}
+
+	if dry_run {
+		log::info!("Note: No profiles were actually rewritten, since dry run mode was enabled");
+	}
+
+	Ok(())
+}
+
+/// Handles the `validate-profile` command processing.
+fn handle_command_validate_profile(
+	opt::ValidateProfile { source, profile }: opt::ValidateProfile,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	let ptf_src = PunktfSource::from_root(source)?;
+
+	let mut paths = if let Some(profile) = profile {
+		vec![validate::find_profile(&ptf_src, &profile)?]
+	} else {
+		let mut paths = Vec::new();
+		for dent in ptf_src.profiles().read_dir()? {
+			let path = dent?.path();
+
+			if path.is_file() {
+				paths.push(path);
+			}
+		}
+		paths
+	};
+	paths.sort();
+
+	let mut total = 0;
+	let mut invalid = 0;
+
+	for path in &paths {
+		let content = std::fs::read_to_string(path)?;
+		let problems = validate::validate(&ptf_src, path, &content);
+
+		if problems.is_empty() {
+			log::info!("[{}] Profile is valid", path.display());
+			continue;
+		}
+
+		total += problems.len();
+		invalid += 1;
+
+		for problem in problems {
+			writeln!(
+				io.stdout,
+				"{}{} {}\n",
+				style("error").red().bold(),
+				style(':').bold(),
+				problem.render(path, &content)
+			)?;
+		}
+	}
+
+	if total == 0 {
+		Ok(())
+	} else {
+		Err(eyre!("Found {total} problem(s) in {invalid} profile(s)"))
+	}
+}
+
+/// Handles the `edit` command processing.
+fn handle_command_edit(
+	opt::Edit {
+		source,
+		profile,
+		dotfile,
+	}: opt::Edit,
+) -> Result<()> {
+	let ptf_src = PunktfSource::from_root(source)?;
+
+	let path = if let Some(dotfile) = dotfile {
+		let path = ptf_src.dotfiles().join(&dotfile);
+
+		if !path.exists() {
+			return Err(eyre!(
+				"Dotfile `{}` does not exist in `{}`",
+				dotfile.display(),
+				ptf_src.dotfiles().display()
+			));
+		}
+
+		path
+	} else {
+		let profile = profile.ok_or_else(|| eyre!("No profile given"))?;
+		profile_path(&ptf_src, &profile)?
+	};
+
+	log::info!("Opening {}", path.display());
+
+	util::open_in_editor(&path)
+}
+
+/// Handles the `man` command processing.
+fn handle_command_man(opt::Man { output }: opt::Man) -> Result<()> {
+	let output = output.join(format!("{BINARY_NAME}.1"));
+
+	let man = clap_mangen::Man::new(opt::Opts::command());
+	let mut buffer: Vec<u8> = Default::default();
+	man.render(&mut buffer)?;
+
+	std::fs::write(output, buffer)?;
+
+	Ok(())
+}
+
+/// Subcommands for which the values of `--profile` are completed dynamically.
+const DYNAMIC_PROFILE_COMMANDS: [&str; 4] = ["deploy", "diff", "render", "verify"];
+
+/// Bash function which completes profile names and falls back to the
+/// generated completions otherwise.
+const BASH_PROFILE_COMPLETIONS: &str = r#"
+_punktf_profiles() {
+    local i cmd="" src=""
+    for (( i = 1; i < COMP_CWORD; i++ )); do
+        case "${COMP_WORDS[i]}" in
+            -s|--source) src="${COMP_WORDS[i+1]}" ;;
+            --source=*) src="${COMP_WORDS[i]#--source=}" ;;
+            -*) ;;
+            *) [[ -z "$cmd" ]] && cmd="${COMP_WORDS[i]}" ;;
+        esac
+    done
+
+    case "$cmd" in
+        deploy|diff|render|verify)
+            case "${COMP_WORDS[COMP_CWORD-1]}" in
+                -p|--profile)
+                    COMPREPLY=($(compgen -W "$(punktf __complete profiles ${src:+--source "$src"} 2>/dev/null)" -- "${COMP_WORDS[COMP_CWORD]}"))
+                    return 0
+                    ;;
+            esac
+            ;;
+    esac
+
+    _punktf "$@"
+}
+
+complete -F _punktf_profiles -o bashdefault -o default punktf
+"#;
+
+/// Zsh function which completes profile names.
+const ZSH_PROFILE_COMPLETIONS: &str = r#"(( $+functions[_punktf_profiles] )) ||
+_punktf_profiles() {
+    local src=${opt_args[-s]:-${opt_args[--source]}}
+    local -a profiles
+    profiles=(${(f)"$(punktf __complete profiles ${src:+--source "$src"} 2>/dev/null)"})
+    compadd -a profiles
+}
+
+"#;
+
+/// Fish function which completes profile names.
+const FISH_PROFILE_COMPLETIONS: &str = r#"
+function __fish_punktf_profiles
+    set -l args (commandline -opc)
+    set -l src
+    for i in (seq (count $args))
+        switch $args[$i]
+            case -s --source
+                if test $i -lt (count $args)
+                    set src --source $args[(math $i + 1)]
+                end
+            case '--source=*'
+                set src $args[$i]
+        end
+    end
+    punktf __complete profiles $src 2>/dev/null
+end
+
+complete -c punktf -n "__fish_seen_subcommand_from deploy diff render verify" -s p -l profile -x -a "(__fish_punktf_profiles)"
+"#;
+
+/// Handles the `completions` command processing.
+///
+/// For the shells which support it, the generated script is extended to
+/// complete profile names (related: [`DYNAMIC_PROFILE_COMMANDS`]).
+fn handle_command_completions(opt::Completions { shell, output }: opt::Completions) -> Result<()> {
+	let path = clap_complete::generate_to(shell, &mut opt::Opts::command(), BINARY_NAME, output)?;
+
+	let script = std::fs::read_to_string(&path)?;
+	std::fs::write(&path, add_profile_completions(shell, script))?;
+
+	Ok(())
+}
+
+/// Extends a generated completion script for `shell` to complete the values of
+/// `--profile` with the names and aliases of all profiles.
+///
+/// Scripts for unsupported shells are returned unchanged.
+fn add_profile_completions(shell: clap_complete::Shell, script: String) -> String {
+	use clap_complete::Shell;
+
+	match shell {
+		Shell::Bash => script + BASH_PROFILE_COMPLETIONS,
+		Shell::Fish => script + FISH_PROFILE_COMPLETIONS,
+		Shell::Zsh => {
+			let mut extended = String::with_capacity(script.len() + ZSH_PROFILE_COMPLETIONS.len());
+			let mut command = "";
+
+			for line in script.split_inclusive('\n') {
+				// Each subcommand starts with a case pattern like `(deploy)`
+				if let Some(name) = line
+					.trim()
+					.strip_prefix('(')
+					.and_then(|l| l.strip_suffix(')'))
+				{
+					command = name;
+				}
+
+				// The generated dispatch must come after all function definitions
+				if line.starts_with("if [ \"$funcstack[1]\" = \"_punktf\" ]") {
+					extended.push_str(ZSH_PROFILE_COMPLETIONS);
+				}
+
+				if DYNAMIC_PROFILE_COMMANDS.contains(&command) {
+					extended.push_str(
+						&line.replace(":PROFILE:_default'", ":PROFILE:_punktf_profiles'"),
+					);
+				} else {
+					extended.push_str(line);
+				}
+			}
+
+			extended
+		}
+		_ => script,
+	}
+}
+
+/// Handles the hidden `__complete` command processing.
+///
+/// Errors are ignored, as the output is only used for shell completions.
+fn handle_command_complete(
+	opt::Complete { kind, source }: opt::Complete,
+	io: &mut IoStreams<'_>,
+) -> Result<()> {
+	let Some(source) = source else {
+		return Ok(());
+	};
+
+	match kind {
+		opt::CompleteKind::Profiles => {
+			let Ok(ptf_src) = PunktfSource::from_root(source) else {
+				return Ok(());
+			};
+
+			let mut names = collect_profile_names(&ptf_src)
+				.map(|names| names.into_keys().collect::<Vec<_>>())
+				.unwrap_or_default();
+			names.sort();
+
+			for name in names {
+				writeln!(io.stdout, "{name}")?;
+			}
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::ops::Deref;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	use punktf_lib::temp::TempPath;
+
+	use super::*;
+
+	/// A uniquely named directory for a single test (related: [`test_dir`]).
+	///
+	/// The directory is removed once this is dropped, even if the test
+	/// panics.
+	struct TestDir(TempPath);
+
+	impl Deref for TestDir {
+		type Target = Path;
+
+		fn deref(&self) -> &Self::Target {
+			self.0.path()
+		}
+	}
+
+	/// Creates a new empty directory for a test.
+	///
+	/// Tests run concurrently (also in multiple processes), so each directory
+	/// gets a unique name.
+	fn test_dir() -> TestDir {
+		static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+		let path = std::env::temp_dir().join(format!(
+			"punktf-cli-test-{}-{}",
+			std::process::id(),
+			COUNTER.fetch_add(1, Ordering::Relaxed)
+		));
+
+		TestDir(TempPath::create_dir(path).expect("Test directory to be created"))
+	}
+
+	/// Runs `punktf` with `args` and returns the exit code together with the
+	/// captured `stdout` and `stderr`.
+	fn run_captured(args: &[&str]) -> (ExitCode, String, String) {
+		let mut stdout = Vec::new();
+		let mut stderr = Vec::new();
+
+		let io = IoStreams {
+			stdin: Box::new(&b""[..]),
+			stdout: Box::new(&mut stdout),
+			stderr: Box::new(&mut stderr),
+		};
+
+		let code = run(std::iter::once(BINARY_NAME).chain(args.iter().copied()), io);
+
+		(
+			code,
+			String::from_utf8(stdout).expect("Output is valid utf8"),
+			String::from_utf8(stderr).expect("Output is valid utf8"),
+		)
+	}
+
+	#[test]
+	fn run_version() {
+		let (code, stdout, _) = run_captured(&["--version"]);
+
+		assert_eq!(code, ExitCode::SUCCESS);
+		assert!(stdout.contains(env!("CARGO_PKG_VERSION")));
+	}
+
+	#[test]
+	fn run_deploy_without_target() -> std::io::Result<()> {
+		let dir = test_dir();
+		std::fs::create_dir_all(dir.join("profiles"))?;
+		std::fs::create_dir_all(dir.join("dotfiles"))?;
+		std::fs::write(dir.join("profiles/test.yaml"), "dotfiles: []\n")?;
+
+		let source = dir.to_string_lossy();
+		let (code, _, stderr) = run_captured(&["deploy", "--source", &source, "--profile", "test"]);
+
+		assert_eq!(code, ExitCode::FAILURE);
+		assert!(stderr.contains("No target path for the deployment set"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn run_deploy_inline_profile() -> std::io::Result<()> {
+		let dir = test_dir();
+		std::fs::create_dir_all(dir.join("profiles"))?;
+		std::fs::create_dir_all(dir.join("dotfiles"))?;
+		std::fs::write(
+			dir.join("profiles/base.yaml"),
+			"dotfiles:\n  - path: .bashrc\n",
+		)?;
+		std::fs::write(dir.join("dotfiles/.bashrc"), "bash")?;
+
+		let source = dir.to_string_lossy();
+		// Staged, so the deployment is not recorded in the manifest.
+		let destdir = dir.join("stage");
+		let destdir = destdir.to_string_lossy();
+		let (code, _, stderr) = run_captured(&[
+			"deploy",
+			"--source",
+			&source,
+			"--destdir",
+			&destdir,
+			"--profile-inline",
+			r#"{"extends": ["base"], "target": "/home/demo"}"#,
+		]);
+
+		assert_eq!(code, ExitCode::SUCCESS, "{stderr}");
+		assert_eq!(
+			std::fs::read_to_string(dir.join("stage/home/demo/.bashrc"))?,
+			"bash"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn run_deploy_reports() -> std::io::Result<()> {
+		let dir = test_dir();
+		std::fs::create_dir_all(dir.join("dotfiles"))?;
+		std::fs::write(dir.join("dotfiles/.bashrc"), "bash")?;
+
+		let source = dir.to_string_lossy();
+		let destdir = dir.join("stage");
+		let destdir = destdir.to_string_lossy();
+		let report = format!("json:{}", dir.join("report.json").display());
+		let (code, stdout, stderr) = run_captured(&[
+			"deploy",
+			"--source",
+			&source,
+			"--destdir",
+			&destdir,
+			"--profile-inline",
+			r#"{"target": "/home/demo", "dotfiles": [{"path": ".bashrc"}]}"#,
+			"--report",
+			&report,
+			"--report",
+			"stdout:yaml",
+		]);
+
+		assert_eq!(code, ExitCode::SUCCESS, "{stderr}");
+		assert!(std::fs::read_to_string(dir.join("report.json"))?.contains("\"dotfiles\""));
+		assert!(stdout.contains("dotfiles:"), "{stdout}");
+
+		let (code, _, stderr) = run_captured(&[
+			"deploy",
+			"--source",
+			&source,
+			"--profile",
+			"none",
+			"--report",
+			"mail:demo@example.com",
+		]);
+
+		assert_ne!(code, ExitCode::SUCCESS);
+		assert!(stderr.contains("Invalid report"), "{stderr}");
+
+		Ok(())
+	}
+
+	#[test]
+	fn run_diff_baseline() -> std::io::Result<()> {
+		let dir = test_dir();
+		std::fs::create_dir_all(dir.join("dotfiles"))?;
+		std::fs::write(dir.join("dotfiles/.bashrc"), "bash")?;
+		std::fs::write(dir.join("dotfiles/.vimrc"), "vim")?;
+
+		let source = dir.to_string_lossy();
+		let destdir = dir.join("stage");
+		let destdir = destdir.to_string_lossy();
+		let baseline = dir.join("baseline.json");
+		let report = format!("json:{}", baseline.display());
+		let profile =
+			r#"{"target": "/home/demo", "dotfiles": [{"path": ".bashrc"}, {"path": ".vimrc"}]}"#;
+		let (code, _, stderr) = run_captured(&[
+			"deploy",
+			"--source",
+			&source,
+			"--destdir",
+			&destdir,
+			"--profile-inline",
+			profile,
+			"--report",
+			&report,
+		]);
+
+		assert_eq!(code, ExitCode::SUCCESS, "{stderr}");
+
+		let baseline = baseline.to_string_lossy();
+		let diff = |profile: &str| {
+			run_captured(&[
+				"diff",
+				"--source",
+				&source,
+				"--profile-inline",
+				profile,
+				"--baseline",
+				&baseline,
+			])
+		};
+
+		let (code, stdout, stderr) = diff(profile);
+		assert_eq!(code, ExitCode::SUCCESS, "{stderr}");
+		assert!(stdout.contains("No changes"), "{stdout}");
+
+		std::fs::write(dir.join("dotfiles/.bashrc"), "changed")?;
+		std::fs::write(dir.join("dotfiles/.zshrc"), "zsh")?;
+
+		let (code, stdout, stderr) = diff(
+			r#"{"target": "/home/demo", "dotfiles": [{"path": ".bashrc"}, {"path": ".zshrc"}]}"#,
+		);
+		assert_eq!(code, ExitCode::SUCCESS, "{stderr}");
+		assert!(stdout.contains(".bashrc: Content changed"), "{stdout}");
+		assert!(stdout.contains("Added (Success)"), "{stdout}");
+		assert!(stdout.contains("Removed (Success)"), "{stdout}");
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn run_hooks() -> std::io::Result<()> {
+		let dir = test_dir();
+		std::fs::create_dir_all(dir.join("profiles"))?;
+		std::fs::create_dir_all(dir.join("dotfiles"))?;
+		std::fs::write(
+			dir.join("profiles/test.yaml"),
+			"extends: [base]\npre_hooks:\n  - echo \"pre $PUNKTF_CURRENT_PROFILE\"\n",
+		)?;
+		std::fs::write(
+			dir.join("profiles/base.yaml"),
+			"pre_hooks:\n  - exit 4\npost_hooks:\n  - pwd\n",
+		)?;
+
+		let source = dir.to_string_lossy();
+		let hooks = |args: &[&str]| {
+			run_captured(
+				&[
+					&["hooks"],
+					args,
+					&["--source", &source, "--profile", "test"],
+				]
+				.concat(),
+			)
+		};
+
+		let (code, stdout, _) = hooks(&["list"]);
+		let stdout = console::strip_ansi_codes(&stdout);
+		assert_eq!(code, ExitCode::SUCCESS);
+		assert!(stdout.contains("1: echo \"pre $PUNKTF_CURRENT_PROFILE\" (test)"));
+		assert!(stdout.contains("2: exit 4 (base)"));
+		assert!(stdout.contains("1: pwd (base)"));
+
+		let (code, stdout, _) = hooks(&["run", "pre"]);
+		assert_eq!(code, ExitCode::SUCCESS);
+		assert_eq!(stdout, "pre test\n");
+
+		let (code, stdout, _) = hooks(&["run", "post", "1"]);
+		assert_eq!(code, ExitCode::SUCCESS);
+		assert_eq!(
+			Path::new(stdout.trim_end()).canonicalize()?,
+			dir.join("profiles").canonicalize()?
+		);
+
+		let (code, _, stderr) = hooks(&["run", "pre", "2"]);
+		assert_eq!(code, ExitCode::FAILURE);
+		assert!(stderr.contains("Failed to execute pre-hook `exit 4`"));
+
+		let (code, _, stderr) = hooks(&["run", "on-failure"]);
+		assert_eq!(code, ExitCode::FAILURE);
+		assert!(stderr.contains("No on-failure-hook number 1 found"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn user_config_defaults() -> Result<()> {
+		let config: config::UserConfig = toml::from_str(
+			"source = \"/dotfiles\"\nprofile = \"arch\"\nverbosity = 2\ndiff_format = \"unified\"\n",
+		)?;
+		let parse = |args: &[&str]| {
+			config
+				.apply(opt::Opts::command())
+				.try_get_matches_from(std::iter::once(BINARY_NAME).chain(args.iter().copied()))
+				.and_then(|matches| opt::Opts::from_arg_matches(&matches))
+		};
+
+		let opts = parse(&["diff"])?;
+		assert_eq!(opts.shared.verbose, 2);
+		let Command::Diff(diff) = opts.command else {
+			panic!("Expected diff command");
+		};
+		assert_eq!(diff.shared.source, PathBuf::from("/dotfiles"));
+		assert_eq!(diff.shared.profile.as_deref(), Some("arch"));
+		assert!(matches!(diff.format, opt::DiffFormat::Unified));
+
+		// Options on the command line take precedence
+		let opts = parse(&["-v", "deploy", "-s", "/other", "-p", "work"])?;
+		assert_eq!(opts.shared.verbose, 1);
+		let Command::Deploy(deploy) = opts.command else {
+			panic!("Expected deploy command");
+		};
+		assert_eq!(deploy.shared.source, PathBuf::from("/other"));
+		assert_eq!(deploy.shared.profile.as_deref(), Some("work"));
+
+		// Unknown keys are rejected
+		assert!(toml::from_str::<config::UserConfig>("sorce = \"/dotfiles\"").is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn run_complete_profiles() -> std::io::Result<()> {
+		let dir = test_dir();
+		std::fs::create_dir_all(dir.join("profiles"))?;
+		std::fs::create_dir_all(dir.join("dotfiles"))?;
+		std::fs::write(
+			dir.join("profiles/work.yaml"),
+			"aliases: [office]\ndotfiles: []\n",
+		)?;
+		std::fs::write(dir.join("profiles/home.yaml"), "dotfiles: []\n")?;
+
+		let source = dir.to_string_lossy();
+		let (code, stdout, _) = run_captured(&["__complete", "profiles", "--source", &source]);
+
+		assert_eq!(code, ExitCode::SUCCESS);
+		assert_eq!(stdout, "home\noffice\nwork\n");
+
+		Ok(())
+	}
+
+	#[test]
+	fn profile_completions() {
+		let script = add_profile_completions(
+			clap_complete::Shell::Zsh,
+			String::from(
+				"        (deploy)\n'-p+[Profile]:PROFILE:_default' \\\n        (clean)\n'-p+[Profile]:PROFILE:_default' \\\n",
+			),
+		);
+
+		assert!(script.contains("(deploy)\n'-p+[Profile]:PROFILE:_punktf_profiles'"));
+		assert!(script.contains("(clean)\n'-p+[Profile]:PROFILE:_default'"));
+	}
+}
@@ -13,6 +13,7 @@ use std::path::PathBuf;
 
 use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
+use punktf_lib::visit::deploy::report::ReportSpec;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
@@ -48,25 +49,92 @@ pub struct Shared {
 	/// Will only print errors
 	#[arg(short, long)]
 	pub quite: bool,
+
+	/// Sets the log level of single modules.
+	///
+	/// Uses the syntax of `RUST_LOG` (e.g.
+	/// `punktf_lib::template=trace,punktf_lib::visit=info`). The directives
+	/// are applied on top of the level set by `--verbose`/`--quite`.
+	#[arg(long, value_name = "SPEC")]
+	pub log_filter: Option<String>,
 }
 
 #[derive(Debug, Args)]
 pub struct OutputShared {
 	/// Writes the deployment status as json to the given path.
+	///
+	/// Same as `--report json:<PATH>`.
 	#[arg(long)]
 	pub json_output: Option<PathBuf>,
 
 	/// Writes the deployment status as yaml to the given path.
+	///
+	/// Same as `--report yaml:<PATH>`.
 	#[arg(long)]
 	pub yaml_output: Option<PathBuf>,
+
+	/// Writes the deployment status to the given destination. Can be given
+	/// multiple times.
+	///
+	/// Supported are `json:<PATH>` and `yaml:<PATH>` (new files),
+	/// `stdout[:json|:yaml]` and `webhook:<URL>` (json `POST` request which
+	/// times out after 10 seconds).
+	#[arg(long = "report", value_name = "KIND:DEST")]
+	pub reports: Vec<ReportSpec>,
+
+	/// Compares the deployment against a previous deployment status.
+	///
+	/// The file needs to be a json or yaml file as created with
+	/// `--json-output`/`--yaml-output`. All items which were added, removed or
+	/// changed their status since then are printed.
+	#[arg(long)]
+	pub compare: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct FilterShared {
+	/// Only processes dotfiles with at least one of the given tags.
+	#[arg(long, value_name = "TAGS", value_delimiter = ',')]
+	pub only: Vec<String>,
+
+	/// Does not process dotfiles with any of the given tags.
+	#[arg(long, value_name = "TAGS", value_delimiter = ',')]
+	pub skip: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ReportShared {
+	/// Exits with a non-zero exit code if any item was skipped.
+	///
+	/// This includes items skipped for any reason (e.g. a denied merge or a
+	/// dotfile with a higher priority), which is useful for automation.
+	#[arg(long)]
+	pub fail_on_skip: bool,
+
+	/// Only prints the final counters of the deployment instead of the status
+	/// of each item.
+	#[arg(long)]
+	pub summary_only: bool,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
+	Init(Init),
+	Import(Import),
 	Deploy(Deploy),
 	Render(Render),
 	Verify(Verify),
 	Diff(Diff),
+	Clean(Clean),
+	Explain(Explain),
+	Conflicts(Conflicts),
+	Doctor(Doctor),
+	Manifest(Manifest),
+	Hooks(Hooks),
+	Watch(Watch),
+	MigrateProfiles(MigrateProfiles),
+	ValidateProfile(ValidateProfile),
+	Edit(Edit),
 	Man(Man),
 	Completions(Completions),
 }
@@ -81,8 +149,101 @@ pub struct RepoShared {
 	///
 	/// The name should be the file name of the profile without an extension (e.g.
 	/// `profiles/arch.json` should be given as `arch`).
-	#[arg(short, long, env = super::PUNKTF_PROFILE_ENVVAR)]
+	///
+	/// Together with `--profile-stdin` or `--profile-inline` this is only used
+	/// as name of the given profile.
+	#[arg(
+		short,
+		long,
+		env = super::PUNKTF_PROFILE_ENVVAR,
+		required_unless_present_any = ["auto", "profile_stdin", "profile_inline"]
+	)]
+	pub profile: Option<String>,
+
+	/// Selects the profile for the current machine automatically.
+	///
+	/// The profile is chosen by the first entry of the `machines` file
+	/// (`machines.yaml`/`machines.json`) in the source directory which matches
+	/// the hostname and operating system of this machine. Takes precedence over
+	/// `--profile`.
+	#[arg(long)]
+	pub auto: bool,
+
+	/// Reads the profile definition (YAML or JSON) from stdin instead of the
+	/// `profiles` directory.
+	///
+	/// The profile can still extend profiles of the source directory. As stdin
+	/// is consumed by the profile, merge modes which ask the user are not
+	/// answered.
+	#[arg(long, conflicts_with_all = ["auto", "profile_inline"])]
+	pub profile_stdin: bool,
+
+	/// Uses the given profile definition (YAML or JSON) instead of looking it
+	/// up in the `profiles` directory.
+	///
+	/// The profile can still extend profiles of the source directory.
+	#[arg(long, value_name = "PROFILE", conflicts_with = "auto")]
+	pub profile_inline: Option<String>,
+}
+
+/// Creates a new source directory.
+///
+/// Creates the `profiles` and `dotfiles` directories and a starter profile
+/// named `default`, which lists the most common fields. Existing files are
+/// kept as they are.
+#[derive(Debug, Parser)]
+pub struct Init {
+	/// The source directory to create.
+	#[arg(env = super::PUNKTF_SOURCE_ENVVAR, default_value = ".")]
+	pub path: PathBuf,
+
+	/// Initializes a git repository with a `.gitignore` in the source
+	/// directory.
+	#[arg(long)]
+	pub git: bool,
+}
+
+/// Imports the dotfiles of another dotfile manager as a new profile.
+///
+/// Plain files and symlinks are converted, everything else (e.g. scripts or
+/// templates of the other manager) is listed to be converted by hand. The
+/// imported files are copied into the `dotfiles` directory; existing files
+/// are never overwritten.
+#[derive(Debug, Parser)]
+pub struct Import {
+	/// The dotfile manager to import from.
+	#[arg(value_enum, long)]
+	pub from: ImportFormat,
+
+	/// The directory of the other dotfile manager.
+	///
+	/// For `dotbot`, this can also be the path of the configuration file.
+	pub path: PathBuf,
+
+	/// The source directory into which the profile is imported.
+	#[arg(short, long, env = super::PUNKTF_SOURCE_ENVVAR)]
+	pub source: PathBuf,
+
+	/// Name of the imported profile.
+	#[arg(short, long, default_value = "imported")]
 	pub profile: String,
+
+	/// Only prints what would be imported without writing anything.
+	#[arg(short, long)]
+	pub dry_run: bool,
+}
+
+/// Dotfile manager to import from (related: [`Import::from`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+	/// GNU Stow packages.
+	Stow,
+
+	/// The chezmoi source state.
+	Chezmoi,
+
+	/// The configuration of Dotbot.
+	Dotbot,
 }
 
 /// Deploys a profile.
@@ -91,6 +252,9 @@ pub struct Deploy {
 	#[command(flatten)]
 	pub shared: RepoShared,
 
+	#[command(flatten)]
+	pub filter: FilterShared,
+
 	/// Alternative deployment target path.
 	///
 	/// This path will take precedence over all other ways to define a deployment
@@ -105,14 +269,150 @@ pub struct Deploy {
 	#[arg(short, long)]
 	pub dry_run: bool,
 
+	/// Skips the check for enough free space on the target filesystems.
+	///
+	/// By default the space required by the deployment is estimated before
+	/// anything is written and the deployment is aborted if not enough space
+	/// is available.
+	#[arg(long)]
+	pub skip_space_check: bool,
+
+	/// Removes files which were deployed by a previous deployment of this
+	/// profile but are no longer part of it.
+	#[arg(long, conflicts_with_all = ["only", "skip"])]
+	pub prune: bool,
+
+	/// Deploys only the dotfile with this path (as given in the profile,
+	/// e.g. `windows/alacritty.yml`).
+	///
+	/// The dotfile is deployed like with a full deployment (including
+	/// priority and merge checks, templates and transformers), but no other
+	/// dotfiles and links are touched. Hooks are still executed, unless
+	/// `--no-exec` is given.
+	#[arg(long, value_name = "PATH", conflicts_with_all = ["prune", "progress"])]
+	pub only_path: Option<PathBuf>,
+
+	/// Prefixes every resolved target path with this directory (like
+	/// `DESTDIR` of `make`).
+	///
+	/// This stages the whole deployment into a scratch directory, e.g. for
+	/// packaging or inspection. The target `/home/demo/.bashrc` is deployed
+	/// to `<DESTDIR>/home/demo/.bashrc`. Staged deployments are not recorded
+	/// in the manifest.
+	#[arg(long, value_name = "DESTDIR", conflicts_with = "prune")]
+	pub destdir: Option<PathBuf>,
+
+	/// Fails the deployment if any item was skipped because of a conflict.
+	///
+	/// This includes items which already exist and are kept (merge mode
+	/// `keep` or a denied merge), links with an existing target and rejected
+	/// items. The items are still processed the same way, only the exit code
+	/// is affected.
+	#[arg(long)]
+	pub strict: bool,
+
+	/// Deploys without acquiring the deployment lock.
+	///
+	/// By default a lock file is created in the target directory, which
+	/// prevents two deployments from running at the same time and
+	/// interleaving their writes.
+	#[arg(long)]
+	pub no_lock: bool,
+
+	/// Does not execute any commands defined by the profile (e.g. hooks).
+	///
+	/// This is useful to cautiously try dotfiles of other people. All
+	/// commands which would have been executed are listed in the report
+	/// instead.
+	#[arg(long)]
+	pub no_exec: bool,
+
+	/// Shows a progress bar with the number of deployed items and the time
+	/// each item took.
+	///
+	/// The bar is written to stderr.
+	#[arg(long)]
+	pub progress: bool,
+
+	/// Prints statistics about the deployment after it finished.
+	///
+	/// This includes the N slowest items (default: 10), the number of items
+	/// per status, the bytes written and how the time was split between
+	/// resolving templates and filesystem operations. Useful to find out why
+	/// a deployment is slow (e.g. on a network filesystem).
+	#[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "10")]
+	pub stats: Option<usize>,
+
+	/// Stores the outputs of templates in the cache directory, so that
+	/// unchanged templates are not resolved again by following deployments.
+	///
+	/// A cached output is only reused if the template and the values of all
+	/// variables it uses did not change.
+	#[arg(long)]
+	pub template_cache: bool,
+
+	/// Decides about existing targets of dotfiles and links with merge mode
+	/// `ask` without prompting.
+	///
+	/// This allows unattended deployments (e.g. provisioning) which never
+	/// block on a prompt. `keep` reports the items as conflicts, `fail` as
+	/// failed.
+	#[arg(value_enum, long)]
+	pub merge_policy: Option<MergePolicy>,
+
+	/// Overwrites read-only targets.
+	///
+	/// The targets are made writable before they are overwritten and
+	/// read-only again afterwards. Without this flag, deploying to a
+	/// read-only target fails.
+	#[arg(long)]
+	pub force: bool,
+
+	/// Never overwrites existing targets which were not deployed by `punktf`.
+	///
+	/// Only targets which are recorded in the manifest of deployed files of
+	/// the profile may be overwritten, regardless of the merge mode. All
+	/// other existing targets are kept and reported as conflicts. This
+	/// protects hand written files, e.g. on a new machine.
+	#[arg(long, conflicts_with = "destdir")]
+	pub only_managed: bool,
+
+	/// Writes a manifest of all deployed items (with hashes, sources and the
+	/// profile which defined them) as json to the given path.
+	///
+	/// The manifest can later be checked against the target with
+	/// `punktf manifest verify`.
+	#[arg(long, value_name = "PATH", conflicts_with = "dry_run")]
+	pub emit_manifest: Option<PathBuf>,
+
 	#[command(flatten)]
 	pub output: OutputShared,
+
+	#[command(flatten)]
+	pub report: ReportShared,
+}
+
+/// Policy for existing targets of items with merge mode `ask` (related:
+/// [`Deploy::merge_policy`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MergePolicy {
+	/// Overwrites the existing target.
+	Overwrite,
+
+	/// Keeps the existing target.
+	Keep,
+
+	/// Keeps the existing target and fails the item.
+	Fail,
 }
 
 /// Prints the resolved dotfile to stdout.
 ///
 /// This is mainly intended for template dotifles to see the what the real content
 /// would look like once it is deployed.
+///
+/// With `--out`, the whole profile is rendered into a staging directory
+/// instead.
 #[derive(Debug, Parser)]
 pub struct Render {
 	#[command(flatten)]
@@ -121,7 +421,24 @@ pub struct Render {
 	/// Dotfile to render.
 	///
 	/// Relative path starting from the `dotfiles` directory.
-	pub dotfile: PathBuf,
+	#[arg(required_unless_present_any = ["out", "check"])]
+	pub dotfile: Option<PathBuf>,
+
+	/// Renders all dotfiles of the profile into this directory.
+	///
+	/// The target paths keep their absolute structure below the directory
+	/// (e.g. `/home/demo/.bashrc` is rendered to `<OUT>/home/demo/.bashrc`).
+	/// No hooks are executed and the deployment is not recorded.
+	#[arg(short, long, value_name = "OUT", conflicts_with = "dotfile")]
+	pub out: Option<PathBuf>,
+
+	/// Only checks that all templates of the profile can be parsed and
+	/// resolved.
+	///
+	/// The templates are checked in parallel and all problems are reported
+	/// at once. Fails if any template has a problem.
+	#[arg(long, conflicts_with_all = ["dotfile", "out"])]
+	pub check: bool,
 }
 
 /// Verifies a profile.
@@ -139,8 +456,14 @@ pub struct Verify {
 	#[command(flatten)]
 	pub shared: RepoShared,
 
+	#[command(flatten)]
+	pub filter: FilterShared,
+
 	#[command(flatten)]
 	pub output: OutputShared,
+
+	#[command(flatten)]
+	pub report: ReportShared,
 }
 
 /// Format of the output of the [`Diff`] command.
@@ -165,9 +488,275 @@ pub struct Diff {
 	#[command(flatten)]
 	pub shared: RepoShared,
 
+	#[command(flatten)]
+	pub filter: FilterShared,
+
 	/// Defines the output format for the diffs.
 	#[arg(value_enum, short, long, default_value_t = DiffFormat::Pretty)]
 	pub format: DiffFormat,
+
+	/// Compares against a stored deployment status (json or yaml) instead of
+	/// the deployed files.
+	///
+	/// Lists all items which were added, removed or whose content changed
+	/// since the deployment which wrote the status.
+	#[arg(long)]
+	pub baseline: Option<PathBuf>,
+}
+
+/// Removes files which were deployed by a previous deployment of a profile but
+/// are no longer part of it.
+///
+/// Only files which are recorded as deployed by `punktf` are ever removed.
+#[derive(Debug, Parser)]
+pub struct Clean {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Alternative deployment target path.
+	///
+	/// This path will take precedence over all other ways to define a deployment
+	/// path.
+	#[arg(short, long)]
+	pub target: Option<PathBuf>,
+
+	/// Only prints the files which would be removed.
+	#[arg(short, long)]
+	pub dry_run: bool,
+}
+
+/// Explains why a target path has its content.
+///
+/// Lists all dotfiles of the profile which would be deployed to the path,
+/// which profile defines them, which variables and transformers are used and
+/// why competing dotfiles lose (priority/merge mode).
+#[derive(Debug, Parser)]
+pub struct Explain {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Alternative deployment target path.
+	///
+	/// This path will take precedence over all other ways to define a deployment
+	/// path.
+	#[arg(short, long)]
+	pub target: Option<PathBuf>,
+
+	/// Absolute path of the deployed file to explain.
+	pub path: PathBuf,
+}
+
+/// Reports target paths which are claimed by multiple items.
+///
+/// Walks the profile without writing anything and lists all target paths to
+/// which more than one dotfile or symlink would be deployed, together with the
+/// item which wins under the current priority and merge rules.
+#[derive(Debug, Parser)]
+pub struct Conflicts {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Alternative deployment target path.
+	///
+	/// This path will take precedence over all other ways to define a deployment
+	/// path.
+	#[arg(short, long)]
+	pub target: Option<PathBuf>,
+}
+
+/// Checks the health of a deployed profile.
+///
+/// Reports symlinks which are broken or point to the wrong source, items
+/// whose source no longer exists, files deployed by a previous deployment
+/// which are no longer part of the profile and targets which can not be
+/// written. Nothing is changed. Exits with a non-zero exit code if any
+/// problem is found.
+#[derive(Debug, Parser)]
+pub struct Doctor {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Alternative deployment target path.
+	///
+	/// This path will take precedence over all other ways to define a deployment
+	/// path.
+	#[arg(short, long)]
+	pub target: Option<PathBuf>,
+
+	/// Writes the report as json to the given path.
+	#[arg(long)]
+	pub json_output: Option<PathBuf>,
+}
+
+/// Works with manifests written by `deploy --emit-manifest`.
+#[derive(Debug, Parser)]
+pub struct Manifest {
+	#[command(subcommand)]
+	pub command: ManifestCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ManifestCommand {
+	Verify(ManifestVerify),
+}
+
+/// Checks the target against a manifest.
+///
+/// Reports all items of the manifest which are missing, changed their content
+/// or kind, or links which point somewhere else. Items which are not part of
+/// the manifest are ignored. Exits with a non-zero exit code if any item does
+/// not match.
+#[derive(Debug, Parser)]
+pub struct ManifestVerify {
+	/// Path of the manifest.
+	pub path: PathBuf,
+}
+
+/// Lists and executes the hooks of a profile without deploying anything.
+#[derive(Debug, Parser)]
+pub struct Hooks {
+	#[command(subcommand)]
+	pub command: HooksCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HooksCommand {
+	List(HooksList),
+	Run(HooksRun),
+}
+
+/// Lists all hooks of the profile.
+///
+/// The hooks are grouped by their kind and numbered in the order in which
+/// they are executed. For each hook the profile defining it is shown.
+#[derive(Debug, Parser)]
+pub struct HooksList {
+	#[command(flatten)]
+	pub shared: RepoShared,
+}
+
+/// Executes a single hook of the profile.
+///
+/// The hook is executed like during a deployment: in the `profiles` directory
+/// with all `PUNKTF_*` environment variables set. Dotfile hooks receive the
+/// stdin of `punktf` (the JSON description of an item). Variables which are
+/// only set for some hooks (e.g. `PUNKTF_FAILURE_REASON`) are passed on from
+/// the environment of `punktf`.
+///
+/// Exits with a non-zero exit code if the hook fails.
+#[derive(Debug, Parser)]
+pub struct HooksRun {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Kind of the hook.
+	#[arg(value_enum)]
+	pub kind: HookKind,
+
+	/// Number of the hook as shown by `hooks list`.
+	#[arg(default_value_t = 1)]
+	pub number: usize,
+}
+
+/// Kind of a hook (related: [`HooksRun::kind`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HookKind {
+	/// A hook executed before the deployment.
+	Pre,
+
+	/// A hook executed after the deployment.
+	Post,
+
+	/// A hook executed after a failed deployment.
+	OnFailure,
+
+	/// A hook executed before each item is deployed.
+	PreDotfile,
+
+	/// A hook executed after each item was processed.
+	PostDotfile,
+}
+
+/// Watches items for changes.
+///
+/// With `--drift`, all items which were deployed by a previous deployment of
+/// the profile are watched and every change made to them outside of `punktf`
+/// is reported (e.g. a manual edit of a deployed config file). Runs until
+/// interrupted.
+#[derive(Debug, Parser)]
+pub struct Watch {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Watches the deployed items (as recorded by the last deployment) for
+	/// changes made outside of `punktf`.
+	#[arg(long)]
+	pub drift: bool,
+
+	/// Interval in seconds in which the items are checked for changes.
+	#[arg(short, long, default_value_t = 2)]
+	pub interval: u64,
+
+	/// Command which is executed for each detected change.
+	///
+	/// The path of the changed item is passed with the environment variable
+	/// `PUNKTF_DRIFT_PATH` and the kind of change (`created`, `modified` or
+	/// `removed`) with `PUNKTF_DRIFT_KIND`.
+	#[arg(short, long)]
+	pub notify: Option<String>,
+}
+
+/// Rewrites all profiles which use deprecated fields to the current schema.
+///
+/// Only profiles with deprecated fields are rewritten. As the profiles are
+/// serialized again, comments and the original formatting are lost for them.
+#[derive(Debug, Parser)]
+pub struct MigrateProfiles {
+	/// The source directory where the profiles and dotfiles are located.
+	#[arg(short, long, env = super::PUNKTF_SOURCE_ENVVAR)]
+	pub source: PathBuf,
+
+	/// Only prints the deprecated fields without rewriting any profile.
+	#[arg(short, long)]
+	pub dry_run: bool,
+}
+
+/// Validates profiles and prints all problems which were found.
+///
+/// Besides the schema, this checks that all dotfiles exist, that no two
+/// dotfiles are deployed to the same target, that all extended profiles exist
+/// and that all aliases are unique.
+#[derive(Debug, Parser)]
+pub struct ValidateProfile {
+	/// The source directory where the profiles and dotfiles are located.
+	#[arg(short, long, env = super::PUNKTF_SOURCE_ENVVAR)]
+	pub source: PathBuf,
+
+	/// Name of the profile to validate.
+	///
+	/// If not given, all profiles are validated.
+	#[arg(short, long, env = super::PUNKTF_PROFILE_ENVVAR)]
+	pub profile: Option<String>,
+}
+
+/// Opens a profile or a dotfile in the editor.
+///
+/// The editor is taken from the environment variable `VISUAL` or `EDITOR`
+/// (`notepad` on windows and `vi` on all other systems if neither is set).
+#[derive(Debug, Parser)]
+pub struct Edit {
+	/// The source directory where the profiles and dotfiles are located.
+	#[arg(short, long, env = super::PUNKTF_SOURCE_ENVVAR)]
+	pub source: PathBuf,
+
+	/// Name or alias of the profile to open.
+	#[arg(env = super::PUNKTF_PROFILE_ENVVAR, required_unless_present = "dotfile")]
+	pub profile: Option<String>,
+
+	/// Opens the dotfile with this path (relative to the `dotfiles`
+	/// directory, e.g. `windows/alacritty.yml`) instead of a profile.
+	#[arg(short, long, value_name = "PATH")]
+	pub dotfile: Option<PathBuf>,
 }
 
 /// Generates man pages for this application.
@@ -182,9 +771,38 @@ pub struct Man {
 #[derive(Debug, Parser)]
 pub struct Completions {
 	/// Shell for which to generate the completions.
+	///
+	/// For `bash`, `zsh` and `fish`, the names and aliases of the profiles
+	/// (read from `--source`/`PUNKTF_SOURCE` while completing) are also
+	/// completed for `--profile`.
 	pub shell: Shell,
 
 	/// Output path for the man pages.
 	#[arg(short, long, default_value = ".")]
 	pub output: PathBuf,
 }
+
+/// Name of the hidden command which prints candidates for dynamic shell
+/// completions (related: [`Complete`]).
+pub const COMPLETE_COMMAND: &str = "__complete";
+
+/// Prints candidates for dynamic shell completions, one per line.
+///
+/// This is used by the scripts generated with `completions` and not meant to
+/// be called directly.
+#[derive(Debug, Parser)]
+#[command(name = "punktf __complete")]
+pub struct Complete {
+	/// Kind of the candidates to print.
+	pub kind: CompleteKind,
+
+	/// The source directory where the profiles and dotfiles are located.
+	#[arg(short, long, env = super::PUNKTF_SOURCE_ENVVAR)]
+	pub source: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CompleteKind {
+	/// Names and aliases of all profiles.
+	Profiles,
+}
@@ -1,14 +1,29 @@
 //! Various utility functions.
 
 use std::{
-	collections::HashMap,
+	collections::BTreeMap,
+	io::{BufRead, Write},
 	path::{Path, PathBuf},
 };
 
+use color_eyre::eyre::{eyre, Context as _};
 use color_eyre::owo_colors::OwoColorize;
 use color_eyre::Result;
 use log::Level;
-use punktf_lib::visit::deploy::deployment::{Deployment, DeploymentStatus, ItemStatus};
+use punktf_lib::profile::hook::Hook;
+use punktf_lib::profile::LayeredProfile;
+use punktf_lib::template::check::CheckReport;
+use punktf_lib::visit::conflicts::{Conflict, Winner};
+use punktf_lib::visit::deploy::ask::{MergeDecision, MergeRequest};
+use punktf_lib::visit::deploy::audit::{AuditManifest, AuditMismatch};
+use punktf_lib::visit::deploy::deployment::{
+	CommandKind, Deployment, DeploymentDelta, DeploymentStatus, ItemChange, ItemStatus, SkipReason,
+};
+use punktf_lib::visit::deploy::manifest::Manifest;
+use punktf_lib::visit::deploy::progress::Progress;
+use punktf_lib::visit::deploy::stats::DeploymentStats;
+use punktf_lib::visit::doctor::{FindingKind, Report};
+use punktf_lib::visit::explain::{Explanation, Outcome};
 
 /// Retrieves the target path for the deployment by reading the environment
 /// variable with the name determined by [`super::PUNKTF_TARGET_ENVVAR`].
@@ -16,38 +31,186 @@ pub fn get_target_path() -> Option<PathBuf> {
 	std::env::var_os(super::PUNKTF_TARGET_ENVVAR).map(|val| val.into())
 }
 
+/// Retrieves the cache directory of `punktf`.
+///
+/// The directory is determined by the environment variable with the name
+/// [`super::PUNKTF_CACHE_ENVVAR`] and falls back to the platform specific
+/// cache directory (`$XDG_CACHE_HOME/punktf`, `$HOME/.cache/punktf` or
+/// `%LOCALAPPDATA%\punktf`).
+pub fn get_cache_path() -> Option<PathBuf> {
+	if let Some(path) = std::env::var_os(super::PUNKTF_CACHE_ENVVAR) {
+		return Some(path.into());
+	}
+
+	std::env::var_os("XDG_CACHE_HOME")
+		.map(PathBuf::from)
+		.or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+		.or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+		.map(|cache| cache.join(super::BINARY_NAME))
+}
+
+/// Retrieves the state directory of `punktf`.
+///
+/// The directory is determined by the environment variable with the name
+/// [`super::PUNKTF_STATE_ENVVAR`] and falls back to the platform specific
+/// state directory (`$XDG_STATE_HOME/punktf`, `$HOME/.local/state/punktf` or
+/// `%LOCALAPPDATA%\punktf`).
+pub fn get_state_path() -> Option<PathBuf> {
+	if let Some(path) = std::env::var_os(super::PUNKTF_STATE_ENVVAR) {
+		return Some(path.into());
+	}
+
+	std::env::var_os("XDG_STATE_HOME")
+		.map(PathBuf::from)
+		.or_else(|| {
+			std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state"))
+		})
+		.or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+		.map(|state| state.join(super::BINARY_NAME))
+}
+
+/// Retrieves the path of the manifest file for the profile `profile_name`
+/// (related: [`get_state_path`]).
+pub fn get_manifest_path(profile_name: &str) -> Option<PathBuf> {
+	get_state_path().map(|state| state.join("manifests").join(format!("{profile_name}.json")))
+}
+
+/// Retrieves the command of the editor used to edit files.
+///
+/// The command is taken from the environment variable `VISUAL` or `EDITOR` and
+/// falls back to `notepad` on windows and `vi` on all other systems.
+pub fn get_editor() -> String {
+	["VISUAL", "EDITOR"]
+		.into_iter()
+		.filter_map(|name| std::env::var(name).ok())
+		.find(|editor| !editor.trim().is_empty())
+		.unwrap_or_else(|| String::from(if cfg!(windows) { "notepad" } else { "vi" }))
+}
+
+/// Opens `path` with the editor (related: [`get_editor`]) and waits until it
+/// exits.
+///
+/// The editor command may contain arguments separated by whitespace (e.g.
+/// `code --wait`).
+pub fn open_in_editor(path: &Path) -> Result<()> {
+	let editor = get_editor();
+	let mut args = editor.split_whitespace();
+	let program = args.next().ok_or_else(|| eyre!("No editor set"))?;
+
+	let status = std::process::Command::new(program)
+		.args(args)
+		.arg(path)
+		.status()
+		.wrap_err_with(|| format!("Failed to start editor `{editor}`"))?;
+
+	if status.success() {
+		Ok(())
+	} else {
+		Err(eyre!("Editor `{editor}` exited with {status}"))
+	}
+}
+
+/// Reads the manifest at `path`.
+///
+/// If no manifest exists, an empty one is returned.
+pub fn read_manifest(path: &Path) -> Result<Manifest> {
+	match std::fs::File::open(path) {
+		Ok(file) => serde_json::from_reader(file)
+			.wrap_err_with(|| format!("Failed to parse manifest `{}`", path.display())),
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Manifest::new()),
+		Err(err) => {
+			Err(err).wrap_err_with(|| format!("Failed to open manifest `{}`", path.display()))
+		}
+	}
+}
+
+/// Writes the `manifest` to `path`, creating all missing parent directories.
+pub fn write_manifest(path: &Path, manifest: &Manifest) -> Result<()> {
+	if let Some(parent) = path.parent() {
+		std::fs::create_dir_all(parent)
+			.wrap_err_with(|| format!("Failed to create directory `{}`", parent.display()))?;
+	}
+
+	let file = std::fs::File::create(path)
+		.wrap_err_with(|| format!("Failed to create manifest `{}`", path.display()))?;
+
+	serde_json::to_writer_pretty(file, manifest).wrap_err("Failed to write manifest")
+}
+
 /// Function which get's called when a merge conflict arises and the merge mode
 /// of the [dotfile](`punktf_lib::profile::dotfile::Dotfile`) is set to
 /// [MergeMode::Ask](`punktf_lib::profile::MergeMode::Ask`). The function will
 /// ask the user to accept the merge (`y`) or deny it (`n`) via the command line
-/// ([`std::io::stdout`]/[`std::io::stdin`]). If an invalid answer is given it
-/// will ask again until a valid answer is given.
-pub fn ask_user_merge(source_path: &Path, deploy_path: &Path) -> Result<bool> {
-	use std::io::Write;
-
-	let stdin = std::io::stdin();
-	let mut stdout = std::io::stdout();
+/// (`stdout`/`stdin`). With `a` all remaining merges are accepted, with `k` all
+/// remaining merges are denied and with `q` the deployment is aborted. With `d`
+/// the start of the existing and the new contents are shown before asking
+/// again. If an invalid answer is given it will ask
+/// again until a valid answer is given. If `stdin` is closed, the merge is
+/// denied.
+pub fn ask_user_merge(
+	stdin: &mut dyn BufRead,
+	stdout: &mut dyn Write,
+	request: &MergeRequest<'_>,
+) -> Result<MergeDecision> {
 	let mut line = String::new();
 
+	let mut context = Vec::new();
+	if let Some(priority) = request.priority {
+		context.push(format!("priority {}", priority.0));
+	}
+	if let Some(priority) = request.existing_priority {
+		context.push(format!(
+			"replaces an item with priority {} of this deployment",
+			priority.0
+		));
+	}
+
 	loop {
 		stdout.write_all(
 			format!(
-				"Overwrite `{}` with `{}` [y/N]: ",
-				deploy_path.display(),
-				source_path.display()
+				"Overwrite `{}` with `{}`{} [y/N/a/k/q/d]: ",
+				request.target_path.display(),
+				request.source_path.display(),
+				if context.is_empty() {
+					String::new()
+				} else {
+					format!(" ({})", context.join(", "))
+				}
 			)
 			.as_bytes(),
 		)?;
 
 		stdout.flush()?;
 
-		stdin.read_line(&mut line)?;
+		if stdin.read_line(&mut line)? == 0 {
+			return Ok(MergeDecision::Keep);
+		}
 
 		line.make_ascii_lowercase();
 
 		return match line.trim() {
-			"y" => Ok(true),
-			"n" => Ok(false),
+			"y" => Ok(MergeDecision::Overwrite),
+			"n" => Ok(MergeDecision::Keep),
+			"a" => Ok(MergeDecision::OverwriteAll),
+			"k" => Ok(MergeDecision::KeepAll),
+			"q" => Ok(MergeDecision::Abort),
+			"d" => {
+				for (title, preview) in [
+					("Existing", request.old_preview()),
+					("New", request.new_preview()),
+				] {
+					let preview = preview.unwrap_or_else(|err| format!("<{err}>"));
+					writeln!(
+						stdout,
+						"{}\n{}",
+						format!("{title}:").bold(),
+						preview.trim_end()
+					)?;
+				}
+
+				line.clear();
+				continue;
+			}
 			_ => {
 				line.clear();
 				continue;
@@ -58,16 +221,18 @@ pub fn ask_user_merge(source_path: &Path, deploy_path: &Path) -> Result<bool> {
 
 /// Outputs the given message `s`.
 ///
-/// If `print` is `false` all messages will be logged with the `log` create,
-/// otherwise `stdout` is used.
+/// If `print` is `None` all messages will be logged with the `log` create,
+/// otherwise they are written to `print`.
 ///
 /// # NOTE
 /// This will also clear the output.
 /// This is needed to reuse the same buffer `s` but log it with different log levels.
-fn output_and_clear(print: bool, s: &mut String, level: Level) {
+fn output_and_clear(print: &mut Option<&mut dyn Write>, s: &mut String, level: Level) {
 	if !s.is_empty() {
-		if print {
-			println!("{s}");
+		if let Some(print) = print {
+			if let Err(err) = writeln!(print, "{s}") {
+				log::error!("Failed to write output: {err}");
+			}
 		} else {
 			log::log!(level, "{}", s);
 		}
@@ -99,9 +264,9 @@ struct DeployCounts {
 /// items is returned.
 fn log_success<T, F>(
 	out: &mut String,
-	print: bool,
+	print: &mut Option<&mut dyn Write>,
 	item_name: &str,
-	items: &HashMap<PathBuf, T>,
+	items: &BTreeMap<PathBuf, T>,
 	fmt_fn: F,
 ) -> usize
 where
@@ -135,14 +300,14 @@ where
 /// items is returned.
 fn log_skipped<T, F>(
 	out: &mut String,
-	print: bool,
+	print: &mut Option<&mut dyn Write>,
 	item_name: &str,
-	items: &HashMap<PathBuf, T>,
+	items: &BTreeMap<PathBuf, T>,
 	fmt_fn: F,
 ) -> usize
 where
 	T: AsRef<ItemStatus>,
-	F: Fn(&Path, &T, &str) -> String,
+	F: Fn(&Path, &T, &SkipReason) -> String,
 {
 	let mut item_count = 0;
 	for (idx, (path, item, reason)) in items
@@ -177,9 +342,9 @@ where
 /// items is returned.
 fn log_failed<T, F>(
 	out: &mut String,
-	print: bool,
+	print: &mut Option<&mut dyn Write>,
 	item_name: &str,
-	items: &HashMap<PathBuf, T>,
+	items: &BTreeMap<PathBuf, T>,
 	fmt_fn: F,
 ) -> usize
 where
@@ -213,16 +378,46 @@ where
 
 /// Logs all deployed dotfiles together with the status.
 ///
-/// If `print` is `false` all messages will be logged with the `log` create,
-/// otherwise `stdout` is used.
-fn log_dotfiles(out: &mut String, deployment: &Deployment, print: bool) -> DeployCounts {
+/// If `print` is `None` all messages will be logged with the `log` create,
+/// otherwise they are written to `print`.
+fn log_dotfiles(
+	out: &mut String,
+	deployment: &Deployment,
+	print: &mut Option<&mut dyn Write>,
+) -> DeployCounts {
 	/// Name of item being processed.
 	/// Used for logging.
 	const ITEM_NAME: &str = "Dotfiles";
 
-	let files_success = log_success(out, print, ITEM_NAME, deployment.dotfiles(), |path, _| {
-		format!("\n\t{}", path.display().bright_black())
-	});
+	let files_success = log_success(
+		out,
+		print,
+		ITEM_NAME,
+		deployment.dotfiles(),
+		|path, file| {
+			if file.status().is_copied_binary() {
+				format!(
+					"\n\t{} {}",
+					path.display().bright_black(),
+					"(binary)".bright_black()
+				)
+			} else if file.status().is_forced() {
+				format!(
+					"\n\t{} {}",
+					path.display().bright_black(),
+					"(read-only overwritten)".bright_black()
+				)
+			} else if let Some(conflicts) = file.status().merge_conflict_count() {
+				format!(
+					"\n\t{} {}",
+					path.display().bright_black(),
+					format!("(merged with {conflicts} conflicts)").yellow()
+				)
+			} else {
+				format!("\n\t{}", path.display().bright_black())
+			}
+		},
+	);
 
 	let files_skipped = log_skipped(
 		out,
@@ -249,9 +444,13 @@ fn log_dotfiles(out: &mut String, deployment: &Deployment, print: bool) -> Deplo
 
 /// Logs all deployed links together with the status.
 ///
-/// If `print` is `false` all messages will be logged with the `log` create,
-/// otherwise `stdout` is used.
-fn log_links(out: &mut String, deployment: &Deployment, print: bool) -> DeployCounts {
+/// If `print` is `None` all messages will be logged with the `log` create,
+/// otherwise they are written to `print`.
+fn log_links(
+	out: &mut String,
+	deployment: &Deployment,
+	print: &mut Option<&mut dyn Write>,
+) -> DeployCounts {
 	/// Name of item being processed.
 	/// Used for logging.
 	const ITEM_NAME: &str = "Links";
@@ -309,25 +508,42 @@ fn log_links(out: &mut String, deployment: &Deployment, print: bool) -> DeployCo
 
 /// Logs the finished state of the
 /// [deployment](`punktf_lib::visit::deploy::deployment::Deployment`).
-/// If the `print` argument is `true` then stdout will be used, otherwise the
-/// crate [`log`] is used.
+/// If the `print` argument is `Some` then the output is written to it,
+/// otherwise the crate [`log`] is used.
 /// This includes amount, state and the names of the deployed
 /// [dotfiles](`punktf_lib::profile::dotfile::Dotfile`) and also the total time
 /// the deployment took to execute.
-pub fn log_deployment(deployment: &Deployment, print: bool) {
+///
+/// If `summary_only` is set, only the final counters are written.
+pub fn log_deployment(
+	deployment: &Deployment,
+	summary_only: bool,
+	mut print: Option<&mut dyn Write>,
+) {
 	let mut out = String::new();
 
+	// The status of each item is discarded if only the summary is requested
+	let mut sink = std::io::sink();
+	let mut items_print: Option<&mut dyn Write> = if summary_only {
+		Some(&mut sink)
+	} else {
+		match &mut print {
+			Some(print) => Some(&mut **print),
+			None => None,
+		}
+	};
+
 	let DeployCounts {
 		success: dotfiles_success,
 		skipped: dotfiles_skipped,
 		failed: dotfiles_failed,
-	} = log_dotfiles(&mut out, deployment, print);
+	} = log_dotfiles(&mut out, deployment, &mut items_print);
 
 	let DeployCounts {
 		success: links_success,
 		skipped: links_skipped,
 		failed: links_failed,
-	} = log_links(&mut out, deployment, print);
+	} = log_links(&mut out, deployment, &mut items_print);
 
 	match deployment.status() {
 		DeploymentStatus::Success => {
@@ -345,6 +561,52 @@ pub fn log_deployment(deployment: &Deployment, print: bool) {
 		.duration()
 		.expect("Failed to get duration from deployment");
 
+	if !deployment.blocked().is_empty() {
+		out.push_str(&format!(
+			"\n{}",
+			"Blocked commands (execution disabled):".yellow()
+		));
+
+		for blocked in deployment.blocked() {
+			out.push_str(&format!("\n\t{}: {}", blocked.kind, blocked.command));
+		}
+	}
+
+	// The captured output of the hooks is only shown in verbose mode
+	if log::log_enabled!(Level::Info) && !deployment.hooks().is_empty() {
+		out.push_str("\nExecuted hooks:");
+
+		for hook in deployment.hooks() {
+			let exit_code = hook
+				.exit_code
+				.map_or_else(|| String::from("none"), |code| code.to_string());
+			let exit_code = if hook.is_success() {
+				exit_code.green().to_string()
+			} else {
+				exit_code.red().to_string()
+			};
+
+			out.push_str(&format!(
+				"\n\t{}: {} (exit code: {}, took {:?})",
+				hook.kind, hook.command, exit_code, hook.duration
+			));
+
+			for line in hook.stdout.lines() {
+				out.push_str(&format!("\n\t\t{} {}", "stdout >".dimmed(), line));
+			}
+
+			for line in hook.stderr.lines() {
+				out.push_str(&format!("\n\t\t{} {}", "stderr >".dimmed(), line));
+			}
+		}
+	}
+
+	out.push_str(&format!("\nDeployment ID   : {}", deployment.id()));
+
+	if let Some(destdir) = deployment.destdir() {
+		out.push_str(&format!("\nStaged into     : {}", destdir.display()));
+	}
+
 	// NOTE: Needs to be indented like this to not mess up the final result.
 	let report = format!(
 		"
@@ -373,5 +635,574 @@ Links (total)   : {}",
 
 	out.push_str(&report);
 
-	output_and_clear(print, &mut out, Level::Info)
+	output_and_clear(&mut print, &mut out, Level::Info)
+}
+
+/// Logs the statistics of a deployment, including the `slowest` items which
+/// took the longest to deploy.
+///
+/// If `print` is `None` all messages will be logged with the `log` create,
+/// otherwise `print` is used.
+pub fn log_deployment_stats(
+	stats: &DeploymentStats,
+	slowest: usize,
+	mut print: Option<&mut dyn Write>,
+) {
+	let mut out = String::from("Statistics:");
+
+	out.push_str(&format!(
+		"\n\tItems           : {} succeeded, {} skipped, {} failed",
+		stats.succeeded.green(),
+		stats.skipped.yellow(),
+		stats.failed.red()
+	));
+	out.push_str(&format!(
+		"\n\tBytes written   : {}",
+		stats.total.bytes_written
+	));
+	out.push_str(&format!("\n\tTime (items)    : {:?}", stats.total.total));
+	out.push_str(&format!("\n\tTime (templates): {:?}", stats.total.template));
+	out.push_str(&format!("\n\tTime (io)       : {:?}", stats.total.io));
+
+	if slowest > 0 {
+		out.push_str("\n\tSlowest items:");
+
+		for (path, timing) in stats.slowest(slowest) {
+			out.push_str(&format!(
+				"\n\t\t{:?}: {} (templates: {:?}, io: {:?}, {} bytes)",
+				timing.total,
+				path.display(),
+				timing.template,
+				timing.io,
+				timing.bytes_written
+			));
+		}
+	}
+
+	output_and_clear(&mut print, &mut out, Level::Info)
+}
+
+/// Reads a previously written deployment status from a json or yaml file.
+///
+/// The format is determined by the file extension of `path`.
+pub fn read_deployment(path: &Path) -> Result<Deployment> {
+	let file = std::fs::File::open(path)
+		.wrap_err_with(|| format!("Failed to open `{}`", path.display()))?;
+
+	match path.extension().and_then(|ext| ext.to_str()) {
+		Some(ext) if ext.eq_ignore_ascii_case("json") => {
+			serde_json::from_reader(file).wrap_err("Failed to parse deployment from json content")
+		}
+		Some(ext) if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") => {
+			serde_yaml::from_reader(file).wrap_err("Failed to parse deployment from yaml content")
+		}
+		ext => Err(eyre!(
+			"Found unsupported file extension for deployment status (extension: {:?})",
+			ext
+		)),
+	}
+}
+
+/// Logs all changes between two deployments.
+///
+/// If `print` is `None` all messages will be logged with the `log` create,
+/// otherwise they are written to `print`.
+pub fn log_deployment_delta(delta: &DeploymentDelta, mut print: Option<&mut dyn Write>) {
+	/// Formats a single change.
+	fn fmt_change(out: &mut String, path: &Path, change: &ItemChange) {
+		let change = match change {
+			ItemChange::Added(_) => change.green().to_string(),
+			ItemChange::Removed(_) => change.yellow().to_string(),
+			ItemChange::Changed { new, .. } if new.is_failed() => change.red().to_string(),
+			ItemChange::Changed { .. } | ItemChange::ContentChanged => change.to_string(),
+		};
+
+		out.push_str(&format!("\n\t{}: {}", path.display(), change));
+	}
+
+	let mut out = String::new();
+
+	if delta.is_empty() {
+		out.push_str("No changes since the previous deployment");
+	} else {
+		for (item_name, items) in [("Dotfiles", &delta.dotfiles), ("Links", &delta.symlinks)] {
+			if items.is_empty() {
+				continue;
+			}
+
+			if !out.is_empty() {
+				out.push('\n');
+			}
+
+			out.push_str(&format!("{} ({})", item_name, "CHANGED".bold()));
+
+			for (path, change) in items {
+				fmt_change(&mut out, path, change);
+			}
+		}
+	}
+
+	output_and_clear(&mut print, &mut out, Level::Info)
+}
+
+/// Returns the hooks of `kind` collected from all profiles of the extend chain
+/// together with the index of the profile which defines them.
+///
+/// Only hooks are supported, for other kinds nothing is returned.
+pub fn profile_hooks(profile: &LayeredProfile, kind: CommandKind) -> &[(usize, Hook)] {
+	match kind {
+		CommandKind::PreHook => &profile.pre_hooks,
+		CommandKind::PostHook => &profile.post_hooks,
+		CommandKind::OnFailureHook => &profile.on_failure_hooks,
+		CommandKind::PreDotfileHook => &profile.pre_dotfile_hooks,
+		CommandKind::PostDotfileHook => &profile.post_dotfile_hooks,
+	}
+}
+
+/// Logs all hooks of the profile grouped by their kind.
+///
+/// If `print` is `None` all messages will be logged with the `log` create,
+/// otherwise they are written to `print`.
+pub fn log_hooks(profile: &LayeredProfile, mut print: Option<&mut dyn Write>) {
+	let mut out = String::new();
+
+	for kind in [
+		CommandKind::PreHook,
+		CommandKind::PostHook,
+		CommandKind::OnFailureHook,
+		CommandKind::PreDotfileHook,
+		CommandKind::PostDotfileHook,
+	] {
+		let hooks = profile_hooks(profile, kind);
+
+		if hooks.is_empty() {
+			continue;
+		}
+
+		if !out.is_empty() {
+			out.push('\n');
+		}
+
+		out.push_str(&format!("{kind}s").bold().to_string());
+
+		for (number, (idx, hook)) in hooks.iter().enumerate() {
+			let layer = profile
+				.profile_names
+				.get(*idx)
+				.map(String::as_str)
+				.unwrap_or_default();
+
+			out.push_str(&format!(
+				"\n\t{}: {} ({})",
+				number + 1,
+				hook.command().trim_end().replace('\n', "\n\t   "),
+				layer.dimmed()
+			));
+		}
+	}
+
+	if out.is_empty() {
+		out.push_str("No hooks defined");
+	} else if !profile.trusted() {
+		out.push_str(&format!(
+			"\n{}",
+			"The profile is not trusted; no hooks are executed".yellow()
+		));
+	}
+
+	output_and_clear(&mut print, &mut out, Level::Info)
+}
+
+/// Logs the status of all removed orphans.
+///
+/// If `print` is `None` all messages will be logged with the `log` create,
+/// otherwise they are written to `print`.
+pub fn log_pruned(
+	pruned: &BTreeMap<PathBuf, ItemStatus>,
+	dry_run: bool,
+	mut print: Option<&mut dyn Write>,
+) {
+	let mut out = String::new();
+
+	if pruned.is_empty() {
+		out.push_str("No orphaned files found");
+	} else {
+		let title = if dry_run { "Would remove" } else { "Removed" };
+		out.push_str(&format!("Orphans ({})", title.bold()));
+
+		for (path, status) in pruned {
+			let status = match status {
+				ItemStatus::Success
+				| ItemStatus::CopiedBinary
+				| ItemStatus::Forced
+				| ItemStatus::MergeConflicts(_) => "removed".green().to_string(),
+				ItemStatus::Skipped(reason) => format!("{}: {reason}", "skipped".yellow()),
+				ItemStatus::Failed(reason) => format!("{}: {reason}", "failed".red()),
+			};
+
+			out.push_str(&format!("\n\t{}: {}", path.display(), status));
+		}
+	}
+
+	output_and_clear(&mut print, &mut out, Level::Info)
+}
+
+/// Logs the explanation for a target path.
+///
+/// If `print` is `None` all messages will be logged with the `log` create,
+/// otherwise they are written to `print`.
+pub fn log_explanation(explanation: &Explanation, mut print: Option<&mut dyn Write>) {
+	let mut out = String::new();
+
+	out.push_str(&format!("{}", explanation.target_path.display().bold()));
+
+	if explanation.candidates.is_empty() {
+		out.push_str("\n\tNo dotfile of the profile is deployed to this path");
+	}
+
+	for (idx, candidate) in explanation.candidates.iter().enumerate() {
+		let outcome = format_outcome(&candidate.outcome, |by| {
+			explanation.candidates[by]
+				.relative_source_path
+				.display()
+				.to_string()
+		});
+
+		out.push_str(&format!(
+			"\n\n[{}] {} ({outcome})",
+			idx + 1,
+			candidate.relative_source_path.display()
+		));
+
+		if let Some(layer) = &candidate.layer {
+			out.push_str(&format!("\n\tProfile: {layer}"));
+		}
+
+		if candidate.dotfile.path != candidate.relative_source_path {
+			out.push_str(&format!(
+				"\n\tDotfile: {}",
+				candidate.dotfile.path.display()
+			));
+		}
+
+		out.push_str(&format!(
+			"\n\tPriority: {}",
+			candidate
+				.dotfile
+				.priority
+				.map(|p| p.0.to_string())
+				.unwrap_or_else(|| String::from("-"))
+		));
+		out.push_str(&format!(
+			"\n\tMerge: {:?}",
+			candidate.dotfile.merge.unwrap_or_default()
+		));
+
+		if !candidate.transformers.is_empty() {
+			out.push_str("\n\tTransformers:");
+
+			for transformer in &candidate.transformers {
+				out.push_str(&format!("\n\t\t{transformer:?}"));
+			}
+		}
+
+		if !candidate.variables.is_empty() {
+			out.push_str("\n\tVariables:");
+
+			for variable in &candidate.variables {
+				let usage = &variable.usage;
+
+				let value = match (&usage.value, &usage.source) {
+					(Some(value), Some(source)) => {
+						let layer = variable
+							.layer
+							.as_ref()
+							.map(|layer| format!(" `{layer}`"))
+							.unwrap_or_default();
+
+						format!("{value:?} (from {source:?}{layer})")
+					}
+					_ => "unresolved".red().to_string(),
+				};
+
+				out.push_str(&format!("\n\t\t{} = {value}", usage.name));
+			}
+		}
+
+		if let Some(err) = &candidate.template_error {
+			out.push_str(&format!("\n\tTemplate error: {err}"));
+		}
+	}
+
+	output_and_clear(&mut print, &mut out, Level::Info)
+}
+
+/// Formats the `outcome` of an item for the output.
+///
+/// `name` returns the name of the item with the given index, which overwrites
+/// an item.
+fn format_outcome(outcome: &Outcome, name: impl Fn(usize) -> String) -> String {
+	match outcome {
+		Outcome::Deployed => "DEPLOYED".green().to_string(),
+		Outcome::Overwritten { by } => format!("{} by `{}`", "OVERWRITTEN".yellow(), name(*by)),
+		Outcome::Lost { reason } => format!("{}: {reason}", "LOST".yellow()),
+		Outcome::Rejected { reason } => format!("{}: {reason}", "REJECTED".yellow()),
+		Outcome::Errored { reason } => format!("{}: {reason}", "ERRORED".red()),
+	}
+}
+
+/// Prints all target paths which are claimed by multiple items together with
+/// the item which wins.
+pub fn log_conflicts(conflicts: &[Conflict], mut print: Option<&mut dyn Write>) {
+	let mut out = String::new();
+
+	if conflicts.is_empty() {
+		out.push_str("No conflicting target paths found");
+	}
+
+	for (idx, conflict) in conflicts.iter().enumerate() {
+		if idx > 0 {
+			out.push_str("\n\n");
+		}
+
+		out.push_str(&format!(
+			"{} ({} claims)",
+			conflict.target_path.display().bold(),
+			conflict.claims()
+		));
+
+		for candidate in &conflict.candidates {
+			let outcome = format_outcome(&candidate.outcome, |by| {
+				conflict.candidates[by]
+					.relative_source_path
+					.display()
+					.to_string()
+			});
+			let priority = candidate
+				.dotfile
+				.priority
+				.map(|p| p.0.to_string())
+				.unwrap_or_else(|| String::from("-"));
+			let layer = candidate
+				.layer
+				.as_ref()
+				.map(|layer| format!(", profile `{layer}`"))
+				.unwrap_or_default();
+
+			out.push_str(&format!(
+				"\n\t{} (priority {priority}{layer}): {outcome}",
+				candidate.relative_source_path.display()
+			));
+		}
+
+		for link in &conflict.links {
+			let outcome = format_outcome(&link.outcome, |by| {
+				conflict.links[by].source_path.display().to_string()
+			});
+
+			out.push_str(&format!(
+				"\n\tsymlink {}: {outcome}",
+				link.source_path.display()
+			));
+		}
+
+		let winner = match conflict.winner() {
+			Some(Winner::Dotfile(candidate)) => {
+				candidate.relative_source_path.display().to_string()
+			}
+			Some(Winner::Link(link)) => format!("symlink {}", link.source_path.display()),
+			None => String::from("none"),
+		};
+
+		out.push_str(&format!("\n\tWinner: {}", winner.green()));
+	}
+
+	output_and_clear(&mut print, &mut out, Level::Info)
+}
+
+/// Logs all problems of a doctor report.
+///
+/// If `print` is `None` all messages will be logged with the `log` create,
+/// otherwise they are written to `print`.
+pub fn log_doctor_report(report: &Report, mut print: Option<&mut dyn Write>) {
+	let mut out = String::new();
+
+	if report.is_healthy() {
+		out.push_str(&"No problems found".green().to_string());
+	} else {
+		out.push_str(&format!("Problems ({})", report.findings.len().bold()));
+	}
+
+	for finding in &report.findings {
+		let problem = match &finding.kind {
+			FindingKind::BrokenLink { points_to } => {
+				format!("broken symlink to {}", points_to.display())
+			}
+			FindingKind::WrongLink { expected, actual } => format!(
+				"symlink points to {} instead of {}",
+				actual.display(),
+				expected.display()
+			),
+			FindingKind::MissingLink => String::from("symlink is not deployed"),
+			FindingKind::MissingSource { source_path } => {
+				format!("source {} does not exist", source_path.display())
+			}
+			FindingKind::Orphan => {
+				String::from("no longer part of the profile (remove with `punktf clean`)")
+			}
+			FindingKind::Permission { reason } => reason.clone(),
+			FindingKind::Errored { reason } => reason.clone(),
+		};
+
+		out.push_str(&format!(
+			"\n\t{}: {}",
+			finding.path.display(),
+			problem.red()
+		));
+	}
+
+	output_and_clear(&mut print, &mut out, Level::Info)
+}
+
+/// Logs the items of the audit `manifest` which no longer match the target.
+///
+/// If `print` is given, the output is written to it instead of the log.
+pub fn log_audit_mismatches(
+	manifest: &AuditManifest,
+	mismatches: &[AuditMismatch],
+	mut print: Option<&mut dyn Write>,
+) {
+	let mut out = String::new();
+
+	if mismatches.is_empty() {
+		out.push_str(
+			&format!(
+				"All {} items of profile `{}` match",
+				manifest.items.len(),
+				manifest.profile
+			)
+			.green()
+			.to_string(),
+		);
+	} else {
+		out.push_str(&format!("Mismatches ({})", mismatches.len().bold()));
+	}
+
+	for mismatch in mismatches {
+		out.push_str(&format!(
+			"\n\t{}: {}",
+			mismatch.target.display(),
+			mismatch.problem.to_string().red()
+		));
+	}
+
+	output_and_clear(&mut print, &mut out, Level::Info)
+}
+
+/// Logs the problems found by checking all templates of a profile.
+pub fn log_template_check(report: &CheckReport, mut print: Option<&mut dyn Write>) {
+	let mut out = String::new();
+
+	if report.is_ok() {
+		out.push_str(
+			&format!("All {} templates are valid", report.checked)
+				.green()
+				.to_string(),
+		);
+	} else {
+		out.push_str(&format!(
+			"Invalid templates ({}/{})",
+			report.problems.len().bold(),
+			report.checked
+		));
+	}
+
+	for (path, problem) in &report.problems {
+		out.push_str(&format!(
+			"\n\t{}: {}",
+			path.display(),
+			problem.to_string().red()
+		));
+	}
+
+	output_and_clear(&mut print, &mut out, Level::Info)
+}
+
+/// Renders the [progress](`Progress`) of a deployment as a progress bar.
+///
+/// If `interactive` is set, the bar is redrawn in place, otherwise one line is
+/// written for each item (e.g. for logs of CI runs).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProgressBar {
+	/// If the bar is redrawn in place.
+	interactive: bool,
+
+	/// Total number of items.
+	total: usize,
+
+	/// Number of items processed so far.
+	done: usize,
+}
+
+impl ProgressBar {
+	/// Number of characters which make up the bar.
+	const WIDTH: usize = 30;
+
+	/// Creates a new instance.
+	pub const fn new(interactive: bool) -> Self {
+		Self {
+			interactive,
+			total: 0,
+			done: 0,
+		}
+	}
+
+	/// Updates the bar with a progress event of a deployment and writes it to
+	/// `out`.
+	pub fn update(&mut self, out: &mut dyn Write, progress: Progress<'_>) {
+		let (target_path, elapsed) = match progress {
+			Progress::Started { total } => {
+				self.total = total;
+				return;
+			}
+			Progress::Item {
+				target_path,
+				elapsed,
+				done,
+				total,
+			} => {
+				self.done = done;
+				self.total = total;
+				(target_path, elapsed)
+			}
+		};
+
+		let filled = (self.done * Self::WIDTH)
+			.checked_div(self.total)
+			.unwrap_or(Self::WIDTH);
+		let line = format!(
+			"[{}{}] {}/{} ({}%) {:>9.1?} {}",
+			"#".repeat(filled),
+			"-".repeat(Self::WIDTH - filled),
+			self.done,
+			self.total,
+			(self.done * 100).checked_div(self.total).unwrap_or(100),
+			elapsed,
+			target_path.display()
+		);
+
+		// Progress output is best effort and never aborts a deployment
+		let _ = if self.interactive {
+			write!(out, "\r\x1b[2K{line}").and_then(|_| out.flush())
+		} else {
+			writeln!(out, "{line}")
+		};
+	}
+
+	/// Ends the bar, so that following output starts on a new line.
+	pub fn finish(&self, out: &mut dyn Write) {
+		if self.interactive && self.done > 0 {
+			let _ = writeln!(out);
+		}
+	}
 }
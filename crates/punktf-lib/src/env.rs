@@ -0,0 +1,120 @@
+//! Environment variables which `punktf` passes to hooks and templates.
+//!
+//! Modifying the environment of the process is not thread safe. Instead, the
+//! values are collected in a [`PunktfEnv`] and passed explicitly: Hooks
+//! receive them as environment variables of the spawned process, templates,
+//! dotfile paths and the
+//! [`ExpandVariables`](`crate::profile::transform::ExpandVariables`)
+//! transformer look them up before the environment of the process.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::profile::source::PunktfSource;
+
+/// Name of the environment variable which contains the root of the source
+/// directory.
+pub const CURRENT_SOURCE_ENVVAR: &str = "PUNKTF_CURRENT_SOURCE";
+
+/// Name of the environment variable which contains the target path of the
+/// profile.
+pub const CURRENT_TARGET_ENVVAR: &str = "PUNKTF_CURRENT_TARGET";
+
+/// Name of the environment variable which contains the name of the profile.
+pub const CURRENT_PROFILE_ENVVAR: &str = "PUNKTF_CURRENT_PROFILE";
+
+/// Environment variables which are passed to hooks and templates in addition
+/// to the environment of the process.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct PunktfEnv {
+	/// The variables by name.
+	vars: BTreeMap<String, String>,
+}
+
+impl PunktfEnv {
+	/// Creates a new, empty environment.
+	pub const fn new() -> Self {
+		Self {
+			vars: BTreeMap::new(),
+		}
+	}
+
+	/// Creates the environment for deploying the profile `profile_name` from
+	/// `source` to `target`.
+	///
+	/// It contains the following variables:
+	///
+	/// - [`CURRENT_SOURCE_ENVVAR`]
+	/// - [`CURRENT_TARGET_ENVVAR`] (only if `target` is given)
+	/// - [`CURRENT_PROFILE_ENVVAR`]
+	pub fn for_profile(source: &PunktfSource, target: Option<&Path>, profile_name: &str) -> Self {
+		let mut env = Self::new();
+
+		env.set(
+			CURRENT_SOURCE_ENVVAR,
+			source.root().to_string_lossy().into_owned(),
+		);
+		if let Some(target) = target {
+			env.set(CURRENT_TARGET_ENVVAR, target.to_string_lossy().into_owned());
+		}
+		env.set(CURRENT_PROFILE_ENVVAR, profile_name);
+
+		env
+	}
+
+	/// Sets the variable `name` to `value`.
+	pub fn set<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) -> &mut Self {
+		self.vars.insert(name.into(), value.into());
+		self
+	}
+
+	/// Gets the value of the variable `name` if it is set in this environment.
+	pub fn get(&self, name: &str) -> Option<&str> {
+		self.vars.get(name).map(String::as_str)
+	}
+
+	/// Gets the value of the variable `name` from this environment, falling
+	/// back to the environment of the process.
+	pub fn var(&self, name: &str) -> Option<String> {
+		self.get(name)
+			.map(ToString::to_string)
+			.or_else(|| std::env::var(name).ok())
+	}
+
+	/// Returns an iterator over all variables of this environment.
+	pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+		self.vars
+			.iter()
+			.map(|(name, value)| (name.as_str(), value.as_str()))
+	}
+
+	/// Checks if no variables are set.
+	pub fn is_empty(&self) -> bool {
+		self.vars.is_empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn lookup_order() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let mut env = PunktfEnv::new();
+		env.set("PUNKTF_TEST_ENV_LOOKUP", "explicit");
+
+		assert_eq!(env.get("PUNKTF_TEST_ENV_LOOKUP"), Some("explicit"));
+		assert_eq!(
+			env.var("PUNKTF_TEST_ENV_LOOKUP").as_deref(),
+			Some("explicit")
+		);
+		assert_eq!(std::env::var_os("PUNKTF_TEST_ENV_LOOKUP"), None);
+
+		assert_eq!(env.get("PATH"), None);
+		assert_eq!(env.var("PATH"), std::env::var("PATH").ok());
+
+		Ok(())
+	}
+}
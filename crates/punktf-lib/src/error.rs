@@ -0,0 +1,235 @@
+//! The error type of the library.
+//!
+//! Most fallible functions of the library return [`Error`](`enum@Error`), so
+//! that users of the library can match on the kind of a failure instead of
+//! only displaying it. Deployments report errors of single items in the
+//! [`Deployment`](`crate::visit::deploy::deployment::Deployment`) and only
+//! return a [`DeployError`](`crate::visit::deploy::DeployError`) if the
+//! deployment as a whole is aborted.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+
+use thiserror::Error;
+
+use crate::profile::hook::HookError;
+use crate::profile::source::SourceError;
+use crate::profile::validate::ProfileErrors;
+use crate::template::engine::EngineKind;
+
+/// A result with [`Error`](`enum@Error`) as the default error type.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// An error returned by functions provided by users of the library (e.g. a
+/// [`MergeHandler`](`crate::punktf::MergeHandler`)).
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// An error which can occur while reading profiles, resolving templates or
+/// running hooks.
+#[derive(Error, Debug)]
+pub enum Error {
+	/// No profile with the given name or alias exists.
+	#[error("No profile found for name `{name}`{}", did_you_mean(suggestions))]
+	ProfileNotFound {
+		/// The name which was looked up.
+		name: String,
+
+		/// Existing names and aliases which are close to `name`, closest
+		/// first.
+		suggestions: Vec<String>,
+	},
+
+	/// Two profiles use the same name or alias.
+	#[error(
+		"The profile name `{name}` of `{}` is already taken by `{}`",
+		path.display(),
+		other.display()
+	)]
+	DuplicateProfileName {
+		/// The name or alias which is used twice.
+		name: String,
+
+		/// Path of the profile which uses the name.
+		path: PathBuf,
+
+		/// Path of the profile which already took the name.
+		other: PathBuf,
+	},
+
+	/// A profile extends itself, directly or by one of the profiles it
+	/// extends.
+	#[error(
+		"Circular dependency detected while resolving profile `{name}` (extend chain: {})",
+		chain.join(" -> ")
+	)]
+	CyclicExtends {
+		/// The profile which was extended again.
+		name: String,
+
+		/// The names of all profiles from the first one to the one which
+		/// extended `name` again.
+		chain: Vec<String>,
+	},
+
+	/// One or more profiles are invalid. All problems which were found are
+	/// reported together.
+	#[error(transparent)]
+	Profile(#[from] ProfileErrors),
+
+	/// A directory of the source can not be used.
+	#[error(transparent)]
+	Source(#[from] SourceError),
+
+	/// A file has an extension for which no (de-)serializer is available.
+	#[error("Found unsupported file extension for `{}`", path.display())]
+	UnsupportedFormat {
+		/// Path to the file.
+		path: PathBuf,
+	},
+
+	/// A file could not be deserialized.
+	#[error("Failed to parse `{}`: {reason}", path.display())]
+	Parse {
+		/// Path to the file.
+		path: PathBuf,
+
+		/// Description of the problem.
+		reason: String,
+	},
+
+	/// A value could not be serialized for the file at `path`.
+	#[error("Failed to serialize `{}`: {reason}", path.display())]
+	Serialize {
+		/// Path to the file.
+		path: PathBuf,
+
+		/// Description of the problem.
+		reason: String,
+	},
+
+	/// A template contains invalid syntax.
+	///
+	/// The diagnostics with the referenced lines are logged as well.
+	#[error("Invalid template `{origin}`: {}", messages.join("; "))]
+	TemplateSyntax {
+		/// Where the template came from (e.g. the path of the file).
+		origin: String,
+
+		/// Messages of all errors which were found.
+		messages: Vec<String>,
+	},
+
+	/// A template could not be resolved (e.g. because of a missing
+	/// variable).
+	///
+	/// The diagnostics with the referenced lines are logged as well.
+	#[error("Failed to resolve template `{origin}`: {}", messages.join("; "))]
+	TemplateResolve {
+		/// Where the template came from (e.g. the path of the file).
+		origin: String,
+
+		/// Messages of all errors which occurred.
+		messages: Vec<String>,
+	},
+
+	/// A template engine was selected which was not enabled when `punktf`
+	/// was built (related: [`EngineKind`](`crate::template::engine::EngineKind`)).
+	#[error("Template engine `{0}` is not available (enable the cargo feature `engine-{0}`)")]
+	EngineUnavailable(EngineKind),
+
+	/// A content transformer failed.
+	#[error("Failed to transform content: {0}")]
+	Transform(String),
+
+	/// A hook failed to execute.
+	#[error(transparent)]
+	Hook(#[from] HookError),
+
+	/// No machines file exists in the source directory (related:
+	/// [`select`](`crate::profile::select::select`)).
+	#[error(
+		"No machines file found in `{}` (expected `machines.yaml` or `machines.json`)",
+		root.display()
+	)]
+	NoMachinesFile {
+		/// The root of the source.
+		root: PathBuf,
+	},
+
+	/// No entry of the machines file matches the current machine.
+	#[error(
+		"No entry in `{}` matches the current machine (hostname: `{hostname}`; os: `{os}`)",
+		path.display()
+	)]
+	NoMachineMatched {
+		/// Path to the machines file.
+		path: PathBuf,
+
+		/// Hostname of the current machine.
+		hostname: String,
+
+		/// Operating system of the current machine.
+		os: String,
+	},
+
+	/// An external command (e.g. `git init`) exited with a failure status.
+	#[error("Command `{command}` failed ({status})")]
+	Command {
+		/// The command which was run.
+		command: String,
+
+		/// The exit status of the command.
+		status: ExitStatus,
+	},
+
+	/// A required option was not given (e.g. to a
+	/// [`PunktfBuilder`](`crate::PunktfBuilder`)).
+	#[error("No {0} given")]
+	MissingOption(&'static str),
+
+	/// A file or directory could not be accessed.
+	#[error("Failed to access `{}`", path.display())]
+	Io {
+		/// Path to the file or directory.
+		path: PathBuf,
+
+		/// The underlying error.
+		#[source]
+		source: io::Error,
+	},
+}
+
+/// Formats the `suggestions` for an unknown name.
+fn did_you_mean(suggestions: &[String]) -> String {
+	match suggestions {
+		[] => String::new(),
+		[suggestion] => format!("; did you mean `{suggestion}`?"),
+		[suggestions @ .., last] => format!(
+			"; did you mean {} or `{last}`?",
+			suggestions
+				.iter()
+				.map(|suggestion| format!("`{suggestion}`"))
+				.collect::<Vec<_>>()
+				.join(", ")
+		),
+	}
+}
+
+impl Error {
+	/// Creates an [`Error::Io`] for `path`.
+	pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+		Self::Io {
+			path: path.into(),
+			source,
+		}
+	}
+
+	/// Creates an [`Error::Parse`] for `path`.
+	pub(crate) fn parse(path: &Path, reason: impl ToString) -> Self {
+		Self::Parse {
+			path: path.to_path_buf(),
+			reason: reason.to_string(),
+		}
+	}
+}
@@ -32,14 +32,25 @@
 
 //! This is the library powering `punktf`, a cross-platform multi-target dotfiles manager.
 
+pub mod env;
+pub mod error;
 pub mod profile;
+pub mod punktf;
+pub mod temp;
 pub mod template;
 pub mod visit;
 
+pub use crate::error::{Error, Result};
+pub use crate::punktf::{Punktf, PunktfBuilder};
+
 #[cfg(test)]
 mod tests {
+	use std::ops::Deref;
+	use std::path::Path;
 	use std::sync::Once;
 
+	use crate::temp::TempPath;
+
 	static SETUP_GATE: Once = Once::new();
 
 	pub fn setup_test_env() {
@@ -54,4 +65,45 @@ mod tests {
 			color_eyre::install().expect("color_eyre setup to not fail");
 		})
 	}
+
+	/// A uniquely named directory for a single test (related: [`test_dir`]).
+	///
+	/// The directory is removed once this is dropped, even if the test
+	/// panics.
+	#[derive(Debug)]
+	pub struct TestDir(TempPath);
+
+	impl Deref for TestDir {
+		type Target = Path;
+
+		fn deref(&self) -> &Self::Target {
+			self.0.path()
+		}
+	}
+
+	impl AsRef<Path> for TestDir {
+		fn as_ref(&self) -> &Path {
+			self.0.path()
+		}
+	}
+
+	/// Creates a new empty directory for a test.
+	///
+	/// Tests run concurrently, so each directory gets a unique name.
+	pub fn test_dir() -> TestDir {
+		let path = std::env::temp_dir().join(format!("punktf-test-{}", uuid::Uuid::new_v4()));
+
+		TestDir(TempPath::create_dir(path).expect("Test directory to be created"))
+	}
+
+	/// Asserts that no temporary path (related: [`crate::temp::tracked`])
+	/// below `dir` is still alive.
+	pub fn assert_no_temp_leaks(dir: &Path) {
+		let leaked: Vec<_> = crate::temp::tracked()
+			.into_iter()
+			.filter(|path| path != dir && path.starts_with(dir))
+			.collect();
+
+		assert!(leaked.is_empty(), "Leaked temporary paths: {leaked:?}");
+	}
 }
@@ -0,0 +1,357 @@
+//! Extraction of archives which are deployed as dotfiles (related:
+//! [`Dotfile::extract`](`crate::profile::dotfile::Dotfile::extract`)).
+//!
+//! Supported are plain tar archives, gzip compressed tar archives and zip
+//! archives with stored or deflated entries. Only regular files and
+//! directories are extracted; links and other special entries are skipped.
+//!
+//! Entry paths must be relative and must not contain `..` components, so
+//! that nothing is ever written outside of the destination directory. The
+//! total size of the extracted data is limited by [`MAX_EXTRACTED_SIZE`].
+
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// Maximum number of bytes which are extracted from a single archive.
+///
+/// Protects against small archives which decompress to huge amounts of data
+/// (e.g. "zip bombs").
+pub const MAX_EXTRACTED_SIZE: u64 = 1 << 30;
+
+/// Formats of archives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ArchiveFormat {
+	/// Uncompressed tar archive (extension `.tar`).
+	Tar,
+
+	/// Gzip compressed tar archive (extensions `.tar.gz` and `.tgz`).
+	TarGz,
+
+	/// Zip archive (extension `.zip`).
+	Zip,
+}
+
+impl ArchiveFormat {
+	/// Determines the format of the archive at `path` by its extension.
+	pub fn from_path(path: &Path) -> Option<Self> {
+		let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+
+		if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+			Some(Self::TarGz)
+		} else if name.ends_with(".tar") {
+			Some(Self::Tar)
+		} else if name.ends_with(".zip") {
+			Some(Self::Zip)
+		} else {
+			None
+		}
+	}
+}
+
+/// Extracts the archive `data` of the given `format` into the directory
+/// `dest`.
+///
+/// # Errors
+///
+/// An error is returned if the archive is malformed (including checksum
+/// mismatches), uses unsupported features (e.g. encrypted zip entries),
+/// extracts to more than [`MAX_EXTRACTED_SIZE`] bytes or contains an entry
+/// whose path would escape `dest`. Errors from writing the extracted items
+/// are passed through.
+pub fn extract(format: ArchiveFormat, data: &[u8], dest: &Path) -> io::Result<()> {
+	extract_limited(format, data, dest, MAX_EXTRACTED_SIZE)
+}
+
+/// Extracts the archive `data` into `dest` and fails once more than `limit`
+/// bytes were extracted.
+fn extract_limited(format: ArchiveFormat, data: &[u8], dest: &Path, limit: u64) -> io::Result<()> {
+	match format {
+		ArchiveFormat::Tar => extract_tar(Limited::new(data, limit), dest),
+		ArchiveFormat::TarGz => extract_tar(
+			Limited::new(flate2::read::GzDecoder::new(data), limit),
+			dest,
+		),
+		ArchiveFormat::Zip => extract_zip(data, dest, limit),
+	}
+}
+
+/// Creates an error of kind [`io::ErrorKind::InvalidData`].
+fn invalid(msg: impl Into<String>) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// A reader which fails once more than a given number of bytes were read.
+struct Limited<R> {
+	/// The wrapped reader.
+	inner: R,
+
+	/// Number of bytes which may still be read.
+	remaining: u64,
+}
+
+impl<R> Limited<R> {
+	/// Creates a new reader which reads at most `limit` bytes from `inner`.
+	const fn new(inner: R, limit: u64) -> Self {
+		Self {
+			inner,
+			remaining: limit,
+		}
+	}
+}
+
+impl<R: Read> Read for Limited<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let read = self.inner.read(buf)?;
+
+		self.remaining = self
+			.remaining
+			.checked_sub(read as u64)
+			.ok_or_else(|| invalid("Archive exceeds the maximum extracted size"))?;
+
+		Ok(read)
+	}
+}
+
+/// Converts the path of an archive entry into a relative path.
+///
+/// Returns `None` if the path is empty (e.g. `./`).
+fn entry_path(name: &Path) -> io::Result<Option<PathBuf>> {
+	let mut path = PathBuf::new();
+
+	for component in name.components() {
+		match component {
+			Component::Normal(part) => path.push(part),
+			Component::CurDir => {}
+			Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+				return Err(invalid(format!(
+					"Archive entry `{}` points outside of the destination",
+					name.display()
+				)));
+			}
+		}
+	}
+
+	Ok((!path.as_os_str().is_empty()).then_some(path))
+}
+
+/// Writes an extracted file with the `content` read from the archive to
+/// `dest` and applies the unix permissions given by `mode` (if any).
+fn write_file(
+	dest: &Path,
+	path: &Path,
+	content: &mut dyn Read,
+	mode: Option<u32>,
+) -> io::Result<()> {
+	let path = dest.join(path);
+
+	if let Some(parent) = path.parent() {
+		std::fs::create_dir_all(parent)?;
+	}
+
+	io::copy(content, &mut std::fs::File::create(&path)?)?;
+
+	cfg_if::cfg_if! {
+		if #[cfg(unix)] {
+			use std::os::unix::fs::PermissionsExt;
+
+			if let Some(mode) = mode {
+				std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode & 0o777))?;
+			}
+		} else {
+			let _ = mode;
+		}
+	}
+
+	Ok(())
+}
+
+/// Extracts a tar archive read from `reader` into `dest`.
+fn extract_tar<R: Read>(reader: R, dest: &Path) -> io::Result<()> {
+	let mut archive = tar::Archive::new(reader);
+
+	for entry in archive.entries()? {
+		let mut entry = entry?;
+		let name = entry.path()?.into_owned();
+		let kind = entry.header().entry_type();
+
+		if kind.is_file() {
+			if let Some(path) = entry_path(&name)? {
+				let mode = entry.header().mode().ok();
+				write_file(dest, &path, &mut entry, mode)?;
+			}
+		} else if kind.is_dir() {
+			if let Some(path) = entry_path(&name)? {
+				std::fs::create_dir_all(dest.join(path))?;
+			}
+		} else if !kind.is_pax_global_extensions() {
+			log::warn!(
+				"Skipping archive entry `{}` - Unsupported entry type `{}`",
+				name.display(),
+				char::from(kind.as_byte())
+			);
+		}
+	}
+
+	Ok(())
+}
+
+/// Converts an error of the zip crate into an [`io::Error`].
+fn zip_error(err: zip::result::ZipError) -> io::Error {
+	match err {
+		zip::result::ZipError::Io(err) => err,
+		err => invalid(err.to_string()),
+	}
+}
+
+/// Extracts a zip archive into `dest` and fails once more than `limit` bytes
+/// were extracted.
+fn extract_zip(data: &[u8], dest: &Path, limit: u64) -> io::Result<()> {
+	let mut archive = zip::ZipArchive::new(io::Cursor::new(data)).map_err(zip_error)?;
+	let mut remaining = limit;
+
+	for idx in 0..archive.len() {
+		let file = archive.by_index(idx).map_err(zip_error)?;
+
+		let Some(path) = entry_path(Path::new(file.name()))? else {
+			continue;
+		};
+
+		if file.is_dir() {
+			std::fs::create_dir_all(dest.join(path))?;
+			continue;
+		}
+
+		let mode = file.unix_mode();
+		let mut content = Limited::new(file, remaining);
+		write_file(dest, &path, &mut content, mode)?;
+		remaining = content.remaining;
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use std::io::Write;
+
+	use super::*;
+
+	/// Creates a tar archive which contains the given files.
+	///
+	/// The paths are written as is, so that invalid paths can be tested.
+	pub fn tar(files: &[(&str, &str)]) -> Vec<u8> {
+		let mut builder = tar::Builder::new(Vec::new());
+
+		for (name, content) in files {
+			let mut header = tar::Header::new_ustar();
+			header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+			header.set_mode(0o644);
+			header.set_size(content.len() as u64);
+			header.set_entry_type(tar::EntryType::Regular);
+			header.set_cksum();
+
+			builder
+				.append(&header, content.as_bytes())
+				.expect("Writing to memory to succeed");
+		}
+
+		builder.into_inner().expect("Writing to memory to succeed")
+	}
+
+	/// Compresses `data` with gzip.
+	fn gzip(data: &[u8]) -> Vec<u8> {
+		let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+		encoder
+			.write_all(data)
+			.expect("Writing to memory to succeed");
+		encoder.finish().expect("Writing to memory to succeed")
+	}
+
+	/// Creates a zip archive which contains the given deflated files.
+	fn zip(files: &[(&str, &str)]) -> Vec<u8> {
+		let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+
+		for (name, content) in files {
+			writer
+				.start_file(*name, zip::write::SimpleFileOptions::default())
+				.expect("Writing to memory to succeed");
+			writer
+				.write_all(content.as_bytes())
+				.expect("Writing to memory to succeed");
+		}
+
+		writer
+			.finish()
+			.expect("Writing to memory to succeed")
+			.into_inner()
+	}
+
+	#[test]
+	fn format_from_path() {
+		crate::tests::setup_test_env();
+
+		assert_eq!(
+			ArchiveFormat::from_path(Path::new("fonts.tar")),
+			Some(ArchiveFormat::Tar)
+		);
+		assert_eq!(
+			ArchiveFormat::from_path(Path::new("fonts.TAR.GZ")),
+			Some(ArchiveFormat::TarGz)
+		);
+		assert_eq!(
+			ArchiveFormat::from_path(Path::new("fonts.tgz")),
+			Some(ArchiveFormat::TarGz)
+		);
+		assert_eq!(
+			ArchiveFormat::from_path(Path::new("fonts.zip")),
+			Some(ArchiveFormat::Zip)
+		);
+		assert_eq!(ArchiveFormat::from_path(Path::new("fonts.gz")), None);
+	}
+
+	#[test]
+	fn extract_archives() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+
+		let files = [("a.txt", "Hello"), ("./nested/b.txt", "World")];
+
+		for (format, data) in [
+			(ArchiveFormat::Tar, tar(&files)),
+			(ArchiveFormat::TarGz, gzip(&tar(&files))),
+			(ArchiveFormat::Zip, zip(&files)),
+		] {
+			let dest = dir.join(format!("{format:?}"));
+			extract(format, &data, &dest)?;
+
+			assert_eq!(std::fs::read_to_string(dest.join("a.txt"))?, "Hello");
+			assert_eq!(std::fs::read_to_string(dest.join("nested/b.txt"))?, "World");
+		}
+
+		for name in ["../evil.txt", "/evil.txt", "a/../../evil.txt"] {
+			let dest = dir.join("evil");
+			let err = extract(ArchiveFormat::Tar, &tar(&[(name, "")]), &dest)
+				.expect_err("Entry outside of destination to be rejected");
+
+			assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+			assert!(!dir.join("evil.txt").exists());
+		}
+
+		for (format, data) in [
+			(ArchiveFormat::Tar, tar(&files)),
+			(ArchiveFormat::TarGz, gzip(&tar(&files))),
+			(ArchiveFormat::Zip, zip(&files)),
+		] {
+			let err = extract_limited(format, &data, &dir.join("limit"), 8)
+				.expect_err("Archive larger than the limit to be rejected");
+
+			assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+		}
+
+		assert!(extract(ArchiveFormat::Zip, b"not a zip", &dir).is_err());
+		assert!(extract(ArchiveFormat::TarGz, b"not a gzip", &dir).is_err());
+
+		Ok(())
+	}
+}
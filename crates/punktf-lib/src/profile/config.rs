@@ -0,0 +1,153 @@
+//! Settings which apply to all [profiles](`crate::profile::Profile`) of a
+//! [source directory](`crate::profile::source::PunktfSource`).
+//!
+//! The settings are read from a `punktf` file (`punktf.yaml`, `punktf.yml` or
+//! `punktf.json`) in the root of the source directory:
+//!
+//! ```yaml
+//! # Items of directory dotfiles which are never deployed.
+//! ignore:
+//!   - README.md
+//!   - .git
+//!   - .DS_Store
+//!   - "*.swp"
+//!   - lua/scratch/*
+//! ```
+
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// File stem of the file which contains the [`SourceConfig`].
+pub const CONFIG_FILE_STEM: &str = "punktf";
+
+/// A glob pattern for items which are ignored.
+///
+/// Supported are `*` (any number of characters except `/`), `**` (any number
+/// of characters) and `?` (a single character except `/`).
+///
+/// A pattern without a `/` is matched against the file name of an item, a
+/// pattern with a `/` against the path of the item relative to the directory
+/// dotfile it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct IgnorePattern(pub String);
+
+impl IgnorePattern {
+	/// Checks if the item at the `relative` path matches this pattern.
+	pub fn matches(&self, relative: &Path) -> bool {
+		let pattern = self.0.trim_start_matches('/');
+
+		if pattern.contains('/') {
+			let relative = relative
+				.components()
+				.filter_map(|component| match component {
+					Component::Normal(name) => Some(name.to_string_lossy()),
+					_ => None,
+				})
+				.collect::<Vec<_>>()
+				.join("/");
+
+			glob_match(pattern.as_bytes(), relative.as_bytes())
+		} else {
+			relative.file_name().is_some_and(|name| {
+				glob_match(pattern.as_bytes(), name.to_string_lossy().as_bytes())
+			})
+		}
+	}
+}
+
+/// Matches `text` against the glob `pattern`.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+	match pattern {
+		[] => text.is_empty(),
+		[b'*', b'*', rest @ ..] => (0..=text.len()).any(|idx| glob_match(rest, &text[idx..])),
+		[b'*', rest @ ..] => {
+			// A single `*` does not cross directory boundaries.
+			let end = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+			(0..=end).any(|idx| glob_match(rest, &text[idx..]))
+		}
+		[b'?', rest @ ..] => {
+			matches!(text, [c, text @ ..] if *c != b'/' && glob_match(rest, text))
+		}
+		[c, rest @ ..] => matches!(text, [t, text @ ..] if t == c && glob_match(rest, text)),
+	}
+}
+
+/// Settings which apply to all profiles of a source directory.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SourceConfig {
+	/// Items of directory dotfiles which are never deployed.
+	pub ignore: Vec<IgnorePattern>,
+}
+
+impl SourceConfig {
+	/// Creates a new instance from the file at `path`.
+	///
+	/// This function will try to guess the correct deserializer by the file
+	/// extension of `path`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file does not exist or could not be read.
+	/// An error is returned if the file extension is unknown or missing.
+	pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+		let path = path.as_ref();
+
+		let content = std::fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+
+		super::deserialize(path, &content)
+	}
+
+	/// Finds the path of the config file in the source directory `root`.
+	pub fn find(root: &Path) -> Option<PathBuf> {
+		["yaml", "yml", "json"]
+			.into_iter()
+			.map(|extension| root.join(CONFIG_FILE_STEM).with_extension(extension))
+			.find(|path| path.is_file())
+	}
+
+	/// Checks if the item at the `relative` path (relative to the directory
+	/// dotfile it belongs to) is ignored.
+	pub fn is_ignored(&self, relative: &Path) -> bool {
+		self.ignore.iter().any(|pattern| pattern.matches(relative))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ignore_patterns() {
+		crate::tests::setup_test_env();
+
+		let config = SourceConfig {
+			ignore: [
+				"README.md",
+				".git",
+				"*.swp",
+				"lua/*/scratch.lua",
+				"**/*.bak",
+			]
+			.into_iter()
+			.map(|pattern| IgnorePattern(pattern.into()))
+			.collect(),
+		};
+
+		assert!(config.is_ignored(Path::new("README.md")));
+		assert!(config.is_ignored(Path::new("plugins/README.md")));
+		assert!(config.is_ignored(Path::new(".git")));
+		assert!(config.is_ignored(Path::new("lua/.init.lua.swp")));
+		assert!(config.is_ignored(Path::new("lua/user/scratch.lua")));
+		assert!(config.is_ignored(Path::new("a/b/c.bak")));
+
+		assert!(!config.is_ignored(Path::new("README.md.tmpl")));
+		assert!(!config.is_ignored(Path::new(".gitignore")));
+		assert!(!config.is_ignored(Path::new("lua/a/b/scratch.lua")));
+		assert!(!config.is_ignored(Path::new("init.lua")));
+	}
+}
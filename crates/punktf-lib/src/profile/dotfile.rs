@@ -3,14 +3,21 @@
 
 use serde::{Deserialize, Serialize};
 
-use crate::profile::{transform::ContentTransformer, variables::Variables, MergeMode, Priority};
+use crate::profile::{
+	transform::ContentTransformer, variables::Variables, BinaryMode, DeployStrategy, MergeMode,
+	Priority, WindowsAcl,
+};
 
+use crate::template::engine::EngineKind;
+use crate::template::mode::TemplateMode;
+
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 /// A dotfile represents a single item to be deployed by `punktf`. This can
 /// either be a single file or a directory. This struct holds attributes to
 /// control how the item will be deployed.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Dotfile {
 	/// Relative path inside the
@@ -28,11 +35,40 @@ pub struct Dotfile {
 	#[serde(skip_serializing_if = "Option::is_none", default)]
 	pub rename: Option<PathBuf>,
 
+	/// Alternative names/paths like [`Dotfile::rename`] which are only used
+	/// on specific machines (e.g. `{windows: _vimrc, linux: .vimrc}`).
+	///
+	/// A key is either an operating system (e.g. `linux`, `macos` or
+	/// `windows`; related: [`std::env::consts::OS`]), an operating system
+	/// family (`unix` or `windows`; related: [`std::env::consts::FAMILY`]) or
+	/// a condition which is evaluated like [`Dotfile::enabled`]. Operating
+	/// systems and families are compared case-insensitive. An operating system
+	/// takes precedence over a family, which takes precedence over conditions.
+	/// Of multiple true conditions, the first in alphabetical order is used.
+	///
+	/// If no key matches, [`Dotfile::rename`] is used.
+	#[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+	pub rename_map: BTreeMap<String, PathBuf>,
+
 	/// Alternative absolute deploy target path. This will be used instead of
 	/// [`Profile::target`](`crate::profile::Profile::target`) when deploying.
-	#[serde(alias = "target", skip_serializing_if = "Option::is_none", default)]
+	///
+	/// The deprecated form `target: { Path: <path> }` is accepted as well.
+	#[serde(
+		alias = "target",
+		deserialize_with = "crate::profile::migrate::deserialize_target",
+		skip_serializing_if = "Option::is_none",
+		default
+	)]
 	pub overwrite_target: Option<PathBuf>,
 
+	/// Name of the target root path to deploy to, as defined in
+	/// [`Profile::targets`](`crate::profile::Profile::targets`). This will be
+	/// used instead of [`Profile::target`](`crate::profile::Profile::target`)
+	/// when deploying. [`Dotfile::overwrite_target`] takes precedence.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub target_name: Option<String>,
+
 	/// Priority of the dotfile. Dotfiles with higher priority as others are
 	/// allowed to overwrite an already deployed dotfile if the
 	/// [Dotfile::merge](`crate::profile::dotfile::Dotfile::merge`) allows for it.
@@ -42,9 +78,21 @@ pub struct Dotfile {
 	/// Variables specifically defined for this dotfile. These variables will
 	/// take precedence over the ones defined in
 	/// [`Profile::variables`](`crate::profile::Profile::variables`).
-	#[serde(skip_serializing_if = "Option::is_none", default)]
+	///
+	/// The deprecated name `env` is accepted as well.
+	#[serde(alias = "env", skip_serializing_if = "Option::is_none", default)]
 	pub variables: Option<Variables>,
 
+	/// Path of a file (`.yaml`, `.yml` or `.json`) which contains additional
+	/// variables for this dotfile. Relative paths are resolved against the
+	/// root of the source directory.
+	///
+	/// The file is loaded when the profile is resolved and merged into
+	/// [`Dotfile::variables`], where variables defined inline take
+	/// precedence.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub variables_file: Option<PathBuf>,
+
 	/// Content transform defined for the dotfile. These variables will take
 	/// precedence over the ones defined in
 	/// [`profile::Profile::transformers`](`crate::profile::Profile::transformers`).
@@ -60,11 +108,171 @@ pub struct Dotfile {
 	/// no template processing will be done.
 	#[serde(skip_serializing_if = "Option::is_none", default)]
 	pub template: Option<bool>,
+
+	/// Defines if blocks of the template which can not be parsed or resolved
+	/// (e.g. the syntax of another template engine) are errors or are copied
+	/// as is.
+	///
+	/// Defaults to [`TemplateMode::Strict`].
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub template_mode: Option<TemplateMode>,
+
+	/// Engine which resolves the template of the dotfile. Engines other than
+	/// [`EngineKind::Builtin`] require the corresponding cargo feature.
+	///
+	/// Defaults to [`EngineKind::Builtin`].
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub engine: Option<EngineKind>,
+
+	/// Defines if the contents are treated as binary data, which are copied
+	/// without template processing or content transformation. Takes
+	/// precedence over [`Dotfile::template`].
+	///
+	/// Defaults to [`BinaryMode::Auto`].
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub binary: Option<BinaryMode>,
+
+	/// Strategy which is used to deploy the dotfile to the target location.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub strategy: Option<DeployStrategy>,
+
+	/// Access control list operation which is applied to all deployed files
+	/// of this dotfile. Only supported on windows.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub windows_acl: Option<WindowsAcl>,
+
+	/// Indicates if the dotfile is stored encrypted in the source directory.
+	/// If this is `true` the contents are decrypted in memory before they are
+	/// processed (related: [`crate::profile::encryption`]).
+	///
+	/// The extension of the encrypted file (e.g. `.age`) is removed from the
+	/// target path, unless [`Dotfile::rename`] is given.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub encrypted: Option<bool>,
+
+	/// Preserves the permission bits, ownership and extended attributes of an
+	/// already existing target file when it is overwritten. Takes precedence
+	/// over
+	/// [`profile::Profile::preserve_attributes`](`crate::profile::Profile::preserve_attributes`).
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub preserve_attributes: Option<bool>,
+
+	/// Follows symlinks inside the source directory of the dotfile instead of
+	/// rejecting them. Directory links which would lead to a cycle are
+	/// reported as errors. Takes precedence over
+	/// [`profile::Profile::follow_source_symlinks`](`crate::profile::Profile::follow_source_symlinks`).
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub follow_source_symlinks: Option<bool>,
+
+	/// Tags which group the dotfile (e.g. `shell` or `editor`). These can be
+	/// used to only deploy a part of a profile (related:
+	/// [`TagFilter`](`crate::visit::TagFilter`)).
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub tags: Vec<String>,
+
+	/// Defines if the dotfile is deployed. A disabled dotfile is reported as
+	/// rejected instead.
+	///
+	/// Defaults to `true`.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub enabled: Option<Enabled>,
+
+	/// Indicates if the dotfile is an archive (`.tar`, `.tar.gz`, `.tgz` or
+	/// `.zip`) whose contents are deployed instead of the archive itself
+	/// (related: [`archive`](`crate::profile::archive`)).
+	///
+	/// The contents are extracted into the directory containing the target
+	/// path of the dotfile, or into [`Dotfile::rename`] if set. Each
+	/// extracted file is treated like a child of a directory dotfile and is
+	/// no template unless [`Dotfile::template`] is set explicitly.
+	///
+	/// Defaults to `false`.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub extract: Option<bool>,
+}
+
+/// Defines if a [`Dotfile`] is deployed (related: [`Dotfile::enabled`]).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Enabled {
+	/// A fixed value.
+	Bool(bool),
+
+	/// A condition which is evaluated like the condition of an `if` block of
+	/// a [template](`crate::template`) (e.g. `{{PUNKTF_OS}} == "linux"`). The
+	/// same variables as for the contents of the dotfile are available.
+	Expr(String),
+}
+
+/// Defaults for fields of all dotfiles of a profile (related:
+/// [`Profile::defaults`](`crate::profile::Profile::defaults`)).
+///
+/// They are only applied to dotfiles which do not specify the field
+/// themselves.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DotfileDefaults {
+	/// Default for [`Dotfile::merge`].
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub merge: Option<MergeMode>,
+
+	/// Default for [`Dotfile::template`].
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub template: Option<bool>,
+
+	/// Default for [`Dotfile::priority`].
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub priority: Option<Priority>,
+}
+
+impl DotfileDefaults {
+	/// Fills all fields which are not set with the ones from `other`.
+	pub fn or(self, other: Self) -> Self {
+		Self {
+			merge: self.merge.or(other.merge),
+			template: self.template.or(other.template),
+			priority: self.priority.or(other.priority),
+		}
+	}
+
+	/// Sets all fields of `dotfile` which are not set to the defaults.
+	pub fn apply(&self, dotfile: &mut Dotfile) {
+		dotfile.merge = dotfile.merge.or(self.merge);
+		dotfile.template = dotfile.template.or(self.template);
+		dotfile.priority = dotfile.priority.or(self.priority);
+	}
 }
 
 impl Dotfile {
 	/// Checks if the dotfile is considered to be a template.
+	///
+	/// Dotfiles which are always treated as binary data are never templates.
 	pub fn is_template(&self) -> bool {
-		self.template.unwrap_or(true)
+		self.template.unwrap_or(true) && self.binary_mode() != BinaryMode::Binary
+	}
+
+	/// Returns the mode in which the template of the dotfile is parsed.
+	pub fn template_mode(&self) -> TemplateMode {
+		self.template_mode.unwrap_or_default()
+	}
+
+	/// Returns the engine which resolves the template of the dotfile.
+	pub fn engine(&self) -> EngineKind {
+		self.engine.unwrap_or_default()
+	}
+
+	/// Returns how the contents of the dotfile are treated.
+	pub fn binary_mode(&self) -> BinaryMode {
+		self.binary.unwrap_or_default()
+	}
+
+	/// Checks if the dotfile is stored encrypted.
+	pub fn is_encrypted(&self) -> bool {
+		self.encrypted.unwrap_or(false)
+	}
+
+	/// Checks if the dotfile is an archive which gets extracted.
+	pub fn is_extracted(&self) -> bool {
+		self.extract.unwrap_or(false)
 	}
 }
@@ -0,0 +1,125 @@
+//! Decryption of dotfiles which are stored encrypted in the source directory.
+//!
+//! The decryption is delegated to the external `age` and `gpg` programs, which
+//! also take care of prompting for passphrases or sourcing the keys.
+//!
+//! For `age` an identity file can be given with the environment variable
+//! [`PUNKTF_AGE_IDENTITY_ENVVAR`]. Without it, `age` prompts for a
+//! passphrase. `gpg` uses the configured agent.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Name of the environment variable which points to the identity file used to
+/// decrypt `age` encrypted dotfiles.
+pub const PUNKTF_AGE_IDENTITY_ENVVAR: &str = "PUNKTF_AGE_IDENTITY";
+
+/// Formats of encrypted dotfiles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Cipher {
+	/// Encrypted with [age](https://age-encryption.org) (extension `.age`).
+	Age,
+
+	/// Encrypted with [GnuPG](https://gnupg.org) (extensions `.gpg`, `.pgp`
+	/// and `.asc`).
+	Gpg,
+}
+
+impl Cipher {
+	/// Determines the cipher of the file at `path` by its extension.
+	pub fn from_path(path: &Path) -> Option<Self> {
+		match path.extension()?.to_str()? {
+			"age" => Some(Self::Age),
+			"gpg" | "pgp" | "asc" => Some(Self::Gpg),
+			_ => None,
+		}
+	}
+
+	/// Prepares the command which decrypts the file at `path` to stdout.
+	fn command(self, path: &Path) -> Command {
+		match self {
+			Self::Age => {
+				let mut cmd = Command::new("age");
+				cmd.arg("--decrypt");
+
+				if let Some(identity) = std::env::var_os(PUNKTF_AGE_IDENTITY_ENVVAR) {
+					cmd.arg("--identity").arg(identity);
+				}
+
+				cmd.arg(path);
+				cmd
+			}
+			Self::Gpg => {
+				let mut cmd = Command::new("gpg");
+				cmd.args(["--quiet", "--decrypt"]).arg(path);
+				cmd
+			}
+		}
+	}
+}
+
+/// Removes the extension of an encrypted file from `path` (e.g.
+/// `.netrc.age` => `.netrc`).
+///
+/// Returns `None` if `path` has no known extension (related:
+/// [`Cipher::from_path`]).
+pub fn strip_extension(path: &Path) -> Option<PathBuf> {
+	Cipher::from_path(path).map(|_| path.with_extension(""))
+}
+
+/// Decrypts the file at `path` and returns the plain content.
+///
+/// The content is never written to disk. Prompts of the decrypting program
+/// are shown on the terminal.
+///
+/// # Errors
+///
+/// An error is returned if `path` has no known extension, if the decrypting
+/// program could not be executed or if it exited unsuccessfully.
+pub fn decrypt(path: &Path) -> io::Result<Vec<u8>> {
+	let cipher = Cipher::from_path(path).ok_or_else(|| {
+		io::Error::new(
+			io::ErrorKind::InvalidInput,
+			"Unknown encryption format (expected one of the extensions `.age`, `.gpg`, `.pgp` or `.asc`)",
+		)
+	})?;
+
+	let output = cipher
+		.command(path)
+		.stdin(Stdio::inherit())
+		.stderr(Stdio::inherit())
+		.output()?;
+
+	if output.status.success() {
+		Ok(output.stdout)
+	} else {
+		Err(io::Error::other(format!(
+			"Decryption with {cipher:?} failed with status `{}`",
+			output.status
+		)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn cipher_from_path() {
+		crate::tests::setup_test_env();
+
+		assert_eq!(
+			Cipher::from_path(Path::new(".netrc.age")),
+			Some(Cipher::Age)
+		);
+		assert_eq!(Cipher::from_path(Path::new("a/b.asc")), Some(Cipher::Gpg));
+		assert_eq!(Cipher::from_path(Path::new("a/b.txt")), None);
+
+		assert_eq!(
+			strip_extension(Path::new("a/.netrc.gpg")),
+			Some(PathBuf::from("a/.netrc"))
+		);
+		assert_eq!(strip_extension(Path::new("a/.netrc")), None);
+	}
+}
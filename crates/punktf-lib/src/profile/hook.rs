@@ -1,13 +1,21 @@
 //! Hooks which can be execute by the native os shell.
+//!
+//! A hook with multiple lines (e.g. a YAML block scalar) is treated as an
+//! inline script. It is written to a temporary file and executed with the
+//! interpreter given by its shebang (e.g. `#!/usr/bin/env python3`). Scripts
+//! without a shebang are executed by `sh` on unix and `cmd` on windows.
 
-use std::io::{BufRead as _, BufReader};
+use std::ffi::OsStr;
+use std::io::Write as _;
 use std::path::Path;
 use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
 
-use color_eyre::eyre::Result;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::temp::TempPath;
+
 /// An enum of errors which can occur during the execution of a [`Hook`].
 #[derive(Error, Debug)]
 pub enum HookError {
@@ -49,6 +57,49 @@ impl ExitOk for std::process::ExitStatus {
 	}
 }
 
+/// The captured result of an executed [`Hook`] (related:
+/// [`Hook::run_with_env`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookOutput {
+	/// The exit status of the hook.
+	pub status: std::process::ExitStatus,
+
+	/// The time it took to execute the hook.
+	pub duration: Duration,
+
+	/// Everything the hook wrote to stdout.
+	///
+	/// Invalid UTF-8 sequences are replaced.
+	pub stdout: String,
+
+	/// Everything the hook wrote to stderr.
+	///
+	/// Invalid UTF-8 sequences are replaced.
+	pub stderr: String,
+}
+
+impl HookOutput {
+	/// Checks if the hook exited successfully.
+	///
+	/// # Errors
+	///
+	/// Returns [`HookError::ExitStatusError`] if the hook failed.
+	pub fn exit_ok(&self) -> Result<(), HookError> {
+		self.status.exit_ok()
+	}
+
+	/// Logs the captured output line by line.
+	pub(crate) fn log_lines(&self) {
+		for line in self.stdout.lines() {
+			log::info!("hook::stdout > {}", line);
+		}
+
+		for line in self.stderr.lines() {
+			log::error!("hook::stderr > {}", line);
+		}
+	}
+}
+
 /// Implements the `Hook` trait, which is used to run a command after or before a build.
 #[derive(Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
@@ -65,68 +116,259 @@ impl Hook {
 		&self.0
 	}
 
+	/// Returns `true` if the hook is an inline script, which is the case if
+	/// it spans multiple lines.
+	pub fn is_script(&self) -> bool {
+		self.0.trim_end().contains('\n')
+	}
+
+	/// Returns the interpreter and its arguments from the shebang of the
+	/// hook (e.g. `#!/usr/bin/env bash`).
+	pub fn shebang(&self) -> Option<Vec<&str>> {
+		let line = self.0.lines().next()?.strip_prefix("#!")?;
+		let interpreter: Vec<_> = line.split_whitespace().collect();
+
+		(!interpreter.is_empty()).then_some(interpreter)
+	}
+
 	/// Executes the hook command.
-	pub fn execute(&self, cwd: &Path) -> Result<()> {
-		let mut child = self
-			.prepare_command()?
+	pub fn execute(&self, cwd: &Path) -> crate::error::Result<()> {
+		self.execute_with_env(cwd, std::iter::empty::<(&str, &str)>())
+	}
+
+	/// Executes the hook command with the additional environment variables
+	/// `envs`.
+	///
+	/// The captured output is logged line by line.
+	pub fn execute_with_env<I, K, V>(&self, cwd: &Path, envs: I) -> crate::error::Result<()>
+	where
+		I: IntoIterator<Item = (K, V)>,
+		K: AsRef<OsStr>,
+		V: AsRef<OsStr>,
+	{
+		let output = self.run_with_env(cwd, envs)?;
+		output.log_lines();
+
+		output.exit_ok().map_err(Into::into)
+	}
+
+	/// Executes the hook command with the additional environment variables
+	/// `envs` and captures its output.
+	///
+	/// In contrast to [`Hook::execute_with_env`], a hook which exits with a
+	/// failure status is not treated as an error.
+	pub fn run_with_env<I, K, V>(&self, cwd: &Path, envs: I) -> Result<HookOutput, HookError>
+	where
+		I: IntoIterator<Item = (K, V)>,
+		K: AsRef<OsStr>,
+		V: AsRef<OsStr>,
+	{
+		self.run(cwd, envs, None)
+	}
+
+	/// Executes the hook command like [`Hook::run_with_env`], but passes
+	/// `input` on stdin instead of inheriting it.
+	pub fn run_with_input<I, K, V>(
+		&self,
+		cwd: &Path,
+		envs: I,
+		input: &[u8],
+	) -> Result<HookOutput, HookError>
+	where
+		I: IntoIterator<Item = (K, V)>,
+		K: AsRef<OsStr>,
+		V: AsRef<OsStr>,
+	{
+		self.run(cwd, envs, Some(input))
+	}
+
+	/// Executes the hook command and captures its output.
+	///
+	/// If `input` is given, it is written to stdin of the hook, otherwise
+	/// stdin is inherited.
+	fn run<I, K, V>(
+		&self,
+		cwd: &Path,
+		envs: I,
+		input: Option<&[u8]>,
+	) -> Result<HookOutput, HookError>
+	where
+		I: IntoIterator<Item = (K, V)>,
+		K: AsRef<OsStr>,
+		V: AsRef<OsStr>,
+	{
+		let start = Instant::now();
+
+		// Both streams are read concurrently by `wait_with_output`, so a hook
+		// which writes a lot to one of them can not block. Without input,
+		// stdin is inherited to allow interactive hooks.
+		// The script file needs to be kept alive until the command finished.
+		let (mut command, _script) = self.prepare_command()?;
+
+		let mut child = command
 			.current_dir(cwd)
+			.envs(envs)
+			.stdin(if input.is_some() {
+				Stdio::piped()
+			} else {
+				Stdio::inherit()
+			})
 			.stdout(Stdio::piped())
 			.stderr(Stdio::piped())
 			.spawn()?;
 
-		// No need to call kill here as the program will immediately exit
-		// and thereby kill all spawned children
-		let stdout = child.stdout.take().expect("Failed to get stdout from hook");
-
-		for line in BufReader::new(stdout).lines() {
-			match line {
-				Ok(line) => log::info!("hook::stdout > {}", line),
-				Err(err) => {
-					// Result is explicitly ignored as an error was already
-					// encountered
+		if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+			// Hooks are free to ignore their input and exit early.
+			match stdin.write_all(input) {
+				Err(err) if err.kind() != std::io::ErrorKind::BrokenPipe => {
 					let _ = child.kill();
 					return Err(err.into());
 				}
+				_ => {}
 			}
 		}
 
-		// No need to call kill here as the program will immediately exit
-		// and thereby kill all spawned children
-		let stderr = child.stderr.take().expect("Failed to get stderr from hook");
+		let output = child.wait_with_output()?;
 
-		for line in BufReader::new(stderr).lines() {
-			match line {
-				Ok(line) => log::error!("hook::stderr > {}", line),
-				Err(err) => {
-					// Result is explicitly ignored as an error was already
-					// encountered
-					let _ = child.kill();
-					return Err(err.into());
-				}
-			}
-		}
-
-		child
-			.wait_with_output()?
-			.status
-			.exit_ok()
-			.map_err(Into::into)
+		Ok(HookOutput {
+			status: output.status,
+			duration: start.elapsed(),
+			stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+			stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+		})
 	}
 
 	/// Prepares the command for execution depending on the platform.
-	fn prepare_command(&self) -> Result<Command> {
+	///
+	/// For [inline scripts](`Hook::is_script`), the temporary script file is
+	/// returned as well.
+	fn prepare_command(&self) -> std::io::Result<(Command, Option<TempPath>)> {
+		if self.is_script() {
+			let script = self.write_script()?;
+			let cmd = self.script_command(script.path());
+
+			return Ok((cmd, Some(script)));
+		}
+
 		cfg_if::cfg_if! {
 			if #[cfg(target_family = "windows")] {
 				let mut cmd = Command::new("cmd");
 				cmd.args(["/C", &self.0]);
-				Ok(cmd)
+				Ok((cmd, None))
 			} else if #[cfg(target_family = "unix")] {
 				let mut cmd = Command::new("sh");
 				cmd.args(["-c", &self.0]);
-				Ok(cmd)
+				Ok((cmd, None))
 			} else {
 				Err(std::io::Error::new(std::io::ErrorKind::Other, "Hooks are only supported on Windows and Unix-based systems"))
 			}
 		}
 	}
+
+	/// Writes the inline script to a new temporary file and marks it as
+	/// executable.
+	fn write_script(&self) -> std::io::Result<TempPath> {
+		// `cmd` only executes files with a known extension.
+		let extension = if cfg!(windows) && self.shebang().is_none() {
+			".cmd"
+		} else {
+			""
+		};
+
+		let path =
+			std::env::temp_dir().join(format!("punktf-hook-{}{extension}", uuid::Uuid::new_v4()));
+
+		// Never reuse an existing path and never make the script readable by
+		// other users, not even for a moment.
+		let mut options = std::fs::OpenOptions::new();
+		options.write(true).create_new(true);
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::OpenOptionsExt;
+
+			options.mode(0o700);
+		}
+
+		let mut file = options.open(&path)?;
+		let script = TempPath::new(path);
+
+		// The file is closed at the end of this function, as some systems
+		// refuse to execute files which are still open for writing.
+		file.write_all(self.0.as_bytes())?;
+
+		Ok(script)
+	}
+
+	/// Creates the command which executes the inline script at `path` with
+	/// the interpreter of its shebang or the default one of the platform.
+	fn script_command(&self, path: &Path) -> Command {
+		if let Some((interpreter, args)) = self
+			.shebang()
+			.as_deref()
+			.and_then(|shebang| shebang.split_first())
+		{
+			let mut cmd = Command::new(interpreter);
+			cmd.args(args).arg(path);
+			return cmd;
+		}
+
+		if cfg!(windows) {
+			let mut cmd = Command::new("cmd");
+			cmd.arg("/C").arg(path);
+			cmd
+		} else {
+			let mut cmd = Command::new("sh");
+			cmd.arg(path);
+			cmd
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	#[cfg(unix)]
+	fn inline_script() -> Result<(), HookError> {
+		crate::tests::setup_test_env();
+
+		let hook = Hook::new("echo single");
+		assert!(!hook.is_script());
+		assert_eq!(hook.shebang(), None);
+
+		let hook = Hook::new("#!/usr/bin/env sh\nset -e\necho \"$0\" \"$NAME\"\n");
+		assert!(hook.is_script());
+		assert_eq!(hook.shebang(), Some(vec!["/usr/bin/env", "sh"]));
+
+		let output = hook.run_with_env(&std::env::temp_dir(), [("NAME", "demo")])?;
+		output.exit_ok()?;
+
+		let (script, name) = output
+			.stdout
+			.trim_end()
+			.split_once(' ')
+			.expect("Script path and name are printed");
+		assert!(script.contains("punktf-hook-"));
+		assert!(!Path::new(script).exists());
+		assert!(!crate::temp::tracked().contains(&Path::new(script).to_path_buf()));
+		assert_eq!(name, "demo");
+
+		let hook = Hook::new("read -r line\necho \"got $line\"\n");
+		let output = hook.run_with_input(
+			&std::env::temp_dir(),
+			std::iter::empty::<(&str, &str)>(),
+			b"input\n",
+		)?;
+		assert_eq!(output.stdout, "got input\n");
+
+		let hook = Hook::new("echo first\nexit 3\n");
+		let output =
+			hook.run_with_env(&std::env::temp_dir(), std::iter::empty::<(&str, &str)>())?;
+		assert_eq!(output.stdout, "first\n");
+		assert_eq!(output.status.code(), Some(3));
+
+		Ok(())
+	}
 }
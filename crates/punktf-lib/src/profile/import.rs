@@ -0,0 +1,858 @@
+//! Import of source directories of other dotfile managers.
+//!
+//! An [`Import`] is created by reading the layout or configuration of another
+//! manager (related: [`ImportFormat`]) and can then be written as a new
+//! profile into a [source directory](`crate::profile::source::PunktfSource`)
+//! (related: [`Import::write`]). The conversion is best-effort: plain files
+//! and symlinks are converted, everything else (e.g. scripts or templates of
+//! the other manager) is reported in [`Import::notes`] to be converted by
+//! hand.
+//!
+//! All imported files deploy to the home directory of the current user. As
+//! the other managers do not use the template syntax of `punktf`, none of the
+//! imported dotfiles are treated as templates.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::profile::dotfile::Dotfile;
+use crate::profile::hook::Hook;
+use crate::profile::link::{LinkKind, Symlink};
+use crate::profile::{MergeMode, Profile};
+
+/// The dotfile managers which can be imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ImportFormat {
+	/// [GNU Stow](https://www.gnu.org/software/stow/): Each directory is a
+	/// package which mirrors the home directory. Package names are used as
+	/// tags of the imported dotfiles. The `dot-` prefix of `--dotfiles` is
+	/// supported.
+	Stow,
+
+	/// [chezmoi](https://www.chezmoi.io/): The source state with its
+	/// attribute prefixes (e.g. `dot_` or `private_`). Scripts, templates and
+	/// encrypted files are not converted.
+	Chezmoi,
+
+	/// [Dotbot](https://github.com/anishathalye/dotbot): The `link` and
+	/// `shell` directives of the `install.conf.yaml` (or `.json`)
+	/// configuration.
+	Dotbot,
+}
+
+/// A file which is copied into the `dotfiles` directory by
+/// [`Import::write`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedFile {
+	/// Absolute path of the file in the directory of the other manager.
+	pub from: PathBuf,
+
+	/// Path of the file relative to the `dotfiles` directory.
+	pub path: PathBuf,
+}
+
+/// The result of an [`import`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+	/// The profile which deploys the imported files.
+	pub profile: Profile,
+
+	/// All files which need to be copied into the source directory.
+	pub files: Vec<ImportedFile>,
+
+	/// Everything which could not be converted.
+	pub notes: Vec<String>,
+}
+
+impl Import {
+	/// Creates a new, empty, instance which deploys to `home`.
+	fn new(home: &Path) -> Self {
+		Self {
+			profile: Profile {
+				target: Some(home.to_path_buf()),
+				..Default::default()
+			},
+			files: Vec::new(),
+			notes: Vec::new(),
+		}
+	}
+
+	/// Adds the file `from` which is stored at `path` in the `dotfiles`
+	/// directory.
+	fn add_file(&mut self, from: PathBuf, path: PathBuf) {
+		self.files.push(ImportedFile { from, path });
+	}
+
+	/// Adds a symlink at `target_path` which points to `source_path`.
+	fn add_link(&mut self, source_path: PathBuf, target_path: PathBuf) {
+		self.profile.symlinks.push(Symlink {
+			source_path,
+			target_path,
+			replace: true,
+			kind: LinkKind::Symlink,
+			priority: None,
+			merge: None,
+			when: None,
+		});
+	}
+
+	/// Adds a dotfile for the item at `path` in the `dotfiles` directory.
+	fn add_dotfile(&mut self, dotfile: Dotfile) {
+		self.profile.dotfiles.push(Dotfile {
+			template: Some(false),
+			..dotfile
+		});
+	}
+
+	/// Writes the imported profile as `name` into the source directory `root`
+	/// and copies all imported files into its `dotfiles` directory.
+	///
+	/// Returns the path of the written profile.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the profile or any of the files already exist
+	/// in the source directory. In that case nothing is written. An error is
+	/// also returned if any file could not be copied.
+	pub fn write(&self, root: &Path, name: &str) -> Result<PathBuf> {
+		let extension = if cfg!(feature = "profile-yaml") {
+			"yaml"
+		} else {
+			"json"
+		};
+		let profile_path = root.join("profiles").join(name).with_extension(extension);
+		let dotfiles = root.join("dotfiles");
+
+		let targets: Vec<_> = self
+			.files
+			.iter()
+			.map(|file| (&file.from, dotfiles.join(&file.path)))
+			.collect();
+
+		for path in std::iter::once(&profile_path).chain(targets.iter().map(|(_, path)| path)) {
+			if path.symlink_metadata().is_ok() {
+				return Err(Error::io(
+					path,
+					io::Error::new(io::ErrorKind::AlreadyExists, "File already exists"),
+				));
+			}
+		}
+
+		for (from, to) in targets {
+			if let Some(parent) = to.parent() {
+				std::fs::create_dir_all(parent).map_err(|err| Error::io(parent, err))?;
+			}
+
+			std::fs::copy(from, &to).map_err(|err| Error::io(from, err))?;
+		}
+
+		let content = super::migrate::serialize(&profile_path, &self.profile)?;
+
+		let profiles = root.join("profiles");
+		std::fs::create_dir_all(&profiles).map_err(|err| Error::io(&profiles, err))?;
+		std::fs::write(&profile_path, content).map_err(|err| Error::io(&profile_path, err))?;
+
+		Ok(profile_path)
+	}
+}
+
+/// An item found by [`walk`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Entry {
+	/// A file with its absolute path.
+	File(PathBuf),
+
+	/// A symlink with the absolute path it points to.
+	Link(PathBuf),
+}
+
+/// Recursively collects all files and symlinks below `dir`, sorted by their
+/// path relative to `dir`.
+///
+/// Items whose name matches `skip` are ignored, including their children.
+fn walk(dir: &Path, skip: &dyn Fn(&OsStr) -> bool) -> Result<Vec<(PathBuf, Entry)>> {
+	/// Collects the items below `dir` which is at `relative` below the root.
+	fn walk_inner(
+		dir: &Path,
+		relative: &Path,
+		skip: &dyn Fn(&OsStr) -> bool,
+		entries: &mut Vec<(PathBuf, Entry)>,
+	) -> Result<()> {
+		let mut dents = Vec::new();
+		for dent in std::fs::read_dir(dir).map_err(|err| Error::io(dir, err))? {
+			dents.push(dent.map_err(|err| Error::io(dir, err))?);
+		}
+		dents.sort_by_key(std::fs::DirEntry::file_name);
+
+		for dent in dents {
+			let name = dent.file_name();
+			if skip(&name) {
+				continue;
+			}
+
+			let path = dent.path();
+			let relative = relative.join(&name);
+			let file_type = dent.file_type().map_err(|err| Error::io(&path, err))?;
+
+			if file_type.is_symlink() {
+				let target = path.read_link().map_err(|err| Error::io(&path, err))?;
+				entries.push((relative, Entry::Link(dir.join(target))));
+			} else if file_type.is_dir() {
+				walk_inner(&path, &relative, skip, entries)?;
+			} else {
+				entries.push((relative, Entry::File(path)));
+			}
+		}
+
+		Ok(())
+	}
+
+	let mut entries = Vec::new();
+	walk_inner(dir, Path::new(""), skip, &mut entries)?;
+
+	Ok(entries)
+}
+
+/// Reads the directory of another dotfile manager at `from` and converts it
+/// to a profile which deploys to the home directory of the current user.
+///
+/// # Errors
+///
+/// An error is returned if the directory (or for
+/// [`ImportFormat::Dotbot`] the configuration) could not be read.
+pub fn import(format: ImportFormat, from: &Path) -> Result<Import> {
+	let home = PathBuf::from(shellexpand::tilde("~").as_ref());
+
+	match format {
+		ImportFormat::Stow => import_stow(from, &home),
+		ImportFormat::Chezmoi => import_chezmoi(from, &home),
+		ImportFormat::Dotbot => import_dotbot(from, &home),
+	}
+}
+
+/// Converts the stow packages in `from`.
+///
+/// Each package becomes a directory dotfile which deploys its contents into
+/// `home`.
+fn import_stow(from: &Path, home: &Path) -> Result<Import> {
+	/// Checks if the item is ignored by stow by default.
+	fn skip(name: &OsStr) -> bool {
+		let name = name.to_string_lossy();
+
+		[".git", ".gitignore", ".stow-local-ignore", "COPYING"].contains(&name.as_ref())
+			|| name.starts_with("README")
+			|| name.starts_with("LICENSE")
+	}
+
+	/// Replaces the `dot-` prefix of all components of `path` with a `.`.
+	fn translate(path: &Path) -> PathBuf {
+		path.iter()
+			.map(
+				|name| match name.to_str().and_then(|name| name.strip_prefix("dot-")) {
+					Some(name) => format!(".{name}").into(),
+					None => name.to_os_string(),
+				},
+			)
+			.collect()
+	}
+
+	let mut import = Import::new(home);
+
+	for (relative, entry) in walk(from, &skip)? {
+		let mut components = relative.components();
+		let Some(package) = components.next() else {
+			continue;
+		};
+		let package = package.as_os_str().to_string_lossy().into_owned();
+		let relative = components.as_path();
+
+		// Items in the root and hidden directories are no packages
+		if relative.as_os_str().is_empty() || package.starts_with('.') {
+			continue;
+		}
+
+		let translated = translate(relative);
+
+		match entry {
+			Entry::File(path) => {
+				if !import
+					.profile
+					.dotfiles
+					.iter()
+					.any(|dotfile| dotfile.path == Path::new(&package))
+				{
+					import.add_dotfile(Dotfile {
+						path: PathBuf::from(&package),
+						tags: vec![package.clone()],
+						..Default::default()
+					});
+				}
+
+				import.add_file(path, Path::new(&package).join(translated));
+			}
+			Entry::Link(target) => import.add_link(target, home.join(translated)),
+		}
+	}
+
+	Ok(import)
+}
+
+/// Attributes of an item of the chezmoi source state, taken from the prefixes
+/// and suffixes of its name.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct ChezmoiAttributes {
+	/// The name in the target state.
+	name: String,
+
+	/// `create_`: Only created if it does not exist.
+	create: bool,
+
+	/// `symlink_`: A symlink with the content as target.
+	symlink: bool,
+
+	/// `.tmpl`: A chezmoi template.
+	template: bool,
+
+	/// `run_`, `modify_`, `remove_` or `encrypted_`: An item which can not be
+	/// converted. Contains the prefix.
+	unsupported: Option<&'static str>,
+}
+
+impl ChezmoiAttributes {
+	/// Parses the attributes of the source state name `name`.
+	fn parse(name: &str) -> Self {
+		/// Prefixes which are removed without any effect on the import.
+		const IGNORED: &[&str] = &[
+			"exact_",
+			"external_",
+			"private_",
+			"readonly_",
+			"empty_",
+			"executable_",
+			"once_",
+			"onchange_",
+			"before_",
+			"after_",
+		];
+
+		/// Prefixes of items which can not be converted.
+		const UNSUPPORTED: &[&str] = &["run_", "modify_", "remove_", "encrypted_"];
+
+		let mut attributes = Self::default();
+		let mut name = name;
+
+		loop {
+			if let Some(rest) = name.strip_prefix("literal_") {
+				name = rest;
+				break;
+			} else if let Some(rest) = name.strip_prefix("create_") {
+				attributes.create = true;
+				name = rest;
+			} else if let Some(rest) = name.strip_prefix("symlink_") {
+				attributes.symlink = true;
+				name = rest;
+			} else if let Some(prefix) = UNSUPPORTED.iter().find(|p| name.starts_with(*p)) {
+				attributes.unsupported.get_or_insert(prefix);
+				name = &name[prefix.len()..];
+			} else if let Some(prefix) = IGNORED.iter().find(|p| name.starts_with(*p)) {
+				name = &name[prefix.len()..];
+			} else {
+				break;
+			}
+		}
+
+		let name = match name.strip_prefix("dot_") {
+			Some(rest) => format!(".{rest}"),
+			None => name.to_string(),
+		};
+
+		attributes.name = if let Some(rest) = name.strip_suffix(".literal") {
+			rest.to_string()
+		} else if let Some(rest) = name.strip_suffix(".tmpl") {
+			attributes.template = true;
+			rest.to_string()
+		} else {
+			name
+		};
+
+		attributes
+	}
+}
+
+/// Converts the chezmoi source state in `from`.
+///
+/// Each file becomes its own dotfile, so that attributes like `create_` can
+/// be kept.
+fn import_chezmoi(from: &Path, home: &Path) -> Result<Import> {
+	let root_file = from.join(".chezmoiroot");
+	let root = if root_file.is_file() {
+		let content =
+			std::fs::read_to_string(&root_file).map_err(|err| Error::io(&root_file, err))?;
+		from.join(content.trim())
+	} else {
+		from.to_path_buf()
+	};
+
+	let mut import = Import::new(home);
+
+	let entries = walk(&root, &|name| {
+		let name = name.to_string_lossy();
+		name.starts_with(".chezmoi") || name == ".git"
+	})?;
+
+	for (relative, entry) in entries {
+		let components: Vec<_> = relative
+			.iter()
+			.map(|name| ChezmoiAttributes::parse(&name.to_string_lossy()))
+			.collect();
+		let Some(attributes) = components.last() else {
+			continue;
+		};
+
+		let translated: PathBuf = components.iter().map(|c| c.name.as_str()).collect();
+
+		if let Some(prefix) = components.iter().find_map(|c| c.unsupported) {
+			import.notes.push(format!(
+				"Skipped `{}`: items with the prefix `{prefix}` are not supported",
+				relative.display()
+			));
+			continue;
+		}
+
+		match entry {
+			Entry::File(path) if attributes.symlink => {
+				if attributes.template {
+					import.notes.push(format!(
+						"Skipped `{}`: the target of the symlink is a template",
+						relative.display()
+					));
+					continue;
+				}
+
+				let content =
+					std::fs::read_to_string(&path).map_err(|err| Error::io(&path, err))?;
+				let target_path = home.join(&translated);
+				let parent = target_path.parent().unwrap_or(home);
+
+				import.add_link(parent.join(content.trim()), target_path);
+			}
+			Entry::File(path) => {
+				if attributes.template {
+					import.notes.push(format!(
+						"Imported `{}` as plain file: the chezmoi template syntax needs to be converted by hand",
+						relative.display()
+					));
+				}
+
+				import.add_dotfile(Dotfile {
+					path: translated.clone(),
+					merge: attributes.create.then_some(MergeMode::Keep),
+					..Default::default()
+				});
+				import.add_file(path, translated);
+			}
+			Entry::Link(target) => import.add_link(target, home.join(translated)),
+		}
+	}
+
+	Ok(import)
+}
+
+/// Options of a link of the dotbot `link` directive.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum DotbotLink {
+	/// Only the source path.
+	Source(String),
+
+	/// The source path with options.
+	Options {
+		/// The source path.
+		path: Option<String>,
+
+		/// Indicates if the source path is a glob pattern.
+		#[serde(default)]
+		glob: bool,
+
+		/// All other options, which are ignored.
+		#[serde(flatten)]
+		other: BTreeMap<String, IgnoredAny>,
+	},
+}
+
+/// A command of the dotbot `shell` directive.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum DotbotShell {
+	/// Only the command.
+	Command(String),
+
+	/// The command followed by a description.
+	WithDescription(Vec<String>),
+
+	/// The command with options.
+	Options {
+		/// The command.
+		command: String,
+
+		/// All other options, which are ignored.
+		#[serde(flatten)]
+		other: BTreeMap<String, IgnoredAny>,
+	},
+}
+
+/// A task of the dotbot configuration.
+#[derive(Debug, Deserialize)]
+struct DotbotTask {
+	/// Links from the target path to the source path.
+	#[serde(default)]
+	link: BTreeMap<String, Option<DotbotLink>>,
+
+	/// Commands to run.
+	#[serde(default)]
+	shell: Vec<DotbotShell>,
+
+	/// All other directives, which are not supported.
+	#[serde(flatten)]
+	other: BTreeMap<String, IgnoredAny>,
+}
+
+/// Converts the dotbot configuration in (or at) `from`.
+///
+/// Each link becomes a dotfile and each shell command a post-hook.
+fn import_dotbot(from: &Path, home: &Path) -> Result<Import> {
+	let config = if from.is_file() {
+		from.to_path_buf()
+	} else {
+		["install.conf.yaml", "install.conf.yml", "install.conf.json"]
+			.into_iter()
+			.map(|name| from.join(name))
+			.find(|path| path.is_file())
+			.ok_or_else(|| {
+				Error::io(
+					from.join("install.conf.yaml"),
+					io::Error::new(io::ErrorKind::NotFound, "No dotbot configuration found"),
+				)
+			})?
+	};
+	let base = config.parent().unwrap_or(from);
+
+	let content = std::fs::read_to_string(&config).map_err(|err| Error::io(&config, err))?;
+	let tasks: Vec<DotbotTask> = super::deserialize(&config, &content)?;
+
+	let mut import = Import::new(home);
+
+	for task in tasks {
+		for directive in task.other.keys() {
+			import
+				.notes
+				.push(format!("Skipped dotbot directive `{directive}`"));
+		}
+
+		for (target, link) in task.link {
+			let source = match link {
+				Some(DotbotLink::Options { glob: true, .. }) => {
+					import.notes.push(format!(
+						"Skipped link `{target}`: glob links are not supported"
+					));
+					continue;
+				}
+				Some(
+					DotbotLink::Source(path)
+					| DotbotLink::Options {
+						path: Some(path), ..
+					},
+				) => PathBuf::from(path),
+				// Without a source, the name of the target without a leading
+				// dot is used.
+				Some(DotbotLink::Options { path: None, .. }) | None => {
+					let name = Path::new(&target)
+						.file_name()
+						.map(|name| name.to_string_lossy().into_owned())
+						.unwrap_or_default();
+
+					PathBuf::from(name.trim_start_matches('.'))
+				}
+			};
+
+			if source.is_absolute() || source.components().any(|c| c == Component::ParentDir) {
+				import.notes.push(format!(
+					"Skipped link `{target}`: the source `{}` is outside of the dotbot directory",
+					source.display()
+				));
+				continue;
+			}
+
+			let source_path = base.join(&source);
+			let target = PathBuf::from(
+				shellexpand::tilde_with_context(&target, || Some(home.to_string_lossy())).as_ref(),
+			);
+			let target = home.join(target);
+
+			let (overwrite_target, rename) = match target.strip_prefix(home) {
+				Ok(relative) => (None, relative.to_path_buf()),
+				Err(_) => (
+					target.parent().map(Path::to_path_buf),
+					target.file_name().map(PathBuf::from).unwrap_or_default(),
+				),
+			};
+
+			if source_path.is_dir() {
+				for (relative, entry) in walk(&source_path, &|name| name == ".git")? {
+					match entry {
+						Entry::File(path) => import.add_file(path, source.join(relative)),
+						Entry::Link(link) => import.add_link(link, target.join(relative)),
+					}
+				}
+			} else if source_path.is_file() {
+				import.add_file(source_path, source.clone());
+			} else {
+				import.notes.push(format!(
+					"Skipped link `{}`: the source `{}` does not exist",
+					target.display(),
+					source.display()
+				));
+				continue;
+			}
+
+			import.add_dotfile(Dotfile {
+				path: source,
+				rename: Some(rename),
+				overwrite_target,
+				..Default::default()
+			});
+		}
+
+		for shell in task.shell {
+			let command = match shell {
+				DotbotShell::Command(command) | DotbotShell::Options { command, .. } => {
+					Some(command)
+				}
+				DotbotShell::WithDescription(parts) => parts.into_iter().next(),
+			};
+
+			if let Some(command) = command {
+				import.profile.post_hooks.push(Hook::new(command));
+			}
+		}
+	}
+
+	Ok(import)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use pretty_assertions::assert_eq;
+
+	/// Creates the files `files` (relative path and content) below `dir`.
+	fn create_files(dir: &Path, files: &[(&str, &str)]) -> io::Result<()> {
+		for (path, content) in files {
+			let path = dir.join(path);
+			std::fs::create_dir_all(path.parent().expect("File to have a parent"))?;
+			std::fs::write(path, content)?;
+		}
+
+		Ok(())
+	}
+
+	/// Returns the paths of all imported files.
+	fn file_paths(import: &Import) -> Vec<&Path> {
+		import
+			.files
+			.iter()
+			.map(|file| file.path.as_path())
+			.collect()
+	}
+
+	#[test]
+	fn import_stow_packages() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		create_files(
+			&dir,
+			&[
+				("bash/.bashrc", "bash"),
+				("bash/README.md", "readme"),
+				("nvim/dot-config/nvim/init.vim", "nvim"),
+				(".stowrc", "--dotfiles"),
+			],
+		)?;
+
+		let import = import_stow(&dir, Path::new("/home/demo"))?;
+
+		assert_eq!(
+			file_paths(&import),
+			[
+				Path::new("bash/.bashrc"),
+				Path::new("nvim/.config/nvim/init.vim")
+			]
+		);
+		assert_eq!(
+			import
+				.profile
+				.dotfiles
+				.iter()
+				.map(|dotfile| (dotfile.path.as_path(), dotfile.tags.as_slice()))
+				.collect::<Vec<_>>(),
+			[
+				(Path::new("bash"), &[String::from("bash")][..]),
+				(Path::new("nvim"), &[String::from("nvim")][..])
+			]
+		);
+		assert!(import.notes.is_empty());
+
+		Ok(())
+	}
+
+	#[test]
+	fn import_chezmoi_source_state() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		create_files(
+			&dir,
+			&[
+				("dot_bashrc", "bash"),
+				("private_dot_config/nvim/create_init.vim", "nvim"),
+				("dot_gitconfig.tmpl", "{{ .email }}"),
+				("symlink_dot_vimrc", ".config/nvim/init.vim\n"),
+				("run_once_install.sh", "true"),
+				(".chezmoiignore", "README.md"),
+			],
+		)?;
+
+		let import = import_chezmoi(&dir, Path::new("/home/demo"))?;
+
+		assert_eq!(
+			file_paths(&import),
+			[
+				Path::new(".bashrc"),
+				Path::new(".gitconfig"),
+				Path::new(".config/nvim/init.vim")
+			]
+		);
+		assert_eq!(
+			import.profile.dotfiles[2].merge,
+			Some(MergeMode::Keep),
+			"create_ to keep existing files"
+		);
+		assert!(import
+			.profile
+			.dotfiles
+			.iter()
+			.all(|dotfile| !dotfile.is_template()));
+		assert_eq!(
+			import
+				.profile
+				.symlinks
+				.iter()
+				.map(|link| (link.source_path.as_path(), link.target_path.as_path()))
+				.collect::<Vec<_>>(),
+			[(
+				Path::new("/home/demo/.config/nvim/init.vim"),
+				Path::new("/home/demo/.vimrc")
+			)]
+		);
+		assert_eq!(import.notes.len(), 2, "{:?}", import.notes);
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn import_dotbot_config() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		create_files(
+			&dir,
+			&[
+				(
+					"install.conf.yaml",
+					r#"
+- defaults:
+    link:
+      relink: true
+- clean: ['~']
+- link:
+    ~/.bashrc:
+    ~/.config/nvim:
+      path: nvim
+      create: true
+    /etc/hosts: hosts
+    ~/.missing: missing
+- shell:
+    - [git submodule update --init, Installing submodules]
+    - command: echo done
+"#,
+				),
+				("bashrc", "bash"),
+				("nvim/init.vim", "nvim"),
+				("hosts", "hosts"),
+			],
+		)?;
+
+		let import = import_dotbot(&dir, Path::new("/home/demo"))?;
+
+		assert_eq!(
+			file_paths(&import),
+			[
+				Path::new("hosts"),
+				Path::new("bashrc"),
+				Path::new("nvim/init.vim")
+			]
+		);
+		assert_eq!(
+			import
+				.profile
+				.dotfiles
+				.iter()
+				.map(|dotfile| (
+					dotfile.path.as_path(),
+					dotfile.rename.as_deref(),
+					dotfile.overwrite_target.as_deref()
+				))
+				.collect::<Vec<_>>(),
+			[
+				(
+					Path::new("hosts"),
+					Some(Path::new("hosts")),
+					Some(Path::new("/etc"))
+				),
+				(Path::new("bashrc"), Some(Path::new(".bashrc")), None),
+				(Path::new("nvim"), Some(Path::new(".config/nvim")), None),
+			]
+		);
+		assert_eq!(
+			import.profile.post_hooks,
+			[
+				Hook::new("git submodule update --init"),
+				Hook::new("echo done")
+			]
+		);
+		assert_eq!(import.notes.len(), 3, "{:?}", import.notes);
+
+		// Write and read back
+		let root = dir.join("punktf");
+		let path = import.write(&root, "imported")?;
+		assert_eq!(Profile::from_file(&path)?, import.profile);
+		assert_eq!(
+			std::fs::read_to_string(root.join("dotfiles/bashrc"))?,
+			"bash"
+		);
+
+		// Existing files are never overwritten
+		assert!(import.write(&root, "imported").is_err());
+
+		Ok(())
+	}
+}
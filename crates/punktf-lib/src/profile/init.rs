@@ -0,0 +1,201 @@
+//! Scaffolding of a new [source directory](`crate::profile::source::PunktfSource`).
+//!
+//! [`init`] creates the `profiles` and `dotfiles` directories, a starter
+//! profile which lists the most common fields as comments and optionally a
+//! git repository with a `.gitignore`. Existing files are never overwritten,
+//! so it is safe to run it on an existing source directory.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{Error, Result};
+
+/// Name of the starter profile.
+pub const STARTER_PROFILE_NAME: &str = "default";
+
+/// Content of the `.gitignore` created with the git repository.
+const GITIGNORE: &str = "\
+# Editor and operating system files
+*.swp
+*~
+.DS_Store
+Thumbs.db
+";
+
+/// Options for [`init`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InitOptions {
+	/// Initializes a git repository with a `.gitignore` in the source
+	/// directory.
+	pub git: bool,
+}
+
+/// Returns the content of the starter profile which deploys to `target`.
+#[cfg(feature = "profile-yaml")]
+fn starter_profile(target: &Path) -> (&'static str, String) {
+	let content = format!(
+		r#"# The starter profile of this source directory.
+#
+# Deploy it with `punktf deploy --profile {STARTER_PROFILE_NAME}`. All fields
+# besides `dotfiles` are optional.
+
+# Other names by which this profile can be selected.
+# aliases: [home]
+
+# Profiles whose dotfiles, variables and hooks are included.
+# extends: [base]
+
+# Directory to which the dotfiles are deployed.
+target: {target:?}
+
+# Variables which can be used in templates (e.g. `{{{{EDITOR}}}}`).
+# variables:
+#   EDITOR: nvim
+
+# Commands which are run before and after the deployment.
+# pre_hooks: []
+# post_hooks: []
+
+# Dotfiles to deploy. Paths are relative to the `dotfiles` directory.
+dotfiles: []
+# dotfiles:
+#   - path: .gitconfig
+#   - path: nvim
+#     rename: .config/nvim
+#     # Resolves `{{{{ ... }}}}` blocks in the dotfile.
+#     template: true
+#     # What to do if the target already exists (Overwrite, Keep, Ask or Merge).
+#     merge: Ask
+#     # Only deploys the dotfile with `--tag editor`.
+#     tags: [editor]
+"#,
+		target = target.display().to_string(),
+	);
+
+	("yaml", content)
+}
+
+/// Returns the content of the starter profile which deploys to `target`.
+#[cfg(not(feature = "profile-yaml"))]
+fn starter_profile(target: &Path) -> (&'static str, String) {
+	let content = format!(
+		"{{\n\t\"target\": {:?},\n\t\"dotfiles\": []\n}}\n",
+		target.display().to_string()
+	);
+
+	("json", content)
+}
+
+/// Writes `content` to `path` if nothing exists there yet.
+///
+/// Returns `true` if the file was created.
+fn create_file(path: &Path, content: &str) -> Result<bool> {
+	if path.symlink_metadata().is_ok() {
+		log::info!("Keeping existing {}", path.display());
+		return Ok(false);
+	}
+
+	std::fs::write(path, content).map_err(|err| Error::io(path, err))?;
+
+	Ok(true)
+}
+
+/// Creates a new source directory at `root`.
+///
+/// The starter profile deploys to the home directory of the current user.
+/// Directories and files which already exist are kept as they are.
+///
+/// Returns the paths of all created files and directories.
+///
+/// # Errors
+///
+/// An error is returned if any of the files or directories could not be
+/// created or if `git init` fails.
+pub fn init(root: &Path, options: InitOptions) -> Result<Vec<PathBuf>> {
+	let mut created = Vec::new();
+
+	for dir in [
+		root.to_path_buf(),
+		root.join("profiles"),
+		root.join("dotfiles"),
+	] {
+		if dir.is_dir() {
+			continue;
+		}
+
+		std::fs::create_dir_all(&dir).map_err(|err| Error::io(&dir, err))?;
+		created.push(dir);
+	}
+
+	let home = PathBuf::from(shellexpand::tilde("~").as_ref());
+	let (extension, content) = starter_profile(&home);
+	let profile = root
+		.join("profiles")
+		.join(STARTER_PROFILE_NAME)
+		.with_extension(extension);
+
+	if create_file(&profile, &content)? {
+		created.push(profile);
+	}
+
+	if options.git {
+		if root.join(".git").exists() {
+			log::info!("Keeping existing git repository");
+		} else {
+			let status = Command::new("git")
+				.arg("init")
+				.arg("--quiet")
+				.current_dir(root)
+				.status()
+				.map_err(|err| Error::io(root, err))?;
+
+			if !status.success() {
+				return Err(Error::Command {
+					command: String::from("git init"),
+					status,
+				});
+			}
+
+			created.push(root.join(".git"));
+		}
+
+		let gitignore = root.join(".gitignore");
+		if create_file(&gitignore, GITIGNORE)? {
+			created.push(gitignore);
+		}
+	}
+
+	Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::profile::source::PunktfSource;
+	use crate::profile::Profile;
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn init_source() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		let root = dir.join("source");
+
+		let created = init(&root, InitOptions::default())?;
+		assert_eq!(created.len(), 4);
+
+		assert!(PunktfSource::from_root(root.clone()).is_ok());
+		let profile = Profile::from_file(&created[3])?;
+		assert!(profile.target.is_some());
+		assert!(profile.dotfiles.is_empty());
+
+		// Existing files are kept
+		std::fs::write(&created[3], "dotfiles: []\n")?;
+		assert!(init(&root, InitOptions::default())?.is_empty());
+		assert_eq!(std::fs::read_to_string(&created[3])?, "dotfiles: []\n");
+
+		Ok(())
+	}
+}
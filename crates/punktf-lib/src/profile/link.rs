@@ -1,7 +1,37 @@
 //! Defines definitions for a [`Symlink`].
 
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::profile::select::Host;
+use crate::profile::{MergeMode, Priority};
+use crate::visit::normalize_lexically;
+
+/// Maximum number of links which are followed while checking for cycles
+/// (related: [`detect_cycle`]).
+const MAX_LINK_DEPTH: usize = 40;
+
+/// The kind of link which is created for a [`Symlink`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LinkKind {
+	/// A symbolic link.
+	///
+	/// On windows this requires either the developer mode or administrator
+	/// privileges.
+	#[default]
+	Symlink,
+
+	/// A hard link. The source needs to be a file on the same filesystem as
+	/// the target.
+	Hardlink,
+
+	/// A directory junction. The source needs to be a directory.
+	///
+	/// This is only supported on windows. On other systems a symbolic link is
+	/// created instead.
+	Junction,
+}
 
 /// A symlink to be created during the deployment.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -20,9 +50,238 @@ pub struct Symlink {
 	/// It will only replace existing symlink.
 	#[serde(default = "default_replace_value")]
 	pub replace: bool,
+
+	/// The kind of link to create.
+	#[serde(default)]
+	pub kind: LinkKind,
+
+	/// Priority of the link. Links and dotfiles with a lower priority are not
+	/// allowed to replace an already deployed item with a higher one.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub priority: Option<Priority>,
+
+	/// Merge operation if the target already exists and can not be replaced
+	/// (related: [`Symlink::replace`]).
+	///
+	/// If not set, such a target is never touched.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub merge: Option<MergeMode>,
+
+	/// Condition which needs to match the current machine for the link to be
+	/// deployed. If not set, the link is always deployed.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub when: Option<LinkCondition>,
+}
+
+impl Symlink {
+	/// Checks if the link should be deployed on `host` (related:
+	/// [`Symlink::when`]).
+	pub fn is_enabled_for(&self, host: &Host) -> bool {
+		self.when.as_ref().is_none_or(|when| when.matches(host))
+	}
+}
+
+/// Condition under which a [`Symlink`] is deployed.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinkCondition {
+	/// Operating system of the machine (e.g. `linux`, `macos` or `windows`;
+	/// related: [`std::env::consts::OS`]). It is compared case-insensitive.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub os: Option<String>,
+
+	/// Hostname of the machine. It is compared case-insensitive.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub hostname: Option<String>,
+}
+
+impl LinkCondition {
+	/// Checks if the condition matches `host`.
+	///
+	/// A condition without any criteria matches every host.
+	pub fn matches(&self, host: &Host) -> bool {
+		let os_matches = self
+			.os
+			.as_ref()
+			.is_none_or(|os| os.eq_ignore_ascii_case(&host.os));
+
+		let hostname_matches = self
+			.hostname
+			.as_ref()
+			.is_none_or(|hostname| hostname.eq_ignore_ascii_case(&host.hostname));
+
+		os_matches && hostname_matches
+	}
 }
 
 /// Provides the default value for [`Symlink::replace`].
 const fn default_replace_value() -> bool {
 	true
 }
+
+/// Describes why creating a link would result in a loop (related:
+/// [`detect_cycle`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkCycle {
+	/// The link would point to itself.
+	SelfTarget,
+
+	/// The link would be created inside of the directory it points to.
+	TargetInsideSource,
+
+	/// The source is located inside of the path which is replaced by the
+	/// link.
+	SourceInsideTarget,
+
+	/// The source resolves (through other existing links) to the link
+	/// itself.
+	Cycle,
+}
+
+impl fmt::Display for LinkCycle {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::SelfTarget => f.write_str("link source and target are the same"),
+			Self::TargetInsideSource => f.write_str("link target is inside of the link source"),
+			Self::SourceInsideTarget => f.write_str("link source is inside of the link target"),
+			Self::Cycle => f.write_str("link source resolves to the link target"),
+		}
+	}
+}
+
+/// Resolves `path` as far as possible without requiring it to exist.
+///
+/// The parent directory is canonicalized if it exists, so that paths which
+/// differ only by symlinked directories or `..` components are equal.
+fn resolve_parent(path: &Path) -> PathBuf {
+	let path = normalize_lexically(path);
+
+	match (path.parent(), path.file_name()) {
+		(Some(parent), Some(name)) => parent
+			.canonicalize()
+			.map(|parent| parent.join(name))
+			.unwrap_or(path),
+		_ => path,
+	}
+}
+
+/// Checks if creating a link at `target_path` which points to `source_path`
+/// would result in a loop.
+///
+/// Besides comparing both paths, all links which `source_path` consists of
+/// are followed, as the source might resolve to the target once the link is
+/// created.
+pub fn detect_cycle(source_path: &Path, target_path: &Path) -> Option<LinkCycle> {
+	let target = resolve_parent(target_path);
+	let mut source = resolve_parent(source_path);
+
+	for depth in 0..MAX_LINK_DEPTH {
+		if source == target {
+			return Some(if depth == 0 {
+				LinkCycle::SelfTarget
+			} else {
+				LinkCycle::Cycle
+			});
+		}
+
+		if target.starts_with(&source) {
+			return Some(LinkCycle::TargetInsideSource);
+		}
+
+		if source.starts_with(&target) {
+			return Some(if depth == 0 {
+				LinkCycle::SourceInsideTarget
+			} else {
+				LinkCycle::Cycle
+			});
+		}
+
+		let Ok(next) = std::fs::read_link(&source) else {
+			return None;
+		};
+
+		// Relative links are relative to the directory of the link.
+		source = match source.parent() {
+			Some(parent) => resolve_parent(&parent.join(next)),
+			None => resolve_parent(&next),
+		};
+	}
+
+	// The existing links already form a loop.
+	Some(LinkCycle::Cycle)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn detect_cycle_lexically() {
+		crate::tests::setup_test_env();
+
+		let root = Path::new("/punktf_no_such_dir");
+
+		assert_eq!(
+			detect_cycle(&root.join("a"), &root.join("a")),
+			Some(LinkCycle::SelfTarget)
+		);
+		assert_eq!(
+			detect_cycle(&root.join("a/./b/.."), &root.join("a")),
+			Some(LinkCycle::SelfTarget)
+		);
+		assert_eq!(
+			detect_cycle(&root.join("a"), &root.join("b/../a/c")),
+			Some(LinkCycle::TargetInsideSource)
+		);
+		assert_eq!(
+			detect_cycle(&root.join("a/b/c"), &root.join("a/./b")),
+			Some(LinkCycle::SourceInsideTarget)
+		);
+		assert_eq!(detect_cycle(&root.join("a"), &root.join("ab")), None);
+		assert_eq!(detect_cycle(&root.join("a/b"), &root.join("a/c")), None);
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn detect_cycle_through_links() -> color_eyre::Result<()> {
+		use std::os::unix::fs::symlink;
+
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("real"))?;
+		std::fs::write(dir.join("file"), "")?;
+
+		// `alias` is a relative link to the directory `real`.
+		symlink("real", dir.join("alias"))?;
+		// `back` points to the (not yet existing) link target `target`.
+		symlink("./sub/../target", dir.join("back"))?;
+		// `chain` points to `back`, which points to `target`.
+		symlink(dir.join("back"), dir.join("chain"))?;
+
+		assert_eq!(detect_cycle(&dir.join("file"), &dir.join("target")), None);
+		assert_eq!(detect_cycle(&dir.join("alias"), &dir.join("target")), None);
+		assert_eq!(
+			detect_cycle(&dir.join("alias"), &dir.join("real/target")),
+			Some(LinkCycle::TargetInsideSource)
+		);
+		assert_eq!(
+			detect_cycle(&dir.join("real"), &dir.join("alias/target")),
+			Some(LinkCycle::TargetInsideSource)
+		);
+		assert_eq!(
+			detect_cycle(&dir.join("alias"), &dir.join("real")),
+			Some(LinkCycle::Cycle)
+		);
+		assert_eq!(
+			detect_cycle(&dir.join("back"), &dir.join("target")),
+			Some(LinkCycle::Cycle)
+		);
+		assert_eq!(
+			detect_cycle(&dir.join("chain"), &dir.join("target")),
+			Some(LinkCycle::Cycle)
+		);
+
+		Ok(())
+	}
+}
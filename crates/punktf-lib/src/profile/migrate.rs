@@ -0,0 +1,264 @@
+//! Handling of deprecated profile fields and the migration of profiles to the
+//! current schema.
+//!
+//! Deprecated fields are still accepted when a profile is loaded (e.g. `env`
+//! instead of `variables`), but a warning is logged for each of them. With
+//! [`migrate_file`] a profile can be rewritten to only use the current field
+//! names.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Deserializer};
+
+use crate::error::{Error, Result};
+use crate::profile::Profile;
+
+/// Legacy forms of the
+/// [`Dotfile::overwrite_target`](`crate::profile::dotfile::Dotfile::overwrite_target`)
+/// field.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum LegacyTarget {
+	/// Current form, a plain path (`target: "/home/demo"`).
+	Plain(PathBuf),
+
+	/// Legacy form, a path tagged with `Path` (`target: { Path: "/home/demo" }`).
+	Tagged {
+		/// The target path.
+		#[serde(rename = "Path")]
+		path: PathBuf,
+	},
+}
+
+impl LegacyTarget {
+	/// Checks if this is a deprecated form.
+	const fn is_deprecated(&self) -> bool {
+		matches!(self, Self::Tagged { .. })
+	}
+
+	/// Returns the target path.
+	fn into_path(self) -> PathBuf {
+		match self {
+			Self::Plain(path) | Self::Tagged { path } => path,
+		}
+	}
+}
+
+/// Deserializes a target path which can either be given as plain path or in
+/// the legacy form tagged with `Path`.
+pub(crate) fn deserialize_target<'de, D>(deserializer: D) -> Result<Option<PathBuf>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	Option::<LegacyTarget>::deserialize(deserializer)
+		.map(|target| target.map(LegacyTarget::into_path))
+}
+
+/// A minimal struct to read all deprecated fields of a dotfile.
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct LegacyDotfile {
+	/// Path of the dotfile; used to identify it in messages.
+	path: Option<PathBuf>,
+
+	/// Deprecated name of `variables`.
+	env: Option<IgnoredAny>,
+
+	/// Target which may be given in the deprecated `Path` form.
+	#[serde(alias = "overwrite_target")]
+	target: Option<LegacyTarget>,
+}
+
+/// A minimal struct to read all deprecated fields of a profile.
+///
+/// This is used to detect deprecated fields, as a `serde` alias does not
+/// report which name was used.
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct LegacyProfile {
+	/// Deprecated name of `variables`.
+	env: Option<IgnoredAny>,
+
+	/// Dotfiles of the profile.
+	dotfiles: Vec<LegacyDotfile>,
+}
+
+/// A deprecated field which was found in a profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+	/// Location of the field inside the profile (e.g. `dotfiles[0].env`).
+	pub field: String,
+
+	/// Describes the replacement of the deprecated field.
+	pub replacement: &'static str,
+}
+
+impl fmt::Display for Deprecation {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Field `{}` is deprecated; use {} instead",
+			self.field, self.replacement
+		)
+	}
+}
+
+/// Finds all deprecated fields in the profile `content`.
+///
+/// The format of `content` is determined by the file extension of `path`.
+/// If the content can not be parsed, no deprecations are returned.
+pub fn deprecations(path: &Path, content: &str) -> Vec<Deprecation> {
+	let legacy: LegacyProfile = match super::deserialize(path, content) {
+		Ok(legacy) => legacy,
+		Err(err) => {
+			log::debug!(
+				"[{}] Failed to check for deprecations: {err}",
+				path.display()
+			);
+			return Vec::new();
+		}
+	};
+
+	let mut deprecations = Vec::new();
+
+	if legacy.env.is_some() {
+		deprecations.push(Deprecation {
+			field: String::from("env"),
+			replacement: "`variables`",
+		});
+	}
+
+	for (idx, dotfile) in legacy.dotfiles.into_iter().enumerate() {
+		let name = dotfile
+			.path
+			.map(|path| format!("dotfiles[{}]", path.display()))
+			.unwrap_or_else(|| format!("dotfiles[{idx}]"));
+
+		if dotfile.env.is_some() {
+			deprecations.push(Deprecation {
+				field: format!("{name}.env"),
+				replacement: "`variables`",
+			});
+		}
+
+		if dotfile
+			.target
+			.as_ref()
+			.is_some_and(LegacyTarget::is_deprecated)
+		{
+			deprecations.push(Deprecation {
+				field: format!("{name}.target.Path"),
+				replacement: "a plain path (`overwrite_target: <path>`)",
+			});
+		}
+	}
+
+	deprecations
+}
+
+/// Serializes `profile` with the serializer guessed by the file extension of
+/// `path`.
+pub(crate) fn serialize(path: &Path, profile: &Profile) -> Result<String> {
+	// Allowed in case no feature is present.
+	#[allow(unused_variables)]
+	let extension = path.extension().ok_or_else(|| Error::UnsupportedFormat {
+		path: path.to_path_buf(),
+	})?;
+
+	let serialize_error = |err: &dyn fmt::Display| Error::Serialize {
+		path: path.to_path_buf(),
+		reason: err.to_string(),
+	};
+
+	if extension.eq_ignore_ascii_case("json") {
+		let mut content =
+			serde_json::to_string_pretty(profile).map_err(|err| serialize_error(&err))?;
+		content.push('\n');
+		return Ok(content);
+	}
+
+	#[cfg(feature = "profile-yaml")]
+	{
+		if extension.eq_ignore_ascii_case("yaml") || extension.eq_ignore_ascii_case("yml") {
+			return serde_yaml::to_string(profile).map_err(|err| serialize_error(&err));
+		}
+	}
+
+	Err(Error::UnsupportedFormat {
+		path: path.to_path_buf(),
+	})
+}
+
+/// Rewrites the profile at `path` to the current schema.
+///
+/// The file is only rewritten if it contains deprecated fields. As the
+/// profile is serialized again, comments and the original formatting are
+/// not retained.
+///
+/// If `dry_run` is set, the file is not modified.
+///
+/// Returns all deprecated fields which were found.
+///
+/// # Errors
+///
+/// An error is returned if the profile could not be read, parsed or written.
+pub fn migrate_file(path: &Path, dry_run: bool) -> Result<Vec<Deprecation>> {
+	let content = std::fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+
+	let deprecations = deprecations(path, &content);
+
+	if deprecations.is_empty() || dry_run {
+		return Ok(deprecations);
+	}
+
+	let profile: Profile = super::deserialize(path, &content)?;
+
+	std::fs::write(path, serialize(path, &profile)?).map_err(|err| Error::io(path, err))?;
+
+	Ok(deprecations)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn migrate_legacy_fields() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let content = r#"
+env:
+  OS: linux
+dotfiles:
+  - path: init.vim
+    env:
+      APP: nvim
+    target:
+      Path: /home/demo/.config/nvim
+  - path: .bashrc
+    target: /home/demo
+"#;
+
+		let dir = crate::tests::test_dir();
+		let path = dir.join("profile.yaml");
+		std::fs::write(&path, content)?;
+
+		let profile = Profile::from_file(&path)?;
+		assert!(profile.variables.is_some());
+		assert!(profile.dotfiles[0].variables.is_some());
+		assert_eq!(
+			profile.dotfiles[0].overwrite_target,
+			Some(PathBuf::from("/home/demo/.config/nvim"))
+		);
+
+		assert_eq!(deprecations(&path, content).len(), 3);
+		assert_eq!(migrate_file(&path, false)?.len(), 3);
+		assert_eq!(Profile::from_file(&path)?, profile);
+		assert!(migrate_file(&path, false)?.is_empty());
+
+		Ok(())
+	}
+}
@@ -1,34 +1,60 @@
 //! Defines profiles and ways to layer multiple of them.
 
+pub mod archive;
+pub mod config;
 pub mod dotfile;
+pub mod encryption;
 pub mod hook;
+pub mod import;
+pub mod init;
 pub mod link;
+pub mod migrate;
+pub mod select;
 pub mod source;
 pub mod transform;
+pub mod validate;
 pub mod variables;
 
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
-use std::fs::File;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 
-use color_eyre::eyre::{bail, eyre, Context};
-use color_eyre::Result;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
+use crate::env::PunktfEnv;
+use crate::error::{Error, Result};
+use crate::profile::config::IgnorePattern;
 use crate::profile::hook::Hook;
 use crate::profile::link::Symlink;
 use crate::profile::transform::ContentTransformer;
-use crate::profile::variables::{Variables, Vars};
-use crate::profile::{dotfile::Dotfile, source::PunktfSource};
+use crate::profile::validate::ProfileErrors;
+use crate::profile::variables::{Value, Variables, Vars};
+use crate::profile::{
+	dotfile::{Dotfile, DotfileDefaults},
+	source::PunktfSource,
+};
+use crate::template::limits::Limits;
+use crate::template::VarEnvSet;
+
+/// Name of the profile layer which contains the built-in variables (related:
+/// [`LayeredProfile::add_builtin_variables`]).
+pub const BUILTIN_LAYER_NAME: &str = "builtin";
+
+/// Name of the directory in the root of the
+/// [source directory](`crate::profile::source::PunktfSource`) which contains
+/// the variable overlays (related: [`LayeredProfile::add_variable_overlays`]).
+pub const VARIABLE_OVERLAY_DIR_NAME: &str = "variables.d";
 
 /// This enum represents all available merge modes `punktf` supports. The merge
 /// mode is important when a file already exists at the target location of a
 /// [`Dotfile`](`crate::profile::dotfile::Dotfile`).
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub enum MergeMode {
 	/// Overwrites the existing file.
+	#[default]
 	Overwrite,
 
 	/// Keeps the existing file.
@@ -36,14 +62,109 @@ pub enum MergeMode {
 
 	/// Asks the user for input to decide what to do.
 	Ask,
+
+	/// Merges the changes made to the existing file since the last deployment
+	/// with the new content (three-way merge). Changes made on both sides are
+	/// marked with conflict markers (related:
+	/// [`merge`](`crate::visit::deploy::merge`)).
+	Merge,
+}
+
+/// This enum represents all available strategies to deploy a
+/// [`Dotfile`](`crate::profile::dotfile::Dotfile`) to the target location.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum DeployStrategy {
+	/// Writes the (resolved) contents directly to the target location.
+	#[default]
+	Copy,
+
+	/// Writes the (resolved) contents to a file inside the cache directory
+	/// managed by `punktf` and creates a symlink at the target location which
+	/// points to the cached file.
+	///
+	/// This makes it possible to see which files are managed by `punktf`
+	/// (e.g. with `ls -l`) while still supporting templates.
+	CacheLink,
+}
+
+/// Defines whether the contents of a
+/// [`Dotfile`](`crate::profile::dotfile::Dotfile`) are treated as binary data.
+///
+/// Binary data is copied as is, without any template processing or content
+/// transformation. In a profile, it is given as `true` (binary), `false`
+/// (text) or `auto`.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "BinaryModeRepr", into = "BinaryModeRepr")]
+pub enum BinaryMode {
+	/// The contents are treated as binary data if they are not valid UTF-8.
+	#[default]
+	Auto,
+
+	/// The contents are always treated as binary data.
+	Binary,
+
+	/// The contents are always treated as text. A dotfile which is not valid
+	/// UTF-8 fails to deploy.
+	Text,
 }
 
-impl Default for MergeMode {
-	fn default() -> Self {
-		Self::Overwrite
+/// The serialized form of a [`BinaryMode`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(untagged)]
+enum BinaryModeRepr {
+	/// Either [`BinaryMode::Binary`] (`true`) or [`BinaryMode::Text`] (`false`).
+	Fixed(bool),
+
+	/// [`BinaryMode::Auto`].
+	Auto(BinaryModeAuto),
+}
+
+/// The keyword for [`BinaryMode::Auto`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+enum BinaryModeAuto {
+	/// The `auto` keyword.
+	#[serde(rename = "auto", alias = "Auto")]
+	Auto,
+}
+
+impl From<BinaryModeRepr> for BinaryMode {
+	fn from(value: BinaryModeRepr) -> Self {
+		match value {
+			BinaryModeRepr::Fixed(true) => Self::Binary,
+			BinaryModeRepr::Fixed(false) => Self::Text,
+			BinaryModeRepr::Auto(_) => Self::Auto,
+		}
 	}
 }
 
+impl From<BinaryMode> for BinaryModeRepr {
+	fn from(value: BinaryMode) -> Self {
+		match value {
+			BinaryMode::Auto => Self::Auto(BinaryModeAuto::Auto),
+			BinaryMode::Binary => Self::Fixed(true),
+			BinaryMode::Text => Self::Fixed(false),
+		}
+	}
+}
+
+/// Access control list (ACL) operation which is applied to a deployed
+/// [`Dotfile`](`crate::profile::dotfile::Dotfile`) after it was written.
+///
+/// This is only supported on windows and ignored on all other platforms.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum WindowsAcl {
+	/// Removes all explicit entries and re-applies the ACL inherited from the
+	/// parent directory.
+	Inherit,
+
+	/// Sets the security descriptor given in the
+	/// [SDDL](https://learn.microsoft.com/en-us/windows/win32/secauthz/security-descriptor-definition-language)
+	/// format.
+	Sddl(String),
+}
+
 /// This struct represents the priority a
 /// [`Dotfile`](`crate::profile::dotfile::Dotfile`)
 /// can have. A bigger value means a higher priority. Dotfiles with lower priority
@@ -76,19 +197,91 @@ pub struct Profile {
 	pub extends: Vec<String>,
 
 	/// Variables of the profile. Each dotfile will have this environment.
-	#[serde(skip_serializing_if = "Option::is_none", default)]
+	///
+	/// The deprecated name `env` is accepted as well.
+	#[serde(alias = "env", skip_serializing_if = "Option::is_none", default)]
 	pub variables: Option<Variables>,
 
+	/// Order in which the variable environments are searched for template
+	/// variables which do not specify any explicitly (e.g. `{{NAME}}`).
+	///
+	/// Defaults to `[Dotfile, Profile]`.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub variable_resolution_order: Option<VarEnvSet>,
+
+	/// Resource limits for the parsing and resolving of template dotfiles.
+	///
+	/// Each limit which is not set uses its default (related:
+	/// [`Limits::new`](`crate::template::limits::Limits::new`)).
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub template_limits: Option<Limits>,
+
 	/// Content transform of the profile. Each dotfile will have these applied.
 	#[serde(skip_serializing_if = "Vec::is_empty", default)]
 	pub transformers: Vec<ContentTransformer>,
 
+	/// Content transformer which are applied to all dotfiles with a specific
+	/// file extension (e.g. `.sh: [LineTerminator: LF]`).
+	///
+	/// The extension is matched against the target path of the dotfile,
+	/// ignoring the leading dot and the case. These are applied after
+	/// [`Profile::transformers`] and before the transformers of the dotfile.
+	#[serde(skip_serializing_if = "HashMap::is_empty", default)]
+	pub transformers_by_extension: HashMap<String, Vec<ContentTransformer>>,
+
 	/// Target root path of the deployment. Will be used as file stem for the dotfiles
 	/// when not overwritten by
 	/// [`Dotfile::overwrite_target`](`crate::profile::dotfile::Dotfile::overwrite_target`).
 	#[serde(skip_serializing_if = "Option::is_none", default)]
 	pub target: Option<PathBuf>,
 
+	/// Additional named target root paths (e.g. `config: ~/.config`).
+	///
+	/// A dotfile is deployed to one of these instead of
+	/// [`Profile::target`] by setting
+	/// [`Dotfile::target_name`](`crate::profile::dotfile::Dotfile::target_name`).
+	#[serde(skip_serializing_if = "HashMap::is_empty", default)]
+	pub targets: HashMap<String, PathBuf>,
+
+	/// Creates the [target](`Profile::target`) directory if it does not exist.
+	///
+	/// If not set and the target directory does not exist, the deployment
+	/// fails before anything is deployed.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub create_target: Option<bool>,
+
+	/// Preserves the permission bits, ownership and extended attributes of
+	/// already existing target files when they are overwritten.
+	///
+	/// Can be overwritten for each dotfile with
+	/// [`Dotfile::preserve_attributes`](`crate::profile::dotfile::Dotfile::preserve_attributes`).
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub preserve_attributes: Option<bool>,
+
+	/// Follows symlinks inside the `dotfiles` directory instead of rejecting
+	/// them. This allows to organize the source directory with internal links.
+	///
+	/// Can be overwritten for each dotfile with
+	/// [`Dotfile::follow_source_symlinks`](`crate::profile::dotfile::Dotfile::follow_source_symlinks`).
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub follow_source_symlinks: Option<bool>,
+
+	/// Allows dotfiles to reference paths outside of the `dotfiles` directory
+	/// (e.g. absolute paths or paths containing `..`), e.g. to deploy files
+	/// from a git submodule next to the source directory.
+	///
+	/// Defaults to `false`.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub allow_external: Option<bool>,
+
+	/// Marks the profile as (un)trusted. No commands defined by an untrusted
+	/// profile (e.g. hooks) are executed during a deployment. This is useful
+	/// to cautiously try dotfiles of other people.
+	///
+	/// Defaults to `true`.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub trusted: Option<bool>,
+
 	/// Hook will be executed once before the deployment begins. If the hook fails
 	/// the deployment will not be continued.
 	#[serde(skip_serializing_if = "Vec::is_empty", default)]
@@ -98,6 +291,48 @@ pub struct Profile {
 	#[serde(skip_serializing_if = "Vec::is_empty", default)]
 	pub post_hooks: Vec<Hook>,
 
+	/// Hook will be executed once after the deployment finished, but only if
+	/// the deployment failed.
+	///
+	/// The reason of the failure is passed with the environment variable
+	/// `PUNKTF_FAILURE_REASON` and the target paths of all failed items with
+	/// `PUNKTF_FAILED_ITEMS` (one path per line).
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub on_failure_hooks: Vec<Hook>,
+
+	/// Hooks which will be executed before each file and link is deployed.
+	///
+	/// A JSON description of the item (e.g. its paths and if it is a
+	/// template) is passed on stdin. If a hook fails, the item is not
+	/// deployed and marked as failed.
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub pre_dotfile_hooks: Vec<Hook>,
+
+	/// Hooks which will be executed after each file and link was processed.
+	///
+	/// The same JSON description as for
+	/// [`Profile::pre_dotfile_hooks`] together with the status of the item
+	/// is passed on stdin. If a hook fails, the item is marked as failed.
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub post_dotfile_hooks: Vec<Hook>,
+
+	/// Glob patterns which limit the [`Profile::pre_dotfile_hooks`] and
+	/// [`Profile::post_dotfile_hooks`] to matching items (related:
+	/// [`IgnorePattern`]). Files are matched by their path relative to the
+	/// `dotfiles` directory and links by their source path.
+	///
+	/// If empty, the hooks are executed for all items.
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub dotfile_hook_patterns: Vec<IgnorePattern>,
+
+	/// Defaults for fields of the dotfiles, which are applied to every
+	/// dotfile that does not specify the field itself.
+	///
+	/// The defaults of the profile which defines a dotfile take precedence
+	/// over the ones of the other profiles of the extend chain.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub defaults: Option<DotfileDefaults>,
+
 	/// Dotfiles which will be deployed.
 	#[serde(skip_serializing_if = "Vec::is_empty", default)]
 	pub dotfiles: Vec<Dotfile>,
@@ -105,6 +340,17 @@ pub struct Profile {
 	/// Symlinks which will be deployed.
 	#[serde(rename = "links", skip_serializing_if = "Vec::is_empty", default)]
 	pub symlinks: Vec<Symlink>,
+
+	/// Auxiliary files or directories (e.g. wallpapers or fonts) which will
+	/// be deployed.
+	///
+	/// The paths are relative to the `assets` directory of the source
+	/// directory (related:
+	/// [`PunktfSource::assets`](`crate::profile::source::PunktfSource::assets`)).
+	/// Extra paths are deployed like dotfiles, but are ignored when the
+	/// deployed items are compared with the source (e.g. by `diff`).
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub extra_paths: Vec<Dotfile>,
 }
 
 impl Profile {
@@ -117,64 +363,117 @@ impl Profile {
 	///
 	/// An error is returned if the file does not exist or could not be read.
 	/// An error is returned if the file extension is unknown or missing.
+	/// If the profile could not be parsed, [`Error::Profile`] with all
+	/// problems found is returned.
 	pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
 		let path = path.as_ref();
 
 		/// Inner function is used to reduce monomorphizes as path here is a
 		/// concrete type and no generic one.
 		fn from_file_inner(path: &Path) -> Result<Profile> {
-			// Allowed in case no feature is present.
-			#[allow(unused_variables)]
-			let file = File::open(path)?;
-
-			let extension = path.extension().ok_or_else(|| {
-				std::io::Error::new(
-					std::io::ErrorKind::InvalidData,
-					"Failed to get file extension for profile",
-				)
-			})?;
-
-			#[cfg(feature = "profile-json")]
-			{
-				if extension.eq_ignore_ascii_case("json") {
-					return Profile::from_json_file(file);
+			let content = std::fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+
+			Profile::from_content(path, &content)
+		}
+
+		from_file_inner(path)
+	}
+
+	/// Tries to parse a profile which is not read from a file (e.g. from
+	/// stdin).
+	///
+	/// `origin` is only used in error messages. The content is parsed as YAML
+	/// (which includes JSON) if the `profile-yaml` feature is enabled,
+	/// otherwise as JSON.
+	///
+	/// # Errors
+	///
+	/// If the profile could not be parsed, [`Error::Profile`] with all
+	/// problems found is returned.
+	pub fn parse(origin: &str, content: &str) -> Result<Self> {
+		let extension = if cfg!(feature = "profile-yaml") {
+			"yaml"
+		} else {
+			"json"
+		};
+
+		Self::from_content(&PathBuf::from(format!("<{origin}>.{extension}")), content)
+	}
+
+	/// Parses the profile `content` with the deserializer guessed by the file
+	/// extension of `path`.
+	fn from_content(path: &Path, content: &str) -> Result<Self> {
+		let profile = match deserialize(path, content) {
+			Ok(profile) => profile,
+			Err(err) => {
+				// Try to find all problems instead of only the first one.
+				let problems = validate::schema_problems(path, content);
+
+				if problems.is_empty() {
+					return Err(err);
 				}
+
+				let mut errors = ProfileErrors::default();
+				errors.add_problems(path, content, &problems);
+				return Err(errors.into());
 			}
+		};
 
-			#[cfg(feature = "profile-yaml")]
-			{
-				if extension.eq_ignore_ascii_case("yaml") || extension.eq_ignore_ascii_case("yml") {
-					return Profile::from_yaml_file(file);
-				}
+		for deprecation in migrate::deprecations(path, content) {
+			log::warn!("[{}] {deprecation}", path.display());
+		}
+
+		Ok(profile)
+	}
+
+	/// Merges the [variables files](`Dotfile::variables_file`) of all dotfiles
+	/// and extra paths into their variables. Relative paths are resolved
+	/// against `root`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if a file could not be read or parsed.
+	fn load_variables_files(&mut self, root: &Path) -> Result<()> {
+		for dotfile in self.dotfiles.iter_mut().chain(self.extra_paths.iter_mut()) {
+			let Some(file) = dotfile.variables_file.take() else {
+				continue;
+			};
+
+			let path = root.join(file);
+			let content = std::fs::read_to_string(&path).map_err(|err| Error::io(&path, err))?;
+			let mut variables: Variables = deserialize(&path, &content)?;
+
+			// Variables defined inline take precedence.
+			if let Some(inline) = dotfile.variables.take() {
+				variables.inner.extend(inline.inner);
 			}
 
-			Err(eyre!(
-				"Found unsupported file extension for profile (extension: {:?})",
-				extension
-			))
+			dotfile.variables = Some(variables);
 		}
 
-		from_file_inner(path).wrap_err(format!(
-			"Failed to process profile at path `{}`",
-			path.display()
-		))
+		Ok(())
 	}
+}
 
-	/// Tries to load a profile from a json file.
-	#[cfg(feature = "profile-json")]
-	fn from_json_file(file: File) -> Result<Self> {
-		serde_json::from_reader(&file).map_err(|err| {
-			color_eyre::Report::msg(err).wrap_err("Failed to parse profile from json content.")
-		})
+/// Deserializes the profile `content` with the deserializer guessed by the
+/// file extension of `path`.
+fn deserialize<T: DeserializeOwned>(path: &Path, content: &str) -> Result<T> {
+	let extension = path.extension().unwrap_or_default();
+
+	if extension.eq_ignore_ascii_case("json") {
+		return serde_json::from_str(content).map_err(|err| Error::parse(path, err));
 	}
 
-	/// Tries to load a profile from a yaml file.
 	#[cfg(feature = "profile-yaml")]
-	fn from_yaml_file(file: File) -> Result<Self> {
-		serde_yaml::from_reader(file).map_err(|err| {
-			color_eyre::Report::msg(err).wrap_err("Failed to parse profile from yaml content.")
-		})
+	{
+		if extension.eq_ignore_ascii_case("yaml") || extension.eq_ignore_ascii_case("yml") {
+			return serde_yaml::from_str(content).map_err(|err| Error::parse(path, err));
+		}
 	}
+
+	Err(Error::UnsupportedFormat {
+		path: path.to_path_buf(),
+	})
 }
 
 /// Stores variables defined on different layers.
@@ -184,15 +483,26 @@ pub struct LayeredVariables {
 	/// Stores the variables together with the index, which indexed
 	/// [`LayeredProfile::profile_names`](`crate::profile::LayeredProfile::profile_names`)
 	/// to retrieve the name of the profile, the variable came from.
-	pub inner: HashMap<String, (usize, String)>,
+	pub inner: HashMap<String, (usize, Value)>,
 }
 
 impl Vars for LayeredVariables {
-	fn var<K>(&self, key: K) -> Option<&str>
+	fn var<K>(&self, key: K) -> Option<Cow<'_, str>>
 	where
 		K: AsRef<str>,
 	{
-		self.inner.get(key.as_ref()).map(|(_, value)| value.deref())
+		self.inner
+			.get(key.as_ref())
+			.map(|(_, value)| value.as_str())
+	}
+
+	fn value<K>(&self, key: K) -> Option<Cow<'_, Value>>
+	where
+		K: AsRef<str>,
+	{
+		self.inner
+			.get(key.as_ref())
+			.map(|(_, value)| Cow::Borrowed(value))
 	}
 }
 
@@ -208,18 +518,88 @@ pub struct LayeredProfile {
 	/// top.
 	pub target: Option<(usize, PathBuf)>,
 
+	/// The named targets collected from all profiles of the extend chain.
+	///
+	/// For each name the first value found by traversing the extend chain
+	/// from the top is used.
+	pub targets: HashMap<String, (usize, PathBuf)>,
+
+	/// If the target directory should be created if it does not exist.
+	///
+	/// This is the first value found by traversing the extend chain from the
+	/// top.
+	pub create_target: Option<(usize, bool)>,
+
+	/// If the attributes of existing target files should be preserved.
+	///
+	/// This is the first value found by traversing the extend chain from the
+	/// top.
+	pub preserve_attributes: Option<(usize, bool)>,
+
+	/// If symlinks inside the `dotfiles` directory should be followed.
+	///
+	/// This is the first value found by traversing the extend chain from the
+	/// top.
+	pub follow_source_symlinks: Option<(usize, bool)>,
+
+	/// If dotfiles may reference paths outside of the `dotfiles` directory.
+	///
+	/// This is the first value found by traversing the extend chain from the
+	/// top.
+	pub allow_external: Option<(usize, bool)>,
+
+	/// If commands defined by the profile may be executed.
+	///
+	/// This is the first value found by traversing the extend chain from the
+	/// top.
+	pub trusted: Option<(usize, bool)>,
+
 	/// The variables collected from all profiles of the extend chain.
 	pub variables: LayeredVariables,
 
+	/// The order in which variable environments are searched by default.
+	///
+	/// This is the first value found by traversing the extend chain from the
+	/// top.
+	pub variable_resolution_order: Option<(usize, VarEnvSet)>,
+
+	/// The resource limits for templates.
+	///
+	/// This is the first value found by traversing the extend chain from the
+	/// top.
+	pub template_limits: Option<(usize, Limits)>,
+
 	/// The content transformer collected from all profiles of the extend chain.
 	pub transformers: Vec<(usize, ContentTransformer)>,
 
+	/// The content transformer for each file extension collected from all
+	/// profiles of the extend chain.
+	///
+	/// The keys are stored without a leading dot and in lowercase. For each
+	/// extension the first value found by traversing the extend chain from the
+	/// top is used.
+	pub transformers_by_extension: HashMap<String, (usize, Vec<ContentTransformer>)>,
+
 	/// The pre-hooks collected from all profiles of the extend chain.
 	pub pre_hooks: Vec<(usize, Hook)>,
 
 	/// The post-hooks collected from all profiles of the extend chain.
 	pub post_hooks: Vec<(usize, Hook)>,
 
+	/// The on-failure-hooks collected from all profiles of the extend chain.
+	pub on_failure_hooks: Vec<(usize, Hook)>,
+
+	/// The pre-dotfile-hooks collected from all profiles of the extend chain.
+	pub pre_dotfile_hooks: Vec<(usize, Hook)>,
+
+	/// The post-dotfile-hooks collected from all profiles of the extend
+	/// chain.
+	pub post_dotfile_hooks: Vec<(usize, Hook)>,
+
+	/// The patterns for dotfile hooks collected from all profiles of the
+	/// extend chain.
+	pub dotfile_hook_patterns: Vec<(usize, IgnorePattern)>,
+
 	/// The dotfiles collected from all profiles of the extend chain.
 	///
 	/// The index indexes into
@@ -233,6 +613,18 @@ pub struct LayeredProfile {
 	/// [`LayeredProfile::profile_names`](`crate::profile::LayeredProfile::profile_names`)
 	/// to retrieve the name of the profile from which the link came from.
 	pub symlinks: Vec<(usize, Symlink)>,
+
+	/// The extra paths collected from all profiles of the extend chain.
+	///
+	/// The index indexes into
+	/// [`LayeredProfile::profile_names`](`crate::profile::LayeredProfile::profile_names`)
+	/// to retrieve the name of the profile from which the extra path came
+	/// from.
+	pub extra_paths: Vec<(usize, Dotfile)>,
+
+	/// The environment variables which are passed to hooks and templates
+	/// (related: [`LayeredProfile::add_builtin_variables`]).
+	pub env: PunktfEnv,
 }
 
 impl LayeredProfile {
@@ -254,13 +646,194 @@ impl LayeredProfile {
 		self.target.as_ref().map(|(_, path)| path.deref())
 	}
 
+	/// Returns the path of the named target `name`.
+	pub fn named_target(&self, name: &str) -> Option<&Path> {
+		self.targets.get(name).map(|(_, path)| path.deref())
+	}
+
+	/// Returns `true` if the target directory should be created if it does not
+	/// exist.
+	pub fn create_target(&self) -> bool {
+		self.create_target
+			.map(|(_, create)| create)
+			.unwrap_or_default()
+	}
+
+	/// Returns `true` if the attributes of existing target files should be
+	/// preserved.
+	pub fn preserve_attributes(&self) -> bool {
+		self.preserve_attributes
+			.map(|(_, preserve)| preserve)
+			.unwrap_or_default()
+	}
+
+	/// Returns `true` if dotfiles may reference paths outside of the
+	/// `dotfiles` directory.
+	pub fn allow_external(&self) -> bool {
+		self.allow_external
+			.map(|(_, allow)| allow)
+			.unwrap_or_default()
+	}
+
+	/// Returns `true` if commands defined by the profile (e.g. hooks) may be
+	/// executed.
+	pub fn trusted(&self) -> bool {
+		self.trusted.map(|(_, trusted)| trusted).unwrap_or(true)
+	}
+
+	/// Returns `true` if symlinks inside the `dotfiles` directory should be
+	/// followed.
+	pub fn follow_source_symlinks(&self) -> bool {
+		self.follow_source_symlinks
+			.map(|(_, follow)| follow)
+			.unwrap_or_default()
+	}
+
 	/// Returns all collected variables for the profile.
 	pub const fn variables(&self) -> &LayeredVariables {
 		&self.variables
 	}
 
+	/// Adds the built-in variables to the variables of the profile:
+	///
+	/// - `PUNKTF_OS`: Operating system of the machine (e.g. `linux`)
+	/// - `PUNKTF_ARCH`: Architecture of the machine (e.g. `x86_64`)
+	/// - `PUNKTF_HOSTNAME`: Hostname of the machine
+	/// - `PUNKTF_PROFILE`: Name of the profile (`profile_name`)
+	/// - `PUNKTF_SOURCE`: Root directory of the `source`
+	/// - `PUNKTF_TARGET`: Target directory of the profile (only if defined)
+	///
+	/// This makes them available to templates without relying on the process
+	/// environment. They are added as a new layer named [`BUILTIN_LAYER_NAME`], so
+	/// variables defined by any profile take precedence. As the value of
+	/// `PUNKTF_TARGET` is taken from [`LayeredProfile::target_path`], this
+	/// should be called once all layers were added.
+	///
+	/// Additionally, the [environment](`LayeredProfile::env`) passed to hooks
+	/// and templates is set up for the profile (related:
+	/// [`PunktfEnv::for_profile`]).
+	pub fn add_builtin_variables(&mut self, source: &PunktfSource, profile_name: &str) {
+		let host = select::Host::current();
+
+		let mut values = vec![
+			("PUNKTF_OS", host.os),
+			("PUNKTF_ARCH", std::env::consts::ARCH.to_string()),
+			("PUNKTF_HOSTNAME", host.hostname),
+			("PUNKTF_PROFILE", profile_name.to_string()),
+			("PUNKTF_SOURCE", source.root().display().to_string()),
+		];
+
+		if let Some(target) = self.target_path() {
+			values.push(("PUNKTF_TARGET", target.display().to_string()));
+		}
+
+		let idx = self.profile_names.len();
+		self.profile_names.push(String::from(BUILTIN_LAYER_NAME));
+
+		for (name, value) in values {
+			self.variables
+				.inner
+				.entry(name.to_string())
+				.or_insert((idx, Value::String(value)));
+		}
+
+		self.env = PunktfEnv::for_profile(source, self.target_path(), profile_name);
+	}
+
+	/// Returns the environment variables which are passed to hooks and
+	/// templates.
+	pub const fn env(&self) -> &PunktfEnv {
+		&self.env
+	}
+
+	/// Merges the machine specific variable overlays of `source` into the
+	/// variables of the profile.
+	///
+	/// The overlays are read from the directory [`VARIABLE_OVERLAY_DIR_NAME`]
+	/// in the root of `source`:
+	///
+	/// - `<os>.yaml`: Applies to all machines with the operating system `os`
+	///   (e.g. `linux`)
+	/// - `<hostname>.yaml`: Applies only to the machine with the hostname
+	///   `hostname`
+	///
+	/// Besides `.yaml`, the extensions `.yml` and `.json` are supported.
+	/// Each file contains a plain mapping of variable names to values.
+	///
+	/// The overlays are added as new layers (named after their path relative
+	/// to `source`) which take precedence over all other variables. Variables
+	/// of the hostname overlay take precedence over the ones of the os
+	/// overlay. Missing overlays are skipped.
+	///
+	/// # Errors
+	///
+	/// An error is returned if an overlay exists but could not be read or
+	/// parsed.
+	pub fn add_variable_overlays(&mut self, source: &PunktfSource) -> Result<()> {
+		self.add_variable_overlays_for(source, &select::Host::current())
+	}
+
+	/// Merges the variable overlays of `source` which apply to `host` (related:
+	/// [`LayeredProfile::add_variable_overlays`]).
+	fn add_variable_overlays_for(
+		&mut self,
+		source: &PunktfSource,
+		host: &select::Host,
+	) -> Result<()> {
+		let dir = source.root().join(VARIABLE_OVERLAY_DIR_NAME);
+
+		if !dir.is_dir() {
+			return Ok(());
+		}
+
+		// Applied in order of increasing precedence.
+		for stem in [&host.os, &host.hostname] {
+			let Some(path) = ["yaml", "yml", "json"]
+				.into_iter()
+				// Not using `with_extension`, as hostnames may contain dots.
+				.map(|extension| dir.join(format!("{stem}.{extension}")))
+				.find(|path| path.is_file())
+			else {
+				continue;
+			};
+
+			let content = std::fs::read_to_string(&path).map_err(|err| Error::io(&path, err))?;
+			let variables: Variables = deserialize(&path, &content)?;
+
+			let name = path
+				.strip_prefix(source.root())
+				.unwrap_or(&path)
+				.display()
+				.to_string();
+
+			let idx = self.profile_names.len();
+			self.profile_names.push(name);
+
+			for (name, value) in variables.inner {
+				self.variables.inner.insert(name, (idx, value));
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Returns the order in which variable environments are searched for
+	/// template variables which do not specify any explicitly.
+	pub fn variable_resolution_order(&self) -> VarEnvSet {
+		self.variable_resolution_order
+			.map(|(_, order)| order)
+			.unwrap_or_default()
+	}
+
+	/// Returns the resource limits for the parsing and resolving of templates.
+	pub fn template_limits(&self) -> Limits {
+		self.template_limits
+			.map(|(_, limits)| limits)
+			.unwrap_or_default()
+	}
+
 	/// Returns all the count of collected transformers for the profile.
-	pub fn transformers_len(&self) -> usize {
+	pub const fn transformers_len(&self) -> usize {
 		self.transformers.len()
 	}
 
@@ -269,6 +842,18 @@ impl LayeredProfile {
 		self.transformers.iter().map(|(_, transformer)| transformer)
 	}
 
+	/// Returns the content transformer which are configured for the file
+	/// extension of `path`.
+	pub fn extension_transformers(&self, path: &Path) -> &[ContentTransformer] {
+		path.extension()
+			.and_then(|extension| {
+				self.transformers_by_extension
+					.get(&normalize_extension(&extension.to_string_lossy()))
+			})
+			.map(|(_, transformers)| transformers.as_slice())
+			.unwrap_or_default()
+	}
+
 	/// Returns all collected pre-hooks for the profile.
 	pub fn pre_hooks(&self) -> impl Iterator<Item = &Hook> {
 		self.pre_hooks.iter().map(|(_, hook)| hook)
@@ -279,6 +864,31 @@ impl LayeredProfile {
 		self.post_hooks.iter().map(|(_, hook)| hook)
 	}
 
+	/// Returns all collected on-failure-hooks for the profile.
+	pub fn on_failure_hooks(&self) -> impl Iterator<Item = &Hook> {
+		self.on_failure_hooks.iter().map(|(_, hook)| hook)
+	}
+
+	/// Returns all collected pre-dotfile-hooks for the profile.
+	pub fn pre_dotfile_hooks(&self) -> impl Iterator<Item = &Hook> {
+		self.pre_dotfile_hooks.iter().map(|(_, hook)| hook)
+	}
+
+	/// Returns all collected post-dotfile-hooks for the profile.
+	pub fn post_dotfile_hooks(&self) -> impl Iterator<Item = &Hook> {
+		self.post_dotfile_hooks.iter().map(|(_, hook)| hook)
+	}
+
+	/// Checks if the dotfile hooks are executed for the item at `path`
+	/// (related: [`Profile::dotfile_hook_patterns`]).
+	pub fn runs_dotfile_hooks(&self, path: &Path) -> bool {
+		self.dotfile_hook_patterns.is_empty()
+			|| self
+				.dotfile_hook_patterns
+				.iter()
+				.any(|(_, pattern)| pattern.matches(path))
+	}
+
 	/// Returns all collected dotfiles for the profile.
 	pub fn dotfiles(&self) -> impl Iterator<Item = &Dotfile> {
 		self.dotfiles.iter().map(|(_, dotfile)| dotfile)
@@ -288,6 +898,11 @@ impl LayeredProfile {
 	pub fn symlinks(&self) -> impl Iterator<Item = &Symlink> {
 		self.symlinks.iter().map(|(_, symlink)| symlink)
 	}
+
+	/// Returns all collected extra paths for the profile.
+	pub fn extra_paths(&self) -> impl Iterator<Item = &Dotfile> {
+		self.extra_paths.iter().map(|(_, extra_path)| extra_path)
+	}
 }
 
 /// Collects different profiles from multiple layers.
@@ -319,6 +934,58 @@ impl LayeredProfileBuilder {
 				.map(move |target| (idx, target.to_path_buf()))
 		});
 
+		let mut targets = HashMap::new();
+
+		for (idx, profile) in self.profiles.iter().enumerate() {
+			for (name, path) in profile.targets.iter() {
+				targets
+					.entry(name.to_owned())
+					.or_insert_with(|| (idx, path.to_owned()));
+			}
+		}
+
+		let create_target = self
+			.profiles
+			.iter()
+			.enumerate()
+			.find_map(|(idx, profile)| profile.create_target.map(move |create| (idx, create)));
+
+		let preserve_attributes = self.profiles.iter().enumerate().find_map(|(idx, profile)| {
+			profile
+				.preserve_attributes
+				.map(move |preserve| (idx, preserve))
+		});
+
+		let follow_source_symlinks = self.profiles.iter().enumerate().find_map(|(idx, profile)| {
+			profile
+				.follow_source_symlinks
+				.map(move |follow| (idx, follow))
+		});
+
+		let allow_external = self
+			.profiles
+			.iter()
+			.enumerate()
+			.find_map(|(idx, profile)| profile.allow_external.map(move |allow| (idx, allow)));
+
+		let trusted = self
+			.profiles
+			.iter()
+			.enumerate()
+			.find_map(|(idx, profile)| profile.trusted.map(move |trusted| (idx, trusted)));
+
+		let variable_resolution_order =
+			self.profiles.iter().enumerate().find_map(|(idx, profile)| {
+				profile
+					.variable_resolution_order
+					.map(move |order| (idx, order))
+			});
+
+		let template_limits =
+			self.profiles.iter().enumerate().find_map(|(idx, profile)| {
+				profile.template_limits.map(move |limits| (idx, limits))
+			});
+
 		let mut variables = LayeredVariables::default();
 
 		for (idx, vars) in self
@@ -346,11 +1013,21 @@ impl LayeredProfileBuilder {
 		{
 			for t in transformer.iter() {
 				if !transformers.iter().any(|(_, tt)| t == tt) {
-					transformers.push((idx, *t));
+					transformers.push((idx, t.clone()));
 				}
 			}
 		}
 
+		let mut transformers_by_extension = HashMap::new();
+
+		for (idx, profile) in self.profiles.iter().enumerate() {
+			for (extension, transformers) in profile.transformers_by_extension.iter() {
+				transformers_by_extension
+					.entry(normalize_extension(extension))
+					.or_insert_with(|| (idx, transformers.to_vec()));
+			}
+		}
+
 		let pre_hooks = self
 			.profiles
 			.iter()
@@ -377,19 +1054,77 @@ impl LayeredProfileBuilder {
 			})
 			.collect();
 
-		let mut added_dotfile_paths = HashSet::new();
-		let mut dotfiles = Vec::new();
+		let on_failure_hooks = self
+			.profiles
+			.iter()
+			.enumerate()
+			.flat_map(|(idx, profile)| {
+				profile
+					.on_failure_hooks
+					.iter()
+					.cloned()
+					.map(move |hook| (idx, hook))
+			})
+			.collect();
 
-		for (idx, dfiles) in self
+		let pre_dotfile_hooks = self
 			.profiles
 			.iter()
 			.enumerate()
-			.map(|(idx, profile)| (idx, &profile.dotfiles))
-		{
-			for dotfile in dfiles.iter() {
+			.flat_map(|(idx, profile)| {
+				profile
+					.pre_dotfile_hooks
+					.iter()
+					.cloned()
+					.map(move |hook| (idx, hook))
+			})
+			.collect();
+
+		let post_dotfile_hooks = self
+			.profiles
+			.iter()
+			.enumerate()
+			.flat_map(|(idx, profile)| {
+				profile
+					.post_dotfile_hooks
+					.iter()
+					.cloned()
+					.map(move |hook| (idx, hook))
+			})
+			.collect();
+
+		let dotfile_hook_patterns = self
+			.profiles
+			.iter()
+			.enumerate()
+			.flat_map(|(idx, profile)| {
+				profile
+					.dotfile_hook_patterns
+					.iter()
+					.cloned()
+					.map(move |pattern| (idx, pattern))
+			})
+			.collect();
+
+		let layered_defaults = self
+			.profiles
+			.iter()
+			.filter_map(|profile| profile.defaults)
+			.fold(DotfileDefaults::default(), DotfileDefaults::or);
+
+		let mut added_dotfile_paths = HashSet::new();
+		let mut dotfiles = Vec::new();
+
+		for (idx, profile) in self.profiles.iter().enumerate() {
+			let defaults = profile.defaults.unwrap_or_default().or(layered_defaults);
+
+			for dotfile in profile.dotfiles.iter() {
 				if !added_dotfile_paths.contains(&dotfile.path) {
-					dotfiles.push((idx, dotfile.clone()));
+					let mut dotfile = dotfile.clone();
+					defaults.apply(&mut dotfile);
+
 					added_dotfile_paths.insert(dotfile.path.clone());
+					dotfiles.push((idx, dotfile));
 				}
 			}
 		}
@@ -407,19 +1142,55 @@ impl LayeredProfileBuilder {
 			})
 			.collect();
 
+		let mut added_extra_paths = HashSet::new();
+		let mut extra_paths = Vec::new();
+
+		for (idx, profile) in self.profiles.iter().enumerate() {
+			for extra_path in profile.extra_paths.iter() {
+				if added_extra_paths.insert(extra_path.path.clone()) {
+					extra_paths.push((idx, extra_path.clone()));
+				}
+			}
+		}
+
 		LayeredProfile {
 			profile_names: self.profile_names,
 			target,
+			targets,
+			create_target,
+			preserve_attributes,
+			follow_source_symlinks,
+			allow_external,
+			trusted,
 			variables,
+			variable_resolution_order,
+			template_limits,
 			transformers,
+			transformers_by_extension,
 			pre_hooks,
 			post_hooks,
+			on_failure_hooks,
+			pre_dotfile_hooks,
+			post_dotfile_hooks,
+			dotfile_hook_patterns,
 			dotfiles,
 			symlinks,
+			extra_paths,
+			env: PunktfEnv::new(),
 		}
 	}
 }
 
+/// Normalizes a file extension for lookups in
+/// [`LayeredProfile::transformers_by_extension`] by removing a leading dot and
+/// converting it to lowercase.
+fn normalize_extension(extension: &str) -> String {
+	extension
+		.strip_prefix('.')
+		.unwrap_or(extension)
+		.to_ascii_lowercase()
+}
+
 /// A minimal struct to read the `aliases` from a profile file.
 ///
 /// This is used for profile name resolution.
@@ -432,49 +1203,45 @@ struct Aliases {
 	aliases: Vec<String>,
 }
 
-/// Collects all profile names and aliases from the `profiles` directory.
-pub fn collect_profile_names(source: &PunktfSource) -> Result<HashMap<String, PathBuf>> {
-	log::info!("Collecting profile names and aliases");
+/// A profile file found in the `profiles` directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ProfileEntry {
+	/// Name of the profile (file name without extension).
+	pub name: String,
+
+	/// Path to the profile file.
+	pub path: PathBuf,
 
+	/// Aliases of the profile.
+	pub aliases: Vec<String>,
+}
+
+/// Finds all profile files in the `profiles` directory and reads their
+/// aliases.
+pub(crate) fn profile_entries(source: &PunktfSource) -> Result<Vec<ProfileEntry>> {
 	/// Tries to read all alias from a given file.
-	fn get_aliases(path: &Path, extension: &str) -> Option<Aliases> {
-		let Ok(file) = File::open(path) else {
+	fn get_aliases(path: &Path) -> Option<Aliases> {
+		let Ok(content) = std::fs::read_to_string(path) else {
 			log::debug!("[{}] Failed to read content", path.display());
 			return None;
 		};
 
-		#[cfg(feature = "profile-json")]
-		{
-			if extension.eq_ignore_ascii_case("json") {
-				let Ok(aliases) = serde_json::from_reader(file) else {
-					log::debug!("[{}] Failed to read aliases", path.display());
-					return None;
-				};
-
-				return Some(aliases);
-			}
-		}
-
-		#[cfg(feature = "profile-yaml")]
-		{
-			if extension.eq_ignore_ascii_case("yaml") || extension.eq_ignore_ascii_case("yml") {
-				let Ok(aliases) = serde_yaml::from_reader(file) else {
-					log::debug!("[{}] Failed to read aliases", path.display());
-					return None;
-				};
-
-				return Some(aliases);
-			}
-		}
+		let Ok(aliases) = deserialize(path, &content) else {
+			log::debug!("[{}] Failed to read aliases", path.display());
+			return None;
+		};
 
-		None
+		Some(aliases)
 	}
 
-	let mut names = HashMap::new();
+	let mut entries = Vec::new();
 
-	let dents = source.profiles().read_dir()?;
+	let dents = source
+		.profiles()
+		.read_dir()
+		.map_err(|err| Error::io(source.profiles(), err))?;
 	for dent in dents {
-		let dent = dent?;
+		let dent = dent.map_err(|err| Error::io(source.profiles(), err))?;
 		let path = dent.path();
 
 		let Ok(ft) = dent.file_type() else {
@@ -497,31 +1264,50 @@ pub fn collect_profile_names(source: &PunktfSource) -> Result<HashMap<String, Pa
 			continue;
 		};
 		// Remove extension and `.`
-		let name = &name[..(name.len() - (extension.len() + 1))];
+		let name = name[..(name.len() - (extension.len() + 1))].to_string();
 
-		for alias in get_aliases(&path, extension)
-			.into_iter()
-			.flat_map(|a| a.aliases.into_iter())
-		{
+		let aliases = get_aliases(&path).map(|a| a.aliases).unwrap_or_default();
+
+		entries.push(ProfileEntry {
+			name,
+			path,
+			aliases,
+		});
+	}
+
+	Ok(entries)
+}
+
+/// Collects all profile names and aliases from the `profiles` directory.
+pub fn collect_profile_names(source: &PunktfSource) -> Result<HashMap<String, PathBuf>> {
+	log::info!("Collecting profile names and aliases");
+
+	let mut names = HashMap::new();
+
+	for ProfileEntry {
+		name,
+		path,
+		aliases,
+	} in profile_entries(source)?
+	{
+		for alias in aliases {
 			log::debug!("[{}] Adding alias {}", path.display(), alias);
 
 			if let Some(evicted) = names.insert(alias.clone(), path.clone()) {
-				bail!(
-					"[{}] The profile alias {} is already taken by {}",
-					path.display(),
-					alias,
-					evicted.display()
-				);
+				return Err(Error::DuplicateProfileName {
+					name: alias,
+					path,
+					other: evicted,
+				});
 			}
 		}
 
-		if let Some(evicted) = names.insert(name.to_string(), path.clone()) {
-			bail!(
-				"[{}] The profile name {} is already taken by {}",
-				path.display(),
+		if let Some(evicted) = names.insert(name.clone(), path.clone()) {
+			return Err(Error::DuplicateProfileName {
 				name,
-				evicted.display()
-			);
+				path,
+				other: evicted,
+			});
 		}
 	}
 
@@ -530,76 +1316,255 @@ pub fn collect_profile_names(source: &PunktfSource) -> Result<HashMap<String, Pa
 	Ok(names)
 }
 
+/// Finds the path of the profile file for the profile name or alias `name`
+/// (related: [`collect_profile_names`]).
+///
+/// Names are matched like in [`resolve_profile`].
+///
+/// # Errors
+///
+/// An error is returned if the profile names could not be collected or no
+/// profile with the given name exists.
+pub fn profile_path(source: &PunktfSource, name: &str) -> Result<PathBuf> {
+	let names = collect_profile_names(source)?;
+
+	lookup_profile(
+		names
+			.iter()
+			.map(|(name, path)| (name.as_str(), path.as_path())),
+		name,
+	)
+	.map(|(_, path)| path.to_path_buf())
+}
+
+/// Computes the levenshtein distance between `a` and `b`.
+fn levenshtein(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut row: Vec<usize> = (0..=b.len()).collect();
+
+	for (i, ca) in a.chars().enumerate() {
+		let mut diagonal = row[0];
+		row[0] = i + 1;
+
+		for (j, cb) in b.iter().enumerate() {
+			let above = row[j + 1];
+			row[j + 1] = if ca == *cb {
+				diagonal
+			} else {
+				1 + diagonal.min(above).min(row[j])
+			};
+			diagonal = above;
+		}
+	}
+
+	row[b.len()]
+}
+
+/// Maximum number of suggestions given for an unknown profile name.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Looks up the profile for the profile name or alias `name` among
+/// `candidates` (pairs of a name or alias and the path of its profile).
+///
+/// An exact match is preferred. Otherwise the name is matched ignoring case,
+/// as long as this is unambiguous.
+///
+/// Returns the matched name together with the path of the profile.
+///
+/// # Errors
+///
+/// [`Error::ProfileNotFound`] with the closest names and aliases as
+/// suggestions is returned if no profile matches.
+pub(crate) fn lookup_profile<'a, I>(candidates: I, name: &str) -> Result<(&'a str, &'a Path)>
+where
+	I: IntoIterator<Item = (&'a str, &'a Path)>,
+{
+	let candidates: Vec<_> = candidates.into_iter().collect();
+
+	if let Some(found) = candidates.iter().find(|(candidate, _)| *candidate == name) {
+		return Ok(*found);
+	}
+
+	let mut matches = candidates
+		.iter()
+		.filter(|(candidate, _)| candidate.eq_ignore_ascii_case(name));
+
+	if let (Some(found), None) = (matches.next(), matches.next()) {
+		log::debug!("Matched profile name `{name}` to `{}`", found.0);
+		return Ok(*found);
+	}
+
+	let lower = name.to_lowercase();
+	let threshold = (name.chars().count() / 3).max(1);
+
+	let mut suggestions: Vec<_> = candidates
+		.iter()
+		.map(|(candidate, _)| (levenshtein(&lower, &candidate.to_lowercase()), *candidate))
+		.filter(|(distance, _)| *distance <= threshold)
+		.collect();
+	suggestions.sort();
+	suggestions.dedup();
+
+	Err(Error::ProfileNotFound {
+		name: name.to_string(),
+		suggestions: suggestions
+			.into_iter()
+			.take(MAX_SUGGESTIONS)
+			.map(|(_, candidate)| candidate.to_string())
+			.collect(),
+	})
+}
+
 /// Recursively resolves a profile and it's [extend
 /// chain](`crate::profile::Profile::extends`) and adds them to the layered
 /// profile in order of occurrence.
+///
+/// # Errors
+///
+/// If any profile of the extend chain could not be resolved, the resolution
+/// continues with the remaining profiles and [`Error::Profile`] with the
+/// problems of all failed profiles is returned.
 pub fn resolve_profile(
 	builder: &mut LayeredProfileBuilder,
 	source: &PunktfSource,
 	name: &str,
 ) -> Result<()> {
-	/// Recursive resolution of all profiles needed.
-	///
-	/// Checks for cycles while resolving.
-	fn _resolve_profile_inner(
-		profiles: &HashMap<String, PathBuf>,
-		builder: &mut LayeredProfileBuilder,
-		name: &str,
-		resolved_profiles: &mut Vec<String>,
-	) -> Result<()> {
-		log::trace!("Resolving profile `{}`", name);
+	let available_profiles = collect_profile_names(source)?;
+	let mut resolved_profiles = Vec::new();
 
-		let path = profiles
-			.get(name)
-			.ok_or_else(|| eyre!("No profile found for name {}", name))?;
+	resolve_profile_inner(
+		source,
+		&available_profiles,
+		builder,
+		name,
+		&mut resolved_profiles,
+	)
+}
 
-		let mut profile = Profile::from_file(path)?;
-		let name = name.to_string();
+/// Adds a profile which is not part of the `profiles` directory (e.g. read
+/// from stdin) under the name `name` to the layered profile and recursively
+/// resolves its [extend chain](`crate::profile::Profile::extends`) against
+/// the profiles of `source`.
+///
+/// # Errors
+///
+/// Same as [`resolve_profile`].
+pub fn resolve_inline_profile(
+	builder: &mut LayeredProfileBuilder,
+	source: &PunktfSource,
+	name: &str,
+	profile: Profile,
+) -> Result<()> {
+	let available_profiles = collect_profile_names(source)?;
+	let mut resolved_profiles = Vec::new();
 
-		if !profile.extends.is_empty() && resolved_profiles.contains(&name) {
-			// profile was already resolve and has "children" which will lead to
-			// a loop while resolving
-			return Err(eyre!(
-			"Circular dependency detected while parsing `{}` (required by: `{:?}`) (Stack: {:#?})",
-			name,
-			resolved_profiles.last(),
-			resolved_profiles
-		));
-		}
+	add_profile_inner(
+		source,
+		&available_profiles,
+		builder,
+		name.to_string(),
+		profile,
+		&mut resolved_profiles,
+	)
+}
+
+/// Looks up the profile `name` among `profiles`, reads it and adds it
+/// together with its extend chain to `builder`.
+fn resolve_profile_inner(
+	source: &PunktfSource,
+	profiles: &HashMap<String, PathBuf>,
+	builder: &mut LayeredProfileBuilder,
+	name: &str,
+	resolved_profiles: &mut Vec<String>,
+) -> Result<()> {
+	log::trace!("Resolving profile `{}`", name);
 
-		let mut extends = Vec::new();
-		std::mem::swap(&mut extends, &mut profile.extends);
+	let (name, path) = lookup_profile(
+		profiles
+			.iter()
+			.map(|(name, path)| (name.as_str(), path.as_path())),
+		name,
+	)?;
+
+	let profile = Profile::from_file(path)?;
+
+	add_profile_inner(
+		source,
+		profiles,
+		builder,
+		name.to_string(),
+		profile,
+		resolved_profiles,
+	)
+}
 
-		builder.add(name.clone(), profile);
+/// Adds `profile` to `builder` and recursively resolves all profiles it
+/// extends.
+///
+/// Checks for cycles while resolving.
+fn add_profile_inner(
+	source: &PunktfSource,
+	profiles: &HashMap<String, PathBuf>,
+	builder: &mut LayeredProfileBuilder,
+	name: String,
+	mut profile: Profile,
+	resolved_profiles: &mut Vec<String>,
+) -> Result<()> {
+	if !profile.extends.is_empty() && resolved_profiles.contains(&name) {
+		// profile was already resolve and has "children" which will lead to
+		// a loop while resolving
+		let mut chain = resolved_profiles.clone();
+		chain.push(name.clone());
 
-		resolved_profiles.push(name);
+		return Err(Error::CyclicExtends { name, chain });
+	}
 
-		for child in extends {
-			_resolve_profile_inner(profiles, builder, &child, resolved_profiles)?;
-		}
+	let mut extends = Vec::new();
+	std::mem::swap(&mut extends, &mut profile.extends);
 
-		let _ = resolved_profiles
-			.pop()
-			.expect("Misaligned push/pop operation");
+	profile.load_variables_files(source.root())?;
+	builder.add(name.clone(), profile);
 
-		Ok(())
-	}
+	resolved_profiles.push(name);
 
-	let available_profiles = collect_profile_names(source)?;
-	let mut resolved_profiles = Vec::new();
+	// Continue with the remaining profiles on errors to report all
+	// problems at once.
+	let mut errors: Vec<_> = extends
+		.iter()
+		.filter_map(|child| {
+			resolve_profile_inner(source, profiles, builder, child, resolved_profiles).err()
+		})
+		.collect();
+
+	let _ = resolved_profiles
+		.pop()
+		.expect("Misaligned push/pop operation");
+
+	// A single error is kept as is, so it can still be matched on.
+	match errors.len() {
+		0 => Ok(()),
+		1 => Err(errors.remove(0)),
+		_ => {
+			let mut problems = ProfileErrors::default();
+			for err in &errors {
+				problems.add_error(err);
+			}
 
-	_resolve_profile_inner(&available_profiles, builder, name, &mut resolved_profiles)
+			Err(problems.into())
+		}
+	}
 }
 
 #[cfg(test)]
 mod tests {
-	use std::collections::HashMap;
+	use std::collections::{BTreeMap, HashMap};
 
 	use super::*;
+	use crate::profile::dotfile::Enabled;
 	use crate::profile::hook::Hook;
-	use crate::profile::variables::Variables;
+	use crate::profile::variables::{Value, Variables};
 	use crate::profile::Profile;
-	use crate::profile::{MergeMode, Priority};
+	use crate::profile::{DeployStrategy, MergeMode, Priority, WindowsAcl};
 
 	#[test]
 	fn priority_order() {
@@ -611,17 +1576,16 @@ mod tests {
 	}
 
 	#[test]
-	#[cfg(feature = "profile-json")]
 	fn profile_serde() {
 		crate::tests::setup_test_env();
 
 		let mut profile_vars = HashMap::new();
-		profile_vars.insert(String::from("RUSTC_VERSION"), String::from("XX.YY"));
-		profile_vars.insert(String::from("RUSTC_PATH"), String::from("/usr/bin/rustc"));
+		profile_vars.insert(String::from("RUSTC_VERSION"), Value::from("XX.YY"));
+		profile_vars.insert(String::from("RUSTC_PATH"), Value::from("/usr/bin/rustc"));
 
 		let mut dotfile_vars = HashMap::new();
-		dotfile_vars.insert(String::from("RUSTC_VERSION"), String::from("55.22"));
-		dotfile_vars.insert(String::from("USERNAME"), String::from("demo"));
+		dotfile_vars.insert(String::from("RUSTC_VERSION"), Value::from("55.22"));
+		dotfile_vars.insert(String::from("USERNAME"), Value::from("demo"));
 
 		let profile = Profile {
 			extends: Vec::new(),
@@ -629,35 +1593,78 @@ mod tests {
 			variables: Some(Variables {
 				inner: profile_vars,
 			}),
+			variable_resolution_order: None,
+			template_limits: None,
 			transformers: Vec::new(),
+			transformers_by_extension: HashMap::new(),
 			target: Some(PathBuf::from("/home/demo/.config")),
+			targets: HashMap::from([(String::from("home"), PathBuf::from("/home/demo"))]),
+			create_target: None,
+			preserve_attributes: Some(true),
+			follow_source_symlinks: Some(true),
+			allow_external: Some(true),
+			trusted: Some(false),
 			pre_hooks: vec![Hook::new("echo \"Foo\"")],
 			post_hooks: vec![Hook::new("profiles/test.sh")],
+			on_failure_hooks: vec![Hook::new("notify-send \"Deployment failed\"")],
+			pre_dotfile_hooks: Vec::new(),
+			post_dotfile_hooks: vec![Hook::new("profiles/reload.sh")],
+			dotfile_hook_patterns: vec![IgnorePattern(String::from("*.conf"))],
+			defaults: None,
 			dotfiles: vec![
 				Dotfile {
 					path: PathBuf::from("init.vim.ubuntu"),
 					rename: Some(PathBuf::from("init.vim")),
+					rename_map: BTreeMap::new(),
 					overwrite_target: None,
+					target_name: Some(String::from("home")),
 					priority: Some(Priority::new(2)),
 					variables: None,
+					variables_file: None,
 					transformers: Vec::new(),
 					merge: Some(MergeMode::Overwrite),
 					template: None,
+					template_mode: None,
+					engine: None,
+					binary: Some(BinaryMode::Auto),
+					strategy: None,
+					windows_acl: None,
+					encrypted: None,
+					preserve_attributes: Some(false),
+					follow_source_symlinks: None,
+					tags: vec![String::from("editor")],
+					enabled: Some(Enabled::Expr(String::from("{{PUNKTF_OS}} == \"linux\""))),
+					extract: None,
 				},
 				Dotfile {
 					path: PathBuf::from(".bashrc"),
 					rename: None,
+					rename_map: BTreeMap::new(),
 					overwrite_target: Some(PathBuf::from("/home/demo")),
+					target_name: None,
 					priority: None,
 					variables: Some(Variables {
 						inner: dotfile_vars,
 					}),
+					variables_file: None,
 					transformers: Vec::new(),
 					merge: Some(MergeMode::Overwrite),
 					template: Some(false),
+					template_mode: None,
+					engine: None,
+					binary: Some(BinaryMode::Binary),
+					strategy: Some(DeployStrategy::CacheLink),
+					windows_acl: Some(WindowsAcl::Inherit),
+					encrypted: Some(true),
+					preserve_attributes: None,
+					follow_source_symlinks: Some(false),
+					tags: Vec::new(),
+					enabled: Some(Enabled::Bool(false)),
+					extract: Some(true),
 				},
 			],
 			symlinks: vec![],
+			extra_paths: vec![],
 		};
 
 		let json = serde_json::to_string(&profile).expect("Profile to be serializeable");
@@ -666,4 +1673,354 @@ mod tests {
 
 		assert_eq!(parsed, profile);
 	}
+
+	#[test]
+	fn dotfile_defaults() {
+		crate::tests::setup_test_env();
+
+		let dotfile = |path: &str| Dotfile {
+			path: PathBuf::from(path),
+			..Default::default()
+		};
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("child"),
+			Profile {
+				defaults: Some(DotfileDefaults {
+					template: Some(false),
+					..Default::default()
+				}),
+				dotfiles: vec![
+					dotfile("binary"),
+					Dotfile {
+						template: Some(true),
+						merge: Some(MergeMode::Ask),
+						..dotfile("explicit")
+					},
+				],
+				..Default::default()
+			},
+		);
+		builder.add(
+			String::from("base"),
+			Profile {
+				defaults: Some(DotfileDefaults {
+					merge: Some(MergeMode::Keep),
+					template: Some(true),
+					priority: Some(Priority::new(10)),
+				}),
+				dotfiles: vec![dotfile("base")],
+				..Default::default()
+			},
+		);
+		let profile = builder.finish();
+
+		let fields = |path: &str| {
+			profile
+				.dotfiles()
+				.find(|dotfile| dotfile.path == Path::new(path))
+				.map(|dotfile| (dotfile.merge, dotfile.template, dotfile.priority))
+		};
+
+		assert_eq!(
+			fields("binary"),
+			Some((Some(MergeMode::Keep), Some(false), Some(Priority::new(10))))
+		);
+		assert_eq!(
+			fields("explicit"),
+			Some((Some(MergeMode::Ask), Some(true), Some(Priority::new(10))))
+		);
+		assert_eq!(
+			fields("base"),
+			Some((Some(MergeMode::Keep), Some(true), Some(Priority::new(10))))
+		);
+	}
+
+	#[test]
+	fn builtin_variables() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		let source = PunktfSource::from_root(dir.to_path_buf())?;
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("demo"),
+			Profile {
+				target: Some(PathBuf::from("/home/demo")),
+				variables: Some(Variables::from_items([("PUNKTF_OS", "custom")])),
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+		profile.add_builtin_variables(&source, "demo");
+
+		let vars = profile.variables();
+		assert_eq!(vars.var("PUNKTF_OS").as_deref(), Some("custom"));
+		assert_eq!(
+			vars.var("PUNKTF_ARCH").as_deref(),
+			Some(std::env::consts::ARCH)
+		);
+		assert_eq!(vars.var("PUNKTF_PROFILE").as_deref(), Some("demo"));
+		assert_eq!(vars.var("PUNKTF_TARGET").as_deref(), Some("/home/demo"));
+		assert_eq!(
+			vars.inner
+				.get("PUNKTF_PROFILE")
+				.map(|(idx, _)| &profile.profile_names[*idx]),
+			Some(&String::from(BUILTIN_LAYER_NAME))
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn variable_overlays() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let root = crate::tests::test_dir();
+		let dir = root.join(VARIABLE_OVERLAY_DIR_NAME);
+		std::fs::create_dir_all(&dir)?;
+		std::fs::write(dir.join("linux.yaml"), "EDITOR: vim\nTHEME: dark\n")?;
+		std::fs::write(dir.join("work.example.yml"), "EDITOR: nvim\n")?;
+		std::fs::write(dir.join("other.yaml"), "THEME: light\n")?;
+
+		let source = PunktfSource::from_root(root.to_path_buf())?;
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("demo"),
+			Profile {
+				variables: Some(Variables::from_items([
+					("EDITOR", "nano"),
+					("SHELL", "bash"),
+				])),
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+		profile.add_variable_overlays_for(
+			&source,
+			&select::Host {
+				hostname: String::from("work.example"),
+				os: String::from("linux"),
+			},
+		)?;
+
+		let vars = profile.variables();
+		assert_eq!(vars.var("EDITOR").as_deref(), Some("nvim"));
+		assert_eq!(vars.var("THEME").as_deref(), Some("dark"));
+		assert_eq!(vars.var("SHELL").as_deref(), Some("bash"));
+		assert_eq!(
+			vars.inner
+				.get("EDITOR")
+				.map(|(idx, _)| profile.profile_names[*idx].replace('\\', "/")),
+			Some(String::from("variables.d/work.example.yml"))
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn resolve_errors() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("profiles"))?;
+		std::fs::write(
+			dir.join("profiles/a.yaml"),
+			"extends: [b]
+",
+		)?;
+		std::fs::write(
+			dir.join("profiles/b.yaml"),
+			"extends: [a]
+",
+		)?;
+
+		let source = PunktfSource::from_root(dir.to_path_buf())?;
+
+		assert_eq!(
+			profile_path(&source, "B")?,
+			source.profiles().join("b.yaml")
+		);
+		assert!(matches!(
+			profile_path(&source, "missing"),
+			Err(Error::ProfileNotFound { .. })
+		));
+
+		let err = resolve_profile(&mut LayeredProfile::build(), &source, "missing")
+			.expect_err("Profile to be missing");
+		assert!(matches!(err, Error::ProfileNotFound { name, .. } if name == "missing"));
+
+		let err = resolve_profile(&mut LayeredProfile::build(), &source, "a")
+			.expect_err("Profiles to be cyclic");
+		let Error::CyclicExtends { name, chain } = err else {
+			panic!("Expected a cyclic extend: {err}");
+		};
+		assert_eq!(name, "a");
+		assert_eq!(chain, ["a", "b", "a"]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn inline_profile() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("profiles"))?;
+		std::fs::write(dir.join("profiles/base.yaml"), "variables:\n  OS: linux\n")?;
+
+		let source = PunktfSource::from_root(dir.to_path_buf())?;
+
+		let profile = Profile::parse("stdin", "extends: [base]\ntarget: /home/demo\n")?;
+		assert_eq!(profile.extends, ["base"]);
+
+		let mut builder = LayeredProfile::build();
+		resolve_inline_profile(&mut builder, &source, "machine", profile)?;
+		let profile = builder.finish();
+
+		assert_eq!(profile.profile_names, ["machine", "base"]);
+		assert_eq!(profile.target_path(), Some(Path::new("/home/demo")));
+		assert_eq!(
+			profile.variables.inner.get("OS"),
+			Some(&(1, Value::from("linux")))
+		);
+
+		// JSON is accepted as well
+		assert_eq!(
+			Profile::parse("inline", r#"{"extends": ["base"]}"#)?.extends,
+			["base"]
+		);
+
+		let err = Profile::parse("stdin", "dotfiles: 3\n").expect_err("Profile to be invalid");
+		assert!(err.to_string().contains("<stdin>"), "{err}");
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn variables_file() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("profiles"))?;
+		std::fs::create_dir_all(dir.join("vars"))?;
+		std::fs::write(
+			dir.join("vars/alacritty.yaml"),
+			"FONT: Hack\nFONT_SIZE: 12\n",
+		)?;
+		std::fs::write(
+			dir.join("profiles/base.yaml"),
+			"dotfiles:\n  - path: alacritty.yml\n    variables_file: vars/alacritty.yaml\n    variables:\n      FONT_SIZE: 14\n  - path: missing\n    variables_file: vars/missing.yaml\n",
+		)?;
+		std::fs::write(
+			dir.join("profiles/test.yaml"),
+			"dotfiles:\n  - path: alacritty.yml\n    variables_file: vars/alacritty.yaml\n    variables:\n      FONT_SIZE: 14\n",
+		)?;
+
+		let source = PunktfSource::from_root(dir.to_path_buf())?;
+
+		let mut builder = LayeredProfile::build();
+		resolve_profile(&mut builder, &source, "test")?;
+		let profile = builder.finish();
+
+		let (_, dotfile) = &profile.dotfiles[0];
+		assert_eq!(dotfile.variables_file, None);
+		assert_eq!(
+			dotfile.variables.as_ref().map(|vars| &vars.inner),
+			Some(&HashMap::from([
+				(String::from("FONT"), Value::from("Hack")),
+				(String::from("FONT_SIZE"), Value::Int(14)),
+			]))
+		);
+
+		let err = resolve_profile(&mut LayeredProfile::build(), &source, "base")
+			.expect_err("Variables file to be missing");
+		assert!(err.to_string().contains("missing.yaml"), "{err}");
+
+		Ok(())
+	}
+
+	#[test]
+	fn extension_transformers() {
+		use crate::profile::transform::LineTerminator;
+
+		crate::tests::setup_test_env();
+
+		let lf = ContentTransformer::LineTerminator(LineTerminator::LF);
+		let crlf = ContentTransformer::LineTerminator(LineTerminator::CRLF);
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("child"),
+			Profile {
+				transformers_by_extension: HashMap::from([(String::from(".sh"), vec![lf.clone()])]),
+				..Default::default()
+			},
+		);
+		builder.add(
+			String::from("base"),
+			Profile {
+				transformers_by_extension: HashMap::from([
+					(String::from("sh"), vec![crlf.clone()]),
+					(String::from(".PS1"), vec![crlf.clone()]),
+				]),
+				..Default::default()
+			},
+		);
+		let profile = builder.finish();
+
+		assert_eq!(
+			profile.extension_transformers(Path::new("bin/setup.sh")),
+			[lf]
+		);
+		assert_eq!(
+			profile.extension_transformers(Path::new("profile.ps1")),
+			[crlf]
+		);
+		assert!(profile
+			.extension_transformers(Path::new(".bashrc"))
+			.is_empty());
+		assert!(profile
+			.extension_transformers(Path::new("notes.txt"))
+			.is_empty());
+	}
+
+	#[test]
+	fn profile_lookup() {
+		crate::tests::setup_test_env();
+
+		let candidates = [
+			("arch-desktop", Path::new("arch-desktop.yaml")),
+			("Work", Path::new("work.yaml")),
+			("home", Path::new("home.yaml")),
+			("HOME", Path::new("home-upper.yaml")),
+		];
+
+		let lookup = |name: &str| lookup_profile(candidates, name);
+
+		assert_eq!(lookup("Work").ok(), Some(candidates[1]));
+		assert_eq!(lookup("work").ok(), Some(candidates[1]));
+		assert_eq!(lookup("HOME").ok(), Some(candidates[3]));
+
+		// Ambiguous without an exact match
+		let err = lookup("Home").expect_err("Name to be ambiguous");
+		assert!(
+			matches!(&err, Error::ProfileNotFound { suggestions, .. } if suggestions == &["HOME", "home"])
+		);
+
+		let err = lookup("arch-destkop").expect_err("Name to be unknown");
+		assert_eq!(
+			err.to_string(),
+			"No profile found for name `arch-destkop`; did you mean `arch-desktop`?"
+		);
+
+		let err = lookup("linux").expect_err("Name to be unknown");
+		assert_eq!(err.to_string(), "No profile found for name `linux`");
+	}
 }
@@ -0,0 +1,227 @@
+//! Automatic selection of a [profile](`crate::profile::Profile`) for the
+//! current machine.
+//!
+//! The selection is defined by a `machines` file (`machines.yaml`,
+//! `machines.yml` or `machines.json`) in the root of the
+//! [source directory](`crate::profile::source::PunktfSource`). It maps
+//! machines (by hostname and/or operating system) to profile names:
+//!
+//! ```yaml
+//! machines:
+//!   - hostname: work-laptop
+//!     profile: work
+//!   - os: windows
+//!     profile: windows
+//!   # Matches every machine
+//!   - profile: default
+//! ```
+//!
+//! The first entry which matches the current machine is selected.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::profile::source::PunktfSource;
+
+/// File stem of the file which contains the [`Machines`].
+pub const MACHINES_FILE_STEM: &str = "machines";
+
+/// Information about a machine which is used to select a profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Host {
+	/// Hostname of the machine.
+	pub hostname: String,
+
+	/// Operating system of the machine (related: [`std::env::consts::OS`]).
+	pub os: String,
+}
+
+impl Host {
+	/// Retrieves the information of the current machine.
+	pub fn current() -> Self {
+		Self {
+			hostname: gethostname::gethostname().to_string_lossy().into_owned(),
+			os: std::env::consts::OS.into(),
+		}
+	}
+}
+
+/// Maps a machine to a profile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Machine {
+	/// Hostname of the machine. It is compared case-insensitive.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub hostname: Option<String>,
+
+	/// Operating system of the machine (e.g. `linux`, `macos` or `windows`;
+	/// related: [`std::env::consts::OS`]).
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub os: Option<String>,
+
+	/// Name of the profile which is selected for the machine.
+	pub profile: String,
+}
+
+impl Machine {
+	/// Checks if this entry matches `host`.
+	///
+	/// An entry without any criteria matches every host.
+	pub fn matches(&self, host: &Host) -> bool {
+		let hostname_matches = self
+			.hostname
+			.as_ref()
+			.is_none_or(|hostname| hostname.eq_ignore_ascii_case(&host.hostname));
+
+		let os_matches = self
+			.os
+			.as_ref()
+			.is_none_or(|os| os.eq_ignore_ascii_case(&host.os));
+
+		hostname_matches && os_matches
+	}
+}
+
+/// Mapping of machines to profile names.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Machines {
+	/// All machine entries in order of precedence.
+	#[serde(default)]
+	pub machines: Vec<Machine>,
+}
+
+impl Machines {
+	/// Tries to load the mapping from the file located at `path`.
+	///
+	/// This function will try to guess the correct deserializer by the file
+	/// extension of `path`
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file does not exist or could not be read.
+	/// An error is returned if the file extension is unknown or missing.
+	pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+		let path = path.as_ref();
+
+		/// Inner function is used to reduce monomorphizes as path here is a
+		/// concrete type and no generic one.
+		fn from_file_inner(path: &Path) -> Result<Machines> {
+			let file = File::open(path).map_err(|err| Error::io(path, err))?;
+
+			let extension = path.extension().unwrap_or_default();
+
+			if extension.eq_ignore_ascii_case("json") {
+				return serde_json::from_reader(file).map_err(|err| Error::parse(path, err));
+			}
+
+			#[cfg(feature = "profile-yaml")]
+			{
+				if extension.eq_ignore_ascii_case("yaml") || extension.eq_ignore_ascii_case("yml") {
+					return serde_yaml::from_reader(file).map_err(|err| Error::parse(path, err));
+				}
+			}
+
+			Err(Error::UnsupportedFormat {
+				path: path.to_path_buf(),
+			})
+		}
+
+		from_file_inner(path)
+	}
+
+	/// Finds the path of the machines file in the root of `source`.
+	pub fn find(source: &PunktfSource) -> Option<PathBuf> {
+		["yaml", "yml", "json"]
+			.into_iter()
+			.map(|extension| {
+				source
+					.root()
+					.join(MACHINES_FILE_STEM)
+					.with_extension(extension)
+			})
+			.find(|path| path.is_file())
+	}
+
+	/// Returns the name of the profile of the first entry which matches
+	/// `host`.
+	pub fn select(&self, host: &Host) -> Option<&str> {
+		self.machines
+			.iter()
+			.find(|machine| machine.matches(host))
+			.map(|machine| machine.profile.as_str())
+	}
+}
+
+/// Selects the profile for the current machine by the machines file of
+/// `source` (related: [`Machines::find`]).
+///
+/// # Errors
+///
+/// An error is returned if no machines file exists, if it could not be read
+/// or if no entry matches the current machine.
+pub fn select(source: &PunktfSource) -> Result<String> {
+	let path = Machines::find(source).ok_or_else(|| Error::NoMachinesFile {
+		root: source.root().to_path_buf(),
+	})?;
+
+	let machines = Machines::from_file(&path)?;
+	let host = Host::current();
+
+	log::debug!("Selecting profile for {host:?} from `{}`", path.display());
+
+	machines
+		.select(&host)
+		.map(ToOwned::to_owned)
+		.ok_or_else(|| Error::NoMachineMatched {
+			path: path.clone(),
+			hostname: host.hostname,
+			os: host.os,
+		})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn machines_select() {
+		crate::tests::setup_test_env();
+
+		let machines = Machines {
+			machines: vec![
+				Machine {
+					hostname: Some(String::from("Work-Laptop")),
+					os: Some(String::from("linux")),
+					profile: String::from("work"),
+				},
+				Machine {
+					hostname: None,
+					os: Some(String::from("windows")),
+					profile: String::from("windows"),
+				},
+				Machine {
+					hostname: None,
+					os: None,
+					profile: String::from("default"),
+				},
+			],
+		};
+
+		let host = |hostname: &str, os: &str| Host {
+			hostname: hostname.into(),
+			os: os.into(),
+		};
+
+		assert_eq!(machines.select(&host("work-laptop", "linux")), Some("work"));
+		assert_eq!(
+			machines.select(&host("work-laptop", "windows")),
+			Some("windows")
+		);
+		assert_eq!(machines.select(&host("home", "linux")), Some("default"));
+		assert_eq!(Machines::default().select(&host("home", "linux")), None);
+	}
+}
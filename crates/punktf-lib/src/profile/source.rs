@@ -12,11 +12,162 @@
 //!   ...
 //! + dotfiles/
 //!   ...
+//! + assets/ (optional)
+//!   ...
 //! ```
 
-use color_eyre::eyre::Context as _;
+use std::fmt;
+use std::io;
 use std::path::{Path, PathBuf};
 
+use thiserror::Error;
+
+use crate::profile::config::SourceConfig;
+
+/// Information about the state of a directory of the
+/// [source directory](`PunktfSource`).
+///
+/// This is used to explain why a directory could not be used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirDiagnostic {
+	/// Name of the directory (e.g. `dotfiles`).
+	pub name: &'static str,
+
+	/// The path of the directory as given.
+	pub path: PathBuf,
+
+	/// Whether something exists at `path` (symlinks are followed).
+	pub exists: bool,
+
+	/// The target of `path` if it is a symlink.
+	pub link_target: Option<PathBuf>,
+
+	/// Whether `path` is a directory (symlinks are followed).
+	pub is_dir: bool,
+
+	/// Whether the content of the directory can be read.
+	pub readable: bool,
+
+	/// The resolved real path of `path`, if it could be resolved.
+	pub real_path: Option<PathBuf>,
+}
+
+impl DirDiagnostic {
+	/// Inspects the directory `name` at `path`.
+	pub fn inspect(name: &'static str, path: &Path) -> Self {
+		let metadata = std::fs::metadata(path);
+
+		Self {
+			name,
+			path: path.to_path_buf(),
+			exists: metadata.is_ok(),
+			link_target: std::fs::read_link(path).ok(),
+			is_dir: metadata.map(|m| m.is_dir()).unwrap_or(false),
+			readable: std::fs::read_dir(path).is_ok(),
+			real_path: path.canonicalize().ok(),
+		}
+	}
+
+	/// Returns a description of the problem with the directory, if there is
+	/// any.
+	pub const fn problem(&self) -> Option<&'static str> {
+		if !self.exists {
+			if self.link_target.is_some() {
+				Some("symlink points to a missing location")
+			} else {
+				Some("directory does not exist")
+			}
+		} else if !self.is_dir {
+			Some("not a directory")
+		} else if !self.readable {
+			Some("directory is not readable")
+		} else if self.real_path.is_none() {
+			Some("path could not be resolved")
+		} else {
+			None
+		}
+	}
+
+	/// Checks if the directory can be used.
+	pub const fn is_ok(&self) -> bool {
+		self.problem().is_none()
+	}
+}
+
+impl fmt::Display for DirDiagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		/// Formats a boolean check.
+		const fn yes_no(value: bool) -> &'static str {
+			if value {
+				"yes"
+			} else {
+				"no"
+			}
+		}
+
+		writeln!(f, "{} directory: {}", self.name, self.path.display())?;
+		writeln!(f, "  exists:     {}", yes_no(self.exists))?;
+		if let Some(target) = &self.link_target {
+			writeln!(f, "  symlink to: {}", target.display())?;
+		}
+		writeln!(f, "  directory:  {}", yes_no(self.is_dir))?;
+		writeln!(f, "  readable:   {}", yes_no(self.readable))?;
+		match &self.real_path {
+			Some(real_path) => write!(f, "  real path:  {}", real_path.display()),
+			None => write!(f, "  real path:  <unresolved>"),
+		}
+	}
+}
+
+/// An error which occurs when a directory of the
+/// [source directory](`PunktfSource`) can not be used.
+#[derive(Error, Debug)]
+#[error(
+	"Invalid punktf {} directory: {}\n{diagnostic}",
+	diagnostic.name,
+	diagnostic.problem().unwrap_or("unknown problem")
+)]
+pub struct SourceError {
+	/// Information about the state of the directory.
+	pub diagnostic: DirDiagnostic,
+
+	/// The error which occurred while accessing the directory.
+	#[source]
+	pub source: Option<io::Error>,
+}
+
+impl SourceError {
+	/// Prepares the directory `name` at `path` for use.
+	///
+	/// If nothing exists at `path`, the directory is created. The returned
+	/// path is the resolved real path of the directory.
+	fn prepare_dir(name: &'static str, path: &Path) -> Result<PathBuf, Self> {
+		let path = &crate::visit::extended_length_path(path.to_path_buf());
+
+		// `symlink_metadata` also succeeds for dangling symlinks, which must
+		// not be replaced by a new directory.
+		if std::fs::symlink_metadata(path).is_err() {
+			if let Err(err) = std::fs::create_dir(path) {
+				return Err(Self {
+					diagnostic: DirDiagnostic::inspect(name, path),
+					source: Some(err),
+				});
+			}
+		}
+
+		let diagnostic = DirDiagnostic::inspect(name, path);
+
+		if diagnostic.problem().is_some() {
+			let source = path.canonicalize().and_then(std::fs::read_dir).err();
+			return Err(Self { diagnostic, source });
+		}
+
+		Ok(diagnostic
+			.real_path
+			.expect("Resolved path of a valid directory"))
+	}
+}
+
 /// This struct represents the source directory used by `punktf`. The source
 /// directory is the central repository used to store
 /// [`Profile`s](`crate::profile::Profile`) and [`Dotfile`s](`crate::profile::dotfile::Dotfile`).
@@ -30,6 +181,8 @@ use std::path::{Path, PathBuf};
 ///   ...
 /// + dotfiles/
 ///   ...
+/// + assets/ (optional)
+///   ...
 /// ```
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PunktfSource {
@@ -41,6 +194,17 @@ pub struct PunktfSource {
 
 	/// The absolute path to the `dotfiles` directory.
 	pub dotfiles: PathBuf,
+
+	/// The absolute path to the optional `assets` directory, which contains
+	/// auxiliary files (e.g. wallpapers or fonts) deployed by
+	/// [`Profile::extra_paths`](`crate::profile::Profile::extra_paths`).
+	///
+	/// This is `None` if the directory does not exist.
+	pub assets: Option<PathBuf>,
+
+	/// Settings read from the config file in the `root` directory (related:
+	/// [`SourceConfig::find`]).
+	pub config: SourceConfig,
 }
 
 impl PunktfSource {
@@ -50,63 +214,61 @@ impl PunktfSource {
 	/// the above mentioned paths will also be resolved by calling
 	/// [`std::path::Path::canonicalize`].
 	///
+	/// The `root/assets` subdirectory is optional and only checked if
+	/// something exists at its path.
+	///
+	/// If the `root` directory contains a config file, it is read as well.
+	///
 	/// # Errors
 	///
-	/// If any of the checks fail an error will be returned.
-	pub fn from_root(root: PathBuf) -> color_eyre::Result<Self> {
-		/// Tries to create a directory if it does not exist.
-		/// Bubbles up any error encountered and add some context to it.
-		macro_rules! try_exists {
-			( $var:ident ) => {
-				// TODO: Replace once `try_exists` becomes stable
-				if $var.exists() {
-					// Should check if read/write is possible
-				} else {
-					let _ = std::fs::create_dir(&$var).wrap_err_with(|| {
-						format!(
-							"{} directory does not exist and could not be created (path: {})",
-							stringify!($var),
-							$var.display()
-						)
-					})?;
-				}
-			};
-		}
-
-		/// Tries to canonicalize/resolve a path.
-		/// Bubbles up any error encountered and add some context to it.
-		macro_rules! try_canonicalize {
-			($var:ident) => {
-				$var.canonicalize().wrap_err_with(|| {
-					format!(
-						"Failed to resolve punktf's {} directory (path: {})",
-						stringify!($var),
-						$var.display()
-					)
-				})?
-			};
-		}
-
-		// Renames the `root` variable for better error messages
-		let source = root;
-		try_exists!(source);
-		let source = try_canonicalize!(source);
-
-		let profiles = source.join("profiles");
-		try_exists!(profiles);
-		let profiles = try_canonicalize!(profiles);
+	/// If any of the checks fail a [`SourceError`] describing the state of the
+	/// directory will be returned.
+	/// If the config file can not be read or parsed an error will be returned.
+	pub fn from_root(root: PathBuf) -> crate::error::Result<Self> {
+		let source = SourceError::prepare_dir("source", &root)?;
+		let profiles = SourceError::prepare_dir("profiles", &source.join("profiles"))?;
+		let dotfiles = SourceError::prepare_dir("dotfiles", &source.join("dotfiles"))?;
+		let assets = source.join("assets");
+		let assets = if std::fs::symlink_metadata(&assets).is_ok() {
+			Some(SourceError::prepare_dir("assets", &assets)?)
+		} else {
+			None
+		};
 
-		let dotfiles = source.join("dotfiles");
-		try_exists!(dotfiles);
-		let dotfiles = try_canonicalize!(dotfiles);
+		let config = match SourceConfig::find(&source) {
+			Some(path) => SourceConfig::from_file(path)?,
+			None => SourceConfig::default(),
+		};
 
 		Ok(Self {
 			root: source,
 			profiles,
 			dotfiles,
+			assets,
+			config,
 		})
 	}
 
+	/// Inspects the `root` directory and its `profiles` and `dotfiles`
+	/// subdirectories without creating or modifying anything.
+	///
+	/// The `assets` subdirectory is only inspected if something exists at its
+	/// path.
+	pub fn diagnose(root: &Path) -> Vec<DirDiagnostic> {
+		let mut diagnostics = vec![
+			DirDiagnostic::inspect("source", root),
+			DirDiagnostic::inspect("profiles", &root.join("profiles")),
+			DirDiagnostic::inspect("dotfiles", &root.join("dotfiles")),
+		];
+
+		let assets = root.join("assets");
+		if std::fs::symlink_metadata(&assets).is_ok() {
+			diagnostics.push(DirDiagnostic::inspect("assets", &assets));
+		}
+
+		diagnostics
+	}
+
 	/// Returns the absolute path for the `root` directory.
 	pub fn root(&self) -> &Path {
 		&self.root
@@ -121,4 +283,46 @@ impl PunktfSource {
 	pub fn dotfiles(&self) -> &Path {
 		&self.dotfiles
 	}
+
+	/// Returns the absolute path to the `root/assets` directory, if it
+	/// exists.
+	pub fn assets(&self) -> Option<&Path> {
+		self.assets.as_deref()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	#[cfg(unix)]
+	fn dangling_dotfiles_symlink() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let root = crate::tests::test_dir();
+		std::fs::create_dir_all(root.join("profiles"))?;
+		std::os::unix::fs::symlink(root.join("missing"), root.join("dotfiles"))?;
+
+		let crate::error::Error::Source(err) =
+			PunktfSource::from_root(root.to_path_buf()).expect_err("Dangling symlink accepted")
+		else {
+			panic!("Not a source error");
+		};
+
+		assert_eq!(err.diagnostic.name, "dotfiles");
+		assert!(!err.diagnostic.exists);
+		assert_eq!(err.diagnostic.link_target, Some(root.join("missing")));
+		assert_eq!(
+			err.diagnostic.problem(),
+			Some("symlink points to a missing location")
+		);
+
+		let diagnostics = PunktfSource::diagnose(&root);
+		assert!(diagnostics[0].is_ok());
+		assert!(diagnostics[1].is_ok());
+		assert!(!diagnostics[2].is_ok());
+
+		Ok(())
+	}
 }
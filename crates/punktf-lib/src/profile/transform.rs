@@ -9,7 +9,8 @@
 
 use std::fmt;
 
-use color_eyre::Result;
+use crate::env::PunktfEnv;
+use crate::error::{Error, Result};
 
 /// A transform takes the contents of a dotfile, processes it and returns a new
 /// version of the content.
@@ -23,31 +24,76 @@ pub trait Transform {
 	///
 	/// If any error occurs during the processing it can be returned.
 	fn transform(&self, content: String) -> Result<String>;
+
+	/// Like [`Transform::transform`], but environment variables are looked up
+	/// in `env` before the environment of the process.
+	///
+	/// # Errors
+	///
+	/// If any error occurs during the processing it can be returned.
+	fn transform_with_env(&self, content: String, env: &PunktfEnv) -> Result<String> {
+		let _ = env;
+		self.transform(content)
+	}
 }
 
 /// List of all available [`Transform`s](`crate::profile::transform::Transform`).
 ///
 /// These can be added to a [`Profile`](`crate::profile::Profile`) or a
 /// [`Dotfile`](`crate::profile::dotfile::Dotfile`) to modify the text content.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 #[serde(deny_unknown_fields)]
 pub enum ContentTransformer {
 	/// Transformer which replaces line termination characters with either unix
 	/// style (`\n`) or windows style (`\r\b`).
 	LineTerminator(LineTerminator),
+
+	/// Transformer which adds or removes the UTF-8 byte order mark.
+	ByteOrderMark(ByteOrderMark),
+
+	/// Transformer which converts the indentation of each line between tabs
+	/// and spaces.
+	Indentation(Indentation),
+
+	/// Transformer which removes trailing whitespace (spaces and tabs) from
+	/// each line.
+	TrimTrailingWhitespace,
+
+	/// Transformer which replaces references to the given environment
+	/// variables (`$NAME` or `${NAME}`) with their values.
+	ExpandVariables(ExpandVariables),
 }
 
 impl Transform for ContentTransformer {
 	fn transform(&self, content: String) -> Result<String> {
 		match self {
 			Self::LineTerminator(lt) => lt.transform(content),
+			Self::ByteOrderMark(bom) => bom.transform(content),
+			Self::Indentation(indentation) => indentation.transform(content),
+			Self::TrimTrailingWhitespace => Ok(trim_trailing_whitespace(&content)),
+			Self::ExpandVariables(expand) => expand.transform(content),
+		}
+	}
+
+	fn transform_with_env(&self, content: String, env: &PunktfEnv) -> Result<String> {
+		match self {
+			Self::ExpandVariables(expand) => expand.transform_with_env(content, env),
+			transformer => transformer.transform(content),
 		}
 	}
 }
 
 impl fmt::Display for ContentTransformer {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-		fmt::Display::fmt(&self, f)
+		match self {
+			Self::LineTerminator(lt) => write!(f, "LineTerminator({lt})"),
+			Self::ByteOrderMark(bom) => write!(f, "ByteOrderMark({bom:?})"),
+			Self::Indentation(indentation) => write!(f, "Indentation({indentation:?})"),
+			Self::TrimTrailingWhitespace => f.write_str("TrimTrailingWhitespace"),
+			Self::ExpandVariables(expand) => {
+				write!(f, "ExpandVariables({})", expand.0.join(", "))
+			}
+		}
 	}
 }
 
@@ -107,6 +153,167 @@ impl fmt::Display for LineTerminator {
 	}
 }
 
+/// The UTF-8 byte order mark.
+const BOM: char = '\u{feff}';
+
+/// Transformer which adds or removes the UTF-8 byte order mark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum ByteOrderMark {
+	/// Adds a byte order mark if the content does not start with one.
+	Add,
+
+	/// Removes the byte order mark if the content starts with one.
+	Remove,
+}
+
+impl Transform for ByteOrderMark {
+	fn transform(&self, mut content: String) -> Result<String> {
+		match self {
+			Self::Add if !content.starts_with(BOM) => content.insert(0, BOM),
+			Self::Remove if content.starts_with(BOM) => {
+				let _ = content.remove(0);
+			}
+			_ => {}
+		}
+
+		Ok(content)
+	}
+}
+
+/// Transformer which converts the indentation (the leading whitespace) of
+/// each line between tabs and spaces.
+///
+/// The value is the width of a tab in spaces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Indentation {
+	/// Replaces the tabs of the indentation with spaces.
+	TabsToSpaces(usize),
+
+	/// Replaces the spaces of the indentation with tabs. Spaces which do not
+	/// fill a whole tab are retained.
+	SpacesToTabs(usize),
+}
+
+impl Transform for Indentation {
+	fn transform(&self, content: String) -> Result<String> {
+		let (Self::TabsToSpaces(width) | Self::SpacesToTabs(width)) = *self;
+
+		if width == 0 {
+			return Err(Error::Transform(String::from(
+				"The tab width of the indentation must be at least 1",
+			)));
+		}
+
+		let mut out = String::with_capacity(content.len());
+
+		for line in content.split_inclusive('\n') {
+			let rest = line.trim_start_matches([' ', '\t']);
+			let indent = &line[..line.len() - rest.len()];
+
+			// Width of the indentation in columns.
+			let columns = indent.chars().fold(0, |columns, c| match c {
+				'\t' => (columns / width + 1) * width,
+				_ => columns + 1,
+			});
+
+			match self {
+				Self::TabsToSpaces(_) => out.push_str(&" ".repeat(columns)),
+				Self::SpacesToTabs(_) => {
+					out.push_str(&"\t".repeat(columns / width));
+					out.push_str(&" ".repeat(columns % width));
+				}
+			}
+
+			out.push_str(rest);
+		}
+
+		Ok(out)
+	}
+}
+
+/// Removes trailing spaces and tabs from each line of `content`.
+///
+/// The line terminators are retained.
+fn trim_trailing_whitespace(content: &str) -> String {
+	content
+		.split_inclusive('\n')
+		.map(|line| {
+			let (line, terminator) = match line.strip_suffix("\r\n") {
+				Some(line) => (line, "\r\n"),
+				None => match line.strip_suffix('\n') {
+					Some(line) => (line, "\n"),
+					None => (line, ""),
+				},
+			};
+
+			format!("{}{terminator}", line.trim_end_matches([' ', '\t']))
+		})
+		.collect()
+}
+
+/// Transformer which replaces references to the given environment variables
+/// (`$NAME` or `${NAME}`) with their values.
+///
+/// References to variables which are not listed are retained. Variables are
+/// looked up in the [`PunktfEnv`] (if given) before the environment of the
+/// process. If `HOME` is listed but not set, the home directory of the
+/// current user is used.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct ExpandVariables(pub Vec<String>);
+
+impl ExpandVariables {
+	/// Gets the value of the environment variable `name`.
+	fn value(name: &str, env: &PunktfEnv) -> Result<String> {
+		match env.var(name) {
+			Some(value) => Ok(value),
+			None if name == "HOME" => Ok(shellexpand::tilde("~").into_owned()),
+			None => Err(Error::Transform(format!(
+				"Failed to get variable `{name}`: environment variable not found"
+			))),
+		}
+	}
+}
+
+impl Transform for ExpandVariables {
+	fn transform(&self, content: String) -> Result<String> {
+		self.transform_with_env(content, &PunktfEnv::new())
+	}
+
+	fn transform_with_env(&self, content: String, env: &PunktfEnv) -> Result<String> {
+		/// Checks if `c` can be part of a variable name.
+		const fn is_name(c: char) -> bool {
+			c.is_ascii_alphanumeric() || c == '_'
+		}
+
+		let mut out = String::with_capacity(content.len());
+		let mut rest = content.as_str();
+
+		while let Some(idx) = rest.find('$') {
+			out.push_str(&rest[..idx]);
+			rest = &rest[idx + 1..];
+
+			let (name, len) = match rest.strip_prefix('{').and_then(|r| r.split_once('}')) {
+				Some((name, _)) => (name, name.len() + 2),
+				None => {
+					let len = rest.find(|c| !is_name(c)).unwrap_or(rest.len());
+					(&rest[..len], len)
+				}
+			};
+
+			if self.0.iter().any(|n| n == name) {
+				out.push_str(&Self::value(name, env)?);
+				rest = &rest[len..];
+			} else {
+				out.push('$');
+			}
+		}
+
+		out.push_str(rest);
+
+		Ok(out)
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use pretty_assertions::assert_eq;
@@ -136,4 +343,48 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[test]
+	fn content_transformers() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		assert_eq!(
+			ByteOrderMark::Add.transform(String::from("a"))?,
+			"\u{feff}a"
+		);
+		assert_eq!(
+			ByteOrderMark::Remove.transform(String::from("\u{feff}a"))?,
+			"a"
+		);
+
+		assert_eq!(
+			Indentation::TabsToSpaces(4).transform(String::from("\t  \ta\tb\n\tc"))?,
+			"        a\tb\n    c"
+		);
+		assert_eq!(
+			Indentation::SpacesToTabs(4).transform(String::from("      a\n\t b"))?,
+			"\t  a\n\t b"
+		);
+		assert!(Indentation::TabsToSpaces(0)
+			.transform(String::new())
+			.is_err());
+
+		assert_eq!(
+			ContentTransformer::TrimTrailingWhitespace
+				.transform(String::from("a \t\r\nb  \nc "))?,
+			"a\r\nb\nc"
+		);
+
+		let mut env = PunktfEnv::new();
+		env.set("PUNKTF_TEST_EXPAND", "/home/demo");
+		assert_eq!(
+			ExpandVariables(vec![String::from("PUNKTF_TEST_EXPAND")]).transform_with_env(
+				String::from("$PUNKTF_TEST_EXPAND/a ${PUNKTF_TEST_EXPAND}/b $OTHER $"),
+				&env
+			)?,
+			"/home/demo/a /home/demo/b $OTHER $"
+		);
+
+		Ok(())
+	}
 }
@@ -0,0 +1,667 @@
+//! Validation of profile files.
+//!
+//! In contrast to loading a profile (related:
+//! [`Profile::from_file`](`crate::profile::Profile::from_file`)), which stops
+//! at the first error, the validation tries to find all problems of a profile.
+//! Each dotfile and link is checked on its own and semantic rules are checked
+//! in addition to the schema:
+//!
+//! - The path of each dotfile exists in the `dotfiles` directory.
+//! - No two dotfiles with the same priority are deployed to the same target.
+//! - All profiles given in `extends` exist.
+//! - All aliases are unique among all profiles.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+
+use crate::error::{Error, Result};
+use crate::profile::dotfile::Dotfile;
+use crate::profile::link::Symlink;
+use crate::profile::source::PunktfSource;
+use crate::profile::{lookup_profile, profile_entries, Profile};
+use crate::template::diagnostic::DiagnosticFormatter;
+use crate::template::source::Source;
+use crate::template::span::ByteSpan;
+
+/// A problem which was found in a profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+	/// Description of the problem.
+	pub message: String,
+
+	/// Byte range in the profile content the problem refers to, if it could
+	/// be determined.
+	pub span: Option<Range<usize>>,
+}
+
+impl Problem {
+	/// Creates a new problem.
+	fn new<M: Into<String>>(message: M, span: Option<Range<usize>>) -> Self {
+		Self {
+			message: message.into(),
+			span,
+		}
+	}
+
+	/// Formats the problem together with the referenced lines of `content`,
+	/// which is the content of the profile at `path`.
+	pub fn render(&self, path: &Path, content: &str) -> String {
+		let source = Source::file(path, content);
+		let mut fmt = DiagnosticFormatter::new(&source, &self.message);
+
+		if let Some(span) = &self.span {
+			let _ = fmt.primary_span(&ByteSpan::new(span.start, span.end));
+		}
+
+		fmt.finish()
+	}
+}
+
+impl fmt::Display for Problem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.message)
+	}
+}
+
+/// Problems of one or more profiles which are reported together.
+///
+/// This is returned as error if a profile could not be loaded (related:
+/// [`Profile::from_file`](`crate::profile::Profile::from_file`) and
+/// [`resolve_profile`](`crate::profile::resolve_profile`)), so that all
+/// problems can be fixed at once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileErrors {
+	/// Rendered problems with the referenced lines of the profiles.
+	entries: Vec<String>,
+}
+
+impl ProfileErrors {
+	/// Adds all `problems` of the profile with the `content` located at
+	/// `path`.
+	pub fn add_problems(&mut self, path: &Path, content: &str, problems: &[Problem]) {
+		self.entries
+			.extend(problems.iter().map(|problem| match problem.span {
+				Some(_) => problem.render(path, content),
+				None => format!("{} (in `{}`)", problem.message, path.display()),
+			}));
+	}
+
+	/// Adds an error which occurred while loading a profile.
+	///
+	/// If the error is an [`Error::Profile`], all of its entries are added.
+	pub fn add_error(&mut self, err: &Error) {
+		if let Error::Profile(errors) = err {
+			self.entries.extend(errors.entries.iter().cloned());
+			return;
+		}
+
+		let mut entry = err.to_string();
+		let mut source = std::error::Error::source(err);
+
+		while let Some(err) = source {
+			entry.push_str(&format!(": {err}"));
+			source = err.source();
+		}
+
+		self.entries.push(entry);
+	}
+
+	/// Returns the number of problems.
+	pub const fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Checks if no problems were added.
+	pub const fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+}
+
+impl fmt::Display for ProfileErrors {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Found {} problem(s) in profiles", self.entries.len())?;
+
+		for entry in &self.entries {
+			write!(f, "\n\n{entry}")?;
+		}
+
+		Ok(())
+	}
+}
+
+impl std::error::Error for ProfileErrors {}
+
+/// Converts a one-indexed `line` and `column` into a byte offset of `content`.
+fn offset(content: &str, line: usize, column: usize) -> usize {
+	let line_start: usize = content
+		.split_inclusive('\n')
+		.take(line.saturating_sub(1))
+		.map(str::len)
+		.sum();
+
+	(line_start + column.saturating_sub(1)).min(content.len())
+}
+
+/// Searches for the text of values in the content of a profile.
+///
+/// The search continues after the last found value, which allows to find the
+/// spans of values which appear multiple times (e.g. dotfile paths) in the
+/// order they are defined.
+struct Locator<'a> {
+	/// The content of the profile.
+	content: &'a str,
+
+	/// Byte offset from which the next search starts.
+	cursor: usize,
+}
+
+impl<'a> Locator<'a> {
+	/// Creates a new locator which starts searching after the first
+	/// occurrence of `key`.
+	fn new(content: &'a str, key: &str) -> Self {
+		let cursor = content.find(key).map(|idx| idx + key.len()).unwrap_or(0);
+
+		Self { content, cursor }
+	}
+
+	/// Finds the span of the next occurrence of `value`.
+	///
+	/// Occurrences which are only part of a longer word or path (e.g. `a` in
+	/// `path`) are skipped.
+	fn next(&mut self, value: &str) -> Option<Range<usize>> {
+		/// Checks if `c` can be part of a word or path.
+		fn is_word(c: char) -> bool {
+			c.is_alphanumeric() || "_-./\\~".contains(c)
+		}
+
+		if value.is_empty() {
+			return None;
+		}
+
+		let mut from = self.cursor;
+		loop {
+			let start = from + self.content[from..].find(value)?;
+			let end = start + value.len();
+
+			let before = self.content[..start].chars().next_back();
+			let after = self.content[end..].chars().next();
+
+			if before.is_some_and(is_word) || after.is_some_and(is_word) {
+				from = end;
+				continue;
+			}
+
+			self.cursor = end;
+			return Some(start..end);
+		}
+	}
+}
+
+/// A parsed profile document of a specific format.
+trait Document: Sized {
+	/// Parses the document from `content`.
+	fn parse(content: &str) -> std::result::Result<Self, Problem>;
+
+	/// Removes the sequence with the name `key` from the document and returns
+	/// its items.
+	///
+	/// If the value is not a sequence, it is left in the document.
+	fn take_items(&mut self, key: &str) -> Vec<Self>;
+
+	/// Returns the value of the string field `key`.
+	fn field(&self, key: &str) -> Option<&str>;
+
+	/// Deserializes the document into `T`.
+	fn deserialize<T: DeserializeOwned>(self) -> std::result::Result<T, String>;
+}
+
+#[cfg(feature = "profile-yaml")]
+impl Document for serde_yaml::Value {
+	fn parse(content: &str) -> std::result::Result<Self, Problem> {
+		serde_yaml::from_str(content).map_err(|err| {
+			let span = err.location().map(|loc| loc.index()..loc.index());
+			Problem::new(format!("Invalid yaml: {err}"), span)
+		})
+	}
+
+	fn take_items(&mut self, key: &str) -> Vec<Self> {
+		let Some(mapping) = self.as_mapping_mut() else {
+			return Vec::new();
+		};
+
+		match mapping.remove(key) {
+			Some(Self::Sequence(items)) => items,
+			Some(other) => {
+				let _ = mapping.insert(Self::from(key), other);
+				Vec::new()
+			}
+			None => Vec::new(),
+		}
+	}
+
+	fn field(&self, key: &str) -> Option<&str> {
+		self.get(key).and_then(Self::as_str)
+	}
+
+	fn deserialize<T: DeserializeOwned>(self) -> std::result::Result<T, String> {
+		serde_yaml::from_value(self).map_err(|err| err.to_string())
+	}
+}
+
+impl Document for serde_json::Value {
+	fn parse(content: &str) -> std::result::Result<Self, Problem> {
+		serde_json::from_str(content).map_err(|err| {
+			let idx = offset(content, err.line(), err.column());
+			Problem::new(format!("Invalid json: {err}"), Some(idx..idx))
+		})
+	}
+
+	fn take_items(&mut self, key: &str) -> Vec<Self> {
+		let Some(object) = self.as_object_mut() else {
+			return Vec::new();
+		};
+
+		match object.remove(key) {
+			Some(Self::Array(items)) => items,
+			Some(other) => {
+				let _ = object.insert(key.to_string(), other);
+				Vec::new()
+			}
+			None => Vec::new(),
+		}
+	}
+
+	fn field(&self, key: &str) -> Option<&str> {
+		self.get(key).and_then(Self::as_str)
+	}
+
+	fn deserialize<T: DeserializeOwned>(self) -> std::result::Result<T, String> {
+		serde_json::from_value(self).map_err(|err| err.to_string())
+	}
+}
+
+/// The parts of a profile which could be parsed.
+#[derive(Default)]
+struct Parsed {
+	/// The profile without dotfiles and links, if it could be parsed.
+	profile: Option<Profile>,
+
+	/// All valid dotfiles together with the span of their path.
+	dotfiles: Vec<(Dotfile, Option<Range<usize>>)>,
+
+	/// All problems found while parsing.
+	problems: Vec<Problem>,
+}
+
+/// Parses the profile `content` and checks each dotfile and link on its own.
+fn parse<D: Document>(content: &str) -> Parsed {
+	let mut doc = match D::parse(content) {
+		Ok(doc) => doc,
+		Err(problem) => {
+			return Parsed {
+				problems: vec![problem],
+				..Default::default()
+			}
+		}
+	};
+
+	let dotfiles = doc.take_items("dotfiles");
+	let links = doc.take_items("links");
+
+	let mut parsed = Parsed::default();
+
+	match doc.deserialize::<Profile>() {
+		Ok(profile) => parsed.profile = Some(profile),
+		Err(err) => parsed
+			.problems
+			.push(Problem::new(format!("Invalid profile: {err}"), None)),
+	}
+
+	let mut locator = Locator::new(content, "dotfiles");
+	for (idx, item) in dotfiles.into_iter().enumerate() {
+		let span = item.field("path").and_then(|path| locator.next(path));
+
+		match item.deserialize::<Dotfile>() {
+			Ok(dotfile) => parsed.dotfiles.push((dotfile, span)),
+			Err(err) => parsed
+				.problems
+				.push(Problem::new(format!("Invalid dotfile #{idx}: {err}"), span)),
+		}
+	}
+
+	let mut locator = Locator::new(content, "links");
+	for (idx, item) in links.into_iter().enumerate() {
+		let span = item
+			.field("source_path")
+			.and_then(|path| locator.next(path));
+
+		if let Err(err) = item.deserialize::<Symlink>() {
+			parsed
+				.problems
+				.push(Problem::new(format!("Invalid link #{idx}: {err}"), span));
+		}
+	}
+
+	parsed
+}
+
+/// Parses the profile `content` with the parser guessed by the file extension
+/// of `path`.
+fn parse_path(path: &Path, content: &str) -> Parsed {
+	let extension = path
+		.extension()
+		.and_then(|ext| ext.to_str())
+		.unwrap_or_default()
+		.to_ascii_lowercase();
+
+	match extension.as_str() {
+		"json" => parse::<serde_json::Value>(content),
+		#[cfg(feature = "profile-yaml")]
+		"yaml" | "yml" => parse::<serde_yaml::Value>(content),
+		_ => Parsed {
+			problems: vec![Problem::new(
+				format!("Found unsupported file extension for profile (extension: {extension:?})"),
+				None,
+			)],
+			..Default::default()
+		},
+	}
+}
+
+/// Returns all problems of the profile `content` located at `path` which
+/// prevent it from being parsed.
+///
+/// In contrast to [`validate`], no semantic rules are checked.
+pub(crate) fn schema_problems(path: &Path, content: &str) -> Vec<Problem> {
+	parse_path(path, content).problems
+}
+
+/// Validates the profile with the given `content` located at `path`.
+///
+/// Returns all problems which were found. If the profile is valid, the
+/// returned list is empty.
+pub fn validate(source: &PunktfSource, path: &Path, content: &str) -> Vec<Problem> {
+	let Parsed {
+		profile,
+		dotfiles,
+		mut problems,
+	} = parse_path(path, content);
+
+	check_dotfiles(source, profile.as_ref(), &dotfiles, &mut problems);
+
+	if let Some(profile) = &profile {
+		check_references(source, path, content, profile, &mut problems);
+	}
+
+	problems
+}
+
+/// Reads and validates the profile file at `path`
+/// (related: [`validate`]).
+///
+/// # Errors
+///
+/// An error is returned if the file could not be read.
+pub fn validate_file(source: &PunktfSource, path: &Path) -> Result<Vec<Problem>> {
+	let content = std::fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+
+	Ok(validate(source, path, &content))
+}
+
+/// Checks that all dotfiles exist and that no two dotfiles with the same
+/// priority are deployed to the same target.
+fn check_dotfiles(
+	source: &PunktfSource,
+	profile: Option<&Profile>,
+	dotfiles: &[(Dotfile, Option<Range<usize>>)],
+	problems: &mut Vec<Problem>,
+) {
+	let target = profile.and_then(|profile| profile.target.as_deref());
+	let mut targets: HashMap<(PathBuf, u32), &Dotfile> = HashMap::new();
+
+	for (dotfile, span) in dotfiles {
+		if source
+			.dotfiles()
+			.join(&dotfile.path)
+			.symlink_metadata()
+			.is_err()
+		{
+			problems.push(Problem::new(
+				format!(
+					"Dotfile `{}` does not exist in `{}`",
+					dotfile.path.display(),
+					source.dotfiles().display()
+				),
+				span.clone(),
+			));
+		}
+
+		let named_target = dotfile.target_name.as_ref().and_then(|name| {
+			let path = profile.and_then(|profile| profile.targets.get(name));
+
+			// Named targets may also be defined by an extended profile
+			if path.is_none() && profile.is_some_and(|profile| profile.extends.is_empty()) {
+				problems.push(Problem::new(
+					format!(
+						"Dotfile `{}` references the undefined target `{name}`",
+						dotfile.path.display()
+					),
+					span.clone(),
+				));
+			}
+
+			path.map(PathBuf::as_path)
+		});
+
+		let target_path = dotfile
+			.overwrite_target
+			.as_deref()
+			.or(named_target)
+			.or(target)
+			.unwrap_or_else(|| Path::new(""))
+			.join(dotfile.rename.as_ref().unwrap_or(&dotfile.path));
+		let priority = dotfile.priority.map(|p| p.0).unwrap_or(0);
+
+		if let Some(other) = targets.insert((target_path.clone(), priority), dotfile) {
+			problems.push(Problem::new(
+				format!(
+					"Dotfile `{}` is deployed to the same target `{}` as dotfile `{}` with the same priority",
+					dotfile.path.display(),
+					target_path.display(),
+					other.path.display()
+				),
+				span.clone(),
+			));
+		}
+	}
+}
+
+/// Checks that all profiles in `extends` exist and that all aliases of the
+/// profile are unique.
+fn check_references(
+	source: &PunktfSource,
+	path: &Path,
+	content: &str,
+	profile: &Profile,
+	problems: &mut Vec<Problem>,
+) {
+	let entries = match profile_entries(source) {
+		Ok(entries) => entries,
+		Err(err) => {
+			problems.push(Problem::new(
+				format!("Failed to read other profiles: {err}"),
+				None,
+			));
+			return;
+		}
+	};
+
+	let is_self = |other: &Path| {
+		other == path
+			|| other
+				.canonicalize()
+				.ok()
+				.zip(path.canonicalize().ok())
+				.is_some_and(|(a, b)| a == b)
+	};
+
+	let mut locator = Locator::new(content, "extends");
+	for name in &profile.extends {
+		let span = locator.next(name);
+
+		let target = entries
+			.iter()
+			.find(|entry| &entry.name == name || entry.aliases.contains(name));
+
+		match target {
+			None => problems.push(Problem::new(
+				format!("Extended profile `{name}` does not exist"),
+				span,
+			)),
+			Some(entry) if is_self(&entry.path) => problems.push(Problem::new(
+				format!("Profile extends itself with `{name}`"),
+				span,
+			)),
+			Some(_) => {}
+		}
+	}
+
+	let mut locator = Locator::new(content, "aliases");
+	for (idx, alias) in profile.aliases.iter().enumerate() {
+		let span = locator.next(alias);
+
+		if profile.aliases[..idx].contains(alias) {
+			problems.push(Problem::new(
+				format!("Alias `{alias}` is given multiple times"),
+				span,
+			));
+			continue;
+		}
+
+		let taken_by = entries.iter().find(|entry| {
+			!is_self(&entry.path) && (&entry.name == alias || entry.aliases.contains(alias))
+		});
+
+		if let Some(entry) = taken_by {
+			problems.push(Problem::new(
+				format!(
+					"Alias `{alias}` is already taken by profile `{}`",
+					entry.path.display()
+				),
+				span,
+			));
+		}
+	}
+}
+
+/// Finds the profile file for the profile name or alias `name`.
+///
+/// Names are matched like in
+/// [`resolve_profile`](`crate::profile::resolve_profile`).
+///
+/// # Errors
+///
+/// An error is returned if no profile with the given name exists.
+pub fn find_profile(source: &PunktfSource, name: &str) -> Result<PathBuf> {
+	let entries = profile_entries(source)?;
+
+	let candidates = entries.iter().flat_map(|entry| {
+		std::iter::once(entry.name.as_str())
+			.chain(entry.aliases.iter().map(String::as_str))
+			.map(|name| (name, entry.path.as_path()))
+	});
+
+	lookup_profile(candidates, name).map(|(_, path)| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn validate_collects_all_problems() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("profiles"))?;
+		std::fs::create_dir_all(dir.join("dotfiles"))?;
+		std::fs::write(dir.join("dotfiles/init.vim"), "")?;
+		std::fs::write(dir.join("profiles/base.yaml"), "aliases: [common]\n")?;
+
+		let source = PunktfSource::from_root(dir.to_path_buf())?;
+		let path = dir.join("profiles/test.yaml");
+		let content = r#"aliases: [common]
+extends: [base, unknown]
+target: /home/demo
+dotfiles:
+  - path: init.vim
+  - path: init.vim
+  - path: missing
+  - path: invalid
+    merge: Sometimes
+"#;
+
+		let problems = validate(&source, &path, content);
+		let messages: Vec<_> = problems.iter().map(|p| p.message.as_str()).collect();
+		assert_eq!(problems.len(), 5, "{messages:#?}");
+
+		assert!(messages[0].starts_with("Invalid dotfile #3"));
+		assert!(messages[1].contains("same target"));
+		assert!(messages[2].starts_with("Dotfile `missing` does not exist"));
+		assert!(messages[3].starts_with("Extended profile `unknown`"));
+		assert!(messages[4].starts_with("Alias `common` is already taken"));
+
+		// Second occurrence of `init.vim`
+		assert_eq!(problems[1].span, Some(101..109));
+		assert_eq!(&content[101..109], "init.vim");
+
+		assert_eq!(
+			find_profile(&source, "common")?,
+			dir.join("profiles/base.yaml")
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn profile_errors_are_grouped() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("profiles"))?;
+		std::fs::write(
+			dir.join("profiles/main.yaml"),
+			"extends: [first, second, unknown]\n",
+		)?;
+		std::fs::write(
+			dir.join("profiles/first.yaml"),
+			"dotfiles:\n  - path: a\n    merge: Sometimes\n  - priority: high\n",
+		)?;
+		std::fs::write(dir.join("profiles/second.yaml"), "target: [\n")?;
+
+		let source = PunktfSource::from_root(dir.to_path_buf())?;
+
+		let err =
+			Profile::from_file(dir.join("profiles/first.yaml")).expect_err("Profile to be invalid");
+		let Error::Profile(errors) = err else {
+			panic!("Error to contain all problems: {err}");
+		};
+		assert_eq!(errors.len(), 2);
+
+		let mut builder = crate::profile::LayeredProfile::build();
+		let err = crate::profile::resolve_profile(&mut builder, &source, "main")
+			.expect_err("Profiles to be invalid");
+		let Error::Profile(errors) = err else {
+			panic!("Error to contain all problems: {err}");
+		};
+		assert_eq!(errors.len(), 4, "{errors}");
+
+		Ok(())
+	}
+}
@@ -1,15 +1,296 @@
 //! User defined variables used by [profiles](`crate::profile::Profile`) and
 //! [dotfiles](`crate::profile::dotfile::Dotfile`).
 
-use std::collections::HashMap;
-use std::ops::Deref;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 
 /// Variables that replace values in templates
 pub trait Vars {
 	/// Get a variable by name
-	fn var<K: AsRef<str>>(&self, key: K) -> Option<&str>;
+	fn var<K: AsRef<str>>(&self, key: K) -> Option<Cow<'_, str>>;
+
+	/// Get a variable by name, retaining its type.
+	///
+	/// By default all values are treated as strings.
+	fn value<K: AsRef<str>>(&self, key: K) -> Option<Cow<'_, Value>> {
+		self.var(key)
+			.map(|value| Cow::Owned(Value::String(value.into_owned())))
+	}
+}
+
+impl<V: Vars + ?Sized> Vars for &V {
+	fn var<K: AsRef<str>>(&self, key: K) -> Option<Cow<'_, str>> {
+		(**self).var(key)
+	}
+
+	fn value<K: AsRef<str>>(&self, key: K) -> Option<Cow<'_, Value>> {
+		(**self).value(key)
+	}
+}
+
+/// Value of a user defined variable.
+///
+/// Values are converted to strings when they are inserted into a template
+/// (related: [`Value::as_str`]), but retain their type for
+/// [if expressions](`crate::template`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+	/// A boolean (`true`/`false`).
+	Bool(bool),
+
+	/// An integer.
+	Int(i64),
+
+	/// A floating point number.
+	Float(f64),
+
+	/// A string.
+	String(String),
+
+	/// A list of values. Converted to a string, the items are separated by a
+	/// single space.
+	List(Vec<Value>),
+}
+
+impl Value {
+	/// Returns the string representation of the value.
+	pub fn as_str(&self) -> Cow<'_, str> {
+		match self {
+			Self::String(value) => Cow::Borrowed(value),
+			_ => Cow::Owned(self.to_string()),
+		}
+	}
+
+	/// Checks if the value is considered `true` by an if expression like
+	/// `{{@if {{VAR}}}}`.
+	///
+	/// `false`, `0`, `0.0` and empty lists are falsy. Strings are always
+	/// truthy, so that the expression only checks for the existence of a
+	/// variable with a string value.
+	pub fn is_truthy(&self) -> bool {
+		match self {
+			Self::Bool(value) => *value,
+			Self::Int(value) => *value != 0,
+			Self::Float(value) => *value != 0.0,
+			Self::String(_) => true,
+			Self::List(values) => !values.is_empty(),
+		}
+	}
+
+	/// Checks if the value is equal to the literal `other` of an if
+	/// expression.
+	///
+	/// Booleans are compared case-insensitively and numbers by their numeric
+	/// value (e.g. `1` is equal to `"1.0"`). All other values are compared by
+	/// their string representation.
+	pub fn matches(&self, other: &str) -> bool {
+		match self {
+			Self::Bool(value) => other.trim().eq_ignore_ascii_case(&value.to_string()),
+			// Compare as integers first to retain the precision of big values.
+			Self::Int(value) => match other.trim().parse::<i64>() {
+				Ok(other) => other == *value,
+				Err(_) => other
+					.trim()
+					.parse::<f64>()
+					.is_ok_and(|other| other == *value as f64),
+			},
+			Self::Float(value) => other
+				.trim()
+				.parse::<f64>()
+				.is_ok_and(|other| other == *value),
+			_ => self.as_str() == other,
+		}
+	}
+
+	/// Compares the value with the literal `other` of an if expression like
+	/// `{{@if {{VAR}} >= "OTHER"}}`.
+	///
+	/// Both sides are compared as integers, as versions (e.g. `0.9` or
+	/// `v1.2.3-rc1`, see [`compare_versions`]) or as floating point numbers,
+	/// whichever both of them can be interpreted as first. Values which are
+	/// defined as numbers are always compared as such if `other` is a number.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the sides can not be compared, e.g. because one
+	/// of them is not numeric.
+	pub fn compare(&self, other: &str) -> Result<Ordering, String> {
+		let other = other.trim();
+
+		match self {
+			Self::Int(value) => {
+				if let Ok(other) = other.parse::<i64>() {
+					return Ok(value.cmp(&other));
+				}
+			}
+			Self::Float(value) => {
+				if let Ok(other) = other.parse::<f64>() {
+					return value
+						.partial_cmp(&other)
+						.ok_or_else(|| format!("`{value}` and `{other}` can not be compared"));
+				}
+			}
+			Self::Bool(_) | Self::List(_) => {
+				return Err(format!("`{self}` is neither a number nor a version"));
+			}
+			Self::String(_) => {}
+		}
+
+		let value = self.as_str();
+		let value = value.trim();
+
+		if let (Ok(value), Ok(other)) = (value.parse::<i64>(), other.parse::<i64>()) {
+			return Ok(value.cmp(&other));
+		}
+
+		if let Some(ordering) = compare_versions(value, other) {
+			return Ok(ordering);
+		}
+
+		match (value.parse::<f64>(), other.parse::<f64>()) {
+			(Ok(a), Ok(b)) => a
+				.partial_cmp(&b)
+				.ok_or_else(|| format!("`{value}` and `{other}` can not be compared")),
+			(Err(_), _) => Err(format!("`{value}` is neither a number nor a version")),
+			(_, Err(_)) => Err(format!("`{other}` is neither a number nor a version")),
+		}
+	}
+}
+
+/// Compares two version strings like `1.2.3`.
+///
+/// A version consists of numeric components separated by `.`, optionally
+/// prefixed with `v` and followed by a pre-release (`-rc1`) and/or build
+/// (`+abc`) suffix. Missing components are treated as `0`, so `0.9` equals
+/// `0.9.0`. A version with a pre-release comes before the same version
+/// without one, pre-releases are compared as strings and build suffixes are
+/// ignored.
+///
+/// Returns `None` if any of the strings is not a version.
+pub fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+	/// Splits a version into its numeric components and pre-release.
+	fn parse(version: &str) -> Option<(Vec<u64>, Option<&str>)> {
+		let version = version.strip_prefix(['v', 'V']).unwrap_or(version);
+		let version = version
+			.split_once('+')
+			.map_or(version, |(version, _)| version);
+		let (core, pre) = match version.split_once('-') {
+			Some((core, pre)) => (core, Some(pre)),
+			None => (version, None),
+		};
+
+		let components = core
+			.split('.')
+			.map(|component| {
+				if !component.is_empty() && component.bytes().all(|b| b.is_ascii_digit()) {
+					component.parse().ok()
+				} else {
+					None
+				}
+			})
+			.collect::<Option<Vec<u64>>>()?;
+
+		Some((components, pre))
+	}
+
+	let (a, a_pre) = parse(a)?;
+	let (b, b_pre) = parse(b)?;
+
+	let len = a.len().max(b.len());
+	let component = |version: &[u64], idx: usize| version.get(idx).copied().unwrap_or(0);
+
+	let ordering = (0..len)
+		.map(|idx| component(&a, idx).cmp(&component(&b, idx)))
+		.find(|ordering| ordering.is_ne())
+		.unwrap_or(Ordering::Equal);
+
+	Some(ordering.then_with(|| match (a_pre, b_pre) {
+		(None, None) => Ordering::Equal,
+		(Some(_), None) => Ordering::Less,
+		(None, Some(_)) => Ordering::Greater,
+		(Some(a), Some(b)) => a.cmp(b),
+	}))
+}
+
+impl PartialEq for Value {
+	fn eq(&self, other: &Self) -> bool {
+		match (self, other) {
+			(Self::Bool(a), Self::Bool(b)) => a == b,
+			(Self::Int(a), Self::Int(b)) => a == b,
+			// Compare the bits so that `Eq` can be implemented.
+			(Self::Float(a), Self::Float(b)) => a.to_bits() == b.to_bits(),
+			(Self::String(a), Self::String(b)) => a == b,
+			(Self::List(a), Self::List(b)) => a == b,
+			_ => false,
+		}
+	}
+}
+
+impl Eq for Value {}
+
+impl fmt::Display for Value {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Bool(value) => value.fmt(f),
+			Self::Int(value) => value.fmt(f),
+			Self::Float(value) => value.fmt(f),
+			Self::String(value) => f.write_str(value),
+			Self::List(values) => {
+				for (idx, value) in values.iter().enumerate() {
+					if idx > 0 {
+						f.write_str(" ")?;
+					}
+
+					value.fmt(f)?;
+				}
+
+				Ok(())
+			}
+		}
+	}
+}
+
+impl From<String> for Value {
+	fn from(value: String) -> Self {
+		Self::String(value)
+	}
+}
+
+impl From<&str> for Value {
+	fn from(value: &str) -> Self {
+		Self::String(value.to_string())
+	}
+}
+
+impl From<bool> for Value {
+	fn from(value: bool) -> Self {
+		Self::Bool(value)
+	}
+}
+
+impl From<i64> for Value {
+	fn from(value: i64) -> Self {
+		Self::Int(value)
+	}
+}
+
+impl From<f64> for Value {
+	fn from(value: f64) -> Self {
+		Self::Float(value)
+	}
+}
+
+impl<V: Into<Self>> From<Vec<V>> for Value {
+	fn from(values: Vec<V>) -> Self {
+		Self::List(values.into_iter().map(Into::into).collect())
+	}
 }
 
 /// User defined variables
@@ -17,15 +298,22 @@ pub trait Vars {
 pub struct Variables {
 	/// User defined variables with a name and value.
 	#[serde(flatten)]
-	pub inner: HashMap<String, String>,
+	pub inner: HashMap<String, Value>,
 }
 
 impl Vars for Variables {
-	fn var<K>(&self, key: K) -> Option<&str>
+	fn var<K>(&self, key: K) -> Option<Cow<'_, str>>
 	where
 		K: AsRef<str>,
 	{
-		self.inner.get(key.as_ref()).map(|value| value.deref())
+		self.inner.get(key.as_ref()).map(Value::as_str)
+	}
+
+	fn value<K>(&self, key: K) -> Option<Cow<'_, Value>>
+	where
+		K: AsRef<str>,
+	{
+		self.inner.get(key.as_ref()).map(Cow::Borrowed)
 	}
 }
 
@@ -34,7 +322,7 @@ impl Variables {
 	pub fn from_items<K, V, I, II>(iter: II) -> Self
 	where
 		K: Into<String>,
-		V: Into<String>,
+		V: Into<Value>,
 		I: Iterator<Item = (K, V)>,
 		II: IntoIterator<IntoIter = I, Item = (K, V)>,
 	{
@@ -46,3 +334,140 @@ impl Variables {
 		Self { inner }
 	}
 }
+
+/// Memoizes the values of variables, so that each variable is looked up at
+/// most once, even if it is used by many templates.
+///
+/// This is shared across all items of a deployment to avoid running expensive
+/// lookups (e.g. variables backed by commands or secret stores) for every file.
+/// The time each lookup took is recorded.
+#[derive(Default, Debug, Clone)]
+pub struct VariableCache {
+	/// Already looked up values. A value of `None` means the variable was not
+	/// found.
+	values: RefCell<HashMap<String, Option<Value>>>,
+
+	/// Time each lookup took.
+	timings: RefCell<BTreeMap<String, Duration>>,
+}
+
+impl VariableCache {
+	/// Creates a new, empty, instance.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Returns a [`Vars`] implementation which looks up variables in `vars`
+	/// and memoizes them in this cache.
+	///
+	/// # NOTE
+	/// The cache does not differentiate between different `vars`. It must
+	/// only be used with the same set of variables.
+	pub const fn with<'a, V: Vars>(&'a self, vars: &'a V) -> CachedVars<'a, V> {
+		CachedVars { cache: self, vars }
+	}
+
+	/// Returns the time the lookup of each variable took.
+	pub fn timings(&self) -> BTreeMap<String, Duration> {
+		self.timings.borrow().clone()
+	}
+}
+
+/// A [`Vars`] implementation which memoizes all looked up variables in a
+/// [`VariableCache`].
+#[derive(Debug, Clone, Copy)]
+pub struct CachedVars<'a, V> {
+	/// Cache the values are stored in.
+	cache: &'a VariableCache,
+
+	/// Variables which are used for lookups of unknown values.
+	vars: &'a V,
+}
+
+impl<V: Vars> Vars for CachedVars<'_, V> {
+	fn var<K>(&self, key: K) -> Option<Cow<'_, str>>
+	where
+		K: AsRef<str>,
+	{
+		self.value(key)
+			.map(|value| Cow::Owned(value.as_str().into_owned()))
+	}
+
+	fn value<K>(&self, key: K) -> Option<Cow<'_, Value>>
+	where
+		K: AsRef<str>,
+	{
+		let key = key.as_ref();
+
+		if let Some(value) = self.cache.values.borrow().get(key) {
+			return value.clone().map(Cow::Owned);
+		}
+
+		let start = Instant::now();
+		let value = self.vars.value(key).map(Cow::into_owned);
+
+		self.cache
+			.timings
+			.borrow_mut()
+			.insert(key.to_string(), start.elapsed());
+		self.cache
+			.values
+			.borrow_mut()
+			.insert(key.to_string(), value.clone());
+
+		value.map(Cow::Owned)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::Cell;
+
+	use super::*;
+
+	/// Counts the lookups done.
+	struct CountingVars(Cell<usize>);
+
+	impl Vars for CountingVars {
+		fn var<K: AsRef<str>>(&self, key: K) -> Option<Cow<'_, str>> {
+			self.0.set(self.0.get() + 1);
+			(key.as_ref() == "FOO").then_some(Cow::Borrowed("BAR"))
+		}
+	}
+
+	#[test]
+	fn variable_cache_memoizes() {
+		crate::tests::setup_test_env();
+
+		let vars = CountingVars(Cell::new(0));
+		let cache = VariableCache::new();
+
+		for _ in 0..3 {
+			let cached = cache.with(&vars);
+			assert_eq!(cached.var("FOO").as_deref(), Some("BAR"));
+			assert_eq!(cached.var("MISSING"), None);
+		}
+
+		assert_eq!(vars.0.get(), 2);
+		assert_eq!(cache.timings().len(), 2);
+	}
+
+	#[test]
+	fn compare_values() {
+		crate::tests::setup_test_env();
+
+		assert_eq!(Value::Int(10).compare("9"), Ok(Ordering::Greater));
+		assert_eq!(Value::Float(0.5).compare("0.25"), Ok(Ordering::Greater));
+		assert_eq!(Value::from("0.10").compare("0.9"), Ok(Ordering::Greater));
+		assert_eq!(Value::from("v1.2").compare("1.2.0"), Ok(Ordering::Equal));
+		assert_eq!(
+			Value::from("1.2.0-rc1").compare("1.2.0+build"),
+			Ok(Ordering::Less)
+		);
+		assert_eq!(Value::from("-1.5").compare("1e3"), Ok(Ordering::Less));
+
+		assert!(Value::from("linux").compare("1").is_err());
+		assert!(Value::from("1").compare("one").is_err());
+		assert!(Value::Bool(true).compare("1").is_err());
+	}
+}
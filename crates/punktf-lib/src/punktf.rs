@@ -0,0 +1,331 @@
+//! A facade which bundles everything needed to deploy or diff a profile.
+//!
+//! This is the entry point for tools which embed `punktf`. It takes care of
+//! reading the [source](`crate::profile::source::PunktfSource`), resolving the
+//! [profile](`crate::profile::LayeredProfile`) with its extend chain and
+//! running the [`Deployer`] or [`Diff`] visitors on it.
+//!
+//! ```no_run
+//! use punktf_lib::Punktf;
+//!
+//! # fn main() -> color_eyre::Result<()> {
+//! let mut punktf = Punktf::builder()
+//!     .source("/home/demo/dotfiles")
+//!     .profile("linux")
+//!     .target("/home/demo")
+//!     .dry_run(true)
+//!     .build()?;
+//!
+//! let deployment = punktf.deploy()?;
+//! println!("Deployed {} dotfiles", deployment.dotfiles().len());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::error::{BoxError, Error, Result};
+use crate::profile::source::PunktfSource;
+use crate::profile::{resolve_profile, LayeredProfile, Profile};
+use crate::visit::deploy::ask::{MergeDecision, MergeRequest, MergeStrategy};
+use crate::visit::deploy::deployment::Deployment;
+use crate::visit::deploy::{DeployError, DeployOptions, Deployer};
+use crate::visit::diff::{Diff, Event, OwnedEvent};
+use crate::visit::TagFilter;
+
+/// Strategy which is asked whether an existing file should be overwritten
+/// (related: [`MergeMode::Ask`](`crate::profile::MergeMode::Ask`)).
+pub type MergeHandler = Box<dyn MergeStrategy>;
+
+/// Passes the requests of a single deployment on to the [`MergeHandler`].
+struct BorrowedHandler<'a>(&'a mut dyn MergeStrategy);
+
+impl MergeStrategy for BorrowedHandler<'_> {
+	fn decide(&mut self, request: &MergeRequest<'_>) -> Result<MergeDecision, BoxError> {
+		self.0.decide(request)
+	}
+}
+
+/// Builder for a [`Punktf`] instance (related: [`Punktf::builder`]).
+#[derive(Default)]
+pub struct PunktfBuilder {
+	/// Root directory of the source.
+	source: Option<PathBuf>,
+
+	/// Name or alias of the profile.
+	profile: Option<String>,
+
+	/// Target path which takes precedence over the one of the profile.
+	target: Option<PathBuf>,
+
+	/// Options for deployments.
+	options: DeployOptions,
+
+	/// Strategy which decides about merges.
+	merge_handler: Option<MergeHandler>,
+}
+
+impl PunktfBuilder {
+	/// Sets the root directory of the source, which contains the `profiles`
+	/// and `dotfiles` directories.
+	pub fn source(mut self, root: impl Into<PathBuf>) -> Self {
+		self.source = Some(root.into());
+		self
+	}
+
+	/// Sets the name or alias of the profile to use.
+	pub fn profile(mut self, name: impl Into<String>) -> Self {
+		self.profile = Some(name.into());
+		self
+	}
+
+	/// Sets the target path. It takes precedence over the
+	/// [target](`crate::profile::Profile::target`) defined by the profile.
+	pub fn target(mut self, target: impl Into<PathBuf>) -> Self {
+		self.target = Some(target.into());
+		self
+	}
+
+	/// Prevents any write operations during a deployment (related:
+	/// [`DeployOptions::dry_run`]).
+	pub const fn dry_run(mut self, dry_run: bool) -> Self {
+		self.options.dry_run = dry_run;
+		self
+	}
+
+	/// Only deploys or diffs the dotfiles accepted by `filter`.
+	pub fn filter(mut self, filter: TagFilter) -> Self {
+		self.options.filter = filter;
+		self
+	}
+
+	/// Sets all options for deployments at once.
+	///
+	/// This overrides values given to [`PunktfBuilder::dry_run`] and
+	/// [`PunktfBuilder::filter`] before.
+	pub fn options(mut self, options: DeployOptions) -> Self {
+		self.options = options;
+		self
+	}
+
+	/// Sets the strategy which decides if an existing file is overwritten
+	/// when the merge mode of a dotfile is
+	/// [`MergeMode::Ask`](`crate::profile::MergeMode::Ask`). This can also be
+	/// a closure which receives the source and target path (related:
+	/// [`MergeStrategy`]).
+	///
+	/// Without a handler, such files are never overwritten.
+	pub fn merge_handler<M>(mut self, handler: M) -> Self
+	where
+		M: MergeStrategy + 'static,
+	{
+		self.merge_handler = Some(Box::new(handler));
+		self
+	}
+
+	/// Reads the source and resolves the profile.
+	///
+	/// # Errors
+	///
+	/// An error is returned if no source or profile was given, the source
+	/// directory is invalid or the profile (or any profile of its extend
+	/// chain) could not be resolved.
+	pub fn build(self) -> Result<Punktf> {
+		let root = self
+			.source
+			.ok_or(Error::MissingOption("source directory"))?;
+		let name = self.profile.ok_or(Error::MissingOption("profile"))?;
+
+		let source = PunktfSource::from_root(root)?;
+
+		let mut builder = LayeredProfile::build();
+
+		if self.target.is_some() {
+			builder.add(
+				String::from("target_override"),
+				Profile {
+					target: self.target,
+					..Default::default()
+				},
+			);
+		}
+
+		resolve_profile(&mut builder, &source, &name)?;
+
+		let mut profile = builder.finish();
+		profile.add_variable_overlays(&source)?;
+		profile.add_builtin_variables(&source, &name);
+
+		Ok(Punktf {
+			source,
+			profile_name: name,
+			profile,
+			options: self.options,
+			merge_handler: self.merge_handler.unwrap_or_else(|| {
+				Box::new(|_: &Path, _: &Path| -> Result<bool, BoxError> { Ok(false) })
+			}),
+		})
+	}
+}
+
+impl fmt::Debug for PunktfBuilder {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("PunktfBuilder")
+			.field("source", &self.source)
+			.field("profile", &self.profile)
+			.field("target", &self.target)
+			.field("options", &self.options)
+			.finish_non_exhaustive()
+	}
+}
+
+/// A resolved profile together with its source, ready to be deployed or
+/// diffed.
+pub struct Punktf {
+	/// The source of the profile.
+	source: PunktfSource,
+
+	/// The name of the profile as it was given to the builder.
+	profile_name: String,
+
+	/// The resolved profile.
+	profile: LayeredProfile,
+
+	/// Options for deployments.
+	options: DeployOptions,
+
+	/// Strategy which decides about merges.
+	merge_handler: MergeHandler,
+}
+
+impl Punktf {
+	/// Creates a new builder.
+	pub fn builder() -> PunktfBuilder {
+		PunktfBuilder::default()
+	}
+
+	/// Returns the source.
+	pub const fn source(&self) -> &PunktfSource {
+		&self.source
+	}
+
+	/// Returns the name of the profile.
+	pub fn profile_name(&self) -> &str {
+		&self.profile_name
+	}
+
+	/// Returns the resolved profile.
+	pub const fn profile(&self) -> &LayeredProfile {
+		&self.profile
+	}
+
+	/// Returns the options used for deployments.
+	pub const fn options(&self) -> &DeployOptions {
+		&self.options
+	}
+
+	/// Deploys the profile.
+	///
+	/// # Errors
+	///
+	/// Only hard errors which occur before any item is deployed are returned
+	/// (related: [`Deployer::deploy`]).
+	pub fn deploy(&mut self) -> Result<Deployment, DeployError> {
+		Deployer::new(
+			self.options.clone(),
+			BorrowedHandler(&mut *self.merge_handler),
+		)
+		.deploy(&self.source, &mut self.profile)
+	}
+
+	/// Checks for changes a deployment of the profile would make. Each change
+	/// is passed to `f`.
+	pub fn diff<F>(&mut self, f: F)
+	where
+		F: Fn(Event<'_>),
+	{
+		Diff::new(f)
+			.with_filter(self.options.filter.clone())
+			.diff(&self.source, &mut self.profile);
+	}
+
+	/// Checks for changes a deployment of the profile would make and returns
+	/// them.
+	pub fn diff_events(&mut self) -> Vec<OwnedEvent> {
+		Diff::new(|_| {})
+			.with_filter(self.options.filter.clone())
+			.collect(&self.source, &mut self.profile)
+	}
+}
+
+impl fmt::Debug for Punktf {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Punktf")
+			.field("source", &self.source)
+			.field("profile_name", &self.profile_name)
+			.field("profile", &self.profile)
+			.field("options", &self.options)
+			.finish_non_exhaustive()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn deploy_and_diff() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/profiles"))?;
+		std::fs::create_dir_all(dir.join("source/dotfiles"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+		std::fs::write(
+			dir.join("source/profiles/demo.json"),
+			r#"{ "dotfiles": [{ "path": "demo.txt" }] }"#,
+		)?;
+		std::fs::write(dir.join("source/dotfiles/demo.txt"), "Hello")?;
+
+		assert!(Punktf::builder().profile("demo").build().is_err());
+
+		let mut punktf = Punktf::builder()
+			.source(dir.join("source"))
+			.profile("demo")
+			.target(dir.join("target"))
+			.options(DeployOptions {
+				dry_run: true,
+				..Default::default()
+			})
+			.build()?;
+
+		assert_eq!(punktf.profile().target_path(), Some(&*dir.join("target")));
+
+		let deployment = punktf.deploy()?;
+		assert!(!deployment.status().is_failed());
+		assert!(!dir.join("target/demo.txt").exists());
+
+		let changes = std::cell::Cell::new(0);
+		punktf.diff(|_| changes.set(changes.get() + 1));
+		assert_eq!(changes.get(), 1);
+
+		assert_eq!(
+			punktf.diff_events(),
+			[OwnedEvent::NewFile {
+				relative_source_path: PathBuf::from("demo.txt"),
+				target_path: dir.join("target/demo.txt"),
+			}]
+		);
+
+		std::fs::write(dir.join("target/demo.txt"), "Hi")?;
+		let events = punktf.diff_events();
+		assert!(matches!(
+			&events[..],
+			[OwnedEvent::Diff { old_content, new_content, .. }]
+				if old_content == "Hi" && new_content == "Hello"
+		));
+
+		Ok(())
+	}
+}
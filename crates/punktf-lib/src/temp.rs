@@ -0,0 +1,200 @@
+//! Scoped temporary files and directories.
+//!
+//! A [`TempPath`] removes the file or directory it points to once it is
+//! dropped, unless it was [kept](`TempPath::keep`). This guarantees the cleanup
+//! of temporary files on all error paths.
+//!
+//! All live temporary paths are tracked in a global registry. This allows to
+//! remove them when the process is interrupted (e.g. by `Ctrl-C`) with
+//! [`cleanup`], as no destructors are run in that case.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// All temporary paths which are currently alive.
+static REGISTRY: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+/// Locks the registry.
+///
+/// A poisoned registry is still used, as it only contains paths.
+fn registry() -> MutexGuard<'static, BTreeSet<PathBuf>> {
+	REGISTRY
+		.lock()
+		.unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Removes the file or directory at `path`.
+///
+/// A missing path is not considered an error.
+fn remove(path: &Path) -> io::Result<()> {
+	let result = match path.symlink_metadata() {
+		Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+		Ok(_) => std::fs::remove_file(path),
+		Err(err) => Err(err),
+	};
+
+	match result {
+		Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+		result => result,
+	}
+}
+
+/// A temporary file or directory which is removed once this is dropped.
+#[derive(Debug)]
+pub struct TempPath {
+	/// Path of the temporary file or directory.
+	path: PathBuf,
+
+	/// If set, the path is removed on drop.
+	armed: bool,
+}
+
+impl TempPath {
+	/// Tracks `path` as temporary file or directory.
+	///
+	/// The path does not need to exist yet; it is removed on drop if it
+	/// exists by then.
+	pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+		let path = path.into();
+		let _ = registry().insert(path.clone());
+
+		Self { path, armed: true }
+	}
+
+	/// Creates a new temporary directory at `path` (including all missing
+	/// parents).
+	///
+	/// # Errors
+	///
+	/// An error is returned if the directory could not be created.
+	pub fn create_dir<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+		let temp = Self::new(path);
+		std::fs::create_dir_all(&temp.path)?;
+
+		Ok(temp)
+	}
+
+	/// Returns a temporary path next to `path`.
+	///
+	/// The file name is hidden and marked with a `.punktf-tmp` suffix (e.g.
+	/// `.bashrc.punktf-tmp` for `.bashrc`).
+	///
+	/// # Errors
+	///
+	/// An error is returned if `path` has no file name.
+	pub fn sibling(path: &Path) -> io::Result<Self> {
+		let file_name = path
+			.file_name()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+
+		let mut temp_name = std::ffi::OsString::from(".");
+		temp_name.push(file_name);
+		temp_name.push(".punktf-tmp");
+
+		Ok(Self::new(path.with_file_name(temp_name)))
+	}
+
+	/// Returns the path of the temporary file or directory.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Stops tracking the path, so that it is not removed.
+	///
+	/// This is used if the path was moved (e.g. by renaming it to the final
+	/// location) or should persist.
+	pub fn keep(mut self) -> PathBuf {
+		self.armed = false;
+		let _ = registry().remove(&self.path);
+
+		std::mem::take(&mut self.path)
+	}
+}
+
+impl AsRef<Path> for TempPath {
+	fn as_ref(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl Drop for TempPath {
+	fn drop(&mut self) {
+		if !self.armed {
+			return;
+		}
+
+		let _ = registry().remove(&self.path);
+
+		if let Err(err) = remove(&self.path) {
+			log::warn!(
+				"[{}] Failed to remove temporary path: {err}",
+				self.path.display()
+			);
+		}
+	}
+}
+
+/// Returns all temporary paths which are currently alive.
+pub fn tracked() -> Vec<PathBuf> {
+	registry().iter().cloned().collect()
+}
+
+/// Removes all temporary paths which are currently alive (best-effort).
+///
+/// This is intended to be called when the process is interrupted, as no
+/// destructors are run in that case.
+///
+/// Returns the number of removed paths.
+pub fn cleanup() -> usize {
+	let paths = std::mem::take(&mut *registry());
+
+	paths
+		.into_iter()
+		.filter(|path| match remove(path) {
+			Ok(()) => true,
+			Err(err) => {
+				log::warn!(
+					"[{}] Failed to remove temporary path: {err}",
+					path.display()
+				);
+				false
+			}
+		})
+		.count()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn temp_path_cleanup() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+
+		let file = TempPath::sibling(&dir.join("file"))?;
+		std::fs::write(file.path(), "content")?;
+		let file_path = file.path().to_path_buf();
+		assert!(tracked().contains(&file_path));
+		drop(file);
+		assert!(!file_path.exists());
+		assert!(!tracked().contains(&file_path));
+
+		let sub = TempPath::create_dir(dir.join("sub"))?;
+		std::fs::write(sub.path().join("child"), "")?;
+		let sub_path = sub.path().to_path_buf();
+		drop(sub);
+		assert!(!sub_path.exists());
+
+		let kept = TempPath::new(dir.join("kept"));
+		std::fs::write(kept.path(), "")?;
+		let kept_path = kept.keep();
+		assert!(kept_path.exists());
+		assert!(!tracked().contains(&kept_path));
+
+		Ok(())
+	}
+}
@@ -1,8 +1,12 @@
 //! Basic block and tokens a [template](`super::Template`) is created from.
 
+use std::cmp::Ordering;
 use std::fmt;
 
+use serde::{Deserialize, Serialize};
+
 use super::span::{ByteSpan, Spanned};
+use crate::profile::variables::Value;
 
 /// A parsed instruction from a template.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -25,6 +29,12 @@ pub enum BlockHint {
 	Else,
 	/// End an `If` block
 	IfEnd,
+	/// Starts a `Let` block
+	Let,
+	/// Starts a `Literal` block
+	Literal,
+	/// A `Syntax` pragma which selects the syntax version of the template
+	Syntax,
 }
 
 impl BlockHint {
@@ -45,10 +55,16 @@ pub enum BlockKind {
 	Escaped(ByteSpan),
 	/// A `Variable` block, that contains a variable name that is replaced with its value.
 	Var(Var),
-	/// A `Print` block, that contains text that is printed to the log.
-	Print(ByteSpan),
+	/// A `Print` block, that contains text that is printed to the log. Only
+	/// text, escaped and variable blocks are allowed.
+	Print(Vec<Block>),
 	/// An `If` block, that contains a condition that is evaluated and compiles the block conditionally.
 	If(If),
+	/// A `Let` block, that binds a value to a name for the remainder of the enclosing scope.
+	Let(Let),
+	/// A `Literal` block, that contains verbatim text that is copied to the
+	/// output after the margins of its lines are stripped.
+	Literal(ByteSpan),
 }
 
 impl BlockKind {
@@ -61,6 +77,8 @@ impl BlockKind {
 			BlockKind::Var(_) => BlockHint::Var,
 			BlockKind::Print(_) => BlockHint::Print,
 			BlockKind::If(_) => BlockHint::IfEnd,
+			BlockKind::Let(_) => BlockHint::Let,
+			BlockKind::Literal(_) => BlockHint::Literal,
 		}
 	}
 }
@@ -92,7 +110,7 @@ impl Block {
 }
 
 /// The different types of sources for variables values
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum VarEnv {
 	/// A variable that is defined by the system's environment.
 	Environment,
@@ -109,7 +127,11 @@ impl fmt::Display for VarEnv {
 }
 
 /// Defines a set of variables sources that can be used to resolve variables.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+///
+/// When (de)serialized it is represented as a non-empty list of unique
+/// [`VarEnv`]s (e.g. `[Environment, Profile, Dotfile]`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "Vec<VarEnv>", into = "Vec<VarEnv>")]
 pub struct VarEnvSet(pub [Option<VarEnv>; 3]);
 
 impl VarEnvSet {
@@ -144,6 +166,37 @@ impl VarEnvSet {
 	pub const fn capacity(&self) -> usize {
 		self.0.len()
 	}
+
+	/// Returns `true` if no environment is defined.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+impl TryFrom<Vec<VarEnv>> for VarEnvSet {
+	type Error = String;
+
+	fn try_from(value: Vec<VarEnv>) -> Result<Self, Self::Error> {
+		if value.is_empty() {
+			return Err("At least one variable environment must be given".into());
+		}
+
+		let mut set = Self::empty();
+
+		for env in value {
+			if !set.add(env) {
+				return Err(format!("Duplicate variable environment `{env}`"));
+			}
+		}
+
+		Ok(set)
+	}
+}
+
+impl From<VarEnvSet> for Vec<VarEnv> {
+	fn from(value: VarEnvSet) -> Self {
+		value.envs().copied().collect()
+	}
 }
 
 impl Default for VarEnvSet {
@@ -159,12 +212,143 @@ impl fmt::Display for VarEnvSet {
 }
 
 /// A variable that is defined in the template.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Var {
-	/// The `VarEnvSet` for the variable
+	/// The `VarEnvSet` for the variable.
+	///
+	/// If it is empty, no environments were given explicitly and the default
+	/// resolution order of the resolver is used.
 	pub envs: VarEnvSet,
 	/// The `ByteSpan` of the variable
 	pub name: ByteSpan,
+	/// Filters which are applied in order to the value of the variable.
+	///
+	/// `{{VAR | lower | replace('-', '_')}}`
+	pub filters: Vec<Spanned<Filter>>,
+}
+
+/// A filter which transforms the value of a [variable](`Var`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Filter {
+	/// Converts the value to uppercase (`upper`).
+	Upper,
+
+	/// Converts the value to lowercase (`lower`).
+	Lower,
+
+	/// Removes leading and trailing whitespace (`trim`).
+	Trim,
+
+	/// Replaces all occurrences of a literal (`replace('from', 'to')`).
+	Replace {
+		/// Literal which is replaced.
+		from: String,
+
+		/// Replacement for each occurrence.
+		to: String,
+	},
+
+	/// Uses the given literal if the variable is not defined
+	/// (`default('value')`).
+	Default(String),
+
+	/// Returns the last component of a path (`basename`).
+	Basename,
+
+	/// Returns the path without its last component (`dirname`).
+	Dirname,
+
+	/// Converts all path separators to the windows style `\\` (`winpath`).
+	WinPath,
+
+	/// Converts all path separators to the posix style `/` (`posixpath`).
+	PosixPath,
+
+	/// Converts all path separators to the style of the target operating
+	/// system (`nativepath`).
+	NativePath,
+}
+
+impl Filter {
+	/// Creates a filter from its `name` and string literal arguments.
+	///
+	/// # Errors
+	///
+	/// An error is returned if no filter with the name exists or the number
+	/// of arguments does not match.
+	pub fn new(name: &str, args: Vec<String>) -> Result<Self, String> {
+		let expected = match name {
+			"upper" | "lower" | "trim" | "basename" | "dirname" | "winpath" | "posixpath"
+			| "nativepath" => 0,
+			"default" => 1,
+			"replace" => 2,
+			_ => {
+				return Err(format!(
+					"Unknown filter `{name}` (available: upper, lower, trim, replace, default, \
+					 basename, dirname, winpath, posixpath, nativepath)"
+				))
+			}
+		};
+
+		if args.len() != expected {
+			return Err(format!(
+				"Filter `{name}` expects {expected} argument(s) but got {}",
+				args.len()
+			));
+		}
+
+		let mut args = args.into_iter();
+
+		Ok(match name {
+			"upper" => Self::Upper,
+			"lower" => Self::Lower,
+			"trim" => Self::Trim,
+			"basename" => Self::Basename,
+			"dirname" => Self::Dirname,
+			"winpath" => Self::WinPath,
+			"posixpath" => Self::PosixPath,
+			"nativepath" => Self::NativePath,
+			"default" => Self::Default(args.next().unwrap_or_default()),
+			_ => Self::Replace {
+				from: args.next().unwrap_or_default(),
+				to: args.next().unwrap_or_default(),
+			},
+		})
+	}
+
+	/// Applies the filter to `value`, which is `None` if the variable is not
+	/// defined.
+	///
+	/// Only [`Filter::Default`] is able to provide a value for an undefined
+	/// variable.
+	pub fn apply(&self, value: Option<String>) -> Option<String> {
+		let value = match (self, value) {
+			(Self::Default(default), None) => return Some(default.clone()),
+			(_, None) => return None,
+			(_, Some(value)) => value,
+		};
+
+		let value = match self {
+			Self::Upper => value.to_uppercase(),
+			Self::Lower => value.to_lowercase(),
+			Self::Trim => value.trim().to_string(),
+			Self::Replace { from, to } if !from.is_empty() => value.replace(from, to),
+			Self::Basename => std::path::Path::new(&value)
+				.file_name()
+				.map(|name| name.to_string_lossy().into_owned())
+				.unwrap_or_default(),
+			Self::Dirname => std::path::Path::new(&value)
+				.parent()
+				.map(|parent| parent.to_string_lossy().into_owned())
+				.unwrap_or_default(),
+			Self::WinPath => value.replace('/', "\\"),
+			Self::PosixPath => value.replace('\\', "/"),
+			Self::NativePath => value.replace(['/', '\\'], std::path::MAIN_SEPARATOR_STR),
+			Self::Replace { .. } | Self::Default(_) => value,
+		};
+
+		Some(value)
+	}
 }
 
 /// Defines an if block.
@@ -191,6 +375,19 @@ pub struct If {
 	pub end: ByteSpan,
 }
 
+/// Defines a let block.
+///
+/// `{{@let NAME = "VALUE"}}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Let {
+	/// The name the value is bound to.
+	pub name: ByteSpan,
+
+	/// The blocks which make up the value. Only text, escaped and variable
+	/// blocks are allowed.
+	pub value: Vec<Block>,
+}
+
 /// The different types of if expression operations.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum IfOp {
@@ -199,20 +396,59 @@ pub enum IfOp {
 
 	/// Operand to check for inequality.
 	NotEq,
+
+	/// Operand to check if the left side is less than the right side.
+	Lt,
+
+	/// Operand to check if the left side is less than or equal to the right
+	/// side.
+	Le,
+
+	/// Operand to check if the left side is greater than the right side.
+	Gt,
+
+	/// Operand to check if the left side is greater than or equal to the
+	/// right side.
+	Ge,
 }
 
 impl IfOp {
 	/// Evaluates an if expression.
-	pub fn eval(&self, lhs: &str, rhs: &str) -> bool {
+	///
+	/// The comparison is type-aware (related: [`Value::matches`]). Ordering
+	/// operands compare numbers and versions (related: [`Value::compare`]).
+	///
+	/// # Errors
+	///
+	/// An error is returned if the sides of an ordering operand can not be
+	/// compared.
+	pub fn eval(&self, lhs: &Value, rhs: &str) -> Result<bool, String> {
 		match self {
-			Self::Eq => lhs == rhs,
-			Self::NotEq => lhs != rhs,
+			Self::Eq => Ok(lhs.matches(rhs)),
+			Self::NotEq => Ok(!lhs.matches(rhs)),
+			Self::Lt => lhs.compare(rhs).map(Ordering::is_lt),
+			Self::Le => lhs.compare(rhs).map(Ordering::is_le),
+			Self::Gt => lhs.compare(rhs).map(Ordering::is_gt),
+			Self::Ge => lhs.compare(rhs).map(Ordering::is_ge),
 		}
 	}
 }
 
+impl fmt::Display for IfOp {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Eq => "==",
+			Self::NotEq => "!=",
+			Self::Lt => "<",
+			Self::Le => "<=",
+			Self::Gt => ">",
+			Self::Ge => ">=",
+		})
+	}
+}
+
 /// The different if expression types.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum IfExpr {
 	/// An if expression that compares two values.
 	Compare {
@@ -222,17 +458,21 @@ pub enum IfExpr {
 		/// Compare operand.
 		op: IfOp,
 
-		/// Right hand side of the compare operation.
-		other: ByteSpan,
+		/// Right hand side of the compare operation. Only text, escaped and
+		/// variable blocks are allowed, which are resolved before the
+		/// comparison.
+		other: Vec<Block>,
 	},
 
-	/// An if expression that checks if a value is defined.
+	/// An if expression that checks if a value is defined and truthy
+	/// (related: [`Value::is_truthy`]).
 	Exists {
 		/// Variable to check existence for.
 		var: Var,
 	},
 
-	/// An if expression that checks if a value is not defined.
+	/// An if expression that checks if a value is not defined or falsy
+	/// (related: [`Value::is_truthy`]).
 	NotExists {
 		/// Variable to check not existence for.
 		var: Var,
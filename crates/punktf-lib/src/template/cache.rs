@@ -0,0 +1,350 @@
+//! Caching of resolved [templates](`super::Template`).
+//!
+//! An entry is keyed by a hash of the template content (together with the
+//! settings used to resolve it) and stores the resolved output and the names
+//! of all variables the template used. It is only reused if none of the
+//! environments (system, profile and dotfile) changed the value of any of
+//! those variables, which makes parsing and resolving unchanged templates
+//! unnecessary.
+//!
+//! The cache is kept in memory for a single run and can additionally be
+//! stored on disk (related: [`TemplateCache::with_dir`]), so it is shared
+//! between deployments.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::env::PunktfEnv;
+use crate::error::Result;
+
+use super::block::VarEnvSet;
+use super::limits::Limits;
+use super::mode::TemplateMode;
+use super::source::Source;
+use super::Template;
+use crate::profile::variables::Vars;
+
+/// Version of the on disk format of an entry. Entries of other versions are
+/// ignored.
+const FORMAT_VERSION: &str = "punktf-template-cache-v1";
+
+/// Hashes data with the 64 bit FNV-1a function.
+///
+/// Unlike the hashers of the standard library, the result is stable across
+/// runs and compiler versions, which is required for the entries on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fnv(u64);
+
+impl Fnv {
+	/// Creates a new instance.
+	const fn new() -> Self {
+		Self(0xcbf2_9ce4_8422_2325)
+	}
+
+	/// Adds `bytes` to the hash. Each call is terminated, so that different
+	/// splits of the same data result in different hashes.
+	fn write(&mut self, bytes: &[u8]) -> &mut Self {
+		for byte in bytes.iter().chain(&[0xff]) {
+			self.0 ^= u64::from(*byte);
+			self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+		}
+
+		self
+	}
+
+	/// Adds an optional string to the hash.
+	fn write_opt(&mut self, value: Option<&str>) -> &mut Self {
+		match value {
+			Some(value) => self.write(b"+").write(value.as_bytes()),
+			None => self.write(b"-"),
+		}
+	}
+
+	/// Returns the hash.
+	const fn finish(&self) -> u64 {
+		self.0
+	}
+}
+
+/// A cached template output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+	/// Hash of the values of all used variables (related:
+	/// [`variables_hash`]).
+	variables_hash: u64,
+
+	/// Names of all variables used by the template.
+	variables: Vec<String>,
+
+	/// The resolved output.
+	output: String,
+}
+
+impl Entry {
+	/// Parses an entry stored on disk.
+	fn parse(content: &str) -> Option<Self> {
+		let mut parts = content.splitn(4, '\n');
+
+		if parts.next()? != FORMAT_VERSION {
+			return None;
+		}
+
+		let variables_hash = u64::from_str_radix(parts.next()?, 16).ok()?;
+		let variables = parts
+			.next()?
+			.split(',')
+			.filter(|name| !name.is_empty())
+			.map(String::from)
+			.collect();
+		let output = parts.next()?.to_string();
+
+		Some(Self {
+			variables_hash,
+			variables,
+			output,
+		})
+	}
+
+	/// Serializes the entry to be stored on disk.
+	///
+	/// Variable names consist only of `(a..z|A..Z|0-9|_)`, so they can be
+	/// separated by commas.
+	fn serialize(&self) -> String {
+		format!(
+			"{FORMAT_VERSION}\n{:016x}\n{}\n{}",
+			self.variables_hash,
+			self.variables.join(","),
+			self.output
+		)
+	}
+}
+
+/// Hashes the values the `variables` have in all environments.
+///
+/// This includes environments the template did not take the values from, as
+/// defining a variable in an environment which is searched first changes the
+/// output as well.
+fn variables_hash<PV: Vars, DV: Vars>(
+	variables: &[String],
+	profile_vars: Option<&PV>,
+	dotfile_vars: Option<&DV>,
+	env: &PunktfEnv,
+) -> u64 {
+	let mut hash = Fnv::new();
+
+	for name in variables {
+		let env = env.var(name);
+		let profile = profile_vars
+			.and_then(|vars| vars.value(name))
+			.map(|value| format!("{value:?}"));
+		let dotfile = dotfile_vars
+			.and_then(|vars| vars.value(name))
+			.map(|value| format!("{value:?}"));
+
+		hash.write(name.as_bytes())
+			.write_opt(env.as_deref())
+			.write_opt(profile.as_deref())
+			.write_opt(dotfile.as_deref());
+	}
+
+	hash.finish()
+}
+
+/// Cache of resolved templates (related: [module](`self`)).
+#[derive(Debug, Default, Clone)]
+pub struct TemplateCache {
+	/// Directory in which the entries are additionally stored.
+	dir: Option<PathBuf>,
+
+	/// Entries which were used or created during this run.
+	entries: RefCell<HashMap<u64, Entry>>,
+
+	/// Number of templates which were taken from the cache.
+	hits: Cell<usize>,
+
+	/// Number of templates which had to be resolved.
+	misses: Cell<usize>,
+}
+
+impl TemplateCache {
+	/// Creates a new, empty, in memory cache.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Additionally stores the entries in `dir`, one file per template.
+	pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+		self.dir = Some(dir.into());
+		self
+	}
+
+	/// Returns the directory in which the entries are stored.
+	pub fn dir(&self) -> Option<&Path> {
+		self.dir.as_deref()
+	}
+
+	/// Returns the number of templates which were taken from the cache.
+	pub const fn hits(&self) -> usize {
+		self.hits.get()
+	}
+
+	/// Returns the number of templates which had to be resolved.
+	pub const fn misses(&self) -> usize {
+		self.misses.get()
+	}
+
+	/// Resolves the template contained in `source` like
+	/// [`Template::resolve_with_order`], unless an up to date output is
+	/// cached.
+	///
+	/// Templates with print blocks are never cached, as the blocks would not
+	/// be printed for cached outputs.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the template needs to be resolved and parsing
+	/// or resolving fails. Failures to read or write entries on disk are only
+	/// logged.
+	#[allow(clippy::too_many_arguments)]
+	pub fn resolve<PV: Vars, DV: Vars>(
+		&self,
+		source: Source<'_>,
+		limits: Limits,
+		mode: TemplateMode,
+		profile_vars: Option<&PV>,
+		dotfile_vars: Option<&DV>,
+		order: VarEnvSet,
+		env: &PunktfEnv,
+	) -> Result<String> {
+		let content = source.content();
+
+		if content.contains("{{@print") {
+			let template = Template::parse_with_mode(source, limits, mode)?;
+			return template.resolve_with_order(profile_vars, dotfile_vars, order, env);
+		}
+
+		let key = Fnv::new()
+			.write(content.as_bytes())
+			.write(format!("{limits:?}").as_bytes())
+			.write(mode.to_string().as_bytes())
+			.write(order.to_string().as_bytes())
+			.finish();
+
+		if let Some(entry) = self.lookup(key) {
+			if entry.variables_hash
+				== variables_hash(&entry.variables, profile_vars, dotfile_vars, env)
+			{
+				self.hits.set(self.hits.get() + 1);
+				return Ok(entry.output);
+			}
+		}
+
+		self.misses.set(self.misses.get() + 1);
+
+		let template = Template::parse_with_mode(source, limits, mode)?;
+		let (output, usages) =
+			template.resolve_with_usage(profile_vars, dotfile_vars, order, env)?;
+
+		let mut variables: Vec<_> = usages.into_iter().map(|usage| usage.name).collect();
+		variables.sort();
+		variables.dedup();
+
+		let entry = Entry {
+			variables_hash: variables_hash(&variables, profile_vars, dotfile_vars, env),
+			variables,
+			output: output.clone(),
+		};
+
+		if let Err(err) = self.store(key, &entry) {
+			log::warn!("Failed to store template in cache: {err}");
+		}
+
+		self.entries.borrow_mut().insert(key, entry);
+
+		Ok(output)
+	}
+
+	/// Returns the path of the file which stores the entry for `key`.
+	fn entry_path(&self, key: u64) -> Option<PathBuf> {
+		self.dir.as_ref().map(|dir| dir.join(format!("{key:016x}")))
+	}
+
+	/// Looks up the entry for `key`, first in memory and then on disk.
+	fn lookup(&self, key: u64) -> Option<Entry> {
+		if let Some(entry) = self.entries.borrow().get(&key) {
+			return Some(entry.clone());
+		}
+
+		let content = std::fs::read_to_string(self.entry_path(key)?).ok()?;
+		let entry = Entry::parse(&content)?;
+		self.entries.borrow_mut().insert(key, entry.clone());
+
+		Some(entry)
+	}
+
+	/// Stores the `entry` for `key` on disk, if a directory is set.
+	fn store(&self, key: u64, entry: &Entry) -> io::Result<()> {
+		let Some(path) = self.entry_path(key) else {
+			return Ok(());
+		};
+
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent)?;
+		}
+
+		std::fs::write(path, entry.serialize())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use super::*;
+	use crate::profile::variables::Variables;
+
+	#[test]
+	fn cache_outputs() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+
+		let vars = |value: &str| Variables {
+			inner: HashMap::from_iter([(String::from("NAME"), value.into())]),
+		};
+		let resolve = |cache: &TemplateCache, content: &str, dotfile_vars: &Variables| {
+			cache.resolve(
+				Source::anonymous(content),
+				Limits::default(),
+				TemplateMode::default(),
+				None::<&Variables>,
+				Some(dotfile_vars),
+				VarEnvSet::default(),
+				&PunktfEnv::new(),
+			)
+		};
+
+		let cache = TemplateCache::new().with_dir(&*dir);
+		assert_eq!(resolve(&cache, "Hello {{NAME}}", &vars("a"))?, "Hello a");
+		assert_eq!(resolve(&cache, "Hello {{NAME}}", &vars("a"))?, "Hello a");
+		assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+		// Changed variables are resolved again
+		assert_eq!(resolve(&cache, "Hello {{NAME}}", &vars("b"))?, "Hello b");
+		assert_eq!((cache.hits(), cache.misses()), (1, 2));
+
+		// Entries are read back from disk
+		let cache = TemplateCache::new().with_dir(&*dir);
+		assert_eq!(resolve(&cache, "Hello {{NAME}}", &vars("b"))?, "Hello b");
+		assert_eq!((cache.hits(), cache.misses()), (1, 0));
+
+		// Templates which print are always resolved
+		assert_eq!(resolve(&cache, "{{@print hi}}", &vars("b"))?, "");
+		assert_eq!(resolve(&cache, "{{@print hi}}", &vars("b"))?, "");
+		assert_eq!((cache.hits(), cache.misses()), (1, 0));
+
+		Ok(())
+	}
+}
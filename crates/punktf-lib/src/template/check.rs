@@ -0,0 +1,319 @@
+//! Checks all templates of a profile at once.
+//!
+//! Each template is parsed once and then resolved from the parsed blocks. The
+//! templates are checked in parallel, which is considerably faster than
+//! checking them one after another on large profiles. All problems are
+//! collected into a single [`CheckReport`] keyed by file, so that they can be
+//! displayed (e.g. by `render --check`) or be consumed by other tools (e.g.
+//! editor integrations).
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::error::Error;
+use crate::profile::source::PunktfSource;
+use crate::profile::variables::Variables;
+use crate::profile::LayeredProfile;
+use crate::template::engine::{EngineKind, RenderContext};
+use crate::template::mode::TemplateMode;
+use crate::template::source::Source;
+use crate::template::Template;
+use crate::visit::{Directory, Errored, File, Rejected, Result, Symlink, Visitor, Walker};
+
+/// Stage of the check at which a problem was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStage {
+	/// The template could not be read.
+	Read,
+
+	/// The template could not be parsed.
+	Parse,
+
+	/// The template could not be resolved (e.g. because of a missing
+	/// variable).
+	Resolve,
+}
+
+impl fmt::Display for CheckStage {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Read => f.write_str("Failed to read"),
+			Self::Parse => f.write_str("Invalid syntax"),
+			Self::Resolve => f.write_str("Failed to resolve"),
+		}
+	}
+}
+
+/// A problem found while checking a template.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TemplateProblem {
+	/// Stage of the check at which the problem was found.
+	pub stage: CheckStage,
+
+	/// Messages describing the problem.
+	pub messages: Vec<String>,
+}
+
+impl TemplateProblem {
+	/// Creates a new problem from the error which occurred at `stage`.
+	fn from_error(stage: CheckStage, err: Error) -> Self {
+		let messages = match err {
+			Error::TemplateSyntax { messages, .. } | Error::TemplateResolve { messages, .. } => {
+				messages
+			}
+			err => vec![err.to_string()],
+		};
+
+		Self { stage, messages }
+	}
+}
+
+impl fmt::Display for TemplateProblem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {}", self.stage, self.messages.join("; "))
+	}
+}
+
+/// Result of checking all templates of a profile (related: [`check_all`]).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckReport {
+	/// Number of templates which were checked.
+	pub checked: usize,
+
+	/// The problems found, keyed by the path of the template relative to the
+	/// `dotfiles` directory.
+	pub problems: BTreeMap<PathBuf, TemplateProblem>,
+}
+
+impl CheckReport {
+	/// Returns `true` if no problems were found.
+	pub fn is_ok(&self) -> bool {
+		self.problems.is_empty()
+	}
+}
+
+/// A template which was collected from the profile to be checked.
+#[derive(Debug)]
+struct Candidate {
+	/// Path of the template relative to the `dotfiles` directory.
+	relative_source_path: PathBuf,
+
+	/// Absolute path of the template.
+	source_path: PathBuf,
+
+	/// Contents of the template.
+	content: io::Result<String>,
+
+	/// Variables of the dotfile the template belongs to.
+	variables: Option<Variables>,
+
+	/// Mode in which the template is parsed.
+	mode: TemplateMode,
+
+	/// Engine which resolves the template.
+	engine: EngineKind,
+}
+
+impl Candidate {
+	/// Parses and resolves the template with the variables of `profile`.
+	fn check(&self, profile: &LayeredProfile) -> std::result::Result<(), TemplateProblem> {
+		let content = self.content.as_ref().map_err(|err| TemplateProblem {
+			stage: CheckStage::Read,
+			messages: vec![err.to_string()],
+		})?;
+
+		if !self.engine.is_builtin() {
+			let context = RenderContext {
+				path: &self.source_path,
+				limits: profile.template_limits(),
+				mode: self.mode,
+				profile_vars: profile.variables(),
+				dotfile_vars: self.variables.as_ref(),
+				order: profile.variable_resolution_order(),
+				env: profile.env(),
+			};
+
+			return self
+				.engine
+				.engine()
+				.and_then(|engine| engine.render(content, &context))
+				.map(|_| ())
+				.map_err(|err| TemplateProblem::from_error(CheckStage::Resolve, err));
+		}
+
+		let template = Template::parse_with_mode(
+			Source::file(&self.source_path, content),
+			profile.template_limits(),
+			self.mode,
+		)
+		.map_err(|err| TemplateProblem::from_error(CheckStage::Parse, err))?;
+
+		template
+			.resolve_with_order(
+				Some(profile.variables()),
+				self.variables.as_ref(),
+				profile.variable_resolution_order(),
+				profile.env(),
+			)
+			.map(|_| ())
+			.map_err(|err| TemplateProblem::from_error(CheckStage::Resolve, err))
+	}
+}
+
+/// A [`Visitor`] which collects all templates of a profile.
+#[derive(Debug, Default)]
+struct Collector {
+	/// The collected templates.
+	candidates: Vec<Candidate>,
+}
+
+impl Visitor for Collector {
+	fn accept_file<'a>(&mut self, _: &PunktfSource, _: &LayeredProfile, file: &File<'a>) -> Result {
+		if file.dotfile().is_template() {
+			self.candidates.push(Candidate {
+				relative_source_path: file.relative_source_path.clone(),
+				source_path: file.source_path.clone(),
+				content: file.read_source_to_string(),
+				variables: file.dotfile().variables.clone(),
+				mode: file.dotfile().template_mode(),
+				engine: file.dotfile().engine(),
+			});
+		}
+
+		Ok(())
+	}
+
+	fn accept_directory<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Directory<'a>,
+	) -> Result {
+		Ok(())
+	}
+
+	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, _: &Symlink) -> Result {
+		Ok(())
+	}
+
+	fn accept_rejected<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Rejected<'a>,
+	) -> Result {
+		Ok(())
+	}
+
+	fn accept_errored<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Errored<'a>,
+	) -> Result {
+		Ok(())
+	}
+}
+
+/// Parses and resolves all templates of `profile` in parallel and collects
+/// the problems found into a single report.
+///
+/// No hooks are executed and nothing is written to the target.
+pub fn check_all(source: &PunktfSource, profile: &mut LayeredProfile) -> CheckReport {
+	let mut collector = Collector::default();
+
+	if let Err(err) = Walker::new(profile).walk(source, &mut collector) {
+		log::error!("Failed to collect templates: {err}");
+	}
+
+	let profile = &*profile;
+	let candidates = collector.candidates;
+
+	let problems = candidates
+		.par_iter()
+		.filter_map(|candidate| {
+			candidate
+				.check(profile)
+				.err()
+				.map(|problem| (candidate.relative_source_path.clone(), problem))
+		})
+		.collect();
+
+	CheckReport {
+		checked: candidates.len(),
+		problems,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::profile::dotfile::Dotfile;
+	use crate::profile::Profile;
+
+	#[test]
+	fn check_all_templates() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("dotfiles"))?;
+		std::fs::create_dir_all(dir.join("profiles"))?;
+		std::fs::write(dir.join("dotfiles/valid"), "{{NAME}}")?;
+		std::fs::write(dir.join("dotfiles/syntax"), "{{@if {{NAME}}}}")?;
+		std::fs::write(dir.join("dotfiles/missing"), "{{UNKNOWN}}")?;
+		std::fs::write(dir.join("dotfiles/plain"), "{{UNKNOWN}}")?;
+
+		let source = PunktfSource::from_root(dir.to_path_buf())?;
+
+		let template = |path: &str| Dotfile {
+			path: PathBuf::from(path),
+			template: Some(true),
+			..Default::default()
+		};
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("demo"),
+			Profile {
+				target: Some(dir.join("target")),
+				variables: Some(Variables::from_items([("NAME", "demo")])),
+				dotfiles: vec![
+					template("valid"),
+					template("syntax"),
+					template("missing"),
+					Dotfile {
+						path: PathBuf::from("plain"),
+						template: Some(false),
+						..Default::default()
+					},
+				],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let report = check_all(&source, &mut profile);
+
+		assert_eq!(report.checked, 3);
+		assert!(!report.is_ok());
+		assert_eq!(
+			report
+				.problems
+				.iter()
+				.map(|(path, problem)| (path.to_str(), problem.stage))
+				.collect::<Vec<_>>(),
+			[
+				(Some("missing"), CheckStage::Resolve),
+				(Some("syntax"), CheckStage::Parse),
+			]
+		);
+
+		Ok(())
+	}
+}
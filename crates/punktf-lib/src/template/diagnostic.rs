@@ -5,7 +5,7 @@ use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, HashSet};
 use std::fmt::Write as _; // Needed for `write!` calls
 
-use color_eyre::owo_colors::OwoColorize;
+use owo_colors::OwoColorize;
 
 use super::source::Location;
 use super::span::ByteSpan;
@@ -112,6 +112,15 @@ impl Diagnostic {
 	pub const fn level(&self) -> &DiagnosticLevel {
 		&self.level
 	}
+
+	/// Returns the message together with the description, without any
+	/// references to the source.
+	pub fn summary(&self) -> String {
+		match &self.description {
+			Some(description) => format!("{}: {description}", self.msg),
+			None => self.msg.to_string(),
+		}
+	}
 }
 
 /// A builder for a [`Diagnostic`].
@@ -174,6 +183,13 @@ impl DiagnosticBuilder {
 		self
 	}
 
+	/// Returns the primary spans added to the builder.
+	pub fn primary_spans(&self) -> &[ByteSpan] {
+		self.span
+			.as_ref()
+			.map_or(&[], |span| span.primary.as_slice())
+	}
+
 	/// Adds a label span to the builder.
 	pub fn label_span<L: Into<Cow<'static, str>>>(mut self, span: ByteSpan, label: L) -> Self {
 		self.span
@@ -302,9 +318,9 @@ impl<'a> LineMap<'a> {
 	/// The search is done in this order:
 	///
 	/// 1) First searches the primary spans for the smallest location and
-	///       returns any if found.
+	///    returns any if found.
 	/// 2) After that it searches the label spans for the smallest location and
-	///       returns any if found.
+	///    returns any if found.
 	pub fn min_location(&self) -> Option<Location> {
 		self.primary_spans
 			.iter()
@@ -0,0 +1,380 @@
+//! Template engines which can be used to resolve the templates of dotfiles.
+//!
+//! By default all templates are resolved by the [builtin engine](`super`).
+//! Dotfiles can select another engine with
+//! [`Dotfile::engine`](`crate::profile::dotfile::Dotfile::engine`). These are
+//! only available if the corresponding cargo feature (`engine-handlebars` or
+//! `engine-tera`) is enabled.
+//!
+//! Other engines receive all variables of the profile and the dotfile as
+//! context, where variables of the dotfile take precedence. The variables of
+//! the system environment are available as `env` (e.g. `{{ env.HOME }}`).
+
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::env::PunktfEnv;
+use crate::error::{Error, Result};
+use crate::profile::variables::Variables;
+use crate::profile::LayeredVariables;
+
+use super::block::VarEnvSet;
+use super::limits::Limits;
+use super::mode::TemplateMode;
+use super::source::Source;
+use super::Template;
+
+/// Selects the engine which resolves a template.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EngineKind {
+	/// The handlebars-like engine of `punktf` (related: [`BuiltinEngine`]).
+	#[default]
+	Builtin,
+
+	/// The [handlebars](https://handlebarsjs.com/) engine. Requires the cargo
+	/// feature `engine-handlebars`.
+	Handlebars,
+
+	/// The [tera](https://keats.github.io/tera/) engine. Requires the cargo
+	/// feature `engine-tera`.
+	Tera,
+}
+
+impl EngineKind {
+	/// Checks if the kind is [`EngineKind::Builtin`].
+	pub const fn is_builtin(&self) -> bool {
+		matches!(self, Self::Builtin)
+	}
+
+	/// Returns the engine of this kind.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the engine was not enabled when `punktf` was
+	/// built.
+	pub fn engine(self) -> Result<Box<dyn TemplateEngine>> {
+		match self {
+			Self::Builtin => Ok(Box::new(BuiltinEngine)),
+			#[cfg(feature = "engine-handlebars")]
+			Self::Handlebars => Ok(Box::new(HandlebarsEngine)),
+			#[cfg(feature = "engine-tera")]
+			Self::Tera => Ok(Box::new(TeraEngine)),
+			#[allow(unreachable_patterns)]
+			kind => Err(Error::EngineUnavailable(kind)),
+		}
+	}
+}
+
+impl fmt::Display for EngineKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Builtin => f.write_str("builtin"),
+			Self::Handlebars => f.write_str("handlebars"),
+			Self::Tera => f.write_str("tera"),
+		}
+	}
+}
+
+/// Everything an engine needs to resolve a template.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderContext<'a> {
+	/// Path of the template, used for error messages.
+	pub path: &'a Path,
+
+	/// Limits for the resolution of the template.
+	pub limits: Limits,
+
+	/// Mode in which the template is parsed and resolved.
+	pub mode: TemplateMode,
+
+	/// Variables of the profile.
+	pub profile_vars: &'a LayeredVariables,
+
+	/// Variables of the dotfile.
+	pub dotfile_vars: Option<&'a Variables>,
+
+	/// Order in which the environments are searched for variables without a
+	/// prefix. Only used by the [`BuiltinEngine`].
+	pub order: VarEnvSet,
+
+	/// Variables which take precedence over the environment of the process.
+	pub env: &'a PunktfEnv,
+}
+
+/// An engine which resolves the templates of dotfiles.
+pub trait TemplateEngine {
+	/// Resolves the template `content` with the variables of `context`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the template is invalid or can not be
+	/// resolved.
+	fn render(&self, content: &str, context: &RenderContext<'_>) -> Result<String>;
+}
+
+/// The handlebars-like engine of `punktf` (related: [`Template`]).
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BuiltinEngine;
+
+impl TemplateEngine for BuiltinEngine {
+	fn render(&self, content: &str, context: &RenderContext<'_>) -> Result<String> {
+		let template = Template::parse_with_mode(
+			Source::file(context.path, content),
+			context.limits,
+			context.mode,
+		)?;
+
+		template.resolve_with_order(
+			Some(context.profile_vars),
+			context.dotfile_vars,
+			context.order,
+			context.env,
+		)
+	}
+}
+
+/// The data which is passed to other engines than the [`BuiltinEngine`].
+#[cfg(any(feature = "engine-handlebars", feature = "engine-tera"))]
+#[derive(Debug, Serialize)]
+struct EngineData<'a> {
+	/// Variables of the profile and the dotfile.
+	#[serde(flatten)]
+	variables: std::collections::BTreeMap<&'a str, &'a crate::profile::variables::Value>,
+
+	/// Variables of the system environment.
+	env: std::collections::BTreeMap<String, String>,
+}
+
+#[cfg(any(feature = "engine-handlebars", feature = "engine-tera"))]
+impl<'a> EngineData<'a> {
+	/// Collects the data for `context`.
+	fn new(context: &RenderContext<'a>) -> Self {
+		let mut variables: std::collections::BTreeMap<_, _> = context
+			.profile_vars
+			.inner
+			.iter()
+			.map(|(name, (_, value))| (name.as_str(), value))
+			.collect();
+
+		if let Some(dotfile_vars) = context.dotfile_vars {
+			variables.extend(
+				dotfile_vars
+					.inner
+					.iter()
+					.map(|(name, value)| (name.as_str(), value)),
+			);
+		}
+
+		let mut env: std::collections::BTreeMap<_, _> = std::env::vars().collect();
+		env.extend(
+			context
+				.env
+				.iter()
+				.map(|(name, value)| (name.to_string(), value.to_string())),
+		);
+
+		Self { variables, env }
+	}
+}
+
+/// Checks the output of an engine against the limits of `context`.
+#[cfg(any(feature = "engine-handlebars", feature = "engine-tera"))]
+fn check_output(output: String, context: &RenderContext<'_>) -> Result<String> {
+	if context.limits.exceeds_output_size(output.len()) {
+		return Err(Error::TemplateResolve {
+			origin: context.path.display().to_string(),
+			messages: vec![format!(
+				"Output exceeds the maximum size of {} bytes",
+				context.limits.max_output_size
+			)],
+		});
+	}
+
+	Ok(output)
+}
+
+/// Converts an error of an engine into an [`Error::TemplateResolve`] with the
+/// messages of the whole error chain.
+#[cfg(any(feature = "engine-handlebars", feature = "engine-tera"))]
+fn resolve_error(err: &dyn std::error::Error, context: &RenderContext<'_>) -> Error {
+	let mut messages = vec![err.to_string()];
+	let mut source = err.source();
+
+	while let Some(err) = source {
+		messages.push(err.to_string());
+		source = err.source();
+	}
+
+	Error::TemplateResolve {
+		origin: context.path.display().to_string(),
+		messages,
+	}
+}
+
+/// The [handlebars](https://handlebarsjs.com/) engine.
+///
+/// Values are not HTML escaped. In [`TemplateMode::Strict`] undefined
+/// variables are errors, in [`TemplateMode::Lax`] they are replaced with an
+/// empty string.
+#[cfg(feature = "engine-handlebars")]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HandlebarsEngine;
+
+#[cfg(feature = "engine-handlebars")]
+impl TemplateEngine for HandlebarsEngine {
+	fn render(&self, content: &str, context: &RenderContext<'_>) -> Result<String> {
+		let mut handlebars = handlebars::Handlebars::new();
+		handlebars.set_strict_mode(!context.mode.is_lax());
+		handlebars.register_escape_fn(handlebars::no_escape);
+
+		let output = handlebars
+			.render_template(content, &EngineData::new(context))
+			.map_err(|err| resolve_error(&err, context))?;
+
+		check_output(output, context)
+	}
+}
+
+/// The [tera](https://keats.github.io/tera/) engine.
+///
+/// Values are not HTML escaped. Undefined variables are always errors.
+#[cfg(feature = "engine-tera")]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TeraEngine;
+
+#[cfg(feature = "engine-tera")]
+impl TemplateEngine for TeraEngine {
+	fn render(&self, content: &str, context: &RenderContext<'_>) -> Result<String> {
+		let data = tera::Context::from_serialize(EngineData::new(context))
+			.map_err(|err| resolve_error(&err, context))?;
+
+		let output = tera::Tera::one_off(content, &data, false)
+			.map_err(|err| resolve_error(&err, context))?;
+
+		check_output(output, context)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use pretty_assertions::assert_eq;
+
+	use crate::profile::variables::Value;
+
+	/// Environment without any variables.
+	static EMPTY_ENV: PunktfEnv = PunktfEnv::new();
+
+	fn context<'a>(
+		profile_vars: &'a LayeredVariables,
+		dotfile_vars: Option<&'a Variables>,
+	) -> RenderContext<'a> {
+		RenderContext {
+			path: Path::new("test"),
+			limits: Limits::new(),
+			mode: TemplateMode::Strict,
+			profile_vars,
+			dotfile_vars,
+			order: VarEnvSet::default(),
+			env: &EMPTY_ENV,
+		}
+	}
+
+	#[test]
+	fn builtin() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let profile_vars = LayeredVariables {
+			inner: [("OS".into(), (0, Value::from("linux")))]
+				.into_iter()
+				.collect(),
+		};
+		let dotfile_vars = Variables::from_items([("OS", "windows")]);
+
+		let engine = EngineKind::default().engine()?;
+
+		assert_eq!(
+			engine.render(
+				"{{#OS}} {{OS}}",
+				&context(&profile_vars, Some(&dotfile_vars))
+			)?,
+			"linux windows"
+		);
+		assert!(engine
+			.render("{{MISSING}}", &context(&profile_vars, None))
+			.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn unavailable() {
+		crate::tests::setup_test_env();
+
+		#[cfg(not(feature = "engine-handlebars"))]
+		assert!(matches!(
+			EngineKind::Handlebars.engine(),
+			Err(Error::EngineUnavailable(EngineKind::Handlebars))
+		));
+
+		#[cfg(not(feature = "engine-tera"))]
+		assert!(matches!(
+			EngineKind::Tera.engine(),
+			Err(Error::EngineUnavailable(EngineKind::Tera))
+		));
+	}
+
+	#[cfg(feature = "engine-handlebars")]
+	#[test]
+	fn handlebars() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let profile_vars = LayeredVariables {
+			inner: [("OS".into(), (0, Value::from("linux")))]
+				.into_iter()
+				.collect(),
+		};
+		let dotfile_vars = Variables::from_items([("NAME", "<demo>")]);
+		let context = context(&profile_vars, Some(&dotfile_vars));
+
+		let engine = EngineKind::Handlebars.engine()?;
+
+		assert_eq!(
+			engine.render("{{#if OS}}{{OS}} {{NAME}}{{/if}}", &context)?,
+			"linux <demo>"
+		);
+		assert!(engine.render("{{MISSING}}", &context).is_err());
+
+		Ok(())
+	}
+
+	#[cfg(feature = "engine-tera")]
+	#[test]
+	fn tera() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let profile_vars = LayeredVariables {
+			inner: [("OS".into(), (0, Value::from("linux")))]
+				.into_iter()
+				.collect(),
+		};
+		let dotfile_vars = Variables::from_items([("NAME", "<demo>")]);
+		let context = context(&profile_vars, Some(&dotfile_vars));
+
+		let engine = EngineKind::Tera.engine()?;
+
+		assert_eq!(
+			engine.render(
+				"{% if OS == \"linux\" %}{{ OS | upper }} {{ NAME }}{% endif %}",
+				&context
+			)?,
+			"LINUX <demo>"
+		);
+		assert!(engine.render("{{ MISSING }}", &context).is_err());
+
+		Ok(())
+	}
+}
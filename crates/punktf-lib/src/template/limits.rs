@@ -0,0 +1,74 @@
+//! Guards which limit the resources used while parsing and resolving a
+//! [template](`super::Template`).
+//!
+//! Without them a pathological template (e.g. deeply nested `if` blocks or a
+//! huge output) could exhaust the stack or the memory of the process. If a
+//! limit is exceeded, the template fails with a diagnostic pointing to the
+//! offending block.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Resource limits for a single template.
+///
+/// A limit of `0` disables the corresponding guard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+	/// Maximum nesting depth of `if` blocks.
+	pub max_depth: usize,
+
+	/// Maximum size of the resolved output in bytes.
+	pub max_output_size: usize,
+
+	/// Maximum time in milliseconds the resolving of a template may take.
+	pub max_resolve_millis: u64,
+}
+
+impl Limits {
+	/// Creates the default limits.
+	///
+	/// These are generous enough for any reasonable dotfile.
+	pub const fn new() -> Self {
+		Self {
+			max_depth: 64,
+			max_output_size: 16 * 1024 * 1024,
+			max_resolve_millis: 10_000,
+		}
+	}
+
+	/// Creates limits with all guards disabled.
+	pub const fn unlimited() -> Self {
+		Self {
+			max_depth: 0,
+			max_output_size: 0,
+			max_resolve_millis: 0,
+		}
+	}
+
+	/// Checks if `depth` exceeds the maximum nesting depth.
+	pub const fn exceeds_depth(&self, depth: usize) -> bool {
+		self.max_depth != 0 && depth > self.max_depth
+	}
+
+	/// Checks if `size` exceeds the maximum output size.
+	pub const fn exceeds_output_size(&self, size: usize) -> bool {
+		self.max_output_size != 0 && size > self.max_output_size
+	}
+
+	/// Returns the maximum resolve time, if the guard is enabled.
+	pub const fn max_resolve_time(&self) -> Option<Duration> {
+		if self.max_resolve_millis == 0 {
+			None
+		} else {
+			Some(Duration::from_millis(self.max_resolve_millis))
+		}
+	}
+}
+
+impl Default for Limits {
+	fn default() -> Self {
+		Self::new()
+	}
+}
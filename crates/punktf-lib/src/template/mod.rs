@@ -20,6 +20,24 @@
 //!
 //! `{{{ This will be copied over {{ as is }} even with the "{{" inside }}}`
 //!
+//! ## Literal blocks
+//!
+//! Like escape blocks, everything inside will be copied over as is, but literal blocks are easier to use for larger verbatim snippets which span multiple lines.
+//!
+//! - A line break directly after `{{@literal}}` is removed.
+//! - If `{{@endliteral}}` is on a line of its own, the whitespace in front of it is removed.
+//! - Lines can start with a margin marker (`|`), which may be preceded by whitespace. Everything up to and including the marker is removed. This allows indenting the content together with the surrounding template.
+//!
+//! ### Syntax
+//!
+//! ```text
+//! {{@literal}}
+//!     |fn main() {
+//!     |    println!("{}", "{{ as is }}");
+//!     |}
+//! {{@endliteral}}
+//! ```
+//!
 //! ## Variable blocks
 //!
 //! Define a variable which will be inserted instead of the block. The value of the variable can be gotten from three different environments which can be defined by specifying a prefix:
@@ -30,14 +48,18 @@
 //!
 //! To search in more than one environment, these prefixes can be combined. The order they appear in is important, as they will be searched in order of appearance. If one environment does not have a value set for the variable, the next one is searched.
 //!
-//! If no prefixes are defined, it will default to `&#`.
+//! If no prefixes are defined, it will default to `&#`. The default can be changed for all templates of a profile with the `variable_resolution_order` setting.
 //!
 //! Valid symbols/characters for a variable name are: `(a..z|A..Z|0-9|_)`
 //!
+//! Filters can be appended with a `|` to change the value of a variable: `upper`, `lower`, `trim`, `replace('from', 'to')`, `default('value')`, `basename`, `dirname`, `winpath`, `posixpath` and `nativepath` (converts path separators to the one of the target operating system). They are applied in order of appearance.
+//!
 //! ### Syntax
 //!
 //! `{{$&#OS}}`
 //!
+//! `{{HOME | replace('\\', '/') | default('/home/demo')}}`
+//!
 //! ## Print blocks
 //!
 //! Print blocks will simply print everything contained within the block to the command line. The content of the print block **won't** be resolved, meaning it will be printed 1 to 1 (e.g. no variables are resolved).
@@ -57,6 +79,9 @@
 //! - Check if the value of a variable is (not) equal to the literal given: `{{VAR}} (==|!=) "LITERAL"`
 //! - Check if a value for a variable exists: `{{VAR}}`
 //!
+//! Comparisons are type-aware for typed profile variables (e.g. `8 == "8.0"`).
+//! An existence check treats `false`, `0` and empty lists as not set.
+//!
 //! Other blocks can be nested inside the `if`, `elif` and `else` bodies.
 //!
 //! ### Syntax
@@ -77,6 +102,30 @@
 //! {{@fi}}
 //! ```
 //!
+//! ## Syntax pragma
+//!
+//! Pins the version of the template syntax the file is written in, so templates can be migrated one at a time when the syntax evolves. The pragma must be the first block of the template and produces no output. Without it, version `1` is used. Unknown versions are reported as an error.
+//!
+//! ### Syntax
+//!
+//! `{{@syntax 1}}`
+//!
+//! ## Whitespace control
+//!
+//! A `~` directly after the opening `{{` of a block removes the spaces and tabs in front of it on the same line, so an indented block is treated like one at the start of the line. Lines which only contain such blocks then leave no blank lines in the output. A `~` directly before the closing `}}` removes the spaces and tabs following the block. The markers are supported on variable, print, let and if blocks.
+//!
+//! ### Example
+//!
+//! ```text
+//! a
+//!     {{~@if {{OS}}}}
+//! b
+//!     {{~@fi}}
+//! c
+//! ```
+//!
+//! Resolves to `a\nb\nc` if `OS` is set and to `a\nc` otherwise.
+//!
 //! # Copyright Notice
 //!
 //! The code for error/diagnostics and source input handling is heavily inspired by
@@ -95,19 +144,32 @@
 //! - <https://github.com/rust-lang/rust/blob/master/compiler/rustc_errors/src/emitter.rs>
 
 mod block;
-mod diagnostic;
+pub mod cache;
+pub mod check;
+pub(crate) mod diagnostic;
+pub mod engine;
+pub mod limits;
+pub mod mode;
 mod parse;
 mod resolve;
 mod session;
 pub mod source;
-mod span;
+pub(crate) mod span;
+pub mod syntax;
 
-use color_eyre::eyre::Result;
+use crate::env::PunktfEnv;
+use crate::error::Result;
 
 use self::block::Block;
+pub use self::block::{VarEnv, VarEnvSet};
+pub use self::check::check_all;
+use self::limits::Limits;
+use self::mode::TemplateMode;
 use self::parse::Parser;
 use self::resolve::Resolver;
+pub use self::resolve::{VariableSource, VariableUsage};
 use self::source::Source;
+use self::syntax::Syntax;
 use crate::profile::variables::Vars;
 
 /// A `Template` is a file from the Source folder that is not yet deployed. It might contain statements and variables.
@@ -120,12 +182,50 @@ pub struct Template<'a> {
 	///
 	/// These are sorted in the order they occur in `source`.
 	blocks: Vec<Block>,
+
+	/// Resource limits which are enforced while parsing and resolving.
+	limits: Limits,
+
+	/// The syntax version the template is written in.
+	syntax: Syntax,
+
+	/// Defines if blocks which can not be parsed or resolved are errors.
+	mode: TemplateMode,
 }
 
 impl<'a> Template<'a> {
 	/// Parses the source file and returns a `Template` object.
 	pub fn parse(source: Source<'a>) -> Result<Self> {
-		Parser::new(source).parse()
+		Self::parse_with_limits(source, Limits::default())
+	}
+
+	/// Parses the source file like [`Template::parse`], but enforces the given
+	/// resource `limits` instead of the default ones. The limits are also
+	/// enforced when the template is resolved.
+	pub fn parse_with_limits(source: Source<'a>, limits: Limits) -> Result<Self> {
+		Parser::new(source).with_limits(limits).parse()
+	}
+
+	/// Parses the source file like [`Template::parse_with_limits`], but in the
+	/// given `mode`. The mode is also applied when the template is resolved.
+	pub fn parse_with_mode(source: Source<'a>, limits: Limits, mode: TemplateMode) -> Result<Self> {
+		Parser::new(source)
+			.with_limits(limits)
+			.with_mode(mode)
+			.parse()
+	}
+
+	/// Returns the syntax version the template is written in.
+	///
+	/// This is selected by a `{{@syntax N}}` pragma and defaults to
+	/// [`Syntax::V1`].
+	pub const fn syntax(&self) -> Syntax {
+		self.syntax
+	}
+
+	/// Returns the mode the template was parsed in.
+	pub const fn mode(&self) -> TemplateMode {
+		self.mode
 	}
 
 	/// Resolves the variables in the template and returns a `Template` object.
@@ -136,6 +236,40 @@ impl<'a> Template<'a> {
 	) -> Result<String> {
 		Resolver::new(self, profile_vars, dotfile_vars).resolve()
 	}
+
+	/// Resolves the variables in the template like [`Template::resolve`], but
+	/// searches the environments given by `order` for variables which do not
+	/// specify any explicitly.
+	///
+	/// Variables of the system environment are first looked up in `env`.
+	pub fn resolve_with_order<PV: Vars, DV: Vars>(
+		&self,
+		profile_vars: Option<&PV>,
+		dotfile_vars: Option<&DV>,
+		order: VarEnvSet,
+		env: &PunktfEnv,
+	) -> Result<String> {
+		Resolver::new(self, profile_vars, dotfile_vars)
+			.with_default_envs(order)
+			.with_env(env)
+			.resolve()
+	}
+
+	/// Resolves the variables in the template like
+	/// [`Template::resolve_with_order`], but additionally returns all variables
+	/// which were used during the process.
+	pub fn resolve_with_usage<PV: Vars, DV: Vars>(
+		&self,
+		profile_vars: Option<&PV>,
+		dotfile_vars: Option<&DV>,
+		order: VarEnvSet,
+		env: &PunktfEnv,
+	) -> Result<(String, Vec<VariableUsage>)> {
+		Resolver::new(self, profile_vars, dotfile_vars)
+			.with_default_envs(order)
+			.with_env(env)
+			.resolve_with_usage()
+	}
 }
 
 #[cfg(test)]
@@ -143,7 +277,7 @@ mod tests {
 	use std::collections::HashMap;
 
 	use super::*;
-	use crate::profile::variables::Variables;
+	use crate::profile::variables::{Value, Variables};
 
 	#[test]
 	fn parse_template() -> Result<()> {
@@ -190,8 +324,8 @@ mod tests {
 		// println!("{:#?}", template);
 
 		let mut vars = HashMap::new();
-		vars.insert(String::from("BUZZ"), String::from("Hello World"));
-		vars.insert(String::from("OS"), String::from("linux"));
+		vars.insert(String::from("BUZZ"), Value::from("Hello World"));
+		vars.insert(String::from("OS"), Value::from("linux"));
 		let vars = Variables { inner: vars };
 
 		println!("{}", template.resolve(Some(&vars), Some(&vars))?);
@@ -203,88 +337,60 @@ mod tests {
 	fn parse_template_vars() -> Result<()> {
 		crate::tests::setup_test_env();
 
-		// Default
-		let content = r#"{{OS}}"#;
-		let source = Source::anonymous(content);
-		let template = Template::parse(source)?;
+		let mut env = PunktfEnv::new();
+		env.set("PUNKTF_TEST_OS", "macos");
 
-		let profile_vars = Variables::from_items(vec![("OS", "windows")]);
-		let item_vars = Variables::from_items(vec![("OS", "unix")]);
-		std::env::set_var("OS", "macos");
+		let resolve = |content: &str, env: &PunktfEnv| -> Result<String> {
+			let profile_vars = Variables::from_items(vec![("PUNKTF_TEST_OS", "windows")]);
+			let item_vars = Variables::from_items(vec![("PUNKTF_TEST_OS", "unix")]);
 
-		assert_eq!(
-			template.resolve(Some(&profile_vars), Some(&item_vars))?,
-			"unix"
-		);
+			Template::parse(Source::anonymous(content))?.resolve_with_order(
+				Some(&profile_vars),
+				Some(&item_vars),
+				VarEnvSet::default(),
+				env,
+			)
+		};
 
-		// Profile
-		let content = r#"{{#OS}}"#;
-		let source = Source::anonymous(content);
-		let template = Template::parse(source)?;
-
-		let profile_vars = Variables::from_items(vec![("OS", "windows")]);
-		let item_vars = Variables::from_items(vec![("OS", "unix")]);
-		std::env::set_var("OS", "macos");
+		// Default
+		assert_eq!(resolve("{{PUNKTF_TEST_OS}}", &env)?, "unix");
 
-		assert_eq!(
-			template.resolve(Some(&profile_vars), Some(&item_vars))?,
-			"windows"
-		);
+		// Profile
+		assert_eq!(resolve("{{#PUNKTF_TEST_OS}}", &env)?, "windows");
 
 		// Item
-		let content = r#"{{&OS}}"#;
-		let source = Source::anonymous(content);
-		let template = Template::parse(source)?;
-
-		let profile_vars = Variables::from_items(vec![("OS", "windows")]);
-		let item_vars = Variables::from_items(vec![("OS", "unix")]);
-		std::env::set_var("OS", "macos");
-
-		assert_eq!(
-			template.resolve(Some(&profile_vars), Some(&item_vars))?,
-			"unix"
-		);
+		assert_eq!(resolve("{{&PUNKTF_TEST_OS}}", &env)?, "unix");
 
 		// Env
-		let content = r#"{{$OS}}"#;
-		let source = Source::anonymous(content);
-		let template = Template::parse(source)?;
+		assert_eq!(resolve("{{$PUNKTF_TEST_OS}}", &env)?, "macos");
 
-		let profile_vars = Variables::from_items(vec![("OS", "windows")]);
-		let item_vars = Variables::from_items(vec![("OS", "unix")]);
-		std::env::set_var("OS", "macos");
+		// Mixed - First
+		assert_eq!(resolve("{{$#PUNKTF_TEST_OS}}", &env)?, "macos");
 
-		assert_eq!(
-			template.resolve(Some(&profile_vars), Some(&item_vars))?,
-			"macos"
-		);
+		// Mixed - Last
+		assert_eq!(resolve("{{$&PUNKTF_TEST_OS}}", &PunktfEnv::new())?, "unix");
 
-		// Mixed - First
-		let content = r#"{{$#OS}}"#;
-		let source = Source::anonymous(content);
-		let template = Template::parse(source)?;
+		Ok(())
+	}
 
-		let profile_vars = Variables::from_items(vec![("OS", "windows")]);
-		let item_vars = Variables::from_items(vec![("OS", "unix")]);
-		std::env::set_var("OS", "macos");
+	#[test]
+	fn parse_template_lax() -> Result<()> {
+		crate::tests::setup_test_env();
 
-		assert_eq!(
-			template.resolve(Some(&profile_vars), Some(&item_vars))?,
-			"macos"
-		);
+		let content = r#"{{#each items}}{{ item.name }}={{NAME}} {{else}}{{/each}} {{"#;
+		let vars = Variables::from_items(vec![("NAME", "demo")]);
 
-		// Mixed - Last
-		let content = r#"{{$&OS}}"#;
-		let source = Source::anonymous(content);
-		let template = Template::parse(source)?;
+		assert!(Template::parse(Source::anonymous(content)).is_err());
 
-		let profile_vars = Variables::from_items(vec![("OS", "windows")]);
-		let item_vars = Variables::from_items(vec![("OS", "unix")]);
-		std::env::remove_var("OS");
+		let template = Template::parse_with_mode(
+			Source::anonymous(content),
+			Limits::default(),
+			TemplateMode::Lax,
+		)?;
 
 		assert_eq!(
-			template.resolve(Some(&profile_vars), Some(&item_vars))?,
-			"unix"
+			template.resolve(Some(&vars), Option::<&Variables>::None)?,
+			"{{#each items}}{{ item.name }}=demo {{else}}{{/each}} {{"
 		);
 
 		Ok(())
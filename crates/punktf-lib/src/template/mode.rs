@@ -0,0 +1,40 @@
+//! Modes which control how strict a [template](`super::Template`) is parsed
+//! and resolved.
+//!
+//! Some dotfiles legitimately contain the syntax of other template engines
+//! (e.g. handlebars or jinja templates of other tools). In
+//! [`TemplateMode::Lax`] such blocks are copied to the output verbatim, so
+//! these files can still use `punktf` variables selectively.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Defines how strict a template is parsed and resolved.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TemplateMode {
+	/// Every block which can not be parsed or resolved is an error.
+	#[default]
+	Strict,
+
+	/// Blocks which can not be parsed (e.g. `{{ user.name }}` or
+	/// `{{#each items}}`) and variables which are not defined in any
+	/// environment are copied to the output as is.
+	Lax,
+}
+
+impl TemplateMode {
+	/// Checks if the mode is [`TemplateMode::Lax`].
+	pub const fn is_lax(&self) -> bool {
+		matches!(self, Self::Lax)
+	}
+}
+
+impl fmt::Display for TemplateMode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Strict => f.write_str("strict"),
+			Self::Lax => f.write_str("lax"),
+		}
+	}
+}
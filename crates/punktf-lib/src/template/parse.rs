@@ -3,15 +3,18 @@
 #[cfg(test)]
 mod tests;
 
-use color_eyre::eyre::{eyre, Result};
-use color_eyre::Report;
+use thiserror::Error;
 
-use super::block::{Block, BlockHint, If, IfExpr, IfOp, Var, VarEnv, VarEnvSet};
+use super::block::{Block, BlockHint, Filter, If, IfExpr, IfOp, Let, Var, VarEnv, VarEnvSet};
 use super::diagnostic::{Diagnostic, DiagnosticBuilder, DiagnosticLevel};
+use super::limits::Limits;
+use super::mode::TemplateMode;
 use super::session::Session;
 use super::source::Source;
 use super::span::{ByteSpan, Pos, Spanned};
+use super::syntax::Syntax;
 use super::Template;
+use crate::error::{Error, Result};
 use crate::template::block::BlockKind;
 
 /// This is the parser which converts a [source](`super::source::Source`) into
@@ -27,6 +30,18 @@ pub struct Parser<'a> {
 
 	/// An iterator of all blocks found within `source`.
 	blocks: BlockIter<'a>,
+
+	/// Resource limits which are enforced while parsing.
+	limits: Limits,
+
+	/// Number of `if` blocks which are currently open.
+	depth: usize,
+
+	/// The syntax version selected by a `{{@syntax N}}` pragma.
+	syntax: Syntax,
+
+	/// Defines if blocks which can not be parsed are errors.
+	mode: TemplateMode,
 }
 
 impl<'a> Parser<'a> {
@@ -38,9 +53,30 @@ impl<'a> Parser<'a> {
 			source,
 			session: Session::new(),
 			blocks,
+			limits: Limits::new(),
+			depth: 0,
+			syntax: Syntax::V1,
+			mode: TemplateMode::Strict,
 		}
 	}
 
+	/// Sets the resource limits which are enforced while parsing.
+	///
+	/// Defaults to [`Limits::new`].
+	pub const fn with_limits(mut self, limits: Limits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	/// Sets the mode which defines if blocks which can not be parsed are
+	/// errors.
+	///
+	/// Defaults to [`TemplateMode::Strict`].
+	pub const fn with_mode(mut self, mode: TemplateMode) -> Self {
+		self.mode = mode;
+		self
+	}
+
 	/// Consumes self and tries to resolve each block found within
 	/// [`Parser::source`].
 	///
@@ -48,6 +84,13 @@ impl<'a> Parser<'a> {
 	pub fn parse(mut self) -> Result<Template<'a>> {
 		let mut blocks = Vec::new();
 
+		if self.peek_block_hint() == Some(BlockHint::Syntax) {
+			match self.parse_syntax_pragma() {
+				Ok(block) => blocks.push(block),
+				Err(builder) => self.report_diagnostic(builder.build()),
+			}
+		}
+
 		while let Some(res) = self.next_top_level_block() {
 			match res {
 				Ok(block) => blocks.push(block),
@@ -56,14 +99,64 @@ impl<'a> Parser<'a> {
 		}
 
 		self.session.emit(&self.source);
-		self.session.try_finish()?;
+		self.session
+			.try_finish()
+			.map_err(|messages| Error::TemplateSyntax {
+				origin: self.source.origin().to_string(),
+				messages,
+			})?;
 
 		Ok(Template {
 			source: self.source,
 			blocks,
+			limits: self.limits,
+			syntax: self.syntax,
+			mode: self.mode,
 		})
 	}
 
+	/// Parses the `{{@syntax N}}` pragma at the start of the template and
+	/// selects the syntax version used for the remaining blocks.
+	///
+	/// The pragma is turned into a
+	/// [BlockKind::Comment](`super::block::BlockKind::Comment`), as it
+	/// produces no output.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the version is not a number or not supported.
+	fn parse_syntax_pragma(&mut self) -> Result<Block, DiagnosticBuilder> {
+		let Some(Ok(Spanned { span, .. })) = self.blocks.next() else {
+			unreachable!("Peeked a syntax pragma");
+		};
+
+		// {{@syntax N}}
+		let version = self.source[span.offset_low(9).offset_high(-2)].trim();
+
+		let syntax = version
+			.parse()
+			.ok()
+			.and_then(Syntax::from_version)
+			.ok_or_else(|| {
+				let supported = Syntax::SUPPORTED
+					.iter()
+					.map(ToString::to_string)
+					.collect::<Vec<_>>()
+					.join(", ");
+
+				DiagnosticBuilder::new(DiagnosticLevel::Error)
+					.message(format!("unsupported syntax version `{version}`"))
+					.description(format!("supported versions are: {supported}"))
+					.primary_span(span)
+			})?;
+
+		log::trace!("Selected template syntax version {syntax}");
+
+		self.syntax = syntax;
+
+		Ok(self.parse_comment(span))
+	}
+
 	/// Adds a diagnostic to the session.
 	///
 	/// If [Diagnostic::level](`super::diagnostic::Diagnostic::level`) is
@@ -92,9 +185,17 @@ impl<'a> Parser<'a> {
 	/// Returns an error if [`Parser::blocks`] failed to get the next block.
 	/// Returns an error if a none top-level block was found.
 	fn next_top_level_block(&mut self) -> Option<Result<Block, DiagnosticBuilder>> {
+		match self.syntax {
+			Syntax::V1 => self.next_top_level_block_v1(),
+		}
+	}
+
+	/// Tries to resolve the next "top-level" block of a template written in
+	/// [`Syntax::V1`] (related: [`Parser::next_top_level_block`]).
+	fn next_top_level_block_v1(&mut self) -> Option<Result<Block, DiagnosticBuilder>> {
 		let Spanned { span, value: hint } = match self.blocks.next()? {
 			Ok(x) => x,
-			Err(err) => return Some(Err(err)),
+			Err(err) => return Some(self.pass_through(err)),
 		};
 
 		log::trace!("{:?}: {}", hint, &self.source[span]);
@@ -103,13 +204,18 @@ impl<'a> Parser<'a> {
 			BlockHint::Text => Ok(self.parse_text(span)),
 			BlockHint::Comment => Ok(self.parse_comment(span)),
 			BlockHint::Escaped => Ok(self.parse_escaped(span)),
-			BlockHint::Var => self
-				.parse_variable(span)
-				.map(|var| Block::new(span, BlockKind::Var(var))),
-			BlockHint::Print => Ok(self.parse_print(span)),
+			BlockHint::Literal => Ok(self.parse_literal(span)),
+			BlockHint::Var => match self.parse_variable(span) {
+				Ok(var) => Ok(Block::new(span, BlockKind::Var(var))),
+				Err(err) => self.pass_through(err),
+			},
+			BlockHint::Print => self.parse_print(span),
 			BlockHint::IfStart => self
 				.parse_if(span)
 				.map(|Spanned { span, value }| Block::new(span, BlockKind::If(value))),
+			BlockHint::Let => self
+				.parse_let(span)
+				.map(|value| Block::new(span, BlockKind::Let(value))),
 
 			// Illegal top level blocks
 			BlockHint::ElIf => Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
@@ -124,11 +230,32 @@ impl<'a> Parser<'a> {
 				.message("top-level `fi` block")
 				.description("an `fi` can only be used to close an open `if` block")
 				.primary_span(span)),
+			BlockHint::Syntax => Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("misplaced `syntax` pragma")
+				.description("a `syntax` pragma must be the first block of a template")
+				.primary_span(span)),
 		};
 
 		Some(block)
 	}
 
+	/// Turns a block which could not be parsed into a text block, so that it is
+	/// copied to the output as is, if the parser is in [`TemplateMode::Lax`].
+	///
+	/// # Errors
+	///
+	/// Returns `err` if the parser is in [`TemplateMode::Strict`].
+	fn pass_through(&self, err: DiagnosticBuilder) -> Result<Block, DiagnosticBuilder> {
+		match err.primary_spans().first() {
+			Some(&span) if self.mode.is_lax() => {
+				log::trace!("Passing through: {}", &self.source[span]);
+
+				Ok(self.parse_text(span))
+			}
+			_ => Err(err),
+		}
+	}
+
 	/// Resolves the `span` to a block with
 	/// [BlockKind::Text](`super::block::BlockKind::Text`).
 	const fn parse_text(&self, span: ByteSpan) -> Block {
@@ -149,6 +276,19 @@ impl<'a> Parser<'a> {
 		Block::new(span, BlockKind::Escaped(span.offset_low(3).offset_high(-3)))
 	}
 
+	/// Resolves the `span` to a block with
+	/// [BlockKind::Literal](`super::block::BlockKind::Literal`).
+	fn parse_literal(&self, span: ByteSpan) -> Block {
+		// {{@literal}} ... {{@endliteral}}
+		Block::new(
+			span,
+			BlockKind::Literal(
+				span.offset_low(LITERAL_START.len() as i32)
+					.offset_high(-(LITERAL_END.len() as i32)),
+			),
+		)
+	}
+
 	/// Tries to resolves the `span` to a block with
 	/// [BlockKind::Var](`super::block::BlockKind::Var`).
 	///
@@ -170,11 +310,136 @@ impl<'a> Parser<'a> {
 		})
 	}
 
-	/// Resolves the `span` to a block with
+	/// Tries to resolve the `span` to a block with
 	/// [BlockKind::Print](`super::block::BlockKind::Print`).
-	fn parse_print(&self, span: ByteSpan) -> Block {
+	///
+	/// The content can contain variable blocks (e.g. `{{@print Host: {{HOST}}}}`).
+	///
+	/// # Errors
+	///
+	/// Returns an error if the content contains blocks other than text,
+	/// escaped or variable blocks.
+	fn parse_print(&self, span: ByteSpan) -> Result<Block, DiagnosticBuilder> {
 		// {{@print ... }}
-		Block::new(span, BlockKind::Print(span.offset_low(9).offset_high(-2)))
+		let content = self.parse_value_blocks(span.offset_low(9).offset_high(-2), span, "print")?;
+
+		Ok(Block::new(span, BlockKind::Print(content)))
+	}
+
+	/// Tries to resolve the content at `value` to text, escaped and variable
+	/// blocks (e.g. the value of a `let` block).
+	///
+	/// `parent` is the span of the whole block which contains the value and
+	/// `kind` its name, both are only used for diagnostics.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the value contains blocks other than text, escaped
+	/// or variable blocks.
+	fn parse_value_blocks(
+		&self,
+		value: ByteSpan,
+		parent: ByteSpan,
+		kind: &str,
+	) -> Result<Vec<Block>, DiagnosticBuilder> {
+		let label = format!("while parsing this `{kind}` block");
+		let mut blocks = Vec::new();
+
+		for res in BlockIter::new(&self.source[value]) {
+			let Spanned {
+				span: block_span,
+				value: hint,
+			} = res.map_err(|build| build.label_span(parent, label.clone()))?;
+
+			let block_span = block_span.offset(value.low().as_usize() as i32);
+
+			let block = match hint {
+				BlockHint::Text => self.parse_text(block_span),
+				BlockHint::Escaped => self.parse_escaped(block_span),
+				BlockHint::Var => Block::new(
+					block_span,
+					BlockKind::Var(
+						self.parse_variable(block_span)
+							.map_err(|build| build.label_span(parent, label.clone()))?,
+					),
+				),
+				_ => {
+					return Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
+						.message(format!("invalid block in `{kind}` value"))
+						.description("only text, escaped and variable blocks are allowed")
+						.primary_span(block_span)
+						.label_span(parent, label))
+				}
+			};
+
+			blocks.push(block);
+		}
+
+		Ok(blocks)
+	}
+
+	/// Tries to resolves the `span` to a block with
+	/// [BlockKind::Let](`super::block::BlockKind::Let`).
+	///
+	/// The value of the binding is a string literal which can contain
+	/// variable blocks (e.g. `{{@let NAME = "{{FIRST}} {{LAST}}"}}`).
+	///
+	/// # Errors
+	///
+	/// Returns an error if the name is not a valid variable name.
+	/// Returns an error if no `=` or quoted value was found.
+	/// Returns an error if the value contains blocks other than text, escaped
+	/// or variable blocks.
+	fn parse_let(&self, span: ByteSpan) -> Result<Let, DiagnosticBuilder> {
+		// {{@let NAME = "VALUE"}}
+		let inner = span.offset_low(7).offset_high(-2);
+		let content = &self.source[inner];
+
+		let eq_idx = content.find('=').ok_or_else(|| {
+			DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("expected a `=` after the name")
+				.description("bind a value with `{{@let NAME = \"VALUE\"}}`")
+				.primary_span(span)
+		})?;
+
+		let name = &content[..eq_idx];
+		let name_offset = inner.low().as_usize() + (name.len() - name.trim_start().len());
+		let name = name.trim();
+
+		if name.is_empty() || !name.bytes().all(is_var_name_symbol) {
+			return Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("invalid name for `let` block")
+				.description("a name must only contain ascii alphanumeric characters or `_`")
+				.primary_span(span));
+		}
+
+		let name = ByteSpan::new(name_offset, name_offset + name.len());
+
+		let value = &content[eq_idx + 1..];
+		let value_trimmed = value.trim();
+
+		if value_trimmed.len() < 2
+			|| !value_trimmed.starts_with('"')
+			|| !value_trimmed.ends_with('"')
+		{
+			return Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("expected a quoted value")
+				.description("bind a value with `{{@let NAME = \"VALUE\"}}`")
+				.primary_span(span));
+		}
+
+		// +1 to skip the opening `"`
+		let value_offset =
+			inner.low().as_usize() + eq_idx + 1 + (value.len() - value.trim_start().len()) + 1;
+		let value_content = &value_trimmed[1..value_trimmed.len() - 1];
+		let value = ByteSpan::new(value_offset, value_offset + value_content.len());
+
+		let blocks = self.parse_value_blocks(value, span, "let")?;
+
+		Ok(Let {
+			name,
+			value: blocks,
+		})
 	}
 
 	/// Tries to resolves the `span` to a block with
@@ -199,7 +464,32 @@ impl<'a> Parser<'a> {
 	/// Returns an error if no closing [BlockHint::IfEnd](`super::block::BlockHint::IfEnd`) was found.
 	/// Bubbles up any error which may occur during the subsequent calls to
 	/// [`Parser::parse_if_enclosed_blocks`].
+	/// Returns an error if the maximum nesting depth
+	/// ([`Limits::max_depth`](`super::limits::Limits::max_depth`)) is exceeded.
+	/// In this case the whole `if` block is skipped.
 	fn parse_if(&mut self, span: ByteSpan) -> Result<Spanned<If>, DiagnosticBuilder> {
+		if self.limits.exceeds_depth(self.depth + 1) {
+			self.skip_if_blocks();
+
+			return Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("maximum nesting depth exceeded")
+				.description(format!(
+					"`if` blocks can only be nested {} levels deep",
+					self.limits.max_depth
+				))
+				.primary_span(span));
+		}
+
+		self.depth += 1;
+		let result = self.parse_if_blocks(span);
+		self.depth -= 1;
+
+		result
+	}
+
+	/// Parses the `if` block starting at `span` together with all contained
+	/// blocks (related: [`Parser::parse_if`]).
+	fn parse_if_blocks(&mut self, span: ByteSpan) -> Result<Spanned<If>, DiagnosticBuilder> {
 		let head = span.span(
 			self.parse_if_start(span)
 				.map_err(|build| build.label_span(span, "while parsing this `if` block"))?,
@@ -384,7 +674,7 @@ impl<'a> Parser<'a> {
 	/// An error is returned if it fails to resolve the expression (related:
 	/// [`Parser::parse_if_expr`]).
 	fn parse_else(&self, span: ByteSpan) -> Result<ByteSpan, DiagnosticBuilder> {
-		if &self.source[span] != "{{@else}}" {
+		if &self.source[span.offset_low(2).offset_high(-2)] != "@else" {
 			Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
 				.message("expected a `else` block")
 				.primary_span(span))
@@ -401,7 +691,7 @@ impl<'a> Parser<'a> {
 	/// An error is returned if `span` does not contain a
 	/// [BlockHint::IfEnd](`super::block::BlockHint::IfEnd`).
 	fn parse_if_end(&self, span: ByteSpan) -> Result<ByteSpan, DiagnosticBuilder> {
-		if &self.source[span] != "{{@fi}}" {
+		if &self.source[span.offset_low(2).offset_high(-2)] != "@fi" {
 			Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
 				.message("expected a `fi` block")
 				.primary_span(span))
@@ -414,7 +704,9 @@ impl<'a> Parser<'a> {
 	///
 	/// These currently come in two forms:
 	///
-	/// - {{VAR}} (!=|==) "OTHER": Compare value of VAR with the literal OTHER
+	/// - {{VAR}} (==|!=|<|<=|>|>=) "OTHER": Compare value of VAR with the
+	///   literal OTHER, which can contain variable blocks (e.g.
+	///   `"{{OTHER_VAR}}"`)
 	/// - (!){{VAR}}: Checks if the variable is (not) present/can (not) be resolved.
 	///
 	/// # Errors
@@ -422,7 +714,7 @@ impl<'a> Parser<'a> {
 	/// An error is returned if `span` can not be interpreted as an if
 	/// expression.
 	fn parse_if_expr(&self, span: ByteSpan) -> Result<IfExpr, DiagnosticBuilder> {
-		// {{VAR}} (!=|==) "OTHER" OR (!){{VAR}}
+		// {{VAR}} (==|!=|<|<=|>|>=) "OTHER" OR (!){{VAR}}
 		let content = &self.source[span];
 
 		// Read optional `!` for not_exists
@@ -464,7 +756,9 @@ impl<'a> Parser<'a> {
 			let op = parse_ifop(&content[var_block_end..]).map_err(|_| {
 				DiagnosticBuilder::new(DiagnosticLevel::Error)
 					.message("failed to find if operation")
-					.description("add either `==` or `!=` after the variable block")
+					.description(
+						"add one of `==`, `!=`, `<`, `<=`, `>` or `>=` after the variable block",
+					)
 					.primary_span(var_block_span)
 			})?;
 
@@ -479,6 +773,8 @@ impl<'a> Parser<'a> {
 					.primary_span(var_block_span)
 			})?;
 
+			let other = self.parse_value_blocks(other, span, "if")?;
+
 			Ok(IfExpr::Compare { var, op, other })
 		}
 	}
@@ -502,22 +798,68 @@ impl<'a> Parser<'a> {
 		enclosed_blocks
 	}
 
+	/// Skips all blocks up to and including the `fi` block which closes the
+	/// already consumed `if` block.
+	///
+	/// This does not recurse, so it also works for arbitrary deeply nested
+	/// blocks.
+	fn skip_if_blocks(&mut self) {
+		let mut open = 1usize;
+
+		while open > 0 {
+			let Some(res) = self.blocks.next() else {
+				break;
+			};
+
+			match res.map(Spanned::into_value) {
+				Ok(BlockHint::IfStart) => open += 1,
+				Ok(BlockHint::IfEnd) => open -= 1,
+				_ => {}
+			}
+		}
+	}
+
 	/// Peeks at the next block hint. This does not affect any state of the
 	/// resolver.
 	fn peek_block_hint(&self) -> Option<BlockHint> {
 		// Create a copy of the block iter to not mess up the state while peeking
 		let mut peek = self.blocks;
-		peek.next()?.ok().map(|spanned| spanned.into_value())
+
+		match peek.next()? {
+			Ok(spanned) => Some(spanned.into_value()),
+			// Is passed through as text (related: [`Parser::pass_through`])
+			Err(_) if self.mode.is_lax() => Some(BlockHint::Text),
+			Err(_) => None,
+		}
 	}
 }
 
+/// Opening of a literal block.
+const LITERAL_START: &str = "{{@literal}}";
+
+/// Closing of a literal block.
+const LITERAL_END: &str = "{{@endliteral}}";
+
 /// A span together with an optional block hint, describing the type of the
 /// block contained by the span.
 type NextBlock = (ByteSpan, Option<BlockHint>);
 
+/// An error found while parsing the content of a single block. It is reported
+/// as part of a [diagnostic](`super::diagnostic::Diagnostic`).
+#[derive(Debug, Error)]
+#[error("{0}")]
+struct BlockError(String);
+
+/// Creates a [`BlockError`] with a formatted message.
+macro_rules! block_error {
+	($($arg:tt)*) => {
+		BlockError(format!($($arg)*))
+	};
+}
+
 /// An error together with the amount of bytes to skip to continue parsing. The
 /// amount tries to skip the erroneous part.
-type NextBlockError = (Option<usize>, Report);
+type NextBlockError = (Option<usize>, BlockError);
 
 /// Tries to find the next block contained in `s`.
 ///
@@ -546,7 +888,20 @@ fn next_block(s: &str) -> Option<Result<NextBlock, NextBlockError>> {
 			} else {
 				Some(Err((
 					Some(3),
-					eyre!("Found opening for an escaped block but no closing"),
+					block_error!("Found opening for an escaped block but no closing"),
+				)))
+			}
+		} else if s[low..].starts_with(LITERAL_START) {
+			// block is a literal block
+			if let Some(high) = s.find(LITERAL_END) {
+				Some(Ok((
+					ByteSpan::new(low, high + LITERAL_END.len()),
+					Some(BlockHint::Literal),
+				)))
+			} else {
+				Some(Err((
+					Some(LITERAL_START.len()),
+					block_error!("Found opening for a literal block but no closing"),
 				)))
 			}
 		} else if let Some(b"!--") = s.as_bytes().get(low + 2..low + 5) {
@@ -556,7 +911,7 @@ fn next_block(s: &str) -> Option<Result<NextBlock, NextBlockError>> {
 			} else {
 				Some(Err((
 					Some(5),
-					eyre!("Found opening for a comment block but no closing"),
+					block_error!("Found opening for a comment block but no closing"),
 				)))
 			}
 		} else {
@@ -581,7 +936,7 @@ fn next_block(s: &str) -> Option<Result<NextBlock, NextBlockError>> {
 
 			Some(Err((
 				Some(2),
-				eyre!("Found opening for a block but no closing"),
+				block_error!("Found opening for a block but no closing"),
 			)))
 		}
 	} else {
@@ -607,7 +962,173 @@ fn next_block(s: &str) -> Option<Result<NextBlock, NextBlockError>> {
 /// was found more than once.
 /// An error is returned if the name of the variable is not valid (related:
 /// [`is_var_name_symbol`]).
-fn parse_var(inner: &str, mut offset: usize) -> Result<Var> {
+/// An error is returned if a filter could not be parsed (related:
+/// [`parse_filter`]).
+fn parse_var(inner: &str, offset: usize) -> Result<Var, BlockError> {
+	let mut parts = split_filters(inner)?.into_iter();
+
+	// The first part always exists, even for an empty `inner`
+	let (name_offset, name) = parts.next().unwrap_or((0, inner));
+	let mut var = parse_var_name(name, offset + name_offset)?;
+
+	for (filter_offset, filter) in parts {
+		var.filters
+			.push(parse_filter(filter, offset + filter_offset)?);
+	}
+
+	Ok(var)
+}
+
+/// Splits `inner` at each `|` which is not part of a string literal.
+///
+/// Returns each part together with its offset into `inner`.
+///
+/// # Errors
+///
+/// An error is returned if a string literal is not closed.
+fn split_filters(inner: &str) -> Result<Vec<(usize, &str)>, BlockError> {
+	let mut parts = Vec::new();
+	let mut start = 0;
+	let mut quote = None;
+	let mut escaped = false;
+
+	for (idx, c) in inner.char_indices() {
+		match (quote, c) {
+			(Some(_), _) if escaped => escaped = false,
+			(Some(_), '\\') => escaped = true,
+			(Some(q), c) if q == c => quote = None,
+			(Some(_), _) => {}
+			(None, '\'' | '"') => quote = Some(c),
+			(None, '|') => {
+				parts.push((start, &inner[start..idx]));
+				start = idx + 1;
+			}
+			(None, _) => {}
+		}
+	}
+
+	if quote.is_some() {
+		return Err(block_error!(
+			"Found unclosed string literal in variable block"
+		));
+	}
+
+	parts.push((start, &inner[start..]));
+
+	Ok(parts)
+}
+
+/// Tries to parse `inner` as a [`Filter`](`super::block::Filter`) (e.g.
+/// `upper` or `replace('a', 'b')`).
+///
+/// Arguments are string literals enclosed in `'` or `"`. Inside of them `\`
+/// escapes the next character.
+///
+/// # Errors
+///
+/// An error is returned if the filter is unknown, the arguments are malformed
+/// or the number of arguments does not match.
+fn parse_filter(inner: &str, mut offset: usize) -> Result<Spanned<Filter>, BlockError> {
+	let orig_len = inner.len();
+	let inner = inner.trim_start();
+	offset += orig_len - inner.len();
+	let inner = inner.trim_end();
+
+	let span = ByteSpan::new(offset, offset + inner.len());
+
+	let name_len = inner
+		.bytes()
+		.position(|b| !is_var_name_symbol(b))
+		.unwrap_or(inner.len());
+
+	let (name, rest) = inner.split_at(name_len);
+
+	if name.is_empty() {
+		return Err(block_error!("Empty filter name at {}", offset));
+	}
+
+	let rest = rest.trim();
+
+	let args = if rest.is_empty() {
+		Vec::new()
+	} else if let Some(rest) = rest.strip_prefix('(').and_then(|r| r.strip_suffix(')')) {
+		parse_filter_args(rest)?
+	} else {
+		return Err(block_error!(
+			"Expected arguments in parentheses after filter `{}` at {}",
+			name,
+			offset
+		));
+	};
+
+	Filter::new(name, args)
+		.map(|filter| Spanned::new(span, filter))
+		.map_err(|err| block_error!("{} at {}", err, offset))
+}
+
+/// Parses the comma separated string literals `inner` of a filter call.
+///
+/// # Errors
+///
+/// An error is returned if an argument is not a closed string literal.
+fn parse_filter_args(inner: &str) -> Result<Vec<String>, BlockError> {
+	let mut args = Vec::new();
+	let mut chars = inner.trim().chars().peekable();
+
+	while let Some(quote) = chars.next() {
+		if quote != '\'' && quote != '"' {
+			return Err(block_error!("Filter arguments must be string literals"));
+		}
+
+		let mut arg = String::new();
+		let mut closed = false;
+
+		while let Some(c) = chars.next() {
+			match c {
+				'\\' => arg.extend(chars.next()),
+				c if c == quote => {
+					closed = true;
+					break;
+				}
+				c => arg.push(c),
+			}
+		}
+
+		if !closed {
+			return Err(block_error!(
+				"Found unclosed string literal in filter arguments"
+			));
+		}
+
+		args.push(arg);
+
+		// Skip to the next argument
+		while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+		match chars.next() {
+			Some(',') => while chars.next_if(|c| c.is_whitespace()).is_some() {},
+			None => break,
+			Some(c) => {
+				return Err(block_error!(
+					"Expected `,` between filter arguments, found `{c}`"
+				))
+			}
+		}
+	}
+
+	Ok(args)
+}
+
+/// Tries to parse `inner` as the name (including the environments) of a
+/// [`Var`](`super::block::Var`).
+///
+/// # Errors
+///
+/// An error is returned if a (variable environment)[`super::block::VarEnv`]
+/// was found more than once.
+/// An error is returned if the name of the variable is not valid (related:
+/// [`is_var_name_symbol`]).
+fn parse_var_name(inner: &str, mut offset: usize) -> Result<Var, BlockError> {
 	// save original length to keep track of the offset
 	let orig_len = inner.len();
 
@@ -638,7 +1159,7 @@ fn parse_var(inner: &str, mut offset: usize) -> Result<Var> {
 
 			// break if add fails (duplicate)
 			if !env_set.add(env) {
-				return Err(eyre!(
+				return Err(block_error!(
 					"Specified duplicate variable environments at {}",
 					offset
 				));
@@ -651,16 +1172,16 @@ fn parse_var(inner: &str, mut offset: usize) -> Result<Var> {
 
 		env_set
 	} else {
-		VarEnvSet::default()
+		VarEnvSet::empty()
 	};
 
 	// check var name
 	//	- len > 0
 	//	- only ascii + _
 	if inner.is_empty() {
-		Err(eyre!("Empty variable name at {}", offset))
+		Err(block_error!("Empty variable name at {}", offset))
 	} else if let Some(invalid) = inner.as_bytes().iter().find(|&&b| !is_var_name_symbol(b)) {
-		Err(eyre!(
+		Err(block_error!(
 			"Found invalid symbol in variable name: (b`{}`; c`{}`)",
 			invalid,
 			if invalid.is_ascii() {
@@ -673,6 +1194,7 @@ fn parse_var(inner: &str, mut offset: usize) -> Result<Var> {
 		Ok(Var {
 			envs,
 			name: ByteSpan::new(offset, offset + inner.len()),
+			filters: Vec::new(),
 		})
 	}
 }
@@ -682,18 +1204,20 @@ fn parse_var(inner: &str, mut offset: usize) -> Result<Var> {
 /// # Errors
 ///
 /// An error is returned if `inner` could not be interpreted as an if operand.
-fn parse_ifop(inner: &str) -> Result<IfOp> {
-	match (inner.find("=="), inner.find("!=")) {
-		(Some(eq_idx), Some(noteq_idx)) => {
-			if eq_idx < noteq_idx {
-				Ok(IfOp::Eq)
-			} else {
-				Ok(IfOp::NotEq)
-			}
-		}
-		(Some(_), None) => Ok(IfOp::Eq),
-		(None, Some(_)) => Ok(IfOp::NotEq),
-		_ => Err(eyre!("Failed to find a if operand")),
+fn parse_ifop(inner: &str) -> Result<IfOp, BlockError> {
+	let inner = inner.trim_start();
+	let len = inner
+		.find(|c| !matches!(c, '=' | '!' | '<' | '>'))
+		.unwrap_or(inner.len());
+
+	match &inner[..len] {
+		"==" => Ok(IfOp::Eq),
+		"!=" => Ok(IfOp::NotEq),
+		"<" => Ok(IfOp::Lt),
+		"<=" => Ok(IfOp::Le),
+		">" => Ok(IfOp::Gt),
+		">=" => Ok(IfOp::Ge),
+		_ => Err(block_error!("Failed to find a if operand")),
 	}
 }
 
@@ -704,16 +1228,16 @@ fn parse_ifop(inner: &str) -> Result<IfOp> {
 ///
 /// An error is returned if no `"` was found.
 /// An error is returned if a opening `"` was found but no closing one.
-fn parse_other(inner: &str, offset: usize) -> Result<ByteSpan> {
+fn parse_other(inner: &str, offset: usize) -> Result<ByteSpan, BlockError> {
 	let mut matches = inner.match_indices('"').map(|(idx, _)| idx);
 
 	match (matches.next(), matches.next()) {
 		(Some(low), Some(high)) => Ok(ByteSpan::new(offset + low + 1, offset + high)),
-		(Some(low), None) => Err(eyre!(
+		(Some(low), None) => Err(block_error!(
 			"Found opening `\"` at {} but no closing",
 			offset + low
 		)),
-		_ => Err(eyre!("Found no other")),
+		_ => Err(block_error!("Found no other")),
 	}
 }
 
@@ -731,12 +1255,20 @@ struct BlockIter<'a> {
 
 	/// Current index into `content`.
 	index: usize,
+
+	/// Whether leading spaces and tabs of the next block should be skipped, as
+	/// the previous block ended with a `~}}` marker.
+	trim_next: bool,
 }
 
 impl<'a> BlockIter<'a> {
 	/// Creates a new instance for `content`.
 	const fn new(content: &'a str) -> Self {
-		Self { content, index: 0 }
+		Self {
+			content,
+			index: 0,
+			trim_next: false,
+		}
 	}
 }
 
@@ -744,6 +1276,13 @@ impl<'a> Iterator for BlockIter<'a> {
 	type Item = Result<Spanned<BlockHint>, DiagnosticBuilder>;
 
 	fn next(&mut self) -> Option<Self::Item> {
+		if self.trim_next {
+			// previous block ended with `~}}`
+			self.trim_next = false;
+			let rest = &self.content[self.index..];
+			self.index += rest.len() - rest.trim_start_matches([' ', '\t']).len();
+		}
+
 		let (mut span, hint) = match next_block(&self.content[self.index..])? {
 			Ok(x) => x,
 			Err((skip, err)) => {
@@ -769,6 +1308,17 @@ impl<'a> Iterator for BlockIter<'a> {
 		span = span.offset(self.index as i32);
 		self.index = span.high().as_usize();
 
+		if hint == Some(BlockHint::Text) && self.content[self.index..].starts_with("{{~") {
+			// next block starts with `{{~`, drop its indentation
+			let text = self.content[span].trim_end_matches([' ', '\t']);
+
+			if text.is_empty() {
+				return self.next();
+			}
+
+			span = span.with_high(span.low().as_usize() + text.len());
+		}
+
 		if let Some(hint) = hint {
 			return Some(Ok(span.span(hint)));
 		}
@@ -781,7 +1331,21 @@ impl<'a> Iterator for BlockIter<'a> {
 		}
 
 		// Content without block opening and closing
-		let content = &content[2..content.len() - 2];
+		let mut content = &content[2..content.len() - 2];
+
+		// Strip whitespace control markers
+		// e.g. `{{~@if {{VAR}}~}}`
+		// The span is shrunk so that the markers are not seen by the parser.
+		if let Some(stripped) = content.strip_prefix('~') {
+			content = stripped;
+			span = span.offset_low(1);
+		}
+
+		if let Some(stripped) = content.strip_suffix('~') {
+			content = stripped;
+			span = span.offset_high(-1);
+			self.trim_next = true;
+		}
 
 		// Check for escaped
 		// e.g. `{{{ Escaped }}}`
@@ -801,6 +1365,12 @@ impl<'a> Iterator for BlockIter<'a> {
 			return Some(Ok(span.span(BlockHint::Print)));
 		}
 
+		// Check for let
+		// e.g. `{{@let NAME = "VALUE"}}`
+		if content.starts_with("@let ") {
+			return Some(Ok(span.span(BlockHint::Let)));
+		}
+
 		// Check for if
 		// e.g. `{{@if {{VAR}} == "LITERAL"}}`
 		if content.starts_with("@if ") {
@@ -819,6 +1389,21 @@ impl<'a> Iterator for BlockIter<'a> {
 			return Some(Ok(span.span(BlockHint::Else)));
 		}
 
+		// Check for a closing literal without an opening
+		// e.g. `{{@endliteral}}`
+		if content == "@endliteral" {
+			return Some(Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("top-level `endliteral` block")
+				.description("an `endliteral` can only be used to close an open `literal` block")
+				.primary_span(span)));
+		}
+
+		// Check for a syntax pragma
+		// e.g. `{{@syntax 2}}`
+		if content.starts_with("@syntax ") {
+			return Some(Ok(span.span(BlockHint::Syntax)));
+		}
+
 		// Check for fi
 		// e.g. `{{@fi}}`
 		if content.starts_with("@fi") {
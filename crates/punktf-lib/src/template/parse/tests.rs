@@ -4,9 +4,11 @@ use color_eyre::eyre::{eyre, Result};
 use pretty_assertions::assert_eq;
 
 use super::*;
-use crate::template::block::{Block, BlockKind, If, IfExpr, IfOp, Var, VarEnv, VarEnvSet};
+use crate::template::block::{
+	Block, BlockKind, Filter, If, IfExpr, IfOp, Let, Var, VarEnv, VarEnvSet,
+};
 use crate::template::source::Source;
-use crate::template::span::ByteSpan;
+use crate::template::span::{ByteSpan, Spanned};
 
 #[test]
 fn parse_single_text() -> Result<()> {
@@ -89,8 +91,15 @@ fn parse_single_var_default() -> Result<()> {
 
 	let name = ByteSpan::new(2usize, content.len() - 2);
 	assert_eq!(&content[name], "OS");
-	let envs = VarEnvSet([Some(VarEnv::Dotfile), Some(VarEnv::Profile), None]);
-	assert_eq!(block.kind(), &BlockKind::Var(Var { envs, name }));
+	let envs = VarEnvSet::empty();
+	assert_eq!(
+		block.kind(),
+		&BlockKind::Var(Var {
+			envs,
+			name,
+			filters: Vec::new()
+		})
+	);
 
 	Ok(())
 }
@@ -113,7 +122,14 @@ fn parse_single_var_env() -> Result<()> {
 	let name = ByteSpan::new(3usize, content.len() - 2);
 	assert_eq!(&content[name], "ENV");
 	let envs = VarEnvSet([Some(VarEnv::Environment), None, None]);
-	assert_eq!(block.kind(), &BlockKind::Var(Var { envs, name }));
+	assert_eq!(
+		block.kind(),
+		&BlockKind::Var(Var {
+			envs,
+			name,
+			filters: Vec::new()
+		})
+	);
 
 	Ok(())
 }
@@ -136,7 +152,14 @@ fn parse_single_var_profile() -> Result<()> {
 	let name = ByteSpan::new(3usize, content.len() - 2);
 	assert_eq!(&content[name], "PROFILE");
 	let envs = VarEnvSet([Some(VarEnv::Profile), None, None]);
-	assert_eq!(block.kind(), &BlockKind::Var(Var { envs, name }));
+	assert_eq!(
+		block.kind(),
+		&BlockKind::Var(Var {
+			envs,
+			name,
+			filters: Vec::new()
+		})
+	);
 
 	Ok(())
 }
@@ -159,7 +182,14 @@ fn parse_single_var_dotfile() -> Result<()> {
 	let name = ByteSpan::new(3usize, content.len() - 2);
 	assert_eq!(&content[name], "ITEM");
 	let envs = VarEnvSet([Some(VarEnv::Dotfile), None, None]);
-	assert_eq!(block.kind(), &BlockKind::Var(Var { envs, name }));
+	assert_eq!(
+		block.kind(),
+		&BlockKind::Var(Var {
+			envs,
+			name,
+			filters: Vec::new()
+		})
+	);
 
 	Ok(())
 }
@@ -186,7 +216,14 @@ fn parse_single_var_mixed() -> Result<()> {
 		Some(VarEnv::Dotfile),
 		Some(VarEnv::Profile),
 	]);
-	assert_eq!(block.kind(), &BlockKind::Var(Var { envs, name }));
+	assert_eq!(
+		block.kind(),
+		&BlockKind::Var(Var {
+			envs,
+			name,
+			filters: Vec::new()
+		})
+	);
 
 	Ok(())
 }
@@ -226,7 +263,10 @@ fn parse_single_print() -> Result<()> {
 
 	let inner = ByteSpan::new(9usize, content.len() - 2);
 	assert_eq!(&content[inner], "FooBar");
-	assert_eq!(block.kind(), &BlockKind::Print(inner));
+	assert_eq!(
+		block.kind(),
+		&BlockKind::Print(vec![Block::new(inner, BlockKind::Text)])
+	);
 
 	Ok(())
 }
@@ -251,7 +291,7 @@ fn parse_single_if_eq() -> Result<()> {
 
 	let name = ByteSpan::new(8usize, 10usize);
 	assert_eq!(&content[name], "OS");
-	let envs = VarEnvSet([Some(VarEnv::Dotfile), Some(VarEnv::Profile), None]);
+	let envs = VarEnvSet::empty();
 
 	let op = IfOp::Eq;
 
@@ -266,9 +306,13 @@ fn parse_single_if_eq() -> Result<()> {
 		&BlockKind::If(If {
 			head: (
 				if_span.span(IfExpr::Compare {
-					var: Var { envs, name },
+					var: Var {
+						envs,
+						name,
+						filters: Vec::new()
+					},
 					op,
-					other
+					other: vec![Block::new(other, BlockKind::Text)]
 				}),
 				vec![]
 			),
@@ -301,7 +345,7 @@ fn parse_single_if_neq() -> Result<()> {
 
 	let name = ByteSpan::new(8usize, 10usize);
 	assert_eq!(&content[name], "OS");
-	let envs = VarEnvSet([Some(VarEnv::Dotfile), Some(VarEnv::Profile), None]);
+	let envs = VarEnvSet::empty();
 
 	let op = IfOp::NotEq;
 
@@ -316,9 +360,13 @@ fn parse_single_if_neq() -> Result<()> {
 		&BlockKind::If(If {
 			head: (
 				if_span.span(IfExpr::Compare {
-					var: Var { envs, name },
+					var: Var {
+						envs,
+						name,
+						filters: Vec::new()
+					},
 					op,
-					other
+					other: vec![Block::new(other, BlockKind::Text)]
 				}),
 				vec![]
 			),
@@ -361,7 +409,11 @@ fn parse_single_if_exists() -> Result<()> {
 		&BlockKind::If(If {
 			head: (
 				if_span.span(IfExpr::Exists {
-					var: Var { envs, name }
+					var: Var {
+						envs,
+						name,
+						filters: Vec::new()
+					}
 				}),
 				vec![]
 			),
@@ -404,7 +456,11 @@ fn parse_single_if_not_exists() -> Result<()> {
 		&BlockKind::If(If {
 			head: (
 				if_span.span(IfExpr::NotExists {
-					var: Var { envs, name }
+					var: Var {
+						envs,
+						name,
+						filters: Vec::new()
+					}
 				}),
 				vec![]
 			),
@@ -620,6 +676,50 @@ fn parse_print() -> Result<()> {
 	Ok(())
 }
 
+#[test]
+fn parse_let() -> Result<()> {
+	crate::tests::setup_test_env();
+
+	let content = r#"{{@let FULL_NAME = "{{FIRST}} {{LAST}}"}}"#;
+
+	let source = Source::anonymous(content);
+	let mut parser = Parser::new(source);
+	let token = parser
+		.next_top_level_block()
+		.expect("Found no block")
+		.expect("Encountered a parse error");
+
+	assert_eq!(token.span, ByteSpan::new(0usize, content.len()));
+
+	let BlockKind::Let(Let { name, value }) = token.kind else {
+		panic!("Expected a let block");
+	};
+
+	assert_eq!(&content[name], "FULL_NAME");
+	assert_eq!(value.len(), 3);
+	assert!(matches!(value[0].kind, BlockKind::Var(_)));
+	assert_eq!(&content[value[1].span], " ");
+	assert!(matches!(value[2].kind, BlockKind::Var(_)));
+
+	// missing quotes
+	let source = Source::anonymous(r#"{{@let FOO = {{BAR}}}}"#);
+	let mut parser = Parser::new(source);
+	assert!(parser
+		.next_top_level_block()
+		.expect("Found no block")
+		.is_err());
+
+	// nested if not allowed
+	let source = Source::anonymous(r#"{{@let FOO = "{{@if {{BAR}}}}"}}"#);
+	let mut parser = Parser::new(source);
+	assert!(parser
+		.next_top_level_block()
+		.expect("Found no block")
+		.is_err());
+
+	Ok(())
+}
+
 #[test]
 fn parse_variables() -> Result<()> {
 	crate::tests::setup_test_env();
@@ -633,6 +733,7 @@ fn parse_variables() -> Result<()> {
 				Some(VarEnv::Dotfile)
 			]),
 			name: ByteSpan::new(3usize, 10usize),
+			filters: Vec::new(),
 		}
 	);
 
@@ -641,6 +742,7 @@ fn parse_variables() -> Result<()> {
 		Var {
 			envs: VarEnvSet([Some(VarEnv::Dotfile), None, None]),
 			name: ByteSpan::new(1usize, 6usize),
+			filters: Vec::new(),
 		}
 	);
 
@@ -653,6 +755,7 @@ fn parse_variables() -> Result<()> {
 				Some(VarEnv::Dotfile)
 			]),
 			name: ByteSpan::new(13usize, 20usize),
+			filters: Vec::new(),
 		}
 	);
 
@@ -664,6 +767,45 @@ fn parse_variables() -> Result<()> {
 	Ok(())
 }
 
+#[test]
+fn parse_variable_filters() -> Result<()> {
+	crate::tests::setup_test_env();
+
+	let var = parse_var(r#" OS | upper | replace('\\', "/") |default('x') "#, 0)?;
+	assert_eq!(var.name, ByteSpan::new(1usize, 3usize));
+	assert_eq!(
+		var.filters
+			.into_iter()
+			.map(Spanned::into_value)
+			.collect::<Vec<_>>(),
+		vec![
+			Filter::Upper,
+			Filter::Replace {
+				from: String::from("\\"),
+				to: String::from("/"),
+			},
+			Filter::Default(String::from("x")),
+		]
+	);
+
+	// `|` inside of literals does not start a new filter
+	let var = parse_var("FOO | replace('|', '-')", 0)?;
+	assert_eq!(var.filters.len(), 1);
+	assert_eq!(*var.filters[0].span(), ByteSpan::new(6usize, 23usize));
+
+	// unknown filter
+	assert!(parse_var("FOO | shout", 0).is_err());
+	// wrong number of arguments
+	assert!(parse_var("FOO | replace('a')", 0).is_err());
+	assert!(parse_var("FOO | upper('a')", 0).is_err());
+	// unclosed literal
+	assert!(parse_var("FOO | default('a)", 0).is_err());
+	// empty filter
+	assert!(parse_var("FOO |", 0).is_err());
+
+	Ok(())
+}
+
 #[test]
 fn parse_others() -> Result<()> {
 	crate::tests::setup_test_env();
@@ -679,3 +821,94 @@ fn parse_others() -> Result<()> {
 
 	Ok(())
 }
+
+#[test]
+fn parse_literal() -> Result<()> {
+	crate::tests::setup_test_env();
+
+	let content = "{{@literal}}{{ {{{ }}{{@endliteral}}";
+
+	let source = Source::anonymous(content);
+	let mut parser = Parser::new(source);
+	let block = parser
+		.next_top_level_block()
+		.expect("Found no block")
+		.expect("Encountered a parse error");
+
+	assert_eq!(block.span(), &ByteSpan::new(0usize, content.len()));
+
+	let BlockKind::Literal(inner) = block.kind else {
+		panic!("Expected a literal block");
+	};
+	assert_eq!(&content[inner], "{{ {{{ }}");
+
+	// missing closing
+	let source = Source::anonymous("{{@literal}} {{ }}");
+	assert!(Parser::new(source).parse().is_err());
+
+	// closing without opening
+	let source = Source::anonymous("{{@endliteral}}");
+	assert!(Parser::new(source).parse().is_err());
+
+	Ok(())
+}
+
+#[test]
+fn parse_syntax_pragma() -> Result<()> {
+	crate::tests::setup_test_env();
+
+	let content = "{{@syntax 1}}\nHello";
+
+	let template = Parser::new(Source::anonymous(content)).parse()?;
+	assert_eq!(template.syntax(), Syntax::V1);
+	assert_eq!(
+		template.blocks[0],
+		Block::new(ByteSpan::new(0usize, 13usize), BlockKind::Comment)
+	);
+
+	// defaults to the first version
+	let template = Parser::new(Source::anonymous("Hello")).parse()?;
+	assert_eq!(template.syntax(), Syntax::V1);
+
+	// unknown version
+	let source = Source::anonymous("{{@syntax 99}}\nHello");
+	assert!(Parser::new(source).parse().is_err());
+
+	// not a version
+	let source = Source::anonymous("{{@syntax next}}\nHello");
+	assert!(Parser::new(source).parse().is_err());
+
+	// not the first block
+	let source = Source::anonymous("Hello\n{{@syntax 1}}");
+	assert!(Parser::new(source).parse().is_err());
+
+	Ok(())
+}
+
+#[test]
+fn parse_whitespace_control() -> Result<()> {
+	crate::tests::setup_test_env();
+
+	let content = "a\n  {{~@if {{OS}}~}}  b\n\t{{~@else}}c {{@fi~}} \t\n";
+
+	let template = Parser::new(Source::anonymous(content)).parse()?;
+	assert_eq!(template.blocks.len(), 3);
+	assert_eq!(&content[template.blocks[0].span], "a\n");
+	assert_eq!(&content[template.blocks[2].span], "\n");
+
+	let BlockKind::If(ref if_block) = template.blocks[1].kind else {
+		panic!("Expected an if block");
+	};
+
+	assert_eq!(&content[if_block.head.1[0].span], "b\n");
+	assert_eq!(
+		&content[if_block.els.as_ref().expect("Expected an else block").1[0].span],
+		"c "
+	);
+
+	// markers are not part of the parsed expressions
+	let source = Source::anonymous("{{~VAR~}}");
+	assert!(Parser::new(source).parse().is_ok());
+
+	Ok(())
+}
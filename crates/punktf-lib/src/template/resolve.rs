@@ -3,14 +3,18 @@
 //! variable blocks and evaluation of if blocks.
 
 use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ops::Deref;
+use std::time::Instant;
 
-use color_eyre::eyre::Result;
-
-use super::block::{Block, BlockKind, If, IfExpr, Var, VarEnv};
+use super::block::{Block, BlockKind, If, IfExpr, Let, Var, VarEnv, VarEnvSet};
 use super::session::Session;
+use super::span::{ByteSpan, Pos};
 use super::Template;
-use crate::profile::variables::Vars;
+use crate::env::PunktfEnv;
+use crate::error::{Error, Result};
+use crate::profile::variables::{Value, Vars};
 use crate::template::diagnostic::{Diagnostic, DiagnosticBuilder, DiagnosticLevel};
 
 /// This macro resolves to the target architecture string of the compiling
@@ -90,6 +94,37 @@ macro_rules! family {
 	}};
 }
 
+/// The environment from which the value of a variable was taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VariableSource {
+	/// The value was defined by the system's environment.
+	Environment,
+
+	/// The value was defined in the profile.
+	Profile,
+
+	/// The value was defined for the specific dotfile.
+	Dotfile,
+
+	/// The value was bound by a `let` block of the template.
+	Let,
+}
+
+/// Records the usage of a variable while resolving a template.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VariableUsage {
+	/// Name of the variable.
+	pub name: String,
+
+	/// Environment from which the value was taken.
+	///
+	/// This is `None` if the variable could not be resolved.
+	pub source: Option<VariableSource>,
+
+	/// The resolved value of the variable.
+	pub value: Option<String>,
+}
+
 /// The resolver is responsible for evaluating and filling a
 /// [template](`super::Template`). During the filling all found errors are
 /// recorded in the [session](`super::session::Session`) and emitted after the
@@ -105,16 +140,42 @@ pub struct Resolver<'a, PV, DV> {
 	/// which corresponds to the template.
 	dotfile_vars: Option<&'a DV>,
 
+	/// Environments which are searched for variables which do not specify
+	/// any explicitly.
+	default_envs: VarEnvSet,
+
+	/// Variables which are looked up before the environment of the process.
+	env: Option<&'a PunktfEnv>,
+
 	/// Session where all errors/diagnostic which occur during the resolving
 	/// process are recorded to.
 	session: Session,
 
+	/// Stack of scopes which contain the values bound by `let` blocks.
+	///
+	/// The first scope spans the whole template, each taken branch of an `if`
+	/// block opens a new one which is dropped at the end of the branch.
+	scopes: Vec<HashMap<String, String>>,
+
+	/// All variables which were looked up during the resolve process, in the
+	/// order they were first used.
+	usages: RefCell<Vec<VariableUsage>>,
+
 	/// Flag that when it is set prevents a leading new line of a text block to
 	/// be emitted.
 	///
 	/// This is implemented to avoid extra empty lines which could be created
 	/// by either a `comment`, `escaped`, `print` or a not taken `if` block.
 	should_skip_next_newline: bool,
+
+	/// Point in time at which the resolve process started.
+	///
+	/// Used to enforce [`Limits::max_resolve_millis`](`super::limits::Limits::max_resolve_millis`).
+	started: Option<Instant>,
+
+	/// Flag which is set once any resource limit was exceeded. No further
+	/// blocks are processed afterwards.
+	exceeded: bool,
 }
 
 impl<'a, PV, DV> Resolver<'a, PV, DV>
@@ -133,31 +194,85 @@ where
 			template,
 			profile_vars,
 			dotfile_vars,
+			default_envs: VarEnvSet([Some(VarEnv::Dotfile), Some(VarEnv::Profile), None]),
+			env: None,
 			session: Session::new(),
+			scopes: Vec::new(),
+			usages: RefCell::new(Vec::new()),
 			should_skip_next_newline: false,
+			started: None,
+			exceeded: false,
 		}
 	}
 
+	/// Sets the environments which are searched for variables which do not
+	/// specify any explicitly (e.g. `{{NAME}}` instead of `{{$NAME}}`).
+	///
+	/// Defaults to [`VarEnvSet::default`].
+	pub const fn with_default_envs(mut self, envs: VarEnvSet) -> Self {
+		self.default_envs = envs;
+		self
+	}
+
+	/// Sets the variables which are looked up before the environment of the
+	/// process when a variable is searched in [`VarEnv::Environment`].
+	pub const fn with_env(mut self, env: &'a PunktfEnv) -> Self {
+		self.env = Some(env);
+		self
+	}
+
 	/// Consumes the resolver and tries to resolve all blocks defined by the
 	/// template.
 	///
 	/// # Errors
 	///
 	/// An error is returned if a variable could not be resolved.
-	pub fn resolve(mut self) -> Result<String> {
+	pub fn resolve(self) -> Result<String> {
+		self.resolve_with_usage().map(|(output, _)| output)
+	}
+
+	/// Consumes the resolver and tries to resolve all blocks defined by the
+	/// template.
+	///
+	/// In addition to the output, all variables which were used during the
+	/// process are returned.
+	///
+	/// # Errors
+	///
+	/// An error is returned if a variable could not be resolved.
+	pub fn resolve_with_usage(mut self) -> Result<(String, Vec<VariableUsage>)> {
 		let mut output = String::new();
 
+		// Top-level scope spanning the whole template
+		self.scopes.push(HashMap::new());
+		self.started = Some(Instant::now());
+
 		for block in &self.template.blocks {
 			if let Err(builder) = self.process_block(&mut output, block) {
 				self.report_diagnostic(builder.build());
 			}
+
+			if self.exceeded {
+				break;
+			}
 		}
 
 		self.session.emit(&self.template.source);
 
-		let Resolver { session, .. } = self;
-
-		session.try_finish().map(|_| output)
+		let Resolver {
+			template,
+			session,
+			usages,
+			..
+		} = self;
+
+		session
+			.try_finish()
+			.map(|_| (output, usages.into_inner()))
+			.map_err(|messages| Error::TemplateResolve {
+				origin: template.source.origin().to_string(),
+				messages,
+			})
 	}
 
 	/// Adds a diagnostic to the session.
@@ -169,6 +284,30 @@ where
 		self.session.report(diagnostic);
 	}
 
+	/// Checks if the block at `span` starts a new line.
+	///
+	/// This is the case if it is in the first column or if its indentation was
+	/// removed with a `{{~` whitespace control marker. The span of such a block
+	/// starts at the second `{` of the opening.
+	fn starts_line(&self, span: &ByteSpan) -> bool {
+		if self.template.source.get_pos_location(span.low).column() == 0 {
+			return true;
+		}
+
+		let content = self.template.source.content();
+		let low = span.low().as_usize();
+
+		if !content[low..].starts_with("{~") {
+			return false;
+		}
+
+		let line_start = content[..low].rfind('\n').map_or(0, |idx| idx + 1);
+
+		content[line_start..low - 1]
+			.bytes()
+			.all(|b| b == b' ' || b == b'\t')
+	}
+
 	/// Processes a [block](`super::block::Block`) and appends the resolved
 	/// output to `output`.
 	///
@@ -215,8 +354,7 @@ where
 				// Should skip new line if started at the beginning of a line.
 				// As a `print` block has no final `content` is the above the
 				// only condition.
-				self.should_skip_next_newline =
-					self.template.source.get_pos_location(span.low).column() == 0;
+				self.should_skip_next_newline = self.starts_line(span);
 
 				// NOP
 			}
@@ -226,24 +364,42 @@ where
 				// Should skip new line if started at the beginning of a line.
 				// As a `print` block has no final `content` is the above the
 				// only condition.
-				self.should_skip_next_newline = content.is_empty()
-					&& self.template.source.get_pos_location(span.low).column() == 0;
+				self.should_skip_next_newline = content.is_empty() && self.starts_line(span);
 
 				output.push_str(content);
 			}
+			BlockKind::Literal(inner) => {
+				let content = strip_literal_margins(&self.template.source[inner]);
+
+				// Should skip new line if started at the beginning of a line.
+				// The content of a multi-line literal already ends with a new
+				// line.
+				self.should_skip_next_newline = self.starts_line(span);
+
+				output.push_str(&content);
+			}
 			BlockKind::Var(var) => {
 				self.should_skip_next_newline = false;
 
-				output.push_str(&self.resolve_var(var)?);
+				match self.resolve_var(var) {
+					Ok(value) => output.push_str(&value.as_str()),
+					// Undefined variables are copied as is in lax mode, as
+					// they likely belong to another template engine
+					Err(_) if self.template.mode.is_lax() => {
+						output.push_str(&self.template.source[span]);
+					}
+					Err(err) => return Err(err),
+				}
 			}
 			BlockKind::Print(inner) => {
 				// Should skip new line if started at the beginning of a line.
 				// As a `print` block has no final `content` is the above the
 				// only condition.
-				self.should_skip_next_newline =
-					self.template.source.get_pos_location(span.low).column() == 0;
+				let content = self.resolve_value_blocks(inner)?;
+
+				self.should_skip_next_newline = self.starts_line(span);
 
-				log::info!("Print: {}", &self.template.source[inner]);
+				log::info!("Print: {content}");
 			}
 			BlockKind::If(If {
 				head,
@@ -265,9 +421,7 @@ where
 				};
 
 				if matched {
-					for block in head_nested {
-						self.process_block(&mut if_output, block)?;
-					}
+					self.process_scoped_blocks(&mut if_output, head_nested)?;
 				} else {
 					let mut found_elif = false;
 					for (elif, elif_nested) in elifs {
@@ -282,9 +436,7 @@ where
 						if matched {
 							found_elif = true;
 
-							for block in elif_nested {
-								self.process_block(&mut if_output, block)?;
-							}
+							self.process_scoped_blocks(&mut if_output, elif_nested)?;
 
 							break;
 						}
@@ -292,9 +444,7 @@ where
 
 					if !found_elif {
 						if let Some((_, els_nested)) = els {
-							for block in els_nested {
-								self.process_block(&mut if_output, block)?;
-							}
+							self.process_scoped_blocks(&mut if_output, els_nested)?;
 						}
 					}
 				}
@@ -322,14 +472,105 @@ where
 
 				// Should skip new line if started at the beginning of a line
 				// and no new content was added.
-				self.should_skip_next_newline = if_output_prepared.is_empty()
-					&& self.template.source.get_pos_location(span.low).column() == 0;
+				self.should_skip_next_newline =
+					if_output_prepared.is_empty() && self.starts_line(span);
 
 				output.push_str(if_output_prepared);
 			}
+			BlockKind::Let(Let { name, value }) => {
+				let content = self.resolve_value_blocks(value)?;
+
+				// Should skip new line if started at the beginning of a line.
+				// As a `let` block has no final `content` is the above the
+				// only condition.
+				self.should_skip_next_newline = self.starts_line(span);
+
+				self.scopes
+					.last_mut()
+					.expect("Resolver to always have a scope")
+					.insert(self.template.source[name].to_string(), content);
+			}
 		};
 
-		Ok(())
+		self.check_limits(output, *span)
+	}
+
+	/// Checks that no resource limit was exceeded by processing the block at
+	/// `span`, which appended to `output`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the output grew too large or the resolve process
+	/// took too long.
+	fn check_limits(&mut self, output: &str, span: ByteSpan) -> Result<(), DiagnosticBuilder> {
+		let limits = &self.template.limits;
+
+		let builder = if limits.exceeds_output_size(output.len()) {
+			DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("maximum output size exceeded")
+				.description(format!(
+					"the resolved template must not be larger than {} bytes",
+					limits.max_output_size
+				))
+		} else if let Some(max) = limits
+			.max_resolve_time()
+			.filter(|max| self.started.is_some_and(|started| started.elapsed() > *max))
+		{
+			DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("maximum resolve time exceeded")
+				.description(format!(
+					"resolving the template must not take longer than {max:?}"
+				))
+		} else {
+			return Ok(());
+		};
+
+		self.exceeded = true;
+
+		Err(builder.primary_span(span))
+	}
+
+	/// Processes all `blocks` in a new scope. All values bound by `let` blocks
+	/// within are dropped afterwards.
+	///
+	/// # Errors
+	///
+	/// An error is returned if a variable could not be resolved.
+	fn process_scoped_blocks(
+		&mut self,
+		output: &mut String,
+		blocks: &[Block],
+	) -> Result<(), DiagnosticBuilder> {
+		self.scopes.push(HashMap::new());
+
+		let result = blocks
+			.iter()
+			.try_for_each(|block| self.process_block(output, block));
+
+		self.scopes.pop();
+
+		result
+	}
+
+	/// Resolves the text, escaped and variable `blocks` of a value (e.g. of a
+	/// `let` block) to a string.
+	///
+	/// # Errors
+	///
+	/// An error is returned if a variable could not be resolved.
+	fn resolve_value_blocks(&mut self, blocks: &[Block]) -> Result<String, DiagnosticBuilder> {
+		// The value is not part of the output, so it must not be affected by
+		// the new line handling of the surrounding blocks.
+		let should_skip_next_newline = std::mem::replace(&mut self.should_skip_next_newline, false);
+
+		let mut content = String::new();
+		let result = blocks
+			.iter()
+			.try_for_each(|block| self.process_block(&mut content, block));
+
+		self.should_skip_next_newline = should_skip_next_newline;
+
+		result.map(|()| content)
 	}
 
 	/// Tries to resolve an [if expression](`super::block::IfExpr`) and returns
@@ -338,14 +579,71 @@ where
 	/// # Errors
 	///
 	/// An error is returned if a variable could not be resolved.
-	fn resolve_if_expr(&self, expr: &IfExpr) -> Result<bool, DiagnosticBuilder> {
+	fn resolve_if_expr(&mut self, expr: &IfExpr) -> Result<bool, DiagnosticBuilder> {
 		match expr {
 			IfExpr::Compare { var, op, other } => {
-				let var = self.resolve_var(var)?;
-				Ok(op.eval(&var, &self.template.source[other]))
+				let other = self.resolve_value_blocks(other)?;
+				let value = self.resolve_var(var)?;
+
+				op.eval(&value, &other).map_err(|err| {
+					DiagnosticBuilder::new(DiagnosticLevel::Error)
+						.message(format!("failed to evaluate `{op}` comparison"))
+						.description(err)
+						.primary_span(var.name)
+				})
+			}
+			IfExpr::Exists { var } => {
+				Ok(self.resolve_var(var).is_ok_and(|value| value.is_truthy()))
 			}
-			IfExpr::Exists { var } => Ok(self.resolve_var(var).is_ok()),
-			IfExpr::NotExists { var } => Ok(self.resolve_var(var).is_err()),
+			IfExpr::NotExists { var } => {
+				Ok(!self.resolve_var(var).is_ok_and(|value| value.is_truthy()))
+			}
+		}
+	}
+
+	/// Tries to resolve a [variable](`super::block::Var`) and records the
+	/// usage of it (related: [`Resolver::lookup_var`]).
+	///
+	/// # Errors
+	///
+	/// An error is returned if the variable could not be resolved.
+	fn resolve_var(&self, var: &Var) -> Result<Cow<'_, Value>, DiagnosticBuilder> {
+		let name = &self.template.source[var.name];
+		let result = self.lookup_var(var);
+
+		let usage = match &result {
+			Ok((value, source)) => VariableUsage {
+				name: name.to_string(),
+				source: Some(*source),
+				value: Some(value.to_string()),
+			},
+			Err(_) => VariableUsage {
+				name: name.to_string(),
+				source: None,
+				value: None,
+			},
+		};
+
+		let mut usages = self.usages.borrow_mut();
+		if !usages.contains(&usage) {
+			usages.push(usage);
+		}
+
+		if var.filters.is_empty() {
+			return result.map(|(value, _)| value);
+		}
+
+		let value = result.as_ref().ok().map(|(value, _)| value.to_string());
+
+		match var
+			.filters
+			.iter()
+			.fold(value, |value, filter| filter.value().apply(value))
+		{
+			Some(value) => Ok(Cow::Owned(Value::String(value))),
+			// Filters only provide a value for undefined variables, so this
+			// always returns the lookup error
+			None => result.map(|(value, _)| value),
 		}
 	}
 
@@ -353,44 +651,72 @@ where
 	/// value in [`Resolver::profile_vars`], [`Resolver::dotfile_vars`] and the
 	/// system environment.
 	///
+	/// Values bound by `let` blocks take precedence over all environments.
+	/// If the variable does not specify any environments, the
+	/// [default environments](`Resolver::with_default_envs`) are searched.
+	///
 	/// This function injects the following environment
 	/// variables if not present:
 	///
 	/// - `PUNKTF_TARGET_ARCH`: Architecture of the compiling system
 	/// - `PUNKTF_TARGET_OS`: Operating system of the compiling system
 	/// - `PUNKTF_TARGET_FAMILY`: Operating system family of the compiling system
+	/// - `PUNKTF_TARGET_PATHSEP`: Path separator of the compiling system
 	///
 	/// # Errors
 	///
 	/// An error is returned if the variable could not be resolved.
-	fn resolve_var(&self, var: &Var) -> Result<Cow<'_, str>, DiagnosticBuilder> {
+	fn lookup_var(&self, var: &Var) -> Result<(Cow<'_, Value>, VariableSource), DiagnosticBuilder> {
 		let name = &self.template.source[var.name];
 
-		for env in var.envs.envs() {
+		if let Some(value) = self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+			return Ok((Cow::Owned(value.as_str().into()), VariableSource::Let));
+		}
+
+		let envs = if var.envs.is_empty() {
+			&self.default_envs
+		} else {
+			&var.envs
+		};
+
+		for env in envs.envs() {
 			match env {
 				VarEnv::Environment => {
-					match (name, std::env::var(name)) {
-						("PUNKTF_TARGET_ARCH", Err(std::env::VarError::NotPresent)) => {
-							return Ok(arch!().into())
+					let value = match self.env {
+						Some(env) => env.var(name),
+						None => std::env::var(name).ok(),
+					};
+
+					match (name, value) {
+						("PUNKTF_TARGET_ARCH", None) => {
+							return Ok((Cow::Owned(arch!().into()), VariableSource::Environment))
+						}
+						("PUNKTF_TARGET_OS", None) => {
+							return Ok((Cow::Owned(os!().into()), VariableSource::Environment))
 						}
-						("PUNKTF_TARGET_OS", Err(std::env::VarError::NotPresent)) => {
-							return Ok(os!().into())
+						("PUNKTF_TARGET_FAMILY", None) => {
+							return Ok((Cow::Owned(family!().into()), VariableSource::Environment))
 						}
-						("PUNKTF_TARGET_FAMILY", Err(std::env::VarError::NotPresent)) => {
-							return Ok(family!().into())
+						("PUNKTF_TARGET_PATHSEP", None) => {
+							return Ok((
+								Cow::Owned(std::path::MAIN_SEPARATOR_STR.into()),
+								VariableSource::Environment,
+							))
 						}
-						(_, Ok(val)) => return Ok(Cow::Owned(val)),
-						(_, Err(_)) => continue,
+						(_, Some(val)) => {
+							return Ok((Cow::Owned(val.into()), VariableSource::Environment))
+						}
+						(_, None) => continue,
 					};
 				}
 				VarEnv::Profile => {
-					if let Some(Some(val)) = self.profile_vars.map(|vars| vars.var(name)) {
-						return Ok(val.into());
+					if let Some(Some(val)) = self.profile_vars.map(|vars| vars.value(name)) {
+						return Ok((val, VariableSource::Profile));
 					}
 				}
 				VarEnv::Dotfile => {
-					if let Some(Some(val)) = self.dotfile_vars.map(|vars| vars.var(name)) {
-						return Ok(val.into());
+					if let Some(Some(val)) = self.dotfile_vars.map(|vars| vars.value(name)) {
+						return Ok((val, VariableSource::Dotfile));
 					}
 				}
 			};
@@ -400,18 +726,47 @@ where
 			.message("failed to resolve variable")
 			.description(format!(
 				"no variable `{}` found in environments {}",
-				name, var.envs
+				name, envs
 			))
 			.primary_span(var.name))
 	}
 }
 
+/// Applies the emission rules of a literal block to its `content`.
+///
+/// - A line break directly after the opening `{{@literal}}` is removed.
+/// - Trailing whitespace in front of the closing `{{@endliteral}}` is removed
+///   if it is on a line of its own.
+/// - For each line which starts with a margin marker (`|`), optionally preceded
+///   by whitespace, everything up to and including the marker is removed.
+fn strip_literal_margins(content: &str) -> String {
+	let content = content
+		.strip_prefix("\r\n")
+		.or_else(|| content.strip_prefix('\n'))
+		.unwrap_or(content);
+
+	let content = match content.rfind('\n') {
+		Some(idx) if content[idx + 1..].trim().is_empty() => &content[..=idx],
+		_ => content,
+	};
+
+	content
+		.split_inclusive('\n')
+		.map(|line| {
+			line.trim_start_matches([' ', '\t'])
+				.strip_prefix('|')
+				.unwrap_or(line)
+		})
+		.collect()
+}
+
 #[cfg(test)]
 mod tests {
 	use pretty_assertions::assert_eq;
 
 	use super::*;
 	use crate::profile::variables::Variables;
+	use crate::template::limits::Limits;
 	use crate::template::source::Source;
 	use crate::template::Template;
 
@@ -607,8 +962,302 @@ World"#,
 			r#"Hello
 World"#
 		),
+		(
+			r#"{{@syntax 1}}
+Hello
+World"#,
+			r#"Hello
+World"#
+		),
+		(
+			r#"a
+	{{~@if {{DEMO_VAR}}}}
+b
+	{{~@fi}}
+	{{~@if {{OS}}}}
+d
+	{{~@fi}}
+c"#,
+			r#"a
+b
+c"#
+		),
+		(
+			r#"Hello  {{~DEMO_VAR~}}  World"#,
+			r#"HelloDEMOWorld"#
+		),
 	];
 
+	#[test]
+	fn let_scopes() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars = Variables::from_items([("FIRST", "John"), ("LAST", "Doe"), ("OS", "linux")]);
+
+		let content = r#"{{@let NAME = "{{FIRST}} {{LAST}}"}}
+Hello {{NAME}}
+{{@if {{OS}}}}
+{{@let NAME = "{{NAME}} on {{OS}}"}}
+{{NAME}}
+{{@fi}}
+{{@let FIRST = "Jane"}}
+{{NAME}} {{FIRST}}"#;
+
+		let source = Source::anonymous(content);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve::<Variables, Variables>(Some(&vars), None)?,
+			"Hello John Doe\nJohn Doe on linux\nJohn Doe Jane"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn nested_variables_in_literals() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars = Variables::from_items([
+			("HOST", "laptop"),
+			("PRIMARY_HOST", "laptop"),
+			("DOMAIN", "home"),
+		]);
+
+		let content = r#"{{@print Deploying to {{HOST}}}}
+{{@if {{HOST}} == "{{PRIMARY_HOST}}"}}
+primary
+{{@fi}}
+{{@if {{HOST}} != "{{PRIMARY_HOST}}.{{DOMAIN}}"}}
+short
+{{@fi}}
+{{@if {{HOST}} == "{{{ {{PRIMARY_HOST}} }}}"}}
+escaped
+{{@fi}}"#;
+
+		let source = Source::anonymous(content);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve::<Variables, Variables>(Some(&vars), None)?,
+			"primary\nshort\n"
+		);
+
+		// Missing variables on the right hand side are reported
+		let source = Source::anonymous(r#"{{@if {{HOST}} == "{{MISSING}}"}}{{@fi}}"#);
+		let template = Template::parse(source)?;
+		assert!(template
+			.resolve::<Variables, Variables>(Some(&vars), None)
+			.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn typed_values() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars: Variables = serde_yaml::from_str(
+			r#"
+ENABLE_X: false
+ENABLE_Y: true
+THREADS: 8
+PLUGINS: [git, docker]
+"#,
+		)?;
+
+		let content = r#"{{@if {{ENABLE_X}}}}x{{@fi}}
+{{@if {{ENABLE_Y}}}}y{{@fi}}
+{{@if !{{ENABLE_X}}}}not x{{@fi}}
+{{@if {{ENABLE_Y}} == "True"}}typed bool{{@fi}}
+{{@if {{THREADS}} == "8.0"}}{{THREADS}} threads{{@fi}}
+plugins=({{PLUGINS}})"#;
+
+		let source = Source::anonymous(content);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve::<Variables, Variables>(Some(&vars), None)?,
+			"y\nnot x\ntyped bool\n8 threads\nplugins=(git docker)"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn if_ordering() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars = Variables::from_items([
+			("NVIM_VERSION", "v0.10.1"),
+			("THREADS", "8"),
+			("NAME", "demo"),
+		]);
+
+		let content = r#"{{@if {{NVIM_VERSION}} >= "0.9"}}lua{{@else}}vim{{@fi}}
+{{@if {{NVIM_VERSION}} < "0.10.1-rc1"}}pre{{@fi}}
+{{@if {{THREADS}} > "{{NVIM_VERSION}}"}}more{{@fi}}
+{{@if {{THREADS}} <= "8"}}le{{@fi}}"#;
+
+		let source = Source::anonymous(content);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve::<Variables, Variables>(Some(&vars), None)?,
+			"lua\nmore\nle"
+		);
+
+		let source = Source::anonymous(r#"{{@if {{NAME}} > "1"}}x{{@fi}}"#);
+		let template = Template::parse(source)?;
+
+		assert!(template
+			.resolve::<Variables, Variables>(Some(&vars), None)
+			.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn variable_filters() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars =
+			Variables::from_items([("OS", "Linux"), ("CONFIG", " C:\\Users\\demo\\.config ")]);
+
+		let content = r#"{{OS | lower}}-{{OS | upper}}
+{{CONFIG | trim | replace('\\', '/')}}
+{{CONFIG | trim | replace('\\', '/') | basename}} in {{CONFIG | trim | replace('\\', '/') | dirname}}
+{{MISSING | default('fallback') | upper}}
+{{@if {{OS | lower}} == "linux"}}filtered if{{@fi}}"#;
+
+		let source = Source::anonymous(content);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve::<Variables, Variables>(Some(&vars), None)?,
+			"linux-LINUX\nC:/Users/demo/.config\n.config in C:/Users/demo\nFALLBACK\nfiltered if"
+		);
+
+		let source = Source::anonymous("{{MISSING | upper}}");
+		let template = Template::parse(source)?;
+		assert!(template
+			.resolve::<Variables, Variables>(Some(&vars), None)
+			.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn path_filters() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars = Variables::from_items([
+			("WIN", "C:\\Users\\demo/.config"),
+			("POSIX", "/home/demo/.config"),
+		]);
+
+		let content = "{{POSIX | winpath}}\n{{WIN | posixpath}}\n{{POSIX | nativepath}}\n{{WIN | \
+		               nativepath}}\n{{$PUNKTF_TARGET_PATHSEP}}";
+
+		let source = Source::anonymous(content);
+		let template = Template::parse(source)?;
+
+		cfg_if::cfg_if! {
+			if #[cfg(windows)] {
+				let native = "\\home\\demo\\.config\nC:\\Users\\demo\\.config\n\\";
+			} else {
+				let native = "/home/demo/.config\nC:/Users/demo/.config\n/";
+			}
+		}
+
+		assert_eq!(
+			template.resolve::<Variables, Variables>(Some(&vars), None)?,
+			format!("\\home\\demo\\.config\nC:/Users/demo/.config\n{native}")
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn literal_margins() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let content = "fn main() {
+{{@literal}}
+	|println!(\"{{}}\");
+	|	{{{ }}}
+	{{@endliteral}}
+}
+inline: {{@literal}}{{VAR}}{{@endliteral}}";
+
+		let source = Source::anonymous(content);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve::<Variables, Variables>(None, None)?,
+			"fn main() {\nprintln!(\"{{}}\");\n\t{{{ }}}\n}\ninline: {{VAR}}"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn explicit_env() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let mut env = PunktfEnv::new();
+		env.set("PUNKTF_TEST_EXPLICIT_ENV", "explicit");
+
+		let source = Source::anonymous("{{$PUNKTF_TEST_EXPLICIT_ENV}}");
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve_with_order::<Variables, Variables>(
+				None,
+				None,
+				VarEnvSet::default(),
+				&env
+			)?,
+			"explicit"
+		);
+		assert!(template
+			.resolve::<Variables, Variables>(None, None)
+			.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn default_envs_order() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let mut env = PunktfEnv::new();
+		env.set("PUNKTF_TEST_ORDER", "environment");
+		let profile_vars = Variables::from_items([("PUNKTF_TEST_ORDER", "profile")]);
+		let dotfile_vars = Variables::from_items([("PUNKTF_TEST_ORDER", "dotfile")]);
+
+		let source = Source::anonymous("{{PUNKTF_TEST_ORDER}} {{#PUNKTF_TEST_ORDER}}");
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve(Some(&profile_vars), Some(&dotfile_vars))?,
+			"dotfile profile"
+		);
+
+		let order = VarEnvSet([
+			Some(VarEnv::Environment),
+			Some(VarEnv::Profile),
+			Some(VarEnv::Dotfile),
+		]);
+		assert_eq!(
+			template.resolve_with_order(Some(&profile_vars), Some(&dotfile_vars), order, &env)?,
+			"environment profile"
+		);
+
+		Ok(())
+	}
+
 	#[test]
 	fn if_fmt() -> Result<()> {
 		crate::tests::setup_test_env();
@@ -629,4 +1278,57 @@ World"#
 
 		Ok(())
 	}
+
+	#[test]
+	fn template_limits() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars = Variables::from_items([("OS", "linux"), ("LONG", "0123456789")]);
+
+		let nested = format!(
+			"{}linux{}",
+			"{{@if {{OS}}}}".repeat(10_000),
+			"{{@fi}}".repeat(10_000)
+		);
+		assert!(Template::parse(Source::anonymous(&nested)).is_err());
+
+		let limits = Limits {
+			max_depth: 3,
+			..Limits::new()
+		};
+		let nested = "{{@if {{OS}}}}{{@if {{OS}}}}{{@if {{OS}}}}{{OS}}{{@fi}}{{@fi}}{{@fi}}";
+		let template = Template::parse_with_limits(Source::anonymous(nested), limits)?;
+		assert_eq!(
+			template.resolve::<Variables, Variables>(Some(&vars), None)?,
+			"linux"
+		);
+
+		let nested = format!("{{{{@if {{{{OS}}}}}}}}{nested}{{{{@fi}}}}");
+		assert!(Template::parse_with_limits(Source::anonymous(&nested), limits).is_err());
+
+		let limits = Limits {
+			max_output_size: 25,
+			..Limits::new()
+		};
+		let template = Template::parse_with_limits(Source::anonymous("{{LONG}}{{LONG}}"), limits)?;
+		assert!(template
+			.resolve::<Variables, Variables>(Some(&vars), None)
+			.is_ok());
+
+		let template =
+			Template::parse_with_limits(Source::anonymous("{{LONG}}{{LONG}}{{LONG}}"), limits)?;
+		assert!(template
+			.resolve::<Variables, Variables>(Some(&vars), None)
+			.is_err());
+
+		let template = Template::parse_with_limits(
+			Source::anonymous("{{LONG}}{{LONG}}{{LONG}}"),
+			Limits::unlimited(),
+		)?;
+		assert!(template
+			.resolve::<Variables, Variables>(Some(&vars), None)
+			.is_ok());
+
+		Ok(())
+	}
 }
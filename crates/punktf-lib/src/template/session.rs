@@ -2,9 +2,7 @@
 //! a specific task and a specific [source](`super::source::Source`). It is
 //! used to bundle the diagnostics and emit them after the task has finished.
 
-use color_eyre::eyre::{eyre, Result};
-
-use super::diagnostic::Diagnostic;
+use super::diagnostic::{Diagnostic, DiagnosticLevel};
 use super::source::Source;
 
 /// A session collects [diagnostics](`super::diagnostic::Diagnostic`) for a
@@ -33,7 +31,7 @@ impl Session {
 	}
 
 	/// Mark the session as failed.
-	pub fn mark_failed(&mut self) {
+	pub const fn mark_failed(&mut self) {
 		self.failed = true;
 	}
 
@@ -51,10 +49,16 @@ impl Session {
 	///
 	/// # Errors
 	///
-	/// Returns an error if the session is marked as `failed`.
-	pub fn try_finish(self) -> Result<()> {
+	/// Returns the messages of all error diagnostics if the session is marked
+	/// as `failed`.
+	pub fn try_finish(self) -> Result<(), Vec<String>> {
 		if self.failed {
-			Err(eyre!("Session contains errors"))
+			Err(self
+				.diagnostics
+				.iter()
+				.filter(|diagnostic| diagnostic.level() == &DiagnosticLevel::Error)
+				.map(Diagnostic::summary)
+				.collect())
 		} else {
 			Ok(())
 		}
@@ -0,0 +1,43 @@
+//! Versions of the [template](`super::Template`) syntax.
+//!
+//! A template can pin the syntax it is written in with a `{{@syntax N}}`
+//! pragma as its first block. This allows the syntax to evolve (e.g. new
+//! blocks or delimiters) while existing templates keep being parsed the way
+//! they were written, so a repository can migrate one file at a time.
+//! Templates without a pragma use [`Syntax::V1`].
+
+use std::fmt;
+
+/// A version of the template syntax.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Syntax {
+	/// The original syntax.
+	#[default]
+	V1,
+}
+
+impl Syntax {
+	/// All syntax versions supported by this version of `punktf`.
+	pub const SUPPORTED: &'static [Self] = &[Self::V1];
+
+	/// Returns the syntax for the given version number, if it is supported.
+	pub const fn from_version(version: u32) -> Option<Self> {
+		match version {
+			1 => Some(Self::V1),
+			_ => None,
+		}
+	}
+
+	/// Returns the version number of this syntax.
+	pub const fn version(&self) -> u32 {
+		match self {
+			Self::V1 => 1,
+		}
+	}
+}
+
+impl fmt::Display for Syntax {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.version())
+	}
+}
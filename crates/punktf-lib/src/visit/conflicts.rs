@@ -0,0 +1,342 @@
+//! A [`Visitor`](`crate::visit::Visitor`) implementation which finds target
+//! paths claimed by more than one item of a profile.
+//!
+//! Nothing is written while searching. For each conflicting target path the
+//! deployment rules (priority, merge mode and link replacement) are evaluated
+//! the same way an [`Explainer`](`crate::visit::explain::Explainer`) does to
+//! determine which item wins. Directories are never reported, as their
+//! contents are merged.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::profile::source::PunktfSource;
+use crate::profile::{LayeredProfile, MergeMode, Priority};
+use crate::visit::canonicalize_parent;
+use crate::visit::explain::{evaluate_outcomes, Candidate, Outcome};
+use crate::visit::*;
+
+/// A symlink of the profile which claims a target path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkClaim {
+	/// Absolute source path of the link.
+	pub source_path: PathBuf,
+
+	/// Whether the link replaces an existing symlink at the target path.
+	pub replace: bool,
+
+	/// The priority of the link.
+	pub priority: Option<Priority>,
+
+	/// Merge operation if the target path exists and can not be replaced.
+	pub merge: Option<MergeMode>,
+
+	/// The outcome of the link.
+	pub outcome: Outcome,
+}
+
+/// The item which wins a [`Conflict`].
+#[derive(Debug, Clone, Copy)]
+pub enum Winner<'a> {
+	/// A file of a dotfile wins.
+	Dotfile(&'a Candidate),
+
+	/// A symlink wins.
+	Link(&'a LinkClaim),
+}
+
+/// A target path which is claimed by more than one item.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+	/// The conflicting target path.
+	pub target_path: PathBuf,
+
+	/// Whether the target path existed before the deployment.
+	pub target_exists: bool,
+
+	/// All files which claim the target path in the order a deployment would
+	/// process them.
+	pub candidates: Vec<Candidate>,
+
+	/// All symlinks which claim the target path in the order a deployment
+	/// would process them.
+	///
+	/// Symlinks are always processed after all files.
+	pub links: Vec<LinkClaim>,
+}
+
+impl Conflict {
+	/// Returns the number of items which claim the target path.
+	pub const fn claims(&self) -> usize {
+		self.candidates.len() + self.links.len()
+	}
+
+	/// Returns the item which ends up at the target path.
+	pub fn winner(&self) -> Option<Winner<'_>> {
+		self.links
+			.iter()
+			.rev()
+			.find(|link| link.outcome == Outcome::Deployed)
+			.map(Winner::Link)
+			.or_else(|| {
+				self.candidates
+					.iter()
+					.find(|candidate| candidate.outcome == Outcome::Deployed)
+					.map(Winner::Dotfile)
+			})
+	}
+}
+
+/// Evaluates which of the `links` would be deployed.
+///
+/// A link is skipped if an already deployed item has a higher priority. It
+/// only replaces an already deployed item if it is a symlink and
+/// [`Symlink::replace`](`crate::visit::Symlink::replace`) is set, or if the
+/// merge mode of the link allows it.
+fn evaluate_link_outcomes(conflict: &mut Conflict) {
+	let deployed_file = conflict
+		.candidates
+		.iter()
+		.find(|candidate| candidate.outcome == Outcome::Deployed);
+
+	let mut current: Option<usize> = None;
+
+	for idx in 0..conflict.links.len() {
+		let link = &conflict.links[idx];
+
+		let deployed_priority = match current {
+			Some(current_idx) => conflict.links[current_idx].priority,
+			None => deployed_file.and_then(|candidate| candidate.dotfile.priority),
+		};
+
+		if let (Some(a), Some(b)) = (link.priority, deployed_priority) {
+			if b > a {
+				conflict.links[idx].outcome = Outcome::Lost {
+					reason: format!(
+						"Item with higher priority ({} > {}) is already deployed",
+						b.0, a.0
+					),
+				};
+				continue;
+			}
+		}
+
+		let exists = current.is_some() || deployed_file.is_some() || conflict.target_exists;
+
+		if exists {
+			let reason = if link.replace && (current.is_some() || deployed_file.is_none()) {
+				None
+			} else {
+				match link.merge {
+					None if !link.replace => Some(String::from(
+						"Target already exists and `replace` is not set",
+					)),
+					None => Some(String::from(
+						"Target is a deployed file which is never replaced by a link",
+					)),
+					Some(mode @ (MergeMode::Keep | MergeMode::Merge)) => {
+						Some(format!("Target already exists and merge mode is {mode:?}"))
+					}
+					Some(MergeMode::Ask) => {
+						log::info!(
+							"[{}] Outcome depends on the answer to the merge question",
+							link.source_path.display()
+						);
+						None
+					}
+					Some(MergeMode::Overwrite) => None,
+				}
+			};
+
+			if let Some(reason) = reason {
+				conflict.links[idx].outcome = Outcome::Lost { reason };
+				continue;
+			}
+		}
+
+		if let Some(current_idx) = current {
+			conflict.links[current_idx].outcome = Outcome::Overwritten { by: idx };
+		}
+
+		current = Some(idx);
+	}
+}
+
+/// A [`Visitor`](`crate::visit::Visitor`) implementation which collects all
+/// items of a profile by their target path.
+#[derive(Debug, Default, Clone)]
+pub struct ConflictFinder {
+	/// All collected files and links by their (normalized) target path.
+	claims: BTreeMap<PathBuf, (Vec<Candidate>, Vec<LinkClaim>)>,
+}
+
+impl ConflictFinder {
+	/// Creates a new instance.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Runs the finder to completion for a given profile.
+	///
+	/// Returns all conflicting target paths sorted by path.
+	pub fn find(mut self, source: &PunktfSource, profile: &mut LayeredProfile) -> Vec<Conflict> {
+		let walker = Walker::new(profile);
+
+		if let Err(err) = walker.walk(source, &mut self) {
+			log::error!("Failed to search for conflicts: {err}");
+		}
+
+		self.claims
+			.into_iter()
+			.filter(|(_, (candidates, links))| candidates.len() + links.len() > 1)
+			.map(|(target_path, (mut candidates, links))| {
+				let target_exists = target_path.symlink_metadata().is_ok();
+				evaluate_outcomes(&mut candidates, target_exists);
+
+				let mut conflict = Conflict {
+					target_path,
+					target_exists,
+					candidates,
+					links,
+				};
+				evaluate_link_outcomes(&mut conflict);
+
+				conflict
+			})
+			.collect()
+	}
+}
+
+impl Visitor for ConflictFinder {
+	/// Accepts a file item and records its target path.
+	fn accept_file<'a>(
+		&mut self,
+		_: &PunktfSource,
+		profile: &LayeredProfile,
+		file: &File<'a>,
+	) -> Result {
+		self.claims
+			.entry(canonicalize_parent(&file.target_path))
+			.or_default()
+			.0
+			.push(Candidate::new(profile, file, false, Outcome::Deployed));
+
+		Ok(())
+	}
+
+	/// Accepts a directory item and ignores it, as directories are merged.
+	fn accept_directory<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Directory<'a>,
+	) -> Result {
+		Ok(())
+	}
+
+	/// Accepts a link item and records its target path.
+	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, link: &Symlink) -> Result {
+		self.claims
+			.entry(canonicalize_parent(&link.target_path))
+			.or_default()
+			.1
+			.push(LinkClaim {
+				source_path: link.source_path.clone(),
+				replace: link.replace,
+				priority: link.priority,
+				merge: link.merge,
+				outcome: Outcome::Deployed,
+			});
+
+		Ok(())
+	}
+
+	/// Accepts a rejected item and ignores it, as it is never deployed.
+	fn accept_rejected<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Rejected<'a>,
+	) -> Result {
+		Ok(())
+	}
+
+	/// Accepts an errored item and ignores it, as it is never deployed.
+	fn accept_errored<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Errored<'a>,
+	) -> Result {
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Creates a link claim.
+	fn link(path: &str, replace: bool) -> LinkClaim {
+		LinkClaim {
+			source_path: PathBuf::from(path),
+			replace,
+			priority: None,
+			merge: None,
+			outcome: Outcome::Deployed,
+		}
+	}
+
+	#[test]
+	fn link_outcomes() {
+		crate::tests::setup_test_env();
+
+		let mut conflict = Conflict {
+			target_path: PathBuf::from("/target"),
+			target_exists: false,
+			candidates: Vec::new(),
+			links: vec![link("a", false), link("b", false), link("c", true)],
+		};
+
+		evaluate_link_outcomes(&mut conflict);
+
+		assert_eq!(conflict.links[0].outcome, Outcome::Overwritten { by: 2 });
+		assert!(matches!(conflict.links[1].outcome, Outcome::Lost { .. }));
+		assert_eq!(conflict.links[2].outcome, Outcome::Deployed);
+		assert!(
+			matches!(conflict.winner(), Some(Winner::Link(link)) if link.source_path == Path::new("c"))
+		);
+	}
+
+	#[test]
+	fn link_outcomes_priority_and_merge() {
+		crate::tests::setup_test_env();
+
+		let mut conflict = Conflict {
+			target_path: PathBuf::from("/target"),
+			target_exists: true,
+			candidates: Vec::new(),
+			links: vec![
+				LinkClaim {
+					priority: Some(Priority::new(2)),
+					merge: Some(MergeMode::Overwrite),
+					..link("a", false)
+				},
+				LinkClaim {
+					priority: Some(Priority::new(1)),
+					..link("b", true)
+				},
+				LinkClaim {
+					merge: Some(MergeMode::Keep),
+					..link("c", false)
+				},
+			],
+		};
+
+		evaluate_link_outcomes(&mut conflict);
+
+		assert_eq!(conflict.links[0].outcome, Outcome::Deployed);
+		assert!(matches!(conflict.links[1].outcome, Outcome::Lost { .. }));
+		assert!(matches!(conflict.links[2].outcome, Outcome::Lost { .. }));
+	}
+}
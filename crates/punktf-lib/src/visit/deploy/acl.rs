@@ -0,0 +1,71 @@
+//! Windows specific handling of the access control lists (ACL) of deployed
+//! files.
+//!
+//! Files which are written into protected directories can end up with
+//! unexpected ACLs. The operations defined by
+//! [`WindowsAcl`](`crate::profile::WindowsAcl`) are used to fix them up after
+//! the file was written.
+
+use std::io;
+use std::path::Path;
+
+use cfg_if::cfg_if;
+
+use crate::profile::WindowsAcl;
+
+/// Applies the ACL operation `acl` to the file at `path`.
+///
+/// On windows `icacls` is used to re-apply inherited ACLs and `powershell` to
+/// set a security descriptor in the SDDL format. On all other platforms this
+/// is a no-op.
+///
+/// # Errors
+///
+/// An error is returned if the command could not be executed or exited
+/// unsuccessfully.
+pub fn apply(path: &Path, acl: &WindowsAcl) -> io::Result<()> {
+	cfg_if! {
+		if #[cfg(windows)] {
+			use std::process::Command;
+
+			let output = match acl {
+				WindowsAcl::Inherit => Command::new("icacls")
+					.arg(path)
+					.args(["/reset", "/Q"])
+					.output()?,
+				// Path and descriptor are passed as environment variables to
+				// avoid any quoting issues.
+				WindowsAcl::Sddl(sddl) => Command::new("powershell")
+					.args([
+						"-NoProfile",
+						"-NonInteractive",
+						"-Command",
+						"$acl = Get-Acl -LiteralPath $env:PUNKTF_ACL_PATH; \
+						 $acl.SetSecurityDescriptorSddlForm($env:PUNKTF_ACL_SDDL); \
+						 Set-Acl -LiteralPath $env:PUNKTF_ACL_PATH -AclObject $acl",
+					])
+					.env("PUNKTF_ACL_PATH", path)
+					.env("PUNKTF_ACL_SDDL", sddl)
+					.output()?,
+			};
+
+			if output.status.success() {
+				Ok(())
+			} else {
+				Err(io::Error::other(format!(
+					"Process failed with status `{}`: {}",
+					output.status,
+					String::from_utf8_lossy(&output.stderr).trim()
+				)))
+			}
+		} else {
+			log::debug!(
+				"[{}] Ignoring ACL operation `{:?}` on non-windows system",
+				path.display(),
+				acl
+			);
+
+			Ok(())
+		}
+	}
+}
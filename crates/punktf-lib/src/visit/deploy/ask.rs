@@ -0,0 +1,123 @@
+//! Decisions about existing targets of items with
+//! [`MergeMode::Ask`](`crate::profile::MergeMode::Ask`).
+//!
+//! The [`Deployer`](`super::Deployer`) asks a [`MergeStrategy`] what to do
+//! whenever such an item would overwrite an existing target. The strategy
+//! receives the full context of the item as [`MergeRequest`], which allows
+//! frontends (e.g. the command line or a graphical interface) to show rich
+//! prompts.
+//!
+//! For simple cases a closure `Fn(&Path, &Path) -> Result<bool, BoxError>`
+//! which receives the source and target path can be used as strategy, where
+//! `true` overwrites the target.
+
+use std::fs::File;
+use std::io::{self, Read as _};
+use std::path::Path;
+
+use crate::error::BoxError;
+use crate::profile::dotfile::Dotfile;
+use crate::profile::Priority;
+
+/// Maximum number of bytes read for a preview of the contents of an item
+/// (related: [`MergeRequest::new_preview`]).
+pub const PREVIEW_LEN: u64 = 4096;
+
+/// The decision of a [`MergeStrategy`] about an existing target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergeDecision {
+	/// Overwrites the existing target.
+	Overwrite,
+
+	/// Keeps the existing target. The item is reported as conflict.
+	Keep,
+
+	/// Overwrites the existing target and all following targets of items
+	/// with [`MergeMode::Ask`](`crate::profile::MergeMode::Ask`) without
+	/// asking again.
+	OverwriteAll,
+
+	/// Keeps the existing target and all following targets of items with
+	/// [`MergeMode::Ask`](`crate::profile::MergeMode::Ask`) without asking
+	/// again. The items are reported as conflicts.
+	KeepAll,
+
+	/// Aborts the deployment. The item is marked as failed and all following
+	/// items are skipped.
+	Abort,
+}
+
+/// The context of an item which would overwrite an existing target.
+#[derive(Debug, Clone, Copy)]
+pub struct MergeRequest<'a> {
+	/// Absolute path of the source of the item.
+	pub source_path: &'a Path,
+
+	/// Absolute path of the existing target.
+	pub target_path: &'a Path,
+
+	/// The dotfile the item belongs to. This is `None` for links.
+	pub dotfile: Option<&'a Dotfile>,
+
+	/// Priority of the item.
+	pub priority: Option<Priority>,
+
+	/// Priority of the item which was already deployed to the target during
+	/// this deployment, if any.
+	pub existing_priority: Option<Priority>,
+}
+
+impl MergeRequest<'_> {
+	/// Returns the start (at most [`PREVIEW_LEN`] bytes) of the new contents.
+	///
+	/// For templates these are the contents before the template is resolved.
+	/// Invalid UTF-8 sequences are replaced.
+	pub fn new_preview(&self) -> io::Result<String> {
+		preview(self.source_path)
+	}
+
+	/// Returns the start (at most [`PREVIEW_LEN`] bytes) of the contents of
+	/// the existing target.
+	///
+	/// Invalid UTF-8 sequences are replaced.
+	pub fn old_preview(&self) -> io::Result<String> {
+		preview(self.target_path)
+	}
+}
+
+/// Reads the first [`PREVIEW_LEN`] bytes of the file at `path`.
+fn preview(path: &Path) -> io::Result<String> {
+	let mut content = Vec::new();
+	File::open(path)?
+		.take(PREVIEW_LEN)
+		.read_to_end(&mut content)?;
+
+	Ok(String::from_utf8_lossy(&content).into_owned())
+}
+
+/// Decides what to do with existing targets of items with
+/// [`MergeMode::Ask`](`crate::profile::MergeMode::Ask`).
+pub trait MergeStrategy {
+	/// Decides what to do with the existing target of the item described by
+	/// `request`.
+	///
+	/// # Errors
+	///
+	/// If an error is returned, the item is marked as failed.
+	fn decide(&mut self, request: &MergeRequest<'_>) -> Result<MergeDecision, BoxError>;
+}
+
+impl<F> MergeStrategy for F
+where
+	F: FnMut(&Path, &Path) -> Result<bool, BoxError>,
+{
+	fn decide(&mut self, request: &MergeRequest<'_>) -> Result<MergeDecision, BoxError> {
+		let overwrite = self(request.source_path, request.target_path)?;
+
+		Ok(if overwrite {
+			MergeDecision::Overwrite
+		} else {
+			MergeDecision::Keep
+		})
+	}
+}
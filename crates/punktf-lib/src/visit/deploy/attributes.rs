@@ -0,0 +1,191 @@
+//! Preservation of the attributes of already existing target files.
+//!
+//! If [`Dotfile::preserve_attributes`](`crate::profile::dotfile::Dotfile::preserve_attributes`)
+//! is set, the attributes of a target file are saved before it is overwritten
+//! and restored afterwards. Without this, a deployment resets them (e.g. the
+//! execute bit of a script which was `chmod +x`'ed on the target).
+//!
+//! The permission bits are supported on all platforms. Ownership and extended
+//! attributes are only supported on unix systems.
+//!
+//! Read-only targets can temporarily be made writable with [`Writable`]
+//! (related: [`DeployOptions::force`](`super::DeployOptions::force`)).
+
+use std::fs::Permissions;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use cfg_if::cfg_if;
+
+/// Saved attributes of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attributes {
+	/// Permission bits of the file.
+	permissions: Permissions,
+
+	/// User and group id of the owner of the file.
+	#[cfg(unix)]
+	owner: (u32, u32),
+
+	/// Names and values of all extended attributes of the file.
+	#[cfg(unix)]
+	xattrs: Vec<(std::ffi::OsString, Vec<u8>)>,
+}
+
+impl Attributes {
+	/// Reads the attributes of the file at `path`.
+	///
+	/// If the filesystem does not support extended attributes, none are saved.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the metadata or the extended attributes of the
+	/// file could not be read.
+	pub fn read(path: &Path) -> io::Result<Self> {
+		let metadata = std::fs::metadata(path)?;
+
+		cfg_if! {
+			if #[cfg(unix)] {
+				use std::os::unix::fs::MetadataExt;
+
+				let mut xattrs = Vec::new();
+
+				match xattr::list(path) {
+					Ok(names) => {
+						for name in names {
+							if let Some(value) = xattr::get(path, &name)? {
+								xattrs.push((name, value));
+							}
+						}
+					}
+					Err(err) if err.kind() == io::ErrorKind::Unsupported => {}
+					Err(err) => return Err(err),
+				}
+
+				Ok(Self {
+					permissions: metadata.permissions(),
+					owner: (metadata.uid(), metadata.gid()),
+					xattrs,
+				})
+			} else {
+				Ok(Self {
+					permissions: metadata.permissions(),
+				})
+			}
+		}
+	}
+
+	/// Applies the saved attributes to the file at `path`.
+	///
+	/// The ownership is only changed if it differs from the saved one, as
+	/// this usually requires elevated privileges.
+	///
+	/// # Errors
+	///
+	/// An error is returned if any of the attributes could not be applied.
+	pub fn apply(&self, path: &Path) -> io::Result<()> {
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::MetadataExt;
+
+			let metadata = std::fs::metadata(path)?;
+			let (uid, gid) = self.owner;
+
+			// Needs to happen before the permissions are set, as a change of
+			// the owner can clear the setuid/setgid bits.
+			if (metadata.uid(), metadata.gid()) != self.owner {
+				std::os::unix::fs::chown(path, Some(uid), Some(gid))?;
+			}
+
+			for (name, value) in &self.xattrs {
+				xattr::set(path, name, value)?;
+			}
+		}
+
+		std::fs::set_permissions(path, self.permissions.clone())
+	}
+}
+
+/// Keeps a read-only file writable until it is dropped or
+/// [restored](`Writable::restore`).
+///
+/// On unix systems the write permission of the owner is added, on windows the
+/// read-only attribute is cleared. The original permissions are restored
+/// afterwards.
+#[derive(Debug)]
+pub struct Writable {
+	/// Path of the file.
+	path: PathBuf,
+
+	/// Original permissions of the file. `None` once they were restored.
+	permissions: Option<Permissions>,
+}
+
+impl Writable {
+	/// Makes the file at `path` writable if it is read-only.
+	///
+	/// Returns `None` if the file does not exist or is already writable.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the permissions of the file could not be read
+	/// or changed.
+	pub fn acquire(path: &Path) -> io::Result<Option<Self>> {
+		let permissions = match std::fs::metadata(path) {
+			Ok(metadata) if metadata.is_file() => metadata.permissions(),
+			Ok(_) => return Ok(None),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+			Err(err) => return Err(err),
+		};
+
+		if !permissions.readonly() {
+			return Ok(None);
+		}
+
+		let mut writable = permissions.clone();
+
+		cfg_if! {
+			if #[cfg(unix)] {
+				use std::os::unix::fs::PermissionsExt;
+
+				writable.set_mode(writable.mode() | 0o200);
+			} else {
+				// Only clears the read-only attribute on windows
+				#[allow(clippy::permissions_set_readonly_false)]
+				writable.set_readonly(false);
+			}
+		}
+
+		std::fs::set_permissions(path, writable)?;
+
+		Ok(Some(Self {
+			path: path.to_path_buf(),
+			permissions: Some(permissions),
+		}))
+	}
+
+	/// Restores the original permissions of the file.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the permissions could not be changed.
+	pub fn restore(mut self) -> io::Result<()> {
+		match self.permissions.take() {
+			Some(permissions) => std::fs::set_permissions(&self.path, permissions),
+			None => Ok(()),
+		}
+	}
+}
+
+impl Drop for Writable {
+	fn drop(&mut self) {
+		if let Some(permissions) = self.permissions.take() {
+			if let Err(err) = std::fs::set_permissions(&self.path, permissions) {
+				log::warn!(
+					"Failed to restore permissions of {}: {err}",
+					self.path.display()
+				);
+			}
+		}
+	}
+}
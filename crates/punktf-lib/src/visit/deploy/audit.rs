@@ -0,0 +1,396 @@
+//! Exportable record of the state a deployment left on the target.
+//!
+//! Unlike the [`Manifest`](`super::manifest::Manifest`), which only tracks
+//! target paths to find orphans, an [`AuditManifest`] records for every
+//! deployed item its kind, where it came from (source path and profile) and a
+//! hash of its content. It can be stored (e.g. next to a package) and later be
+//! used to check if a machine still matches that state
+//! (related: [`AuditManifest::verify`]).
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::profile::LayeredProfile;
+use crate::visit::deploy::deployment::{DeployedDotfileKind, Deployment};
+
+/// Version of the format of [`AuditManifest`]. It is increased on every
+/// incompatible change.
+pub const AUDIT_MANIFEST_VERSION: u32 = 1;
+
+/// Kind of an item recorded in an [`AuditManifest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditItemKind {
+	/// A regular file.
+	File,
+
+	/// A directory.
+	Directory,
+
+	/// A symlink.
+	Symlink,
+}
+
+impl fmt::Display for AuditItemKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::File => "file",
+			Self::Directory => "directory",
+			Self::Symlink => "symlink",
+		})
+	}
+}
+
+/// A single deployed item of an [`AuditManifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditItem {
+	/// Kind of the item on the target.
+	pub kind: AuditItemKind,
+
+	/// Path from which the item was deployed.
+	///
+	/// For files and directories this is relative to the `dotfiles`
+	/// directory of the source, for symlinks it is the path the link points
+	/// to.
+	pub source: PathBuf,
+
+	/// Name of the profile of the extend chain which defined the item.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub profile: Option<String>,
+
+	/// Hex encoded SHA-256 hash of the content of files.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub sha256: Option<String>,
+}
+
+/// Record of all items a deployment left on the target (related:
+/// [module](`self`)).
+///
+/// Items are ordered by their target path, so that the serialized form is
+/// stable and can be compared with plain text tools.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditManifest {
+	/// Version of the format (related: [`AUDIT_MANIFEST_VERSION`]).
+	pub version: u32,
+
+	/// Name of the deployed profile.
+	pub profile: String,
+
+	/// All successfully deployed items keyed by their target path.
+	pub items: BTreeMap<PathBuf, AuditItem>,
+}
+
+impl AuditManifest {
+	/// Creates a manifest for all items which were successfully deployed by
+	/// `deployment`.
+	///
+	/// The content of files is read back from the target, so this must be
+	/// called after the deployment actually wrote to the target (i.e. not for
+	/// dry runs).
+	///
+	/// # Errors
+	///
+	/// An error is returned if a deployed file can not be read.
+	pub fn from_deployment(
+		profile_name: &str,
+		profile: &LayeredProfile,
+		deployment: &Deployment,
+	) -> io::Result<Self> {
+		let mut items = BTreeMap::new();
+
+		for (target, deployed) in deployment.dotfiles() {
+			if !deployed.status().is_success() {
+				continue;
+			}
+
+			let (dotfile, source) = match deployed.kind() {
+				DeployedDotfileKind::Dotfile(dotfile) => (dotfile, dotfile.path.clone()),
+				DeployedDotfileKind::Child(parent) => {
+					let Some(DeployedDotfileKind::Dotfile(dotfile)) = deployment
+						.dotfiles()
+						.get(parent)
+						.map(|parent| parent.kind())
+					else {
+						continue;
+					};
+
+					let relative = target.strip_prefix(parent).unwrap_or(target);
+					(dotfile, dotfile.path.join(relative))
+				}
+			};
+
+			let provenance = profile
+				.dotfiles
+				.iter()
+				.find(|(_, candidate)| candidate == dotfile)
+				.map(|(idx, _)| profile.profile_names[*idx].clone());
+
+			// Dotfiles deployed as links (e.g. with the `Symlink` strategy)
+			// are recorded with the path the link points to.
+			let metadata = target.symlink_metadata()?;
+			let item = if metadata.file_type().is_symlink() {
+				AuditItem {
+					kind: AuditItemKind::Symlink,
+					source: std::fs::read_link(target)?,
+					profile: provenance,
+					sha256: None,
+				}
+			} else if metadata.is_dir() {
+				AuditItem {
+					kind: AuditItemKind::Directory,
+					source,
+					profile: provenance,
+					sha256: None,
+				}
+			} else {
+				AuditItem {
+					kind: AuditItemKind::File,
+					source,
+					profile: provenance,
+					sha256: Some(hash_file(target)?),
+				}
+			};
+
+			items.insert(target.clone(), item);
+		}
+
+		for (target, deployed) in deployment.symlinks() {
+			if !deployed.status().is_success() {
+				continue;
+			}
+
+			let provenance = profile
+				.symlinks
+				.iter()
+				.find(|(_, link)| link.source_path == deployed.source())
+				.map(|(idx, _)| profile.profile_names[*idx].clone());
+
+			items.insert(
+				target.clone(),
+				AuditItem {
+					kind: AuditItemKind::Symlink,
+					source: deployed.source().to_path_buf(),
+					profile: provenance,
+					sha256: None,
+				},
+			);
+		}
+
+		Ok(Self {
+			version: AUDIT_MANIFEST_VERSION,
+			profile: profile_name.to_string(),
+			items,
+		})
+	}
+
+	/// Checks the current state of the target against the manifest.
+	///
+	/// Items which exist on the target but are not part of the manifest are
+	/// not reported.
+	///
+	/// Returns all items which no longer match, ordered by their target path.
+	pub fn verify(&self) -> Vec<AuditMismatch> {
+		self.items
+			.iter()
+			.filter_map(|(target, item)| {
+				verify_item(target, item).map(|problem| AuditMismatch {
+					target: target.clone(),
+					problem,
+				})
+			})
+			.collect()
+	}
+}
+
+/// An item of an [`AuditManifest`] which no longer matches the target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditMismatch {
+	/// Target path of the item.
+	pub target: PathBuf,
+
+	/// How the item differs.
+	pub problem: AuditProblem,
+}
+
+/// Describes how an item differs from the [`AuditManifest`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditProblem {
+	/// The item does not exist.
+	Missing,
+
+	/// The item exists but is of another kind (e.g. a file instead of a
+	/// symlink).
+	KindChanged {
+		/// The recorded kind.
+		expected: AuditItemKind,
+
+		/// The kind found on the target.
+		found: AuditItemKind,
+	},
+
+	/// The content of a file changed.
+	ContentChanged,
+
+	/// A symlink points to another path.
+	LinkChanged {
+		/// The path the link points to.
+		found: PathBuf,
+	},
+
+	/// The item could not be read.
+	Unreadable(String),
+}
+
+impl fmt::Display for AuditProblem {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Missing => f.write_str("Missing"),
+			Self::KindChanged { expected, found } => {
+				write!(f, "Expected a {expected} but found a {found}")
+			}
+			Self::ContentChanged => f.write_str("Content changed"),
+			Self::LinkChanged { found } => write!(f, "Link points to `{}`", found.display()),
+			Self::Unreadable(reason) => write!(f, "Failed to read: {reason}"),
+		}
+	}
+}
+
+/// Checks a single item against the target.
+fn verify_item(target: &Path, item: &AuditItem) -> Option<AuditProblem> {
+	let metadata = match target.symlink_metadata() {
+		Ok(metadata) => metadata,
+		Err(err) if err.kind() == io::ErrorKind::NotFound => return Some(AuditProblem::Missing),
+		Err(err) => return Some(AuditProblem::Unreadable(err.to_string())),
+	};
+
+	let found = if metadata.file_type().is_symlink() {
+		AuditItemKind::Symlink
+	} else if metadata.is_dir() {
+		AuditItemKind::Directory
+	} else {
+		AuditItemKind::File
+	};
+
+	if found != item.kind {
+		return Some(AuditProblem::KindChanged {
+			expected: item.kind,
+			found,
+		});
+	}
+
+	match item.kind {
+		AuditItemKind::File => match (hash_file(target), &item.sha256) {
+			(Err(err), _) => Some(AuditProblem::Unreadable(err.to_string())),
+			(Ok(hash), Some(expected)) if &hash != expected => Some(AuditProblem::ContentChanged),
+			(Ok(_), _) => None,
+		},
+		AuditItemKind::Directory => None,
+		AuditItemKind::Symlink => match std::fs::read_link(target) {
+			Err(err) => Some(AuditProblem::Unreadable(err.to_string())),
+			Ok(found) if found != item.source => Some(AuditProblem::LinkChanged { found }),
+			Ok(_) => None,
+		},
+	}
+}
+
+/// Returns the hex encoded SHA-256 hash of the file at `path`.
+///
+/// The file is streamed through the hasher, so it is never read into memory
+/// as a whole.
+///
+/// # Errors
+///
+/// An error is returned if the file could not be read.
+pub fn hash_file(path: &Path) -> io::Result<String> {
+	let mut hasher = Sha256::new();
+	io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+
+	Ok(hex(&hasher.finalize()))
+}
+
+/// Returns the hex encoded SHA-256 hash of `data`.
+pub fn sha256_hex(data: &[u8]) -> String {
+	hex(&Sha256::digest(data))
+}
+
+/// Encodes `bytes` as lowercase hex string.
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::profile::dotfile::Dotfile;
+	use crate::visit::deploy::deployment::ItemStatus;
+
+	#[test]
+	fn sha256_hex_encoding() {
+		crate::tests::setup_test_env();
+
+		assert_eq!(
+			sha256_hex(b""),
+			"e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+		);
+		assert_eq!(
+			sha256_hex(b"abc"),
+			"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+		);
+	}
+
+	#[test]
+	fn audit_manifest_verify() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+
+		let file = dir.join("demo.txt");
+		std::fs::write(&file, "Hello")?;
+
+		let dotfile = Dotfile {
+			path: PathBuf::from("demo.txt"),
+			..Default::default()
+		};
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("base"),
+			crate::profile::Profile {
+				dotfiles: vec![dotfile.clone()],
+				..Default::default()
+			},
+		);
+		let profile = builder.finish();
+
+		let mut builder = Deployment::build();
+		builder.add_dotfile(file.clone(), dotfile, ItemStatus::success());
+		let deployment = builder.success();
+
+		let manifest = AuditManifest::from_deployment("demo", &profile, &deployment)?;
+		let item = &manifest.items[&file];
+		assert_eq!(item.kind, AuditItemKind::File);
+		assert_eq!(item.source, Path::new("demo.txt"));
+		assert_eq!(item.profile.as_deref(), Some("base"));
+		assert!(manifest.verify().is_empty());
+
+		std::fs::write(&file, "Changed")?;
+		assert_eq!(
+			manifest.verify(),
+			[AuditMismatch {
+				target: file.clone(),
+				problem: AuditProblem::ContentChanged
+			}]
+		);
+
+		std::fs::remove_file(&file)?;
+		assert_eq!(manifest.verify()[0].problem, AuditProblem::Missing);
+
+		Ok(())
+	}
+}
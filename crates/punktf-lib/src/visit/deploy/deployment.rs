@@ -1,25 +1,43 @@
 //! Models and structs used by and for the deployment process.
 
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt;
 use std::path::{Path, PathBuf};
-use std::time::{Duration, SystemTime, SystemTimeError};
+use std::time::{Duration, Instant, SystemTime, SystemTimeError};
 
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::profile::dotfile::Dotfile;
+use crate::profile::hook::HookOutput;
 use crate::profile::Priority;
+use crate::visit::deploy::stats::{DeploymentStats, ItemTiming};
 
 /// Contains the status of a deployed item.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ItemStatus {
 	/// The item was successfully created.
 	Success,
+	/// The item was successfully created by copying its contents as binary
+	/// data, without any template processing or content transformation
+	/// (related: [`BinaryMode`](`crate::profile::BinaryMode`)).
+	CopiedBinary,
+	/// The item was successfully created by overwriting a read-only target,
+	/// which was made writable for the deployment and read-only again
+	/// afterwards (related:
+	/// [`DeployOptions::force`](`crate::visit::deploy::DeployOptions::force`)).
+	Forced,
+	/// The item was successfully created by three-way merging the changes of
+	/// the existing target, but the merge produced the given number of
+	/// conflicts. They are marked with conflict markers in the target, which
+	/// need to be resolved by hand (related:
+	/// [`MergeMode::Merge`](`crate::profile::MergeMode::Merge`)).
+	MergeConflicts(usize),
 	/// The item deployment failed.
 	Failed(Cow<'static, str>),
 	/// The item deployment was skipped.
-	Skipped(Cow<'static, str>),
+	Skipped(SkipReason),
 }
 
 impl ItemStatus {
@@ -35,12 +53,60 @@ impl ItemStatus {
 
 	/// Indicates that the item operation was skipped.
 	pub fn skipped<S: Into<Cow<'static, str>>>(reason: S) -> Self {
-		Self::Skipped(reason.into())
+		Self::Skipped(SkipReason::Message(reason.into()))
+	}
+
+	/// Indicates that the item operation was skipped, as `winner` with a
+	/// higher priority is already deployed at the target.
+	pub const fn overruled(winner: PriorityWinner) -> Self {
+		Self::Skipped(SkipReason::Priority(winner))
+	}
+
+	/// Marks the item operation as successful, with the contents copied as
+	/// binary data.
+	pub const fn copied_binary() -> Self {
+		Self::CopiedBinary
+	}
+
+	/// Marks the item operation as successful, with a read-only target
+	/// overwritten.
+	pub const fn forced() -> Self {
+		Self::Forced
+	}
+
+	/// Marks the item operation as successful, with `conflicts` conflicts
+	/// written to the target by a merge.
+	pub const fn merge_conflicts(conflicts: usize) -> Self {
+		Self::MergeConflicts(conflicts)
 	}
 
 	/// Checks if the item operation was successful.
-	pub fn is_success(&self) -> bool {
-		self == &Self::Success
+	///
+	/// This includes items which were copied as binary data, forced items and
+	/// items merged with conflicts.
+	pub const fn is_success(&self) -> bool {
+		matches!(
+			self,
+			&Self::Success | &Self::CopiedBinary | &Self::Forced | &Self::MergeConflicts(_)
+		)
+	}
+
+	/// Checks if a read-only target was overwritten.
+	pub const fn is_forced(&self) -> bool {
+		matches!(self, &Self::Forced)
+	}
+
+	/// Returns the number of conflicts if the item was merged with conflicts.
+	pub const fn merge_conflict_count(&self) -> Option<usize> {
+		match self {
+			Self::MergeConflicts(conflicts) => Some(*conflicts),
+			_ => None,
+		}
+	}
+
+	/// Checks if the contents of the item were copied as binary data.
+	pub const fn is_copied_binary(&self) -> bool {
+		matches!(self, &Self::CopiedBinary)
 	}
 
 	/// Checks if the item operation has failed.
@@ -58,6 +124,11 @@ impl fmt::Display for ItemStatus {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
 			Self::Success => f.write_str("Success"),
+			Self::CopiedBinary => f.write_str("Success (copied as binary)"),
+			Self::Forced => f.write_str("Success (read-only target overwritten)"),
+			Self::MergeConflicts(conflicts) => {
+				write!(f, "Success (merged with {conflicts} conflicts)")
+			}
 			Self::Failed(reason) => write!(f, "Failed: {reason}"),
 			Self::Skipped(reason) => write!(f, "Skipped: {reason}"),
 		}
@@ -73,7 +144,70 @@ where
 	}
 }
 
+/// Reason why an item was skipped (related: [`ItemStatus::Skipped`]).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SkipReason {
+	/// A description why the item was skipped.
+	Message(Cow<'static, str>),
+
+	/// An item with a higher priority is already deployed at the target.
+	Priority(PriorityWinner),
+}
+
+impl SkipReason {
+	/// Returns the item which caused the skip because of its higher priority.
+	pub const fn winner(&self) -> Option<&PriorityWinner> {
+		match self {
+			Self::Message(_) => None,
+			Self::Priority(winner) => Some(winner),
+		}
+	}
+}
+
+impl fmt::Display for SkipReason {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Message(reason) => f.write_str(reason),
+			Self::Priority(winner) => write!(
+				f,
+				"Item with higher priority is already deployed ({winner})"
+			),
+		}
+	}
+}
+
+/// The item which is already deployed at a target and caused another item
+/// to be skipped because of its higher priority.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriorityWinner {
+	/// Path of the dotfile relative to the `dotfiles` directory, or the
+	/// source path of a link.
+	pub source: PathBuf,
+
+	/// Name of the profile of the extend chain which defines the item.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub profile: Option<String>,
+
+	/// Priority of the item.
+	pub priority: Priority,
+}
+
+impl fmt::Display for PriorityWinner {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "`{}`", self.source.display())?;
+
+		if let Some(profile) = &self.profile {
+			write!(f, " of profile `{profile}`")?;
+		}
+
+		write!(f, " with priority {}", self.priority.0)
+	}
+}
+
 /// Defines the type of dotfile.
+// Boxing the dotfile would change the public API for little gain.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DeployedDotfileKind {
 	/// A normal dotfile.
@@ -105,6 +239,11 @@ pub struct DeployedDotfile {
 
 	/// The kind of the deployed dotfile.
 	pub kind: DeployedDotfileKind,
+
+	/// Hex encoded SHA-256 hash of the content which was (or, for a dry run,
+	/// would have been) written to the target.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub sha256: Option<String>,
 }
 
 impl DeployedDotfile {
@@ -117,6 +256,11 @@ impl DeployedDotfile {
 	pub const fn kind(&self) -> &DeployedDotfileKind {
 		&self.kind
 	}
+
+	/// Returns the hash of the content written to the target.
+	pub fn sha256(&self) -> Option<&str> {
+		self.sha256.as_deref()
+	}
 }
 
 impl AsRef<ItemStatus> for DeployedDotfile {
@@ -133,6 +277,10 @@ pub struct DeployedSymlink {
 
 	/// The source path of the link.
 	pub source: PathBuf,
+
+	/// The priority of the link.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub priority: Option<Priority>,
 }
 
 impl DeployedSymlink {
@@ -202,9 +350,102 @@ where
 	}
 }
 
+/// A kind of command which can be executed during a deployment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CommandKind {
+	/// A hook executed before the deployment.
+	PreHook,
+
+	/// A hook executed after the deployment.
+	PostHook,
+
+	/// A hook executed after a failed deployment.
+	OnFailureHook,
+
+	/// A hook executed before an item is deployed.
+	PreDotfileHook,
+
+	/// A hook executed after an item was processed.
+	PostDotfileHook,
+}
+
+impl fmt::Display for CommandKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::PreHook => f.write_str("pre-hook"),
+			Self::PostHook => f.write_str("post-hook"),
+			Self::OnFailureHook => f.write_str("on-failure-hook"),
+			Self::PreDotfileHook => f.write_str("pre-dotfile-hook"),
+			Self::PostDotfileHook => f.write_str("post-dotfile-hook"),
+		}
+	}
+}
+
+/// A command which was not executed, because the execution of commands was
+/// disabled (related: [`DeployOptions::no_exec`](`super::DeployOptions::no_exec`)).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BlockedCommand {
+	/// The kind of the command.
+	pub kind: CommandKind,
+
+	/// The command which would have been executed.
+	pub command: String,
+}
+
+/// A hook which was executed during a deployment, together with its captured
+/// output.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ExecutedHook {
+	/// The kind of the hook.
+	pub kind: CommandKind,
+
+	/// The command of the hook.
+	pub command: String,
+
+	/// The time it took to execute the hook.
+	pub duration: Duration,
+
+	/// The exit code of the hook.
+	///
+	/// This is `None` if the hook was terminated by a signal.
+	pub exit_code: Option<i32>,
+
+	/// Everything the hook wrote to stdout.
+	pub stdout: String,
+
+	/// Everything the hook wrote to stderr.
+	pub stderr: String,
+}
+
+impl ExecutedHook {
+	/// Creates a new executed hook from the captured `output` of the hook
+	/// `command`.
+	pub fn new(kind: CommandKind, command: String, output: &HookOutput) -> Self {
+		Self {
+			kind,
+			command,
+			duration: output.duration,
+			exit_code: output.status.code(),
+			stdout: output.stdout.clone(),
+			stderr: output.stderr.clone(),
+		}
+	}
+
+	/// Checks if the hook exited successfully.
+	pub fn is_success(&self) -> bool {
+		self.exit_code == Some(0)
+	}
+}
+
 /// Describes the deployment of a profile.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Deployment {
+	/// Unique id of the deployment run.
+	///
+	/// Deployments recorded by older versions have a nil id.
+	#[serde(default = "Uuid::nil")]
+	id: Uuid,
+
 	/// The time the deployment was started.
 	time_start: SystemTime,
 
@@ -215,13 +456,36 @@ pub struct Deployment {
 	status: DeploymentStatus,
 
 	/// The dotfiles that were deployed.
-	dotfiles: HashMap<PathBuf, DeployedDotfile>,
+	dotfiles: BTreeMap<PathBuf, DeployedDotfile>,
 
 	/// The links that were deployed.
-	symlinks: HashMap<PathBuf, DeployedSymlink>,
+	symlinks: BTreeMap<PathBuf, DeployedSymlink>,
+
+	/// Directory which prefixed all target paths, if the deployment was
+	/// staged (related: [`DeployOptions::destdir`](`super::DeployOptions::destdir`)).
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	destdir: Option<PathBuf>,
+
+	/// All commands which were not executed, because the execution of
+	/// commands was disabled.
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	blocked: Vec<BlockedCommand>,
+
+	/// All hooks which were executed, in the order of their execution.
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	hooks: Vec<ExecutedHook>,
+
+	/// Time spent on and bytes written for each deployed item.
+	#[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+	timings: BTreeMap<PathBuf, ItemTiming>,
 }
 
 impl Deployment {
+	/// Returns the unique id of the deployment run.
+	pub const fn id(&self) -> &Uuid {
+		&self.id
+	}
+
 	/// Returns the time the deployment was started.
 	pub const fn time_start(&self) -> &SystemTime {
 		&self.time_start
@@ -243,24 +507,217 @@ impl Deployment {
 	}
 
 	/// Returns the dotfiles.
-	pub const fn dotfiles(&self) -> &HashMap<PathBuf, DeployedDotfile> {
+	pub const fn dotfiles(&self) -> &BTreeMap<PathBuf, DeployedDotfile> {
 		&self.dotfiles
 	}
 
 	/// Returns the symlinks.
-	pub const fn symlinks(&self) -> &HashMap<PathBuf, DeployedSymlink> {
+	pub const fn symlinks(&self) -> &BTreeMap<PathBuf, DeployedSymlink> {
 		&self.symlinks
 	}
 
+	/// Returns the directory which prefixed all target paths, if the
+	/// deployment was staged.
+	pub fn destdir(&self) -> Option<&Path> {
+		self.destdir.as_deref()
+	}
+
+	/// Returns all commands which were not executed, because the execution of
+	/// commands was disabled.
+	pub fn blocked(&self) -> &[BlockedCommand] {
+		&self.blocked
+	}
+
+	/// Records a command which was not executed after the deployment was
+	/// finished (e.g. an on-failure-hook).
+	pub(super) fn add_blocked(&mut self, kind: CommandKind, command: String) {
+		self.blocked.push(BlockedCommand { kind, command });
+	}
+
+	/// Returns all hooks which were executed, in the order of their
+	/// execution.
+	pub fn hooks(&self) -> &[ExecutedHook] {
+		&self.hooks
+	}
+
+	/// Records a hook which was executed after the deployment was finished
+	/// (e.g. an on-failure-hook).
+	pub(super) fn add_hook(&mut self, hook: ExecutedHook) {
+		self.hooks.push(hook);
+	}
+
+	/// Returns the time spent on and bytes written for each deployed item.
+	pub const fn timings(&self) -> &BTreeMap<PathBuf, ItemTiming> {
+		&self.timings
+	}
+
+	/// Collects statistics about the deployment (e.g. the slowest items).
+	pub fn stats(&self) -> DeploymentStats {
+		DeploymentStats::new(self)
+	}
+
 	/// Builds the deployment.
 	pub fn build() -> DeploymentBuilder {
 		DeploymentBuilder::default()
 	}
+
+	/// Compares this deployment against an `other`, earlier, deployment and
+	/// returns all items which changed between the two.
+	///
+	/// Items which are present in both deployments with the same status are
+	/// not included in the result.
+	pub fn compare(&self, other: &Deployment) -> DeploymentDelta {
+		DeploymentDelta {
+			dotfiles: compare_items(&self.dotfiles, &other.dotfiles),
+			symlinks: compare_items(&self.symlinks, &other.symlinks),
+		}
+	}
+
+	/// Compares the content of this deployment against a `baseline`, earlier,
+	/// deployment (e.g. a stored deployment report).
+	///
+	/// In addition to [`Deployment::compare`], dotfiles whose content hash
+	/// differs and symlinks which point to a different source are reported as
+	/// [`ItemChange::ContentChanged`]. Dotfiles without a recorded hash in
+	/// either deployment are only compared by their status.
+	pub fn compare_contents(&self, baseline: &Deployment) -> DeploymentDelta {
+		let mut delta = self.compare(baseline);
+
+		for (path, dotfile) in &self.dotfiles {
+			let old = baseline
+				.dotfiles
+				.get(path)
+				.and_then(DeployedDotfile::sha256);
+
+			if let (Some(old), Some(new)) = (old, dotfile.sha256()) {
+				if old != new {
+					delta
+						.dotfiles
+						.entry(path.clone())
+						.or_insert(ItemChange::ContentChanged);
+				}
+			}
+		}
+
+		for (path, symlink) in &self.symlinks {
+			if let Some(old) = baseline.symlinks.get(path) {
+				if old.source != symlink.source {
+					delta
+						.symlinks
+						.entry(path.clone())
+						.or_insert(ItemChange::ContentChanged);
+				}
+			}
+		}
+
+		delta
+	}
+}
+
+/// Compares the items of two deployments with each other.
+///
+/// `new` is considered to be the later deployment.
+fn compare_items<T: AsRef<ItemStatus>>(
+	new: &BTreeMap<PathBuf, T>,
+	old: &BTreeMap<PathBuf, T>,
+) -> BTreeMap<PathBuf, ItemChange> {
+	let mut changes = BTreeMap::new();
+
+	for (path, new_item) in new {
+		let new_status = new_item.as_ref();
+
+		let change = match old.get(path).map(AsRef::as_ref) {
+			None => ItemChange::Added(new_status.clone()),
+			Some(old_status) if old_status != new_status => ItemChange::Changed {
+				old: old_status.clone(),
+				new: new_status.clone(),
+			},
+			Some(_) => continue,
+		};
+
+		changes.insert(path.clone(), change);
+	}
+
+	for (path, old_item) in old {
+		if !new.contains_key(path) {
+			changes.insert(path.clone(), ItemChange::Removed(old_item.as_ref().clone()));
+		}
+	}
+
+	changes
+}
+
+/// Describes how a single item changed between two deployments.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ItemChange {
+	/// The item was not part of the earlier deployment.
+	Added(ItemStatus),
+
+	/// The item was only part of the earlier deployment.
+	Removed(ItemStatus),
+
+	/// The status of the item changed.
+	Changed {
+		/// Status of the item in the earlier deployment.
+		old: ItemStatus,
+
+		/// Status of the item in the later deployment.
+		new: ItemStatus,
+	},
+
+	/// The status of the item is the same, but its content (or the source of
+	/// a symlink) changed (related: [`Deployment::compare_contents`]).
+	ContentChanged,
+}
+
+impl fmt::Display for ItemChange {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Added(status) => write!(f, "Added ({status})"),
+			Self::Removed(status) => write!(f, "Removed ({status})"),
+			Self::Changed { old, new } => write!(f, "Changed ({old} => {new})"),
+			Self::ContentChanged => f.write_str("Content changed"),
+		}
+	}
+}
+
+/// Describes all changes between two deployments (related:
+/// [`Deployment::compare`]).
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeploymentDelta {
+	/// Changes of the dotfiles, keyed by the target path.
+	pub dotfiles: BTreeMap<PathBuf, ItemChange>,
+
+	/// Changes of the symlinks, keyed by the target path.
+	pub symlinks: BTreeMap<PathBuf, ItemChange>,
+}
+
+impl DeploymentDelta {
+	/// Checks if there are no changes between the two deployments.
+	pub fn is_empty(&self) -> bool {
+		self.dotfiles.is_empty() && self.symlinks.is_empty()
+	}
+}
+
+/// Defines how the [`DeploymentStatus`] is aggregated from the status of all
+/// items (related: [`DeploymentBuilder::finish_with`]).
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatusAggregation {
+	/// The deployment only fails if any item failed.
+	#[default]
+	Lenient,
+
+	/// The deployment also fails if any item was skipped because of a
+	/// conflict (related: [`DeploymentBuilder::add_conflict`]).
+	Strict,
 }
 
 /// A builder for a [`Deployment`].
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DeploymentBuilder {
+	/// Unique id of the deployment run.
+	id: Uuid,
+
 	/// The start time of the deployment.
 	///
 	/// This used to keep track of the total execution time of the deployment
@@ -268,13 +725,117 @@ pub struct DeploymentBuilder {
 	time_start: SystemTime,
 
 	/// All dotfiles which were already process by the deployment process.
-	dotfiles: HashMap<PathBuf, DeployedDotfile>,
+	dotfiles: BTreeMap<PathBuf, DeployedDotfile>,
 
 	/// All symlinks which were already process by the deployment process.
-	symlinks: HashMap<PathBuf, DeployedSymlink>,
+	symlinks: BTreeMap<PathBuf, DeployedSymlink>,
+
+	/// Target paths of all items which were skipped because of a conflict.
+	conflicts: HashSet<PathBuf>,
+
+	/// Directory which prefixed all target paths.
+	destdir: Option<PathBuf>,
+
+	/// All commands which were not executed.
+	blocked: Vec<BlockedCommand>,
+
+	/// All hooks which were executed.
+	hooks: Vec<ExecutedHook>,
+
+	/// Timings of all items which were already processed.
+	timings: BTreeMap<PathBuf, ItemTiming>,
+
+	/// The time the deployment of the current item was started together with
+	/// its timing so far (related: [`DeploymentBuilder::start_item`]).
+	current: Option<(Instant, ItemTiming)>,
+
+	/// Hash of the content written for the current item (related:
+	/// [`DeploymentBuilder::set_content_hash`]).
+	content_hash: Option<String>,
 }
 
 impl DeploymentBuilder {
+	/// Returns the unique id of the deployment run.
+	pub const fn id(&self) -> &Uuid {
+		&self.id
+	}
+
+	/// Marks the deployment as staged into `destdir`.
+	pub fn destdir(&mut self, destdir: PathBuf) -> &mut Self {
+		self.destdir = Some(destdir);
+		self
+	}
+
+	/// Records a command which was not executed, because the execution of
+	/// commands was disabled.
+	pub fn add_blocked(&mut self, kind: CommandKind, command: String) -> &mut Self {
+		self.blocked.push(BlockedCommand { kind, command });
+		self
+	}
+
+	/// Records a hook which was executed.
+	pub fn add_hook(&mut self, hook: ExecutedHook) -> &mut Self {
+		self.hooks.push(hook);
+		self
+	}
+
+	/// Starts tracking the time spent on the next item.
+	///
+	/// The timing is stored once a status for the item is added to the
+	/// builder.
+	pub fn start_item(&mut self) -> &mut Self {
+		self.current = Some((Instant::now(), ItemTiming::default()));
+		self.content_hash = None;
+		self
+	}
+
+	/// Sets the hex encoded SHA-256 hash of the content written for the
+	/// current item.
+	///
+	/// The hash is stored once a status for the item is added to the
+	/// builder.
+	pub fn set_content_hash(&mut self, hash: String) -> &mut Self {
+		self.content_hash = Some(hash);
+		self
+	}
+
+	/// Adds `duration` to the time the current item spent resolving its
+	/// template.
+	pub fn add_template_time(&mut self, duration: Duration) -> &mut Self {
+		if let Some((_, timing)) = &mut self.current {
+			timing.template += duration;
+		}
+
+		self
+	}
+
+	/// Adds `duration` to the time the current item spent reading and writing
+	/// files.
+	pub fn add_io_time(&mut self, duration: Duration) -> &mut Self {
+		if let Some((_, timing)) = &mut self.current {
+			timing.io += duration;
+		}
+
+		self
+	}
+
+	/// Adds `bytes` to the number of bytes written for the current item.
+	pub const fn add_bytes_written(&mut self, bytes: u64) -> &mut Self {
+		if let Some((_, timing)) = &mut self.current {
+			timing.bytes_written += bytes;
+		}
+
+		self
+	}
+
+	/// Stores the timing of the current item under `path`.
+	fn finish_item(&mut self, path: &Path) {
+		if let Some((start, mut timing)) = self.current.take() {
+			timing.total = start.elapsed();
+			self.timings.insert(path.to_path_buf(), timing);
+		}
+	}
+
 	/// Adds a dotfile with the given `status` to the builder.
 	pub fn add_dotfile(
 		&mut self,
@@ -282,11 +843,13 @@ impl DeploymentBuilder {
 		dotfile: Dotfile,
 		status: ItemStatus,
 	) -> &mut Self {
+		self.finish_item(&path);
 		self.dotfiles.insert(
 			path,
 			DeployedDotfile {
 				kind: DeployedDotfileKind::Dotfile(dotfile),
 				status,
+				sha256: self.content_hash.take(),
 			},
 		);
 
@@ -296,11 +859,13 @@ impl DeploymentBuilder {
 	/// Adds the child of a dotfile directory with the given `status` to the
 	/// builder.
 	pub fn add_child(&mut self, path: PathBuf, parent: PathBuf, status: ItemStatus) -> &mut Self {
+		self.finish_item(&path);
 		self.dotfiles.insert(
 			path,
 			DeployedDotfile {
 				kind: DeployedDotfileKind::Child(parent),
 				status,
+				sha256: self.content_hash.take(),
 			},
 		);
 
@@ -309,8 +874,41 @@ impl DeploymentBuilder {
 
 	/// Adds a symlink with the given `status` to the builder.
 	pub fn add_link(&mut self, source: PathBuf, target: PathBuf, status: ItemStatus) -> &mut Self {
-		self.symlinks
-			.insert(target, DeployedSymlink { source, status });
+		self.add_prioritized_link(source, target, None, status)
+	}
+
+	/// Adds a symlink with the given `priority` and `status` to the builder.
+	pub fn add_prioritized_link(
+		&mut self,
+		source: PathBuf,
+		target: PathBuf,
+		priority: Option<Priority>,
+		status: ItemStatus,
+	) -> &mut Self {
+		self.finish_item(&target);
+		self.symlinks.insert(
+			target,
+			DeployedSymlink {
+				source,
+				status,
+				priority,
+			},
+		);
+
+		self
+	}
+
+	/// Marks the item at the target `path` as conflicting.
+	///
+	/// A conflicting item is an item which was skipped, because the target
+	/// could not be brought into the desired state (e.g. the target already
+	/// exists and the merge was denied). With [`StatusAggregation::Strict`]
+	/// these are treated as failures.
+	///
+	/// The mark is ignored if the item is not skipped when the deployment is
+	/// finished.
+	pub fn add_conflict(&mut self, path: PathBuf) -> &mut Self {
+		self.conflicts.insert(path);
 
 		self
 	}
@@ -356,12 +954,37 @@ impl DeploymentBuilder {
 		}
 	}
 
-	/// Gets the priority of the dotfile already deployed at `path`.
+	/// Gets the symlink which was successfully deployed at `path`.
+	pub fn get_deployed_symlink<P: AsRef<Path>>(&self, path: P) -> Option<&DeployedSymlink> {
+		self.symlinks
+			.get(path.as_ref())
+			.filter(|link| link.status.is_success())
+	}
+
+	/// Gets the priority of the dotfile or symlink already deployed at `path`.
 	///
-	/// This function only evaluates a dotfile with [`ItemStatus::Success`].
+	/// This function only evaluates an item with [`ItemStatus::Success`].
 	pub fn get_priority<P: AsRef<Path>>(&self, path: P) -> Option<&Priority> {
-		self.get_deployed_dotfile(path)
-			.and_then(|d| d.priority.as_ref())
+		let path = path.as_ref();
+
+		self.symlinks
+			.get(path)
+			.filter(|link| link.status.is_success())
+			.and_then(|link| link.priority.as_ref())
+			.or_else(|| {
+				self.get_deployed_dotfile(path)
+					.and_then(|d| d.priority.as_ref())
+			})
+	}
+
+	/// Gets the status of the dotfile or symlink already processed at `path`.
+	pub fn get_status<P: AsRef<Path>>(&self, path: P) -> Option<&ItemStatus> {
+		let path = path.as_ref();
+
+		self.dotfiles
+			.get(path)
+			.map(|dotfile| &dotfile.status)
+			.or_else(|| self.symlinks.get(path).map(|link| &link.status))
 	}
 
 	/// Checks if a dotfile was already successfully deployed at `path`.
@@ -378,6 +1001,14 @@ impl DeploymentBuilder {
 	/// This will try to guess the state of the deployment by looking for any
 	/// failed deployed dotfile.
 	pub fn finish(self) -> Deployment {
+		self.finish_with(StatusAggregation::Lenient)
+	}
+
+	/// Consumes self and creates a [`Deployment`] from it.
+	///
+	/// The state of the deployment is aggregated from the status of all items
+	/// as defined by `aggregation`.
+	pub fn finish_with(self, aggregation: StatusAggregation) -> Deployment {
 		let failed_dotfiles = self
 			.dotfiles
 			.values()
@@ -390,20 +1021,44 @@ impl DeploymentBuilder {
 			.filter(|d| d.status.is_failed())
 			.count();
 
+		let conflicts = match aggregation {
+			StatusAggregation::Lenient => 0,
+			StatusAggregation::Strict => self
+				.conflicts
+				.iter()
+				.filter(|path| {
+					self.dotfiles
+						.get(*path)
+						.map(|d| &d.status)
+						.or_else(|| self.symlinks.get(*path).map(|l| &l.status))
+						.is_some_and(ItemStatus::is_skipped)
+				})
+				.count(),
+		};
+
 		let status = if failed_dotfiles > 0 {
 			DeploymentStatus::failed(format!(
 				"Deployment of {failed_dotfiles} dotfiles and {failed_links} links failed"
 			))
+		} else if conflicts > 0 {
+			DeploymentStatus::failed(format!(
+				"{conflicts} items were skipped because of conflicts (strict mode)"
+			))
 		} else {
 			DeploymentStatus::Success
 		};
 
 		Deployment {
+			id: self.id,
 			time_start: self.time_start,
 			time_end: SystemTime::now(),
 			status,
 			dotfiles: self.dotfiles,
 			symlinks: self.symlinks,
+			destdir: self.destdir,
+			blocked: self.blocked,
+			hooks: self.hooks,
+			timings: self.timings,
 		}
 	}
 
@@ -412,11 +1067,16 @@ impl DeploymentBuilder {
 	/// This will mark the deployment as success.
 	pub fn success(self) -> Deployment {
 		Deployment {
+			id: self.id,
 			time_start: self.time_start,
 			time_end: SystemTime::now(),
 			status: DeploymentStatus::Success,
 			dotfiles: self.dotfiles,
 			symlinks: self.symlinks,
+			destdir: self.destdir,
+			blocked: self.blocked,
+			hooks: self.hooks,
+			timings: self.timings,
 		}
 	}
 
@@ -426,11 +1086,16 @@ impl DeploymentBuilder {
 	/// `reason`.
 	pub fn failed<S: Into<Cow<'static, str>>>(self, reason: S) -> Deployment {
 		Deployment {
+			id: self.id,
 			time_start: self.time_start,
 			time_end: SystemTime::now(),
 			status: DeploymentStatus::Failed(reason.into()),
 			dotfiles: self.dotfiles,
 			symlinks: self.symlinks,
+			destdir: self.destdir,
+			blocked: self.blocked,
+			hooks: self.hooks,
+			timings: self.timings,
 		}
 	}
 }
@@ -438,9 +1103,17 @@ impl DeploymentBuilder {
 impl Default for DeploymentBuilder {
 	fn default() -> Self {
 		Self {
+			id: Uuid::new_v4(),
 			time_start: SystemTime::now(),
-			dotfiles: HashMap::new(),
-			symlinks: HashMap::new(),
+			dotfiles: BTreeMap::new(),
+			symlinks: BTreeMap::new(),
+			conflicts: HashSet::new(),
+			destdir: None,
+			blocked: Vec::new(),
+			hooks: Vec::new(),
+			timings: BTreeMap::new(),
+			current: None,
+			content_hash: None,
 		}
 	}
 }
@@ -463,4 +1136,109 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[test]
+	fn deployment_compare() {
+		crate::tests::setup_test_env();
+
+		let mut builder = Deployment::build();
+		builder.add_child("a".into(), "root".into(), ItemStatus::success());
+		builder.add_child("b".into(), "root".into(), ItemStatus::success());
+		builder.add_link("src".into(), "link".into(), ItemStatus::success());
+		let old = builder.finish();
+
+		let mut builder = Deployment::build();
+		builder.add_child("a".into(), "root".into(), ItemStatus::success());
+		builder.add_child("b".into(), "root".into(), ItemStatus::failed("error"));
+		builder.add_child("c".into(), "root".into(), ItemStatus::success());
+		let new = builder.finish();
+
+		let delta = new.compare(&old);
+
+		assert_eq!(delta.dotfiles.len(), 2);
+		assert_eq!(
+			delta.dotfiles.get(Path::new("b")),
+			Some(&ItemChange::Changed {
+				old: ItemStatus::success(),
+				new: ItemStatus::failed("error")
+			})
+		);
+		assert_eq!(
+			delta.dotfiles.get(Path::new("c")),
+			Some(&ItemChange::Added(ItemStatus::success()))
+		);
+		assert_eq!(
+			delta.symlinks.get(Path::new("link")),
+			Some(&ItemChange::Removed(ItemStatus::success()))
+		);
+
+		assert!(new.compare(&new).is_empty());
+	}
+
+	#[test]
+	fn deployment_compare_contents() {
+		crate::tests::setup_test_env();
+
+		let mut builder = Deployment::build();
+		builder.start_item().set_content_hash("aaa".into());
+		builder.add_child("a".into(), "root".into(), ItemStatus::success());
+		builder.start_item().set_content_hash("bbb".into());
+		builder.add_child("b".into(), "root".into(), ItemStatus::success());
+		builder.add_link("src".into(), "link".into(), ItemStatus::success());
+		let baseline = builder.finish();
+
+		let mut builder = Deployment::build();
+		builder.start_item().set_content_hash("aaa".into());
+		builder.add_child("a".into(), "root".into(), ItemStatus::success());
+		builder.start_item().set_content_hash("ccc".into());
+		builder.add_child("b".into(), "root".into(), ItemStatus::success());
+		builder.add_link("other".into(), "link".into(), ItemStatus::success());
+		let new = builder.finish();
+
+		// Statuses alone did not change
+		assert!(new.compare(&baseline).is_empty());
+
+		let delta = new.compare_contents(&baseline);
+
+		assert_eq!(delta.dotfiles.len(), 1);
+		assert_eq!(
+			delta.dotfiles.get(Path::new("b")),
+			Some(&ItemChange::ContentChanged)
+		);
+		assert_eq!(
+			delta.symlinks.get(Path::new("link")),
+			Some(&ItemChange::ContentChanged)
+		);
+
+		assert!(new.compare_contents(&new).is_empty());
+	}
+
+	#[test]
+	fn deployment_strict() {
+		crate::tests::setup_test_env();
+
+		let mut builder = Deployment::build();
+		builder.add_child("a".into(), "root".into(), ItemStatus::success());
+		builder.add_child("b".into(), "root".into(), ItemStatus::skipped("priority"));
+		builder.add_link("src".into(), "link".into(), ItemStatus::skipped("exists"));
+		builder.add_conflict("link".into());
+
+		assert!(builder
+			.clone()
+			.finish_with(StatusAggregation::Lenient)
+			.status()
+			.is_success());
+		assert!(builder
+			.clone()
+			.finish_with(StatusAggregation::Strict)
+			.status()
+			.is_failed());
+
+		// A conflict is only relevant as long as the item is skipped.
+		builder.add_link("src".into(), "link".into(), ItemStatus::success());
+		assert!(builder
+			.finish_with(StatusAggregation::Strict)
+			.status()
+			.is_success());
+	}
 }
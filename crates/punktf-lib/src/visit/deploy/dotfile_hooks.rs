@@ -0,0 +1,303 @@
+//! Hooks which are executed before and after each file and link of a
+//! deployment (related:
+//! [`Profile::pre_dotfile_hooks`](`crate::profile::Profile::pre_dotfile_hooks`)
+//! and
+//! [`Profile::post_dotfile_hooks`](`crate::profile::Profile::post_dotfile_hooks`)).
+//!
+//! Each hook receives a JSON description of the item on stdin, e.g.:
+//!
+//! ```json
+//! {
+//!   "phase": "post",
+//!   "kind": "file",
+//!   "source_path": "/home/demo/dotfiles/dotfiles/.bashrc",
+//!   "target_path": "/home/demo/.bashrc",
+//!   "relative_source_path": ".bashrc",
+//!   "template": true,
+//!   "status": "success",
+//!   "reason": null
+//! }
+//! ```
+
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::env::PunktfEnv;
+use crate::profile::hook::Hook;
+use crate::profile::source::PunktfSource;
+use crate::profile::LayeredProfile;
+use crate::visit::canonicalize_parent;
+use crate::visit::deploy::ask::MergeStrategy;
+use crate::visit::deploy::deployment::{CommandKind, DeploymentBuilder, ExecutedHook, ItemStatus};
+use crate::visit::deploy::{Deployer, DEPLOYMENT_ID_ENVVAR};
+use crate::visit::*;
+
+/// Description of an item which is passed to the dotfile hooks on stdin.
+#[derive(Debug, Clone, Serialize)]
+struct HookItem<'a> {
+	/// Either `pre` (before the item is deployed) or `post` (after the item
+	/// was processed).
+	phase: &'static str,
+
+	/// Either `file` or `link`.
+	kind: &'static str,
+
+	/// Absolute source path of the item.
+	source_path: &'a Path,
+
+	/// Absolute target path of the item.
+	target_path: &'a Path,
+
+	/// Path of the item relative to the `dotfiles` directory. This is `None`
+	/// for links.
+	relative_source_path: Option<&'a Path>,
+
+	/// Indicates if the item is resolved as template.
+	template: bool,
+
+	/// Status of the item (`success`, `skipped` or `failed`). This is only
+	/// set in the `post` phase.
+	status: Option<&'static str>,
+
+	/// Reason why the item was skipped or failed.
+	reason: Option<String>,
+}
+
+impl HookItem<'_> {
+	/// Switches the item to the `post` phase with the given `status`.
+	fn with_status(mut self, status: Option<&ItemStatus>) -> Self {
+		self.phase = "post";
+
+		(self.status, self.reason) = match status {
+			Some(ItemStatus::Failed(reason)) => (Some("failed"), Some(reason.to_string())),
+			Some(ItemStatus::Skipped(reason)) => (Some("skipped"), Some(reason.to_string())),
+			Some(_) => (Some("success"), None),
+			None => (None, None),
+		};
+
+		self
+	}
+}
+
+/// A [`Visitor`] which passes all items on to the deployer and executes the
+/// dotfile hooks of the profile before and after each file and link.
+///
+/// A failing hook marks the item as failed. If a pre-dotfile-hook fails, the
+/// item is not deployed.
+pub(super) struct DotfileHooks<'v, M> {
+	/// The visitor which deploys the items.
+	resolver: &'v mut ResolvingVisitor<Deployer<M>>,
+
+	/// If not set, all items are passed on without executing any hooks.
+	exec: bool,
+}
+
+impl<'v, M> DotfileHooks<'v, M>
+where
+	M: MergeStrategy,
+{
+	/// Creates a new instance. Hooks are only executed if `exec` is set.
+	pub(super) const fn new(resolver: &'v mut ResolvingVisitor<Deployer<M>>, exec: bool) -> Self {
+		Self { resolver, exec }
+	}
+
+	/// Checks if any dotfile hook needs to be executed for the item at
+	/// `path`.
+	fn is_hooked(&self, profile: &LayeredProfile, path: &Path) -> bool {
+		self.exec
+			&& (!profile.pre_dotfile_hooks.is_empty() || !profile.post_dotfile_hooks.is_empty())
+			&& profile.runs_dotfile_hooks(path)
+	}
+
+	/// Executes the pre-dotfile-hooks, passes the item on to `deploy` and
+	/// executes the post-dotfile-hooks afterwards.
+	///
+	/// `report_path` is the path under which the status of the item is
+	/// recorded and `add_to_builder` records a new status for the item.
+	fn hooked<D, A>(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		item: HookItem<'_>,
+		report_path: &Path,
+		deploy: D,
+		add_to_builder: A,
+	) -> Result
+	where
+		D: FnOnce(&mut ResolvingVisitor<Deployer<M>>) -> Result,
+		A: Fn(&mut DeploymentBuilder, ItemStatus),
+	{
+		let builder = &mut self.resolver.inner_mut().builder;
+
+		if let Err(reason) = run_hooks(
+			builder,
+			source,
+			profile.env(),
+			CommandKind::PreDotfileHook,
+			profile.pre_dotfile_hooks(),
+			&item,
+		) {
+			log::error!("{}: {reason}", item.target_path.display());
+			add_to_builder(builder, ItemStatus::failed(reason));
+			return Ok(());
+		}
+
+		deploy(self.resolver)?;
+
+		let builder = &mut self.resolver.inner_mut().builder;
+		let item = item.with_status(builder.get_status(report_path));
+
+		if let Err(reason) = run_hooks(
+			builder,
+			source,
+			profile.env(),
+			CommandKind::PostDotfileHook,
+			profile.post_dotfile_hooks(),
+			&item,
+		) {
+			log::error!("{}: {reason}", item.target_path.display());
+			add_to_builder(builder, ItemStatus::failed(reason));
+		}
+
+		Ok(())
+	}
+}
+
+/// Executes all `hooks` of `kind` with the JSON description of `item` on
+/// stdin and the variables of `env` and records them in `builder`.
+///
+/// Stops at the first hook which fails and returns the reason.
+fn run_hooks<'h>(
+	builder: &mut DeploymentBuilder,
+	source: &PunktfSource,
+	env: &PunktfEnv,
+	kind: CommandKind,
+	hooks: impl Iterator<Item = &'h Hook>,
+	item: &HookItem<'_>,
+) -> std::result::Result<(), String> {
+	let input = serde_json::to_vec(item).map_err(|err| err.to_string())?;
+	let id = builder.id().to_string();
+
+	for hook in hooks {
+		log::debug!("[{id}] Executing {kind}: {}", hook.command());
+
+		let output = hook
+			.run_with_input(
+				source.profiles(),
+				env.iter().chain([(DEPLOYMENT_ID_ENVVAR, id.as_str())]),
+				&input,
+			)
+			.map_err(|err| format!("Failed to execute {kind} `{}`: {err}", hook.command()))?;
+
+		output.log_lines();
+		builder.add_hook(ExecutedHook::new(kind, hook.command().to_string(), &output));
+
+		output
+			.exit_ok()
+			.map_err(|err| format!("Failed to execute {kind} `{}`: {err}", hook.command()))?;
+	}
+
+	Ok(())
+}
+
+impl<M> Visitor for DotfileHooks<'_, M>
+where
+	M: MergeStrategy,
+{
+	/// Accepts a file item and executes the dotfile hooks around its
+	/// deployment.
+	fn accept_file<'a>(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		file: &File<'a>,
+	) -> Result {
+		if !self.is_hooked(profile, &file.relative_source_path) {
+			return self.resolver.accept_file(source, profile, file);
+		}
+
+		let item = HookItem {
+			phase: "pre",
+			kind: "file",
+			source_path: &file.source_path,
+			target_path: &file.target_path,
+			relative_source_path: Some(&file.relative_source_path),
+			template: file.dotfile().is_template(),
+			status: None,
+			reason: None,
+		};
+
+		self.hooked(
+			source,
+			profile,
+			item,
+			&canonicalize_parent(&file.target_path),
+			|resolver| resolver.accept_file(source, profile, file),
+			|builder, status| file.add_to_builder(builder, status),
+		)
+	}
+
+	/// Accepts a directory item and passes it on.
+	fn accept_directory<'a>(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		directory: &Directory<'a>,
+	) -> Result {
+		self.resolver.accept_directory(source, profile, directory)
+	}
+
+	/// Accepts a link item and executes the dotfile hooks around its
+	/// deployment.
+	fn accept_link(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		symlink: &Symlink,
+	) -> Result {
+		if !self.is_hooked(profile, &symlink.source_path) {
+			return self.resolver.accept_link(source, profile, symlink);
+		}
+
+		let item = HookItem {
+			phase: "pre",
+			kind: "link",
+			source_path: &symlink.source_path,
+			target_path: &symlink.target_path,
+			relative_source_path: None,
+			template: false,
+			status: None,
+			reason: None,
+		};
+
+		self.hooked(
+			source,
+			profile,
+			item,
+			&symlink.target_path,
+			|resolver| resolver.accept_link(source, profile, symlink),
+			|builder, status| symlink.add_to_builder(builder, status),
+		)
+	}
+
+	/// Accepts a rejected item and passes it on.
+	fn accept_rejected<'a>(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		rejected: &Rejected<'a>,
+	) -> Result {
+		self.resolver.accept_rejected(source, profile, rejected)
+	}
+
+	/// Accepts an errored item and passes it on.
+	fn accept_errored<'a>(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		errored: &Errored<'a>,
+	) -> Result {
+		self.resolver.accept_errored(source, profile, errored)
+	}
+}
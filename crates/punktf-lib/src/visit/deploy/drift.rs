@@ -0,0 +1,209 @@
+//! Detection of changes to deployed items which were made outside of `punktf`
+//! (drift).
+//!
+//! A [`DriftWatcher`] takes a snapshot of all target paths recorded in a
+//! [`Manifest`] and compares them against the current state of the filesystem
+//! each time it is [polled](`DriftWatcher::poll`). Only cheap metadata (file
+//! type, size, modification time and link target) is compared, so polling is
+//! fast even for large deployments.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::visit::deploy::manifest::Manifest;
+
+/// Snapshot of the state of a single target path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Fingerprint {
+	/// A regular file.
+	File {
+		/// Size of the file in bytes.
+		len: u64,
+
+		/// Last modification time of the file (if supported by the platform).
+		modified: Option<SystemTime>,
+	},
+
+	/// A directory. Changes to the children are not tracked, as they are
+	/// recorded individually in the manifest.
+	Directory,
+
+	/// A symlink pointing to the contained path.
+	Symlink(PathBuf),
+}
+
+impl Fingerprint {
+	/// Takes a snapshot of `path`.
+	///
+	/// Returns `None` if nothing exists at `path`.
+	fn of(path: &Path) -> io::Result<Option<Self>> {
+		let metadata = match path.symlink_metadata() {
+			Ok(metadata) => metadata,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+			Err(err) => return Err(err),
+		};
+
+		let fingerprint = if metadata.is_symlink() {
+			Self::Symlink(std::fs::read_link(path)?)
+		} else if metadata.is_dir() {
+			Self::Directory
+		} else {
+			Self::File {
+				len: metadata.len(),
+				modified: metadata.modified().ok(),
+			}
+		};
+
+		Ok(Some(fingerprint))
+	}
+}
+
+/// Kind of change which was detected for a target path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Drift {
+	/// The item was created after it was removed.
+	Created,
+
+	/// The content, type or link target of the item changed.
+	Modified,
+
+	/// The item was removed.
+	Removed,
+}
+
+impl fmt::Display for Drift {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Created => "created",
+			Self::Modified => "modified",
+			Self::Removed => "removed",
+		})
+	}
+}
+
+/// Watches target paths for changes made outside of `punktf`.
+#[derive(Debug, Clone, Default)]
+pub struct DriftWatcher {
+	/// Last known state of each watched path.
+	snapshots: BTreeMap<PathBuf, Option<Fingerprint>>,
+}
+
+impl DriftWatcher {
+	/// Creates a new watcher for `paths` with the current state of them as
+	/// baseline.
+	pub fn new<I: IntoIterator<Item = PathBuf>>(paths: I) -> Self {
+		let snapshots = paths
+			.into_iter()
+			.map(|path| {
+				let fingerprint = Self::fingerprint(&path);
+				(path, fingerprint)
+			})
+			.collect();
+
+		Self { snapshots }
+	}
+
+	/// Creates a new watcher for all target paths recorded in `manifest`.
+	pub fn from_manifest(manifest: &Manifest) -> Self {
+		Self::new(
+			manifest
+				.dotfiles
+				.iter()
+				.chain(manifest.symlinks.iter())
+				.cloned(),
+		)
+	}
+
+	/// Returns the number of watched paths.
+	pub fn len(&self) -> usize {
+		self.snapshots.len()
+	}
+
+	/// Checks if no paths are watched.
+	pub fn is_empty(&self) -> bool {
+		self.snapshots.is_empty()
+	}
+
+	/// Takes a snapshot of `path`.
+	///
+	/// A path which can not be inspected is logged and treated as missing.
+	fn fingerprint(path: &Path) -> Option<Fingerprint> {
+		Fingerprint::of(path).unwrap_or_else(|err| {
+			log::warn!("[{}] Failed to inspect target: {err}", path.display());
+			None
+		})
+	}
+
+	/// Compares all watched paths against their last known state.
+	///
+	/// Returns all paths which changed since the last call (or since the
+	/// watcher was created) in sorted order. The current state becomes the new
+	/// baseline, so each change is only reported once.
+	pub fn poll(&mut self) -> Vec<(PathBuf, Drift)> {
+		let mut drifts = Vec::new();
+
+		for (path, snapshot) in &mut self.snapshots {
+			let current = Self::fingerprint(path);
+
+			let drift = match (&*snapshot, &current) {
+				(None, Some(_)) => Drift::Created,
+				(Some(_), None) => Drift::Removed,
+				(old, new) if old != new => Drift::Modified,
+				_ => continue,
+			};
+
+			drifts.push((path.clone(), drift));
+			*snapshot = current;
+		}
+
+		drifts
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn drift_watcher() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+
+		let changed = dir.join("changed");
+		let removed = dir.join("removed");
+		let created = dir.join("created");
+		let untouched = dir.join("untouched");
+		std::fs::write(&changed, "a")?;
+		std::fs::write(&removed, "")?;
+		std::fs::write(&untouched, "")?;
+
+		let mut manifest = Manifest::new();
+		manifest
+			.dotfiles
+			.extend([changed.clone(), removed.clone(), created.clone(), untouched]);
+
+		let mut watcher = DriftWatcher::from_manifest(&manifest);
+		assert_eq!(watcher.len(), 4);
+		assert!(watcher.poll().is_empty());
+
+		std::fs::write(&changed, "ab")?;
+		std::fs::remove_file(&removed)?;
+		std::fs::write(&created, "")?;
+
+		assert_eq!(
+			watcher.poll(),
+			vec![
+				(changed, Drift::Modified),
+				(created, Drift::Created),
+				(removed, Drift::Removed),
+			]
+		);
+		assert!(watcher.poll().is_empty());
+
+		Ok(())
+	}
+}
@@ -0,0 +1,162 @@
+//! Lock which prevents concurrent deployments to the same target.
+//!
+//! Two simultaneous runs of `punktf` (e.g. a bootstrap script run by cron and
+//! a manual run) could otherwise interleave their writes. The lock is an
+//! exclusive lock on a [lock file](`LOCK_FILE_NAME`) in the target directory
+//! which contains the id of the process holding it.
+//!
+//! The lock is released when the [`DeployLock`] is dropped or the process
+//! exits. The lock file itself is never removed: another process may
+//! already have opened it and would otherwise lock a file which is no longer
+//! reachable under the path, while a third process locks a new file at the
+//! same path.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+/// Name of the lock file which is created in the locked directory.
+pub const LOCK_FILE_NAME: &str = ".punktf.lock";
+
+/// An error which occurs while acquiring a [`DeployLock`].
+#[derive(Error, Debug)]
+pub enum LockError {
+	/// The lock is already held by another process.
+	#[error(
+		"Another deployment is already running (lock `{}` is held by {}); \
+		 use `--no-lock` to deploy anyway",
+		path.display(),
+		pid.map(|pid| format!("process {pid}")).unwrap_or_else(|| String::from("an unknown process"))
+	)]
+	Locked {
+		/// Path of the lock file.
+		path: PathBuf,
+
+		/// Id of the process holding the lock, if it could be read.
+		pid: Option<u32>,
+	},
+
+	/// The lock file could not be created or locked.
+	#[error("Failed to acquire the deployment lock `{}`: {source}", path.display())]
+	Io {
+		/// Path of the lock file.
+		path: PathBuf,
+
+		/// The underlying error.
+		source: io::Error,
+	},
+}
+
+/// An exclusive lock for deployments to a directory.
+#[derive(Debug)]
+pub struct DeployLock {
+	/// The locked lock file.
+	file: File,
+
+	/// Path of the lock file.
+	path: PathBuf,
+}
+
+impl DeployLock {
+	/// Acquires the lock for the directory `dir`.
+	///
+	/// This does not wait for the lock to be released if it is already held.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the lock is already held by another process or
+	/// the lock file could not be created.
+	pub fn acquire(dir: &Path) -> Result<Self, LockError> {
+		let path = dir.join(LOCK_FILE_NAME);
+
+		let io_err = |source| LockError::Io {
+			path: path.clone(),
+			source,
+		};
+
+		let mut file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(false)
+			.open(&path)
+			.map_err(io_err)?;
+
+		match file.try_lock() {
+			Ok(()) => {}
+			Err(TryLockError::WouldBlock) => {
+				let mut content = String::new();
+				let pid = file
+					.read_to_string(&mut content)
+					.ok()
+					.and_then(|_| content.trim().parse().ok());
+
+				return Err(LockError::Locked { path, pid });
+			}
+			Err(TryLockError::Error(err)) => return Err(io_err(err)),
+		}
+
+		file.set_len(0)
+			.and_then(|_| file.rewind())
+			.and_then(|_| write!(file, "{}", std::process::id()))
+			.and_then(|_| file.flush())
+			.map_err(io_err)?;
+
+		log::debug!("[{}] Acquired deployment lock", path.display());
+
+		Ok(Self { file, path })
+	}
+
+	/// Returns the path of the lock file.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl Drop for DeployLock {
+	fn drop(&mut self) {
+		// Only the process id is cleared, the file is kept (related:
+		// [module](`self`)).
+		if let Err(err) = self.file.set_len(0) {
+			log::debug!(
+				"[{}] Failed to clear deployment lock: {err}",
+				self.path.display()
+			);
+		}
+
+		let _ = self.file.unlock();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn deploy_lock() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+
+		let lock = DeployLock::acquire(&dir).expect("Failed to acquire lock");
+		assert_eq!(
+			std::fs::read_to_string(lock.path())?,
+			std::process::id().to_string()
+		);
+
+		match DeployLock::acquire(&dir) {
+			Err(LockError::Locked { pid, .. }) => assert_eq!(pid, Some(std::process::id())),
+			other => panic!("Expected the lock to be held: {other:?}"),
+		}
+
+		drop(lock);
+		assert_eq!(std::fs::read_to_string(dir.join(LOCK_FILE_NAME))?, "");
+
+		let lock = DeployLock::acquire(&dir).expect("Failed to acquire released lock");
+		drop(lock);
+
+		Ok(())
+	}
+}
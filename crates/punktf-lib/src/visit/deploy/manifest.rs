@@ -0,0 +1,205 @@
+//! Record of all items which were deployed by `punktf`.
+//!
+//! The manifest is used to find items which were deployed by a previous
+//! deployment but are no longer part of the profile (orphans). These can then
+//! be removed from the target (pruned), instead of leaving stale copies
+//! behind (e.g. after a dotfile was renamed).
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::visit::deploy::deployment::{Deployment, ItemStatus};
+
+/// Record of all target paths which were deployed by `punktf` for a profile.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Manifest {
+	/// Target paths of all deployed dotfiles (including the children of
+	/// directory dotfiles).
+	pub dotfiles: BTreeSet<PathBuf>,
+
+	/// Target paths of all deployed symlinks.
+	pub symlinks: BTreeSet<PathBuf>,
+
+	/// Id of the last deployment which updated the manifest (related:
+	/// [`Deployment::id`](`crate::visit::deploy::deployment::Deployment::id`)).
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub deployment_id: Option<Uuid>,
+}
+
+impl Manifest {
+	/// Creates a new, empty, manifest.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Checks if `path` is the target path of an item which was deployed by
+	/// `punktf`.
+	pub fn contains(&self, path: &Path) -> bool {
+		self.dotfiles.contains(path) || self.symlinks.contains(path)
+	}
+
+	/// Records all items which were successfully deployed by `deployment` and
+	/// its id.
+	///
+	/// Existing entries are retained, even if they are no longer part of the
+	/// deployment. Use [`Manifest::prune`] to remove them.
+	pub fn update(&mut self, deployment: &Deployment) {
+		self.deployment_id = Some(*deployment.id());
+
+		self.dotfiles.extend(
+			deployment
+				.dotfiles()
+				.iter()
+				.filter(|(_, dotfile)| dotfile.status().is_success())
+				.map(|(path, _)| path.clone()),
+		);
+
+		self.symlinks.extend(
+			deployment
+				.symlinks()
+				.iter()
+				.filter(|(_, symlink)| symlink.status.is_success())
+				.map(|(path, _)| path.clone()),
+		);
+	}
+
+	/// Returns all recorded target paths which are no longer part of
+	/// `deployment`.
+	///
+	/// Items which are part of `deployment` are never orphans, regardless of
+	/// their status (e.g. a failed or skipped item is still part of the
+	/// profile).
+	pub fn orphans<'a>(&'a self, deployment: &'a Deployment) -> impl Iterator<Item = &'a Path> {
+		let dotfiles = self
+			.dotfiles
+			.iter()
+			.filter(|path| !deployment.dotfiles().contains_key(*path));
+
+		let symlinks = self
+			.symlinks
+			.iter()
+			.filter(|path| !deployment.symlinks().contains_key(*path));
+
+		dotfiles.chain(symlinks).map(PathBuf::as_path)
+	}
+
+	/// Removes all orphans (related: [`Manifest::orphans`]) from the target
+	/// and from the manifest.
+	///
+	/// Directories are only removed if they are empty. Orphans which no longer
+	/// exist are dropped from the manifest without further actions.
+	///
+	/// If `dry_run` is set, nothing is removed, neither from the target nor
+	/// from the manifest.
+	///
+	/// Returns the status of the removal for each orphan.
+	pub fn prune(
+		&mut self,
+		deployment: &Deployment,
+		dry_run: bool,
+	) -> BTreeMap<PathBuf, ItemStatus> {
+		let orphans: BTreeSet<PathBuf> = self.orphans(deployment).map(Path::to_path_buf).collect();
+
+		let mut pruned = BTreeMap::new();
+
+		// Reverse order ensures that the children of a directory are removed
+		// before the directory itself.
+		for orphan in orphans.into_iter().rev() {
+			let status = if dry_run {
+				ItemStatus::success()
+			} else {
+				match remove(&orphan) {
+					Ok(true) => ItemStatus::success(),
+					Ok(false) => ItemStatus::skipped("Directory is not empty"),
+					Err(err) => ItemStatus::failed(format!("Failed to remove: {err}")),
+				}
+			};
+
+			if !dry_run && status.is_success() {
+				self.dotfiles.remove(&orphan);
+				self.symlinks.remove(&orphan);
+			}
+
+			pruned.insert(orphan, status);
+		}
+
+		pruned
+	}
+}
+
+/// Removes the file, symlink or empty directory at `path`.
+///
+/// Returns `false` if `path` is a directory which is not empty.
+fn remove(path: &Path) -> io::Result<bool> {
+	let metadata = match path.symlink_metadata() {
+		Ok(metadata) => metadata,
+		Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(true),
+		Err(err) => return Err(err),
+	};
+
+	if metadata.is_dir() {
+		if std::fs::read_dir(path)?.next().is_some() {
+			return Ok(false);
+		}
+
+		std::fs::remove_dir(path)?;
+	} else {
+		std::fs::remove_file(path)?;
+	}
+
+	Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn manifest_prune() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("old_dir"))?;
+
+		let kept = dir.join("kept");
+		let old = dir.join("old");
+		let old_child = dir.join("old_dir/child");
+		std::fs::write(&kept, "")?;
+		std::fs::write(&old, "")?;
+		std::fs::write(&old_child, "")?;
+
+		let root = dir.to_path_buf();
+
+		let mut builder = Deployment::build();
+		builder.add_child(kept.clone(), root.clone(), ItemStatus::success());
+		builder.add_child(old.clone(), root.clone(), ItemStatus::success());
+		builder.add_child(dir.join("old_dir"), root.clone(), ItemStatus::success());
+		builder.add_child(old_child.clone(), root.clone(), ItemStatus::success());
+
+		let mut manifest = Manifest::new();
+		manifest.update(&builder.success());
+
+		let mut builder = Deployment::build();
+		builder.add_child(kept.clone(), root, ItemStatus::success());
+		let deployment = builder.success();
+
+		assert_eq!(manifest.orphans(&deployment).count(), 3);
+
+		let pruned = manifest.prune(&deployment, true);
+		assert_eq!(pruned.len(), 3);
+		assert!(old.exists());
+
+		let pruned = manifest.prune(&deployment, false);
+		assert!(pruned.values().all(ItemStatus::is_success));
+		assert!(kept.exists());
+		assert!(!old.exists());
+		assert!(!dir.join("old_dir").exists());
+		assert_eq!(manifest.dotfiles, BTreeSet::from([kept]));
+
+		Ok(())
+	}
+}
@@ -0,0 +1,249 @@
+//! Line based three-way merge used by
+//! [`MergeMode::Merge`](`crate::profile::MergeMode::Merge`).
+//!
+//! The merge combines the content of the previous deployment (base), the
+//! current content of the target (ours) and the newly rendered content
+//! (theirs). Changes made on only one side are applied, changes made on both
+//! sides are marked with conflict markers:
+//!
+//! ```text
+//! <<<<<<< target
+//! local change
+//! ||||||| base
+//! previously deployed
+//! =======
+//! upstream change
+//! >>>>>>> source
+//! ```
+
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+
+/// Marker which starts a conflict.
+pub const MARKER_OURS: &str = "<<<<<<< target";
+
+/// Marker which starts the base section of a conflict.
+pub const MARKER_BASE: &str = "||||||| base";
+
+/// Marker which separates both sides of a conflict.
+pub const MARKER_SEPARATOR: &str = "=======";
+
+/// Marker which ends a conflict.
+pub const MARKER_THEIRS: &str = ">>>>>>> source";
+
+/// The result of a [`merge`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merged {
+	/// The merged content, including conflict markers.
+	pub content: String,
+
+	/// Number of conflicting hunks.
+	pub conflicts: usize,
+}
+
+impl Merged {
+	/// Checks if the merge finished without conflicts.
+	pub const fn is_clean(&self) -> bool {
+		self.conflicts == 0
+	}
+}
+
+/// Returns for each line of `old` the index of the matching line in `new`.
+fn matches(old: &[&str], new: &[&str]) -> Vec<Option<usize>> {
+	let mut map = vec![None; old.len()];
+
+	for op in capture_diff_slices(Algorithm::Myers, old, new) {
+		if let DiffOp::Equal {
+			old_index,
+			new_index,
+			len,
+		} = op
+		{
+			for offset in 0..len {
+				map[old_index + offset] = Some(new_index + offset);
+			}
+		}
+	}
+
+	map
+}
+
+/// Splits `content` into lines without their line feeds.
+fn lines(content: &str) -> Vec<&str> {
+	if content.is_empty() {
+		return Vec::new();
+	}
+
+	content
+		.strip_suffix('\n')
+		.unwrap_or(content)
+		.split('\n')
+		.collect()
+}
+
+/// Appends `lines` to `out`, each terminated by a line feed.
+fn push_lines(out: &mut String, lines: &[&str]) {
+	for line in lines {
+		out.push_str(line);
+		out.push('\n');
+	}
+}
+
+/// Appends a conflict between `ours` and `theirs` to `out`.
+///
+/// The base section is omitted if `base` is `None`.
+fn push_conflict(out: &mut String, ours: &[&str], base: Option<&[&str]>, theirs: &[&str]) {
+	push_lines(out, &[MARKER_OURS]);
+	push_lines(out, ours);
+
+	if let Some(base) = base {
+		push_lines(out, &[MARKER_BASE]);
+		push_lines(out, base);
+	}
+
+	push_lines(out, &[MARKER_SEPARATOR]);
+	push_lines(out, theirs);
+	push_lines(out, &[MARKER_THEIRS]);
+}
+
+/// Merges the changes between `base` and `ours` and between `base` and
+/// `theirs`.
+///
+/// Without a `base` (e.g. the dotfile was never deployed with
+/// [`MergeMode::Merge`](`crate::profile::MergeMode::Merge`) before), the
+/// origin of a difference can not be determined. In that case every hunk in
+/// which `ours` and `theirs` differ is a conflict.
+///
+/// Whether the merged content ends with a line feed is taken from `theirs`.
+pub fn merge(base: Option<&str>, ours: &str, theirs: &str) -> Merged {
+	let ours_lines = lines(ours);
+	let theirs_lines = lines(theirs);
+
+	// Without a base, the lines common to both sides act as the base.
+	let (base_lines, has_base) = match base {
+		Some(base) => (lines(base), true),
+		None => (
+			matches(&ours_lines, &theirs_lines)
+				.iter()
+				.zip(&ours_lines)
+				.filter(|(matched, _)| matched.is_some())
+				.map(|(_, line)| *line)
+				.collect(),
+			false,
+		),
+	};
+
+	let to_ours = matches(&base_lines, &ours_lines);
+	let to_theirs = matches(&base_lines, &theirs_lines);
+
+	// Base lines which are unchanged on both sides.
+	let stable = to_ours
+		.iter()
+		.zip(&to_theirs)
+		.enumerate()
+		.filter_map(|(base_idx, (ours_idx, theirs_idx))| {
+			Some((base_idx, (*ours_idx)?, (*theirs_idx)?))
+		})
+		.chain(std::iter::once((
+			base_lines.len(),
+			ours_lines.len(),
+			theirs_lines.len(),
+		)));
+
+	let mut content = String::with_capacity(theirs.len());
+	let mut conflicts = 0;
+	let (mut base_pos, mut ours_pos, mut theirs_pos) = (0, 0, 0);
+
+	for (base_idx, ours_idx, theirs_idx) in stable {
+		let base_hunk = &base_lines[base_pos..base_idx];
+		let ours_hunk = &ours_lines[ours_pos..ours_idx];
+		let theirs_hunk = &theirs_lines[theirs_pos..theirs_idx];
+
+		if ours_hunk == theirs_hunk {
+			push_lines(&mut content, ours_hunk);
+		} else if has_base && ours_hunk == base_hunk {
+			push_lines(&mut content, theirs_hunk);
+		} else if has_base && theirs_hunk == base_hunk {
+			push_lines(&mut content, ours_hunk);
+		} else {
+			conflicts += 1;
+			push_conflict(
+				&mut content,
+				ours_hunk,
+				has_base.then_some(base_hunk),
+				theirs_hunk,
+			);
+		}
+
+		// The stable line itself
+		if let Some(line) = ours_lines.get(ours_idx) {
+			push_lines(&mut content, &[line]);
+		}
+
+		(base_pos, ours_pos, theirs_pos) = (base_idx + 1, ours_idx + 1, theirs_idx + 1);
+	}
+
+	if !theirs.is_empty()
+		&& !theirs.ends_with('\n')
+		&& !content.ends_with(&format!("{MARKER_THEIRS}\n"))
+	{
+		content.pop();
+	}
+
+	Merged { content, conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use pretty_assertions::assert_eq;
+
+	#[test]
+	fn merge_changes() {
+		crate::tests::setup_test_env();
+
+		let base = "a\nb\nc\nd\n";
+		let ours = "a\nB\nc\nd\n";
+		let theirs = "a\nb\nc\nD\ne\n";
+
+		assert_eq!(
+			merge(Some(base), ours, theirs),
+			Merged {
+				content: String::from("a\nB\nc\nD\ne\n"),
+				conflicts: 0
+			}
+		);
+
+		// Unchanged target takes the new content
+		assert_eq!(merge(Some(base), base, theirs).content, theirs);
+
+		// Same change on both sides
+		assert_eq!(merge(Some(base), theirs, theirs).content, theirs);
+
+		let merged = merge(Some(base), "a\nX\nc\nd\n", "a\nY\nc\nd\n");
+		assert_eq!(merged.conflicts, 1);
+		assert_eq!(
+			merged.content,
+			format!(
+				"a\n{MARKER_OURS}\nX\n{MARKER_BASE}\nb\n{MARKER_SEPARATOR}\nY\n{MARKER_THEIRS}\nc\nd\n"
+			)
+		);
+	}
+
+	#[test]
+	fn merge_without_base() {
+		crate::tests::setup_test_env();
+
+		assert!(merge(None, "a\nb\n", "a\nb\n").is_clean());
+		assert_eq!(merge(None, "a", "a").content, "a");
+
+		let merged = merge(None, "a\nX\nc", "a\nc\nd");
+		assert_eq!(merged.conflicts, 2);
+		assert_eq!(
+			merged.content,
+			format!(
+				"a\n{MARKER_OURS}\nX\n{MARKER_SEPARATOR}\n{MARKER_THEIRS}\nc\n{MARKER_OURS}\n{MARKER_SEPARATOR}\nd\n{MARKER_THEIRS}\n"
+			)
+		);
+	}
+}
@@ -1,18 +1,47 @@
 //! A [`Visit`](`crate::visit::Visitor`) implementation which deploys the items.
 
+pub mod acl;
+pub mod ask;
+pub mod attributes;
+pub mod audit;
 pub mod deployment;
+mod dotfile_hooks;
+pub mod drift;
+pub mod lock;
+pub mod manifest;
+pub mod merge;
+pub mod progress;
+pub mod report;
+pub mod space;
+pub mod stats;
+pub mod target;
 
 use cfg_if::cfg_if;
-use color_eyre::eyre::Context;
+use thiserror::Error;
 
-use crate::profile::{source::PunktfSource, MergeMode};
+use crate::profile::link::{self, LinkKind};
+use crate::profile::{source::PunktfSource, BinaryMode, DeployStrategy, MergeMode};
 use crate::visit::*;
 
 use crate::profile::transform::Transform as _;
 use crate::profile::LayeredProfile;
-use crate::visit::deploy::deployment::{Deployment, DeploymentBuilder, ItemStatus};
-use std::borrow::Borrow;
-use std::path::Path;
+use crate::temp::TempPath;
+use crate::template::cache::TemplateCache;
+use crate::visit::deploy::ask::{MergeDecision, MergeRequest, MergeStrategy};
+use crate::visit::deploy::attributes::{Attributes, Writable};
+use crate::visit::deploy::deployment::{
+	CommandKind, Deployment, DeploymentBuilder, DeploymentStatus, ExecutedHook, ItemStatus,
+	PriorityWinner, StatusAggregation,
+};
+use crate::visit::deploy::dotfile_hooks::DotfileHooks;
+use crate::visit::deploy::lock::{DeployLock, LockError};
+use crate::visit::deploy::manifest::Manifest;
+use crate::visit::deploy::merge::Merged;
+use crate::visit::deploy::progress::{Counter, Progress, Tracker};
+use crate::visit::deploy::space::{SpaceError, SpaceEstimator};
+use crate::visit::deploy::target::TargetError;
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
 
 use crate::visit::{ResolvingVisitor, TemplateVisitor};
 
@@ -25,21 +54,181 @@ enum SafeRead {
 	Binary(Vec<u8>),
 }
 
-/// Reads the contents of a file, first trying to interpret them as a string and if that fails
-/// returning the raw bytes.
-fn safe_read<P: AsRef<Path>>(path: P) -> io::Result<SafeRead> {
-	/// Inner function to reduce size of monomorphization.
-	fn inner(path: &Path) -> io::Result<SafeRead> {
-		match std::fs::read_to_string(path) {
+/// Reads the contents of the source of a file according to the
+/// [`BinaryMode`](`crate::profile::BinaryMode`) of its dotfile.
+///
+/// With [`BinaryMode::Auto`](`crate::profile::BinaryMode::Auto`) it first
+/// tries to interpret them as a string and if that fails returns the raw
+/// bytes.
+///
+/// Encrypted dotfiles are decrypted (related: [`File::read_source`]).
+///
+/// # Errors
+///
+/// An error of kind [`io::ErrorKind::InvalidData`] is returned if the dotfile
+/// is always treated as text but the contents are not valid UTF-8.
+fn safe_read(file: &File<'_>) -> io::Result<SafeRead> {
+	let content = file.read_source()?;
+
+	match file.dotfile().binary_mode() {
+		BinaryMode::Binary => Ok(SafeRead::Binary(content)),
+		BinaryMode::Text => String::from_utf8(content)
+			.map(SafeRead::String)
+			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+		BinaryMode::Auto => match String::from_utf8(content) {
 			Ok(s) => Ok(SafeRead::String(s)),
-			Err(err) if err.kind() == io::ErrorKind::InvalidData => {
-				std::fs::read(path).map(SafeRead::Binary)
+			Err(err) => Ok(SafeRead::Binary(err.into_bytes())),
+		},
+	}
+}
+
+/// Maps the absolute `target_path` of an item to a path inside of the
+/// `cache_dir`.
+///
+/// The whole (normalized) target path is mirrored inside the cache directory
+/// to avoid collisions between different targets.
+fn cache_path(cache_dir: &Path, target_path: &Path) -> PathBuf {
+	let mut path = cache_dir.to_path_buf();
+
+	for component in normalize_lexically(target_path).components() {
+		match component {
+			Component::Prefix(prefix) => {
+				// Windows drive letters/UNC prefixes (e.g. `C:`).
+				let prefix = prefix.as_os_str().to_string_lossy();
+				path.push(prefix.replace([':', '\\', '?'], ""));
 			}
-			Err(err) => Err(err),
+			Component::Normal(name) => path.push(name),
+			Component::RootDir | Component::CurDir | Component::ParentDir => {}
+		}
+	}
+
+	path
+}
+
+/// Name of the directory inside of [`DeployOptions::cache_dir`] which stores
+/// the content of the last deployment of each dotfile with
+/// [`MergeMode::Merge`].
+const MERGE_BASE_DIR: &str = "merge-base";
+
+/// Name of the directory inside of [`DeployOptions::cache_dir`] which stores
+/// the outputs of templates (related: [`DeployOptions::template_cache`]).
+const TEMPLATE_CACHE_DIR: &str = "templates";
+
+/// Writes `content` to `path` by first writing it to a temporary file next to
+/// it and then renaming it. This prevents partially written files at `path`.
+fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+	let tmp = TempPath::sibling(path)?;
+
+	if let Some(parent) = path.parent() {
+		std::fs::create_dir_all(parent)?;
+	}
+
+	std::fs::write(tmp.path(), content)?;
+	std::fs::rename(tmp.path(), path)?;
+
+	// The temporary file was moved into place and must not be removed
+	let _ = tmp.keep();
+
+	Ok(())
+}
+
+/// Checks if `link_path` is a symlink pointing to `target`.
+fn links_to(link_path: &Path, target: &Path) -> bool {
+	std::fs::read_link(link_path).is_ok_and(|destination| destination == target)
+}
+
+/// Creates a file symlink at `link_path` pointing to `target`.
+///
+/// Any existing file or symlink at `link_path` is removed beforehand.
+fn replace_with_link(target: &Path, link_path: &Path) -> io::Result<()> {
+	if links_to(link_path, target) {
+		return Ok(());
+	}
+
+	if link_path.symlink_metadata().is_ok() {
+		std::fs::remove_file(link_path)?;
+	}
+
+	cfg_if! {
+		if #[cfg(unix)] {
+			std::os::unix::fs::symlink(target, link_path)
+		} else if #[cfg(windows)] {
+			std::os::windows::fs::symlink_file(target, link_path)
+		} else {
+			Err(io::Error::new(io::ErrorKind::Unsupported, "Link operations are only supported on unix and windows systems"))
 		}
 	}
+}
 
-	inner(path.as_ref())
+/// Creates a link of the given `kind` at `target_path` pointing to
+/// `source_path`.
+///
+/// Junctions are only supported on windows, on all other systems a symlink is
+/// created instead.
+#[cfg(any(unix, windows))]
+fn create_link(kind: LinkKind, source_path: &Path, target_path: &Path) -> io::Result<()> {
+	let metadata = source_path.symlink_metadata()?;
+
+	match kind {
+		LinkKind::Hardlink => {
+			if metadata.is_dir() {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidInput,
+					"Hard links can not point to directories",
+				));
+			}
+
+			std::fs::hard_link(source_path, target_path)
+		}
+		LinkKind::Junction => {
+			if !metadata.is_dir() {
+				return Err(io::Error::new(
+					io::ErrorKind::InvalidInput,
+					"Junctions can only point to directories",
+				));
+			}
+
+			cfg_if! {
+				if #[cfg(windows)] {
+					let output = std::process::Command::new("cmd")
+						.arg("/C")
+						.arg("mklink")
+						.arg("/J")
+						.arg(target_path)
+						.arg(source_path)
+						.output()?;
+
+					if output.status.success() {
+						Ok(())
+					} else {
+						Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()))
+					}
+				} else {
+					log::info!(
+						"[{}] Junctions are only supported on windows; Creating a symlink instead",
+						source_path.display()
+					);
+
+					create_link(LinkKind::Symlink, source_path, target_path)
+				}
+			}
+		}
+		LinkKind::Symlink => {
+			cfg_if! {
+				if #[cfg(unix)] {
+					std::os::unix::fs::symlink(source_path, target_path)
+				} else {
+					if metadata.is_dir() {
+						std::os::windows::fs::symlink_dir(source_path, target_path)
+					} else if metadata.is_file() {
+						std::os::windows::fs::symlink_file(source_path, target_path)
+					} else {
+						Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid type of link source"))
+					}
+				}
+			}
+		}
+	}
 }
 
 impl<'a> Item<'a> {
@@ -48,10 +237,7 @@ impl<'a> Item<'a> {
 	fn add_to_builder<S: Into<ItemStatus>>(&self, builder: &mut DeploymentBuilder, status: S) {
 		let status = status.into();
 
-		let resolved_target_path = self
-			.target_path
-			.canonicalize()
-			.unwrap_or_else(|_| self.target_path.clone());
+		let resolved_target_path = canonicalize_parent(&self.target_path);
 
 		match &self.kind {
 			Kind::Root(dotfile) => {
@@ -60,9 +246,7 @@ impl<'a> Item<'a> {
 			Kind::Child {
 				root_target_path, ..
 			} => {
-				let resolved_root_target_path = root_target_path
-					.canonicalize()
-					.unwrap_or_else(|_| root_target_path.clone());
+				let resolved_root_target_path = canonicalize_parent(root_target_path);
 
 				builder.add_child(resolved_target_path, resolved_root_target_path, status)
 			}
@@ -74,9 +258,10 @@ impl Symlink {
 	/// Adds this item to the given
 	/// [`DeploymentBuilder`](`crate::visit::deploy::deployment::DeploymentBuilder`).
 	fn add_to_builder<S: Into<ItemStatus>>(&self, builder: &mut DeploymentBuilder, status: S) {
-		builder.add_link(
+		builder.add_prioritized_link(
 			self.source_path.clone(),
 			self.target_path.clone(),
+			self.priority,
 			status.into(),
 		);
 	}
@@ -87,6 +272,9 @@ macro_rules! success {
 	($builder:expr, $item:expr) => {
 		$item.add_to_builder($builder, ItemStatus::success());
 	};
+	($builder:expr, $item:expr, $status:expr) => {
+		$item.add_to_builder($builder, $status);
+	};
 }
 
 /// Marks the given item as skipped.
@@ -103,6 +291,23 @@ macro_rules! skipped {
 	};
 }
 
+/// Marks the given item as skipped because of a conflict (related:
+/// [`DeploymentBuilder::add_conflict`]).
+///
+/// This will instantly return from the out function after reporting the skip.
+macro_rules! conflict {
+	($builder:expr, $item:expr, $reason:expr => $ret:expr ) => {
+		$item.add_to_builder($builder, ItemStatus::skipped($reason));
+		$builder.add_conflict($item.target_path.clone());
+		return Ok($ret);
+	};
+	($builder:expr, $item:expr, $reason:expr) => {
+		$item.add_to_builder($builder, ItemStatus::skipped($reason));
+		$builder.add_conflict($item.target_path.clone());
+		return Ok(());
+	};
+}
+
 /// Marks the given item as failed.
 ///
 /// This will instantly return from the out function after reporting the error.
@@ -121,14 +326,210 @@ macro_rules! failed {
 	};
 }
 
+/// Name of the environment variable which contains the unique id of the
+/// running deployment (related:
+/// [`Deployment::id`](`crate::visit::deploy::deployment::Deployment::id`)).
+///
+/// It is set for all hooks executed during a deployment.
+pub const DEPLOYMENT_ID_ENVVAR: &str = "PUNKTF_DEPLOYMENT_ID";
+
+/// Executes all on-failure-hooks of `profile` for the failed `deployment`.
+///
+/// Errors are only logged, as the deployment already failed.
+fn run_on_failure_hooks(
+	source: &PunktfSource,
+	profile: &LayeredProfile,
+	deployment: &mut Deployment,
+	reason: &str,
+) {
+	let mut failed_items: Vec<_> = deployment
+		.dotfiles()
+		.iter()
+		.filter(|(_, dotfile)| dotfile.status().is_failed())
+		.map(|(path, _)| path)
+		.chain(
+			deployment
+				.symlinks()
+				.iter()
+				.filter(|(_, link)| link.status().is_failed())
+				.map(|(path, _)| path),
+		)
+		.map(|path| path.display().to_string())
+		.collect();
+	failed_items.sort();
+	let failed_items = failed_items.join("\n");
+
+	for hook in profile.on_failure_hooks() {
+		log::info!("Executing on-failure-hook: {}", hook.command());
+
+		let id = deployment.id().to_string();
+		let envs = [
+			(DEPLOYMENT_ID_ENVVAR, id.as_str()),
+			("PUNKTF_FAILURE_REASON", reason),
+			("PUNKTF_FAILED_ITEMS", &failed_items),
+		];
+
+		let result = hook
+			.run_with_env(source.profiles(), profile.env().iter().chain(envs))
+			.and_then(|output| {
+				output.log_lines();
+				deployment.add_hook(ExecutedHook::new(
+					CommandKind::OnFailureHook,
+					hook.command().to_string(),
+					&output,
+				));
+				output.exit_ok()
+			});
+
+		if let Err(err) = result {
+			log::error!("Failed to execute on-failure-hook ({})", err);
+		}
+	}
+}
+
+/// Decides about existing targets of items with [`MergeMode::Ask`] without
+/// asking (related: [`DeployOptions::merge_policy`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergePolicy {
+	/// Overwrites the existing target.
+	Overwrite,
+
+	/// Keeps the existing target. The item is reported as conflict.
+	Keep,
+
+	/// Keeps the existing target and marks the item as failed.
+	Fail,
+}
+
 /// Configuration options for the [`Deployer`].
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DeployOptions {
 	/// If this flag is set, it will prevent any write operations from occurring
 	/// during the deployment.
 	///
 	/// This includes write, copy and directory creation operations.
 	pub dry_run: bool,
+
+	/// If this flag is set, the space required by the deployment is
+	/// estimated before any item is deployed. If any target filesystem does
+	/// not have enough space available, the deployment fails without writing
+	/// anything.
+	pub check_free_space: bool,
+
+	/// Directory managed by `punktf` which is used to store the rendered
+	/// contents of dotfiles deployed with
+	/// [`DeployStrategy::CacheLink`](`crate::profile::DeployStrategy::CacheLink`)
+	/// and the last deployed contents of dotfiles with [`MergeMode::Merge`].
+	///
+	/// If this is not set, all dotfiles with this strategy will fail to deploy
+	/// and dotfiles with [`MergeMode::Merge`] are merged without a base.
+	pub cache_dir: Option<PathBuf>,
+
+	/// If this flag is set, the deployment fails if any item was skipped
+	/// because of a conflict (e.g. merge mode `keep`, a denied merge or a
+	/// rejected item). The items are still processed the same way.
+	pub strict: bool,
+
+	/// If this flag is set, no [lock](`lock::DeployLock`) is acquired for the
+	/// deployment. This allows concurrent deployments to the same target,
+	/// which can interleave their writes.
+	pub no_lock: bool,
+
+	/// If set, all target paths are prefixed with this directory (similar to
+	/// `DESTDIR` of `make`). This allows to stage a whole deployment into a
+	/// scratch directory, e.g. for packaging or inspection.
+	pub destdir: Option<PathBuf>,
+
+	/// Filter which selects the dotfiles to deploy by their tags.
+	pub filter: TagFilter,
+
+	/// If this flag is set, no commands defined by the profile (e.g. hooks)
+	/// are executed. All commands which would have been executed are recorded
+	/// in the deployment instead (related:
+	/// [`Deployment::blocked`](`crate::visit::deploy::deployment::Deployment::blocked`)).
+	///
+	/// Commands are also never executed for untrusted profiles (related:
+	/// [`LayeredProfile::trusted`]).
+	pub no_exec: bool,
+
+	/// If set, items with [`MergeMode::Ask`] are never asked about but
+	/// handled according to the policy. This allows unattended deployments
+	/// which never block on a prompt.
+	pub merge_policy: Option<MergePolicy>,
+
+	/// If this flag is set, the outputs of templates are additionally stored
+	/// in [`DeployOptions::cache_dir`], so that templates which did not
+	/// change are not resolved again by following deployments (related:
+	/// [`TemplateCache`]).
+	///
+	/// Within a single deployment, the outputs are always cached.
+	pub template_cache: bool,
+
+	/// If this flag is set, read-only targets are made writable before they
+	/// are overwritten and made read-only again afterwards. Such items are
+	/// recorded with [`ItemStatus::Forced`].
+	///
+	/// Without it, overwriting a read-only target fails.
+	pub force: bool,
+
+	/// If set, existing targets are only ever overwritten if they are
+	/// recorded in this manifest (i.e. they were deployed by `punktf` before)
+	/// or were deployed earlier during the same deployment. All other
+	/// existing targets are kept regardless of the merge mode and reported as
+	/// conflicts.
+	///
+	/// This prevents the accidental destruction of hand written files, e.g.
+	/// when deploying to a new machine.
+	pub only_managed: Option<Manifest>,
+}
+
+/// An error which aborts a deployment as a whole.
+///
+/// Errors which only affect a single item are not reported with this type,
+/// but are recorded in the
+/// [`Deployment`](`crate::visit::deploy::deployment::Deployment`) instead.
+#[derive(Error, Debug)]
+pub enum DeployError {
+	/// The profile has no target path, but at least one dotfile depends on it
+	/// (related: [`LayeredProfile::target_path`]).
+	#[error("No target path for the deployment set")]
+	MissingTarget,
+
+	/// A dotfile references a named target which is not defined by the
+	/// profile (related: [`LayeredProfile::named_target`]).
+	#[error("No target named `{0}` defined")]
+	UnknownTarget(String),
+
+	/// The pre-check of the target directory failed.
+	#[error(transparent)]
+	Target(#[from] TargetError),
+
+	/// The pre-check of the available disk space failed.
+	#[error(transparent)]
+	Space(#[from] SpaceError),
+
+	/// The deployment lock could not be acquired.
+	#[error(transparent)]
+	Lock(#[from] LockError),
+
+	/// A pre-hook failed to execute.
+	#[error("Failed to execute pre-hook `{command}`: {source}")]
+	PreHook {
+		/// Command of the hook.
+		command: String,
+
+		/// The underlying error.
+		source: Box<dyn std::error::Error + Send + Sync>,
+	},
+
+	/// Walking the items of the profile failed.
+	#[error("Failed to walk the profile: {0}")]
+	Walk(String),
+
+	/// The profile has no dotfile with the given path (related:
+	/// [`Deployer::deploy_single`]).
+	#[error("No dotfile with the path `{}` found in the profile", .0.display())]
+	UnknownDotfile(PathBuf),
 }
 
 /// Responsible for deploying a [profile](`crate::profile::Profile`).
@@ -137,16 +538,18 @@ pub struct DeployOptions {
 /// directory dotfile, parsing and resolving of templates and the actual
 /// writing of the dotfile to the target destination.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Deployer<F> {
+pub struct Deployer<M> {
 	/// Configuration options
 	options: DeployOptions,
 
-	/// This function gets called when a dotfile at the target destination
-	/// already exists and the merge mode is
+	/// This strategy gets asked when the target of an item already exists
+	/// and the merge mode is
 	/// [MergeMode::Ask](`crate::profile::MergeMode::Ask`).
-	///
-	/// The arguments for the function are (dotfile_source_path, dotfile_target_path).
-	merge_ask_fn: F,
+	merge_strategy: M,
+
+	/// Set once the merge strategy aborted the deployment. All following
+	/// items are skipped.
+	aborted: bool,
 
 	/// Builder for the deployment.
 	///
@@ -155,32 +558,122 @@ pub struct Deployer<F> {
 	builder: DeploymentBuilder,
 }
 
-impl<F> Deployer<F>
+impl<M> Deployer<M>
 where
-	F: Fn(&Path, &Path) -> color_eyre::Result<bool>,
+	M: MergeStrategy,
 {
 	/// Creates a new instance.
-	pub fn new(options: DeployOptions, merge_ask_fn: F) -> Self {
+	///
+	/// `merge_strategy` decides about existing targets of items with
+	/// [MergeMode::Ask](`crate::profile::MergeMode::Ask`) (related:
+	/// [`MergeStrategy`]).
+	pub fn new(options: DeployOptions, merge_strategy: M) -> Self {
+		let mut builder = DeploymentBuilder::default();
+
+		if let Some(destdir) = &options.destdir {
+			builder.destdir(destdir.clone());
+		}
+
 		Self {
 			options,
-			merge_ask_fn,
-			builder: DeploymentBuilder::default(),
+			merge_strategy,
+			aborted: false,
+			builder,
 		}
 	}
 
 	/// Retrieves the finished deployment from this instance.
 	pub fn into_deployment(self) -> Deployment {
-		self.builder.finish()
+		let aggregation = if self.options.strict {
+			StatusAggregation::Strict
+		} else {
+			StatusAggregation::Lenient
+		};
+
+		self.builder.finish_with(aggregation)
 	}
 
 	/// Tries to deploy the given `profile`.
 	///
 	/// # Errors
 	///
-	/// Only hard errors will be returned as error, everything else will be
+	/// Only hard errors which occur before any item is deployed will be
+	/// returned as error (related: [`DeployError`]), everything else will be
 	/// recorded in the [Deployment](`crate::visit::deploy::deployment::Deployment`)
-	/// on a dotfile level.
-	pub fn deploy(self, source: &PunktfSource, profile: &mut LayeredProfile) -> Deployment {
+	/// on a dotfile level. This includes failing post-hooks, which mark the
+	/// whole deployment as failed.
+	pub fn deploy(
+		self,
+		source: &PunktfSource,
+		profile: &mut LayeredProfile,
+	) -> std::result::Result<Deployment, DeployError> {
+		self.run(source, profile, None)
+	}
+
+	/// Tries to deploy only the dotfile with the path `dotfile_path` (as given
+	/// in the profile) of `profile`.
+	///
+	/// The dotfile goes through the same steps as with [`Deployer::deploy`]
+	/// (e.g. priority and merge checks, templates and transformers). Links and
+	/// extra paths of the profile are not deployed. Hooks are executed like for a full
+	/// deployment, unless disabled with [`DeployOptions::no_exec`].
+	///
+	/// # Errors
+	///
+	/// Returns [`DeployError::UnknownDotfile`] if the profile has no dotfile
+	/// with the path. Otherwise see [`Deployer::deploy`].
+	pub fn deploy_single(
+		self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		dotfile_path: &Path,
+	) -> std::result::Result<Deployment, DeployError> {
+		let dotfile_path = normalize_lexically(dotfile_path);
+
+		let Some(dotfile) = profile
+			.dotfiles
+			.iter()
+			.find(|(_, dotfile)| normalize_lexically(&dotfile.path) == dotfile_path)
+		else {
+			return Err(DeployError::UnknownDotfile(dotfile_path));
+		};
+
+		let mut single = profile.clone();
+		single.dotfiles = vec![dotfile.clone()];
+		single.symlinks.clear();
+		single.extra_paths.clear();
+
+		self.run(source, &mut single, None)
+	}
+
+	/// Tries to deploy the given `profile` like [`Deployer::deploy`] and
+	/// reports the progress to `on_progress`.
+	///
+	/// To know the total number of items, the profile is walked once more
+	/// before the deployment starts.
+	///
+	/// # Errors
+	///
+	/// See [`Deployer::deploy`].
+	pub fn deploy_with_progress<P>(
+		self,
+		source: &PunktfSource,
+		profile: &mut LayeredProfile,
+		mut on_progress: P,
+	) -> std::result::Result<Deployment, DeployError>
+	where
+		P: FnMut(Progress<'_>),
+	{
+		self.run(source, profile, Some(&mut on_progress))
+	}
+
+	/// Deploys the given `profile` and optionally reports the progress.
+	fn run(
+		mut self,
+		source: &PunktfSource,
+		profile: &mut LayeredProfile,
+		on_progress: Option<&mut dyn FnMut(Progress<'_>)>,
+	) -> std::result::Result<Deployment, DeployError> {
 		// General flow:
 		//	- get deployment path
 		//	- check if dotfile already deployed
@@ -192,68 +685,332 @@ where
 		//	- YES:
 		//		- check merge operation
 		//		- if merge operation == ASK
-		//			- Run merge_ask_fn
+		//			- Ask merge_strategy
 		//			- FALSE: continue next dotfile
 		//	- check if template
 		//	- YES: resolve template
 		//	- IF FILE: write dotfile
 		//	- IF DIR: for each dotfile in dir START AT TOP
 
+		if profile.target_path().is_none()
+			&& profile
+				.dotfiles()
+				.any(|d| d.overwrite_target.is_none() && d.target_name.is_none())
+		{
+			return Err(DeployError::MissingTarget);
+		}
+
+		let target_root = match Walker::new(profile)
+			.with_destdir(self.options.destdir.clone())
+			.resolve_target_root()
+		{
+			Ok(Some(root)) => {
+				// The staged target directory usually does not exist yet
+				let create = profile.create_target() || self.options.destdir.is_some();
+				target::check(&root, create, self.options.dry_run)?;
+				Some(root)
+			}
+			Ok(None) => None,
+			Err(err) => {
+				log::warn!("Failed to resolve target directory for pre-check: {err}");
+				None
+			}
+		};
+
+		// Held until the deployment is finished.
+		let _lock = if self.options.dry_run || self.options.no_lock {
+			None
+		} else {
+			let dir = target_root
+				.as_deref()
+				.filter(|root| root.is_dir())
+				.unwrap_or_else(|| source.root());
+
+			Some(DeployLock::acquire(dir)?)
+		};
+
+		if self.options.check_free_space {
+			SpaceEstimator::new()
+				.with_destdir(self.options.destdir.clone())
+				.with_filter(self.options.filter.clone())
+				.estimate(source, profile)
+				.check()?;
+		}
+
+		let id = self.builder.id().to_string();
+		let hook_env = [(DEPLOYMENT_ID_ENVVAR, id.as_str())];
+		let exec = !self.options.no_exec && profile.trusted();
+
+		log::info!("[{id}] Starting deployment");
+
 		for hook in profile.pre_hooks() {
-			log::info!("Executing pre-hook: {}", hook.command());
+			if !exec {
+				log::warn!("[{id}] Blocked pre-hook: {}", hook.command());
+				self.builder
+					.add_blocked(CommandKind::PreHook, hook.command().to_string());
+				continue;
+			}
+
+			log::info!("[{id}] Executing pre-hook: {}", hook.command());
 			// No files are deployed yet, meaning if an error during hook
 			// execution occurs it will return with an error instead of just
 			// logging it.
 
-			if let Err(err) = hook
-				.execute(source.profiles())
-				.wrap_err("Failed to execute pre-hook")
-			{
-				log::error!("Failed to execute pre-hook ({})", err);
-				return self.builder.failed(err.to_string());
+			let result = hook
+				.run_with_env(source.profiles(), profile.env().iter().chain(hook_env))
+				.and_then(|output| {
+					output.log_lines();
+					self.builder.add_hook(ExecutedHook::new(
+						CommandKind::PreHook,
+						hook.command().to_string(),
+						&output,
+					));
+					output.exit_ok()
+				});
+
+			if let Err(err) = result {
+				return Err(DeployError::PreHook {
+					command: hook.command().to_string(),
+					source: err.into(),
+				});
 			};
 		}
 
-		let mut resolver = ResolvingVisitor(self);
-		let walker = Walker::new(profile);
-		if let Err(err) = walker.walk(source, &mut resolver) {
-			return resolver.into_inner().builder.failed(err.to_string());
+		let destdir = self.options.destdir.clone();
+		let filter = self.options.filter.clone();
+		let templates = match &self.options.cache_dir {
+			Some(cache_dir) if self.options.template_cache => {
+				TemplateCache::new().with_dir(cache_dir.join(TEMPLATE_CACHE_DIR))
+			}
+			_ => TemplateCache::new(),
+		};
+		let mut resolver = ResolvingVisitor::new(self).with_templates(templates);
+
+		if !exec {
+			for (kind, hook) in profile
+				.pre_dotfile_hooks()
+				.map(|hook| (CommandKind::PreDotfileHook, hook))
+				.chain(
+					profile
+						.post_dotfile_hooks()
+						.map(|hook| (CommandKind::PostDotfileHook, hook)),
+				) {
+				log::warn!("[{id}] Blocked {kind}: {}", hook.command());
+				resolver
+					.inner_mut()
+					.builder
+					.add_blocked(kind, hook.command().to_string());
+			}
+		}
+
+		let mut hooks = DotfileHooks::new(&mut resolver, exec);
+		let walker = Walker::new(profile)
+			.with_destdir(destdir)
+			.with_filter(filter)
+			.with_extra_paths(true);
+
+		let walked = match on_progress {
+			Some(on_progress) => {
+				let mut counter = Counter::default();
+				if let Err(err) = walker.walk(source, &mut counter) {
+					log::warn!("[{id}] Failed to count the items: {err}");
+				}
+
+				on_progress(Progress::Started {
+					total: counter.count,
+				});
+
+				walker.walk(
+					source,
+					&mut Tracker::new(&mut hooks, on_progress, counter.count),
+				)
+			}
+			None => walker.walk(source, &mut hooks),
+		};
+
+		if let Err(err) = walked {
+			return Err(DeployError::Walk(err.to_string()));
+		}
+
+		for (name, duration) in resolver.variables().timings() {
+			log::debug!("Variable `{name}` resolved in {duration:?}");
+		}
+
+		log::debug!(
+			"[{id}] Templates: {} cached, {} resolved",
+			resolver.templates().hits(),
+			resolver.templates().misses()
+		);
+
+		let mut this = resolver.into_inner();
+
+		let post_hook_err = profile.post_hooks().find_map(|hook| {
+			if !exec {
+				log::warn!("[{id}] Blocked post-hook: {}", hook.command());
+				this.builder
+					.add_blocked(CommandKind::PostHook, hook.command().to_string());
+				return None;
+			}
+
+			log::info!("[{id}] Executing post-hook: {}", hook.command());
+			let err = hook
+				.run_with_env(source.profiles(), profile.env().iter().chain(hook_env))
+				.and_then(|output| {
+					output.log_lines();
+					this.builder.add_hook(ExecutedHook::new(
+						CommandKind::PostHook,
+						hook.command().to_string(),
+						&output,
+					));
+					output.exit_ok()
+				})
+				.err()?;
+			log::error!("Failed to execute post-hook ({})", err);
+			Some(err)
+		});
+
+		let mut deployment = match post_hook_err {
+			Some(err) => this.builder.failed(err.to_string()),
+			None => this.into_deployment(),
+		};
+
+		if let DeploymentStatus::Failed(reason) = deployment.status().clone() {
+			if exec {
+				run_on_failure_hooks(source, profile, &mut deployment, &reason);
+			} else {
+				for hook in profile.on_failure_hooks() {
+					log::warn!("[{id}] Blocked on-failure-hook: {}", hook.command());
+					deployment.add_blocked(CommandKind::OnFailureHook, hook.command().to_string());
+				}
+			}
 		}
 
-		let this = resolver.into_inner();
+		log::info!("[{id}] Deployment finished: {}", deployment.status());
+
+		Ok(deployment)
+	}
+
+	/// Runs `f` and adds the time it took to the IO time of the item which is
+	/// currently deployed.
+	fn timed_io<T>(&mut self, f: impl FnOnce(&Self) -> T) -> T {
+		let start = Instant::now();
+		let result = f(self);
+		self.builder.add_io_time(start.elapsed());
+
+		result
+	}
+
+	/// Asks the merge strategy what to do with the existing target described
+	/// by `request`.
+	///
+	/// [`MergeDecision::OverwriteAll`] and [`MergeDecision::KeepAll`] switch
+	/// to [`MergePolicy::Overwrite`] and [`MergePolicy::Keep`] for all
+	/// following items and [`MergeDecision::Abort`] marks the deployment as
+	/// aborted.
+	fn ask_merge(
+		&mut self,
+		request: &MergeRequest<'_>,
+	) -> std::result::Result<MergeDecision, crate::error::BoxError> {
+		let decision = self.merge_strategy.decide(request)?;
 
-		for hook in profile.post_hooks() {
-			log::info!("Executing post-hook: {}", hook.command());
-			if let Err(err) = hook.execute(source.profiles()) {
-				log::error!("Failed to execute post-hook ({})", err);
-				return this.builder.failed(err.to_string());
+		match decision {
+			MergeDecision::OverwriteAll => {
+				self.options.merge_policy = Some(MergePolicy::Overwrite);
 			}
+			MergeDecision::KeepAll => {
+				self.options.merge_policy = Some(MergePolicy::Keep);
+			}
+			MergeDecision::Abort => {
+				self.aborted = true;
+			}
+			MergeDecision::Overwrite | MergeDecision::Keep => {}
 		}
 
-		this.into_deployment()
+		Ok(decision)
+	}
+
+	/// Checks if the existing target at `target_path` must not be overwritten,
+	/// as it is not managed by `punktf` (related:
+	/// [`DeployOptions::only_managed`]).
+	fn is_unmanaged(&self, target_path: &Path) -> bool {
+		self.options.only_managed.as_ref().is_some_and(|manifest| {
+			!manifest.contains(target_path)
+				&& self.builder.is_deployed(target_path) != Some(true)
+				&& self.builder.get_priority(target_path).is_none()
+		})
+	}
+
+	/// Describes the item with `priority` which is already deployed at
+	/// `target_path`, together with the profile which defines it.
+	fn priority_winner(
+		&self,
+		profile: &LayeredProfile,
+		target_path: &Path,
+		priority: Priority,
+	) -> PriorityWinner {
+		let name = |idx: &usize| profile.profile_names.get(*idx).cloned();
+
+		if let Some(link) = self.builder.get_deployed_symlink(target_path) {
+			PriorityWinner {
+				source: link.source.clone(),
+				profile: profile
+					.symlinks
+					.iter()
+					.find(|(_, symlink)| symlink.source_path == link.source)
+					.and_then(|(idx, _)| name(idx)),
+				priority,
+			}
+		} else if let Some(dotfile) = self.builder.get_deployed_dotfile(target_path) {
+			PriorityWinner {
+				source: dotfile.path.clone(),
+				profile: profile
+					.dotfiles
+					.iter()
+					.find(|(_, layered)| layered == dotfile)
+					.and_then(|(idx, _)| name(idx)),
+				priority,
+			}
+		} else {
+			PriorityWinner {
+				source: target_path.to_path_buf(),
+				profile: None,
+				priority,
+			}
+		}
 	}
 
 	/// Checks common things for a given file item before deploying it.
 	///
 	/// The returned boolean indicates if the deployment of the file should
 	/// continue.
-	fn pre_deploy_checks(&mut self, file: &File<'_>) -> color_eyre::Result<bool> {
-		let other_priority = self.builder.get_priority(&file.target_path);
+	fn pre_deploy_checks(
+		&mut self,
+		profile: &LayeredProfile,
+		file: &File<'_>,
+	) -> crate::error::Result<bool> {
+		if self.aborted {
+			skipped!(&mut self.builder, file, "Deployment was aborted" => false);
+		}
 
-		match (file.dotfile().priority.as_ref(), other_priority) {
+		let other_priority = self.builder.get_priority(&file.target_path).copied();
+
+		match (file.dotfile().priority.as_ref(), other_priority.as_ref()) {
 			(Some(a), Some(b)) if b > a => {
+				let winner = self.priority_winner(profile, &file.target_path, *b);
+
 				log::info!(
-					"[{}] Dotfile with higher priority is already deployed at {}",
+					"[{}] Item with higher priority is already deployed at {} ({winner})",
 					file.relative_source_path.display(),
 					file.target_path.display()
 				);
 
-				skipped!(&mut self.builder, file, "Dotfile with higher priority is already deployed" => false);
+				file.add_to_builder(&mut self.builder, ItemStatus::overruled(winner));
+				return Ok(false);
 			}
 			(_, _) => {}
 		};
 
-		if file.target_path.exists() {
+		if file.target_path.exists() && !self.is_cache_linked(file) {
 			// No previously deployed dotfile at `deploy_path`. Check for merge.
 
 			log::debug!(
@@ -262,6 +1019,15 @@ where
 				file.target_path.display()
 			);
 
+			if self.is_unmanaged(&file.target_path) {
+				log::info!(
+					"[{}] Skipping existing target which is not managed by punktf",
+					file.relative_source_path.display()
+				);
+
+				conflict!(&mut self.builder, file, "Target already exists and is not managed by punktf" => false);
+			}
+
 			match file.dotfile().merge.unwrap_or_default() {
 				MergeMode::Overwrite => {
 					log::info!(
@@ -275,7 +1041,29 @@ where
 						file.relative_source_path.display()
 					);
 
-					skipped!(&mut self.builder, file, format!("Dotfile already exists and merge mode is {:?}", MergeMode::Keep) => false);
+					conflict!(&mut self.builder, file, format!("Dotfile already exists and merge mode is {:?}", MergeMode::Keep) => false);
+				}
+				MergeMode::Ask if self.options.merge_policy == Some(MergePolicy::Overwrite) => {
+					log::info!(
+						"[{}] Overwriting existing dotfile (merge policy)",
+						file.relative_source_path.display()
+					)
+				}
+				MergeMode::Ask if self.options.merge_policy == Some(MergePolicy::Keep) => {
+					log::info!(
+						"[{}] Skipping existing dotfile (merge policy)",
+						file.relative_source_path.display()
+					);
+
+					conflict!(&mut self.builder, file, "Dotfile already exists and merge policy is keep" => false);
+				}
+				MergeMode::Ask if self.options.merge_policy == Some(MergePolicy::Fail) => {
+					log::error!(
+						"[{}] Dotfile already exists",
+						file.relative_source_path.display()
+					);
+
+					failed!(&mut self.builder, file, "Dotfile already exists and merge policy is fail" => false);
 				}
 				MergeMode::Ask => {
 					log::info!(
@@ -283,30 +1071,46 @@ where
 						file.relative_source_path.display()
 					);
 
-					let should_deploy = match (self.merge_ask_fn)(
-						&file.source_path,
-						file.target_path.borrow(),
-					)
-					.wrap_err("Error evaluating user response")
-					{
-						Ok(should_deploy) => should_deploy,
+					let request = MergeRequest {
+						source_path: &file.source_path,
+						target_path: &file.target_path,
+						dotfile: Some(file.dotfile()),
+						priority: file.dotfile().priority,
+						existing_priority: other_priority,
+					};
+
+					match self.ask_merge(&request) {
+						Ok(MergeDecision::Overwrite | MergeDecision::OverwriteAll) => {}
+						Ok(MergeDecision::Keep | MergeDecision::KeepAll) => {
+							log::info!("{} Merge was denied", file.relative_source_path.display());
+
+							conflict!(&mut self.builder, file, "Dotfile already exists and merge ask was denied" => false);
+						}
+						Ok(MergeDecision::Abort) => {
+							log::error!(
+								"[{}] Deployment was aborted",
+								file.relative_source_path.display()
+							);
+
+							failed!(&mut self.builder, file, "Deployment was aborted" => false);
+						}
 						Err(err) => {
 							log::error!(
-								"[{}] Failed to execute ask function ({})",
+								"[{}] Failed to execute merge strategy ({})",
 								file.relative_source_path.display(),
 								err
 							);
 
-							failed!(&mut self.builder, file, format!("Failed to execute merge ask function: {err}") => false);
+							failed!(&mut self.builder, file, format!("Failed to execute merge strategy: {err}") => false);
 						}
-					};
-
-					if !should_deploy {
-						log::info!("{} Merge was denied", file.relative_source_path.display());
-
-						skipped!(&mut self.builder, file, "Dotfile already exists and merge ask was denied" => false);
 					}
 				}
+				MergeMode::Merge => {
+					log::info!(
+						"[{}] Merging with existing dotfile",
+						file.relative_source_path.display()
+					)
+				}
 			}
 		}
 
@@ -330,46 +1134,209 @@ where
 		Ok(true)
 	}
 
-	/// Applies any relevant [`Transform`](`crate::profile::transform::Transform`)
-	/// for the given file.
-	fn transform_content(
-		&mut self,
-		profile: &LayeredProfile,
-		file: &File<'_>,
-		content: String,
-	) -> color_eyre::Result<String> {
-		let mut content = content;
+	/// Checks if the target of `file` is a symlink to the cached contents of
+	/// a previous deployment with
+	/// [`DeployStrategy::CacheLink`](`crate::profile::DeployStrategy::CacheLink`).
+	fn is_cache_linked(&self, file: &File<'_>) -> bool {
+		file.dotfile().strategy == Some(DeployStrategy::CacheLink)
+			&& self.options.cache_dir.as_deref().is_some_and(|cache_dir| {
+				links_to(&file.target_path, &cache_path(cache_dir, &file.target_path))
+			})
+	}
 
-		// Copy so we exec_dotfile is not referenced by this in case an error occurs.
-		let exec_transformers: Vec<_> = file.dotfile().transformers.to_vec();
+	/// Writes the final `content` of `file` to the target location.
+	///
+	/// Depending on the
+	/// [`Dotfile::strategy`](`crate::profile::dotfile::Dotfile::strategy`),
+	/// the content is either written directly to the target location or to
+	/// the cache directory with a symlink at the target location pointing to
+	/// it.
+	fn write_content(&self, file: &File<'_>, content: &[u8]) -> io::Result<()> {
+		if self.options.dry_run {
+			return Ok(());
+		}
 
-		// Apply transformers.
-		// Order:
-		//   - Transformers which are specified in the profile root
-		//   - Transformers which are specified on a specific dotfile of a profile
-		for transformer in profile.transformers().chain(exec_transformers.iter()) {
-			content = match transformer.transform(content) {
-				Ok(content) => content,
-				Err(err) => {
-					log::info!(
-						"[{}] Failed to apply content transformer `{}`: `{}`",
-						file.relative_source_path.display(),
-						transformer,
-						err
-					);
+		match file.dotfile().strategy.unwrap_or_default() {
+			DeployStrategy::Copy => std::fs::write(&file.target_path, content),
+			DeployStrategy::CacheLink => {
+				let cache_dir = self.options.cache_dir.as_deref().ok_or_else(|| {
+					io::Error::new(io::ErrorKind::NotFound, "No cache directory set")
+				})?;
 
-					failed!(&mut self.builder, file, format!("Failed to apply content transformer `{transformer}`: `{err}`") => Err(err));
-				}
-			};
-		}
+				let cache_path = cache_path(cache_dir, &file.target_path);
+
+				write_atomic(&cache_path, content)?;
+				replace_with_link(&cache_path, &file.target_path)
+			}
+		}
+	}
+
+	/// Returns the path which stores the content of the last deployment of
+	/// `file` for [`MergeMode::Merge`].
+	fn merge_base_path(&self, file: &File<'_>) -> Option<PathBuf> {
+		self.options
+			.cache_dir
+			.as_deref()
+			.map(|cache_dir| cache_path(&cache_dir.join(MERGE_BASE_DIR), &file.target_path))
+	}
+
+	/// Merges the new `content` of `file` with the existing target if the
+	/// merge mode of the dotfile is [`MergeMode::Merge`] (related:
+	/// [`merge::merge`]).
+	///
+	/// Returns `None` if nothing needs to be merged.
+	fn merge_content(&self, file: &File<'_>, content: &[u8]) -> io::Result<Option<Merged>> {
+		if file.dotfile().merge.unwrap_or_default() != MergeMode::Merge
+			|| !file.target_path.is_file()
+			|| self.is_cache_linked(file)
+		{
+			return Ok(None);
+		}
+
+		let to_text = |content: Vec<u8>| {
+			String::from_utf8(content).map_err(|_| {
+				io::Error::new(
+					io::ErrorKind::InvalidData,
+					"Binary content can not be merged",
+				)
+			})
+		};
+
+		let theirs = to_text(content.to_vec())?;
+		let ours = to_text(std::fs::read(&file.target_path)?)?;
+		let base = match self.merge_base_path(file).map(std::fs::read) {
+			Some(Ok(base)) => Some(to_text(base)?),
+			Some(Err(err)) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+			_ => None,
+		};
+
+		Ok(Some(merge::merge(base.as_deref(), &ours, &theirs)))
+	}
+
+	/// Stores the newly deployed `content` of `file` as base for the next
+	/// merge if the merge mode of the dotfile is [`MergeMode::Merge`].
+	fn store_merge_base(&self, file: &File<'_>, content: &[u8]) -> io::Result<()> {
+		if self.options.dry_run || file.dotfile().merge.unwrap_or_default() != MergeMode::Merge {
+			return Ok(());
+		}
+
+		match self.merge_base_path(file) {
+			Some(base_path) => write_atomic(&base_path, content),
+			None => {
+				log::warn!(
+					"[{}] No cache directory set; Changes to the target can not be merged on the next deployment",
+					file.relative_source_path.display()
+				);
+
+				Ok(())
+			}
+		}
+	}
+
+	/// Saves the attributes of the existing target of `file`, so they can be
+	/// restored after it was overwritten.
+	///
+	/// Returns `None` if
+	/// [`Dotfile::preserve_attributes`](`crate::profile::dotfile::Dotfile::preserve_attributes`)
+	/// is not set, the target does not exist yet or is not written directly
+	/// (e.g. [`DeployStrategy::CacheLink`]).
+	fn saved_attributes(
+		&self,
+		profile: &LayeredProfile,
+		file: &File<'_>,
+	) -> io::Result<Option<Attributes>> {
+		let preserve = file
+			.dotfile()
+			.preserve_attributes
+			.unwrap_or_else(|| profile.preserve_attributes());
+
+		if self.options.dry_run
+			|| !preserve
+			|| file.dotfile().strategy.unwrap_or_default() != DeployStrategy::Copy
+			|| !file.target_path.is_file()
+		{
+			return Ok(None);
+		}
+
+		Attributes::read(&file.target_path).map(Some)
+	}
+
+	/// Makes the existing target of `file` writable for the deployment, if it
+	/// is read-only and [`DeployOptions::force`] is set.
+	///
+	/// Returns `None` if nothing was changed. Only targets which are written
+	/// directly (i.e. [`DeployStrategy::Copy`]) are changed.
+	fn writable_target(&self, file: &File<'_>) -> io::Result<Option<Writable>> {
+		if !self.options.force
+			|| self.options.dry_run
+			|| file.dotfile().strategy.unwrap_or_default() != DeployStrategy::Copy
+		{
+			return Ok(None);
+		}
+
+		Writable::acquire(&file.target_path)
+	}
+
+	/// Applies all post deployment operations (e.g.
+	/// [`Dotfile::windows_acl`](`crate::profile::dotfile::Dotfile::windows_acl`))
+	/// to the already written `file`.
+	fn post_deploy(&self, file: &File<'_>) -> io::Result<()> {
+		if self.options.dry_run {
+			return Ok(());
+		}
+
+		if let Some(acl) = &file.dotfile().windows_acl {
+			acl::apply(&file.target_path, acl)?;
+		}
+
+		Ok(())
+	}
+
+	/// Applies any relevant [`Transform`](`crate::profile::transform::Transform`)
+	/// for the given file.
+	fn transform_content(
+		&mut self,
+		profile: &LayeredProfile,
+		file: &File<'_>,
+		content: String,
+	) -> crate::error::Result<String> {
+		let mut content = content;
+
+		// Copy so we exec_dotfile is not referenced by this in case an error occurs.
+		let exec_transformers: Vec<_> = file.dotfile().transformers.to_vec();
+
+		// Apply transformers.
+		// Order:
+		//   - Transformers which are specified in the profile root
+		//   - Transformers which are specified in the profile for the file extension
+		//   - Transformers which are specified on a specific dotfile of a profile
+		for transformer in profile
+			.transformers()
+			.chain(profile.extension_transformers(&file.target_path))
+			.chain(exec_transformers.iter())
+		{
+			content = match transformer.transform_with_env(content, profile.env()) {
+				Ok(content) => content,
+				Err(err) => {
+					log::info!(
+						"[{}] Failed to apply content transformer `{}`: `{}`",
+						file.relative_source_path.display(),
+						transformer,
+						err
+					);
+
+					failed!(&mut self.builder, file, format!("Failed to apply content transformer `{transformer}`: `{err}`") => Err(err));
+				}
+			};
+		}
 
 		Ok(content)
 	}
 }
 
-impl<F> Visitor for Deployer<F>
+impl<M> Visitor for Deployer<M>
 where
-	F: Fn(&Path, &Path) -> color_eyre::Result<bool>,
+	M: MergeStrategy,
 {
 	/// Accepts a file item and tries to deploy it.
 	fn accept_file<'a>(
@@ -380,37 +1347,104 @@ where
 	) -> Result {
 		log::info!("[{}] Deploying file", file.relative_source_path.display());
 
-		let cont = self.pre_deploy_checks(file)?;
+		self.builder.start_item();
+
+		let cont = self.pre_deploy_checks(profile, file)?;
 
 		if !cont {
 			return Ok(());
 		}
 
+		let attributes = match self.saved_attributes(profile, file) {
+			Ok(attributes) => attributes,
+			Err(err) => {
+				log::info!(
+					"[{}] Failed to read attributes of target",
+					file.relative_source_path.display()
+				);
+
+				failed!(
+					&mut self.builder,
+					file,
+					format!("Failed to read attributes of target: {err}")
+				);
+			}
+		};
+
+		let writable = match self.writable_target(file) {
+			Ok(writable) => writable,
+			Err(err) => {
+				log::info!(
+					"[{}] Failed to make target writable",
+					file.relative_source_path.display()
+				);
+
+				failed!(
+					&mut self.builder,
+					file,
+					format!("Failed to make read-only target writable: {err}")
+				);
+			}
+		};
+
+		let mut status = ItemStatus::success();
+		let mut merge_conflicts = 0;
+
 		// Fast path
-		if profile.transformers_len() == 0 && file.dotfile().transformers.is_empty() {
-			// File is no template and no transformers are specified. This means
-			// we can take the fast path of just copying via the filesystem.
+		if profile.transformers_len() == 0
+			&& profile.extension_transformers(&file.target_path).is_empty()
+			&& file.dotfile().transformers.is_empty()
+			&& !file.dotfile().is_encrypted()
+			&& file.dotfile().binary_mode() != BinaryMode::Text
+			&& file.dotfile().strategy.unwrap_or_default() == DeployStrategy::Copy
+			&& file.dotfile().merge.unwrap_or_default() != MergeMode::Merge
+		{
+			if file.dotfile().binary_mode() == BinaryMode::Binary {
+				status = ItemStatus::copied_binary();
+			}
+
+			// File is no template, not encrypted and no transformers are
+			// specified. This means we can take the fast path of just copying via the filesystem.
+
+			match audit::hash_file(&file.source_path) {
+				Ok(hash) => {
+					self.builder.set_content_hash(hash);
+				}
+				Err(err) => {
+					log::debug!(
+						"[{}] Failed to hash file: {err}",
+						file.relative_source_path.display()
+					);
+				}
+			}
 
 			// Allowed for readability
 			#[allow(clippy::collapsible_else_if)]
 			if !self.options.dry_run {
-				if let Err(err) = std::fs::copy(&file.source_path, &file.target_path) {
-					log::info!(
-						"[{}] Failed to copy file",
-						file.relative_source_path.display()
-					);
+				match self.timed_io(|_| std::fs::copy(&file.source_path, &file.target_path)) {
+					Ok(bytes) => {
+						self.builder.add_bytes_written(bytes);
+					}
+					Err(err) => {
+						log::info!(
+							"[{}] Failed to copy file",
+							file.relative_source_path.display()
+						);
 
-					failed!(&mut self.builder, file, format!("Failed to copy: {err}"));
+						failed!(&mut self.builder, file, format!("Failed to copy: {err}"));
+					}
 				}
 			}
 		} else {
-			let content = match safe_read(&file.source_path) {
+			let content = match self.timed_io(|_| safe_read(file)) {
 				Ok(SafeRead::Binary(b)) => {
 					log::info!(
-						"[{}] Not evaluated as template - Binary data",
+						"[{}] Not transformed - Binary data",
 						file.relative_source_path.display()
 					);
 
+					status = ItemStatus::copied_binary();
+
 					b
 				}
 				Ok(SafeRead::String(s)) => {
@@ -431,20 +1465,118 @@ where
 				}
 			};
 
-			if !self.options.dry_run {
-				if let Err(err) = std::fs::write(&file.target_path, content) {
+			let merged = match self.merge_content(file, &content) {
+				Ok(merged) => merged,
+				Err(err) => {
 					log::info!(
-						"[{}] Failed to write content",
+						"[{}] Failed to merge with target",
 						file.relative_source_path.display()
 					);
 
 					failed!(
 						&mut self.builder,
 						file,
-						format!("Failed to write content: {err}")
+						format!("Failed to merge with target: {err}")
 					);
 				}
+			};
+
+			let written = merged
+				.as_ref()
+				.map_or(content.as_slice(), |merged| merged.content.as_bytes());
+
+			self.builder.set_content_hash(audit::sha256_hex(written));
+
+			if let Err(err) = self.timed_io(|this| this.write_content(file, written)) {
+				log::info!(
+					"[{}] Failed to write content",
+					file.relative_source_path.display()
+				);
+
+				failed!(
+					&mut self.builder,
+					file,
+					format!("Failed to write content: {err}")
+				);
+			}
+
+			if !self.options.dry_run {
+				self.builder.add_bytes_written(written.len() as u64);
+			}
+
+			if let Err(err) = self.store_merge_base(file, &content) {
+				log::info!(
+					"[{}] Failed to store merge base",
+					file.relative_source_path.display()
+				);
+
+				failed!(
+					&mut self.builder,
+					file,
+					format!("Failed to store merge base: {err}")
+				);
+			}
+
+			merge_conflicts = merged.map_or(0, |merged| merged.conflicts);
+		}
+
+		if let Some(attributes) = &attributes {
+			if let Err(err) = attributes.apply(&file.target_path) {
+				log::info!(
+					"[{}] Failed to restore attributes",
+					file.relative_source_path.display()
+				);
+
+				failed!(
+					&mut self.builder,
+					file,
+					format!("Failed to restore attributes: {err}")
+				);
+			}
+		}
+
+		if let Some(writable) = writable {
+			if let Err(err) = writable.restore() {
+				log::info!(
+					"[{}] Failed to restore read-only permission",
+					file.relative_source_path.display()
+				);
+
+				failed!(
+					&mut self.builder,
+					file,
+					format!("Failed to restore read-only permission: {err}")
+				);
 			}
+
+			log::info!(
+				"[{}] Overwrote read-only target",
+				file.relative_source_path.display()
+			);
+
+			status = ItemStatus::forced();
+		}
+
+		if let Err(err) = self.post_deploy(file) {
+			log::info!(
+				"[{}] Failed to apply ACL",
+				file.relative_source_path.display()
+			);
+
+			failed!(
+				&mut self.builder,
+				file,
+				format!("Failed to apply ACL: {err}")
+			);
+		}
+
+		if merge_conflicts > 0 {
+			log::warn!(
+				"[{}] Merged with {merge_conflicts} conflicts",
+				file.relative_source_path.display()
+			);
+
+			status = ItemStatus::merge_conflicts(merge_conflicts);
 		}
 
 		log::info!(
@@ -452,7 +1584,7 @@ where
 			file.relative_source_path.display()
 		);
 
-		success!(&mut self.builder, file);
+		success!(&mut self.builder, file, status);
 
 		Ok(())
 	}
@@ -469,6 +1601,12 @@ where
 			directory.relative_source_path.display()
 		);
 
+		self.builder.start_item();
+
+		if self.aborted {
+			skipped!(&mut self.builder, directory, "Deployment was aborted");
+		}
+
 		if !self.options.dry_run {
 			if let Err(err) = std::fs::create_dir_all(&directory.target_path) {
 				log::error!(
@@ -498,9 +1636,20 @@ where
 	}
 
 	/// Accepts a link item and tries to deploy it.
-	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, link: &Symlink) -> Result {
+	fn accept_link(
+		&mut self,
+		_: &PunktfSource,
+		profile: &LayeredProfile,
+		link: &Symlink,
+	) -> Result {
 		log::info!("[{}] Deploying symlink", link.source_path.display());
 
+		self.builder.start_item();
+
+		if self.aborted {
+			skipped!(&mut self.builder, link, "Deployment was aborted");
+		}
+
 		// Log an warning if deploying of links is not supported for the
 		// operating system.
 		#[cfg(all(not(unix), not(windows)))]
@@ -526,108 +1675,261 @@ where
 			failed!(&mut self.builder, link, "Link source does not exist");
 		}
 
+		// Creating the link must not result in a loop
+		if let Some(cycle) = link::detect_cycle(source_path, target_path) {
+			log::error!(
+				"[{}] Link to {} would create a cycle: {cycle}",
+				source_path.display(),
+				target_path.display()
+			);
+
+			failed!(
+				&mut self.builder,
+				link,
+				format!("Link would create a cycle: {cycle}")
+			);
+		}
+
+		match (
+			link.priority.as_ref(),
+			self.builder.get_priority(target_path).copied(),
+		) {
+			(Some(a), Some(b)) if b > *a => {
+				let winner = self.priority_winner(profile, target_path, b);
+
+				log::info!(
+					"[{}] Item with higher priority is already deployed at {} ({winner})",
+					source_path.display(),
+					target_path.display()
+				);
+
+				link.add_to_builder(&mut self.builder, ItemStatus::overruled(winner));
+				return Ok(());
+			}
+			(_, _) => {}
+		};
+
 		// Check that either the target does not exist or that i can be replaced
 		if target_path.exists() {
-			if link.replace {
-				if !self.options.dry_run {
-					// Verify that the target is a symlink
-					let target_metadata = match target_path.symlink_metadata() {
-						Ok(m) => m,
-						Err(err) => {
-							log::error!("[{}] Failed to read metadata", source_path.display());
+			if self.is_unmanaged(target_path) {
+				log::info!(
+					"[{}] Skipping existing link target which is not managed by punktf",
+					source_path.display()
+				);
 
-							failed!(
-								&mut self.builder,
-								link,
-								format!("Failed get link target metadata: {err}")
-							);
-						}
-					};
+				conflict!(
+					&mut self.builder,
+					link,
+					"Link target already exists and is not managed by punktf"
+				);
+			}
 
-					if target_metadata.is_symlink() {
-						// Get metadata of symlink target
-						let res = if let Ok(target_metadata) = target_path.metadata() {
-							if target_metadata.is_dir() {
-								std::fs::remove_dir(target_path)
-							} else {
-								std::fs::remove_file(target_path)
-							}
-						} else {
-							std::fs::remove_file(target_path)
-								.or_else(|_| std::fs::remove_dir(target_path))
-						};
+			let target_metadata = match target_path.symlink_metadata() {
+				Ok(m) => m,
+				Err(err) => {
+					log::error!("[{}] Failed to read metadata", source_path.display());
 
-						if let Err(err) = res {
-							log::error!(
-								"[{}] Failed to remove old link at target",
-								source_path.display()
-							);
+					failed!(
+						&mut self.builder,
+						link,
+						format!("Failed get link target metadata: {err}")
+					);
+				}
+			};
 
-							failed!(
-								&mut self.builder,
-								link,
-								format!("Failed to remove old link target: {err}")
-							);
+			// A hard link is a regular file, so it is only replaced if it
+			// points to the same file as the source.
+			let is_hardlink = link.kind == LinkKind::Hardlink
+				&& target_metadata.is_file()
+				&& same_file::is_same_file(source_path, target_path).unwrap_or(false);
+
+			if link.replace && (target_metadata.is_symlink() || is_hardlink) {
+				if !self.options.dry_run {
+					// Get metadata of symlink target
+					let res = if let Ok(target_metadata) = target_path.metadata() {
+						if target_metadata.is_dir() {
+							std::fs::remove_dir(target_path)
 						} else {
-							log::info!(
-								"[{}] Removed old link target at {}",
-								source_path.display(),
-								target_path.display()
-							);
+							std::fs::remove_file(target_path)
 						}
 					} else {
+						std::fs::remove_file(target_path)
+							.or_else(|_| std::fs::remove_dir(target_path))
+					};
+
+					if let Err(err) = res {
 						log::error!(
-							"[{}] Target already exists and is no link",
+							"[{}] Failed to remove old link at target",
 							source_path.display()
 						);
 
-						failed!(&mut self.builder, link, "Not allowed to replace target");
+						failed!(
+							&mut self.builder,
+							link,
+							format!("Failed to remove old link target: {err}")
+						);
+					} else {
+						log::info!(
+							"[{}] Removed old link target at {}",
+							source_path.display(),
+							target_path.display()
+						);
 					}
 				}
 			} else {
-				log::error!(
-					"[{}] Target already exists and is not allowed to be replaced",
-					source_path.display()
-				);
+				match link.merge {
+					None if !link.replace => {
+						log::error!(
+							"[{}] Target already exists and is not allowed to be replaced",
+							source_path.display()
+						);
 
-				skipped!(&mut self.builder, link, "Link target does already exist");
-			}
-		}
+						conflict!(&mut self.builder, link, "Link target does already exist");
+					}
+					None => {
+						log::error!(
+							"[{}] Target already exists and is no link",
+							source_path.display()
+						);
 
-		if !self.options.dry_run {
-			cfg_if! {
-				if #[cfg(unix)] {
-					if let Err(err) = std::os::unix::fs::symlink(source_path, target_path) {
-						log::error!("[{}] Failed to create link", source_path.display());
+						failed!(&mut self.builder, link, "Not allowed to replace target");
+					}
+					Some(MergeMode::Overwrite) => {
+						log::info!(
+							"[{}] Overwriting existing link target",
+							source_path.display()
+						);
+					}
+					// Links can not be merged
+					Some(mode @ (MergeMode::Keep | MergeMode::Merge)) => {
+						log::info!("[{}] Skipping existing link target", source_path.display());
 
-						failed!(&mut self.builder, link, format!("Failed create link: {err}"));
-					};
-				} else if #[cfg(windows)] {
-					let metadata = match source_path.symlink_metadata() {
-						Ok(m) => m,
-						Err(err) => {
-							log::error!("[{}] Failed to read metadata", source_path.display());
+						conflict!(
+							&mut self.builder,
+							link,
+							format!("Link target does already exist and merge mode is {mode:?}")
+						);
+					}
+					Some(MergeMode::Ask)
+						if self.options.merge_policy == Some(MergePolicy::Overwrite) =>
+					{
+						log::info!(
+							"[{}] Overwriting existing link target (merge policy)",
+							source_path.display()
+						);
+					}
+					Some(MergeMode::Ask)
+						if self.options.merge_policy == Some(MergePolicy::Keep) =>
+					{
+						log::info!(
+							"[{}] Skipping existing link target (merge policy)",
+							source_path.display()
+						);
+
+						conflict!(
+							&mut self.builder,
+							link,
+							"Link target does already exist and merge policy is keep"
+						);
+					}
+					Some(MergeMode::Ask)
+						if self.options.merge_policy == Some(MergePolicy::Fail) =>
+					{
+						log::error!("[{}] Link target does already exist", source_path.display());
 
-							failed!(&mut self.builder, link, format!("Failed get link source metadata: {err}"));
+						failed!(
+							&mut self.builder,
+							link,
+							"Link target does already exist and merge policy is fail"
+						);
+					}
+					Some(MergeMode::Ask) => {
+						log::info!("[{}] Asking for action", source_path.display());
+
+						let request = MergeRequest {
+							source_path,
+							target_path,
+							dotfile: None,
+							priority: link.priority,
+							existing_priority: self.builder.get_priority(target_path).copied(),
+						};
+
+						match self.ask_merge(&request) {
+							Ok(MergeDecision::Overwrite | MergeDecision::OverwriteAll) => {}
+							Ok(MergeDecision::Keep | MergeDecision::KeepAll) => {
+								log::info!("[{}] Merge was denied", source_path.display());
+
+								conflict!(
+									&mut self.builder,
+									link,
+									"Link target does already exist and merge ask was denied"
+								);
+							}
+							Ok(MergeDecision::Abort) => {
+								log::error!("[{}] Deployment was aborted", source_path.display());
+
+								failed!(&mut self.builder, link, "Deployment was aborted");
+							}
+							Err(err) => {
+								log::error!(
+									"[{}] Failed to execute merge strategy ({})",
+									source_path.display(),
+									err
+								);
+
+								failed!(
+									&mut self.builder,
+									link,
+									format!("Failed to execute merge strategy: {err}")
+								);
+							}
 						}
-					};
+					}
+				}
 
-					if metadata.is_dir() {
-						if let Err(err) = std::os::windows::fs::symlink_dir(source_path, target_path) {
-							log::error!("[{}] Failed to create directory link", source_path.display());
+				// Directories are never removed, as their contents might not be
+				// managed by punktf.
+				if target_metadata.is_dir() {
+					log::error!(
+						"[{}] Target is a directory and can not be replaced",
+						source_path.display()
+					);
 
-							failed!(&mut self.builder, link, format!("Failed create directory link: {err}"));
-						};
-					} else if metadata.is_file() {
-						if let Err(err) = std::os::windows::fs::symlink_file(source_path, target_path) {
-							log::error!("[{}] Failed to create file link", source_path.display());
+					failed!(
+						&mut self.builder,
+						link,
+						"Not allowed to replace a directory with a link"
+					);
+				}
 
-							failed!(&mut self.builder, link, format!("Failed create file link: {err}"));
-						};
-					} else {
-						log::error!("[{}] Invalid link source type", source_path.display());
+				if !self.options.dry_run {
+					if let Err(err) = std::fs::remove_file(target_path) {
+						log::error!(
+							"[{}] Failed to remove existing target",
+							source_path.display()
+						);
+
+						failed!(
+							&mut self.builder,
+							link,
+							format!("Failed to remove existing link target: {err}")
+						);
+					}
+				}
+			}
+		}
+
+		if !self.options.dry_run {
+			cfg_if! {
+				if #[cfg(any(unix, windows))] {
+					if let Err(err) = create_link(link.kind, source_path, target_path) {
+						log::error!("[{}] Failed to create link", source_path.display());
 
-						failed!(&mut self.builder, link, "Invalid type of link source");
+						failed!(
+							&mut self.builder,
+							link,
+							format!("Failed create link: {err}")
+						);
 					}
 				} else {
 					log::warn!("[{}] Link operations are only supported for unix and windows systems", source_path.display());
@@ -655,7 +1957,7 @@ where
 			rejected.reason
 		);
 
-		skipped!(&mut self.builder, rejected, rejected.reason.clone());
+		conflict!(&mut self.builder, rejected, rejected.reason.clone());
 	}
 
 	/// Accepts a errored item and reports it.
@@ -675,9 +1977,9 @@ where
 	}
 }
 
-impl<F> TemplateVisitor for Deployer<F>
+impl<M> TemplateVisitor for Deployer<M>
 where
-	F: Fn(&Path, &Path) -> color_eyre::Result<bool>,
+	M: MergeStrategy,
 {
 	/// Accepts a file template item and tries to deploy it.
 	///
@@ -689,30 +1991,80 @@ where
 		file: &File<'a>,
 		// Returns a function to resolve the content to make the resolving lazy
 		// for upstream visitors.
-		resolve_content: impl FnOnce(&str) -> color_eyre::Result<String>,
+		resolve_content: impl FnOnce(&str) -> crate::error::Result<String>,
 	) -> Result {
 		log::info!(
 			"[{}] Deploying template",
 			file.relative_source_path.display()
 		);
 
-		let cont = self.pre_deploy_checks(file)?;
+		self.builder.start_item();
+
+		let cont = self.pre_deploy_checks(profile, file)?;
 
 		if !cont {
 			return Ok(());
 		}
 
-		let content = match safe_read(&file.source_path) {
-			Ok(SafeRead::Binary(b)) => {
+		let attributes = match self.saved_attributes(profile, file) {
+			Ok(attributes) => attributes,
+			Err(err) => {
+				log::info!(
+					"[{}] Failed to read attributes of target",
+					file.relative_source_path.display()
+				);
+
+				failed!(
+					&mut self.builder,
+					file,
+					format!("Failed to read attributes of target: {err}")
+				);
+			}
+		};
+
+		let writable = match self.writable_target(file) {
+			Ok(writable) => writable,
+			Err(err) => {
 				log::info!(
-					"[{}] Not evaluated as template - Binary data",
+					"[{}] Failed to make target writable",
 					file.relative_source_path.display()
 				);
 
+				failed!(
+					&mut self.builder,
+					file,
+					format!("Failed to make read-only target writable: {err}")
+				);
+			}
+		};
+
+		let mut status = ItemStatus::success();
+
+		let content = match self.timed_io(|_| safe_read(file)) {
+			Ok(SafeRead::Binary(b)) => {
+				if file.dotfile().template == Some(true) {
+					log::warn!(
+						"[{}] Marked as template but contains binary data; Copied without \
+						 template processing",
+						file.relative_source_path.display()
+					);
+				} else {
+					log::info!(
+						"[{}] Not evaluated as template - Binary data",
+						file.relative_source_path.display()
+					);
+				}
+
+				status = ItemStatus::copied_binary();
+
 				b
 			}
 			Ok(SafeRead::String(s)) => {
-				let content = match resolve_content(&s) {
+				let start = Instant::now();
+				let resolved = resolve_content(&s);
+				self.builder.add_template_time(start.elapsed());
+
+				let content = match resolved {
 					Ok(content) => content,
 					Err(err) => {
 						log::info!(
@@ -745,27 +2097,1241 @@ where
 			}
 		};
 
+		let merged = match self.merge_content(file, &content) {
+			Ok(merged) => merged,
+			Err(err) => {
+				log::info!(
+					"[{}] Failed to merge with target",
+					file.relative_source_path.display()
+				);
+
+				failed!(
+					&mut self.builder,
+					file,
+					format!("Failed to merge with target: {err}")
+				);
+			}
+		};
+
+		let written = merged
+			.as_ref()
+			.map_or(content.as_slice(), |merged| merged.content.as_bytes());
+
+		self.builder.set_content_hash(audit::sha256_hex(written));
+
+		if let Err(err) = self.timed_io(|this| this.write_content(file, written)) {
+			log::info!(
+				"[{}] Failed to write content",
+				file.relative_source_path.display()
+			);
+
+			failed!(
+				&mut self.builder,
+				file,
+				format!("Failed to write content: {err}")
+			);
+		}
+
 		if !self.options.dry_run {
-			if let Err(err) = std::fs::write(&file.target_path, content) {
+			self.builder.add_bytes_written(written.len() as u64);
+		}
+
+		if let Err(err) = self.store_merge_base(file, &content) {
+			log::info!(
+				"[{}] Failed to store merge base",
+				file.relative_source_path.display()
+			);
+
+			failed!(
+				&mut self.builder,
+				file,
+				format!("Failed to store merge base: {err}")
+			);
+		}
+
+		if let Some(attributes) = &attributes {
+			if let Err(err) = attributes.apply(&file.target_path) {
 				log::info!(
-					"[{}] Failed to write content",
+					"[{}] Failed to restore attributes",
 					file.relative_source_path.display()
 				);
 
 				failed!(
 					&mut self.builder,
 					file,
-					format!("Failed to write content: {err}")
+					format!("Failed to restore attributes: {err}")
 				);
 			}
 		}
 
+		if let Some(writable) = writable {
+			if let Err(err) = writable.restore() {
+				log::info!(
+					"[{}] Failed to restore read-only permission",
+					file.relative_source_path.display()
+				);
+
+				failed!(
+					&mut self.builder,
+					file,
+					format!("Failed to restore read-only permission: {err}")
+				);
+			}
+
+			log::info!(
+				"[{}] Overwrote read-only target",
+				file.relative_source_path.display()
+			);
+
+			status = ItemStatus::forced();
+		}
+
+		if let Err(err) = self.post_deploy(file) {
+			log::info!(
+				"[{}] Failed to apply ACL",
+				file.relative_source_path.display()
+			);
+
+			failed!(
+				&mut self.builder,
+				file,
+				format!("Failed to apply ACL: {err}")
+			);
+		}
+
+		if let Some(merged) = merged.filter(|merged| !merged.is_clean()) {
+			log::warn!(
+				"[{}] Merged with {} conflicts",
+				file.relative_source_path.display(),
+				merged.conflicts
+			);
+
+			status = ItemStatus::merge_conflicts(merged.conflicts);
+		}
+
 		log::info!(
 			"[{}] Template successfully deployed",
 			file.relative_source_path.display()
 		);
 
-		success!(&mut self.builder, file);
+		success!(&mut self.builder, file, status);
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Creates a deployer which overwrites all existing targets of items with
+	/// [`MergeMode::Ask`].
+	fn deployer(options: DeployOptions) -> Deployer<impl MergeStrategy> {
+		Deployer::new(options, |_: &Path, _: &Path| Ok(true))
+	}
+
+	#[test]
+	fn create_link_kinds() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source_dir"))?;
+		std::fs::write(dir.join("source_file"), "content")?;
+
+		create_link(
+			LinkKind::Hardlink,
+			&dir.join("source_file"),
+			&dir.join("hardlink"),
+		)?;
+		assert!(same_file::is_same_file(
+			dir.join("source_file"),
+			dir.join("hardlink")
+		)?);
+		assert!(!dir.join("hardlink").symlink_metadata()?.is_symlink());
+
+		create_link(
+			LinkKind::Junction,
+			&dir.join("source_dir"),
+			&dir.join("junction"),
+		)?;
+		assert!(dir.join("junction").is_dir());
+
+		assert!(create_link(
+			LinkKind::Hardlink,
+			&dir.join("source_dir"),
+			&dir.join("invalid_hardlink")
+		)
+		.is_err());
+		assert!(create_link(
+			LinkKind::Junction,
+			&dir.join("source_file"),
+			&dir.join("invalid_junction")
+		)
+		.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn cache_path_mirrors_target() {
+		crate::tests::setup_test_env();
+
+		assert_eq!(
+			cache_path(Path::new("/cache"), Path::new("/home/demo/.bashrc")),
+			PathBuf::from("/cache/home/demo/.bashrc")
+		);
+
+		assert_eq!(
+			cache_path(Path::new("/cache"), Path::new("/home/../demo/./.bashrc")),
+			PathBuf::from("/cache/demo/.bashrc")
+		);
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn on_failure_hooks() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("target"))?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+		let report = dir.join("report");
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				on_failure_hooks: vec![crate::profile::hook::Hook::new(format!(
+					"echo \"$PUNKTF_DEPLOYMENT_ID $PUNKTF_FAILED_ITEMS\" > {}",
+					report.display()
+				))],
+				dotfiles: vec![crate::visit::tests::dotfile("missing")],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let deployment = deployer(DeployOptions::default()).deploy(&source, &mut profile)?;
+
+		assert!(deployment.status().is_failed());
+		assert_eq!(
+			std::fs::read_to_string(&report)?.trim(),
+			format!(
+				"{} {}",
+				deployment.id(),
+				dir.join("target/missing").display()
+			)
+		);
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn link_priority_merge_and_condition() -> color_eyre::Result<()> {
+		use crate::profile::link::{LinkCondition, Symlink as ProfileLink};
+		use crate::profile::Priority;
+
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("target"))?;
+		std::fs::write(dir.join("a"), "a")?;
+		std::fs::write(dir.join("b"), "b")?;
+		std::fs::write(dir.join("target/existing"), "existing")?;
+		std::fs::write(dir.join("target/kept"), "kept")?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		let link = |source: &str, target: &str| ProfileLink {
+			source_path: dir.join(source),
+			target_path: dir.join("target").join(target),
+			replace: true,
+			kind: LinkKind::Symlink,
+			priority: None,
+			merge: None,
+			when: None,
+		};
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				symlinks: vec![
+					ProfileLink {
+						merge: Some(MergeMode::Overwrite),
+						..link("a", "existing")
+					},
+					ProfileLink {
+						merge: Some(MergeMode::Keep),
+						..link("a", "kept")
+					},
+					ProfileLink {
+						priority: Some(Priority::new(2)),
+						..link("a", "prioritized")
+					},
+					ProfileLink {
+						priority: Some(Priority::new(1)),
+						..link("b", "prioritized")
+					},
+					ProfileLink {
+						when: Some(LinkCondition {
+							os: Some(String::from("none")),
+							hostname: None,
+						}),
+						..link("a", "disabled")
+					},
+				],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let deployment = deployer(DeployOptions::default()).deploy(&source, &mut profile)?;
+
+		let status = |target: &str| {
+			deployment
+				.symlinks()
+				.get(&dir.join("target").join(target))
+				.map(|link| link.status().clone())
+		};
+
+		assert_eq!(status("existing"), Some(ItemStatus::success()));
+		assert_eq!(
+			std::fs::read_link(dir.join("target/existing"))?,
+			dir.join("a")
+		);
+
+		assert!(status("kept").is_some_and(|status| status.is_skipped()));
+		assert_eq!(std::fs::read_to_string(dir.join("target/kept"))?, "kept");
+
+		let winner = PriorityWinner {
+			source: dir.join("a"),
+			profile: Some(String::from("test")),
+			priority: Priority::new(2),
+		};
+		assert_eq!(
+			status("prioritized"),
+			Some(ItemStatus::overruled(winner.clone()))
+		);
+		assert_eq!(
+			serde_json::to_value(ItemStatus::overruled(winner))?["Skipped"]["profile"],
+			"test"
+		);
+		assert_eq!(
+			std::fs::read_link(dir.join("target/prioritized"))?,
+			dir.join("a")
+		);
+
+		assert_eq!(status("disabled"), None);
+		assert!(!dir.join("target/disabled").exists());
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn hook_output() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("target"))?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				pre_hooks: vec![crate::profile::hook::Hook::new("echo pre; echo oops >&2")],
+				post_hooks: vec![crate::profile::hook::Hook::new("echo post; exit 3")],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let deployment = deployer(DeployOptions::default()).deploy(&source, &mut profile)?;
+
+		assert!(deployment.status().is_failed());
+
+		let hooks = deployment.hooks();
+		assert_eq!(hooks.len(), 2);
+
+		assert_eq!(hooks[0].kind, CommandKind::PreHook);
+		assert!(hooks[0].is_success());
+		assert_eq!(hooks[0].stdout, "pre\n");
+		assert_eq!(hooks[0].stderr, "oops\n");
+
+		assert_eq!(hooks[1].kind, CommandKind::PostHook);
+		assert_eq!(hooks[1].command, "echo post; exit 3");
+		assert_eq!(hooks[1].exit_code, Some(3));
+		assert_eq!(hooks[1].stdout, "post\n");
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn dotfile_hooks() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+		std::fs::write(dir.join("source/dotfiles/app.conf"), "app")?;
+		std::fs::write(dir.join("source/dotfiles/fail.conf"), "fail")?;
+		std::fs::write(dir.join("source/dotfiles/notes.txt"), "notes")?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+		let log = dir.join("log");
+		let log_hook =
+			crate::profile::hook::Hook::new(format!("cat >> {0}; echo >> {0}", log.display()));
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				dotfiles: vec![
+					crate::visit::tests::dotfile("app.conf"),
+					crate::visit::tests::dotfile("fail.conf"),
+					crate::visit::tests::dotfile("notes.txt"),
+				],
+				pre_dotfile_hooks: vec![
+					log_hook.clone(),
+					crate::profile::hook::Hook::new("if grep -q '\"fail.conf\"'; then exit 1; fi"),
+				],
+				post_dotfile_hooks: vec![log_hook],
+				dotfile_hook_patterns: vec![crate::profile::config::IgnorePattern(String::from(
+					"*.conf",
+				))],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let deployment = deployer(DeployOptions::default()).deploy(&source, &mut profile)?;
+
+		assert!(deployment.status().is_failed());
+		assert!(dir.join("target/app.conf").exists());
+		assert!(dir.join("target/notes.txt").exists());
+		assert!(!dir.join("target/fail.conf").exists());
+
+		let items = std::fs::read_to_string(&log)?
+			.lines()
+			.map(serde_json::from_str)
+			.collect::<std::result::Result<Vec<serde_json::Value>, _>>()?;
+		let described: Vec<_> = items
+			.iter()
+			.map(|item| {
+				(
+					item["phase"].as_str().unwrap_or_default(),
+					item["relative_source_path"].as_str().unwrap_or_default(),
+					item["status"].as_str(),
+				)
+			})
+			.collect();
+
+		// The post-dotfile-hooks are not executed for `fail.conf` and no hooks
+		// for `notes.txt`.
+		assert_eq!(
+			described,
+			[
+				("pre", "app.conf", None),
+				("post", "app.conf", Some("success")),
+				("pre", "fail.conf", None),
+			]
+		);
+		assert_eq!(items[0]["kind"], "file");
+		assert_eq!(items[0]["template"], true);
+
+		let hooks = deployment.hooks();
+		assert_eq!(hooks.len(), 5);
+		assert_eq!(hooks[0].kind, CommandKind::PreDotfileHook);
+		assert_eq!(hooks[2].kind, CommandKind::PostDotfileHook);
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn no_exec() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("target"))?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+		let marker = dir.join("marker");
+
+		let deploy = |no_exec: bool, trusted: Option<bool>| -> color_eyre::Result<Deployment> {
+			let mut builder = LayeredProfile::build();
+			builder.add(
+				String::from("test"),
+				crate::profile::Profile {
+					target: Some(dir.join("target")),
+					trusted,
+					pre_hooks: vec![crate::profile::hook::Hook::new(format!(
+						"touch {}",
+						marker.display()
+					))],
+					..Default::default()
+				},
+			);
+			let mut profile = builder.finish();
+
+			let options = DeployOptions {
+				no_exec,
+				..Default::default()
+			};
+
+			Ok(deployer(options).deploy(&source, &mut profile)?)
+		};
+
+		let deployment = deploy(true, None)?;
+		assert!(!marker.exists());
+		assert_eq!(deployment.blocked().len(), 1);
+		assert_eq!(deployment.blocked()[0].kind, CommandKind::PreHook);
+
+		let deployment = deploy(false, Some(false))?;
+		assert!(!marker.exists());
+		assert_eq!(deployment.blocked().len(), 1);
+
+		let deployment = deploy(false, None)?;
+		assert!(marker.exists());
+		assert!(deployment.blocked().is_empty());
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn preserve_attributes() -> color_eyre::Result<()> {
+		use std::os::unix::fs::PermissionsExt;
+
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+
+		for name in ["kept", "reset"] {
+			let source_file = dir.join("source/dotfiles").join(name);
+			std::fs::write(&source_file, "new")?;
+			std::fs::set_permissions(&source_file, std::fs::Permissions::from_mode(0o644))?;
+
+			let target_file = dir.join("target").join(name);
+			std::fs::write(&target_file, "old")?;
+			std::fs::set_permissions(&target_file, std::fs::Permissions::from_mode(0o755))?;
+		}
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		// Not a template, so the file is copied which resets the permissions
+		let mut kept = crate::visit::tests::dotfile("kept");
+		kept.template = Some(false);
+
+		let mut reset = crate::visit::tests::dotfile("reset");
+		reset.template = Some(false);
+		reset.preserve_attributes = Some(false);
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				preserve_attributes: Some(true),
+				dotfiles: vec![kept, reset],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let deployment = deployer(DeployOptions::default()).deploy(&source, &mut profile)?;
+
+		assert!(deployment.status().is_success());
+
+		let mode = |name: &str| -> io::Result<u32> {
+			Ok(std::fs::metadata(dir.join("target").join(name))?
+				.permissions()
+				.mode() & 0o777)
+		};
+
+		assert_eq!(std::fs::read_to_string(dir.join("target/kept"))?, "new");
+		assert_eq!(mode("kept")?, 0o755);
+		assert_eq!(mode("reset")?, 0o644);
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	fn deploy_single() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles/windows"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+		std::fs::write(
+			dir.join("source/dotfiles/windows/alacritty.yml"),
+			"{{NAME}}",
+		)?;
+		std::fs::write(dir.join("source/dotfiles/other"), "other")?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				variables: Some(Variables::from_items([("NAME", "demo")])),
+				dotfiles: vec![
+					crate::visit::tests::dotfile("windows/alacritty.yml"),
+					crate::visit::tests::dotfile("other"),
+				],
+				..Default::default()
+			},
+		);
+		let profile = builder.finish();
+
+		let err = deployer(DeployOptions::default())
+			.deploy_single(&source, &profile, Path::new("missing"))
+			.expect_err("Unknown dotfile was deployed");
+		assert!(matches!(err, DeployError::UnknownDotfile(_)));
+
+		let deployment = deployer(DeployOptions::default()).deploy_single(
+			&source,
+			&profile,
+			Path::new("./windows/alacritty.yml"),
+		)?;
+
+		assert_eq!(deployment.dotfiles().len(), 1);
+		assert_eq!(
+			std::fs::read_to_string(dir.join("target/windows/alacritty.yml"))?,
+			"demo"
+		);
+		assert!(!dir.join("target/other").exists());
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	fn force_read_only() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+
+		for name in ["copied", "template"] {
+			std::fs::write(dir.join("source/dotfiles").join(name), "new")?;
+
+			let target_file = dir.join("target").join(name);
+			std::fs::write(&target_file, "old")?;
+
+			let mut permissions = std::fs::metadata(&target_file)?.permissions();
+			permissions.set_readonly(true);
+			std::fs::set_permissions(&target_file, permissions)?;
+		}
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		let mut copied = crate::visit::tests::dotfile("copied");
+		copied.template = Some(false);
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				dotfiles: vec![copied, crate::visit::tests::dotfile("template")],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let options = DeployOptions {
+			force: true,
+			..Default::default()
+		};
+		let deployment = deployer(options).deploy(&source, &mut profile)?;
+
+		for name in ["copied", "template"] {
+			let target_file = dir.join("target").join(name);
+
+			assert_eq!(
+				deployment.dotfiles()[&target_file].status(),
+				&ItemStatus::forced()
+			);
+			assert_eq!(std::fs::read_to_string(&target_file)?, "new");
+			assert!(std::fs::metadata(&target_file)?.permissions().readonly());
+
+			let mut permissions = std::fs::metadata(&target_file)?.permissions();
+			#[allow(clippy::permissions_set_readonly_false)]
+			permissions.set_readonly(false);
+			std::fs::set_permissions(&target_file, permissions)?;
+		}
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	fn binary_mode() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+
+		std::fs::write(dir.join("source/dotfiles/detected"), [0xff, 0xfe, 0x00])?;
+		std::fs::write(dir.join("source/dotfiles/text"), [0xff, 0xfe, 0x00])?;
+		std::fs::write(dir.join("source/dotfiles/forced"), "{{OS}}")?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		let mut detected = crate::visit::tests::dotfile("detected");
+		detected.template = Some(true);
+
+		let mut text = crate::visit::tests::dotfile("text");
+		text.binary = Some(BinaryMode::Text);
+
+		let mut forced = crate::visit::tests::dotfile("forced");
+		forced.binary = Some(BinaryMode::Binary);
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				dotfiles: vec![detected, text, forced],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let deployment = deployer(DeployOptions::default()).deploy(&source, &mut profile)?;
+
+		let status = |name: &str| {
+			deployment
+				.dotfiles()
+				.iter()
+				.find(|(path, _)| path.ends_with(name))
+				.map(|(_, dotfile)| dotfile.status().clone())
+		};
+
+		assert_eq!(status("detected"), Some(ItemStatus::copied_binary()));
+		assert!(status("text").is_some_and(|status| status.is_failed()));
+		assert_eq!(status("forced"), Some(ItemStatus::copied_binary()));
+		assert_eq!(
+			std::fs::read_to_string(dir.join("target/forced"))?,
+			"{{OS}}"
+		);
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	fn extract_archive() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles/fonts"))?;
+		std::fs::create_dir_all(dir.join("target/fonts"))?;
+
+		std::fs::write(
+			dir.join("source/dotfiles/fonts/fonts.tar"),
+			archive::tests::tar(&[
+				("a.ttf", "{{OS}}"),
+				("nested/b.ttf", "b"),
+				("kept.ttf", "new"),
+			]),
+		)?;
+		std::fs::write(dir.join("target/fonts/kept.ttf"), "old")?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		let mut fonts = crate::visit::tests::dotfile("fonts/fonts.tar");
+		fonts.extract = Some(true);
+		fonts.merge = Some(MergeMode::Keep);
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				dotfiles: vec![fonts],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let deployment = deployer(DeployOptions::default()).deploy(&source, &mut profile)?;
+
+		assert!(!deployment.status().is_failed());
+		assert!(!dir.join("target/fonts/fonts.tar").exists());
+		assert_eq!(
+			std::fs::read_to_string(dir.join("target/fonts/a.ttf"))?,
+			"{{OS}}"
+		);
+		assert_eq!(
+			std::fs::read_to_string(dir.join("target/fonts/nested/b.ttf"))?,
+			"b"
+		);
+		assert_eq!(
+			std::fs::read_to_string(dir.join("target/fonts/kept.ttf"))?,
+			"old"
+		);
+		assert!(deployment
+			.dotfiles()
+			.get(&dir.join("target/fonts/kept.ttf"))
+			.is_some_and(|dotfile| dotfile.status().is_skipped()));
+
+		crate::tests::assert_no_temp_leaks(&dir);
+		// Archives are extracted outside of the test directory
+		assert!(!crate::temp::tracked().iter().any(|path| path
+			.file_name()
+			.is_some_and(|name| name.to_string_lossy().starts_with("punktf-archive-"))));
+
+		Ok(())
+	}
+
+	#[test]
+	fn merge_mode_merge() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		let mut config = crate::visit::tests::dotfile("config");
+		config.template = Some(false);
+		config.merge = Some(MergeMode::Merge);
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				dotfiles: vec![config],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let options = DeployOptions {
+			cache_dir: Some(dir.join("cache")),
+			..Default::default()
+		};
+		let mut deploy = |source_content: &str| -> color_eyre::Result<Deployment> {
+			std::fs::write(dir.join("source/dotfiles/config"), source_content)?;
+
+			Ok(deployer(options.clone()).deploy(&source, &mut profile)?)
+		};
+		let target = || std::fs::read_to_string(dir.join("target/config"));
+
+		deploy("a\nb\nc\nd\ne\n")?;
+		assert_eq!(target()?, "a\nb\nc\nd\ne\n");
+
+		// Local and upstream changes to different lines are both kept
+		std::fs::write(dir.join("target/config"), "A\nb\nc\nd\ne\n")?;
+		let deployment = deploy("a\nb\nc\nd\nE\n")?;
+		assert!(deployment.status().is_success());
+		assert_eq!(target()?, "A\nb\nc\nd\nE\n");
+
+		// Changes to the same line conflict
+		std::fs::write(dir.join("target/config"), "A\nb\nX\nd\nE\n")?;
+		let deployment = deploy("a\nb\nY\nd\nE\n")?;
+		assert!(deployment.status().is_success());
+		assert_eq!(
+			deployment
+				.dotfiles()
+				.get(&dir.join("target/config"))
+				.map(|dotfile| dotfile.status()),
+			Some(&ItemStatus::merge_conflicts(1))
+		);
+		assert_eq!(
+			target()?,
+			"A\nb\n<<<<<<< target\nX\n||||||| base\nc\n=======\nY\n>>>>>>> source\nd\nE\n"
+		);
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	fn merge_policy() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+		std::fs::write(dir.join("source/dotfiles/config"), "new")?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		let mut config = crate::visit::tests::dotfile("config");
+		config.template = Some(false);
+		config.merge = Some(MergeMode::Ask);
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				dotfiles: vec![config],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let mut deploy = |policy: MergePolicy| -> color_eyre::Result<Deployment> {
+			std::fs::write(dir.join("target/config"), "old")?;
+
+			let options = DeployOptions {
+				merge_policy: Some(policy),
+				..Default::default()
+			};
+
+			// The policy must decide without asking
+			Ok(
+				Deployer::new(options, |_: &Path, _: &Path| Err("Asked for merge".into()))
+					.deploy(&source, &mut profile)?,
+			)
+		};
+		let target = || std::fs::read_to_string(dir.join("target/config"));
+
+		let deployment = deploy(MergePolicy::Keep)?;
+		assert!(deployment.status().is_success());
+		assert!(deployment
+			.dotfiles()
+			.get(&dir.join("target/config"))
+			.is_some_and(|dotfile| dotfile.status().is_skipped()));
+		assert_eq!(target()?, "old");
+
+		let deployment = deploy(MergePolicy::Fail)?;
+		assert!(deployment.status().is_failed());
+		assert_eq!(target()?, "old");
+
+		let deployment = deploy(MergePolicy::Overwrite)?;
+		assert!(deployment.status().is_success());
+		assert_eq!(target()?, "new");
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	fn merge_strategy() -> color_eyre::Result<()> {
+		use std::cell::Cell;
+
+		/// Gives the same answer to all requests and counts them.
+		struct Answer<'a> {
+			decision: MergeDecision,
+			asked: &'a Cell<usize>,
+		}
+
+		impl MergeStrategy for Answer<'_> {
+			fn decide(
+				&mut self,
+				request: &MergeRequest<'_>,
+			) -> std::result::Result<MergeDecision, crate::error::BoxError> {
+				assert_eq!(request.old_preview()?, "old");
+				assert_eq!(request.priority, Some(Priority(1)));
+
+				self.asked.set(self.asked.get() + 1);
+				Ok(self.decision)
+			}
+		}
+
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles/dir"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+
+		for name in ["a", "b", "c"] {
+			std::fs::write(dir.join("source/dotfiles/dir").join(name), "new")?;
+		}
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		let mut config = crate::visit::tests::dotfile("dir");
+		config.template = Some(false);
+		config.merge = Some(MergeMode::Ask);
+		config.priority = Some(Priority(1));
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				dotfiles: vec![config],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let mut deploy = |decision: MergeDecision| -> color_eyre::Result<(Deployment, usize)> {
+			for name in ["a", "b", "c"] {
+				std::fs::write(dir.join("target").join(name), "old")?;
+			}
+
+			let asked = Cell::new(0);
+			let deployment = Deployer::new(
+				DeployOptions::default(),
+				Answer {
+					decision,
+					asked: &asked,
+				},
+			)
+			.deploy(&source, &mut profile)?;
+
+			Ok((deployment, asked.get()))
+		};
+		let count = |deployment: &Deployment, f: fn(&ItemStatus) -> bool| {
+			deployment
+				.dotfiles()
+				.values()
+				.filter(|dotfile| f(dotfile.status()))
+				.count()
+		};
+		let targets = || -> io::Result<Vec<String>> {
+			["a", "b", "c"]
+				.into_iter()
+				.map(|name| std::fs::read_to_string(dir.join("target").join(name)))
+				.collect()
+		};
+
+		let (deployment, asked) = deploy(MergeDecision::OverwriteAll)?;
+		assert!(deployment.status().is_success());
+		assert_eq!(asked, 1);
+		assert_eq!(targets()?, ["new", "new", "new"]);
+
+		let (deployment, asked) = deploy(MergeDecision::KeepAll)?;
+		assert_eq!(asked, 1);
+		assert_eq!(count(&deployment, ItemStatus::is_skipped), 3);
+		assert_eq!(targets()?, ["old", "old", "old"]);
+
+		let (deployment, asked) = deploy(MergeDecision::Abort)?;
+		assert!(deployment.status().is_failed());
+		assert_eq!(asked, 1);
+		assert_eq!(count(&deployment, ItemStatus::is_failed), 1);
+		assert_eq!(count(&deployment, ItemStatus::is_skipped), 2);
+		assert_eq!(targets()?, ["old", "old", "old"]);
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	fn deploy_with_progress() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles/dir"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+		std::fs::write(dir.join("source/dotfiles/file"), "file")?;
+		std::fs::write(dir.join("source/dotfiles/dir/a"), "a")?;
+		std::fs::write(dir.join("source/dotfiles/dir/b"), "b")?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				dotfiles: vec![
+					crate::visit::tests::dotfile("file"),
+					crate::visit::tests::dotfile("dir"),
+				],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let mut totals = Vec::new();
+		let mut items = Vec::new();
+		let deployment = deployer(DeployOptions::default()).deploy_with_progress(
+			&source,
+			&mut profile,
+			|progress| match progress {
+				Progress::Started { total } => totals.push(total),
+				Progress::Item {
+					target_path,
+					done,
+					total,
+					..
+				} => {
+					totals.push(total);
+					items.push((done, target_path.strip_prefix(&dir).map(Path::to_path_buf)));
+				}
+			},
+		)?;
+
+		assert!(deployment.status().is_success());
+		assert_eq!(totals, [4; 5]);
+		assert_eq!(
+			items.iter().map(|(done, _)| *done).collect::<Vec<_>>(),
+			[1, 2, 3, 4]
+		);
+
+		// The directory is deployed directly into the target
+		let mut paths = items
+			.into_iter()
+			.map(|(_, path)| path)
+			.collect::<std::result::Result<Vec<_>, _>>()?;
+		paths.sort();
+		assert_eq!(
+			paths,
+			["target", "target/a", "target/b", "target/file"].map(PathBuf::from)
+		);
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	fn deploy_extra_paths() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles"))?;
+		std::fs::create_dir_all(dir.join("source/assets/fonts"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+		std::fs::write(dir.join("source/dotfiles/file"), "file")?;
+		std::fs::write(dir.join("source/assets/wallpaper.png"), "wallpaper")?;
+		std::fs::write(dir.join("source/assets/fonts/mono.ttf"), "font")?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+		assert_eq!(
+			source.assets(),
+			Some(source.root().join("assets").as_path())
+		);
+
+		let mut fonts = crate::visit::tests::dotfile("fonts");
+		fonts.rename = Some(PathBuf::from(".fonts"));
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				dotfiles: vec![crate::visit::tests::dotfile("file")],
+				extra_paths: vec![crate::visit::tests::dotfile("wallpaper.png"), fonts],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		// Only deployments visit extra paths
+		let mut counter = Counter::default();
+		Walker::new(&mut profile)
+			.walk(&source, &mut counter)
+			.expect("Failed to walk profile");
+		assert_eq!(counter.count, 1);
+
+		let deployment = deployer(DeployOptions::default()).deploy(&source, &mut profile)?;
+
+		assert!(deployment.status().is_success());
+		assert_eq!(deployment.dotfiles().len(), 4);
+		assert_eq!(
+			std::fs::read_to_string(dir.join("target/wallpaper.png"))?,
+			"wallpaper"
+		);
+		assert_eq!(
+			std::fs::read_to_string(dir.join("target/.fonts/mono.ttf"))?,
+			"font"
+		);
+
+		crate::tests::assert_no_temp_leaks(&dir);
+
+		Ok(())
+	}
+
+	#[test]
+	fn deploy_only_managed() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+
+		for name in ["managed", "unmanaged", "new"] {
+			std::fs::write(dir.join("source/dotfiles").join(name), "new")?;
+		}
+		for name in ["managed", "unmanaged"] {
+			std::fs::write(dir.join("target").join(name), "old")?;
+		}
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			crate::profile::Profile {
+				target: Some(dir.join("target")),
+				dotfiles: ["managed", "unmanaged", "new"]
+					.map(crate::visit::tests::dotfile)
+					.to_vec(),
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let mut manifest = Manifest::new();
+		manifest.dotfiles.insert(dir.join("target/managed"));
+
+		let options = DeployOptions {
+			only_managed: Some(manifest),
+			..Default::default()
+		};
+		let deployment = deployer(options).deploy(&source, &mut profile)?;
+
+		let skipped = deployment
+			.dotfiles()
+			.iter()
+			.filter(|(_, dotfile)| dotfile.status().is_skipped())
+			.map(|(path, _)| path.clone())
+			.collect::<Vec<_>>();
+		assert_eq!(skipped, [dir.join("target/unmanaged")]);
+
+		for (name, content) in [("managed", "new"), ("unmanaged", "old"), ("new", "new")] {
+			assert_eq!(
+				std::fs::read_to_string(dir.join("target").join(name))?,
+				content
+			);
+		}
+
+		crate::tests::assert_no_temp_leaks(&dir);
 
 		Ok(())
 	}
@@ -0,0 +1,214 @@
+//! Progress reporting for deployments (related:
+//! [`Deployer::deploy_with_progress`](`crate::visit::deploy::Deployer::deploy_with_progress`)).
+//!
+//! Before the items are deployed, the profile is walked once without touching
+//! any target to count the items. Afterwards an event is emitted for every
+//! item once it was processed, together with the time it took.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::profile::source::PunktfSource;
+use crate::profile::LayeredProfile;
+use crate::visit::*;
+
+/// An event which is emitted while a deployment is in progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress<'a> {
+	/// The items are about to be deployed.
+	Started {
+		/// Total number of items which will be processed.
+		total: usize,
+	},
+
+	/// An item was processed, regardless of its outcome.
+	Item {
+		/// Absolute path to the target location of the item.
+		target_path: &'a Path,
+
+		/// Time it took to process the item.
+		elapsed: Duration,
+
+		/// Number of items processed so far, including this one.
+		done: usize,
+
+		/// Total number of items which will be processed.
+		total: usize,
+	},
+}
+
+/// A [`Visitor`] which only counts the items of a profile.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Counter {
+	/// Number of items seen so far.
+	pub(crate) count: usize,
+}
+
+impl Visitor for Counter {
+	/// Accepts a file item and counts it.
+	fn accept_file<'a>(&mut self, _: &PunktfSource, _: &LayeredProfile, _: &File<'a>) -> Result {
+		self.count += 1;
+		Ok(())
+	}
+
+	/// Accepts a directory item and counts it.
+	fn accept_directory<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Directory<'a>,
+	) -> Result {
+		self.count += 1;
+		Ok(())
+	}
+
+	/// Accepts a link item and counts it.
+	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, _: &Symlink) -> Result {
+		self.count += 1;
+		Ok(())
+	}
+
+	/// Accepts a rejected item and counts it.
+	fn accept_rejected<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Rejected<'a>,
+	) -> Result {
+		self.count += 1;
+		Ok(())
+	}
+
+	/// Accepts an errored item and counts it.
+	fn accept_errored<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Errored<'a>,
+	) -> Result {
+		self.count += 1;
+		Ok(())
+	}
+}
+
+/// A [`Visitor`] which passes all items on to another visitor and emits a
+/// [`Progress::Item`] event after each of them.
+pub(crate) struct Tracker<'v, V> {
+	/// The visitor which processes the items.
+	visitor: &'v mut V,
+
+	/// Function which receives the events.
+	on_progress: &'v mut dyn FnMut(Progress<'_>),
+
+	/// Number of items processed so far.
+	done: usize,
+
+	/// Total number of items.
+	total: usize,
+}
+
+impl<'v, V> Tracker<'v, V>
+where
+	V: Visitor,
+{
+	/// Creates a new instance which expects `total` items.
+	pub(crate) fn new(
+		visitor: &'v mut V,
+		on_progress: &'v mut dyn FnMut(Progress<'_>),
+		total: usize,
+	) -> Self {
+		Self {
+			visitor,
+			on_progress,
+			done: 0,
+			total,
+		}
+	}
+
+	/// Passes an item on to `accept` and emits an event for it afterwards.
+	fn track<F>(&mut self, target_path: &Path, accept: F) -> Result
+	where
+		F: FnOnce(&mut V) -> Result,
+	{
+		let start = Instant::now();
+		let result = accept(self.visitor);
+
+		self.done += 1;
+		// The total might be off if the source changed in between
+		self.total = self.total.max(self.done);
+
+		(self.on_progress)(Progress::Item {
+			target_path,
+			elapsed: start.elapsed(),
+			done: self.done,
+			total: self.total,
+		});
+
+		result
+	}
+}
+
+impl<V> Visitor for Tracker<'_, V>
+where
+	V: Visitor,
+{
+	/// Accepts a file item and tracks its deployment.
+	fn accept_file<'a>(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		file: &File<'a>,
+	) -> Result {
+		self.track(&file.target_path, |visitor| {
+			visitor.accept_file(source, profile, file)
+		})
+	}
+
+	/// Accepts a directory item and tracks its deployment.
+	fn accept_directory<'a>(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		directory: &Directory<'a>,
+	) -> Result {
+		self.track(&directory.target_path, |visitor| {
+			visitor.accept_directory(source, profile, directory)
+		})
+	}
+
+	/// Accepts a link item and tracks its deployment.
+	fn accept_link(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		symlink: &Symlink,
+	) -> Result {
+		self.track(&symlink.target_path, |visitor| {
+			visitor.accept_link(source, profile, symlink)
+		})
+	}
+
+	/// Accepts a rejected item and tracks it.
+	fn accept_rejected<'a>(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		rejected: &Rejected<'a>,
+	) -> Result {
+		self.track(&rejected.target_path, |visitor| {
+			visitor.accept_rejected(source, profile, rejected)
+		})
+	}
+
+	/// Accepts an errored item and tracks it.
+	fn accept_errored<'a>(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		errored: &Errored<'a>,
+	) -> Result {
+		self.track(&errored.target_path, |visitor| {
+			visitor.accept_errored(source, profile, errored)
+		})
+	}
+}
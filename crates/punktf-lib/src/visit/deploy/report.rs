@@ -0,0 +1,413 @@
+//! Outputs for the status of a finished [deployment](`Deployment`).
+//!
+//! A [`ReportSink`] receives the deployment once it is finished and writes it
+//! somewhere, e.g. to a file or to a webhook which collects the results of
+//! many machines. Sinks are usually created from a [`ReportSpec`], which
+//! allows multiple of them to be used for a single deployment.
+//!
+//! Webhooks require the cargo feature `report-webhook`. They are called with
+//! a built-in HTTP client, so no external tools are required. A webhook which
+//! does not respond within `WEBHOOK_TIMEOUT` fails the report instead of
+//! blocking the deployment.
+
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+#[cfg(feature = "report-webhook")]
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::visit::deploy::deployment::Deployment;
+
+/// An error which occurs while writing a deployment report.
+#[derive(Error, Debug)]
+pub enum ReportError {
+	/// The report could not be written.
+	#[error("{0}")]
+	Io(#[from] io::Error),
+
+	/// The deployment could not be serialized as json.
+	#[error("{0}")]
+	Json(#[from] serde_json::Error),
+
+	/// The deployment could not be serialized as yaml.
+	#[cfg(feature = "profile-yaml")]
+	#[error("{0}")]
+	Yaml(#[from] serde_yaml::Error),
+
+	/// The format was not enabled when `punktf` was built.
+	#[error("The report format `{0}` is not available")]
+	FormatUnavailable(ReportFormat),
+
+	/// The report could not be sent to a webhook.
+	#[cfg(feature = "report-webhook")]
+	#[error("Failed to send the report to `{url}`: {reason}")]
+	Webhook {
+		/// Url of the webhook.
+		url: String,
+
+		/// Description of the failure.
+		reason: String,
+	},
+
+	/// A [`ReportSpec`] could not be parsed.
+	#[error("Invalid report `{spec}`: {reason}")]
+	InvalidSpec {
+		/// The given specification.
+		spec: String,
+
+		/// Description of the problem.
+		reason: &'static str,
+	},
+}
+
+/// The format in which a deployment is written.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReportFormat {
+	/// The deployment is written as pretty printed json.
+	#[default]
+	Json,
+
+	/// The deployment is written as yaml. Requires the cargo feature
+	/// `profile-yaml`.
+	Yaml,
+}
+
+impl ReportFormat {
+	/// Serializes `deployment` in this format to `writer`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the deployment could not be serialized or
+	/// written.
+	pub fn write<W: Write>(self, writer: W, deployment: &Deployment) -> Result<(), ReportError> {
+		match self {
+			Self::Json => Ok(serde_json::to_writer_pretty(writer, deployment)?),
+			#[cfg(feature = "profile-yaml")]
+			Self::Yaml => Ok(serde_yaml::to_writer(writer, deployment)?),
+			#[allow(unreachable_patterns)]
+			format => Err(ReportError::FormatUnavailable(format)),
+		}
+	}
+}
+
+impl fmt::Display for ReportFormat {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Json => f.write_str("json"),
+			Self::Yaml => f.write_str("yaml"),
+		}
+	}
+}
+
+/// A destination to which the status of a deployment is written.
+pub trait ReportSink {
+	/// Describes the destination for log messages (e.g. `json file
+	/// report.json`).
+	fn describe(&self) -> String;
+
+	/// Writes the report for `deployment`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the report could not be written.
+	fn report(&mut self, deployment: &Deployment) -> Result<(), ReportError>;
+}
+
+/// Writes the report to a new file. Existing files are never overwritten.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSink {
+	/// Path of the file.
+	pub path: PathBuf,
+
+	/// Format of the report.
+	pub format: ReportFormat,
+}
+
+impl ReportSink for FileSink {
+	fn describe(&self) -> String {
+		format!("{} file `{}`", self.format, self.path.display())
+	}
+
+	fn report(&mut self, deployment: &Deployment) -> Result<(), ReportError> {
+		let file = OpenOptions::new()
+			.create_new(true)
+			.write(true)
+			.open(&self.path)?;
+
+		self.format.write(io::BufWriter::new(file), deployment)
+	}
+}
+
+/// Writes the report to any writer (e.g. stdout).
+#[derive(Debug)]
+pub struct WriterSink<W> {
+	/// The writer which receives the report.
+	pub writer: W,
+
+	/// Format of the report.
+	pub format: ReportFormat,
+}
+
+impl<W: Write> ReportSink for WriterSink<W> {
+	fn describe(&self) -> String {
+		format!("{} output", self.format)
+	}
+
+	fn report(&mut self, deployment: &Deployment) -> Result<(), ReportError> {
+		self.format.write(&mut self.writer, deployment)?;
+		Ok(writeln!(self.writer)?)
+	}
+}
+
+/// Default timeout for a request to a webhook, including connecting to it.
+#[cfg(feature = "report-webhook")]
+pub const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends the report as json with an HTTP `POST` request to a webhook.
+#[cfg(feature = "report-webhook")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookSink {
+	/// Url of the webhook.
+	pub url: String,
+
+	/// Timeout for the whole request (related: [`WEBHOOK_TIMEOUT`]).
+	pub timeout: Duration,
+}
+
+#[cfg(feature = "report-webhook")]
+impl ReportSink for WebhookSink {
+	fn describe(&self) -> String {
+		format!("webhook `{}`", self.url)
+	}
+
+	fn report(&mut self, deployment: &Deployment) -> Result<(), ReportError> {
+		let body = serde_json::to_vec(deployment)?;
+
+		ureq::AgentBuilder::new()
+			.timeout(self.timeout)
+			.build()
+			.post(&self.url)
+			.set("Content-Type", "application/json")
+			.send_bytes(&body)
+			.map_err(|err| ReportError::Webhook {
+				url: self.url.clone(),
+				reason: err.to_string(),
+			})?;
+
+		Ok(())
+	}
+}
+
+/// Describes a [`ReportSink`] in the form `<kind>:<dest>`.
+///
+/// - `json:<path>`/`yaml:<path>`: Writes the report to a new file
+///   (related: [`FileSink`])
+/// - `stdout`, `stdout:json` or `stdout:yaml`: Writes the report to stdout
+///   (related: [`WriterSink`])
+/// - `webhook:<url>`: Sends the report to an `http://` or `https://` url
+///   (related: `WebhookSink`). Requires the cargo feature `report-webhook`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReportSpec {
+	/// Writes the report to a new file.
+	File(FileSink),
+
+	/// Writes the report to stdout.
+	Stdout(ReportFormat),
+
+	/// Sends the report to a webhook.
+	#[cfg(feature = "report-webhook")]
+	Webhook(WebhookSink),
+}
+
+impl ReportSpec {
+	/// Creates the sink for this specification, which uses `stdout` to write
+	/// reports to stdout.
+	pub fn sink<'a>(&'a self, stdout: &'a mut dyn Write) -> Box<dyn ReportSink + 'a> {
+		match self {
+			Self::File(sink) => Box::new(sink.clone()),
+			Self::Stdout(format) => Box::new(WriterSink {
+				writer: stdout,
+				format: *format,
+			}),
+			#[cfg(feature = "report-webhook")]
+			Self::Webhook(sink) => Box::new(sink.clone()),
+		}
+	}
+}
+
+impl FromStr for ReportSpec {
+	type Err = ReportError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = |reason| ReportError::InvalidSpec {
+			spec: s.to_string(),
+			reason,
+		};
+
+		let format = |format: &str| match format {
+			"json" => Ok(ReportFormat::Json),
+			"yaml" => Ok(ReportFormat::Yaml),
+			_ => Err(invalid("the format must be either `json` or `yaml`")),
+		};
+
+		let (kind, dest) = s.split_once(':').unwrap_or((s, ""));
+
+		match kind {
+			"json" | "yaml" if dest.is_empty() => Err(invalid("expected a path after `:`")),
+			"json" | "yaml" => Ok(Self::File(FileSink {
+				path: PathBuf::from(dest),
+				format: format(kind)?,
+			})),
+			"stdout" if dest.is_empty() => Ok(Self::Stdout(ReportFormat::Json)),
+			"stdout" => Ok(Self::Stdout(format(dest)?)),
+			#[cfg(feature = "report-webhook")]
+			"webhook" if dest.starts_with("http://") || dest.starts_with("https://") => {
+				Ok(Self::Webhook(WebhookSink {
+					url: dest.to_string(),
+					timeout: WEBHOOK_TIMEOUT,
+				}))
+			}
+			#[cfg(feature = "report-webhook")]
+			"webhook" => Err(invalid("expected an `http://` or `https://` url after `:`")),
+			#[cfg(not(feature = "report-webhook"))]
+			"webhook" => Err(invalid(
+				"webhooks are not available, as `punktf` was built without the cargo feature \
+				 `report-webhook`",
+			)),
+			_ => Err(invalid(
+				"the kind must be one of `json`, `yaml`, `stdout` or `webhook`",
+			)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::visit::deploy::deployment::DeploymentBuilder;
+
+	#[test]
+	fn parse_spec() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		assert_eq!(
+			"json:out/report.json".parse::<ReportSpec>()?,
+			ReportSpec::File(FileSink {
+				path: PathBuf::from("out/report.json"),
+				format: ReportFormat::Json,
+			})
+		);
+		assert_eq!(
+			"stdout".parse::<ReportSpec>()?,
+			ReportSpec::Stdout(ReportFormat::Json)
+		);
+		assert_eq!(
+			"stdout:yaml".parse::<ReportSpec>()?,
+			ReportSpec::Stdout(ReportFormat::Yaml)
+		);
+		#[cfg(feature = "report-webhook")]
+		assert_eq!(
+			"webhook:https://example.com/deploy".parse::<ReportSpec>()?,
+			ReportSpec::Webhook(WebhookSink {
+				url: String::from("https://example.com/deploy"),
+				timeout: WEBHOOK_TIMEOUT,
+			})
+		);
+
+		assert!("json".parse::<ReportSpec>().is_err());
+		assert!("stdout:toml".parse::<ReportSpec>().is_err());
+		assert!("webhook:example.com".parse::<ReportSpec>().is_err());
+		assert!("mail:demo@example.com".parse::<ReportSpec>().is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn multiple_sinks() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+
+		let deployment = DeploymentBuilder::default().success();
+		let specs = [
+			format!("json:{}", dir.join("report.json").display()).parse::<ReportSpec>()?,
+			"stdout".parse()?,
+		];
+
+		let mut stdout = Vec::new();
+		for spec in &specs {
+			spec.sink(&mut stdout).report(&deployment)?;
+		}
+
+		let written: Deployment = serde_json::from_slice(&std::fs::read(dir.join("report.json"))?)?;
+		assert_eq!(written.id(), deployment.id());
+		assert_eq!(
+			serde_json::from_slice::<Deployment>(&stdout)?.id(),
+			deployment.id()
+		);
+
+		// Existing files are never overwritten
+		assert!(specs[0].sink(&mut stdout).report(&deployment).is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(feature = "report-webhook")]
+	fn webhook_sink() -> color_eyre::Result<()> {
+		use std::io::Read;
+		use std::net::TcpListener;
+
+		crate::tests::setup_test_env();
+
+		let deployment = DeploymentBuilder::default().success();
+		let id = deployment.id().to_string();
+
+		let listener = TcpListener::bind("127.0.0.1:0")?;
+		let url = format!("http://{}/deploy", listener.local_addr()?);
+
+		let server = std::thread::spawn(move || -> io::Result<String> {
+			let (mut stream, _) = listener.accept()?;
+			let mut request = Vec::new();
+			let mut buf = [0; 1024];
+
+			while !String::from_utf8_lossy(&request).contains(&id) {
+				let read = stream.read(&mut buf)?;
+				if read == 0 {
+					break;
+				}
+				request.extend_from_slice(&buf[..read]);
+			}
+
+			stream.write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")?;
+
+			// Keep the listener open without answering to test the timeout
+			let (_stream, _) = listener.accept()?;
+			std::thread::sleep(Duration::from_millis(500));
+
+			Ok(String::from_utf8_lossy(&request).into_owned())
+		});
+
+		let mut sink = WebhookSink {
+			url,
+			timeout: Duration::from_millis(200),
+		};
+		sink.report(&deployment)?;
+
+		assert!(matches!(
+			sink.report(&deployment),
+			Err(ReportError::Webhook { .. })
+		));
+
+		let request = server.join().expect("Server thread to not panic")?;
+		assert!(request.starts_with("POST /deploy "), "{request}");
+		assert!(request.contains("application/json"), "{request}");
+
+		Ok(())
+	}
+}
@@ -0,0 +1,317 @@
+//! Estimation of the disk space a deployment requires and checks against the
+//! space which is available on the target filesystems.
+//!
+//! The estimation is done before any item is written, which allows a
+//! deployment to fail fast instead of running out of space mid-run and leaving
+//! a partially deployed profile behind.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use cfg_if::cfg_if;
+use thiserror::Error;
+
+use crate::profile::source::PunktfSource;
+use crate::profile::LayeredProfile;
+use crate::visit::*;
+
+cfg_if! {
+	if #[cfg(unix)] {
+		/// Identifies the filesystem a path resides on.
+		///
+		/// On unix systems this is the device id of the filesystem.
+		type VolumeId = u64;
+
+		/// Retrieves the [`VolumeId`] for the existing `path`.
+		fn volume_id(path: &Path) -> io::Result<VolumeId> {
+			use std::os::unix::fs::MetadataExt as _;
+
+			path.metadata().map(|metadata| metadata.dev())
+		}
+	} else {
+		/// Identifies the filesystem a path resides on.
+		///
+		/// On non-unix systems this is the root of the path (e.g. `C:\`).
+		type VolumeId = PathBuf;
+
+		/// Retrieves the [`VolumeId`] for the existing `path`.
+		fn volume_id(path: &Path) -> io::Result<VolumeId> {
+			Ok(path
+				.ancestors()
+				.last()
+				.map(Path::to_path_buf)
+				.unwrap_or_default())
+		}
+	}
+}
+
+/// An error which is returned when the space check for a deployment fails.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SpaceError {
+	/// The filesystem does not have enough space left for the deployment.
+	#[error(
+		"Not enough space available on the filesystem of `{}` (required: {required} bytes; available: {available} bytes)",
+		path.display()
+	)]
+	Insufficient {
+		/// An existing path on the filesystem which was checked.
+		path: PathBuf,
+
+		/// Amount of bytes the deployment will write to the filesystem.
+		required: u64,
+
+		/// Amount of bytes available on the filesystem.
+		available: u64,
+	},
+}
+
+/// The estimated amount of bytes which will be written to a single filesystem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeRequirement {
+	/// An existing path on the filesystem, which is used to query the
+	/// available space.
+	pub path: PathBuf,
+
+	/// Amount of bytes which will be written to the filesystem.
+	pub required: u64,
+}
+
+/// A [`Visitor`](`crate::visit::Visitor`) implementation which accumulates the
+/// amount of bytes each filesystem needs to have available for a deployment.
+///
+/// Files which already exist at the target location are accounted for, as
+/// their space will be freed once they are overwritten.
+#[derive(Default, Debug, Clone)]
+pub struct SpaceEstimator {
+	/// Accumulated requirements grouped by filesystem.
+	volumes: HashMap<VolumeId, VolumeRequirement>,
+
+	/// Cache of already resolved directories to avoid querying the
+	/// filesystem for each file in the same directory.
+	resolved_dirs: HashMap<PathBuf, (VolumeId, PathBuf)>,
+
+	/// Directory which prefixes all target paths (related:
+	/// [`Walker::with_destdir`]).
+	destdir: Option<PathBuf>,
+
+	/// Filter which selects the dotfiles to estimate (related:
+	/// [`Walker::with_filter`]).
+	filter: TagFilter,
+}
+
+impl SpaceEstimator {
+	/// Creates a new instance.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Estimates the requirements for a deployment which prefixes all target
+	/// paths with `destdir`.
+	pub fn with_destdir(mut self, destdir: Option<PathBuf>) -> Self {
+		self.destdir = destdir;
+		self
+	}
+
+	/// Only estimates the requirements of the dotfiles which are accepted by
+	/// `filter`.
+	pub fn with_filter(mut self, filter: TagFilter) -> Self {
+		self.filter = filter;
+		self
+	}
+
+	/// Runs the estimator to completion for a given profile.
+	pub fn estimate(mut self, source: &PunktfSource, profile: &mut LayeredProfile) -> Self {
+		let walker = Walker::new(profile)
+			.with_destdir(self.destdir.clone())
+			.with_filter(self.filter.clone())
+			.with_extra_paths(true);
+
+		if let Err(err) = walker.walk(source, &mut self) {
+			log::warn!("Failed to estimate required space: {err}");
+		}
+
+		self
+	}
+
+	/// Returns the estimated requirements for all filesystems touched by the
+	/// deployment.
+	pub fn requirements(&self) -> impl Iterator<Item = &VolumeRequirement> {
+		self.volumes.values()
+	}
+
+	/// Checks for each filesystem that the required space is available.
+	///
+	/// If the available space of a filesystem can not be queried, a warning is
+	/// logged and the filesystem is not checked.
+	///
+	/// # Errors
+	///
+	/// An error is returned for the first filesystem which does not have
+	/// enough space available.
+	pub fn check(&self) -> std::result::Result<(), SpaceError> {
+		for VolumeRequirement { path, required } in self.requirements() {
+			let available = match fs4::available_space(path) {
+				Ok(available) => available,
+				Err(err) => {
+					log::warn!(
+						"[{}] Failed to query available space: {err}",
+						path.display()
+					);
+					continue;
+				}
+			};
+
+			log::debug!(
+				"[{}] Space required: {required} bytes; available: {available} bytes",
+				path.display()
+			);
+
+			if *required > available {
+				return Err(SpaceError::Insufficient {
+					path: path.clone(),
+					required: *required,
+					available,
+				});
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Records that `bytes` will be written to `target_path`.
+	fn add(&mut self, target_path: &Path, bytes: u64) -> io::Result<()> {
+		let dir = target_path.parent().unwrap_or(target_path);
+
+		let (volume, existing) = if let Some(resolved) = self.resolved_dirs.get(dir) {
+			resolved.clone()
+		} else {
+			// Directories which do not exist yet will be created on the
+			// filesystem of the nearest existing ancestor.
+			let existing = dir
+				.ancestors()
+				.find(|ancestor| ancestor.exists())
+				.ok_or_else(|| {
+					io::Error::new(io::ErrorKind::NotFound, "No existing ancestor found")
+				})?
+				.to_path_buf();
+
+			let resolved = (volume_id(&existing)?, existing);
+			self.resolved_dirs
+				.insert(dir.to_path_buf(), resolved.clone());
+			resolved
+		};
+
+		self.volumes
+			.entry(volume)
+			.or_insert_with(|| VolumeRequirement {
+				path: existing,
+				required: 0,
+			})
+			.required += bytes;
+
+		Ok(())
+	}
+}
+
+impl Visitor for SpaceEstimator {
+	/// Accepts a file item and records the size of it.
+	///
+	/// For templates the size of the source file is used as an estimate.
+	fn accept_file<'a>(&mut self, _: &PunktfSource, _: &LayeredProfile, file: &File<'a>) -> Result {
+		let required = match file.source_path.metadata() {
+			Ok(metadata) => metadata.len(),
+			Err(err) => {
+				log::debug!(
+					"[{}] Failed to read metadata for space estimation: {err}",
+					file.relative_source_path.display()
+				);
+				return Ok(());
+			}
+		};
+
+		let freed = file
+			.target_path
+			.symlink_metadata()
+			.ok()
+			.filter(|metadata| metadata.is_file())
+			.map(|metadata| metadata.len())
+			.unwrap_or(0);
+
+		if let Err(err) = self.add(&file.target_path, required.saturating_sub(freed)) {
+			log::debug!(
+				"[{}] Failed to resolve target filesystem for space estimation: {err}",
+				file.relative_source_path.display()
+			);
+		}
+
+		Ok(())
+	}
+
+	/// Accepts a directory item and ignores it, as the space needed for a
+	/// directory is negligible.
+	fn accept_directory<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Directory<'a>,
+	) -> Result {
+		Ok(())
+	}
+
+	/// Accepts a link item and ignores it, as the space needed for a link is
+	/// negligible.
+	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, _: &Symlink) -> Result {
+		Ok(())
+	}
+
+	/// Accepts a rejected item and ignores it.
+	fn accept_rejected<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Rejected<'a>,
+	) -> Result {
+		Ok(())
+	}
+
+	/// Accepts a errored item and ignores it.
+	fn accept_errored<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Errored<'a>,
+	) -> Result {
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn space_accumulates_per_volume() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = std::env::temp_dir();
+
+		let mut estimator = SpaceEstimator::new();
+		estimator.add(&dir.join("a/b/c.txt"), 10)?;
+		estimator.add(&dir.join("a/d.txt"), 5)?;
+
+		let requirements: Vec<_> = estimator.requirements().collect();
+		assert_eq!(requirements.len(), 1);
+		assert_eq!(requirements[0].required, 15);
+
+		assert!(estimator.check().is_ok());
+
+		estimator.add(&dir.join("e.txt"), u64::MAX - 15)?;
+		assert!(matches!(
+			estimator.check(),
+			Err(SpaceError::Insufficient { .. })
+		));
+
+		Ok(())
+	}
+}
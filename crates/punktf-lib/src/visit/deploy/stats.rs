@@ -0,0 +1,136 @@
+//! Statistics about a [deployment](`super::deployment::Deployment`).
+//!
+//! The time spent on each item is tracked while deploying (related:
+//! [`DeploymentBuilder::start_item`](`super::deployment::DeploymentBuilder::start_item`)).
+//! The time is further split into the time needed to resolve templates and
+//! the time spent on filesystem operations, which helps to find out why a
+//! deployment is slow (e.g. on a network filesystem).
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::deployment::{Deployment, ItemStatus};
+
+/// Time spent on and bytes written for a single deployed item.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemTiming {
+	/// Total wall time spent on the item.
+	pub total: Duration,
+
+	/// Time spent resolving the template of the item.
+	pub template: Duration,
+
+	/// Time spent reading and writing files of the item.
+	pub io: Duration,
+
+	/// Number of bytes written to the target.
+	pub bytes_written: u64,
+}
+
+/// Statistics of a [`Deployment`] (related: [`Deployment::stats`]).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DeploymentStats {
+	/// Number of items which were deployed successfully.
+	pub succeeded: usize,
+
+	/// Number of items which failed to deploy.
+	pub failed: usize,
+
+	/// Number of items which were skipped.
+	pub skipped: usize,
+
+	/// Sum of the timings of all items.
+	pub total: ItemTiming,
+
+	/// Timings of all items, the slowest first.
+	items: Vec<(PathBuf, ItemTiming)>,
+}
+
+impl DeploymentStats {
+	/// Collects the statistics of `deployment`.
+	pub fn new(deployment: &Deployment) -> Self {
+		let mut stats = Self::default();
+
+		let statuses = deployment
+			.dotfiles()
+			.values()
+			.map(|dotfile| dotfile.status())
+			.chain(deployment.symlinks().values().map(|link| link.status()));
+
+		for status in statuses {
+			match status {
+				ItemStatus::Failed(_) => stats.failed += 1,
+				ItemStatus::Skipped(_) => stats.skipped += 1,
+				_ => stats.succeeded += 1,
+			}
+		}
+
+		for timing in deployment.timings().values() {
+			stats.total.total += timing.total;
+			stats.total.template += timing.template;
+			stats.total.io += timing.io;
+			stats.total.bytes_written += timing.bytes_written;
+		}
+
+		stats.items = deployment
+			.timings()
+			.iter()
+			.map(|(path, timing)| (path.clone(), *timing))
+			.collect();
+
+		stats
+			.items
+			.sort_by(|(a_path, a), (b_path, b)| b.total.cmp(&a.total).then(a_path.cmp(b_path)));
+
+		stats
+	}
+
+	/// Returns the `n` items which took the longest to deploy, the slowest
+	/// first.
+	pub fn slowest(&self, n: usize) -> impl Iterator<Item = (&Path, &ItemTiming)> {
+		self.items
+			.iter()
+			.take(n)
+			.map(|(path, timing)| (path.as_path(), timing))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::profile::dotfile::Dotfile;
+
+	#[test]
+	fn deployment_stats() {
+		crate::tests::setup_test_env();
+
+		let mut builder = Deployment::build();
+
+		for (name, millis, status) in [
+			("fast", 1, ItemStatus::success()),
+			("slow", 30, ItemStatus::failed("error")),
+			("medium", 10, ItemStatus::skipped("skipped")),
+		] {
+			builder.start_item();
+			std::thread::sleep(Duration::from_millis(millis));
+			builder.add_io_time(Duration::from_millis(millis));
+			builder.add_bytes_written(millis);
+			builder.add_dotfile(PathBuf::from(name), Dotfile::default(), status);
+		}
+
+		let stats = builder.finish().stats();
+
+		assert_eq!((stats.succeeded, stats.failed, stats.skipped), (1, 1, 1));
+		assert_eq!(stats.total.io, Duration::from_millis(41));
+		assert_eq!(stats.total.bytes_written, 41);
+		assert_eq!(
+			stats
+				.slowest(2)
+				.map(|(path, _)| path.to_path_buf())
+				.collect::<Vec<_>>(),
+			[PathBuf::from("slow"), PathBuf::from("medium")]
+		);
+	}
+}
@@ -0,0 +1,108 @@
+//! Pre-flight checks for the target root directory of a deployment.
+//!
+//! If the target directory of a profile does not exist (e.g. it lives on a
+//! drive which is not mounted), each item would silently create its parent
+//! directories, leaving a partial deployment in the wrong location behind.
+//! Instead the deployment fails before anything is written, unless
+//! [`Profile::create_target`](`crate::profile::Profile::create_target`) is set.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+/// An error which is returned when the check of the target directory fails.
+#[derive(Error, Debug)]
+pub enum TargetError {
+	/// The target directory does not exist.
+	#[error(
+		"Target directory `{}` does not exist (set `create_target: true` to create it)",
+		path.display()
+	)]
+	Missing {
+		/// Path of the target directory.
+		path: PathBuf,
+	},
+
+	/// The target path exists, but is not a directory.
+	#[error("Target `{}` is not a directory", path.display())]
+	NotADirectory {
+		/// Path of the target.
+		path: PathBuf,
+	},
+
+	/// The target directory could not be created.
+	#[error("Failed to create target directory `{}`: {source}", path.display())]
+	Create {
+		/// Path of the target directory.
+		path: PathBuf,
+
+		/// The underlying error.
+		source: io::Error,
+	},
+}
+
+/// Checks that the target directory `path` exists.
+///
+/// If it does not exist and `create` is set, the directory (and all missing
+/// parents) are created. If `dry_run` is set, nothing is created.
+///
+/// # Errors
+///
+/// An error is returned if the directory does not exist and `create` is not
+/// set, if the path is not a directory or if it could not be created.
+pub fn check(path: &Path, create: bool, dry_run: bool) -> Result<(), TargetError> {
+	match path.metadata() {
+		Ok(metadata) if metadata.is_dir() => Ok(()),
+		Ok(_) => Err(TargetError::NotADirectory {
+			path: path.to_path_buf(),
+		}),
+		Err(_) if !create => Err(TargetError::Missing {
+			path: path.to_path_buf(),
+		}),
+		Err(_) if dry_run => {
+			log::info!("[{}] Would create target directory", path.display());
+			Ok(())
+		}
+		Err(_) => {
+			log::info!("[{}] Creating target directory", path.display());
+			std::fs::create_dir_all(path).map_err(|source| TargetError::Create {
+				path: path.to_path_buf(),
+				source,
+			})
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn target_check() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		let target = dir.join("a/b");
+
+		assert!(matches!(
+			check(&target, false, false),
+			Err(TargetError::Missing { .. })
+		));
+		assert!(check(&target, true, true).is_ok());
+		assert!(!target.exists());
+
+		assert!(check(&target, true, false).is_ok());
+		assert!(target.is_dir());
+		assert!(check(&target, false, false).is_ok());
+
+		let file = dir.join("file");
+		std::fs::write(&file, "")?;
+		assert!(matches!(
+			check(&file, true, false),
+			Err(TargetError::NotADirectory { .. })
+		));
+
+		Ok(())
+	}
+}
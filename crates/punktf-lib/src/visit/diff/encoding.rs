@@ -0,0 +1,214 @@
+//! Decoding of target files which are not necessarily encoded as UTF-8.
+//!
+//! Without the `diff-transcode` feature only UTF-8 is supported. With it,
+//! UTF-16 (detected by a byte order mark or the distribution of zero bytes)
+//! and Latin-1 as fallback for all other non-binary files are supported as
+//! well. This allows to diff e.g. configuration files generated by Windows
+//! applications.
+
+use std::fmt;
+use std::io;
+
+/// Text encoding of a file.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+	/// UTF-8 encoding.
+	#[default]
+	Utf8,
+
+	/// UTF-16 encoding with little endian byte order.
+	Utf16Le,
+
+	/// UTF-16 encoding with big endian byte order.
+	Utf16Be,
+
+	/// Latin-1 (ISO-8859-1) encoding.
+	Latin1,
+}
+
+impl Encoding {
+	/// Checks if this is the UTF-8 encoding.
+	pub const fn is_utf8(&self) -> bool {
+		matches!(self, Self::Utf8)
+	}
+}
+
+impl fmt::Display for Encoding {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Utf8 => "UTF-8",
+			Self::Utf16Le => "UTF-16LE",
+			Self::Utf16Be => "UTF-16BE",
+			Self::Latin1 => "Latin-1",
+		})
+	}
+}
+
+/// Creates the error which is returned for data that could not be decoded.
+fn invalid_data() -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidData,
+		"stream did not contain valid text",
+	)
+}
+
+/// Decodes `bytes` as text.
+///
+/// Returns the decoded text together with the detected encoding.
+///
+/// # Errors
+///
+/// An error of kind [`io::ErrorKind::InvalidData`] is returned if the bytes
+/// are not valid text in any supported encoding (e.g. binary data).
+pub fn decode(bytes: Vec<u8>) -> io::Result<(String, Encoding)> {
+	cfg_if::cfg_if! {
+		if #[cfg(feature = "diff-transcode")] {
+			transcode(bytes)
+		} else {
+			String::from_utf8(bytes)
+				.map(|text| (text, Encoding::Utf8))
+				.map_err(|_| invalid_data())
+		}
+	}
+}
+
+/// Reads the file at `path` and decodes it with [`decode`].
+///
+/// # Errors
+///
+/// An error is returned if the file could not be read or decoded.
+pub fn read_to_string(path: &std::path::Path) -> io::Result<(String, Encoding)> {
+	decode(std::fs::read(path)?)
+}
+
+/// Decodes `bytes` by detecting the encoding.
+#[cfg(feature = "diff-transcode")]
+fn transcode(bytes: Vec<u8>) -> io::Result<(String, Encoding)> {
+	let encoding = match bytes.as_slice() {
+		[0xFF, 0xFE, ..] => return decode_utf16(&bytes[2..], Encoding::Utf16Le),
+		[0xFE, 0xFF, ..] => return decode_utf16(&bytes[2..], Encoding::Utf16Be),
+		_ => guess_utf16(&bytes),
+	};
+
+	if let Some(encoding) = encoding {
+		if let Ok(decoded) = decode_utf16(&bytes, encoding) {
+			return Ok(decoded);
+		}
+	}
+
+	match String::from_utf8(bytes) {
+		Ok(text) => Ok((text, Encoding::Utf8)),
+		// A zero byte is a strong indication for binary data
+		Err(err) if err.as_bytes().contains(&0) => Err(invalid_data()),
+		Err(err) => Ok((
+			err.into_bytes().into_iter().map(char::from).collect(),
+			Encoding::Latin1,
+		)),
+	}
+}
+
+/// Guesses if `bytes` are UTF-16 encoded text without a byte order mark.
+///
+/// Text which consists mostly of ASCII characters has a zero byte in every
+/// code unit. Depending on the position of it, the byte order is detected.
+#[cfg(feature = "diff-transcode")]
+fn guess_utf16(bytes: &[u8]) -> Option<Encoding> {
+	if bytes.len() < 2 || !bytes.len().is_multiple_of(2) {
+		return None;
+	}
+
+	let units = bytes.len() / 2;
+	let (mut even, mut odd) = (0, 0);
+
+	for unit in bytes.chunks_exact(2) {
+		even += usize::from(unit[0] == 0);
+		odd += usize::from(unit[1] == 0);
+	}
+
+	// At least three quarters of the code units must contain a zero byte
+	// at the same position, and none at the other.
+	if odd * 4 >= units * 3 && even == 0 {
+		Some(Encoding::Utf16Le)
+	} else if even * 4 >= units * 3 && odd == 0 {
+		Some(Encoding::Utf16Be)
+	} else {
+		None
+	}
+}
+
+/// Decodes `bytes` as UTF-16 with the byte order of `encoding`.
+#[cfg(feature = "diff-transcode")]
+fn decode_utf16(bytes: &[u8], encoding: Encoding) -> io::Result<(String, Encoding)> {
+	if !bytes.len().is_multiple_of(2) {
+		return Err(invalid_data());
+	}
+
+	let units = bytes.chunks_exact(2).map(|unit| {
+		let unit = [unit[0], unit[1]];
+
+		if encoding == Encoding::Utf16Be {
+			u16::from_be_bytes(unit)
+		} else {
+			u16::from_le_bytes(unit)
+		}
+	});
+
+	char::decode_utf16(units)
+		.collect::<Result<String, _>>()
+		.map(|text| (text, encoding))
+		.map_err(|_| invalid_data())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decode_utf8() {
+		crate::tests::setup_test_env();
+
+		assert_eq!(
+			decode(b"key = value\n".to_vec()).unwrap(),
+			(String::from("key = value\n"), Encoding::Utf8)
+		);
+		assert_eq!(
+			decode(vec![0x00, 0x9F, 0x92, 0x96]).unwrap_err().kind(),
+			io::ErrorKind::InvalidData
+		);
+	}
+
+	#[test]
+	#[cfg(feature = "diff-transcode")]
+	fn decode_other_encodings() {
+		crate::tests::setup_test_env();
+
+		let utf16 = |bom: &[u8], be: bool| {
+			let mut bytes = bom.to_vec();
+			for unit in "[Section]\r\nkey=välue\r\n".encode_utf16() {
+				if be {
+					bytes.extend(unit.to_be_bytes());
+				} else {
+					bytes.extend(unit.to_le_bytes());
+				}
+			}
+			bytes
+		};
+
+		for (bytes, encoding) in [
+			(utf16(&[0xFF, 0xFE], false), Encoding::Utf16Le),
+			(utf16(&[0xFE, 0xFF], true), Encoding::Utf16Be),
+			(utf16(&[], false), Encoding::Utf16Le),
+			(utf16(&[], true), Encoding::Utf16Be),
+		] {
+			assert_eq!(
+				decode(bytes).unwrap(),
+				(String::from("[Section]\r\nkey=välue\r\n"), encoding)
+			);
+		}
+
+		assert_eq!(
+			decode(b"key=v\xE4lue\n".to_vec()).unwrap(),
+			(String::from("key=välue\n"), Encoding::Latin1)
+		);
+	}
+}
@@ -1,12 +1,17 @@
 //! A [`Visitor`](`crate::visit::Visitor`) implementation which creates events for
-//! files which differ from the content it would have once deployed.
+//! files and links which differ from the state they would have once deployed.
 
 use crate::{
 	profile::LayeredProfile,
-	profile::{source::PunktfSource, transform::Transform},
+	profile::{link::LinkKind, source::PunktfSource, transform::Transform},
 	visit::*,
 };
-use std::path::Path;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+pub mod encoding;
+
+use self::encoding::Encoding;
 
 /// Applies any relevant [`Transform`](`crate::profile::transform::Transform`)
 /// for the given file.
@@ -14,7 +19,7 @@ fn transform_content(
 	profile: &LayeredProfile,
 	file: &File<'_>,
 	content: String,
-) -> color_eyre::Result<String> {
+) -> crate::error::Result<String> {
 	let mut content = content;
 
 	// Copy so we exec_dotfile is not referenced by this in case an error occurs.
@@ -23,9 +28,14 @@ fn transform_content(
 	// Apply transformers.
 	// Order:
 	//   - Transformers which are specified in the profile root
+	//   - Transformers which are specified in the profile for the file extension
 	//   - Transformers which are specified on a specific dotfile of a profile
-	for transformer in profile.transformers().chain(exec_transformers.iter()) {
-		content = transformer.transform(content)?;
+	for transformer in profile
+		.transformers()
+		.chain(profile.extension_transformers(&file.target_path))
+		.chain(exec_transformers.iter())
+	{
+		content = transformer.transform_with_env(content, profile.env())?;
 	}
 
 	Ok(content)
@@ -63,6 +73,12 @@ pub enum Event<'a> {
 		/// Contents of the current file on the filesystem.
 		old_content: String,
 
+		/// Detected encoding of the current file on the filesystem.
+		///
+		/// Anything other than UTF-8 is only detected with the
+		/// `diff-transcode` feature.
+		old_encoding: Encoding,
+
 		/// Contents of the file after a deployment.
 		///
 		/// #NOTE
@@ -70,6 +86,37 @@ pub enum Event<'a> {
 		/// fully resolved.
 		new_content: String,
 	},
+
+	/// Link does currently not exist but would be created.
+	NewLink {
+		/// Absolute path of the link source.
+		source_path: &'a Path,
+
+		/// Absolute path to the target location.
+		target_path: &'a Path,
+	},
+
+	/// A link exists at the target location but points to a different source.
+	RetargetedLink {
+		/// Absolute path of the link source.
+		source_path: &'a Path,
+
+		/// Absolute path to the target location.
+		target_path: &'a Path,
+
+		/// Source the existing link currently points to.
+		current_source_path: PathBuf,
+	},
+
+	/// Something other than the expected link (e.g. a regular file) exists at
+	/// the target location.
+	TypeConflict {
+		/// Absolute path of the link source.
+		source_path: &'a Path,
+
+		/// Absolute path to the target location.
+		target_path: &'a Path,
+	},
 }
 
 impl Event<'_> {
@@ -79,50 +126,336 @@ impl Event<'_> {
 			Self::NewFile { target_path, .. } => target_path,
 			Self::NewDirectory { target_path, .. } => target_path,
 			Self::Diff { target_path, .. } => target_path,
+			Self::NewLink { target_path, .. } => target_path,
+			Self::RetargetedLink { target_path, .. } => target_path,
+			Self::TypeConflict { target_path, .. } => target_path,
+		}
+	}
+}
+
+/// An owned version of [`Event`], which can be kept after the diff is done
+/// (related: [`Diff::collect`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OwnedEvent {
+	/// File does currently not exist but would be created.
+	NewFile {
+		/// Relative path to the punktf source.
+		relative_source_path: PathBuf,
+
+		/// Absolute path to the target location.
+		target_path: PathBuf,
+	},
+
+	/// Directory does currently not exist but would be created.
+	NewDirectory {
+		/// Relative path to the punktf source.
+		relative_source_path: PathBuf,
+
+		/// Absolute path to the target location.
+		target_path: PathBuf,
+	},
+
+	/// File does exist but the contents would changed.
+	Diff {
+		/// Relative path to the punktf source.
+		relative_source_path: PathBuf,
+
+		/// Absolute path to the target location.
+		target_path: PathBuf,
+
+		/// Contents of the current file on the filesystem.
+		old_content: String,
+
+		/// Detected encoding of the current file on the filesystem.
+		old_encoding: Encoding,
+
+		/// Contents of the file after a deployment.
+		new_content: String,
+	},
+
+	/// Link does currently not exist but would be created.
+	NewLink {
+		/// Absolute path of the link source.
+		source_path: PathBuf,
+
+		/// Absolute path to the target location.
+		target_path: PathBuf,
+	},
+
+	/// A link exists at the target location but points to a different source.
+	RetargetedLink {
+		/// Absolute path of the link source.
+		source_path: PathBuf,
+
+		/// Absolute path to the target location.
+		target_path: PathBuf,
+
+		/// Source the existing link currently points to.
+		current_source_path: PathBuf,
+	},
+
+	/// Something other than the expected link (e.g. a regular file) exists at
+	/// the target location.
+	TypeConflict {
+		/// Absolute path of the link source.
+		source_path: PathBuf,
+
+		/// Absolute path to the target location.
+		target_path: PathBuf,
+	},
+}
+
+impl OwnedEvent {
+	/// Returns the absolute target path for the diff.
+	pub fn target_path(&self) -> &Path {
+		match self {
+			Self::NewFile { target_path, .. } => target_path,
+			Self::NewDirectory { target_path, .. } => target_path,
+			Self::Diff { target_path, .. } => target_path,
+			Self::NewLink { target_path, .. } => target_path,
+			Self::RetargetedLink { target_path, .. } => target_path,
+			Self::TypeConflict { target_path, .. } => target_path,
+		}
+	}
+
+	/// Returns the path relative to the punktf source.
+	///
+	/// For links this is the absolute path of the link source, as it does
+	/// not need to be part of the punktf source.
+	pub fn relative_source_path(&self) -> &Path {
+		match self {
+			Self::NewFile {
+				relative_source_path,
+				..
+			} => relative_source_path,
+			Self::NewDirectory {
+				relative_source_path,
+				..
+			} => relative_source_path,
+			Self::Diff {
+				relative_source_path,
+				..
+			} => relative_source_path,
+			Self::NewLink { source_path, .. } => source_path,
+			Self::RetargetedLink { source_path, .. } => source_path,
+			Self::TypeConflict { source_path, .. } => source_path,
+		}
+	}
+}
+
+impl From<Event<'_>> for OwnedEvent {
+	fn from(event: Event<'_>) -> Self {
+		match event {
+			Event::NewFile {
+				relative_source_path,
+				target_path,
+			} => Self::NewFile {
+				relative_source_path: relative_source_path.to_path_buf(),
+				target_path: target_path.to_path_buf(),
+			},
+			Event::NewDirectory {
+				relative_source_path,
+				target_path,
+			} => Self::NewDirectory {
+				relative_source_path: relative_source_path.to_path_buf(),
+				target_path: target_path.to_path_buf(),
+			},
+			Event::Diff {
+				relative_source_path,
+				target_path,
+				old_content,
+				old_encoding,
+				new_content,
+			} => Self::Diff {
+				relative_source_path: relative_source_path.to_path_buf(),
+				target_path: target_path.to_path_buf(),
+				old_content,
+				old_encoding,
+				new_content,
+			},
+			Event::NewLink {
+				source_path,
+				target_path,
+			} => Self::NewLink {
+				source_path: source_path.to_path_buf(),
+				target_path: target_path.to_path_buf(),
+			},
+			Event::RetargetedLink {
+				source_path,
+				target_path,
+				current_source_path,
+			} => Self::RetargetedLink {
+				source_path: source_path.to_path_buf(),
+				target_path: target_path.to_path_buf(),
+				current_source_path,
+			},
+			Event::TypeConflict {
+				source_path,
+				target_path,
+			} => Self::TypeConflict {
+				source_path: source_path.to_path_buf(),
+				target_path: target_path.to_path_buf(),
+			},
+		}
+	}
+}
+
+impl<'a> From<&'a OwnedEvent> for Event<'a> {
+	fn from(event: &'a OwnedEvent) -> Self {
+		match event {
+			OwnedEvent::NewFile {
+				relative_source_path,
+				target_path,
+			} => Self::NewFile {
+				relative_source_path,
+				target_path,
+			},
+			OwnedEvent::NewDirectory {
+				relative_source_path,
+				target_path,
+			} => Self::NewDirectory {
+				relative_source_path,
+				target_path,
+			},
+			OwnedEvent::Diff {
+				relative_source_path,
+				target_path,
+				old_content,
+				old_encoding,
+				new_content,
+			} => Self::Diff {
+				relative_source_path,
+				target_path,
+				old_content: old_content.clone(),
+				old_encoding: *old_encoding,
+				new_content: new_content.clone(),
+			},
+			OwnedEvent::NewLink {
+				source_path,
+				target_path,
+			} => Self::NewLink {
+				source_path,
+				target_path,
+			},
+			OwnedEvent::RetargetedLink {
+				source_path,
+				target_path,
+				current_source_path,
+			} => Self::RetargetedLink {
+				source_path,
+				target_path,
+				current_source_path: current_source_path.clone(),
+			},
+			OwnedEvent::TypeConflict {
+				source_path,
+				target_path,
+			} => Self::TypeConflict {
+				source_path,
+				target_path,
+			},
 		}
 	}
 }
 
 /// A [`Visitor`](`crate::visit::Visitor`) implementation which checks for
 /// changes which would be made by a deployment.
-/// For each change an [`Event`] is emitted which can be processed by [`Diff.0`].
-#[derive(Debug, Clone, Copy)]
-pub struct Diff<F>(F);
+/// For each change an [`Event`] is emitted which can be processed by the
+/// function given to [`Diff::new`] or collected with [`Diff::collect`].
+#[derive(Debug, Clone)]
+pub struct Diff<F> {
+	/// Function which processes the emitted events.
+	f: F,
+
+	/// Filter which selects the dotfiles to diff.
+	filter: TagFilter,
+}
 
 impl<F> Diff<F>
 where
 	F: Fn(Event<'_>),
 {
 	/// Creates a new instance of the visitor.
-	pub const fn new(f: F) -> Self {
-		Self(f)
+	pub fn new(f: F) -> Self {
+		Self {
+			f,
+			filter: TagFilter::default(),
+		}
+	}
+
+	/// Only diffs the dotfiles which are accepted by `filter`.
+	pub fn with_filter(mut self, filter: TagFilter) -> Self {
+		self.filter = filter;
+		self
 	}
 
 	/// Runs the visitor to completion for a given profile.
 	pub fn diff(self, source: &PunktfSource, profile: &mut LayeredProfile) {
-		let mut resolver = ResolvingVisitor(self);
-		let walker = Walker::new(profile);
+		let filter = self.filter.clone();
+		let mut resolver = ResolvingVisitor::new(self);
+		let walker = Walker::new(profile).with_filter(filter);
 
 		if let Err(err) = walker.walk(source, &mut resolver) {
 			log::error!("Failed to execute diff: {err}");
 		}
 	}
 
+	/// Runs the visitor to completion for a given profile and returns all
+	/// emitted events in the order they were emitted.
+	///
+	/// Each event is passed to the function given to [`Diff::new`] as well.
+	/// To only collect the events, a no-op function can be given (e.g.
+	/// `Diff::new(|_| {})`).
+	pub fn collect(self, source: &PunktfSource, profile: &mut LayeredProfile) -> Vec<OwnedEvent> {
+		let Self { f, filter } = self;
+		let events = RefCell::new(Vec::new());
+
+		Diff::new(|event: Event<'_>| {
+			let event = OwnedEvent::from(event);
+			f(Event::from(&event));
+			events.borrow_mut().push(event);
+		})
+		.with_filter(filter)
+		.diff(source, profile);
+
+		events.into_inner()
+	}
+
 	/// Emits the given event.
 	fn dispatch(&self, event: Event<'_>) {
-		(self.0)(event)
+		(self.f)(event)
 	}
 }
 
-/// Reads the contents of the given file at `path`.
+/// Checks if the link at `target_path`, which points to `current`, points to
+/// `expected`.
+///
+/// Relative link sources are resolved relative to the directory of the link.
+/// If both sources exist, they are compared after resolving all links.
+fn is_same_link_source(target_path: &Path, current: &Path, expected: &Path) -> bool {
+	let current = match target_path.parent() {
+		Some(parent) if current.is_relative() => parent.join(current),
+		_ => current.to_path_buf(),
+	};
+
+	if current == expected {
+		return true;
+	}
+
+	match (current.canonicalize(), expected.canonicalize()) {
+		(Ok(current), Ok(expected)) => current == expected,
+		_ => false,
+	}
+}
+
+/// Evaluates `read`, which reads the contents of a file.
 ///
 /// Handles common errors by logging them using `display_path` as identifier.
 ///
 /// Will either return the files contents or directly exit the outer function
 /// with `Ok(())`.
 macro_rules! safe_read_file_content {
-	($path:expr, $display_path:expr) => {{
-		match std::fs::read_to_string($path) {
+	($read:expr, $display_path:expr) => {{
+		match $read {
 			Ok(old) => old,
 			Err(err) if err.kind() == std::io::ErrorKind::InvalidData => {
 				log::info!("[{}] Ignored - Binary data", $display_path);
@@ -151,11 +484,15 @@ where
 		file: &File<'a>,
 	) -> Result {
 		if file.target_path.exists() {
-			let old =
-				safe_read_file_content!(&file.target_path, file.relative_source_path.display());
+			let (old, old_encoding) = safe_read_file_content!(
+				encoding::read_to_string(&file.target_path),
+				file.relative_source_path.display()
+			);
 
-			let new =
-				safe_read_file_content!(&file.source_path, file.relative_source_path.display());
+			let new = safe_read_file_content!(
+				file.read_source_to_string(),
+				file.relative_source_path.display()
+			);
 
 			let new = match transform_content(profile, file, new) {
 				Ok(new) => new,
@@ -173,6 +510,7 @@ where
 					relative_source_path: &file.relative_source_path,
 					target_path: &file.target_path,
 					old_content: old,
+					old_encoding,
 					new_content: new,
 				});
 			}
@@ -205,15 +543,80 @@ where
 		Ok(())
 	}
 
-	/// Accepts a rejected item and does nothing besides logging an info message.
+	/// Accepts a link item and checks if the link at the target location
+	/// points to the source.
 	///
-	/// # NOTE
-	/// Links are currently not supported for diffing.
+	/// If not, a change [`Event::NewLink`]/[`Event::RetargetedLink`]/
+	/// [`Event::TypeConflict`] is emitted.
 	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, link: &Symlink) -> Result {
-		log::info!(
-			"[{}] Ignoring - Symlinks are not supported for diffs",
-			link.source_path.display()
-		);
+		let source_path = &link.source_path;
+		let target_path = &link.target_path;
+
+		// Not using `exists` as it follows links and would report a dangling
+		// link as missing.
+		let target_metadata = match target_path.symlink_metadata() {
+			Ok(metadata) => metadata,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => {
+				self.dispatch(Event::NewLink {
+					source_path,
+					target_path,
+				});
+
+				return Ok(());
+			}
+			Err(err) => {
+				log::error!(
+					"[{}] Error - Failed to read link target metadata: {err}",
+					source_path.display()
+				);
+
+				return Ok(());
+			}
+		};
+
+		if link.kind == LinkKind::Hardlink {
+			// A hard link is a regular file, so it is only up to date if it
+			// points to the same file as the source.
+			if !target_metadata.is_file()
+				|| !same_file::is_same_file(source_path, target_path).unwrap_or(false)
+			{
+				self.dispatch(Event::TypeConflict {
+					source_path,
+					target_path,
+				});
+			}
+
+			return Ok(());
+		}
+
+		if !target_metadata.is_symlink() {
+			self.dispatch(Event::TypeConflict {
+				source_path,
+				target_path,
+			});
+
+			return Ok(());
+		}
+
+		let current_source_path = match std::fs::read_link(target_path) {
+			Ok(path) => path,
+			Err(err) => {
+				log::error!(
+					"[{}] Error - Failed to read link: {err}",
+					source_path.display()
+				);
+
+				return Ok(());
+			}
+		};
+
+		if !is_same_link_source(target_path, &current_source_path, source_path) {
+			self.dispatch(Event::RetargetedLink {
+				source_path,
+				target_path,
+				current_source_path,
+			});
+		}
 
 		Ok(())
 	}
@@ -266,14 +669,18 @@ where
 		file: &File<'a>,
 		// Returns a function to resolve the content to make the resolving lazy
 		// for upstream visitors.
-		resolve_content: impl FnOnce(&str) -> color_eyre::Result<String>,
+		resolve_content: impl FnOnce(&str) -> crate::error::Result<String>,
 	) -> Result {
 		if file.target_path.exists() {
-			let old =
-				safe_read_file_content!(&file.target_path, file.relative_source_path.display());
+			let (old, old_encoding) = safe_read_file_content!(
+				encoding::read_to_string(&file.target_path),
+				file.relative_source_path.display()
+			);
 
-			let new =
-				safe_read_file_content!(&file.source_path, file.relative_source_path.display());
+			let new = safe_read_file_content!(
+				file.read_source_to_string(),
+				file.relative_source_path.display()
+			);
 
 			let new = match resolve_content(&new) {
 				Ok(content) => content,
@@ -303,6 +710,7 @@ where
 					relative_source_path: &file.relative_source_path,
 					target_path: &file.target_path,
 					old_content: old,
+					old_encoding,
 					new_content: new,
 				});
 			}
@@ -316,3 +724,63 @@ where
 		Ok(())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	#[cfg(unix)]
+	fn link_events() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::write(dir.join("source"), "source")?;
+		std::fs::write(dir.join("other"), "other")?;
+		std::fs::write(dir.join("file"), "file")?;
+		std::os::unix::fs::symlink(dir.join("source"), dir.join("same"))?;
+		std::os::unix::fs::symlink("source", dir.join("relative"))?;
+		std::os::unix::fs::symlink(dir.join("other"), dir.join("retargeted"))?;
+
+		let source = PunktfSource::from_root(dir.to_path_buf())?;
+		let profile = LayeredProfile::build().finish();
+
+		let events = RefCell::new(Vec::new());
+		let mut diff = Diff::new(|event| events.borrow_mut().push(OwnedEvent::from(event)));
+
+		for target in ["missing", "same", "relative", "retargeted", "file"] {
+			let link = Symlink {
+				source_path: dir.join("source"),
+				target_path: dir.join(target),
+				replace: true,
+				kind: LinkKind::Symlink,
+				priority: None,
+				merge: None,
+			};
+
+			diff.accept_link(&source, &profile, &link)
+				.expect("Diffing a link never fails");
+		}
+
+		assert_eq!(
+			events.into_inner(),
+			[
+				OwnedEvent::NewLink {
+					source_path: dir.join("source"),
+					target_path: dir.join("missing"),
+				},
+				OwnedEvent::RetargetedLink {
+					source_path: dir.join("source"),
+					target_path: dir.join("retargeted"),
+					current_source_path: dir.join("other"),
+				},
+				OwnedEvent::TypeConflict {
+					source_path: dir.join("source"),
+					target_path: dir.join("file"),
+				},
+			]
+		);
+
+		Ok(())
+	}
+}
@@ -0,0 +1,428 @@
+//! A [`Visitor`](`crate::visit::Visitor`) implementation which checks the
+//! health of a deployed profile.
+//!
+//! Nothing is written while checking. For every item of the profile the
+//! deployed target is inspected for broken symlinks and permission problems.
+//! Symlinks of the profile are additionally checked to still point to their
+//! source. If a [`Manifest`] of previous deployments is given, all recorded
+//! target paths which are no longer part of the profile (e.g. because their
+//! source dotfile was deleted) are reported as well.
+
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::profile::link::LinkKind;
+use crate::profile::source::PunktfSource;
+use crate::profile::LayeredProfile;
+use crate::visit::deploy::manifest::Manifest;
+use crate::visit::*;
+
+/// Kind of a problem found by the [`Doctor`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum FindingKind {
+	/// The target is a symlink which points to a path that does not exist.
+	BrokenLink {
+		/// The path the symlink points to.
+		points_to: PathBuf,
+	},
+
+	/// The target of a symlink of the profile points to a different path than
+	/// the source of the link.
+	WrongLink {
+		/// The source path of the link.
+		expected: PathBuf,
+
+		/// The path the symlink actually points to.
+		actual: PathBuf,
+	},
+
+	/// A symlink of the profile was not deployed.
+	MissingLink,
+
+	/// The source of a dotfile does not exist (anymore).
+	MissingSource {
+		/// Path to the missing source.
+		source_path: PathBuf,
+	},
+
+	/// The target was deployed by a previous deployment but is no longer part
+	/// of the profile (related: [`Manifest::orphans`]).
+	Orphan,
+
+	/// The source can not be read or the target can not be written.
+	Permission {
+		/// Description of the problem.
+		reason: String,
+	},
+
+	/// The item could not be processed.
+	Errored {
+		/// Description of the error.
+		reason: String,
+	},
+}
+
+/// A problem found by the [`Doctor`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+	/// The target path which is affected.
+	pub path: PathBuf,
+
+	/// The kind of the problem.
+	pub kind: FindingKind,
+}
+
+/// The result of a [`Doctor`] run.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct Report {
+	/// All problems found, sorted by path.
+	pub findings: Vec<Finding>,
+}
+
+impl Report {
+	/// Checks if no problems were found.
+	pub const fn is_healthy(&self) -> bool {
+		self.findings.is_empty()
+	}
+}
+
+/// A [`Visitor`](`crate::visit::Visitor`) implementation which checks the
+/// deployed targets of all items of a profile.
+#[derive(Debug, Default, Clone)]
+pub struct Doctor {
+	/// Target paths recorded by previous deployments.
+	recorded: BTreeSet<PathBuf>,
+
+	/// Target paths of all items of the profile.
+	visited: BTreeSet<PathBuf>,
+
+	/// All problems found so far.
+	findings: Vec<Finding>,
+}
+
+impl Doctor {
+	/// Creates a new instance.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Reports the target paths recorded in `manifest` which are no longer
+	/// part of the profile.
+	pub fn with_manifest(mut self, manifest: &Manifest) -> Self {
+		self.recorded = manifest
+			.dotfiles
+			.iter()
+			.chain(manifest.symlinks.iter())
+			.cloned()
+			.collect();
+
+		self
+	}
+
+	/// Runs the doctor to completion for a given profile.
+	pub fn diagnose(mut self, source: &PunktfSource, profile: &mut LayeredProfile) -> Report {
+		let walker = Walker::new(profile);
+
+		if let Err(err) = walker.walk(source, &mut self) {
+			log::error!("Failed to check the profile: {err}");
+		}
+
+		for path in std::mem::take(&mut self.recorded) {
+			if self.visited.contains(&path) {
+				continue;
+			}
+
+			match path.symlink_metadata() {
+				Ok(metadata) if metadata.is_symlink() && path.metadata().is_err() => {
+					self.check_link(&path);
+				}
+				Ok(_) => self.report(path, FindingKind::Orphan),
+				// Already removed
+				Err(_) => {}
+			}
+		}
+
+		let mut findings = self.findings;
+		findings.sort_by(|a, b| a.path.cmp(&b.path));
+
+		Report { findings }
+	}
+
+	/// Records a problem.
+	fn report(&mut self, path: impl Into<PathBuf>, kind: FindingKind) {
+		self.findings.push(Finding {
+			path: path.into(),
+			kind,
+		});
+	}
+
+	/// Reports `path` if it is a symlink which points to a missing path.
+	///
+	/// Returns `true` if the path is a symlink.
+	fn check_link(&mut self, path: &Path) -> bool {
+		let Ok(points_to) = path.read_link() else {
+			return false;
+		};
+
+		if path.metadata().is_err() {
+			self.report(path, FindingKind::BrokenLink { points_to });
+		}
+
+		true
+	}
+
+	/// Reports `path` if it (or the closest existing parent directory) can
+	/// not be written.
+	fn check_writable(&mut self, path: &Path) {
+		let reason = match path.symlink_metadata() {
+			Ok(metadata) if metadata.permissions().readonly() => {
+				String::from("Target is read-only")
+			}
+			Ok(_) => return,
+			Err(_) => {
+				let Some(parent) = path.ancestors().skip(1).find(|parent| parent.exists()) else {
+					return;
+				};
+
+				match parent.metadata() {
+					Ok(metadata) if metadata.permissions().readonly() => {
+						format!("Parent directory {} is read-only", parent.display())
+					}
+					_ => return,
+				}
+			}
+		};
+
+		self.report(path, FindingKind::Permission { reason });
+	}
+
+	/// Checks the target of a dotfile item.
+	fn check_item(&mut self, item: &Item<'_>) {
+		self.visited.insert(item.target_path.clone());
+
+		if !self.check_link(&item.target_path) {
+			self.check_writable(&item.target_path);
+		}
+	}
+}
+
+impl Visitor for Doctor {
+	/// Accepts a file item and checks its source and target.
+	fn accept_file<'a>(&mut self, _: &PunktfSource, _: &LayeredProfile, file: &File<'a>) -> Result {
+		if let Err(err) = std::fs::File::open(&file.source_path) {
+			if err.kind() == io::ErrorKind::PermissionDenied {
+				self.report(
+					file.target_path.clone(),
+					FindingKind::Permission {
+						reason: format!("Source {} is not readable", file.source_path.display()),
+					},
+				);
+			}
+		}
+
+		self.check_item(file);
+
+		Ok(())
+	}
+
+	/// Accepts a directory item and checks its target.
+	fn accept_directory<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		directory: &Directory<'a>,
+	) -> Result {
+		self.check_item(directory);
+
+		Ok(())
+	}
+
+	/// Accepts a link item and checks that it points to its source.
+	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, link: &Symlink) -> Result {
+		self.visited.insert(link.target_path.clone());
+
+		if link.target_path.symlink_metadata().is_err() {
+			self.report(link.target_path.clone(), FindingKind::MissingLink);
+			return Ok(());
+		}
+
+		// Hard links can not be told apart from other files.
+		if link.kind == LinkKind::Hardlink {
+			return Ok(());
+		}
+
+		match link.target_path.read_link() {
+			Ok(actual) if actual != link.source_path => {
+				self.report(
+					link.target_path.clone(),
+					FindingKind::WrongLink {
+						expected: link.source_path.clone(),
+						actual,
+					},
+				);
+			}
+			Ok(_) => {
+				self.check_link(&link.target_path);
+			}
+			// Not a symlink; kept by the merge mode of the link
+			Err(_) => {}
+		}
+
+		Ok(())
+	}
+
+	/// Accepts a rejected item and ignores it, as it is never deployed.
+	fn accept_rejected<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Rejected<'a>,
+	) -> Result {
+		Ok(())
+	}
+
+	/// Accepts an errored item and reports it.
+	fn accept_errored<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		errored: &Errored<'a>,
+	) -> Result {
+		self.visited.insert(errored.target_path.clone());
+
+		let kind = if errored.source_path.symlink_metadata().is_err() {
+			FindingKind::MissingSource {
+				source_path: errored.source_path.clone(),
+			}
+		} else {
+			FindingKind::Errored {
+				reason: errored.to_string(),
+			}
+		};
+
+		self.report(errored.target_path.clone(), kind);
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use crate::profile::link::Symlink as ProfileLink;
+	use crate::profile::Profile;
+	use crate::visit::tests::dotfile;
+
+	#[test]
+	#[cfg(unix)]
+	fn diagnose() -> color_eyre::Result<()> {
+		use std::os::unix::fs::{symlink, PermissionsExt};
+
+		crate::tests::setup_test_env();
+
+		let dir = crate::tests::test_dir();
+		std::fs::create_dir_all(dir.join("source/dotfiles"))?;
+		std::fs::create_dir_all(dir.join("target"))?;
+
+		std::fs::write(dir.join("source/dotfiles/ok"), "ok")?;
+		std::fs::write(dir.join("source/dotfiles/cached"), "cached")?;
+		std::fs::write(dir.join("source/dotfiles/locked"), "locked")?;
+		std::fs::write(dir.join("source/link"), "link")?;
+
+		std::fs::write(dir.join("target/ok"), "ok")?;
+		symlink(dir.join("cache/cached"), dir.join("target/cached"))?;
+		std::fs::write(dir.join("target/locked"), "locked")?;
+		std::fs::set_permissions(dir.join("target/locked"), PermissionsExt::from_mode(0o444))?;
+		symlink(dir.join("source/other"), dir.join("target/wrong"))?;
+		std::fs::write(dir.join("target/orphan"), "orphan")?;
+
+		let source = PunktfSource::from_root(dir.join("source"))?;
+
+		let link = |target: &str| ProfileLink {
+			source_path: dir.join("source/link"),
+			target_path: dir.join("target").join(target),
+			replace: true,
+			kind: LinkKind::Symlink,
+			priority: None,
+			merge: None,
+			when: None,
+		};
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			Profile {
+				target: Some(dir.join("target")),
+				dotfiles: vec![
+					dotfile("ok"),
+					dotfile("cached"),
+					dotfile("locked"),
+					dotfile("deleted"),
+				],
+				symlinks: vec![link("wrong"), link("missing")],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let manifest = Manifest {
+			dotfiles: [dir.join("target/ok"), dir.join("target/orphan")].into(),
+			..Default::default()
+		};
+
+		let report = Doctor::new()
+			.with_manifest(&manifest)
+			.diagnose(&source, &mut profile);
+
+		let kinds: Vec<_> = report
+			.findings
+			.iter()
+			.map(|finding| {
+				(
+					finding.path.strip_prefix(&dir).unwrap_or(&finding.path),
+					&finding.kind,
+				)
+			})
+			.collect();
+
+		assert_eq!(
+			kinds,
+			vec![
+				(
+					Path::new("target/cached"),
+					&FindingKind::BrokenLink {
+						points_to: dir.join("cache/cached")
+					}
+				),
+				(
+					Path::new("target/deleted"),
+					&FindingKind::MissingSource {
+						source_path: dir.join("source/dotfiles/deleted")
+					}
+				),
+				(
+					Path::new("target/locked"),
+					&FindingKind::Permission {
+						reason: String::from("Target is read-only")
+					}
+				),
+				(Path::new("target/missing"), &FindingKind::MissingLink),
+				(Path::new("target/orphan"), &FindingKind::Orphan),
+				(
+					Path::new("target/wrong"),
+					&FindingKind::WrongLink {
+						expected: dir.join("source/link"),
+						actual: dir.join("source/other")
+					}
+				),
+			]
+		);
+		assert!(!report.is_healthy());
+
+		Ok(())
+	}
+}
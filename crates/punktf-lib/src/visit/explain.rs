@@ -0,0 +1,495 @@
+//! A [`Visitor`](`crate::visit::Visitor`) implementation which explains why a
+//! target path has its content.
+//!
+//! For this all items which would be deployed to the target path are
+//! collected in the order a deployment would process them. For each of them
+//! the profile layer it was defined in, the variables used to resolve it and
+//! the transformers which are applied are recorded. Finally the deployment
+//! rules (priority and merge mode) are evaluated to determine which item wins.
+
+use std::path::{Path, PathBuf};
+
+use crate::profile::dotfile::Dotfile;
+use crate::profile::source::PunktfSource;
+use crate::profile::transform::ContentTransformer;
+use crate::profile::{LayeredProfile, MergeMode};
+use crate::template::source::Source;
+use crate::template::{Template, VariableSource, VariableUsage};
+use crate::visit::*;
+
+/// The usage of a variable together with the name of the profile layer it
+/// was defined in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplainedVariable {
+	/// The usage of the variable as recorded by the template resolver.
+	pub usage: VariableUsage,
+
+	/// Name of the profile which defined the variable.
+	///
+	/// This is only set for variables with the source
+	/// [`VariableSource::Profile`](`crate::template::VariableSource::Profile`).
+	pub layer: Option<String>,
+}
+
+/// The outcome of a candidate for the target path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+	/// The candidate produces the content of the target path.
+	Deployed,
+
+	/// The candidate would be deployed but is overwritten by a later
+	/// candidate.
+	Overwritten {
+		/// Index of the candidate which overwrites this one.
+		by: usize,
+	},
+
+	/// The candidate is not deployed.
+	Lost {
+		/// Reason why the candidate is not deployed.
+		reason: String,
+	},
+
+	/// The candidate was rejected by the walker (e.g. it is excluded).
+	Rejected {
+		/// Reason why the candidate was rejected.
+		reason: String,
+	},
+
+	/// The candidate could not be processed.
+	Errored {
+		/// Description of the error.
+		reason: String,
+	},
+}
+
+/// An item which would be deployed to the explained target path.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+	/// The dotfile which defines this item.
+	pub dotfile: Dotfile,
+
+	/// Name of the profile layer which defines the dotfile.
+	pub layer: Option<String>,
+
+	/// Relative path to the item inside the `dotfiles` directory.
+	pub relative_source_path: PathBuf,
+
+	/// Whether the item is a directory.
+	pub is_directory: bool,
+
+	/// All variables which were used to resolve the item.
+	///
+	/// This is empty if the item is no template.
+	pub variables: Vec<ExplainedVariable>,
+
+	/// Error which occurred while resolving the template of the item.
+	pub template_error: Option<String>,
+
+	/// The transformers which are applied to the content of the item in the
+	/// order they run.
+	pub transformers: Vec<ContentTransformer>,
+
+	/// The outcome of the item.
+	pub outcome: Outcome,
+}
+
+impl Candidate {
+	/// Creates a new candidate for `item` with the given `outcome`.
+	///
+	/// No variables are recorded for the candidate.
+	pub fn new(
+		profile: &LayeredProfile,
+		item: &Item<'_>,
+		is_directory: bool,
+		outcome: Outcome,
+	) -> Self {
+		let dotfile = item.dotfile();
+
+		let layer = profile
+			.dotfiles
+			.iter()
+			.find(|(_, layered)| std::ptr::eq(layered, dotfile))
+			.and_then(|(idx, _)| profile.profile_names.get(*idx).cloned());
+
+		let transformers = if is_directory {
+			Vec::new()
+		} else {
+			profile
+				.transformers()
+				.chain(profile.extension_transformers(&item.target_path))
+				.chain(dotfile.transformers.iter())
+				.cloned()
+				.collect()
+		};
+
+		Self {
+			dotfile: dotfile.clone(),
+			layer,
+			relative_source_path: item.relative_source_path.clone(),
+			is_directory,
+			variables: Vec::new(),
+			template_error: None,
+			transformers,
+			outcome,
+		}
+	}
+}
+
+/// The explanation for a single target path.
+#[derive(Debug, Clone)]
+pub struct Explanation {
+	/// The explained target path.
+	pub target_path: PathBuf,
+
+	/// Whether the target path existed before the deployment.
+	pub target_exists: bool,
+
+	/// All items which would be deployed to the target path in the order a
+	/// deployment would process them.
+	pub candidates: Vec<Candidate>,
+}
+
+impl Explanation {
+	/// Returns the candidate which produces the content of the target path.
+	pub fn winner(&self) -> Option<&Candidate> {
+		self.candidates
+			.iter()
+			.find(|candidate| candidate.outcome == Outcome::Deployed)
+	}
+}
+
+/// A [`Visitor`](`crate::visit::Visitor`) implementation which collects all
+/// items of a profile which target a specific path.
+#[derive(Debug, Clone)]
+pub struct Explainer {
+	/// The (normalized) target path to explain.
+	target_path: PathBuf,
+
+	/// All collected items.
+	candidates: Vec<Candidate>,
+}
+
+impl Explainer {
+	/// Creates a new instance which explains `target_path`.
+	pub fn new<P: AsRef<Path>>(target_path: P) -> Self {
+		Self {
+			target_path: canonicalize_parent(target_path.as_ref()),
+			candidates: Vec::new(),
+		}
+	}
+
+	/// Runs the explainer to completion for a given profile.
+	pub fn explain(mut self, source: &PunktfSource, profile: &mut LayeredProfile) -> Explanation {
+		let walker = Walker::new(profile);
+
+		if let Err(err) = walker.walk(source, &mut self) {
+			log::error!("Failed to explain target: {err}");
+		}
+
+		let target_exists = self.target_path.symlink_metadata().is_ok();
+		let mut candidates = self.candidates;
+		evaluate_outcomes(&mut candidates, target_exists);
+
+		Explanation {
+			target_path: self.target_path,
+			target_exists,
+			candidates,
+		}
+	}
+
+	/// Checks if `item` is deployed to the explained target path.
+	fn is_relevant(&self, item: &Item<'_>) -> bool {
+		canonicalize_parent(&item.target_path) == self.target_path
+	}
+
+	/// Records a new candidate for `item`.
+	fn add(
+		&mut self,
+		profile: &LayeredProfile,
+		item: &Item<'_>,
+		is_directory: bool,
+		outcome: Outcome,
+	) -> &mut Candidate {
+		self.candidates
+			.push(Candidate::new(profile, item, is_directory, outcome));
+
+		self.candidates
+			.last_mut()
+			.expect("Candidate to be present after push")
+	}
+}
+
+/// Resolves the template `file` and returns all variables which were used.
+fn explain_variables(
+	profile: &LayeredProfile,
+	file: &File<'_>,
+) -> std::result::Result<Vec<ExplainedVariable>, String> {
+	// Binary files are never resolved as templates
+	let Ok(content) = file.read_source_to_string() else {
+		return Ok(Vec::new());
+	};
+
+	let source = Source::file(&file.source_path, &content);
+	let template = Template::parse_with_mode(
+		source,
+		profile.template_limits(),
+		file.dotfile().template_mode(),
+	)
+	.map_err(|err| err.to_string())?;
+
+	let (_, usages) = template
+		.resolve_with_usage(
+			Some(profile.variables()),
+			file.dotfile().variables.as_ref(),
+			profile.variable_resolution_order(),
+			profile.env(),
+		)
+		.map_err(|err| err.to_string())?;
+
+	Ok(usages
+		.into_iter()
+		.map(|usage| {
+			let layer = if usage.source == Some(VariableSource::Profile) {
+				profile
+					.variables
+					.inner
+					.get(&usage.name)
+					.and_then(|(idx, _)| profile.profile_names.get(*idx).cloned())
+			} else {
+				None
+			};
+
+			ExplainedVariable { usage, layer }
+		})
+		.collect())
+}
+
+/// Evaluates which of the `candidates` would be deployed by applying the same
+/// rules a deployment uses.
+///
+/// Candidates are processed in order. A candidate is skipped if a previously
+/// deployed one has a higher priority, otherwise the merge mode decides if an
+/// already existing target is overwritten.
+pub(super) fn evaluate_outcomes(candidates: &mut [Candidate], target_exists: bool) {
+	let mut current: Option<usize> = None;
+
+	for idx in 0..candidates.len() {
+		if candidates[idx].outcome != Outcome::Deployed {
+			continue;
+		}
+
+		let candidate = &candidates[idx];
+
+		if let Some(current_idx) = current {
+			let deployed = &candidates[current_idx];
+
+			if let (Some(a), Some(b)) = (candidate.dotfile.priority, deployed.dotfile.priority) {
+				if b > a {
+					let reason = format!(
+						"`{}` with higher priority ({} > {}) is already deployed",
+						deployed.relative_source_path.display(),
+						b.0,
+						a.0
+					);
+
+					candidates[idx].outcome = Outcome::Lost { reason };
+					continue;
+				}
+			}
+		}
+
+		// Directories are merged and not overwritten
+		let exists = current.is_some() || target_exists;
+
+		if exists && !candidate.is_directory {
+			match candidate.dotfile.merge.unwrap_or_default() {
+				MergeMode::Overwrite => {}
+				MergeMode::Keep => {
+					candidates[idx].outcome = Outcome::Lost {
+						reason: format!(
+							"Target already exists and merge mode is {:?}",
+							MergeMode::Keep
+						),
+					};
+					continue;
+				}
+				MergeMode::Ask => {
+					log::info!(
+						"[{}] Outcome depends on the answer to the merge question",
+						candidate.relative_source_path.display()
+					);
+				}
+				MergeMode::Merge => {
+					log::info!(
+						"[{}] Content is merged with the existing target",
+						candidate.relative_source_path.display()
+					);
+				}
+			}
+		}
+
+		if let Some(current_idx) = current {
+			candidates[current_idx].outcome = Outcome::Overwritten { by: idx };
+		}
+
+		current = Some(idx);
+	}
+}
+
+impl Visitor for Explainer {
+	/// Accepts a file item and records it if it targets the explained path.
+	fn accept_file<'a>(
+		&mut self,
+		_: &PunktfSource,
+		profile: &LayeredProfile,
+		file: &File<'a>,
+	) -> Result {
+		if !self.is_relevant(file) {
+			return Ok(());
+		}
+
+		// Only the builtin engine records which variables were used
+		let variables = if file.dotfile().is_template() && file.dotfile().engine().is_builtin() {
+			explain_variables(profile, file)
+		} else {
+			Ok(Vec::new())
+		};
+
+		let candidate = self.add(profile, file, false, Outcome::Deployed);
+
+		match variables {
+			Ok(variables) => candidate.variables = variables,
+			Err(err) => {
+				candidate.outcome = Outcome::Errored {
+					reason: String::from("Failed to resolve template"),
+				};
+				candidate.template_error = Some(err);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Accepts a directory item and records it if it targets the explained
+	/// path.
+	fn accept_directory<'a>(
+		&mut self,
+		_: &PunktfSource,
+		profile: &LayeredProfile,
+		directory: &Directory<'a>,
+	) -> Result {
+		if self.is_relevant(directory) {
+			self.add(profile, directory, true, Outcome::Deployed);
+		}
+
+		Ok(())
+	}
+
+	/// Accepts a link item and ignores it.
+	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, _: &Symlink) -> Result {
+		Ok(())
+	}
+
+	/// Accepts a rejected item and records it if it targets the explained
+	/// path.
+	fn accept_rejected<'a>(
+		&mut self,
+		_: &PunktfSource,
+		profile: &LayeredProfile,
+		rejected: &Rejected<'a>,
+	) -> Result {
+		if self.is_relevant(rejected) {
+			let outcome = Outcome::Rejected {
+				reason: rejected.reason.to_string(),
+			};
+
+			self.add(profile, rejected, false, outcome);
+		}
+
+		Ok(())
+	}
+
+	/// Accepts an errored item and records it if it targets the explained
+	/// path.
+	fn accept_errored<'a>(
+		&mut self,
+		_: &PunktfSource,
+		profile: &LayeredProfile,
+		errored: &Errored<'a>,
+	) -> Result {
+		if self.is_relevant(errored) {
+			let outcome = Outcome::Errored {
+				reason: errored.to_string(),
+			};
+
+			self.add(profile, errored, false, outcome);
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::BTreeMap;
+
+	use super::*;
+	use crate::profile::Priority;
+
+	/// Creates a candidate for a file dotfile with the given settings.
+	fn candidate(path: &str, priority: Option<u32>, merge: Option<MergeMode>) -> Candidate {
+		Candidate {
+			dotfile: Dotfile {
+				path: PathBuf::from(path),
+				rename: None,
+				rename_map: BTreeMap::new(),
+				overwrite_target: None,
+				target_name: None,
+				priority: priority.map(Priority::new),
+				variables: None,
+				variables_file: None,
+				transformers: Vec::new(),
+				merge,
+				template: None,
+				template_mode: None,
+				engine: None,
+				binary: None,
+				strategy: None,
+				windows_acl: None,
+				encrypted: None,
+				preserve_attributes: None,
+				follow_source_symlinks: None,
+				tags: Vec::new(),
+				enabled: None,
+				extract: None,
+			},
+			layer: None,
+			relative_source_path: PathBuf::from(path),
+			is_directory: false,
+			variables: Vec::new(),
+			template_error: None,
+			transformers: Vec::new(),
+			outcome: Outcome::Deployed,
+		}
+	}
+
+	#[test]
+	fn explain_outcomes() {
+		crate::tests::setup_test_env();
+
+		let mut candidates = vec![
+			candidate("high", Some(5), None),
+			candidate("low", Some(1), None),
+			candidate("keep", None, Some(MergeMode::Keep)),
+			candidate("overwrite", None, None),
+		];
+
+		evaluate_outcomes(&mut candidates, false);
+
+		assert_eq!(candidates[0].outcome, Outcome::Overwritten { by: 3 });
+		assert!(matches!(candidates[1].outcome, Outcome::Lost { .. }));
+		assert!(matches!(candidates[2].outcome, Outcome::Lost { .. }));
+		assert_eq!(candidates[3].outcome, Outcome::Deployed);
+	}
+}
@@ -2,21 +2,33 @@
 //! over every item to be deployed in a given profile.
 //! The visitor accepts items on different functions depending on status and type.
 
+pub mod conflicts;
 pub mod deploy;
 pub mod diff;
+pub mod doctor;
+pub mod explain;
 
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::fmt;
 use std::io;
 use std::ops::Deref;
-use std::path::{Path, PathBuf};
-
-use crate::profile::link;
-use crate::profile::LayeredProfile;
-use crate::profile::{dotfile::Dotfile, source::PunktfSource};
-
-use color_eyre::eyre::Context;
-
+use std::path::{Component, Path, PathBuf};
+
+use crate::profile::archive::{self, ArchiveFormat};
+use crate::profile::encryption;
+use crate::profile::link::{self, LinkKind};
+use crate::profile::select::Host;
+use crate::profile::{
+	dotfile::{Dotfile, Enabled},
+	source::PunktfSource,
+};
+use crate::profile::{LayeredProfile, MergeMode, Priority};
+
+use crate::profile::variables::{VariableCache, Variables};
+use crate::temp::TempPath;
+use crate::template::cache::TemplateCache;
+use crate::template::engine::{EngineKind, RenderContext};
 use crate::template::source::Source;
 use crate::template::Template;
 
@@ -63,6 +75,10 @@ struct Paths {
 	/// If the dotfile is a directory, this contains the relevant path
 	/// to the item which is included by the root dotfile.
 	child: Option<PathLink>,
+
+	/// Source path of the archive the root source path was extracted from
+	/// (related: [`Dotfile::extract`](`crate::profile::dotfile::Dotfile::extract`)).
+	archive: Option<PathBuf>,
 }
 
 impl Paths {
@@ -71,12 +87,24 @@ impl Paths {
 		Self {
 			root: PathLink::new(root_source, root_target),
 			child: None,
+			archive: None,
 		}
 	}
 
+	/// Marks the root source path as the directory into which the archive at
+	/// `archive` was extracted.
+	fn with_archive(mut self, archive: PathBuf) -> Self {
+		self.archive = Some(archive);
+		self
+	}
+
 	/// Appends a relative child path to instance.
 	fn with_child(self, rel_path: impl Into<PathBuf>) -> Self {
-		let Paths { root, child } = self;
+		let Paths {
+			root,
+			child,
+			archive,
+		} = self;
 		let rel_path = rel_path.into();
 
 		let child = if let Some(child) = child {
@@ -88,6 +116,7 @@ impl Paths {
 		Self {
 			root,
 			child: Some(child),
+			archive,
 		}
 	}
 
@@ -200,16 +229,31 @@ impl<'a> Item<'a> {
 	fn new(source: &PunktfSource, paths: Paths, dotfile: &'a Dotfile) -> Self {
 		let source_path = paths.child_source_path().into_owned();
 		let target_path = paths.child_target_path().into_owned();
-		let relative_source_path = source_path
-			.strip_prefix(&source.dotfiles)
-			.expect("Dotfile is not in the dotfile root")
-			.to_path_buf();
+		// Extracted items are named after the archive they stem from.
+		let display_path = match (&paths.archive, &paths.child) {
+			(Some(archive), Some(child)) => archive.join(&child.source),
+			(Some(archive), None) => archive.clone(),
+			(None, _) => source_path.clone(),
+		};
+		// Extra paths are prefixed with `assets` (e.g. `assets/wallpaper.png`).
+		// External dotfiles (related: [`LayeredProfile::allow_external`]) keep
+		// their absolute path.
+		let relative_source_path = if let Ok(path) = display_path.strip_prefix(&source.dotfiles) {
+			path.to_path_buf()
+		} else if let Some(path) = source
+			.assets()
+			.and_then(|assets| display_path.strip_prefix(assets).ok())
+		{
+			Path::new("assets").join(path)
+		} else {
+			display_path
+		};
 		let kind = Kind::from_paths(paths, dotfile);
 
 		Self {
 			relative_source_path,
-			source_path,
-			target_path,
+			source_path: extended_length_path(source_path),
+			target_path: extended_length_path(target_path),
 			kind,
 		}
 	}
@@ -226,6 +270,32 @@ impl Item<'_> {
 #[derive(Debug)]
 pub struct File<'a>(Item<'a>);
 
+impl File<'_> {
+	/// Reads the contents of the source file.
+	///
+	/// Encrypted dotfiles are decrypted (related:
+	/// [`Dotfile::is_encrypted`](`crate::profile::dotfile::Dotfile::is_encrypted`)).
+	pub fn read_source(&self) -> io::Result<Vec<u8>> {
+		if self.dotfile().is_encrypted() {
+			encryption::decrypt(&self.source_path)
+		} else {
+			std::fs::read(&self.source_path)
+		}
+	}
+
+	/// Reads the contents of the source file as a string (related:
+	/// [`File::read_source`]).
+	///
+	/// # Errors
+	///
+	/// An error of kind [`io::ErrorKind::InvalidData`] is returned if the
+	/// contents are not valid UTF-8.
+	pub fn read_source_to_string(&self) -> io::Result<String> {
+		String::from_utf8(self.read_source()?)
+			.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+	}
+}
+
 impl<'a> Deref for File<'a> {
 	type Target = Item<'a>;
 
@@ -258,6 +328,15 @@ pub struct Symlink {
 	/// Indicates if any existing symlink at the [`Symlink::target_path`] should
 	/// be replaced by this item.
 	pub replace: bool,
+
+	/// The kind of link to create.
+	pub kind: LinkKind,
+
+	/// Priority of the link.
+	pub priority: Option<Priority>,
+
+	/// Merge operation if the target already exists and can not be replaced.
+	pub merge: Option<MergeMode>,
 }
 
 /// Holds information about a rejected item.
@@ -373,6 +452,34 @@ pub trait Visitor {
 	) -> Result;
 }
 
+/// Selects the dotfiles which are visited by a [`Walker`] by their
+/// [tags](`crate::profile::dotfile::Dotfile::tags`).
+///
+/// Links are not affected by the filter.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TagFilter {
+	/// If not empty, only dotfiles with at least one of these tags are
+	/// accepted.
+	pub only: Vec<String>,
+
+	/// Dotfiles with any of these tags are not accepted.
+	pub skip: Vec<String>,
+}
+
+impl TagFilter {
+	/// Checks if the filter accepts all dotfiles.
+	pub const fn is_empty(&self) -> bool {
+		self.only.is_empty() && self.skip.is_empty()
+	}
+
+	/// Checks if `dotfile` is accepted by the filter.
+	pub fn accepts(&self, dotfile: &Dotfile) -> bool {
+		let has_any = |tags: &[String]| dotfile.tags.iter().any(|tag| tags.contains(tag));
+
+		(self.only.is_empty() || has_any(&self.only)) && !has_any(&self.skip)
+	}
+}
+
 /// Walks over each item of a [`LayeredProfile`](`crate::profile::LayeredProfile`)
 /// and calls the appropriate functions of the given visitor.
 #[derive(Debug)]
@@ -381,6 +488,22 @@ pub struct Walker<'a> {
 	// Sort by priority and eliminate duplicate lower ones
 	/// The profile to walk.
 	profile: &'a LayeredProfile,
+
+	/// Directory which prefixes all resolved target paths (related:
+	/// [`Walker::with_destdir`]).
+	destdir: Option<PathBuf>,
+
+	/// Filter which selects the dotfiles to visit.
+	filter: TagFilter,
+
+	/// If the extra paths of the profile are visited (related:
+	/// [`Walker::with_extra_paths`]).
+	extra_paths: bool,
+
+	/// Real paths of the directories which are currently walked while
+	/// following symlinks of the source directory. This is used to detect
+	/// symlink cycles.
+	followed: RefCell<Vec<PathBuf>>,
 }
 
 impl<'a> Walker<'a> {
@@ -389,20 +512,77 @@ impl<'a> Walker<'a> {
 	/// The [`LayeredProfile::dotfiles`](`crate::profile::LayeredProfile::dotfiles`)
 	/// will be sorted by [`Dotfile::priority`](`crate::profile::dotfile::Dotfile::priority`)
 	/// to avoid unnecessary read/write operations during a deployment.
+	/// Dotfiles with the same priority are sorted by their path, so the items
+	/// are always visited in the same order.
 	pub fn new(profile: &'a mut LayeredProfile) -> Self {
 		{
 			let dotfiles = &mut profile.dotfiles;
 			// Sorty highest to lowest by priority
-			dotfiles.sort_by_key(|(_, d)| -(d.priority.map(|p| p.0).unwrap_or(0) as i64));
+			dotfiles.sort_by(|(_, a), (_, b)| {
+				b.priority
+					.unwrap_or_default()
+					.cmp(&a.priority.unwrap_or_default())
+					.then_with(|| a.path.cmp(&b.path))
+			});
 		};
 
-		Self { profile }
+		Self {
+			profile,
+			destdir: None,
+			filter: TagFilter::default(),
+			extra_paths: false,
+			followed: RefCell::new(Vec::new()),
+		}
+	}
+
+	/// Only visits the dotfiles which are accepted by `filter`.
+	pub fn with_filter(mut self, filter: TagFilter) -> Self {
+		self.filter = filter;
+		self
+	}
+
+	/// Also visits the
+	/// [`LayeredProfile::extra_paths`](`crate::profile::LayeredProfile::extra_paths`).
+	///
+	/// They are visited like dotfiles, but their source paths are relative
+	/// to the `assets` directory. By default they are not visited, as only
+	/// deployments need them.
+	pub const fn with_extra_paths(mut self, extra_paths: bool) -> Self {
+		self.extra_paths = extra_paths;
+		self
+	}
+
+	/// Prefixes all resolved target paths with `destdir` (similar to
+	/// `DESTDIR` of `make`).
+	///
+	/// The paths of the profile keep their absolute semantic, e.g. the target
+	/// path `/home/demo/.bashrc` is resolved to `<destdir>/home/demo/.bashrc`.
+	/// This allows to stage a whole deployment into a scratch directory.
+	pub fn with_destdir(mut self, destdir: Option<PathBuf>) -> Self {
+		self.destdir = destdir;
+		self
 	}
 
 	/// Walks the profile and calls the appropriate functions on the given [`Visitor`].
 	pub fn walk(&self, source: &PunktfSource, visitor: &mut impl Visitor) -> Result {
 		for dotfile in self.profile.dotfiles() {
-			self.walk_dotfile(source, visitor, dotfile)?;
+			if !self.filter.accepts(dotfile) {
+				log::debug!("[{}] Excluded by tag filter", dotfile.path.display());
+				continue;
+			}
+
+			self.walk_dotfile(source, &source.dotfiles, visitor, dotfile)?;
+		}
+
+		if self.extra_paths {
+			for extra_path in self.profile.extra_paths() {
+				if !self.filter.accepts(extra_path) {
+					log::debug!("[{}] Excluded by tag filter", extra_path.path.display());
+					continue;
+				}
+
+				self.walk_extra_path(source, visitor, extra_path)?;
+			}
 		}
 
 		for link in self.profile.symlinks() {
@@ -412,14 +592,40 @@ impl<'a> Walker<'a> {
 		Ok(())
 	}
 
-	/// Walks each item of a [`Dotfile`](`crate::profile::dotfile::Dotfile`).
+	/// Walks each item of an extra path, which is located in the `assets`
+	/// directory.
+	fn walk_extra_path(
+		&self,
+		source: &PunktfSource,
+		visitor: &mut impl Visitor,
+		extra_path: &Dotfile,
+	) -> Result {
+		let Some(assets) = source.assets() else {
+			let paths = Paths::new(extra_path.path.clone(), extra_path.path.clone());
+
+			return self.walk_errored(
+				source,
+				visitor,
+				paths,
+				extra_path,
+				None::<io::Error>,
+				Some("Source directory has no `assets` directory"),
+			);
+		};
+
+		self.walk_dotfile(source, assets, visitor, extra_path)
+	}
+
+	/// Walks each item of a [`Dotfile`](`crate::profile::dotfile::Dotfile`)
+	/// located in the directory `base`.
 	fn walk_dotfile(
 		&self,
 		source: &PunktfSource,
+		base: &Path,
 		visitor: &mut impl Visitor,
 		dotfile: &Dotfile,
 	) -> Result {
-		let source_path = match self.resolve_source_path(source, dotfile) {
+		let source_path = match self.resolve_source_path(base, dotfile) {
 			Ok(p) => p,
 			Err(err) => {
 				let paths = Paths::new(dotfile.path.clone(), dotfile.path.clone());
@@ -453,6 +659,25 @@ impl<'a> Walker<'a> {
 
 		let paths = Paths::new(source_path, target_path);
 
+		match self.is_enabled(dotfile) {
+			Ok(true) => {}
+			Ok(false) => {
+				log::info!("[{}] Dotfile is disabled", dotfile.path.display());
+
+				return self.walk_rejected(source, visitor, paths, dotfile, "disabled");
+			}
+			Err(err) => {
+				return self.walk_errored(
+					source,
+					visitor,
+					paths,
+					dotfile,
+					Some(err),
+					Some("Failed to evaluate `enabled` of dotfile"),
+				);
+			}
+		}
+
 		if !paths.child_source_path().exists() {
 			let context = format!(
 				"Dotfile at {} does not exist",
@@ -469,9 +694,94 @@ impl<'a> Walker<'a> {
 			);
 		};
 
+		if dotfile.is_extracted() {
+			return self.walk_archive(source, visitor, paths, dotfile);
+		}
+
 		self.walk_path(source, visitor, paths, dotfile)
 	}
 
+	/// Extracts the archive of an extracted
+	/// [`Dotfile`](`crate::profile::dotfile::Dotfile`) into a temporary
+	/// directory and walks its contents like the ones of a directory dotfile
+	/// (related: [`Dotfile::extract`](`crate::profile::dotfile::Dotfile::extract`)).
+	fn walk_archive(
+		&self,
+		source: &PunktfSource,
+		visitor: &mut impl Visitor,
+		paths: Paths,
+		dotfile: &Dotfile,
+	) -> Result {
+		let archive_path = paths.root_source_path().to_path_buf();
+
+		// The contents are placed next to where the archive itself would be
+		// deployed, unless an explicit name is given.
+		let target_path = if self.is_renamed(dotfile) {
+			paths.root_target_path().to_path_buf()
+		} else {
+			paths
+				.root_target_path()
+				.parent()
+				.map_or_else(|| paths.root_target_path().to_path_buf(), Path::to_path_buf)
+		};
+
+		let format_path = if dotfile.is_encrypted() {
+			encryption::strip_extension(&archive_path).unwrap_or_else(|| archive_path.clone())
+		} else {
+			archive_path.clone()
+		};
+
+		let Some(format) = ArchiveFormat::from_path(&format_path) else {
+			let err = io::Error::new(
+				io::ErrorKind::Unsupported,
+				"Unknown archive format (expected one of the extensions `.tar`, `.tar.gz`, `.tgz` or `.zip`)",
+			);
+
+			return self.walk_errored(source, visitor, paths, dotfile, Some(err), None::<&str>);
+		};
+
+		let data = if dotfile.is_encrypted() {
+			encryption::decrypt(&archive_path)
+		} else {
+			std::fs::read(&archive_path)
+		};
+
+		let extracted = data.and_then(|data| {
+			let dir = TempPath::create_dir(
+				std::env::temp_dir().join(format!("punktf-archive-{}", uuid::Uuid::new_v4())),
+			)?;
+			archive::extract(format, &data, dir.path())?;
+
+			Ok(dir)
+		});
+
+		let dir = match extracted {
+			Ok(dir) => dir,
+			Err(err) => {
+				return self.walk_errored(
+					source,
+					visitor,
+					paths,
+					dotfile,
+					Some(err),
+					Some("Failed to extract archive"),
+				);
+			}
+		};
+
+		// The extracted files are plain files which are no templates by
+		// default.
+		let extracted_dotfile = Dotfile {
+			template: dotfile.template.or(Some(false)),
+			encrypted: None,
+			..dotfile.clone()
+		};
+
+		let paths = Paths::new(dir.path().to_path_buf(), target_path).with_archive(archive_path);
+
+		self.walk_directory(source, visitor, paths, &extracted_dotfile)
+	}
+
 	/// Walks a specific path of a [`Dotfile`](`crate::profile::dotfile::Dotfile`).
 	///
 	/// This either calls [`Walker::walk_file`] or [`Walker::walk_directory`].
@@ -485,12 +795,19 @@ impl<'a> Walker<'a> {
 		let source_path = paths.child_source_path();
 
 		if !self.accept(&source_path) {
-			return self.walk_rejected(source, visitor, paths, dotfile);
+			return self.walk_rejected(source, visitor, paths, dotfile, "Rejected by filter");
 		}
 
-		// For now dont follow symlinks (`metadata()` would get the metadata of the target of a
-		// link).
+		// Symlinks are only followed if enabled (`metadata()` would get the
+		// metadata of the target of a link).
 		let metadata = match source_path.symlink_metadata() {
+			Ok(metadata) if metadata.is_symlink() && self.follows_source_symlinks(dotfile) => {
+				source_path.metadata()
+			}
+			res => res,
+		};
+
+		let metadata = match metadata {
 			Ok(metadata) => metadata,
 			Err(err) => {
 				return self.walk_errored(
@@ -515,6 +832,15 @@ impl<'a> Walker<'a> {
 		}
 	}
 
+	/// Checks if symlinks in the source directory are followed for `dotfile`
+	/// (related:
+	/// [`Dotfile::follow_source_symlinks`](`crate::profile::dotfile::Dotfile::follow_source_symlinks`)).
+	fn follows_source_symlinks(&self, dotfile: &Dotfile) -> bool {
+		dotfile
+			.follow_source_symlinks
+			.unwrap_or_else(|| self.profile.follow_source_symlinks())
+	}
+
 	/// Calls [`Visitor::accept_file`].
 	fn walk_file(
 		&self,
@@ -523,7 +849,16 @@ impl<'a> Walker<'a> {
 		paths: Paths,
 		dotfile: &Dotfile,
 	) -> Result {
-		let file = File(Item::new(source, paths, dotfile));
+		let is_renamed = paths.is_root() && self.is_renamed(dotfile);
+		let mut item = Item::new(source, paths, dotfile);
+
+		if dotfile.is_encrypted() && !is_renamed {
+			if let Some(target_path) = encryption::strip_extension(&item.target_path) {
+				item.target_path = target_path;
+			}
+		}
+
+		let file = File(item);
 
 		visitor.accept_file(source, self.profile, &file)
 	}
@@ -540,12 +875,67 @@ impl<'a> Walker<'a> {
 	) -> Result {
 		let source_path = paths.child_source_path();
 
-		let directory = Directory(Item::new(source, paths.clone(), dotfile));
+		if !self.follows_source_symlinks(dotfile) {
+			let directory = Directory(Item::new(source, paths.clone(), dotfile));
+			visitor.accept_directory(source, self.profile, &directory)?;
 
+			return self.walk_children(source, visitor, paths, dotfile);
+		}
+
+		let real_path = match source_path.canonicalize() {
+			Ok(real_path) => real_path,
+			Err(err) => {
+				return self.walk_errored(
+					source,
+					visitor,
+					paths,
+					dotfile,
+					Some(err),
+					Some("Failed to resolve directory"),
+				);
+			}
+		};
+
+		if self.followed.borrow().contains(&real_path) {
+			let err = io::Error::new(
+				io::ErrorKind::InvalidInput,
+				format!("Symlink cycle detected (`{}`)", real_path.display()),
+			);
+
+			return self.walk_errored(source, visitor, paths, dotfile, Some(err), None::<&str>);
+		}
+
+		let directory = Directory(Item::new(source, paths.clone(), dotfile));
 		visitor.accept_directory(source, self.profile, &directory)?;
 
-		let read_dir = match std::fs::read_dir(source_path) {
-			Ok(path) => path,
+		self.followed.borrow_mut().push(real_path);
+		let res = self.walk_children(source, visitor, paths, dotfile);
+		self.followed.borrow_mut().pop();
+
+		res
+	}
+
+	/// Walks all child items of the directory item given by `paths`.
+	fn walk_children(
+		&self,
+		source: &PunktfSource,
+		visitor: &mut impl Visitor,
+		paths: Paths,
+		dotfile: &Dotfile,
+	) -> Result {
+		let source_path = paths.child_source_path();
+
+		// The entries are sorted, as the order of `read_dir` depends on the
+		// platform and filesystem.
+		let names = match std::fs::read_dir(source_path).and_then(|read_dir| {
+			read_dir
+				.map(|dent| dent.map(|dent| dent.file_name()))
+				.collect::<io::Result<Vec<_>>>()
+		}) {
+			Ok(mut names) => {
+				names.sort();
+				names
+			}
 			Err(err) => {
 				return self.walk_errored(
 					source,
@@ -558,27 +948,21 @@ impl<'a> Walker<'a> {
 			}
 		};
 
-		for dent in read_dir {
-			let dent = match dent {
-				Ok(dent) => dent,
-				Err(err) => {
-					return self.walk_errored(
-						source,
-						visitor,
-						paths,
-						dotfile,
-						Some(err),
-						Some("Failed to read directory"),
+		for name in names {
+			let child_paths = paths.clone().with_child(name);
+
+			if let Some(child) = &child_paths.child {
+				if source.config.is_ignored(&child.source) {
+					log::debug!(
+						"[{}] Ignored by source config",
+						child_paths.child_source_path().display()
 					);
+
+					continue;
 				}
-			};
+			}
 
-			self.walk_path(
-				source,
-				visitor,
-				paths.clone().with_child(dent.file_name()),
-				dotfile,
-			)?;
+			self.walk_path(source, visitor, child_paths, dotfile)?;
 		}
 
 		Ok(())
@@ -591,12 +975,25 @@ impl<'a> Walker<'a> {
 		visitor: &mut impl Visitor,
 		link: &link::Symlink,
 	) -> Result {
+		if !link.is_enabled_for(&Host::current()) {
+			log::info!(
+				"[{}] Skipping link - Condition does not match this machine",
+				link.source_path.display()
+			);
+
+			return Ok(());
+		}
+
 		// DO NOT CANONICOLIZE THE PATHS AS THIS WOULD FOLLOW LINKS
-		// TODO: Better error handling
 		let link = Symlink {
 			source_path: self.resolve_path(&link.source_path)?,
-			target_path: self.resolve_path(&link.target_path)?,
+			target_path: extended_length_path(
+				self.prefix_destdir(self.resolve_path(&link.target_path)?),
+			),
 			replace: link.replace,
+			kind: link.kind,
+			priority: link.priority,
+			merge: link.merge,
 		};
 
 		visitor.accept_link(source, self.profile, &link)
@@ -609,10 +1006,11 @@ impl<'a> Walker<'a> {
 		visitor: &mut impl Visitor,
 		paths: Paths,
 		dotfile: &Dotfile,
+		reason: impl Into<Cow<'static, str>>,
 	) -> Result {
 		let rejected = Rejected {
 			item: Item::new(source, paths, dotfile),
-			reason: Cow::Borrowed("Rejected by filter"),
+			reason: reason.into(),
 		};
 
 		visitor.accept_rejected(source, self.profile, &rejected)
@@ -639,6 +1037,10 @@ impl<'a> Walker<'a> {
 
 	/// Applies final transformations for paths from [`Walker::resolve_source_path`]
 	/// and [`Walker::resolve_target_path`].
+	///
+	/// A leading `~` is expanded to the home directory and environment
+	/// variables (`$VAR` and `${VAR}`, on windows also `%VAR%`) to their
+	/// values.
 	fn resolve_path(&self, path: &Path) -> io::Result<PathBuf> {
 		let Some(path_str) = path.to_str() else {
 			return Err(io::Error::new(
@@ -647,34 +1049,258 @@ impl<'a> Walker<'a> {
 			));
 		};
 
-		shellexpand::full(path_str)
-			.map(|resolved| PathBuf::from(resolved.as_ref()))
-			.map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+		let expand_error = |err: &dyn fmt::Display| {
+			io::Error::other(format!("Failed to expand `{path_str}`: {err}"))
+		};
+
+		// Variables of the profile environment (e.g. `PUNKTF_CURRENT_TARGET`)
+		// take precedence over the environment of the process.
+		let env = self.profile.env();
+
+		let path_str = if cfg!(windows) {
+			expand_percent_vars(path_str, |name| env.var(name)).map_err(|err| expand_error(&err))?
+		} else {
+			Cow::Borrowed(path_str)
+		};
+
+		let home_dir = || {
+			let home = shellexpand::tilde("~");
+			(home != "~").then_some(home)
+		};
+
+		shellexpand::full_with_context(path_str.as_ref(), home_dir, |name| {
+			env.var(name)
+				.map(Some)
+				.ok_or(std::env::VarError::NotPresent)
+		})
+		.map(|resolved| PathBuf::from(resolved.as_ref()))
+		.map_err(|err| expand_error(&err))
 	}
 
-	/// Resolves the dotfile to a absolute source path.
-	fn resolve_source_path(&self, source: &PunktfSource, dotfile: &Dotfile) -> io::Result<PathBuf> {
-		self.resolve_path(&source.dotfiles.join(&dotfile.path))
+	/// Resolves the dotfile to a absolute source path inside of `base` (the
+	/// `dotfiles` or `assets` directory).
+	///
+	/// An error of kind [`io::ErrorKind::PermissionDenied`] is returned if the
+	/// path lies outside of `base` (e.g. an absolute path or one containing
+	/// `..`) and
+	/// [`LayeredProfile::allow_external`](`crate::profile::LayeredProfile::allow_external`)
+	/// is not set.
+	fn resolve_source_path(&self, base: &Path, dotfile: &Dotfile) -> io::Result<PathBuf> {
+		let path = normalize_lexically(&self.resolve_path(&base.join(&dotfile.path))?);
+
+		if !path.starts_with(base) && !self.profile.allow_external() {
+			return Err(io::Error::new(
+				io::ErrorKind::PermissionDenied,
+				format!(
+					"Dotfile `{}` lies outside of the {} directory; Set `allow_external` in the \
+					 profile to include it",
+					path.display(),
+					base.file_name().unwrap_or_default().to_string_lossy()
+				),
+			));
+		}
+
+		Ok(path)
+	}
+
+	/// Resolves the target root path of the profile
+	/// ([`LayeredProfile::target_path`](`crate::profile::LayeredProfile::target_path`)).
+	///
+	/// Template blocks are resolved only with the variables of the profile.
+	///
+	/// Returns `None` if the profile has no target path.
+	pub fn resolve_target_root(&self) -> io::Result<Option<PathBuf>> {
+		let Some(target) = self.profile.target_path() else {
+			return Ok(None);
+		};
+
+		let target = self.resolve_path_template(target, None)?;
+		self.resolve_path(&target)
+			.map(|target| Some(self.prefix_destdir(target)))
+	}
+
+	/// Prefixes the resolved target path `path` with
+	/// [`Walker::destdir`](`Walker::with_destdir`), if set.
+	fn prefix_destdir(&self, path: PathBuf) -> PathBuf {
+		let Some(destdir) = &self.destdir else {
+			return path;
+		};
+
+		// Drop the root and prefix (e.g. `C:`) components, as joining an
+		// absolute path would replace the destdir.
+		let relative: PathBuf = path
+			.components()
+			.filter(|component| !matches!(component, Component::Prefix(_) | Component::RootDir))
+			.collect();
+
+		destdir.join(relative)
+	}
+
+	/// Resolves all template blocks contained in `path`.
+	///
+	/// The blocks are resolved with the same variables which would be used for
+	/// the contents of a dotfile with the variables `dotfile_vars`.
+	fn resolve_path_template(
+		&self,
+		path: &Path,
+		dotfile_vars: Option<&Variables>,
+	) -> io::Result<PathBuf> {
+		let Some(path_str) = path.to_str() else {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"File path includes non UTF-8 characters",
+			));
+		};
+
+		// Fast path; No need to invoke the template machinery.
+		if !path_str.contains("{{") {
+			return Ok(path.to_path_buf());
+		}
+
+		let source = Source::anonymous(path_str);
+
+		Template::parse(source)
+			.and_then(|template| {
+				template.resolve_with_order(
+					Some(self.profile.variables()),
+					dotfile_vars,
+					self.profile.variable_resolution_order(),
+					self.profile.env(),
+				)
+			})
+			.map(PathBuf::from)
+			.map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, format!("{err:#}")))
+	}
+
+	/// Checks if `dotfile` is enabled (related:
+	/// [`Dotfile::enabled`](`crate::profile::dotfile::Dotfile::enabled`)).
+	///
+	/// An expression is evaluated with the same variables which would be used
+	/// for the contents of the dotfile.
+	fn is_enabled(&self, dotfile: &Dotfile) -> io::Result<bool> {
+		match &dotfile.enabled {
+			None => Ok(true),
+			Some(Enabled::Bool(enabled)) => Ok(*enabled),
+			Some(Enabled::Expr(expr)) => self.eval_condition(expr, dotfile),
+		}
+	}
+
+	/// Returns the name/path under which `dotfile` is deployed instead of its
+	/// path (related:
+	/// [`Dotfile::rename_map`](`crate::profile::dotfile::Dotfile::rename_map`)
+	/// and [`Dotfile::rename`](`crate::profile::dotfile::Dotfile::rename`)).
+	fn rename<'d>(&self, dotfile: &'d Dotfile) -> io::Result<Option<&'d Path>> {
+		if dotfile.rename_map.is_empty() {
+			return Ok(dotfile.rename.as_deref());
+		}
+
+		let host = Host::current();
+		let find = |name: &str| {
+			dotfile
+				.rename_map
+				.iter()
+				.find(|(key, _)| key.eq_ignore_ascii_case(name))
+		};
+
+		if let Some((_, rename)) = find(&host.os).or_else(|| find(std::env::consts::FAMILY)) {
+			return Ok(Some(rename));
+		}
+
+		for (condition, rename) in &dotfile.rename_map {
+			if condition.contains("{{") && self.eval_condition(condition, dotfile)? {
+				return Ok(Some(rename));
+			}
+		}
+
+		Ok(dotfile.rename.as_deref())
+	}
+
+	/// Checks if `dotfile` is deployed under another name than its path
+	/// (related: [`Walker::rename`]).
+	///
+	/// Errors of conditions are ignored, as they are already reported when the
+	/// target path is resolved.
+	fn is_renamed(&self, dotfile: &Dotfile) -> bool {
+		self.rename(dotfile).is_ok_and(|rename| rename.is_some())
+	}
+
+	/// Evaluates `expr` like the condition of an `if` block of a template
+	/// with the same variables which would be used for the contents of
+	/// `dotfile`.
+	fn eval_condition(&self, expr: &str, dotfile: &Dotfile) -> io::Result<bool> {
+		let content = format!("{{{{@if {expr}}}}}1{{{{@fi}}}}");
+		let source = Source::anonymous(&content);
+
+		Template::parse(source)
+			.and_then(|template| {
+				template.resolve_with_order(
+					Some(self.profile.variables()),
+					dotfile.variables.as_ref(),
+					self.profile.variable_resolution_order(),
+					self.profile.env(),
+				)
+			})
+			.map(|output| output == "1")
+			.map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, format!("{err:#}")))
 	}
 
 	/// Resolves the dotfile to a absolute target path.
 	///
 	/// Some special logic is applied for directories.
+	///
+	/// Template blocks in the target paths and in
+	/// [`Dotfile::rename`](`crate::profile::dotfile::Dotfile::rename`) are
+	/// resolved (related: [`Walker::resolve_path_template`]).
+	///
+	/// An error of kind [`io::ErrorKind::NotFound`] which wraps
+	/// [`DeployError::MissingTarget`](`crate::visit::deploy::DeployError::MissingTarget`)
+	/// is returned if neither the dotfile nor the profile have a target path.
+	/// If the dotfile references a named target which is not defined, the
+	/// error wraps
+	/// [`DeployError::UnknownTarget`](`crate::visit::deploy::DeployError::UnknownTarget`).
 	fn resolve_target_path(&self, dotfile: &Dotfile, is_dir: bool) -> io::Result<PathBuf> {
-		let path = if is_dir && dotfile.rename.is_none() && dotfile.overwrite_target.is_none() {
-			self.profile
-				.target_path()
-				.expect("No target path set")
-				.to_path_buf()
+		let target = if let Some(target) = &dotfile.overwrite_target {
+			target.as_path()
+		} else if let Some(name) = &dotfile.target_name {
+			self.profile.named_target(name).ok_or_else(|| {
+				io::Error::new(
+					io::ErrorKind::NotFound,
+					deploy::DeployError::UnknownTarget(name.clone()),
+				)
+			})?
 		} else {
-			dotfile
-				.overwrite_target
-				.as_deref()
-				.unwrap_or_else(|| self.profile.target_path().expect("No target path set"))
-				.join(dotfile.rename.as_ref().unwrap_or(&dotfile.path))
+			self.profile.target_path().ok_or_else(|| {
+				io::Error::new(io::ErrorKind::NotFound, deploy::DeployError::MissingTarget)
+			})?
+		};
+
+		let rename = self.rename(dotfile)?;
+
+		let path = if is_dir && rename.is_none() && dotfile.overwrite_target.is_none() {
+			self.resolve_path_template(target, dotfile.variables.as_ref())?
+		} else {
+			let name = if let Some(rename) = rename {
+				self.resolve_path_template(rename, dotfile.variables.as_ref())?
+			} else if dotfile.path.is_absolute()
+				|| dotfile.path.components().any(|c| c == Component::ParentDir)
+			{
+				// External dotfiles are deployed under their name, as their
+				// path would point outside of the target.
+				dotfile
+					.path
+					.file_name()
+					.map(PathBuf::from)
+					.unwrap_or_default()
+			} else {
+				dotfile.path.clone()
+			};
+
+			self.resolve_path_template(target, dotfile.variables.as_ref())?
+				.join(name)
 		};
 
 		self.resolve_path(&path)
+			.map(|path| self.prefix_destdir(path))
 	}
 
 	/// TODO
@@ -684,6 +1310,134 @@ impl<'a> Walker<'a> {
 	}
 }
 
+/// Resolves `path` to be comparable with other paths (e.g. for deployment
+/// reports).
+///
+/// Only the parent directory is canonicalized, to keep the path of the item
+/// itself even if it is a symlink (e.g. created by
+/// [`DeployStrategy::CacheLink`](`crate::profile::DeployStrategy::CacheLink`)).
+pub(crate) fn canonicalize_parent(path: &Path) -> PathBuf {
+	match (path.parent(), path.file_name()) {
+		(Some(parent), Some(name)) => parent
+			.canonicalize()
+			.map(|parent| parent.join(name))
+			.unwrap_or_else(|_| path.to_path_buf()),
+		_ => path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+	}
+}
+
+/// Removes all `.` and `..` components from `path` without accessing the
+/// filesystem.
+pub(crate) fn normalize_lexically(path: &Path) -> PathBuf {
+	let mut normalized = PathBuf::new();
+
+	for component in path.components() {
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir => {
+				normalized.pop();
+			}
+			component => normalized.push(component),
+		}
+	}
+
+	normalized
+}
+
+/// Length from which paths need the extended-length prefix on windows.
+///
+/// This is the limit for directories (`MAX_PATH` minus space for a 8.3 file
+/// name), which is a bit lower than the one for files.
+const MAX_DIR_PATH: usize = 248;
+
+/// Expands windows environment variables (`%VAR%`) in `input` with the
+/// values returned by `lookup`.
+///
+/// `%%` is replaced with a single `%`. A `%` without a closing `%` in the same
+/// path component is kept as is.
+fn expand_percent_vars(
+	input: &str,
+	lookup: impl Fn(&str) -> Option<String>,
+) -> std::result::Result<Cow<'_, str>, String> {
+	if !input.contains('%') {
+		return Ok(Cow::Borrowed(input));
+	}
+
+	let mut expanded = String::with_capacity(input.len());
+	let mut rest = input;
+
+	while let Some(start) = rest.find('%') {
+		expanded.push_str(&rest[..start]);
+		let after = &rest[start + 1..];
+
+		let name = after
+			.find('%')
+			.map(|end| &after[..end])
+			.filter(|name| !name.contains(['/', '\\']));
+
+		match name {
+			Some("") => expanded.push('%'),
+			Some(name) => match lookup(name) {
+				Some(value) => expanded.push_str(&value),
+				None => return Err(format!("environment variable `{name}` is not set")),
+			},
+			None => {
+				expanded.push('%');
+				rest = after;
+				continue;
+			}
+		}
+
+		rest = &after[name.map_or(0, str::len) + 1..];
+	}
+
+	expanded.push_str(rest);
+
+	Ok(Cow::Owned(expanded))
+}
+
+/// Converts an absolute windows `path` which is too long for the regular
+/// windows apis to an extended-length path (e.g. `C:\foo` to `\\?\C:\foo`
+/// and `\\server\share` to `\\?\UNC\server\share`).
+///
+/// Returns `None` if `path` is short enough, already an extended-length or
+/// device path or relative. The path must not contain `.` or `..`
+/// components, as they are not resolved for extended-length paths.
+fn to_extended_length(path: &str) -> Option<String> {
+	if path.len() < MAX_DIR_PATH || path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+		return None;
+	}
+
+	let path = path.replace('/', "\\");
+
+	if let Some(unc) = path.strip_prefix(r"\\") {
+		Some(format!(r"\\?\UNC\{unc}"))
+	} else if path.get(1..3) == Some(":\\") {
+		Some(format!(r"\\?\{path}"))
+	} else {
+		None
+	}
+}
+
+/// Converts `path` to an extended-length path on windows if it is too long
+/// for the regular windows apis (which are limited to 260 characters), e.g.
+/// for deep trees of plugins. On all other systems `path` is returned as is.
+///
+/// `.` and `..` components are removed from converted paths.
+pub(crate) fn extended_length_path(path: PathBuf) -> PathBuf {
+	if !cfg!(windows) {
+		return path;
+	}
+
+	match normalize_lexically(&path)
+		.to_str()
+		.and_then(to_extended_length)
+	{
+		Some(extended) => PathBuf::from(extended),
+		None => path,
+	}
+}
+
 /// An extension trait to [`Visitor`] which adds a new function to accept
 /// template items.
 pub trait TemplateVisitor: Visitor {
@@ -698,7 +1452,7 @@ pub trait TemplateVisitor: Visitor {
 		file: &File<'a>,
 		// Returns a function to resolve the content to make the resolving lazy
 		// for upstream visitors.
-		resolve_content: impl FnOnce(&str) -> color_eyre::Result<String>,
+		resolve_content: impl FnOnce(&str) -> crate::error::Result<String>,
 	) -> Result;
 }
 
@@ -709,16 +1463,56 @@ pub trait TemplateVisitor: Visitor {
 /// out with [`Visitor::accept_file`] if they are a normal file or with
 /// [`TemplateVisitor::accept_template`] if it is a template.
 #[derive(Debug)]
-pub struct ResolvingVisitor<V>(V);
+pub struct ResolvingVisitor<V> {
+	/// The visitor which receives the items.
+	visitor: V,
+
+	/// Memoized variables of the profile, shared across all items.
+	variables: VariableCache,
+
+	/// Cached outputs of templates, shared across all items.
+	templates: TemplateCache,
+}
 
 impl<V> ResolvingVisitor<V>
 where
 	V: TemplateVisitor,
 {
+	/// Creates a new instance which passes all items to `visitor`.
+	pub fn new(visitor: V) -> Self {
+		Self {
+			visitor,
+			variables: VariableCache::new(),
+			templates: TemplateCache::new(),
+		}
+	}
+
+	/// Uses `templates` to cache the outputs of templates, instead of an
+	/// empty in memory cache.
+	pub fn with_templates(mut self, templates: TemplateCache) -> Self {
+		self.templates = templates;
+		self
+	}
+
+	/// Gets the cache of all profile variables used so far.
+	pub const fn variables(&self) -> &VariableCache {
+		&self.variables
+	}
+
+	/// Gets the cache of all template outputs.
+	pub const fn templates(&self) -> &TemplateCache {
+		&self.templates
+	}
+
+	/// Gets a mutable reference to the base [`Visitor`].
+	pub(crate) const fn inner_mut(&mut self) -> &mut V {
+		&mut self.visitor
+	}
+
 	/// Gets the base [`Visitor`].
 	#[allow(clippy::missing_const_for_fn)]
 	pub fn into_inner(self) -> V {
-		self.0
+		self.visitor
 	}
 }
 
@@ -730,19 +1524,38 @@ impl<V: TemplateVisitor> Visitor for ResolvingVisitor<V> {
 		file: &File<'a>,
 	) -> Result {
 		if file.dotfile().is_template() {
-			let resolve_fn = |content: &str| {
-				let source = Source::file(&file.source_path, content);
-				let template = Template::parse(source)
-					.with_context(|| format!("File: {}", file.source_path.display()))?;
-
-				template
-					.resolve(Some(profile.variables()), file.dotfile().variables.as_ref())
-					.with_context(|| format!("File: {}", file.source_path.display()))
+			let profile_vars = self.variables.with(profile.variables());
+
+			let templates = &self.templates;
+			let resolve_fn = |content: &str| match file.dotfile().engine() {
+				EngineKind::Builtin => templates.resolve(
+					Source::file(&file.source_path, content),
+					profile.template_limits(),
+					file.dotfile().template_mode(),
+					Some(&profile_vars),
+					file.dotfile().variables.as_ref(),
+					profile.variable_resolution_order(),
+					profile.env(),
+				),
+				// Other engines are neither cached nor memoize variables
+				kind => kind.engine()?.render(
+					content,
+					&RenderContext {
+						path: &file.source_path,
+						limits: profile.template_limits(),
+						mode: file.dotfile().template_mode(),
+						profile_vars: profile.variables(),
+						dotfile_vars: file.dotfile().variables.as_ref(),
+						order: profile.variable_resolution_order(),
+						env: profile.env(),
+					},
+				),
 			};
 
-			self.0.accept_template(source, profile, file, resolve_fn)
+			self.visitor
+				.accept_template(source, profile, file, resolve_fn)
 		} else {
-			self.0.accept_file(source, profile, file)
+			self.visitor.accept_file(source, profile, file)
 		}
 	}
 
@@ -752,7 +1565,7 @@ impl<V: TemplateVisitor> Visitor for ResolvingVisitor<V> {
 		profile: &LayeredProfile,
 		directory: &Directory<'a>,
 	) -> Result {
-		self.0.accept_directory(source, profile, directory)
+		self.visitor.accept_directory(source, profile, directory)
 	}
 
 	fn accept_link(
@@ -761,7 +1574,7 @@ impl<V: TemplateVisitor> Visitor for ResolvingVisitor<V> {
 		profile: &LayeredProfile,
 		symlink: &Symlink,
 	) -> Result {
-		self.0.accept_link(source, profile, symlink)
+		self.visitor.accept_link(source, profile, symlink)
 	}
 
 	fn accept_rejected<'a>(
@@ -770,7 +1583,7 @@ impl<V: TemplateVisitor> Visitor for ResolvingVisitor<V> {
 		profile: &LayeredProfile,
 		rejected: &Rejected<'a>,
 	) -> Result {
-		self.0.accept_rejected(source, profile, rejected)
+		self.visitor.accept_rejected(source, profile, rejected)
 	}
 
 	fn accept_errored<'a>(
@@ -779,6 +1592,687 @@ impl<V: TemplateVisitor> Visitor for ResolvingVisitor<V> {
 		profile: &LayeredProfile,
 		errored: &Errored<'a>,
 	) -> Result {
-		self.0.accept_errored(source, profile, errored)
+		self.visitor.accept_errored(source, profile, errored)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::{BTreeMap, HashMap};
+
+	use super::*;
+	use crate::profile::variables::Variables;
+	use crate::profile::Profile;
+
+	/// Creates a dotfile for `path` with all other fields left empty.
+	pub(super) fn dotfile(path: &str) -> Dotfile {
+		Dotfile {
+			path: PathBuf::from(path),
+			rename: None,
+			rename_map: BTreeMap::new(),
+			overwrite_target: None,
+			target_name: None,
+			priority: None,
+			variables: None,
+			variables_file: None,
+			transformers: Vec::new(),
+			merge: None,
+			template: None,
+			template_mode: None,
+			engine: None,
+			binary: None,
+			strategy: None,
+			windows_acl: None,
+			encrypted: None,
+			preserve_attributes: None,
+			follow_source_symlinks: None,
+			tags: Vec::new(),
+			enabled: None,
+			extract: None,
+		}
+	}
+
+	#[test]
+	fn target_path_template() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			Profile {
+				target: Some(PathBuf::from("/home/{{USER}}")),
+				variables: Some(Variables::from_items([("USER", "demo"), ("APP", "nvim")])),
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+		let walker = Walker::new(&mut profile);
+
+		let mut file = dotfile("init.vim");
+		assert_eq!(
+			walker.resolve_target_path(&file, false)?,
+			PathBuf::from("/home/demo/init.vim")
+		);
+
+		file.overwrite_target = Some(PathBuf::from("/home/{{USER}}/.config/{{APP}}"));
+		file.rename = Some(PathBuf::from("{{&NAME}}.vim"));
+		file.variables = Some(Variables::from_items([("NAME", "config")]));
+		assert_eq!(
+			walker.resolve_target_path(&file, false)?,
+			PathBuf::from("/home/demo/.config/nvim/config.vim")
+		);
+
+		file.rename = Some(PathBuf::from("{{UNKNOWN}}"));
+		assert!(walker.resolve_target_path(&file, false).is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn target_path_rename_map() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			Profile {
+				target: Some(PathBuf::from("/home/demo")),
+				variables: Some(Variables::from_items([("SHELL", "zsh")])),
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+		let walker = Walker::new(&mut profile);
+
+		let os = std::env::consts::OS;
+		let family = std::env::consts::FAMILY;
+
+		let mut file = dotfile("vimrc");
+		file.rename = Some(PathBuf::from("fallback"));
+		file.rename_map = BTreeMap::from([
+			(
+				String::from("{{SHELL}} == \"zsh\""),
+				PathBuf::from("condition"),
+			),
+			(family.to_uppercase(), PathBuf::from("family")),
+			(os.to_string(), PathBuf::from("os")),
+		]);
+		assert_eq!(
+			walker.resolve_target_path(&file, false)?,
+			PathBuf::from("/home/demo/os")
+		);
+
+		file.rename_map.remove(os);
+		assert_eq!(
+			walker.resolve_target_path(&file, false)?,
+			PathBuf::from("/home/demo/family")
+		);
+
+		file.rename_map.remove(&family.to_uppercase());
+		assert_eq!(
+			walker.resolve_target_path(&file, false)?,
+			PathBuf::from("/home/demo/condition")
+		);
+
+		file.rename_map = BTreeMap::from([
+			(
+				String::from("{{SHELL}} == \"bash\""),
+				PathBuf::from("condition"),
+			),
+			(String::from("unknown"), PathBuf::from("unknown")),
+		]);
+		assert_eq!(
+			walker.resolve_target_path(&file, false)?,
+			PathBuf::from("/home/demo/fallback")
+		);
+
+		file.rename_map = BTreeMap::from([(String::from("{{SHELL}} =="), PathBuf::from("a"))]);
+		assert!(walker.resolve_target_path(&file, false).is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn tag_filter() {
+		crate::tests::setup_test_env();
+
+		let mut shell = dotfile(".bashrc");
+		shell.tags = vec![String::from("shell")];
+		let mut editor = dotfile("init.vim");
+		editor.tags = vec![String::from("editor"), String::from("gui")];
+		let untagged = dotfile("other");
+
+		let filter = TagFilter::default();
+		assert!(filter.is_empty());
+		assert!([&shell, &editor, &untagged]
+			.iter()
+			.all(|dotfile| filter.accepts(dotfile)));
+
+		let filter = TagFilter {
+			only: vec![String::from("shell"), String::from("editor")],
+			skip: Vec::new(),
+		};
+		assert!(filter.accepts(&shell));
+		assert!(filter.accepts(&editor));
+		assert!(!filter.accepts(&untagged));
+
+		let filter = TagFilter {
+			only: Vec::new(),
+			skip: vec![String::from("gui")],
+		};
+		assert!(filter.accepts(&shell));
+		assert!(!filter.accepts(&editor));
+		assert!(filter.accepts(&untagged));
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn target_path_destdir() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			Profile {
+				target: Some(PathBuf::from("/home/demo")),
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+		let walker = Walker::new(&mut profile).with_destdir(Some(PathBuf::from("/tmp/stage")));
+
+		assert_eq!(
+			walker.resolve_target_root()?,
+			Some(PathBuf::from("/tmp/stage/home/demo"))
+		);
+
+		let mut file = dotfile("init.vim");
+		assert_eq!(
+			walker.resolve_target_path(&file, false)?,
+			PathBuf::from("/tmp/stage/home/demo/init.vim")
+		);
+
+		file.overwrite_target = Some(PathBuf::from("/etc"));
+		assert_eq!(
+			walker.resolve_target_path(&file, false)?,
+			PathBuf::from("/tmp/stage/etc/init.vim")
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn percent_vars() {
+		crate::tests::setup_test_env();
+
+		let lookup = |name: &str| match name {
+			"USERPROFILE" => Some(String::from(r"C:\Users\demo")),
+			"ProgramFiles(x86)" => Some(String::from(r"C:\Program Files (x86)")),
+			_ => None,
+		};
+
+		assert_eq!(
+			expand_percent_vars(r"%USERPROFILE%\.config", lookup).as_deref(),
+			Ok(r"C:\Users\demo\.config")
+		);
+		assert_eq!(
+			expand_percent_vars(r"%ProgramFiles(x86)%\app\%USERPROFILE%", lookup).as_deref(),
+			Ok(r"C:\Program Files (x86)\app\C:\Users\demo")
+		);
+
+		// Escaped and unmatched `%` are kept
+		assert_eq!(
+			expand_percent_vars(r"C:\100%%\50%\x", lookup).as_deref(),
+			Ok(r"C:\100%\50%\x")
+		);
+		assert!(matches!(
+			expand_percent_vars("/home/demo", lookup),
+			Ok(Cow::Borrowed("/home/demo"))
+		));
+
+		assert_eq!(
+			expand_percent_vars(r"%MISSING%\x", lookup),
+			Err(String::from("environment variable `MISSING` is not set"))
+		);
+	}
+
+	#[test]
+	fn resolve_home() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			Profile {
+				target: Some(PathBuf::from("~/.config")),
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+		profile
+			.env
+			.set("PUNKTF_CURRENT_PROFILE", "test")
+			.set("PUNKTF_TEST_RESOLVE", "explicit");
+		let walker = Walker::new(&mut profile);
+
+		let home = PathBuf::from(shellexpand::tilde("~").as_ref());
+		assert_eq!(walker.resolve_target_root()?, Some(home.join(".config")));
+
+		// Variables of the profile environment are visible without modifying
+		// the environment of the process
+		assert_eq!(
+			walker.resolve_path(Path::new(
+				"~/$PUNKTF_CURRENT_PROFILE/${PUNKTF_TEST_RESOLVE}"
+			))?,
+			home.join("test/explicit")
+		);
+
+		let err = walker
+			.resolve_path(Path::new("$PUNKTF_MISSING_VARIABLE/x"))
+			.expect_err("Missing variables are errors");
+		assert!(err.to_string().contains("PUNKTF_MISSING_VARIABLE"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn extended_length_paths() {
+		crate::tests::setup_test_env();
+
+		let deep = "nvim\\pack\\plugins\\start".repeat(12);
+
+		assert_eq!(to_extended_length(r"C:\Users\demo\.config"), None);
+		assert_eq!(
+			to_extended_length(&format!(r"C:\Users\demo\{deep}")),
+			Some(format!(r"\\?\C:\Users\demo\{deep}"))
+		);
+		assert_eq!(
+			to_extended_length(&format!(r"\\server\share\demo\{deep}")),
+			Some(format!(r"\\?\UNC\server\share\demo\{deep}"))
+		);
+		assert_eq!(
+			to_extended_length(&format!("C:/Users/demo/{deep}")),
+			Some(format!(r"\\?\C:\Users\demo\{deep}"))
+		);
+
+		// Already extended and relative paths are kept
+		assert_eq!(to_extended_length(&format!(r"\\?\C:\{deep}")), None);
+		assert_eq!(to_extended_length(&format!(r"demo\{deep}")), None);
+	}
+
+	#[test]
+	#[cfg(windows)]
+	fn target_path_unc() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			Profile {
+				target: Some(PathBuf::from(r"\\server\share\demo")),
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+		let walker = Walker::new(&mut profile);
+
+		let target = walker.resolve_target_path(&dotfile("init.vim"), false)?;
+		assert_eq!(target, PathBuf::from(r"\\server\share\demo\init.vim"));
+
+		let deep = target.join("nvim\\pack\\plugins\\start".repeat(12));
+		assert!(extended_length_path(deep)
+			.to_string_lossy()
+			.starts_with(r"\\?\UNC\server\share\demo\init.vim\nvim"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn walk_order() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let root = crate::tests::test_dir();
+		std::fs::create_dir_all(root.join("source/dotfiles/dir"))?;
+
+		for name in ["c", "a", "b"] {
+			std::fs::write(root.join("source/dotfiles").join(name), name)?;
+			std::fs::write(root.join("source/dotfiles/dir").join(name), name)?;
+		}
+
+		let source = PunktfSource::from_root(root.join("source"))?;
+
+		let mut important = dotfile("c");
+		important.priority = Some(Priority::new(1));
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			Profile {
+				target: Some(root.join("target")),
+				dotfiles: vec![dotfile("dir"), dotfile("b"), important, dotfile("a")],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let mut recorder = Recorder::default();
+		Walker::new(&mut profile)
+			.walk(&source, &mut recorder)
+			.map_err(|err| color_eyre::eyre::eyre!("{err}"))?;
+
+		// Highest priority first, then sorted by path
+		assert_eq!(
+			recorder.files,
+			["c", "a", "b", "dir/a", "dir/b", "dir/c"].map(PathBuf::from)
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn target_path_missing() {
+		crate::tests::setup_test_env();
+
+		let mut profile = LayeredProfile::build().finish();
+		let walker = Walker::new(&mut profile);
+
+		let mut file = dotfile("init.vim");
+		let err = walker
+			.resolve_target_path(&file, false)
+			.expect_err("Target path to be missing");
+		assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+		file.overwrite_target = Some(PathBuf::from("/home/demo"));
+		assert!(walker.resolve_target_path(&file, false).is_ok());
+	}
+
+	#[test]
+	fn target_path_named() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("child"),
+			Profile {
+				target: Some(PathBuf::from("/home/demo")),
+				targets: HashMap::from([(String::from("config"), PathBuf::from("/etc/xdg"))]),
+				..Default::default()
+			},
+		);
+		builder.add(
+			String::from("base"),
+			Profile {
+				targets: HashMap::from([
+					(String::from("config"), PathBuf::from("/home/demo/.config")),
+					(String::from("app"), PathBuf::from("/opt/{{APP}}")),
+				]),
+				variables: Some(Variables::from_items([("APP", "nvim")])),
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+		let walker = Walker::new(&mut profile);
+
+		let mut file = dotfile("init.vim");
+		file.target_name = Some(String::from("config"));
+		assert_eq!(
+			walker.resolve_target_path(&file, false)?,
+			PathBuf::from("/etc/xdg/init.vim")
+		);
+
+		// named directories are merged into the target like the default one
+		file.target_name = Some(String::from("app"));
+		assert_eq!(
+			walker.resolve_target_path(&file, true)?,
+			PathBuf::from("/opt/nvim")
+		);
+
+		file.target_name = Some(String::from("unknown"));
+		let err = walker
+			.resolve_target_path(&file, false)
+			.expect_err("Target to be unknown");
+		assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+		Ok(())
+	}
+
+	/// Records the relative source paths of all visited files and errored
+	/// items.
+	#[derive(Debug, Default)]
+	struct Recorder {
+		/// Relative source paths of all files.
+		files: Vec<PathBuf>,
+
+		/// Relative source paths of all errored items.
+		errored: Vec<PathBuf>,
+
+		/// Relative source paths and reasons of all rejected items.
+		rejected: Vec<(PathBuf, String)>,
+	}
+
+	impl Visitor for Recorder {
+		fn accept_file<'a>(
+			&mut self,
+			_: &PunktfSource,
+			_: &LayeredProfile,
+			file: &File<'a>,
+		) -> Result {
+			self.files.push(file.relative_source_path.clone());
+			Ok(())
+		}
+
+		fn accept_directory<'a>(
+			&mut self,
+			_: &PunktfSource,
+			_: &LayeredProfile,
+			_: &Directory<'a>,
+		) -> Result {
+			Ok(())
+		}
+
+		fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, _: &Symlink) -> Result {
+			Ok(())
+		}
+
+		fn accept_rejected<'a>(
+			&mut self,
+			_: &PunktfSource,
+			_: &LayeredProfile,
+			rejected: &Rejected<'a>,
+		) -> Result {
+			self.rejected.push((
+				rejected.relative_source_path.clone(),
+				rejected.reason.to_string(),
+			));
+			Ok(())
+		}
+
+		fn accept_errored<'a>(
+			&mut self,
+			_: &PunktfSource,
+			_: &LayeredProfile,
+			errored: &Errored<'a>,
+		) -> Result {
+			self.errored.push(errored.relative_source_path.clone());
+			Ok(())
+		}
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn follow_source_symlinks() -> color_eyre::Result<()> {
+		use std::os::unix::fs::symlink;
+
+		crate::tests::setup_test_env();
+
+		let root = crate::tests::test_dir();
+		std::fs::create_dir_all(root.join("dotfiles/conf"))?;
+		std::fs::create_dir_all(root.join("dotfiles/shared"))?;
+		std::fs::write(root.join("dotfiles/conf/a"), "a")?;
+		std::fs::write(root.join("dotfiles/shared/b"), "b")?;
+		symlink("a", root.join("dotfiles/conf/link"))?;
+		symlink("../shared", root.join("dotfiles/conf/sub"))?;
+		symlink(".", root.join("dotfiles/conf/loop"))?;
+
+		let source = PunktfSource::from_root(root.to_path_buf())?;
+
+		let walk = |follow: bool| -> color_eyre::Result<Recorder> {
+			let mut builder = LayeredProfile::build();
+			builder.add(
+				String::from("test"),
+				Profile {
+					target: Some(root.join("target")),
+					follow_source_symlinks: Some(follow),
+					dotfiles: vec![dotfile("conf")],
+					..Default::default()
+				},
+			);
+			let mut profile = builder.finish();
+
+			let mut recorder = Recorder::default();
+			Walker::new(&mut profile)
+				.walk(&source, &mut recorder)
+				.map_err(|err| color_eyre::eyre::eyre!("{err}"))?;
+
+			recorder.files.sort();
+			recorder.errored.sort();
+			Ok(recorder)
+		};
+
+		let recorder = walk(false)?;
+		assert_eq!(recorder.files, vec![PathBuf::from("conf/a")]);
+		assert_eq!(recorder.errored.len(), 3);
+
+		let recorder = walk(true)?;
+		assert_eq!(
+			recorder.files,
+			vec![
+				PathBuf::from("conf/a"),
+				PathBuf::from("conf/link"),
+				PathBuf::from("conf/sub/b")
+			]
+		);
+		assert_eq!(recorder.errored, vec![PathBuf::from("conf/loop")]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn allow_external() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let root = crate::tests::test_dir();
+		std::fs::create_dir_all(root.join("source/dotfiles"))?;
+		std::fs::create_dir_all(root.join("submodule"))?;
+		std::fs::write(root.join("source/dotfiles/a"), "a")?;
+		std::fs::write(root.join("submodule/b"), "b")?;
+
+		let source = PunktfSource::from_root(root.join("source"))?;
+		let external = root.join("submodule/b").canonicalize()?;
+
+		let walk = |allow: bool| -> color_eyre::Result<Recorder> {
+			let mut builder = LayeredProfile::build();
+			builder.add(
+				String::from("test"),
+				Profile {
+					target: Some(root.join("target")),
+					allow_external: Some(allow),
+					dotfiles: vec![dotfile("a"), dotfile("../../submodule/b")],
+					..Default::default()
+				},
+			);
+			let mut profile = builder.finish();
+
+			let mut recorder = Recorder::default();
+			Walker::new(&mut profile)
+				.walk(&source, &mut recorder)
+				.map_err(|err| color_eyre::eyre::eyre!("{err}"))?;
+
+			Ok(recorder)
+		};
+
+		let recorder = walk(false)?;
+		assert_eq!(recorder.files, vec![PathBuf::from("a")]);
+		assert_eq!(recorder.errored.len(), 1);
+
+		let recorder = walk(true)?;
+		// Sorted by the paths of the dotfiles (`../../submodule/b` first)
+		assert_eq!(recorder.files, vec![external, PathBuf::from("a")]);
+		assert!(recorder.errored.is_empty());
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			Profile {
+				target: Some(PathBuf::from("/home/demo")),
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+		let walker = Walker::new(&mut profile);
+		assert_eq!(
+			walker.resolve_target_path(&dotfile("../../submodule/b"), false)?,
+			PathBuf::from("/home/demo/b")
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn enabled() -> color_eyre::Result<()> {
+		crate::tests::setup_test_env();
+
+		let root = crate::tests::test_dir();
+		std::fs::create_dir_all(root.join("dotfiles"))?;
+		for name in ["a", "b", "c", "d"] {
+			std::fs::write(root.join("dotfiles").join(name), name)?;
+		}
+
+		let source = PunktfSource::from_root(root.to_path_buf())?;
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("test"),
+			Profile {
+				target: Some(root.join("target")),
+				variables: Some(Variables::from_items([("OS", "linux")])),
+				dotfiles: vec![
+					Dotfile {
+						enabled: Some(Enabled::Bool(true)),
+						..dotfile("a")
+					},
+					Dotfile {
+						enabled: Some(Enabled::Bool(false)),
+						..dotfile("b")
+					},
+					Dotfile {
+						enabled: Some(Enabled::Expr(String::from("{{OS}} == \"linux\""))),
+						..dotfile("c")
+					},
+					Dotfile {
+						enabled: Some(Enabled::Expr(String::from("{{OS}} != \"linux\""))),
+						..dotfile("d")
+					},
+				],
+				..Default::default()
+			},
+		);
+		let mut profile = builder.finish();
+
+		let mut recorder = Recorder::default();
+		Walker::new(&mut profile)
+			.walk(&source, &mut recorder)
+			.map_err(|err| color_eyre::eyre::eyre!("{err}"))?;
+
+		assert_eq!(recorder.files, vec![PathBuf::from("a"), PathBuf::from("c")]);
+		assert_eq!(
+			recorder.rejected,
+			vec![
+				(PathBuf::from("b"), String::from("disabled")),
+				(PathBuf::from("d"), String::from("disabled"))
+			]
+		);
+
+		Ok(())
 	}
 }
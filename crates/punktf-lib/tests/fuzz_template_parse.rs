@@ -1,5 +1,5 @@
-/// Tests for cases discovered by fuzzing this crate.
-/// The only checks done are, that no panic occurs.
+//! Tests for cases discovered by fuzzing this crate.
+//! The only checks done are, that no panic occurs.
 
 const TEMPLATES: &[&[u8]] = &[
 	// fuzz/artifacts/fuzz_template_parse/minimized-from-99658ac1fce12b1bd80cfc1d5219cf49284b473a